@@ -0,0 +1,661 @@
+// Local password accounts (optional) — for servers without SSO or a directory.
+//
+// A user registers a password (argon2id hash in `users.password_hash`) and logs in to get a
+// session token. Repeated failures lock the account for a while. The host can require a valid
+// session token on every Connect (`credential`), replacing the honor-system identity. Remote
+// register/login is refused unless the host enables local accounts.
+//
+// Remote clients register/log in over the same Noise channel as chat: a `Register`/`Login`
+// frame before Connect carries the email + password (`credential`), and the host answers with
+// an `AuthResult` (token in `message`, canonical id in `user_id`) or an `ErrorNotice`. Only a
+// SHA-256 of each token is stored, so a DB copy can't be replayed as sessions.
+
+use crate::compression;
+use crate::db_queries::{
+    get_setting_internal, get_user_by_id_internal, normalize_email, record_audit_internal,
+    set_setting_internal, upsert_user_internal, User,
+};
+use crate::error::{AppError, AppResult};
+use crate::secure;
+use crate::sockets::{frame, open_secure_client, send_secure, Message, MessageType, PeerWriter};
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, SaltString};
+use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+const SETTINGS_KEY: &str = "accounts_config";
+
+const MIN_PASSWORD_CHARS: usize = 8;
+const MAX_PASSWORD_CHARS: usize = 256;
+
+/// Failed logins in a row before the account locks, and for how long.
+const MAX_FAILED_LOGINS: i64 = 5;
+const LOCKOUT_MINUTES: i64 = 15;
+
+const SESSION_DAYS: i64 = 30;
+
+/// How long a client waits for the host's AuthResult.
+const AUTH_REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AccountsConfig {
+    // Host side: let remote clients register and log in with a password.
+    #[serde(default)]
+    pub enabled: bool,
+    // Host side: reject Connects without a valid session token for the registering email.
+    #[serde(default)]
+    pub require_on_connect: bool,
+}
+
+/// How a session was opened (`auth_sessions.origin`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SessionOrigin {
+    Password,
+    Sso,
+}
+
+impl SessionOrigin {
+    fn as_str(self) -> &'static str {
+        match self {
+            SessionOrigin::Password => "password",
+            SessionOrigin::Sso => "sso",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct LoginResult {
+    pub user: User,
+    pub token: String,
+}
+
+pub(crate) fn token_hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn validate_password(password: &str) -> AppResult<()> {
+    let len = password.chars().count();
+    if !(MIN_PASSWORD_CHARS..=MAX_PASSWORD_CHARS).contains(&len) {
+        return Err(AppError::Validation(format!(
+            "Password must be between {} and {} characters",
+            MIN_PASSWORD_CHARS, MAX_PASSWORD_CHARS
+        )));
+    }
+    Ok(())
+}
+
+fn hash_password(password: &str) -> AppResult<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))
+}
+
+fn password_matches(password: &str, stored: &str) -> bool {
+    PasswordHash::new(stored)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Create a user with a password. Only a new email can register: an existing user (with a
+/// password or not — honor-system, directory or SSO) would be taken over, so theirs is set by
+/// the host with `set_user_password`.
+pub async fn register_internal(
+    pool: &SqlitePool,
+    name: String,
+    email: String,
+    password: &str,
+) -> AppResult<User> {
+    validate_password(password)?;
+    let email = normalize_email(&email);
+    let existing: Option<i64> = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+        .bind(&email)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    if existing.is_some() {
+        return Err(AppError::Conflict(
+            "An account with that email already exists; ask the host to set its password"
+                .to_string(),
+        ));
+    }
+    let hash = hash_password(password)?;
+    let user = upsert_user_internal(pool, name, email, None)
+        .await
+        .map_err(AppError::Validation)?;
+    let user_id = user
+        .id
+        .ok_or_else(|| AppError::Internal("User was saved without an id".to_string()))?;
+    save_password(pool, user_id, &hash).await?;
+    Ok(user)
+}
+
+async fn save_password(pool: &SqlitePool, user_id: i64, hash: &str) -> AppResult<()> {
+    sqlx::query(
+        "UPDATE users SET password_hash = $1, failed_logins = 0, locked_until = NULL WHERE id = $2",
+    )
+    .bind(hash)
+    .bind(user_id)
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::Db(format!("Failed to save password: {}", e)))?;
+    Ok(())
+}
+
+/// Host: set (or reset) an existing user's password. Their open sessions are ended, and the
+/// lockout cleared.
+pub async fn set_user_password_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    password: &str,
+    actor: Option<i64>,
+) -> AppResult<()> {
+    validate_password(password)?;
+    let Some(user) = get_user_by_id_internal(pool, user_id)
+        .await
+        .map_err(AppError::Db)?
+    else {
+        return Err(AppError::Validation("User not found".to_string()));
+    };
+    let hash = hash_password(password)?;
+    save_password(pool, user_id, &hash).await?;
+    sqlx::query("DELETE FROM auth_sessions WHERE user_id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    record_audit_internal(pool, actor, "user.password", &user.email, None)
+        .await
+        .map_err(AppError::Db)
+}
+
+/// Check `password` for `email` and open a session. Every wrong password counts toward the
+/// lockout; a locked account is refused even with the right password until the lock expires.
+pub async fn login_internal(
+    pool: &SqlitePool,
+    email: &str,
+    password: &str,
+) -> AppResult<LoginResult> {
+    let email = normalize_email(email);
+    let wrong = || AppError::Auth("Wrong email or password".to_string());
+    let row = sqlx::query(
+        "SELECT id, password_hash, COALESCE(locked_until > datetime('now'), 0) AS locked
+         FROM users WHERE email = $1",
+    )
+    .bind(&email)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| AppError::Db(e.to_string()))?;
+    let Some(row) = row else {
+        return Err(wrong());
+    };
+    let user_id = row.get::<i64, _>("id");
+    let Some(stored) = row.get::<Option<String>, _>("password_hash") else {
+        return Err(wrong());
+    };
+    if row.get::<bool, _>("locked") {
+        return Err(AppError::Auth(format!(
+            "Too many failed attempts; try again in {} minutes",
+            LOCKOUT_MINUTES
+        )));
+    }
+
+    if !password_matches(password, &stored) {
+        // Count the failure; the one that reaches the limit locks the account and resets the count.
+        sqlx::query(
+            "UPDATE users SET
+               locked_until = CASE WHEN failed_logins + 1 >= $1
+                                   THEN datetime('now', $2) ELSE locked_until END,
+               failed_logins = CASE WHEN failed_logins + 1 >= $1 THEN 0 ELSE failed_logins + 1 END
+             WHERE id = $3",
+        )
+        .bind(MAX_FAILED_LOGINS)
+        .bind(format!("+{} minutes", LOCKOUT_MINUTES))
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+        return Err(wrong());
+    }
+
+    sqlx::query("UPDATE users SET failed_logins = 0, locked_until = NULL WHERE id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    let token = open_session_internal(pool, user_id, SessionOrigin::Password).await?;
+
+    let user = get_user_by_id_internal(pool, user_id)
+        .await
+        .map_err(AppError::Db)?
+        .ok_or_else(|| AppError::Internal("User vanished during login".to_string()))?;
+    Ok(LoginResult { user, token })
+}
+
+/// Open a session for an already-authenticated user and return its token.
+pub(crate) async fn open_session_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    origin: SessionOrigin,
+) -> AppResult<String> {
+    let token = crate::db::random_hex_key();
+    sqlx::query(
+        "INSERT INTO auth_sessions (token_hash, user_id, expires_at, origin)
+         VALUES ($1, $2, datetime('now', $3), $4)",
+    )
+    .bind(token_hash(&token))
+    .bind(user_id)
+    .bind(format!("+{} days", SESSION_DAYS))
+    .bind(origin.as_str())
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::Db(format!("Failed to open session: {}", e)))?;
+    Ok(token)
+}
+
+/// The user a live (unexpired) session token belongs to.
+pub async fn session_user_internal(pool: &SqlitePool, token: &str) -> Result<Option<i64>, String> {
+    sqlx::query_scalar(
+        "SELECT user_id FROM auth_sessions WHERE token_hash = $1 AND expires_at > datetime('now')",
+    )
+    .bind(token_hash(token))
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<AccountsConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt accounts config: {}", e))),
+        None => Ok(AccountsConfig::default()),
+    }
+}
+
+/// Host-side Connect gate: when required, `credential` must be a live session token belonging
+/// to the user with `email`. Err carries the reason for the client.
+pub async fn check_connect(
+    pool: &SqlitePool,
+    email: Option<&str>,
+    credential: Option<&str>,
+) -> Result<(), String> {
+    let config = load_config(pool).await.map_err(|e| e.to_string())?;
+    if !(config.enabled && config.require_on_connect) {
+        return Ok(());
+    }
+    let (Some(email), Some(token)) = (email, credential) else {
+        return Err("This server requires you to log in".to_string());
+    };
+    match session_owned_by(pool, token, email, None).await? {
+        true => Ok(()),
+        false => Err("Your session has expired; please log in again".to_string()),
+    }
+}
+
+/// Whether `token` is a live session of the user with `email`, opened the `origin` way when
+/// one is given.
+pub(crate) async fn session_owned_by(
+    pool: &SqlitePool,
+    token: &str,
+    email: &str,
+    origin: Option<SessionOrigin>,
+) -> Result<bool, String> {
+    let owner: Option<i64> = sqlx::query_scalar(
+        "SELECT s.user_id FROM auth_sessions s JOIN users u ON u.id = s.user_id
+         WHERE s.token_hash = $1 AND s.expires_at > datetime('now') AND u.email = $2
+           AND ($3 IS NULL OR s.origin = $3)",
+    )
+    .bind(token_hash(token))
+    .bind(normalize_email(email))
+    .bind(origin.map(SessionOrigin::as_str))
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(owner.is_some())
+}
+
+/// Host side: answer a pre-Connect `Register` / `Login` / `SsoLogin` frame with an
+/// `AuthResult` (token + canonical id) or an `ErrorNotice`. Register and Login are refused
+/// unless local accounts are enabled.
+pub async fn handle_auth_frame(pool: &SqlitePool, request: &Message) -> Message {
+    let email = request.email.clone().unwrap_or_default();
+    let password = request.credential.clone().unwrap_or_default();
+    let result = match request.message_type {
+        MessageType::SsoLogin => crate::oidc::exchange_id_token(pool, &email, &password).await,
+        _ => password_auth(pool, request, &email, &password).await,
+    };
+    let (message_type, user_id, message) = match result {
+        Ok(login) => (
+            MessageType::AuthResult,
+            login.user.id.unwrap_or_default() as u64,
+            login.token,
+        ),
+        Err(e) => (MessageType::ErrorNotice, 0, e.to_string()),
+    };
+    Message {
+        username: request.username.clone(),
+        created_at: 0,
+        ..frame(message_type, user_id, message)
+    }
+}
+
+/// Host side: a remote Register or Login, when local accounts are enabled.
+async fn password_auth(
+    pool: &SqlitePool,
+    request: &Message,
+    email: &str,
+    password: &str,
+) -> AppResult<LoginResult> {
+    if !load_config(pool).await?.enabled {
+        return Err(AppError::Auth(
+            "Local accounts are not enabled on this server".to_string(),
+        ));
+    }
+    if request.message_type == MessageType::Register {
+        register_internal(pool, request.username.clone(), email.to_string(), password).await?;
+    }
+    login_internal(pool, email, password).await
+}
+
+/// Client side: send one Register/Login/SsoLogin frame to `host` and return the session token.
+pub(crate) async fn remote_auth(
+    host: &str,
+    password: &str,
+    message_type: MessageType,
+    username: String,
+    email: String,
+    account_password: String,
+) -> AppResult<String> {
+    let (mut reader, writer, transport) = open_secure_client(host, password).await?;
+    let writer = Arc::new(PeerWriter::new(writer));
+    let transport = Arc::new(tokio::sync::Mutex::new(transport));
+    let request = Message {
+        username,
+        created_at: 0,
+        email: Some(email),
+        credential: Some(account_password),
+        ..frame(message_type, 0, String::new())
+    };
+    send_secure(&writer, &transport, &request)
+        .await
+        .map_err(AppError::Network)?;
+
+    let reply = tokio::time::timeout(AUTH_REPLY_TIMEOUT, async {
+        loop {
+            // Skip keep-alives until the real reply arrives.
+            if let Some(ciphertext) = reader.read_frame().await? {
+                return Ok::<_, std::io::Error>(ciphertext);
+            }
+        }
+    })
+    .await
+    .map_err(|_| AppError::Network("The server did not answer".to_string()))?
+    .map_err(|e| AppError::Network(e.to_string()))?;
+    let plaintext = {
+        let mut ts = transport.lock().await;
+        secure::decrypt(&mut ts, &reply).map_err(AppError::Network)?
+    };
+    let plaintext = compression::decompress(plaintext).map_err(AppError::Network)?;
+    let reply: Message = serde_json::from_slice(&plaintext)
+        .map_err(|e| AppError::Network(format!("Malformed reply: {}", e)))?;
+    match reply.message_type {
+        MessageType::AuthResult => Ok(reply.message),
+        _ => Err(AppError::Auth(reply.message)),
+    }
+}
+
+/// Register a local account on this machine's DB (the host's own user).
+#[tauri::command]
+pub async fn register(
+    db: State<'_, SqlitePool>,
+    name: String,
+    email: String,
+    password: String,
+) -> AppResult<User> {
+    register_internal(&db, name, email, &password).await
+}
+
+/// Host: set a password for an existing user, who can then log in with it.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_user_password(
+    state: State<'_, Arc<crate::sockets::AppState>>,
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+    password: String,
+) -> AppResult<()> {
+    let actor = state.user_id.read().await.map(|id| id as i64);
+    set_user_password_internal(&db, user_id, &password, actor).await
+}
+
+/// Log in against this machine's DB and get a session token.
+#[tauri::command]
+pub async fn login(
+    db: State<'_, SqlitePool>,
+    email: String,
+    password: String,
+) -> AppResult<LoginResult> {
+    login_internal(&db, &email, &password).await
+}
+
+/// Register an account on a remote host. Returns the session token to pass as `credential`.
+#[tauri::command]
+pub async fn client_register(
+    host: String,
+    password: String,
+    username: String,
+    email: String,
+    account_password: String,
+) -> AppResult<String> {
+    remote_auth(
+        &host,
+        &password,
+        MessageType::Register,
+        username,
+        email,
+        account_password,
+    )
+    .await
+}
+
+/// Log in to a remote host. Returns the session token to pass as `credential`.
+#[tauri::command]
+pub async fn client_login(
+    host: String,
+    password: String,
+    email: String,
+    account_password: String,
+) -> AppResult<String> {
+    remote_auth(
+        &host,
+        &password,
+        MessageType::Login,
+        String::new(),
+        email,
+        account_password,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn get_accounts_config(db: State<'_, SqlitePool>) -> AppResult<AccountsConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_accounts_config(
+    db: State<'_, SqlitePool>,
+    config: AccountsConfig,
+) -> AppResult<()> {
+    if config.require_on_connect && !config.enabled {
+        return Err(AppError::Validation(
+            "Enable local accounts before requiring them on connect".to_string(),
+        ));
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("open in-memory db");
+        crate::db::run_migrations(&pool)
+            .await
+            .expect("run migrations");
+        pool
+    }
+
+    #[tokio::test]
+    async fn register_then_login_issues_a_session() {
+        let pool = setup().await;
+        register_internal(&pool, "Ada".into(), "Ada@X.io".into(), "correct horse")
+            .await
+            .unwrap();
+        let login = login_internal(&pool, "ada@x.io", "correct horse")
+            .await
+            .unwrap();
+        assert_eq!(
+            session_user_internal(&pool, &login.token).await.unwrap(),
+            login.user.id
+        );
+        assert_eq!(
+            session_user_internal(&pool, "not-a-token").await.unwrap(),
+            None
+        );
+        // The same email can't be registered twice.
+        assert!(matches!(
+            register_internal(&pool, "Eve".into(), "ada@x.io".into(), "password1").await,
+            Err(AppError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn existing_users_get_a_password_only_from_the_host() {
+        let pool = setup().await;
+        let bob = upsert_user_internal(&pool, "Bob".into(), " Bob@X.io".into(), None)
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+        // Bob has no password yet, but his email is still his.
+        assert!(matches!(
+            register_internal(&pool, "Eve".into(), "bob@x.io ".into(), "password1").await,
+            Err(AppError::Conflict(_))
+        ));
+        assert!(login_internal(&pool, "bob@x.io", "password1")
+            .await
+            .is_err());
+
+        set_user_password_internal(&pool, bob, "correct horse", None)
+            .await
+            .unwrap();
+        let login = login_internal(&pool, "BOB@x.io", "correct horse")
+            .await
+            .unwrap();
+        assert_eq!(login.user.id, Some(bob));
+        // A reset ends the sessions opened with the old password.
+        set_user_password_internal(&pool, bob, "battery staple", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            session_user_internal(&pool, &login.token).await.unwrap(),
+            None
+        );
+        assert!(
+            set_user_password_internal(&pool, 999, "battery staple", None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn repeated_failures_lock_the_account() {
+        let pool = setup().await;
+        register_internal(&pool, "Ada".into(), "a@x".into(), "correct horse")
+            .await
+            .unwrap();
+        for _ in 0..MAX_FAILED_LOGINS {
+            assert!(login_internal(&pool, "a@x", "wrong pass").await.is_err());
+        }
+        // Locked: even the right password is refused now.
+        let locked = login_internal(&pool, "a@x", "correct horse").await;
+        assert!(matches!(locked, Err(AppError::Auth(m)) if m.contains("Too many")));
+    }
+
+    #[tokio::test]
+    async fn remote_register_needs_local_accounts_enabled() {
+        let pool = setup().await;
+        let request = Message {
+            username: "Eve".into(),
+            email: Some("eve@x.io".into()),
+            credential: Some("correct horse".into()),
+            ..frame(MessageType::Register, 0, String::new())
+        };
+        let refused = handle_auth_frame(&pool, &request).await;
+        assert_eq!(refused.message_type, MessageType::ErrorNotice);
+
+        let config = AccountsConfig {
+            enabled: true,
+            require_on_connect: false,
+        };
+        set_setting_internal(
+            &pool,
+            SETTINGS_KEY,
+            &serde_json::to_string(&config).unwrap(),
+        )
+        .await
+        .unwrap();
+        let accepted = handle_auth_frame(&pool, &request).await;
+        assert_eq!(accepted.message_type, MessageType::AuthResult);
+        // A password session is a session, but not an SSO one.
+        assert!(session_owned_by(&pool, &accepted.message, "eve@x.io", None)
+            .await
+            .unwrap());
+        assert!(!session_owned_by(
+            &pool,
+            &accepted.message,
+            "eve@x.io",
+            Some(SessionOrigin::Sso)
+        )
+        .await
+        .unwrap());
+        let sso = open_session_internal(&pool, accepted.user_id as i64, SessionOrigin::Sso)
+            .await
+            .unwrap();
+        assert!(
+            session_owned_by(&pool, &sso, "EVE@x.io", Some(SessionOrigin::Sso))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn short_passwords_are_rejected() {
+        assert!(validate_password("short").is_err());
+        assert!(validate_password("long enough").is_ok());
+    }
+}
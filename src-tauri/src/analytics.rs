@@ -0,0 +1,285 @@
+// Activity analytics for the admin dashboard.
+//
+// Engagement over the last day, week or month, from this machine's database (so on the host,
+// everyone's): posted messages and distinct active users per department and per room, each
+// ranked by messages and with its busiest hour, plus the whole workspace's messages per hour
+// of the day and per day. Hours and days are UTC. Deleted messages don't count, and direct
+// messages count towards the totals and departments but aren't listed as rooms.
+//
+// The ranking and peak hours are SQL window functions (RANK, ROW_NUMBER), so the report is a
+// handful of queries whatever the size of the history.
+
+use crate::error::AppResult;
+use crate::room_digest::DigestPeriod;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tauri::State;
+
+/// A query over `posted`: the messages counted, in the period `$1`..`$2`.
+macro_rules! posted_query {
+    ($tail:literal) => {
+        concat!(
+            "WITH posted AS (
+                 SELECT m.room_id, m.user_id, u.department_id, date(m.created_at) AS day,
+                        CAST(strftime('%H', m.created_at) AS INTEGER) AS hour
+                 FROM messages m
+                 LEFT JOIN users u ON u.id = m.user_id
+                 WHERE m.message_type IN ('Chat', 'Sticker', 'Code', 'Attachment', 'Contact', 'Location')
+                   AND m.deleted_at IS NULL
+                   AND m.created_at >= $1 AND m.created_at < $2
+             )",
+            $tail
+        )
+    };
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct DepartmentActivity {
+    pub department_id: i64,
+    pub department: String,
+    pub members: i64,
+    pub active_users: i64,
+    pub messages: i64,
+    // 1 for the busiest; ties share a rank.
+    pub rank: i64,
+    // The UTC hour with the most messages (the earliest of a tie); None if there were none.
+    pub peak_hour: Option<i64>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct RoomActivity {
+    pub room_id: i64,
+    pub room: String,
+    pub active_users: i64,
+    pub messages: i64,
+    pub rank: i64,
+    pub peak_hour: Option<i64>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct DayActivity {
+    // "YYYY-MM-DD", UTC.
+    pub day: String,
+    pub messages: i64,
+    pub active_users: i64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ActivityReport {
+    pub period: DigestPeriod,
+    // UTC, "YYYY-MM-DD HH:MM:SS"; `from` inclusive, `to` exclusive.
+    pub from: String,
+    pub to: String,
+    pub messages: i64,
+    pub active_users: i64,
+    pub departments: Vec<DepartmentActivity>,
+    pub rooms: Vec<RoomActivity>,
+    // Messages per UTC hour of the day, all 24.
+    pub hours: Vec<i64>,
+    // Days with any messages, in order.
+    pub days: Vec<DayActivity>,
+}
+
+/// The report for `from` (inclusive) to `to` (exclusive).
+pub(crate) async fn build_report(
+    pool: &SqlitePool,
+    period: DigestPeriod,
+    from: &str,
+    to: &str,
+) -> AppResult<ActivityReport> {
+    let departments: Vec<DepartmentActivity> = sqlx::query_as(posted_query!(
+        ",
+         totals AS (
+             SELECT department_id, COUNT(*) AS messages, COUNT(DISTINCT user_id) AS active_users
+             FROM posted GROUP BY department_id
+         ),
+         hourly AS (
+             SELECT department_id, hour,
+                    ROW_NUMBER() OVER (PARTITION BY department_id ORDER BY COUNT(*) DESC, hour) AS pos
+             FROM posted GROUP BY department_id, hour
+         ),
+         members AS (SELECT department_id, COUNT(*) AS members FROM users GROUP BY department_id)
+         SELECT d.id AS department_id, d.name AS department,
+                COALESCE(mem.members, 0) AS members,
+                COALESCE(t.active_users, 0) AS active_users,
+                COALESCE(t.messages, 0) AS messages,
+                RANK() OVER (ORDER BY COALESCE(t.messages, 0) DESC) AS rank,
+                h.hour AS peak_hour
+         FROM departments d
+         LEFT JOIN totals t ON t.department_id = d.id
+         LEFT JOIN members mem ON mem.department_id = d.id
+         LEFT JOIN hourly h ON h.department_id = d.id AND h.pos = 1
+         ORDER BY rank, d.name"
+    ))
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    let rooms: Vec<RoomActivity> = sqlx::query_as(posted_query!(
+        ",
+         totals AS (
+             SELECT room_id, COUNT(*) AS messages, COUNT(DISTINCT user_id) AS active_users
+             FROM posted GROUP BY room_id
+         ),
+         hourly AS (
+             SELECT room_id, hour,
+                    ROW_NUMBER() OVER (PARTITION BY room_id ORDER BY COUNT(*) DESC, hour) AS pos
+             FROM posted GROUP BY room_id, hour
+         )
+         SELECT t.room_id, cr.name AS room, t.active_users, t.messages,
+                RANK() OVER (ORDER BY t.messages DESC) AS rank,
+                h.hour AS peak_hour
+         FROM totals t
+         JOIN chat_rooms cr ON cr.id = t.room_id AND cr.is_dm = 0
+         LEFT JOIN hourly h ON h.room_id = t.room_id AND h.pos = 1
+         ORDER BY rank, cr.name"
+    ))
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    let by_hour: Vec<(i64, i64)> = sqlx::query_as(posted_query!(
+        " SELECT hour, COUNT(*) FROM posted GROUP BY hour"
+    ))
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+    let mut hours = vec![0; 24];
+    for (hour, count) in by_hour {
+        if let Some(slot) = hours.get_mut(hour as usize) {
+            *slot = count;
+        }
+    }
+
+    let days: Vec<DayActivity> = sqlx::query_as(posted_query!(
+        " SELECT day, COUNT(*) AS messages, COUNT(DISTINCT user_id) AS active_users
+         FROM posted GROUP BY day ORDER BY day"
+    ))
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    let (messages, active_users): (i64, i64) = sqlx::query_as(posted_query!(
+        " SELECT COUNT(*), COUNT(DISTINCT user_id) FROM posted"
+    ))
+    .bind(from)
+    .bind(to)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(ActivityReport {
+        period,
+        from: from.to_string(),
+        to: to.to_string(),
+        messages,
+        active_users,
+        departments,
+        rooms,
+        hours,
+        days,
+    })
+}
+
+/// Engagement over the `period` ending now (a week by default).
+#[tauri::command]
+pub async fn get_activity_report(
+    db: State<'_, SqlitePool>,
+    period: Option<DigestPeriod>,
+) -> AppResult<ActivityReport> {
+    let period = period.unwrap_or_default();
+    let (from, to): (String, String) =
+        sqlx::query_as("SELECT datetime('now', $1), datetime('now')")
+            .bind(period.modifier())
+            .fetch_one(&*db)
+            .await?;
+    build_report(&db, period, &from, &to).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn activity_is_ranked_per_department_and_room() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO departments (id, name) VALUES (100, 'Lab'), (101, 'Quiet');
+             INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 100), (2, 'Bob', 'b@x', 100),
+                        (3, 'Carol', 'c@x', 101), (4, 'Dave', 'd@x', 101);
+             INSERT INTO chat_rooms (id, name, is_private, created_by)
+                 VALUES (200, 'bench', 0, 1), (201, 'coffee', 0, 3);
+             INSERT INTO chat_rooms (id, name, is_private, created_by, is_dm)
+                 VALUES (202, 'dm-1-3', 1, 1, 1);
+             INSERT INTO messages (room_id, user_id, message, message_type, message_id, created_at) VALUES
+                 (200, 1, 'a', 'Chat', 'a', '2026-03-02 09:05:00'),
+                 (200, 2, 'b', 'Chat', 'b', '2026-03-02 09:30:00'),
+                 (200, 1, 'c', 'Chat', 'c', '2026-03-03 14:00:00'),
+                 (201, 3, 'd', 'Chat', 'd', '2026-03-03 14:10:00'),
+                 (202, 1, 'e', 'Chat', 'e', '2026-03-03 16:00:00'),
+                 (200, 1, 'f', 'Chat', 'f', '2026-03-03 16:30:00'),
+                 (201, 3, 'joined', 'RoomJoin', 'g', '2026-03-03 17:00:00'),
+                 (200, 2, 'old', 'Chat', 'h', '2026-02-01 10:00:00');
+             UPDATE messages SET deleted_at = '2026-03-04T00:00:00Z' WHERE message_id = 'f';",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let report = build_report(
+            &pool,
+            DigestPeriod::Week,
+            "2026-03-02 00:00:00",
+            "2026-03-09 00:00:00",
+        )
+        .await
+        .unwrap();
+        assert_eq!((report.messages, report.active_users), (5, 3));
+
+        let lab = &report.departments[0];
+        assert_eq!(lab.department, "Lab");
+        assert_eq!(
+            (lab.members, lab.active_users, lab.messages, lab.rank),
+            (2, 2, 4, 1)
+        );
+        // Two messages at 09:00, one at 14:00 and 16:00.
+        assert_eq!(lab.peak_hour, Some(9));
+        let quiet = report
+            .departments
+            .iter()
+            .find(|d| d.department == "Quiet")
+            .unwrap();
+        assert_eq!((quiet.messages, quiet.peak_hour), (1, Some(14)));
+        let idle = report.departments.last().unwrap();
+        assert_eq!((idle.messages, idle.peak_hour), (0, None));
+
+        let rooms: Vec<(&str, i64, i64, i64)> = report
+            .rooms
+            .iter()
+            .map(|r| (r.room.as_str(), r.messages, r.active_users, r.rank))
+            .collect();
+        assert_eq!(rooms, [("bench", 3, 2, 1), ("coffee", 1, 1, 2)]);
+
+        assert_eq!(report.hours.len(), 24);
+        assert_eq!(
+            (report.hours[9], report.hours[14], report.hours[16]),
+            (2, 2, 1)
+        );
+        let days: Vec<(&str, i64)> = report
+            .days
+            .iter()
+            .map(|d| (d.day.as_str(), d.messages))
+            .collect();
+        assert_eq!(days, [("2026-03-02", 2), ("2026-03-03", 3)]);
+    }
+}
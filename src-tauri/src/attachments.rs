@@ -0,0 +1,1482 @@
+// File attachments (host-stored).
+//
+// The host keeps every shared file in its attachments directory (`<app config>/attachments/<id>`)
+// with a row in `attachments`: room, uploader, name, MIME type, size and SHA-256. A file is posted
+// to its room as an Attachment message whose `message` is an AttachmentRef.
+//
+// Files cross the wire in CHUNK_BYTES pieces, one AttachmentChunk frame each (base64, keyed by a
+// transfer id in `message_id`), because a frame has to fit in a single Noise message. A client
+// uploads by sending an AttachmentOffer {filename, size, sha256} for a room; the host checks
+// membership and the storage limits and answers with an AttachmentAck, and only then does the
+// client stream the chunks. When the last one arrives the host checks the hash, files the upload
+// and posts the message. Downloads run the other way: an AttachmentRequest, an AttachmentAck with
+// the size and hash, then the chunks, written beside the destination and renamed into place.
+// Either way the receiver screens the finished file (file_scan.rs) before keeping it.
+//
+// File chunks can be paced to a per-connection bandwidth limit (throttle.rs) so a big transfer
+// doesn't starve chat on a slow link.
+//
+// Storage limits (per file and in total) are a host setting; an upload over either fails with
+// `AppError::StorageQuota`, and one the disk can't spare fails with `AppError::DiskFull`
+// (disk_space.rs). An hourly cleanup removes attachments whose message was deleted or
+// pruned, files with no row, and uploads abandoned part-way.
+
+use crate::db_queries::{get_setting_internal, room_join_allowed_internal, set_setting_internal};
+use crate::disk_space;
+use crate::error::{AppError, AppResult};
+use crate::file_scan;
+use crate::media_cache;
+use crate::moderation;
+use crate::ocr;
+use crate::room_permissions;
+use crate::sockets::{
+    frame, publish_host_message, send_error_notice, send_secure, send_secure_client,
+    send_to_client, server_now_secs, AppState, Message, MessageType,
+};
+use crate::throttle;
+use crate::transcription;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager, Runtime, State};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+/// Raw bytes per AttachmentChunk. Base64 grows it by 4/3, which still fits a Noise message.
+pub const CHUNK_BYTES: usize = 32 * 1024;
+
+const SETTINGS_KEY: &str = "storage_limits";
+
+const MB: u64 = 1024 * 1024;
+
+/// How often the cleanup runs.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// A transfer (or its part file) untouched this long is abandoned.
+const STALE_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// How long a client waits for the host to accept an offer or a request.
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn default_max_file_bytes() -> u64 {
+    25 * MB
+}
+
+fn default_max_total_bytes() -> u64 {
+    2048 * MB
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StorageLimits {
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+    #[serde(default = "default_max_total_bytes")]
+    pub max_total_bytes: u64,
+}
+
+impl Default for StorageLimits {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: default_max_file_bytes(),
+            max_total_bytes: default_max_total_bytes(),
+        }
+    }
+}
+
+/// An Attachment message's `message`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AttachmentRef {
+    pub id: i64,
+    pub filename: String,
+    pub size: u64,
+    pub mime: String,
+}
+
+/// An AttachmentOffer's `message`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Offer {
+    filename: String,
+    size: u64,
+    sha256: String,
+}
+
+/// An AttachmentAck's `message`: the host's answer to an offer or a download request. A
+/// download's ack carries the file's size and hash.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TransferAck {
+    #[serde(default)]
+    pub error: Option<AppError>,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RoomUsage {
+    pub room_id: i64,
+    pub room: String,
+    pub files: i64,
+    pub bytes: i64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct StorageUsage {
+    // Totals over every attachment on record.
+    pub files: i64,
+    pub bytes: i64,
+    // What the attachments directory actually takes, including unfinished uploads.
+    pub on_disk_bytes: u64,
+    pub max_file_bytes: u64,
+    pub max_total_bytes: u64,
+    // Largest first.
+    pub rooms: Vec<RoomUsage>,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct CleanupReport {
+    pub attachments: u64,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// What a transfer in progress is for.
+pub enum TransferKind {
+    // Host: a client's upload into a room.
+    Upload {
+        user_id: u64,
+        username: String,
+        room_id: u64,
+        room: String,
+        filename: String,
+    },
+    // Client: a file being fetched from the host to `dest`.
+    Download {
+        attachment_id: i64,
+        dest: PathBuf,
+    },
+    // Client: an image attachment's thumbnail, fetched into the media cache at `dest`.
+    Thumbnail {
+        attachment_id: i64,
+        dest: PathBuf,
+    },
+}
+
+/// A file arriving in chunks, written to `part` until it's complete.
+pub struct Transfer {
+    pub kind: TransferKind,
+    pub part: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+    pub received: u64,
+    pub hasher: Sha256,
+    pub touched: Instant,
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bytes as KB / MB / GB, for error messages.
+fn human(bytes: u64) -> String {
+    if bytes >= 1024 * MB {
+        format!("{:.1} GB", bytes as f64 / (1024 * MB) as f64)
+    } else if bytes >= MB {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    } else {
+        format!("{} KB", bytes.div_ceil(1024))
+    }
+}
+
+/// The file's own name (no directories), without control characters, at most 255 characters.
+pub(crate) fn clean_filename(name: &str) -> AppResult<String> {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = base.chars().filter(|c| !c.is_control()).take(255).collect();
+    let cleaned = cleaned.trim().to_string();
+    if cleaned.is_empty() || cleaned == "." || cleaned == ".." {
+        return Err(AppError::Validation("The file needs a name".to_string()));
+    }
+    Ok(cleaned)
+}
+
+/// A MIME type from the file extension; anything unknown is a generic binary.
+fn mime_for(filename: &str) -> &'static str {
+    let ext = filename
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "txt" | "log" => "text/plain",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "zip" => "application/zip",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        _ => "application/octet-stream",
+    }
+}
+
+/// An Attachment message as plain text (notifications, digests).
+pub(crate) fn preview(message: &str) -> String {
+    match serde_json::from_str::<AttachmentRef>(message) {
+        Ok(a) => format!("📎 {}", a.filename),
+        Err(_) => "📎 Attachment".to_string(),
+    }
+}
+
+async fn load_limits(pool: &SqlitePool) -> AppResult<StorageLimits> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt storage limits: {}", e))),
+        None => Ok(StorageLimits::default()),
+    }
+}
+
+/// Reject a `size`-byte upload that's empty, over the per-file limit, or would take the stored
+/// total past the server's limit.
+pub async fn check_quota_internal(
+    pool: &SqlitePool,
+    limits: &StorageLimits,
+    size: u64,
+) -> AppResult<()> {
+    if size == 0 {
+        return Err(AppError::Validation("The file is empty".to_string()));
+    }
+    if size > limits.max_file_bytes {
+        return Err(AppError::StorageQuota(format!(
+            "Files can be at most {}",
+            human(limits.max_file_bytes)
+        )));
+    }
+    let used: i64 = sqlx::query_scalar("SELECT COALESCE(SUM(size), 0) FROM attachments")
+        .fetch_one(pool)
+        .await?;
+    if used as u64 + size > limits.max_total_bytes {
+        return Err(AppError::StorageQuota(format!(
+            "The server's attachment storage is full ({} of {} used)",
+            human(used as u64),
+            human(limits.max_total_bytes)
+        )));
+    }
+    Ok(())
+}
+
+/// Record a finished upload and move its part file into the store as `<dir>/<id>`.
+#[allow(clippy::too_many_arguments)]
+pub async fn file_internal(
+    pool: &SqlitePool,
+    dir: &Path,
+    part: &Path,
+    room_id: i64,
+    uploaded_by: Option<i64>,
+    filename: &str,
+    size: u64,
+    sha256: &str,
+    message_id: &str,
+) -> AppResult<AttachmentRef> {
+    let mime = mime_for(filename);
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO attachments (room_id, message_id, uploaded_by, filename, mime, size, sha256)
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id",
+    )
+    .bind(room_id)
+    .bind(message_id)
+    .bind(uploaded_by)
+    .bind(filename)
+    .bind(mime)
+    .bind(size as i64)
+    .bind(sha256)
+    .fetch_one(pool)
+    .await?;
+    if let Err(e) = tokio::fs::rename(part, dir.join(id.to_string())).await {
+        let _ = sqlx::query("DELETE FROM attachments WHERE id = $1")
+            .bind(id)
+            .execute(pool)
+            .await;
+        return Err(AppError::Internal(format!(
+            "Couldn't store the attachment: {}",
+            e
+        )));
+    }
+    Ok(AttachmentRef {
+        id,
+        filename: filename.to_string(),
+        size,
+        mime: mime.to_string(),
+    })
+}
+
+/// Total size of the regular files in `dir` (0 if it doesn't exist yet).
+async fn dir_bytes(dir: &Path) -> u64 {
+    let mut total = 0;
+    let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+        return 0;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if let Ok(meta) = entry.metadata().await {
+            if meta.is_file() {
+                total += meta.len();
+            }
+        }
+    }
+    total
+}
+
+pub async fn usage_internal(
+    pool: &SqlitePool,
+    dir: Option<&Path>,
+    limits: &StorageLimits,
+) -> AppResult<StorageUsage> {
+    let rows = sqlx::query(
+        "SELECT a.room_id AS room_id, COALESCE(cr.name, '') AS room,
+                COUNT(*) AS files, SUM(a.size) AS bytes
+         FROM attachments a LEFT JOIN chat_rooms cr ON cr.id = a.room_id
+         GROUP BY a.room_id ORDER BY bytes DESC, a.room_id",
+    )
+    .fetch_all(pool)
+    .await?;
+    let rooms: Vec<RoomUsage> = rows
+        .iter()
+        .map(|r| RoomUsage {
+            room_id: r.get("room_id"),
+            room: r.get("room"),
+            files: r.get("files"),
+            bytes: r.get("bytes"),
+        })
+        .collect();
+    Ok(StorageUsage {
+        files: rooms.iter().map(|r| r.files).sum(),
+        bytes: rooms.iter().map(|r| r.bytes).sum(),
+        on_disk_bytes: match dir {
+            Some(dir) => dir_bytes(dir).await,
+            None => 0,
+        },
+        max_file_bytes: limits.max_file_bytes,
+        max_total_bytes: limits.max_total_bytes,
+        rooms,
+    })
+}
+
+/// Drop attachments whose message was deleted, or never landed / was pruned (after an hour's
+/// grace for one still being posted); then remove files in `dir` with no row, and part files
+/// untouched for `stale`.
+pub async fn cleanup_internal(
+    pool: &SqlitePool,
+    dir: &Path,
+    stale: Duration,
+) -> AppResult<CleanupReport> {
+    let attachments = sqlx::query(
+        "DELETE FROM attachments
+         WHERE EXISTS (SELECT 1 FROM messages m
+                       WHERE m.message_id = attachments.message_id AND m.deleted_at IS NOT NULL)
+            OR (NOT EXISTS (SELECT 1 FROM messages m WHERE m.message_id = attachments.message_id)
+                AND created_at < datetime('now', '-1 hour'))",
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    let ids: HashSet<i64> = sqlx::query_scalar("SELECT id FROM attachments")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+    let mut report = CleanupReport {
+        attachments,
+        ..Default::default()
+    };
+    let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+        return Ok(report);
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let Ok(meta) = entry.metadata().await else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let remove = match name.strip_suffix(".part") {
+            Some(_) => meta
+                .modified()
+                .ok()
+                .and_then(|m| m.elapsed().ok())
+                .is_some_and(|age| age >= stale),
+            None => name.parse::<i64>().map_or(true, |id| !ids.contains(&id)),
+        };
+        if remove && tokio::fs::remove_file(entry.path()).await.is_ok() {
+            report.files += 1;
+            report.bytes += meta.len();
+        }
+    }
+    Ok(report)
+}
+
+fn attachments_dir(state: &AppState) -> AppResult<&PathBuf> {
+    state
+        .attachments_dir
+        .get()
+        .ok_or_else(|| AppError::Internal("Attachment storage isn't ready".to_string()))
+}
+
+async fn room_name(pool: &SqlitePool, room_id: u64) -> AppResult<String> {
+    sqlx::query_scalar("SELECT name FROM chat_rooms WHERE id = $1")
+        .bind(room_id as i64)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Validation("That room doesn't exist".to_string()))
+}
+
+async fn ensure_member(pool: &SqlitePool, user_id: u64, room_id: u64) -> AppResult<()> {
+    if room_join_allowed_internal(pool, user_id as i64, room_id as i64)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Ok(())
+    } else {
+        Err(AppError::Auth(
+            "You're not a member of that room".to_string(),
+        ))
+    }
+}
+
+/// A transfer control frame: no room, no author name.
+fn transfer_frame(message_type: MessageType, user_id: u64, id: &str, message: String) -> Message {
+    Message {
+        message_id: id.to_string(),
+        ..frame(message_type, user_id, message)
+    }
+}
+
+fn ack_frame(user_id: u64, id: &str, ack: &TransferAck) -> Message {
+    let json = serde_json::to_string(ack).unwrap_or_default();
+    transfer_frame(MessageType::AttachmentAck, user_id, id, json)
+}
+
+/// Append one chunk to `transfer`'s part file.
+async fn append(transfer: &mut Transfer, bytes: &[u8]) -> std::io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&transfer.part)
+        .await?;
+    file.write_all(bytes).await?;
+    transfer.received += bytes.len() as u64;
+    transfer.hasher.update(bytes);
+    transfer.touched = Instant::now();
+    Ok(())
+}
+
+/// Host: post an attachment to its room as `user_id`.
+#[allow(clippy::too_many_arguments)]
+async fn post<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    user_id: u64,
+    username: String,
+    room_id: u64,
+    room: String,
+    attachment: &AttachmentRef,
+    message_id: String,
+) {
+    let message = Message {
+        username,
+        message_id,
+        room,
+        room_id,
+        filename: Some(attachment.filename.clone()),
+        ..frame(
+            MessageType::Attachment,
+            user_id,
+            serde_json::to_string(attachment).unwrap_or_default(),
+        )
+    };
+    publish_host_message(app, state, pool, message).await;
+    transcription::after_upload(state, pool, attachment);
+    ocr::after_upload(state, pool, attachment);
+}
+
+/// Host participant: copy a local file into the store and post it.
+async fn share_local(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    user_id: u64,
+    room_id: u64,
+    path: &Path,
+    filename: String,
+) -> AppResult<()> {
+    ensure_member(pool, user_id, room_id).await?;
+    let room = room_name(pool, room_id).await?;
+    let size = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| AppError::Validation(format!("Couldn't read the file: {}", e)))?
+        .len();
+    let limits = load_limits(pool).await?;
+    check_quota_internal(pool, &limits, size).await?;
+    let dir = attachments_dir(state)?;
+    disk_space::ensure_room(app, pool, dir, size).await?;
+    let part = dir.join(format!("{}.part", Uuid::new_v4()));
+    let copied = async {
+        let mut src = tokio::fs::File::open(path).await?;
+        let mut dst = tokio::fs::File::create(&part).await?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; CHUNK_BYTES];
+        let mut total = 0u64;
+        loop {
+            let n = src.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            dst.write_all(&buf[..n]).await?;
+            total += n as u64;
+        }
+        dst.flush().await?;
+        Ok::<_, std::io::Error>((total, hex(&hasher.finalize())))
+    }
+    .await;
+    let (size, sha256) = match copied {
+        Ok(done) => done,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&part).await;
+            return Err(AppError::Internal(format!("Couldn't copy the file: {}", e)));
+        }
+    };
+    let message_id = Uuid::new_v4().to_string();
+    let attachment = match file_internal(
+        pool,
+        dir,
+        &part,
+        room_id as i64,
+        Some(user_id as i64),
+        &filename,
+        size,
+        &sha256,
+        &message_id,
+    )
+    .await
+    {
+        Ok(a) => a,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&part).await;
+            return Err(e);
+        }
+    };
+    let username = state.username.read().await.clone();
+    post(
+        app,
+        state,
+        pool,
+        user_id,
+        username,
+        room_id,
+        room,
+        &attachment,
+        message_id,
+    )
+    .await;
+    Ok(())
+}
+
+/// Client: register interest in the host's AttachmentAck for `id`, send `request`, and wait.
+async fn request_ack(state: &Arc<AppState>, id: &str, request: &Message) -> AppResult<TransferAck> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    state.transfer_acks.lock().await.insert(id.to_string(), tx);
+    if let Err(e) = send_secure_client(state, request).await {
+        state.transfer_acks.lock().await.remove(id);
+        return Err(AppError::Network(e));
+    }
+    match tokio::time::timeout(ACK_TIMEOUT, rx).await {
+        Ok(Ok(ack)) => match ack.error {
+            Some(e) => Err(e),
+            None => Ok(ack),
+        },
+        _ => {
+            state.transfer_acks.lock().await.remove(id);
+            Err(AppError::Network(
+                "The host didn't answer the file transfer".to_string(),
+            ))
+        }
+    }
+}
+
+/// Client: offer a file to the host and, once it's accepted, stream it in chunks. The host
+/// posts the Attachment message when the last chunk is in.
+async fn upload_to_host(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    user_id: u64,
+    room_id: u64,
+    path: &Path,
+    filename: String,
+) -> AppResult<()> {
+    let read_err =
+        |e: std::io::Error| AppError::Validation(format!("Couldn't read the file: {}", e));
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    {
+        let mut file = tokio::fs::File::open(path).await.map_err(read_err)?;
+        let mut buf = vec![0u8; CHUNK_BYTES];
+        loop {
+            let n = file.read(&mut buf).await.map_err(read_err)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            size += n as u64;
+        }
+    }
+    let upload_id = Uuid::new_v4().to_string();
+    let offer = serde_json::to_string(&Offer {
+        filename: filename.clone(),
+        size,
+        sha256: hex(&hasher.finalize()),
+    })
+    .map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut request = transfer_frame(MessageType::AttachmentOffer, user_id, &upload_id, offer);
+    request.room_id = room_id;
+    request.created_at = server_now_secs(&state.clock_offset_ms);
+    request_ack(state, &upload_id, &request).await?;
+
+    let mut file = tokio::fs::File::open(path).await.map_err(read_err)?;
+    let mut buf = vec![0u8; CHUNK_BYTES];
+    let mut sent = 0u64;
+    while sent < size {
+        let n = file.read(&mut buf).await.map_err(read_err)?;
+        if n == 0 {
+            break;
+        }
+        let data = base64::engine::general_purpose::STANDARD.encode(&buf[..n]);
+        throttle::pace(state, throttle::TO_HOST, data.len()).await;
+        let chunk = transfer_frame(MessageType::AttachmentChunk, user_id, &upload_id, data);
+        send_secure_client(state, &chunk)
+            .await
+            .map_err(AppError::Network)?;
+        sent += n as u64;
+        let _ = app.emit(
+            "attachment_progress",
+            serde_json::json!({ "id": upload_id, "filename": filename, "done": sent, "total": size }),
+        );
+    }
+    Ok(())
+}
+
+/// Host: a client's AttachmentOffer / AttachmentChunk / AttachmentRequest.
+pub(crate) async fn handle_client_frame<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    auth_user_id: u64,
+    message: &Message,
+) {
+    match message.message_type {
+        MessageType::AttachmentOffer => {
+            let ack = match accept_offer(app, state, pool, auth_user_id, message).await {
+                Ok(()) => TransferAck::default(),
+                Err(e) => TransferAck {
+                    error: Some(e),
+                    ..Default::default()
+                },
+            };
+            let reply = ack_frame(auth_user_id, &message.message_id, &ack);
+            send_to_client(state, auth_user_id, &reply).await;
+        }
+        MessageType::AttachmentChunk => {
+            receive_chunk(app, state, pool, auth_user_id, message).await;
+        }
+        MessageType::AttachmentRequest => {
+            serve_download(state, pool, auth_user_id, message).await;
+        }
+        _ => {}
+    }
+}
+
+/// Host: check an offer and get ready for its chunks.
+async fn accept_offer<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    user_id: u64,
+    message: &Message,
+) -> AppResult<()> {
+    let offer: Offer = serde_json::from_str(&message.message)
+        .map_err(|_| AppError::Validation("Malformed file offer".to_string()))?;
+    let filename = clean_filename(&offer.filename)?;
+    if let Some(notice) = moderation::mute_notice(pool, user_id).await {
+        return Err(AppError::Auth(notice));
+    }
+    ensure_member(pool, user_id, message.room_id).await?;
+    room_permissions::check(
+        pool,
+        message.room_id as i64,
+        Some(user_id as i64),
+        room_permissions::Action::Post,
+    )
+    .await?;
+    let room = room_name(pool, message.room_id).await?;
+    let limits = load_limits(pool).await?;
+    check_quota_internal(pool, &limits, offer.size).await?;
+    let dir = attachments_dir(state)?;
+    disk_space::ensure_room(app, pool, dir, offer.size).await?;
+    let part = dir.join(format!("{}.part", Uuid::new_v4()));
+    let username = state
+        .server_streams
+        .lock()
+        .await
+        .get(&user_id)
+        .map(|c| c.username.clone())
+        .unwrap_or_default();
+    let transfer = Transfer {
+        kind: TransferKind::Upload {
+            user_id,
+            username,
+            room_id: message.room_id,
+            room,
+            filename,
+        },
+        part,
+        size: offer.size,
+        sha256: offer.sha256,
+        received: 0,
+        hasher: Sha256::new(),
+        touched: Instant::now(),
+    };
+    state
+        .transfers
+        .lock()
+        .await
+        .insert(format!("{}:{}", user_id, message.message_id), transfer);
+    Ok(())
+}
+
+/// Host: one chunk of a client's upload; the last one files and posts it.
+async fn receive_chunk<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    user_id: u64,
+    message: &Message,
+) {
+    let key = format!("{}:{}", user_id, message.message_id);
+    // Taken out while it's written to, so no lock is held across the file I/O.
+    let Some(mut transfer) = state.transfers.lock().await.remove(&key) else {
+        return;
+    };
+    let result = async {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(message.message.as_bytes())
+            .map_err(|_| AppError::Validation("Malformed file chunk".to_string()))?;
+        if transfer.received + bytes.len() as u64 > transfer.size {
+            return Err(AppError::Validation(
+                "The file is bigger than offered".to_string(),
+            ));
+        }
+        append(&mut transfer, &bytes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Couldn't save the upload: {}", e)))?;
+        Ok(transfer.received == transfer.size)
+    }
+    .await;
+    let TransferKind::Upload { filename, .. } = &transfer.kind else {
+        return;
+    };
+    let filename = filename.clone();
+    match result {
+        Ok(false) => {
+            state.transfers.lock().await.insert(key, transfer);
+        }
+        Ok(true) => {
+            if let Err(e) = finish_upload(app, state, pool, transfer).await {
+                let notice = format!("Couldn't share {}: {}", filename, e);
+                send_error_notice(state, user_id, &notice).await;
+            }
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&transfer.part).await;
+            let notice = format!("Couldn't share {}: {}", filename, e);
+            send_error_notice(state, user_id, &notice).await;
+        }
+    }
+}
+
+async fn finish_upload<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    transfer: Transfer,
+) -> AppResult<()> {
+    let TransferKind::Upload {
+        user_id,
+        username,
+        room_id,
+        room,
+        filename,
+    } = transfer.kind
+    else {
+        return Ok(());
+    };
+    let filed = async {
+        // Muted while the chunks were on their way.
+        if let Some(notice) = moderation::mute_notice(pool, user_id).await {
+            return Err(AppError::Auth(notice));
+        }
+        if hex(&transfer.hasher.finalize()) != transfer.sha256 {
+            return Err(AppError::Validation(
+                "The file was corrupted in transit".to_string(),
+            ));
+        }
+        file_scan::screen(
+            app,
+            pool,
+            &transfer.part,
+            &filename,
+            transfer.size,
+            None,
+            Some(user_id as i64),
+        )
+        .await
+        .map_err(|reason| AppError::Validation(format!("The host refused it: {}", reason)))?;
+        // Other uploads may have finished since the offer was accepted.
+        let limits = load_limits(pool).await?;
+        check_quota_internal(pool, &limits, transfer.size).await?;
+        let message_id = Uuid::new_v4().to_string();
+        let attachment = file_internal(
+            pool,
+            attachments_dir(state)?,
+            &transfer.part,
+            room_id as i64,
+            Some(user_id as i64),
+            &filename,
+            transfer.size,
+            &transfer.sha256,
+            &message_id,
+        )
+        .await?;
+        Ok((attachment, message_id))
+    }
+    .await;
+    match filed {
+        Ok((attachment, message_id)) => {
+            post(
+                app,
+                state,
+                pool,
+                user_id,
+                username,
+                room_id,
+                room,
+                &attachment,
+                message_id,
+            )
+            .await;
+            Ok(())
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&transfer.part).await;
+            Err(e)
+        }
+    }
+}
+
+/// Host: answer a client's AttachmentRequest (`message` = attachment id) with an ack, then
+/// stream the file to it in the background.
+async fn serve_download(state: &Arc<AppState>, pool: &SqlitePool, user_id: u64, message: &Message) {
+    let download_id = message.message_id.clone();
+    let found = async {
+        let (thumbnail, id) = match message.message.strip_prefix("thumb:") {
+            Some(id) => (true, id),
+            None => (false, message.message.as_str()),
+        };
+        let id: i64 = id
+            .parse()
+            .map_err(|_| AppError::Validation("Malformed file request".to_string()))?;
+        let row = sqlx::query("SELECT room_id, size, sha256 FROM attachments WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::Validation("That file is no longer available".to_string()))?;
+        ensure_member(pool, user_id, row.get::<i64, _>("room_id") as u64).await?;
+        if thumbnail {
+            let path = media_cache::host_thumbnail(state, pool, id)
+                .await?
+                .ok_or_else(|| AppError::Validation("That file has no preview".to_string()))?;
+            let bytes = tokio::fs::read(&path)
+                .await
+                .map_err(|e| AppError::Internal(e.to_string()))?;
+            let sha256 = hex(&Sha256::digest(&bytes));
+            return Ok((path, bytes.len() as u64, sha256));
+        }
+        let path = attachments_dir(state)?.join(id.to_string());
+        Ok::<_, AppError>((
+            path,
+            row.get::<i64, _>("size") as u64,
+            row.get::<String, _>("sha256"),
+        ))
+    }
+    .await;
+    let (path, size, sha256) = match found {
+        Ok(f) => f,
+        Err(e) => {
+            let ack = TransferAck {
+                error: Some(e),
+                ..Default::default()
+            };
+            send_to_client(state, user_id, &ack_frame(user_id, &download_id, &ack)).await;
+            return;
+        }
+    };
+    let ack = TransferAck {
+        error: None,
+        size: Some(size),
+        sha256: Some(sha256),
+    };
+    send_to_client(state, user_id, &ack_frame(user_id, &download_id, &ack)).await;
+    let Some((writer, transport)) = state
+        .server_streams
+        .lock()
+        .await
+        .get(&user_id)
+        .map(|c| (Arc::clone(&c.writer), Arc::clone(&c.transport)))
+    else {
+        return;
+    };
+    let state = Arc::clone(state);
+    tauri::async_runtime::spawn(async move {
+        let Ok(mut file) = tokio::fs::File::open(&path).await else {
+            return;
+        };
+        let mut buf = vec![0u8; CHUNK_BYTES];
+        loop {
+            let n = match file.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            let data = base64::engine::general_purpose::STANDARD.encode(&buf[..n]);
+            throttle::pace(&state, user_id, data.len()).await;
+            let chunk = transfer_frame(MessageType::AttachmentChunk, user_id, &download_id, data);
+            // Stop once the client is gone.
+            if send_secure(&writer, &transport, &chunk).await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Client: an AttachmentAck or AttachmentChunk from the primary connection's host. Finished
+/// downloads are reported to the UI as `attachment_saved` / `attachment_failed`, and fetched
+/// thumbnails as `thumbnail_ready`.
+pub(crate) async fn handle_host_frame(app: &tauri::AppHandle, message: &Message) {
+    let Some(state) = app.try_state::<Arc<AppState>>() else {
+        return;
+    };
+    let id = &message.message_id;
+    match message.message_type {
+        MessageType::AttachmentAck => {
+            let ack: TransferAck = serde_json::from_str(&message.message).unwrap_or_default();
+            // A download learns what to expect here, before the first chunk is read.
+            let part = {
+                let mut transfers = state.transfers.lock().await;
+                match (transfers.get_mut(id), &ack) {
+                    (
+                        Some(t),
+                        TransferAck {
+                            error: None,
+                            size: Some(size),
+                            sha256: Some(sha),
+                            ..
+                        },
+                    ) => {
+                        t.size = *size;
+                        t.sha256 = sha.clone();
+                        Some(t.part.clone())
+                    }
+                    (Some(_), _) => {
+                        transfers.remove(id);
+                        None
+                    }
+                    (None, _) => None,
+                }
+            };
+            if let Some(part) = part {
+                // Start from an empty file, whatever an earlier attempt left behind.
+                let _ = tokio::fs::File::create(&part).await;
+            }
+            if let Some(tx) = state.transfer_acks.lock().await.remove(id) {
+                let _ = tx.send(ack);
+            }
+        }
+        MessageType::AttachmentChunk => {
+            let Some(mut transfer) = state.transfers.lock().await.remove(id) else {
+                return;
+            };
+            let result = async {
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(message.message.as_bytes())
+                    .map_err(|_| "Malformed file chunk".to_string())?;
+                if transfer.received + bytes.len() as u64 > transfer.size {
+                    return Err("The file is bigger than expected".to_string());
+                }
+                append(&mut transfer, &bytes)
+                    .await
+                    .map_err(|e| format!("Couldn't save the file: {}", e))?;
+                Ok(transfer.received == transfer.size)
+            }
+            .await;
+            let complete = match result {
+                Ok(false) => {
+                    if let TransferKind::Download { .. } = transfer.kind {
+                        let _ = app.emit(
+                            "attachment_progress",
+                            serde_json::json!({ "id": id, "done": transfer.received, "total": transfer.size }),
+                        );
+                    }
+                    state.transfers.lock().await.insert(id.clone(), transfer);
+                    return;
+                }
+                Ok(true) if hex(&transfer.hasher.clone().finalize()) != transfer.sha256 => {
+                    Err("The file was corrupted in transit".to_string())
+                }
+                Ok(true) => Ok(()),
+                Err(e) => Err(e),
+            };
+            match &transfer.kind {
+                TransferKind::Download {
+                    attachment_id,
+                    dest,
+                } => finish_download(app, &state, &transfer, *attachment_id, dest, complete).await,
+                TransferKind::Thumbnail {
+                    attachment_id,
+                    dest,
+                } => {
+                    let stored = match complete {
+                        Ok(()) => media_cache::store_fetched(&state, &transfer.part, dest)
+                            .await
+                            .map_err(|e| e.to_string()),
+                        Err(e) => Err(e),
+                    };
+                    match stored {
+                        Ok(()) => {
+                            let _ = app.emit(
+                                "thumbnail_ready",
+                                serde_json::json!({ "id": attachment_id }),
+                            );
+                        }
+                        Err(e) => {
+                            let _ = tokio::fs::remove_file(&transfer.part).await;
+                            tracing::warn!("Thumbnail for attachment {}: {}", attachment_id, e);
+                        }
+                    }
+                }
+                TransferKind::Upload { .. } => {}
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Client: a download's last chunk is in (or it failed). Screen it and move it into place.
+async fn finish_download(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    transfer: &Transfer,
+    attachment_id: i64,
+    dest: &Path,
+    complete: Result<(), String>,
+) {
+    let outcome = match complete {
+        Ok(()) => {
+            let filename = dest
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let actor = state.user_id.read().await.map(|id| id as i64);
+            if let Some(pool) = state.pool.get() {
+                let screened = file_scan::screen(
+                    app,
+                    pool,
+                    &transfer.part,
+                    &filename,
+                    transfer.size,
+                    Some(attachment_id),
+                    actor,
+                )
+                .await;
+                if screened.is_err() {
+                    // Already quarantined and reported.
+                    return;
+                }
+            }
+            tokio::fs::rename(&transfer.part, dest)
+                .await
+                .map_err(|e| format!("Couldn't save the file: {}", e))
+        }
+        Err(e) => Err(e),
+    };
+    match outcome {
+        Ok(()) => {
+            let _ = app.emit(
+                "attachment_saved",
+                serde_json::json!({ "id": attachment_id, "path": dest }),
+            );
+        }
+        Err(error) => {
+            let _ = tokio::fs::remove_file(&transfer.part).await;
+            let _ = app.emit(
+                "attachment_failed",
+                serde_json::json!({ "id": attachment_id, "error": error }),
+            );
+        }
+    }
+}
+
+/// Clean up on a fixed interval for the life of the app, and forget transfers that stalled.
+pub fn spawn_cleanup(state: Arc<AppState>, pool: SqlitePool) {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            state
+                .transfers
+                .lock()
+                .await
+                .retain(|_, t| t.touched.elapsed() < STALE_AFTER);
+            let Some(dir) = state.attachments_dir.get() else {
+                continue;
+            };
+            match cleanup_internal(&pool, dir, STALE_AFTER).await {
+                Ok(r) if r.files > 0 || r.attachments > 0 => tracing::info!(
+                    "Attachment cleanup: {} attachments, {} files ({} bytes)",
+                    r.attachments,
+                    r.files,
+                    r.bytes
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Attachment cleanup failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Share a file in a room. On the host it's stored and posted directly; a client uploads it to
+/// its host, which posts it once the whole file has arrived.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn send_attachment(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: u64,
+    path: String,
+) -> AppResult<()> {
+    let path = PathBuf::from(path);
+    let filename = clean_filename(&path.to_string_lossy())?;
+    share_file(&app, state.inner(), &db, room_id, &path, filename).await
+}
+
+/// Share the file at `path` in a room under `filename`: stored and posted on the host, uploaded
+/// from a client. Returns once the file is on the host.
+pub(crate) async fn share_file(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    room_id: u64,
+    path: &Path,
+    filename: String,
+) -> AppResult<()> {
+    let Some(user_id) = *state.user_id.read().await else {
+        return Err(AppError::Auth("Sign in to share files".to_string()));
+    };
+    if *state.is_server.read().await {
+        share_local(app, state, pool, user_id, room_id, path, filename).await
+    } else {
+        upload_to_host(app, state, user_id, room_id, path, filename).await
+    }
+}
+
+/// Save an attachment to `dest`. The host copies it from its store; a client fetches it from
+/// its host and returns once the host has accepted, finishing with `attachment_saved` (or
+/// `attachment_failed`).
+#[tauri::command(rename_all = "snake_case")]
+pub async fn download_attachment(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    attachment_id: i64,
+    dest: String,
+) -> AppResult<()> {
+    let dest = PathBuf::from(dest);
+    if *state.is_server.read().await {
+        let known: Option<i64> = sqlx::query_scalar("SELECT id FROM attachments WHERE id = $1")
+            .bind(attachment_id)
+            .fetch_optional(&*db)
+            .await?;
+        if known.is_none() {
+            return Err(AppError::Validation(
+                "That file is no longer available".to_string(),
+            ));
+        }
+        let src = attachments_dir(&state)?.join(attachment_id.to_string());
+        tokio::fs::copy(&src, &dest)
+            .await
+            .map_err(|e| AppError::Internal(format!("Couldn't save the file: {}", e)))?;
+        let _ = app.emit(
+            "attachment_saved",
+            serde_json::json!({ "id": attachment_id, "path": dest }),
+        );
+        return Ok(());
+    }
+    let user_id = state.user_id.read().await.unwrap_or(0);
+    let download_id = Uuid::new_v4().to_string();
+    let mut part = dest.clone().into_os_string();
+    part.push(".part");
+    // Registered before the request goes out so the ack can fill in the size and hash.
+    state.transfers.lock().await.insert(
+        download_id.clone(),
+        Transfer {
+            kind: TransferKind::Download {
+                attachment_id,
+                dest,
+            },
+            part: PathBuf::from(part),
+            size: 0,
+            sha256: String::new(),
+            received: 0,
+            hasher: Sha256::new(),
+            touched: Instant::now(),
+        },
+    );
+    let request = transfer_frame(
+        MessageType::AttachmentRequest,
+        user_id,
+        &download_id,
+        attachment_id.to_string(),
+    );
+    if let Err(e) = request_ack(state.inner(), &download_id, &request).await {
+        state.transfers.lock().await.remove(&download_id);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Client: ask the host for an attachment's thumbnail, to be written to `dest` (see
+/// media_cache.rs). A no-op while one for the same attachment is already on its way.
+pub(crate) async fn fetch_thumbnail(
+    state: &Arc<AppState>,
+    attachment_id: i64,
+    dest: PathBuf,
+) -> AppResult<()> {
+    let user_id = state.user_id.read().await.unwrap_or(0);
+    let fetch_id = Uuid::new_v4().to_string();
+    {
+        let mut transfers = state.transfers.lock().await;
+        let pending = transfers.values().any(|t| {
+            matches!(t.kind, TransferKind::Thumbnail { attachment_id: a, .. } if a == attachment_id)
+        });
+        if pending {
+            return Ok(());
+        }
+        transfers.insert(
+            fetch_id.clone(),
+            Transfer {
+                part: dest.with_extension("part"),
+                kind: TransferKind::Thumbnail {
+                    attachment_id,
+                    dest,
+                },
+                size: 0,
+                sha256: String::new(),
+                received: 0,
+                hasher: Sha256::new(),
+                touched: Instant::now(),
+            },
+        );
+    }
+    let request = transfer_frame(
+        MessageType::AttachmentRequest,
+        user_id,
+        &fetch_id,
+        format!("thumb:{}", attachment_id),
+    );
+    if let Err(e) = request_ack(state, &fetch_id, &request).await {
+        state.transfers.lock().await.remove(&fetch_id);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Host: attachment storage used, in total and by room, against the limits.
+#[tauri::command]
+pub async fn get_storage_usage(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+) -> AppResult<StorageUsage> {
+    let limits = load_limits(&db).await?;
+    usage_internal(
+        &db,
+        state.attachments_dir.get().map(PathBuf::as_path),
+        &limits,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn get_storage_limits(db: State<'_, SqlitePool>) -> AppResult<StorageLimits> {
+    load_limits(&db).await
+}
+
+#[tauri::command]
+pub async fn set_storage_limits(db: State<'_, SqlitePool>, limits: StorageLimits) -> AppResult<()> {
+    if limits.max_file_bytes == 0 || limits.max_total_bytes < limits.max_file_bytes {
+        return Err(AppError::Validation(
+            "Files need some room, and the total must be at least the per-file limit".to_string(),
+        ));
+    }
+    let json = serde_json::to_string(&limits).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[test]
+    fn filenames_are_reduced_to_a_safe_base_name() {
+        assert_eq!(
+            clean_filename("/home/ada/report.pdf").unwrap(),
+            "report.pdf"
+        );
+        assert_eq!(
+            clean_filename("C:\\Users\\ada\\a b.txt").unwrap(),
+            "a b.txt"
+        );
+        assert_eq!(clean_filename("bad\u{7}name.log").unwrap(), "badname.log");
+        assert!(clean_filename("../").is_err());
+        assert!(clean_filename("..").is_err());
+        assert_eq!(mime_for("Shot.PNG"), "image/png");
+        assert_eq!(mime_for("Makefile"), "application/octet-stream");
+    }
+
+    async fn share(
+        pool: &SqlitePool,
+        dir: &Path,
+        limits: &StorageLimits,
+        room_id: i64,
+        filename: &str,
+        bytes: &[u8],
+        message_id: &str,
+    ) -> AppResult<AttachmentRef> {
+        check_quota_internal(pool, limits, bytes.len() as u64).await?;
+        let part = dir.join(format!("{}.part", message_id));
+        tokio::fs::write(&part, bytes).await.unwrap();
+        let sha256 = hex(&Sha256::digest(bytes));
+        file_internal(
+            pool,
+            dir,
+            &part,
+            room_id,
+            Some(1),
+            filename,
+            bytes.len() as u64,
+            &sha256,
+            message_id,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn quotas_usage_and_cleanup() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id) VALUES (1, 'Alice', 'a@x', 1);
+             INSERT INTO chat_rooms (id, name, created_by) VALUES (90, 'design', 1), (100, 'ops', 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let dir = std::env::temp_dir().join(format!("nutler-attachments-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let limits = StorageLimits {
+            max_file_bytes: 10,
+            max_total_bytes: 15,
+        };
+
+        let a = share(&pool, &dir, &limits, 90, "a.txt", b"12345678", "m1")
+            .await
+            .unwrap();
+        assert_eq!(a.mime, "text/plain");
+        // Over the per-file limit, then over what's left of the total.
+        assert!(matches!(
+            share(&pool, &dir, &limits, 100, "big.bin", b"12345678901", "m2").await,
+            Err(AppError::StorageQuota(_))
+        ));
+        assert!(matches!(
+            share(&pool, &dir, &limits, 100, "b.bin", b"12345678", "m3").await,
+            Err(AppError::StorageQuota(_))
+        ));
+        share(&pool, &dir, &limits, 100, "c.bin", b"1234", "m4")
+            .await
+            .unwrap();
+        assert!(matches!(
+            check_quota_internal(&pool, &limits, 0).await,
+            Err(AppError::Validation(_))
+        ));
+
+        let usage = usage_internal(&pool, Some(&dir), &limits).await.unwrap();
+        assert_eq!((usage.files, usage.bytes), (2, 12));
+        let rooms: Vec<_> = usage
+            .rooms
+            .iter()
+            .map(|r| (r.room.as_str(), r.bytes))
+            .collect();
+        assert_eq!(rooms, [("design", 8), ("ops", 4)]);
+
+        // m1's message was deleted; m4's hasn't landed yet but is within its grace period. A
+        // stray file and an abandoned part file go too.
+        sqlx::raw_sql(
+            "INSERT INTO messages (room_id, user_id, message, message_type, message_id, deleted_at)
+                 VALUES (90, 1, '{}', 'Attachment', 'm1', CURRENT_TIMESTAMP);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        tokio::fs::write(dir.join("999"), b"stray").await.unwrap();
+        tokio::fs::write(dir.join("x.part"), b"half").await.unwrap();
+        let before = usage_internal(&pool, Some(&dir), &limits).await.unwrap();
+        let report = cleanup_internal(&pool, &dir, Duration::ZERO).await.unwrap();
+        assert_eq!((report.attachments, report.files), (1, 3));
+        assert_eq!(report.bytes, before.on_disk_bytes - 4);
+        let after = usage_internal(&pool, Some(&dir), &limits).await.unwrap();
+        assert_eq!((after.files, after.bytes, after.on_disk_bytes), (1, 4, 4));
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+// Client version gate (host-side).
+//
+// A client names its app version in its handshake hello ("nutler/<version>", then any
+// compression offer after a `+`; older builds send nothing). When the host has a minimum version configured, a client below it — or one that
+// didn't say — is sent an UpgradeRequired frame, JSON {min_version, version, download_url}, and
+// closed before it can sign in. Each signed-in user's last version is kept on their account, and
+// `get_client_versions` shows how the versions are spread, so the admin can tell when it's safe
+// to raise the minimum.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::{frame, Message, MessageType};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tauri::State;
+
+const SETTINGS_KEY: &str = "client_version_gate";
+
+/// This build's version, as sent in the handshake.
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const HELLO_PREFIX: &str = "nutler/";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct VersionGate {
+    // The oldest client version let in, e.g. "0.5.0"; None lets every version in.
+    #[serde(default)]
+    pub min_version: Option<String>,
+    // Where to get a newer build, shown to turned-away clients.
+    #[serde(default)]
+    pub download_url: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, sqlx::FromRow)]
+pub struct VersionCount {
+    pub version: String,
+    pub users: i64,
+    pub last_seen: String,
+}
+
+#[derive(Serialize)]
+struct UpgradeHint<'a> {
+    min_version: &'a str,
+    version: Option<&'a str>,
+    download_url: Option<&'a str>,
+}
+
+/// Our handshake hello, with the codecs we can inflate appended (see compression.rs).
+pub fn hello() -> Vec<u8> {
+    let offer = crate::compression::offer();
+    if offer.is_empty() {
+        format!("{}{}", HELLO_PREFIX, APP_VERSION).into_bytes()
+    } else {
+        format!("{}{}+{}", HELLO_PREFIX, APP_VERSION, offer).into_bytes()
+    }
+}
+
+/// The version a peer's hello names, if it names one.
+pub fn version_from_hello(hello: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(hello).ok()?;
+    let version = text.strip_prefix(HELLO_PREFIX)?.split('+').next()?.trim();
+    (!version.is_empty() && version.len() <= 32).then(|| version.to_string())
+}
+
+/// "1.2.3" (or "1.2", "1.2.3-beta") as comparable numbers; pre-release tags are ignored.
+fn parse(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.trim().trim_start_matches('v');
+    let core = core.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    parts.next().is_none().then_some((major, minor, patch))
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<VersionGate> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt version gate: {}", e))),
+        None => Ok(VersionGate::default()),
+    }
+}
+
+/// Whether `version` (None = the client didn't say) gets past `gate`.
+fn allowed(gate: &VersionGate, version: Option<&str>) -> bool {
+    let Some(min) = gate.min_version.as_deref().and_then(parse) else {
+        return true;
+    };
+    version.and_then(parse).is_some_and(|v| v >= min)
+}
+
+/// Host: the UpgradeRequired frame to turn a client on `version` away with, if the gate does.
+pub(crate) async fn check(pool: &SqlitePool, version: Option<&str>) -> Option<Message> {
+    let gate = match load_config(pool).await {
+        Ok(gate) => gate,
+        Err(e) => {
+            tracing::warn!("{}", e);
+            return None;
+        }
+    };
+    if allowed(&gate, version) {
+        return None;
+    }
+    let hint = UpgradeHint {
+        min_version: gate.min_version.as_deref().unwrap_or_default(),
+        version,
+        download_url: gate.download_url.as_deref(),
+    };
+    Some(frame(
+        MessageType::UpgradeRequired,
+        0,
+        serde_json::to_string(&hint).unwrap_or_default(),
+    ))
+}
+
+/// Host: note the version a signed-in user connected with.
+pub(crate) async fn record(pool: &SqlitePool, user_id: u64, version: Option<&str>) {
+    let result = sqlx::query(
+        "UPDATE users SET app_version = $1, app_version_seen_at = CURRENT_TIMESTAMP WHERE id = $2",
+    )
+    .bind(version.unwrap_or("unknown"))
+    .bind(user_id as i64)
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("Couldn't record client version: {}", e);
+    }
+}
+
+/// How many users last connected with each version, newest version first.
+pub async fn version_counts_internal(pool: &SqlitePool) -> AppResult<Vec<VersionCount>> {
+    let mut counts: Vec<VersionCount> = sqlx::query_as(
+        "SELECT app_version AS version, COUNT(*) AS users,
+                MAX(app_version_seen_at) AS last_seen
+         FROM users WHERE app_version IS NOT NULL
+         GROUP BY app_version",
+    )
+    .fetch_all(pool)
+    .await?;
+    counts.sort_by_key(|c| std::cmp::Reverse(parse(&c.version)));
+    Ok(counts)
+}
+
+#[tauri::command]
+pub async fn get_version_gate(db: State<'_, SqlitePool>) -> AppResult<VersionGate> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_version_gate(db: State<'_, SqlitePool>, config: VersionGate) -> AppResult<()> {
+    if let Some(min) = &config.min_version {
+        if parse(min).is_none() {
+            return Err(AppError::Validation(format!(
+                "\"{}\" isn't a version like 1.2.3",
+                min
+            )));
+        }
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[tauri::command]
+pub async fn get_client_versions(db: State<'_, SqlitePool>) -> AppResult<Vec<VersionCount>> {
+    version_counts_internal(&db).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[test]
+    fn versions_below_the_minimum_are_turned_away() {
+        assert_eq!(version_from_hello(&hello()).as_deref(), Some(APP_VERSION));
+        assert_eq!(
+            version_from_hello(b"nutler/0.6.0+zstd").as_deref(),
+            Some("0.6.0")
+        );
+        assert_eq!(version_from_hello(b""), None);
+        assert_eq!(parse("v1.2"), Some((1, 2, 0)));
+        assert_eq!(parse("0.10.1-beta"), Some((0, 10, 1)));
+        assert_eq!(parse("1.x"), None);
+
+        let gate = VersionGate {
+            min_version: Some("0.5.0".to_string()),
+            download_url: None,
+        };
+        assert!(allowed(&gate, Some("0.5.0")));
+        assert!(allowed(&gate, Some("0.10.0")));
+        assert!(!allowed(&gate, Some("0.4.9")));
+        assert!(!allowed(&gate, None));
+        assert!(allowed(&VersionGate::default(), None));
+    }
+
+    #[tokio::test]
+    async fn versions_are_counted_per_user() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email) VALUES (1, 'A', 'a@x'), (2, 'B', 'b@x'),
+                                                        (3, 'C', 'c@x');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        record(&pool, 1, Some("0.4.0")).await;
+        record(&pool, 2, Some("0.10.0")).await;
+        record(&pool, 3, Some("0.4.0")).await;
+        record(&pool, 3, Some("0.10.0")).await;
+
+        let counts: Vec<_> = version_counts_internal(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.version, c.users))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("0.10.0".to_string(), 2), ("0.4.0".to_string(), 1)]
+        );
+    }
+}
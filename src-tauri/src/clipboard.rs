@@ -0,0 +1,69 @@
+// Pasting an image (a screenshot, say) from the OS clipboard into a room.
+//
+// The webview only sees pasted images as blobs, with no path to hand send_attachment, so the
+// clipboard is read here instead: its RGBA pixels are encoded as a PNG in a temporary file,
+// which is shared like any other image attachment and then removed.
+
+use crate::attachments;
+use crate::error::{AppError, AppResult};
+use crate::sockets::{now_secs, AppState};
+use sqlx::SqlitePool;
+use std::io::Cursor;
+use std::sync::Arc;
+use tauri::State;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use uuid::Uuid;
+
+/// Encode raw RGBA pixels as a PNG.
+fn encode_png(rgba: &[u8], width: u32, height: u32) -> AppResult<Vec<u8>> {
+    let image = image::RgbaImage::from_raw(width, height, rgba.to_vec())
+        .ok_or_else(|| AppError::Validation("The clipboard image is malformed".to_string()))?;
+    let mut png = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| AppError::Internal(format!("Couldn't encode the image: {}", e)))?;
+    Ok(png.into_inner())
+}
+
+/// Share the image on the clipboard in a room, as "pasted-<time>.png". Fails if the clipboard
+/// holds no image. Returns the file name it was shared under.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn paste_image_from_clipboard(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: u64,
+) -> AppResult<String> {
+    let png = {
+        let image = app
+            .clipboard()
+            .read_image()
+            .map_err(|_| AppError::Validation("There's no image on the clipboard".to_string()))?;
+        encode_png(image.rgba(), image.width(), image.height())?
+    };
+    let filename = format!("pasted-{}.png", now_secs());
+    let path = std::env::temp_dir().join(format!("nutler-paste-{}.png", Uuid::new_v4()));
+    tokio::fs::write(&path, &png)
+        .await
+        .map_err(|e| AppError::Internal(format!("Couldn't save the pasted image: {}", e)))?;
+    let shared =
+        attachments::share_file(&app, state.inner(), &db, room_id, &path, filename.clone()).await;
+    let _ = tokio::fs::remove_file(&path).await;
+    shared.map(|()| filename)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipboard_pixels_become_a_png() {
+        let red = [255u8, 0, 0, 255].repeat(6);
+        let png = encode_png(&red, 3, 2).unwrap();
+        let decoded = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(decoded.dimensions(), (3, 2));
+        assert_eq!(decoded.get_pixel(2, 1).0, [255, 0, 0, 255]);
+        // Fewer bytes than the size claims.
+        assert!(encode_png(&red, 4, 4).is_err());
+    }
+}
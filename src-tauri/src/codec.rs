@@ -0,0 +1,222 @@
+// Length-prefixed framing.
+//
+// Everything on a Nutler connection (the Noise handshake, then each encrypted frame) goes as a
+// 4-byte big-endian length followed by that many bytes. A zero length is a keep-alive with
+// nothing after it. FramedReader and FramedWriter are the only code that reads or writes the
+// header, so the size cap lives in one place and holds both ways: a frame over the limit is
+// refused on write, and on read before anything is allocated for it. The async methods take
+// tokio halves; the `_blocking` ones take std::io readers and writers.
+//
+// A writer can be given a timeout (`with_timeout`): a write the peer doesn't take in time fails
+// with TimedOut, and so does every write after it, as the peer may be left with half a frame.
+
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Largest frame payload either side accepts, unless a reader or writer is given its own.
+pub const MAX_FRAME_BYTES: usize = 10 * 1024 * 1024;
+
+/// The header of a frame of `len` bytes, or an error if it's over `max`.
+fn header(len: usize, max: usize) -> io::Result<[u8; 4]> {
+    match u32::try_from(len) {
+        Ok(n) if len <= max => Ok(n.to_be_bytes()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Frame too large: {} bytes (max {})", len, max),
+        )),
+    }
+}
+
+/// The payload length a header announces, or an error if it's over `max`.
+fn payload_len(header: [u8; 4], max: usize) -> io::Result<usize> {
+    let len = u32::from_be_bytes(header) as usize;
+    if len > max {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Frame too large: {} bytes (max {})", len, max),
+        ));
+    }
+    Ok(len)
+}
+
+pub struct FramedReader<R> {
+    inner: R,
+    max: usize,
+}
+
+impl<R> FramedReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_max(inner, MAX_FRAME_BYTES)
+    }
+
+    pub fn with_max(inner: R, max: usize) -> Self {
+        Self { inner, max }
+    }
+}
+
+impl<R: AsyncRead + Unpin> FramedReader<R> {
+    /// The next frame's payload; `None` for a keep-alive.
+    pub async fn read_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut head = [0u8; 4];
+        self.inner.read_exact(&mut head).await?;
+        let len = payload_len(head, self.max)?;
+        if len == 0 {
+            return Ok(None);
+        }
+        let mut buf = vec![0u8; len];
+        self.inner.read_exact(&mut buf).await?;
+        Ok(Some(buf))
+    }
+}
+
+impl<R: Read> FramedReader<R> {
+    pub fn read_frame_blocking(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut head = [0u8; 4];
+        self.inner.read_exact(&mut head)?;
+        let len = payload_len(head, self.max)?;
+        if len == 0 {
+            return Ok(None);
+        }
+        let mut buf = vec![0u8; len];
+        self.inner.read_exact(&mut buf)?;
+        Ok(Some(buf))
+    }
+}
+
+pub struct FramedWriter<W> {
+    inner: W,
+    max: usize,
+    timeout: Option<Duration>,
+    // Set once a write timed out.
+    timed_out: bool,
+}
+
+impl<W> FramedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_max(inner, MAX_FRAME_BYTES)
+    }
+
+    pub fn with_max(inner: W, max: usize) -> Self {
+        Self {
+            inner,
+            max,
+            timeout: None,
+            timed_out: false,
+        }
+    }
+
+    /// Fail (async) writes that take longer than `timeout`.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "The peer stopped taking writes")
+}
+
+impl<W: AsyncWrite + Unpin> FramedWriter<W> {
+    pub async fn write_frame(&mut self, payload: &[u8]) -> io::Result<()> {
+        let head = header(payload.len(), self.max)?;
+        self.write_parts(&[&head[..], payload]).await
+    }
+
+    pub async fn write_keepalive(&mut self) -> io::Result<()> {
+        self.write_parts(&[&0u32.to_be_bytes()[..]]).await
+    }
+
+    async fn write_parts(&mut self, parts: &[&[u8]]) -> io::Result<()> {
+        if self.timed_out {
+            return Err(timed_out());
+        }
+        let inner = &mut self.inner;
+        let write = async {
+            for part in parts {
+                inner.write_all(part).await?;
+            }
+            Ok::<_, io::Error>(())
+        };
+        match self.timeout {
+            Some(limit) => match tokio::time::timeout(limit, write).await {
+                Ok(result) => result,
+                Err(_) => {
+                    self.timed_out = true;
+                    Err(timed_out())
+                }
+            },
+            None => write.await,
+        }
+    }
+
+    pub async fn shutdown(&mut self) -> io::Result<()> {
+        self.inner.shutdown().await
+    }
+}
+
+impl<W: Write> FramedWriter<W> {
+    pub fn write_frame_blocking(&mut self, payload: &[u8]) -> io::Result<()> {
+        let head = header(payload.len(), self.max)?;
+        self.inner.write_all(&head)?;
+        self.inner.write_all(payload)
+    }
+
+    pub fn write_keepalive_blocking(&mut self) -> io::Result<()> {
+        self.inner.write_all(&0u32.to_be_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocking_frames_round_trip_within_the_limit() {
+        let mut wire = Vec::new();
+        let mut writer = FramedWriter::with_max(&mut wire, 8);
+        writer.write_frame_blocking(b"hello").unwrap();
+        writer.write_keepalive_blocking().unwrap();
+        let err = writer.write_frame_blocking(b"too long!").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(wire, b"\0\0\0\x05hello\0\0\0\0");
+
+        let mut reader = FramedReader::with_max(wire.as_slice(), 8);
+        assert_eq!(reader.read_frame_blocking().unwrap().unwrap(), b"hello");
+        assert_eq!(reader.read_frame_blocking().unwrap(), None);
+        assert!(reader.read_frame_blocking().is_err());
+
+        // A header over the limit is refused before its payload is read.
+        let mut reader = FramedReader::with_max(&b"\0\0\0\x09too long!"[..], 8);
+        let err = reader.read_frame_blocking().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn async_frames_round_trip() {
+        let (a, b) = tokio::io::duplex(1024);
+        let mut writer = FramedWriter::new(a);
+        let mut reader = FramedReader::new(b);
+        writer.write_frame(b"{\"x\":1}").await.unwrap();
+        writer.write_keepalive().await.unwrap();
+        writer.shutdown().await.unwrap();
+        assert_eq!(reader.read_frame().await.unwrap().unwrap(), b"{\"x\":1}");
+        assert_eq!(reader.read_frame().await.unwrap(), None);
+        assert!(reader.read_frame().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn a_write_the_peer_never_takes_times_out_for_good() {
+        let (a, _b) = tokio::io::duplex(8);
+        let mut writer = FramedWriter::new(a).with_timeout(Duration::from_millis(50));
+        let err = writer
+            .write_frame(b"more than eight bytes")
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        let err = writer.write_keepalive().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+}
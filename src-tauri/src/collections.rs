@@ -0,0 +1,289 @@
+// Bookmark collections.
+//
+// Messages can be saved into named collections ("Onboarding links", "Incident 2024-05") and read
+// back one collection at a time. Like reminders they're personal and kept in this machine's own
+// database; a client has no copy of the room's history, so the UI passes the room, author and
+// an excerpt along with the message id. A message can be in several collections, once in each,
+// and deleting a collection drops its bookmarks.
+
+use crate::error::{AppError, AppResult};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tauri::State;
+
+/// Longest collection name.
+const MAX_NAME_CHARS: usize = 60;
+
+/// Longest message excerpt kept with a bookmark.
+const PREVIEW_CHARS: usize = 280;
+
+#[derive(Serialize, Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    // Bookmarks in it.
+    pub count: i64,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct Bookmark {
+    pub collection_id: i64,
+    pub message_id: String,
+    pub room_id: i64,
+    pub room: String,
+    pub author: String,
+    pub preview: String,
+    pub added_at: String,
+}
+
+pub async fn create_collection_internal(pool: &SqlitePool, name: &str) -> AppResult<Collection> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::Validation(
+            "Give the collection a name".to_string(),
+        ));
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(AppError::Validation(format!(
+            "Collection names can be at most {} characters",
+            MAX_NAME_CHARS
+        )));
+    }
+    let taken: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM bookmark_collections WHERE name = $1")
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+    if taken.is_some() {
+        return Err(AppError::Validation(format!(
+            "There's already a collection called \"{}\"",
+            name
+        )));
+    }
+    Ok(sqlx::query_as(
+        "INSERT INTO bookmark_collections (name) VALUES ($1)
+         RETURNING id, name, 0 AS count, created_at",
+    )
+    .bind(name)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Every collection, by name.
+pub async fn list_collections_internal(pool: &SqlitePool) -> AppResult<Vec<Collection>> {
+    Ok(sqlx::query_as(
+        "SELECT c.id, c.name, COUNT(b.message_id) AS count, c.created_at
+         FROM bookmark_collections c LEFT JOIN bookmarks b ON b.collection_id = c.id
+         GROUP BY c.id ORDER BY c.name COLLATE NOCASE, c.id",
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+async fn require_collection(pool: &SqlitePool, collection_id: i64) -> AppResult<()> {
+    let known: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM bookmark_collections WHERE id = $1")
+            .bind(collection_id)
+            .fetch_optional(pool)
+            .await?;
+    match known {
+        Some(_) => Ok(()),
+        None => Err(AppError::Validation(
+            "That collection no longer exists".to_string(),
+        )),
+    }
+}
+
+/// Save a message into a collection. Saving it there again keeps the first bookmark.
+pub async fn add_to_collection_internal(
+    pool: &SqlitePool,
+    collection_id: i64,
+    message_id: &str,
+    room_id: i64,
+    room: &str,
+    author: &str,
+    preview: &str,
+) -> AppResult<()> {
+    if message_id.trim().is_empty() {
+        return Err(AppError::Validation("No message to bookmark".to_string()));
+    }
+    require_collection(pool, collection_id).await?;
+    let preview: String = preview.trim().chars().take(PREVIEW_CHARS).collect();
+    sqlx::query(
+        "INSERT INTO bookmarks (collection_id, message_id, room_id, room, author, preview)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT(collection_id, message_id) DO NOTHING",
+    )
+    .bind(collection_id)
+    .bind(message_id)
+    .bind(room_id)
+    .bind(room)
+    .bind(author)
+    .bind(&preview)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A collection's bookmarks, most recently added first.
+pub async fn get_collection_internal(
+    pool: &SqlitePool,
+    collection_id: i64,
+) -> AppResult<Vec<Bookmark>> {
+    require_collection(pool, collection_id).await?;
+    Ok(sqlx::query_as(
+        "SELECT collection_id, message_id, room_id, room, author, preview, added_at
+         FROM bookmarks WHERE collection_id = $1
+         ORDER BY added_at DESC, rowid DESC",
+    )
+    .bind(collection_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+#[tauri::command]
+pub async fn create_collection(db: State<'_, SqlitePool>, name: String) -> AppResult<Collection> {
+    create_collection_internal(&db, &name).await
+}
+
+#[tauri::command]
+pub async fn list_collections(db: State<'_, SqlitePool>) -> AppResult<Vec<Collection>> {
+    list_collections_internal(&db).await
+}
+
+/// Delete a collection and its bookmarks (not the messages).
+#[tauri::command(rename_all = "snake_case")]
+pub async fn delete_collection(db: State<'_, SqlitePool>, collection_id: i64) -> AppResult<()> {
+    require_collection(&db, collection_id).await?;
+    sqlx::query("DELETE FROM bookmark_collections WHERE id = $1")
+        .bind(collection_id)
+        .execute(db.inner())
+        .await?;
+    Ok(())
+}
+
+/// Bookmark a message in a collection. The room, author and excerpt come from the UI, which
+/// already has the message.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn add_to_collection(
+    db: State<'_, SqlitePool>,
+    collection_id: i64,
+    message_id: String,
+    room_id: i64,
+    room: String,
+    author: String,
+    preview: String,
+) -> AppResult<()> {
+    add_to_collection_internal(
+        &db,
+        collection_id,
+        &message_id,
+        room_id,
+        &room,
+        &author,
+        &preview,
+    )
+    .await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn remove_from_collection(
+    db: State<'_, SqlitePool>,
+    collection_id: i64,
+    message_id: String,
+) -> AppResult<()> {
+    sqlx::query("DELETE FROM bookmarks WHERE collection_id = $1 AND message_id = $2")
+        .bind(collection_id)
+        .bind(&message_id)
+        .execute(db.inner())
+        .await?;
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_collection(
+    db: State<'_, SqlitePool>,
+    collection_id: i64,
+) -> AppResult<Vec<Bookmark>> {
+    get_collection_internal(&db, collection_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::memory_pool;
+
+    #[tokio::test]
+    async fn messages_are_kept_per_collection() {
+        let pool = memory_pool().await;
+
+        let links = create_collection_internal(&pool, " Onboarding links ")
+            .await
+            .unwrap();
+        assert_eq!(links.name, "Onboarding links");
+        assert!(create_collection_internal(&pool, "onboarding LINKS")
+            .await
+            .is_err());
+        assert!(create_collection_internal(&pool, "  ").await.is_err());
+        let incident = create_collection_internal(&pool, "Incident 2024-05")
+            .await
+            .unwrap();
+
+        for (collection, message_id, preview) in [
+            (links.id, "m1", "Wiki: https://wiki.example/start"),
+            (links.id, "m2", "VPN setup guide"),
+            (links.id, "m1", "saved twice"),
+            (incident.id, "m1", "Wiki: https://wiki.example/start"),
+        ] {
+            add_to_collection_internal(
+                &pool,
+                collection,
+                message_id,
+                1,
+                "Company Wide",
+                "Alice",
+                preview,
+            )
+            .await
+            .unwrap();
+        }
+        assert!(
+            add_to_collection_internal(&pool, 999, "m3", 1, "Company Wide", "Alice", "")
+                .await
+                .is_err()
+        );
+
+        let saved = get_collection_internal(&pool, links.id).await.unwrap();
+        let mut ids: Vec<&str> = saved.iter().map(|b| b.message_id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["m1", "m2"]);
+        assert!(saved
+            .iter()
+            .all(|b| b.preview != "saved twice" && b.room == "Company Wide"));
+        let counts: Vec<(String, i64)> = list_collections_internal(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.name, c.count))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("Incident 2024-05".to_string(), 1),
+                ("Onboarding links".to_string(), 2)
+            ]
+        );
+
+        sqlx::query("DELETE FROM bookmark_collections WHERE id = $1")
+            .bind(links.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM bookmarks")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, 1);
+    }
+}
@@ -0,0 +1,243 @@
+// Per-message compression for large payloads.
+//
+// A client lists the codecs it can inflate in its handshake hello, after a `+` so older hosts
+// still read the version in front of it. The host picks the first one it also supports and
+// names it in its handshake reply, which older clients ignore. After that, either side
+// compresses a payload over COMPRESS_THRESHOLD with the agreed codec when that makes it
+// smaller. A compressed payload starts with a marker byte and the codec's id, so each frame
+// says for itself whether (and how) to inflate it.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::State;
+
+const SETTINGS_KEY: &str = "compression";
+
+/// Payloads at or below this size go as they are; chat lines wouldn't gain anything.
+const COMPRESS_THRESHOLD: usize = 4 * 1024;
+
+/// First byte of a compressed payload. Plain payloads are JSON objects, so they never start
+/// with it (nor with the fragment marker, 0x1e).
+const COMPRESSED_MARKER: u8 = 0x1f;
+
+/// Same ceiling as a single frame on the wire, so a tiny payload can't inflate without bound.
+const MAX_INFLATED_BYTES: usize = 10 * 1024 * 1024;
+
+/// zstd's default level: fast, and most of the gain on text.
+const ZSTD_LEVEL: i32 = 3;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Codec {
+    Zstd,
+    Deflate,
+}
+
+impl Codec {
+    /// Everything we can inflate, most preferred first.
+    const ALL: [Codec; 2] = [Codec::Zstd, Codec::Deflate];
+
+    fn name(self) -> &'static str {
+        match self {
+            Codec::Zstd => "zstd",
+            Codec::Deflate => "deflate",
+        }
+    }
+
+    fn id(self) -> u8 {
+        match self {
+            Codec::Zstd => 1,
+            Codec::Deflate => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Codec> {
+        Codec::ALL.into_iter().find(|c| c.id() == id)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CompressionConfig {
+    // Offer (as a client) and accept (as a host) compression in the handshake.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<CompressionConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt compression settings: {}", e))),
+        None => Ok(CompressionConfig::default()),
+    }
+}
+
+pub async fn load(pool: &SqlitePool) {
+    match load_config(pool).await {
+        Ok(config) => ENABLED.store(config.enabled, Ordering::Relaxed),
+        Err(e) => tracing::warn!("{}", e),
+    }
+}
+
+/// The codecs we offer in our hello, e.g. "zstd.deflate"; empty when compression is off.
+pub fn offer() -> String {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return String::new();
+    }
+    Codec::ALL.map(Codec::name).join(".")
+}
+
+/// Host: the codec to use with a peer, from the offer at the end of its hello.
+pub fn choose(hello: &[u8]) -> Option<Codec> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    let (_, offered) = std::str::from_utf8(hello).ok()?.split_once('+')?;
+    let offered: Vec<&str> = offered.split('.').collect();
+    Codec::ALL
+        .into_iter()
+        .find(|codec| offered.contains(&codec.name()))
+}
+
+/// The handshake reply naming `codec`; empty for none.
+pub fn answer(codec: Option<Codec>) -> &'static [u8] {
+    codec.map_or(&[], |codec| codec.name().as_bytes())
+}
+
+/// Client: the codec the host picked, from its handshake reply.
+pub fn accepted(reply: &[u8]) -> Option<Codec> {
+    Codec::ALL
+        .into_iter()
+        .find(|codec| codec.name().as_bytes() == reply)
+}
+
+/// Compress `payload` with the codec agreed with its recipient, if that's worth doing.
+pub fn compress(codec: Option<Codec>, payload: Vec<u8>) -> Vec<u8> {
+    let Some(codec) = codec else {
+        return payload;
+    };
+    if payload.len() <= COMPRESS_THRESHOLD {
+        return payload;
+    }
+    let packed = match codec {
+        Codec::Zstd => zstd::bulk::compress(&payload, ZSTD_LEVEL),
+        Codec::Deflate => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+            encoder.write_all(&payload).and_then(|_| encoder.finish())
+        }
+    };
+    match packed {
+        Ok(packed) if packed.len() + 2 < payload.len() => {
+            let mut out = Vec::with_capacity(packed.len() + 2);
+            out.push(COMPRESSED_MARKER);
+            out.push(codec.id());
+            out.extend_from_slice(&packed);
+            out
+        }
+        Ok(_) => payload,
+        Err(e) => {
+            tracing::warn!("{} compression failed: {}", codec.name(), e);
+            payload
+        }
+    }
+}
+
+fn inflate(reader: impl Read) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    reader
+        .take(MAX_INFLATED_BYTES as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| format!("decompress: {e}"))?;
+    if out.len() > MAX_INFLATED_BYTES {
+        return Err("compressed payload inflates too large".to_string());
+    }
+    Ok(out)
+}
+
+/// Undo `compress`; plain payloads pass through.
+pub fn decompress(payload: Vec<u8>) -> Result<Vec<u8>, String> {
+    if payload.first() != Some(&COMPRESSED_MARKER) {
+        return Ok(payload);
+    }
+    let codec = payload
+        .get(1)
+        .copied()
+        .and_then(Codec::from_id)
+        .ok_or("unknown compression codec")?;
+    let packed = &payload[2..];
+    match codec {
+        Codec::Zstd => {
+            inflate(zstd::stream::read::Decoder::new(packed).map_err(|e| e.to_string())?)
+        }
+        Codec::Deflate => inflate(DeflateDecoder::new(packed)),
+    }
+}
+
+#[tauri::command]
+pub async fn get_compression_config(db: State<'_, SqlitePool>) -> AppResult<CompressionConfig> {
+    load_config(&db).await
+}
+
+/// Takes effect for connections made after the change.
+#[tauri::command]
+pub async fn set_compression_config(
+    db: State<'_, SqlitePool>,
+    config: CompressionConfig,
+) -> AppResult<()> {
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    ENABLED.store(config.enabled, Ordering::Relaxed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codecs_are_negotiated_and_round_trip() {
+        assert_eq!(choose(b"nutler/0.5.0+zstd.deflate"), Some(Codec::Zstd));
+        assert_eq!(choose(b"nutler/0.5.0+deflate"), Some(Codec::Deflate));
+        assert_eq!(choose(b"nutler/0.5.0"), None);
+        assert_eq!(accepted(answer(Some(Codec::Deflate))), Some(Codec::Deflate));
+        assert_eq!(accepted(answer(None)), None);
+
+        let log = "2026-10-17 12:00:00 INFO request served in 3ms\n"
+            .repeat(500)
+            .into_bytes();
+        for codec in Codec::ALL {
+            let packed = compress(Some(codec), log.clone());
+            assert!(
+                packed.len() < log.len() / 4,
+                "{} barely shrank",
+                codec.name()
+            );
+            assert_eq!(decompress(packed).unwrap(), log);
+        }
+        // Small payloads, and peers without a codec, get them as they are.
+        assert_eq!(compress(Some(Codec::Zstd), b"{}".to_vec()), b"{}");
+        assert_eq!(compress(None, log.clone()), log);
+        assert_eq!(decompress(log.clone()).unwrap(), log);
+    }
+}
@@ -0,0 +1,183 @@
+// Connection timeline (host-side).
+//
+// The host records each client connection's lifecycle against the user's canonical id: a
+// connect, a reconnect (a Resume frame, or a fresh Connect while an earlier connection was still
+// live or parked), a rejected sign-in, read errors and timeouts, and the disconnect with its
+// reason. `get_connection_timeline` returns a user's recent events, newest first, so "I keep
+// getting dropped" can be answered from data. Only the latest KEEP_PER_USER events are kept.
+
+use crate::db_queries::normalize_email;
+use crate::error::AppResult;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tauri::State;
+
+pub const CONNECT: &str = "connect";
+pub const RECONNECT: &str = "reconnect";
+pub const DISCONNECT: &str = "disconnect";
+pub const ERROR: &str = "error";
+
+/// Older events beyond this many per user are pruned as new ones arrive.
+const KEEP_PER_USER: i64 = 500;
+const DEFAULT_LIMIT: u32 = 100;
+
+#[derive(Serialize, Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct ConnectionEvent {
+    pub id: i64,
+    pub user_id: i64,
+    pub event: String,
+    // The peer's address as the host saw it.
+    pub peer: Option<String>,
+    // Why (an error, a disconnect reason) or how (e.g. "resumed").
+    pub detail: Option<String>,
+    pub created_at: String,
+}
+
+pub async fn record_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    event: &str,
+    peer: Option<&str>,
+    detail: Option<&str>,
+) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO connection_log (user_id, event, peer, detail) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(user_id)
+    .bind(event)
+    .bind(peer)
+    .bind(detail)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to record connection event: {}", e))?;
+    sqlx::query(
+        "DELETE FROM connection_log
+         WHERE user_id = $1
+           AND id <= (SELECT id FROM connection_log WHERE user_id = $1
+                      ORDER BY id DESC LIMIT 1 OFFSET $2)",
+    )
+    .bind(user_id)
+    .bind(KEEP_PER_USER)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to prune the connection log: {}", e))?;
+    Ok(())
+}
+
+/// Record an event, logging (not failing) if it can't be stored: diagnostics must never get in
+/// the way of the connection itself.
+pub(crate) async fn record(
+    pool: &SqlitePool,
+    user_id: u64,
+    event: &str,
+    peer: &std::net::SocketAddr,
+    detail: Option<&str>,
+) {
+    let peer = peer.to_string();
+    if let Err(e) = record_internal(pool, user_id as i64, event, Some(&peer), detail).await {
+        tracing::warn!("{}", e);
+    }
+}
+
+/// Record a rejected sign-in against the account it was for, if the email is one we know.
+pub(crate) async fn record_rejection(
+    pool: &SqlitePool,
+    email: Option<&str>,
+    peer: &std::net::SocketAddr,
+    reason: &str,
+) {
+    let Some(email) = email else {
+        return;
+    };
+    let user_id: Option<i64> = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+        .bind(normalize_email(email))
+        .fetch_optional(pool)
+        .await
+        .unwrap_or(None);
+    if let Some(id) = user_id {
+        let detail = format!("sign-in rejected: {}", reason);
+        record(pool, id as u64, ERROR, peer, Some(&detail)).await;
+    }
+}
+
+pub async fn get_connection_timeline_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    limit: u32,
+) -> AppResult<Vec<ConnectionEvent>> {
+    Ok(sqlx::query_as(
+        "SELECT id, user_id, event, peer, detail, created_at FROM connection_log
+         WHERE user_id = $1 ORDER BY id DESC LIMIT $2",
+    )
+    .bind(user_id)
+    .bind(i64::from(limit))
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Host: a user's recent connection events, newest first (100 unless `limit` says otherwise).
+#[tauri::command]
+pub async fn get_connection_timeline(
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+    limit: Option<u32>,
+) -> AppResult<Vec<ConnectionEvent>> {
+    get_connection_timeline_internal(&db, user_id, limit.unwrap_or(DEFAULT_LIMIT).min(1000)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn timeline_is_per_user_newest_first_and_bounded() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let peer: std::net::SocketAddr = "10.0.0.7:5123".parse().unwrap();
+        record(&pool, 1, CONNECT, &peer, None).await;
+        record(&pool, 2, CONNECT, &peer, None).await;
+        record(&pool, 1, ERROR, &peer, Some("read timeout")).await;
+        record(&pool, 1, DISCONNECT, &peer, Some("dropped")).await;
+        record_rejection(&pool, Some("a@x"), &peer, "bad password").await;
+        record_rejection(&pool, Some("nobody@x"), &peer, "bad password").await;
+
+        let events = get_connection_timeline_internal(&pool, 1, 10)
+            .await
+            .unwrap();
+        let kinds: Vec<_> = events.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(kinds, [ERROR, DISCONNECT, ERROR, CONNECT]);
+        assert_eq!(
+            events[0].detail.as_deref(),
+            Some("sign-in rejected: bad password")
+        );
+        assert_eq!(events[0].peer.as_deref(), Some("10.0.0.7:5123"));
+        assert_eq!(
+            get_connection_timeline_internal(&pool, 1, 2)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        for _ in 0..KEEP_PER_USER {
+            record(&pool, 1, CONNECT, &peer, None).await;
+        }
+        let kept: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM connection_log WHERE user_id = 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(kept, KEEP_PER_USER);
+    }
+}
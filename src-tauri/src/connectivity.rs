@@ -0,0 +1,141 @@
+// Keeping a client connection healthy on networks that change under it.
+//
+// Phones (and laptops) move between Wi-Fi and cellular, sleep, and get backgrounded. Three
+// things help:
+// - the client's heartbeat is slower on mobile builds, so the radio can sleep between beats
+//   (the host waits long enough for ~3 missed phone heartbeats before dropping a client);
+// - the local addresses are watched, and when they change the old connection is given up at
+//   once (its socket is bound to an address that's gone) rather than after a read timeout, and
+//   the UI is told (`network_changed`) so a reconnect backing off can retry right away;
+// - on mobile, the heartbeat stops while the app is in the background, and on coming back the
+//   connection is either picked up again or, if the host will have given up on it by then,
+//   dropped so the UI reconnects (resuming where it left off) without waiting.
+
+use crate::sockets::{self, AppState};
+#[cfg(mobile)]
+use crate::wake;
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Emitter;
+#[cfg(mobile)]
+use tauri::Manager;
+
+/// Seconds between client heartbeats on a desktop, and on a phone.
+const DESKTOP_HEARTBEAT_SECS: u64 = 15;
+pub(crate) const MOBILE_HEARTBEAT_SECS: u64 = 30;
+
+/// How often the local addresses are checked.
+const NETWORK_POLL: Duration = if cfg!(mobile) {
+    Duration::from_secs(10)
+} else {
+    Duration::from_secs(5)
+};
+
+/// The client's heartbeat interval on this platform.
+pub(crate) fn client_heartbeat_interval() -> Duration {
+    Duration::from_secs(if cfg!(mobile) {
+        MOBILE_HEARTBEAT_SECS
+    } else {
+        DESKTOP_HEARTBEAT_SECS
+    })
+}
+
+/// The machine's non-loopback addresses.
+fn local_addresses() -> BTreeSet<IpAddr> {
+    if_addrs::get_if_addrs()
+        .map(|addrs| {
+            addrs
+                .into_iter()
+                .filter(|i| !i.is_loopback())
+                .map(|i| i.ip())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether going from `before` to `after` should drop the client connection: an address went
+/// away (gaining one, e.g. a VPN coming up, leaves the old route working).
+fn lost_an_address(before: &BTreeSet<IpAddr>, after: &BTreeSet<IpAddr>) -> bool {
+    !before.is_subset(after)
+}
+
+/// Watch the local addresses for the life of the app.
+pub fn spawn_network_watch(app: tauri::AppHandle, state: Arc<AppState>) {
+    tauri::async_runtime::spawn(async move {
+        let mut known = local_addresses();
+        let mut ticker = tokio::time::interval(NETWORK_POLL);
+        loop {
+            ticker.tick().await;
+            let now = local_addresses();
+            if now == known {
+                continue;
+            }
+            tracing::info!("🌐 Network changed: {:?} → {:?}", known, now);
+            if lost_an_address(&known, &now) {
+                sockets::abandon_client_connection(&app, &state, "the network changed").await;
+            }
+            known = now;
+            let _ = app.emit("network_changed", ());
+        }
+    });
+}
+
+/// The app went to the background (mobile): stop the heartbeat so the radio can sleep, and
+/// poll for new messages instead (see wake.rs).
+#[cfg(mobile)]
+pub fn on_suspend(app: &tauri::AppHandle) {
+    let state = Arc::clone(app.state::<Arc<AppState>>().inner());
+    tauri::async_runtime::spawn(async move {
+        *state.suspended_at.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some(tokio::time::Instant::now());
+        sockets::stop_client_heartbeat(&state).await;
+        wake::request_token(&state).await;
+    });
+}
+
+/// The app is back in the foreground (mobile): carry on if the host will still have us and
+/// nothing new came in meanwhile, otherwise drop the connection so the UI reconnects (resuming)
+/// now.
+#[cfg(mobile)]
+pub fn on_resume(app: &tauri::AppHandle) {
+    let app = app.clone();
+    let state = Arc::clone(app.state::<Arc<AppState>>().inner());
+    tauri::async_runtime::spawn(async move {
+        let away = state
+            .suspended_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .map(|at| at.elapsed());
+        let woken = wake::stop(&state);
+        if woken || away.is_some_and(|away| away >= sockets::CLIENT_READ_TIMEOUT) {
+            sockets::abandon_client_connection(&app, &state, "back from the background").await;
+        } else {
+            sockets::restart_client_heartbeat(&state).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_losing_an_address_drops_the_connection() {
+        let wifi: IpAddr = "192.168.1.20".parse().unwrap();
+        let vpn: IpAddr = "10.8.0.2".parse().unwrap();
+        let cell: IpAddr = "100.64.3.9".parse().unwrap();
+        let before = BTreeSet::from([wifi]);
+        assert!(!lost_an_address(&before, &BTreeSet::from([wifi, vpn])));
+        assert!(lost_an_address(&before, &BTreeSet::from([cell])));
+        assert!(lost_an_address(&before, &BTreeSet::new()));
+    }
+
+    #[test]
+    fn the_host_outlasts_a_phones_heartbeat() {
+        assert!(sockets::CLIENT_READ_TIMEOUT >= Duration::from_secs(2 * MOBILE_HEARTBEAT_SECS));
+        assert!(client_heartbeat_interval() < sockets::CLIENT_READ_TIMEOUT);
+    }
+}
@@ -0,0 +1,445 @@
+// User profiles and contact cards.
+//
+// The host's `users` rows carry contact details beyond a name and email: job title, phone,
+// avatar (a data: URL) and timezone (a UTC offset). `get_user_profile` returns them with the
+// person's department and current local time; on a client it asks the host (ProfileRequest,
+// answered by a Profile frame carrying the same `message_id`), since clients keep no copy of the
+// directory. A Contact message passes a colleague's card along in a room: the sender names the
+// user, and the host fills the card in from its own directory before relaying it, so a card
+// can't be forged.
+
+use crate::error::{AppError, AppResult};
+use crate::sockets::{
+    frame, now_secs, publish_host_message, send_secure_client, send_to_client, server_now_secs,
+    AppState, Message, MessageType,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+/// How long a client waits for the host's Profile answer.
+const PROFILE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest title / phone number kept.
+const MAX_FIELD_CHARS: usize = 100;
+
+/// Largest avatar (characters of data: URL): still fits one Noise frame with the rest of the
+/// profile.
+const MAX_AVATAR_CHARS: usize = 48 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UserProfile {
+    pub id: i64,
+    pub name: String,
+    pub email: Option<String>,
+    pub title: Option<String>,
+    pub phone: Option<String>,
+    pub department_id: Option<i64>,
+    pub department: Option<String>,
+    pub avatar: Option<String>,
+    // A UTC offset, e.g. "+05:30".
+    pub timezone: Option<String>,
+    // "HH:MM" where they are, when their timezone is known.
+    pub local_time: Option<String>,
+}
+
+/// The editable part of a profile, as `set_user_profile` takes it.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct ProfileFields {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub phone: Option<String>,
+    #[serde(default)]
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+/// A Contact message's `message`. Only `user_id` is read from the sender; the host fills in
+/// the rest.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ContactCard {
+    pub user_id: i64,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub department: Option<String>,
+}
+
+/// Minutes east of UTC for "+HH:MM" / "-HH:MM" (or "UTC" / "Z"), optionally after "UTC".
+fn parse_utc_offset(timezone: &str) -> Option<i64> {
+    let tz = timezone.trim();
+    let tz = tz.strip_prefix("UTC").unwrap_or(tz);
+    if tz.is_empty() || tz == "Z" {
+        return Some(0);
+    }
+    let (sign, rest) = match tz.as_bytes()[0] {
+        b'+' => (1, &tz[1..]),
+        b'-' => (-1, &tz[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let (hours, minutes): (i64, i64) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// The wall-clock time ("HH:MM") at `timezone` when it's `now` (unix seconds).
+fn local_time(timezone: &str, now: u64) -> Option<String> {
+    let offset = parse_utc_offset(timezone)?;
+    let minutes = (now as i64 / 60 + offset).rem_euclid(24 * 60);
+    Some(format!("{:02}:{:02}", minutes / 60, minutes % 60))
+}
+
+fn trimmed(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+pub async fn get_user_profile_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> AppResult<Option<UserProfile>> {
+    let row = sqlx::query(
+        "SELECT u.id, u.name, u.email, u.title, u.phone, u.department_id, d.name AS department,
+                u.avatar, u.timezone
+         FROM users u LEFT JOIN departments d ON d.id = u.department_id
+         WHERE u.id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| {
+        let timezone: Option<String> = r.get("timezone");
+        UserProfile {
+            id: r.get("id"),
+            name: r.get("name"),
+            email: r.get("email"),
+            title: r.get("title"),
+            phone: r.get("phone"),
+            department_id: r.get("department_id"),
+            department: r.get("department"),
+            avatar: r.get("avatar"),
+            local_time: timezone
+                .as_deref()
+                .and_then(|tz| local_time(tz, now_secs())),
+            timezone,
+        }
+    }))
+}
+
+pub async fn set_user_profile_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    fields: ProfileFields,
+) -> AppResult<()> {
+    let title = trimmed(fields.title);
+    let phone = trimmed(fields.phone);
+    let avatar = trimmed(fields.avatar);
+    let timezone = trimmed(fields.timezone);
+    for (what, value) in [("title", &title), ("phone number", &phone)] {
+        if value
+            .as_ref()
+            .is_some_and(|v| v.chars().count() > MAX_FIELD_CHARS)
+        {
+            return Err(AppError::Validation(format!(
+                "The {} is longer than {} characters",
+                what, MAX_FIELD_CHARS
+            )));
+        }
+    }
+    if let Some(avatar) = &avatar {
+        if !avatar.starts_with("data:image/") {
+            return Err(AppError::Validation(
+                "The avatar must be an image".to_string(),
+            ));
+        }
+        if avatar.len() > MAX_AVATAR_CHARS {
+            return Err(AppError::Validation(
+                "The avatar image is too large".to_string(),
+            ));
+        }
+    }
+    if let Some(tz) = &timezone {
+        if parse_utc_offset(tz).is_none() {
+            return Err(AppError::Validation(format!(
+                "{} isn't a UTC offset like +02:00",
+                tz
+            )));
+        }
+    }
+    let updated = sqlx::query(
+        "UPDATE users SET title = $1, phone = $2, avatar = $3, timezone = $4 WHERE id = $5",
+    )
+    .bind(title)
+    .bind(phone)
+    .bind(avatar)
+    .bind(timezone)
+    .bind(user_id)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Err(AppError::Validation(format!("No user {}", user_id)));
+    }
+    Ok(())
+}
+
+/// Host: a Contact message's card, filled in from this host's directory. `None` if it doesn't
+/// name a known user.
+pub async fn resolve_card(pool: &SqlitePool, message: &str) -> Option<String> {
+    let card: ContactCard = serde_json::from_str(message).ok()?;
+    let profile = get_user_profile_internal(pool, card.user_id)
+        .await
+        .ok()
+        .flatten()?;
+    serde_json::to_string(&ContactCard {
+        user_id: profile.id,
+        name: profile.name,
+        email: profile.email,
+        title: profile.title,
+        phone: profile.phone,
+        department: profile.department,
+    })
+    .ok()
+}
+
+/// A Contact message as plain text (notifications, digests).
+pub(crate) fn preview(message: &str) -> String {
+    match serde_json::from_str::<ContactCard>(message) {
+        Ok(card) if !card.name.is_empty() => format!("[Contact: {}]", card.name),
+        _ => "[Contact]".to_string(),
+    }
+}
+
+/// Host: answer a client's ProfileRequest (`message` = the user id) with a Profile frame under
+/// the same `message_id`; its `message` is the profile's JSON, or empty if there's no such user.
+pub(crate) async fn answer_request(
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    requester: u64,
+    request: &Message,
+) {
+    let profile = match request.message.parse::<i64>() {
+        Ok(id) => get_user_profile_internal(pool, id)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Profile lookup failed: {}", e);
+                None
+            }),
+        Err(_) => None,
+    };
+    let json = profile
+        .and_then(|p| serde_json::to_string(&p).ok())
+        .unwrap_or_default();
+    let mut reply = frame(MessageType::Profile, requester, json);
+    reply.message_id = request.message_id.clone();
+    send_to_client(state, requester, &reply).await;
+}
+
+/// Client: hand a Profile frame from the primary connection's host to whoever asked for it.
+pub(crate) async fn handle_host_frame(state: &Arc<AppState>, message: &Message) {
+    if let Some(tx) = state
+        .profile_replies
+        .lock()
+        .await
+        .remove(&message.message_id)
+    {
+        let _ = tx.send(message.message.clone());
+    }
+}
+
+/// Client: ask the host for a profile and wait for the answer.
+async fn request_profile(state: &Arc<AppState>, user_id: i64) -> AppResult<Option<UserProfile>> {
+    let mut request = frame(
+        MessageType::ProfileRequest,
+        state.user_id.read().await.unwrap_or(0),
+        user_id.to_string(),
+    );
+    request.created_at = server_now_secs(&state.clock_offset_ms);
+    let id = request.message_id.clone();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    state.profile_replies.lock().await.insert(id.clone(), tx);
+    if let Err(e) = send_secure_client(state, &request).await {
+        state.profile_replies.lock().await.remove(&id);
+        return Err(AppError::Network(e));
+    }
+    match tokio::time::timeout(PROFILE_TIMEOUT, rx).await {
+        Ok(Ok(json)) if json.is_empty() => Ok(None),
+        Ok(Ok(json)) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| AppError::Network(format!("The host's profile wasn't understood: {}", e))),
+        _ => {
+            state.profile_replies.lock().await.remove(&id);
+            Err(AppError::Network(
+                "The host didn't answer the profile request".to_string(),
+            ))
+        }
+    }
+}
+
+/// A user's profile: from this host's directory, or on a client from the host's.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_user_profile(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+) -> AppResult<UserProfile> {
+    let profile = if *state.is_server.read().await {
+        get_user_profile_internal(&db, user_id).await?
+    } else {
+        request_profile(state.inner(), user_id).await?
+    };
+    profile.ok_or_else(|| AppError::Validation(format!("No user {}", user_id)))
+}
+
+/// Host: edit a user's contact details. Empty fields are cleared.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_user_profile(
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+    fields: ProfileFields,
+) -> AppResult<()> {
+    set_user_profile_internal(&db, user_id, fields).await
+}
+
+/// Share `contact_user_id`'s card in the current room, as the host participant or over the
+/// client connection.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn share_contact(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    contact_user_id: i64,
+    user_id: u64,
+) -> AppResult<()> {
+    let card = serde_json::to_string(&ContactCard {
+        user_id: contact_user_id,
+        name: String::new(),
+        email: None,
+        title: None,
+        phone: None,
+        department: None,
+    })
+    .map_err(|e| AppError::Internal(e.to_string()))?;
+    let is_server = *state.is_server.read().await;
+    // The host fills the card in; a client leaves that to its host.
+    let card = if is_server {
+        resolve_card(&db, &card)
+            .await
+            .ok_or_else(|| AppError::Validation("That person isn't in the directory".to_string()))?
+    } else {
+        card
+    };
+    let message = Message {
+        username: state.username.read().await.clone(),
+        room: state.current_room.read().await.clone(),
+        room_id: state.current_room_id.read().await.unwrap_or(1),
+        created_at: if is_server {
+            now_secs()
+        } else {
+            server_now_secs(&state.clock_offset_ms)
+        },
+        ..frame(MessageType::Contact, user_id, card)
+    };
+    if is_server {
+        publish_host_message(&app, state.inner(), db.inner(), message).await;
+        Ok(())
+    } else {
+        send_secure_client(state.inner(), &message)
+            .await
+            .map_err(AppError::Network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        pool
+    }
+
+    #[test]
+    fn local_time_follows_the_offset() {
+        // 2024-01-01 23:30 UTC
+        let now = 1_704_151_800;
+        assert_eq!(local_time("+00:00", now).as_deref(), Some("23:30"));
+        assert_eq!(local_time("UTC+05:30", now).as_deref(), Some("05:00"));
+        assert_eq!(local_time("-08:00", now).as_deref(), Some("15:30"));
+        assert_eq!(local_time("+2", now).as_deref(), Some("01:30"));
+        assert_eq!(local_time("Europe/Berlin", now), None);
+        assert_eq!(local_time("+25:00", now), None);
+    }
+
+    #[tokio::test]
+    async fn profiles_carry_the_directory_details() {
+        let pool = setup().await;
+        let dept: i64 = sqlx::query_scalar("SELECT id FROM departments ORDER BY id LIMIT 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO users (name, email, department_id) VALUES ('Ada', 'ada@example.com', $1)
+             RETURNING id",
+        )
+        .bind(dept)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let fields = ProfileFields {
+            title: Some(" Engineer ".to_string()),
+            phone: Some("+1 555 0100".to_string()),
+            avatar: None,
+            timezone: Some("+01:00".to_string()),
+        };
+        set_user_profile_internal(&pool, id, fields).await.unwrap();
+        let bad = ProfileFields {
+            timezone: Some("somewhere".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            set_user_profile_internal(&pool, id, bad).await,
+            Err(AppError::Validation(_))
+        ));
+
+        let profile = get_user_profile_internal(&pool, id).await.unwrap().unwrap();
+        assert_eq!(profile.title.as_deref(), Some("Engineer"));
+        assert_eq!(profile.phone.as_deref(), Some("+1 555 0100"));
+        assert_eq!(profile.department_id, Some(dept));
+        assert!(profile.department.is_some());
+        assert!(profile.local_time.is_some());
+        assert!(get_user_profile_internal(&pool, 999)
+            .await
+            .unwrap()
+            .is_none());
+
+        // A shared card is filled in from the directory, whatever the sender put in it.
+        let sent = format!(r#"{{"user_id":{},"name":"Mallory","phone":"0"}}"#, id);
+        let card = resolve_card(&pool, &sent).await.unwrap();
+        assert_eq!(preview(&card), "[Contact: Ada]");
+        let card: ContactCard = serde_json::from_str(&card).unwrap();
+        assert_eq!(card.phone.as_deref(), Some("+1 555 0100"));
+        assert!(resolve_card(&pool, r#"{"user_id":999}"#).await.is_none());
+    }
+}
@@ -0,0 +1,445 @@
+// Custom emoji packs (host-owned, synced to clients).
+//
+// The host keeps its team's emoji in `custom_emoji` (name, image, SHA-256 of the image). On
+// every Connect it sends the client an EmojiManifest — just names and hashes. The client keeps
+// a cache in its own `custom_emoji` table: it drops names the host no longer has, and asks for
+// the ones it lacks or whose hash changed (EmojiRequest). The host answers with one EmojiData
+// frame per emoji. So an image crosses the wire once per change, not on every connect. A
+// custom emoji is used as `:name:` — in reactions, and anywhere text is shown.
+//
+// Images are small PNG / GIF / WebP files, capped so an EmojiData frame (base64 + envelope)
+// always fits in a single Noise message.
+
+use crate::error::{AppError, AppResult};
+use crate::sockets::{frame, send_secure_client, send_to_client, AppState, Message, MessageType};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool};
+use std::sync::Arc;
+use tauri::{Emitter, Manager, State};
+
+/// Largest image accepted (bytes). Base64 grows it by 4/3, which still fits a Noise frame.
+const MAX_IMAGE_BYTES: usize = 32 * 1024;
+
+/// Most custom emoji a host keeps, and the most one EmojiRequest may ask for.
+const MAX_CUSTOM_EMOJI: usize = 500;
+
+/// One manifest entry: enough for a client to tell whether its cached copy is current.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub hash: String,
+}
+
+/// An EmojiData payload.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EmojiImage {
+    pub name: String,
+    pub mime: String,
+    pub hash: String,
+    // Base64 image bytes.
+    pub data: String,
+}
+
+/// A custom emoji for the UI, its image inlined as a data URL.
+#[derive(Serialize, Debug)]
+pub struct CustomEmoji {
+    pub name: String,
+    pub hash: String,
+    pub url: String,
+}
+
+pub(crate) fn image_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Lowercase letters, digits, `_ + -`, 2–32 characters, and not a built-in shortcode.
+fn validate_name(name: &str) -> AppResult<()> {
+    let ok_chars = name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '+' | '-'));
+    if !(2..=32).contains(&name.len()) || !ok_chars {
+        return Err(AppError::Validation(
+            "Emoji names are 2–32 lowercase letters, digits, '_', '+' or '-'".to_string(),
+        ));
+    }
+    if crate::emoji::lookup(name).is_some() {
+        return Err(AppError::Conflict(format!(
+            ":{}: is a built-in emoji",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// The image's MIME type, from its magic bytes. Only small static/animated web formats, at
+/// most `max_bytes`.
+pub(crate) fn sniff_mime(bytes: &[u8], max_bytes: usize) -> AppResult<&'static str> {
+    if bytes.len() > max_bytes {
+        return Err(AppError::Validation(format!(
+            "Images must be at most {} KB",
+            max_bytes / 1024
+        )));
+    }
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Ok("image/png")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Ok("image/gif")
+    } else if bytes.len() > 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Ok("image/webp")
+    } else {
+        Err(AppError::Validation(
+            "Images must be PNG, GIF or WebP".to_string(),
+        ))
+    }
+}
+
+/// Decode base64 image data, with or without a `data:…;base64,` prefix.
+pub(crate) fn decode_image(image: &str) -> AppResult<Vec<u8>> {
+    let b64 = match image.split_once(";base64,") {
+        Some((_, data)) => data,
+        None => image,
+    };
+    base64::engine::general_purpose::STANDARD
+        .decode(b64.trim())
+        .map_err(|_| AppError::Validation("The image isn't valid base64".to_string()))
+}
+
+/// Insert or replace an emoji; a client's cache uses `replace` to take the host's version.
+async fn store(
+    pool: &SqlitePool,
+    name: &str,
+    mime: &str,
+    bytes: &[u8],
+    created_by: Option<i64>,
+) -> AppResult<String> {
+    let hash = image_hash(bytes);
+    sqlx::query(
+        "INSERT INTO custom_emoji (name, mime, data, hash, created_by) VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT(name) DO UPDATE SET mime = excluded.mime, data = excluded.data,
+                                         hash = excluded.hash",
+    )
+    .bind(name)
+    .bind(mime)
+    .bind(bytes)
+    .bind(&hash)
+    .bind(created_by)
+    .execute(pool)
+    .await?;
+    Ok(hash)
+}
+
+pub async fn upload_custom_emoji_internal(
+    pool: &SqlitePool,
+    name: &str,
+    image: &str,
+    created_by: Option<i64>,
+) -> AppResult<String> {
+    let name = name.trim().trim_matches(':').to_lowercase();
+    validate_name(&name)?;
+    let bytes = decode_image(image)?;
+    let mime = sniff_mime(&bytes, MAX_IMAGE_BYTES)?;
+    let (exists, count): (bool, i64) = {
+        let row = sqlx::query(
+            "SELECT EXISTS (SELECT 1 FROM custom_emoji WHERE name = $1) AS taken,
+                    (SELECT COUNT(*) FROM custom_emoji) AS total",
+        )
+        .bind(&name)
+        .fetch_one(pool)
+        .await?;
+        (row.get("taken"), row.get("total"))
+    };
+    if exists {
+        return Err(AppError::Conflict(format!(":{}: already exists", name)));
+    }
+    if count as usize >= MAX_CUSTOM_EMOJI {
+        return Err(AppError::Validation(format!(
+            "A server can have at most {} custom emoji",
+            MAX_CUSTOM_EMOJI
+        )));
+    }
+    store(pool, &name, mime, &bytes, created_by).await
+}
+
+pub async fn manifest_internal(pool: &SqlitePool) -> AppResult<Vec<ManifestEntry>> {
+    let rows = sqlx::query("SELECT name, hash FROM custom_emoji ORDER BY name")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|r| ManifestEntry {
+            name: r.get("name"),
+            hash: r.get("hash"),
+        })
+        .collect())
+}
+
+/// Apply a host's manifest to the local cache: drop what the host no longer has and return
+/// the names to fetch (missing here, or a different image).
+pub async fn reconcile_manifest_internal(
+    pool: &SqlitePool,
+    manifest: &[ManifestEntry],
+) -> AppResult<(u64, Vec<String>)> {
+    let names = serde_json::to_string(&manifest.iter().map(|e| &e.name).collect::<Vec<_>>())
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let removed =
+        sqlx::query("DELETE FROM custom_emoji WHERE name NOT IN (SELECT value FROM json_each($1))")
+            .bind(names)
+            .execute(pool)
+            .await?
+            .rows_affected();
+    let local = manifest_internal(pool).await?;
+    let wanted = manifest
+        .iter()
+        .filter(|e| !local.contains(e))
+        .map(|e| e.name.clone())
+        .collect();
+    Ok((removed, wanted))
+}
+
+/// Host → one client: the current manifest.
+pub(crate) async fn send_manifest(state: &Arc<AppState>, pool: &SqlitePool, user_id: u64) {
+    let manifest = match manifest_internal(pool).await {
+        Ok(m) => m,
+        Err(e) => {
+            tracing::warn!("Custom emoji manifest failed: {}", e);
+            return;
+        }
+    };
+    let json = serde_json::to_string(&manifest).unwrap_or_default();
+    send_to_client(
+        state,
+        user_id,
+        &frame(MessageType::EmojiManifest, user_id, json),
+    )
+    .await;
+}
+
+/// Host → every connected client, after the set changed.
+async fn broadcast_manifest(state: &Arc<AppState>, pool: &SqlitePool) {
+    let uids: Vec<u64> = state.server_streams.lock().await.keys().copied().collect();
+    for uid in uids {
+        send_manifest(state, pool, uid).await;
+    }
+}
+
+/// Host: answer a client's EmojiRequest (`names_json` is a JSON array of names).
+pub(crate) async fn answer_request(
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    user_id: u64,
+    names_json: &str,
+) {
+    let Ok(names) = serde_json::from_str::<Vec<String>>(names_json) else {
+        return;
+    };
+    for name in names.iter().take(MAX_CUSTOM_EMOJI) {
+        let row = sqlx::query("SELECT mime, data, hash FROM custom_emoji WHERE name = $1")
+            .bind(name)
+            .fetch_optional(pool)
+            .await;
+        let Ok(Some(row)) = row else { continue };
+        let image = EmojiImage {
+            name: name.clone(),
+            mime: row.get("mime"),
+            hash: row.get("hash"),
+            data: base64::engine::general_purpose::STANDARD.encode(row.get::<Vec<u8>, _>("data")),
+        };
+        let json = serde_json::to_string(&image).unwrap_or_default();
+        send_to_client(
+            state,
+            user_id,
+            &frame(MessageType::EmojiData, user_id, json),
+        )
+        .await;
+    }
+}
+
+/// Client: handle an EmojiManifest / EmojiData frame from the primary connection's host,
+/// updating the local cache. The UI is told (`custom_emoji_updated`) when the set changes.
+pub(crate) async fn handle_host_frame(app: &tauri::AppHandle, message: &Message) {
+    let (Some(pool), Some(state)) = (
+        app.try_state::<SqlitePool>(),
+        app.try_state::<Arc<AppState>>(),
+    ) else {
+        return;
+    };
+    let changed = match message.message_type {
+        MessageType::EmojiManifest => {
+            let Ok(manifest) = serde_json::from_str::<Vec<ManifestEntry>>(&message.message) else {
+                return;
+            };
+            match reconcile_manifest_internal(&pool, &manifest).await {
+                Ok((removed, wanted)) => {
+                    if !wanted.is_empty() {
+                        let json = serde_json::to_string(&wanted).unwrap_or_default();
+                        let request = frame(MessageType::EmojiRequest, message.user_id, json);
+                        if let Err(e) = send_secure_client(state.inner(), &request).await {
+                            tracing::warn!("Custom emoji request failed: {}", e);
+                        }
+                    }
+                    removed > 0
+                }
+                Err(e) => {
+                    tracing::warn!("Custom emoji sync failed: {}", e);
+                    false
+                }
+            }
+        }
+        MessageType::EmojiData => {
+            let Ok(image) = serde_json::from_str::<EmojiImage>(&message.message) else {
+                return;
+            };
+            // Trust nothing but the bytes: they must match the advertised hash and pass the
+            // same checks as an upload.
+            let stored = match (
+                validate_name(&image.name),
+                decode_image(&image.data)
+                    .and_then(|b| sniff_mime(&b, MAX_IMAGE_BYTES).map(|m| (b, m))),
+            ) {
+                (Ok(()), Ok((bytes, mime))) if image_hash(&bytes) == image.hash => {
+                    store(&pool, &image.name, mime, &bytes, None).await
+                }
+                _ => Err(AppError::Validation(format!(
+                    "Rejected custom emoji :{}: from host",
+                    image.name
+                ))),
+            };
+            match stored {
+                Ok(_) => true,
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    false
+                }
+            }
+        }
+        _ => false,
+    };
+    if changed {
+        let _ = app.emit("custom_emoji_updated", ());
+    }
+}
+
+/// Add a custom emoji to this host's set (clients pick it up right away).
+#[tauri::command]
+pub async fn upload_custom_emoji(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    name: String,
+    image: String,
+    user_id: Option<i64>,
+) -> AppResult<()> {
+    upload_custom_emoji_internal(&db, &name, &image, user_id).await?;
+    broadcast_manifest(state.inner(), &db).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_custom_emoji(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    name: String,
+) -> AppResult<()> {
+    let deleted = sqlx::query("DELETE FROM custom_emoji WHERE name = $1")
+        .bind(name.trim_matches(':'))
+        .execute(&*db)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(AppError::Validation(format!("No custom emoji :{}:", name)));
+    }
+    broadcast_manifest(state.inner(), &db).await;
+    Ok(())
+}
+
+/// The custom emoji available here: the host's own set, or on a client the synced copy.
+#[tauri::command]
+pub async fn list_custom_emoji(db: State<'_, SqlitePool>) -> AppResult<Vec<CustomEmoji>> {
+    let rows = sqlx::query("SELECT name, hash, mime, data FROM custom_emoji ORDER BY name")
+        .fetch_all(&*db)
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|r| CustomEmoji {
+            name: r.get("name"),
+            hash: r.get("hash"),
+            url: format!(
+                "data:{};base64,{}",
+                r.get::<String, _>("mime"),
+                base64::engine::general_purpose::STANDARD.encode(r.get::<Vec<u8>, _>("data"))
+            ),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        pool
+    }
+
+    fn png(extra: u8) -> String {
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        bytes.push(extra);
+        format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(bytes)
+        )
+    }
+
+    #[tokio::test]
+    async fn uploads_are_validated() {
+        let pool = setup().await;
+        upload_custom_emoji_internal(&pool, ":PartyParrot:", &png(1), None)
+            .await
+            .unwrap();
+        let dup = upload_custom_emoji_internal(&pool, "partyparrot", &png(2), None).await;
+        assert!(matches!(dup, Err(AppError::Conflict(_))));
+        let builtin = upload_custom_emoji_internal(&pool, "fire", &png(1), None).await;
+        assert!(matches!(builtin, Err(AppError::Conflict(_))));
+        let not_image = base64::engine::general_purpose::STANDARD.encode("<svg/>");
+        assert!(
+            upload_custom_emoji_internal(&pool, "logo", &not_image, None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn manifest_sync_fetches_only_what_changed() {
+        let host = setup().await;
+        let client = setup().await;
+        for (name, n) in [("ship", 1), ("parrot", 2)] {
+            upload_custom_emoji_internal(&host, name, &png(n), None)
+                .await
+                .unwrap();
+        }
+        // The client has a stale parrot and an emoji the host since deleted.
+        upload_custom_emoji_internal(&client, "parrot", &png(9), None)
+            .await
+            .unwrap();
+        upload_custom_emoji_internal(&client, "gone", &png(3), None)
+            .await
+            .unwrap();
+
+        let manifest = manifest_internal(&host).await.unwrap();
+        let (removed, wanted) = reconcile_manifest_internal(&client, &manifest)
+            .await
+            .unwrap();
+        assert_eq!(removed, 1);
+        assert_eq!(wanted, ["parrot", "ship"]);
+    }
+}
@@ -4,15 +4,24 @@
 // tauri-plugin-sql), and on a DB that can't be decrypted — an upgrade from an older plaintext
 // build, or a lost key — we start fresh (the app is configured to reset rather than migrate).
 
+//
+// Where the DB lives is configurable: by default `<app config>/nutler.db`; `set_database_path`
+// picks another folder (a network share, say) for the next launch; and `--portable` keeps it in
+// `nutler-data/` beside the executable, with its key file alongside so the folder works on any
+// machine. When the location changes, the existing DB is copied to the new place at startup,
+// checked to open with its key, and only then is the old copy renamed aside (`nutler.db.moved`).
+
+use crate::error::{AppError, AppResult};
 use crate::migration::{get_migrations, MigrationKind};
+use serde::{Deserialize, Serialize};
 use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-const KEYRING_SERVICE: &str = "dev.nutler.app";
+pub(crate) const KEYRING_SERVICE: &str = "dev.nutler.app";
 const KEYRING_USER: &str = "db-key-v1";
 
-fn random_hex_key() -> String {
+pub(crate) fn random_hex_key() -> String {
     let mut buf = [0u8; 32];
     getrandom::getrandom(&mut buf).expect("OS RNG unavailable");
     let mut s = String::with_capacity(64);
@@ -175,13 +184,210 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     Ok(())
 }
 
-/// Open the SQLCipher-encrypted DB (resetting an undecryptable one), run migrations, and return
-/// the FK-enforcing query pool the commands use.
-pub async fn init_encrypted_db(app_dir: &Path) -> Result<SqlitePool, String> {
-    let db_path = app_dir.join("nutler.db");
-    let key_pragma = key_pragma_value(&load_or_create_key(app_dir)?);
+/// Kept in the app config dir: the DB's location can't be stored in the DB.
+const LOCATION_FILE: &str = "db_location.json";
+
+const DB_FILE: &str = "nutler.db";
+
+#[derive(Serialize, Deserialize, Default)]
+struct LocationFile {
+    // The folder chosen with set_database_path; None = the app config dir.
+    #[serde(default)]
+    dir: Option<PathBuf>,
+    // Where the DB was last opened, so it can be moved from there when the location changes.
+    #[serde(default)]
+    last: Option<PathBuf>,
+}
+
+fn read_location_file(app_dir: &Path) -> LocationFile {
+    std::fs::read_to_string(app_dir.join(LOCATION_FILE))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn write_location_file(app_dir: &Path, file: &LocationFile) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(file).map_err(|e| e.to_string())?;
+    std::fs::write(app_dir.join(LOCATION_FILE), json)
+        .map_err(|e| format!("couldn't save the database location: {e}"))
+}
+
+/// Where this run keeps its database.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DbLocation {
+    pub db_path: PathBuf,
+    // Where the key file is looked for: the app config dir, or beside the DB when portable.
+    pub key_dir: PathBuf,
+    pub portable: bool,
+    // A different folder chosen for the next launch, if any.
+    pub pending_dir: Option<PathBuf>,
+}
+
+impl DbLocation {
+    /// WAL needs shared memory that network filesystems don't reliably provide, so anywhere but
+    /// the app's own data dir uses a rollback journal.
+    fn journal_mode(&self, app_dir: &Path) -> SqliteJournalMode {
+        if self.db_path.parent() == Some(app_dir) {
+            SqliteJournalMode::Wal
+        } else {
+            SqliteJournalMode::Delete
+        }
+    }
+}
+
+/// The portable data folder beside the executable, when started with `--portable`.
+pub fn portable_dir() -> Option<PathBuf> {
+    if !std::env::args().any(|a| a == "--portable") {
+        return None;
+    }
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join("nutler-data"))
+}
+
+/// Decide where this run's DB lives: the portable folder, else the chosen folder, else the app
+/// config dir.
+pub fn resolve_location(app_dir: &Path, portable: Option<&Path>) -> DbLocation {
+    if let Some(dir) = portable {
+        return DbLocation {
+            db_path: dir.join(DB_FILE),
+            key_dir: dir.to_path_buf(),
+            portable: true,
+            pending_dir: None,
+        };
+    }
+    let dir = read_location_file(app_dir)
+        .dir
+        .unwrap_or_else(|| app_dir.to_path_buf());
+    DbLocation {
+        db_path: dir.join(DB_FILE),
+        key_dir: app_dir.to_path_buf(),
+        portable: false,
+        pending_dir: None,
+    }
+}
+
+/// The portable folder's key: its key file, else this machine's key written there (so the DB
+/// copied in from this machine stays readable wherever the folder goes).
+fn load_or_create_portable_key(app_dir: &Path, dir: &Path) -> Result<String, String> {
+    if let Ok(k) = std::fs::read_to_string(dir.join("nutler.key")) {
+        if is_hex64(k.trim()) {
+            return Ok(k.trim().to_string());
+        }
+    }
+    let k = load_or_create_key(app_dir)?;
+    write_key_file(&dir.join("nutler.key"), &k)?;
+    Ok(k)
+}
 
-    if db_path.exists() && !can_decrypt(&db_path, &key_pragma).await {
+fn db_files(db_path: &Path) -> [PathBuf; 3] {
+    [
+        db_path.to_path_buf(),
+        db_path.with_extension("db-wal"),
+        db_path.with_extension("db-shm"),
+    ]
+}
+
+/// Copy the DB (and any WAL sidecars) from `from` to `to`, check the copy opens with the key,
+/// then rename the original aside. Nothing at `from` is touched unless the copy is good.
+async fn move_db(from: &Path, to: &Path, key_pragma: &str) -> Result<(), String> {
+    let copied = (|| {
+        for (src, dst) in db_files(from).iter().zip(db_files(to).iter()) {
+            if src.exists() {
+                std::fs::copy(src, dst)?;
+            }
+        }
+        std::io::Result::Ok(())
+    })();
+    if let Err(e) = copied {
+        remove_db_files(to);
+        return Err(format!("couldn't copy the database: {e}"));
+    }
+    if !can_decrypt(to, key_pragma).await {
+        remove_db_files(to);
+        return Err("the copied database didn't open".to_string());
+    }
+    std::fs::rename(from, from.with_extension("db.moved"))
+        .map_err(|e| format!("couldn't set the old database aside: {e}"))?;
+    let _ = std::fs::remove_file(from.with_extension("db-wal"));
+    let _ = std::fs::remove_file(from.with_extension("db-shm"));
+    Ok(())
+}
+
+/// Open the SQLCipher-encrypted DB at `location` (moving it there from where it was last opened,
+/// if that changed; resetting an undecryptable one in the app's own folder), run migrations, and
+/// return the FK-enforcing query pool the commands use, with the location actually used: if the
+/// move fails, or a chosen folder's DB has another key, the DB is opened where it was.
+pub async fn init_encrypted_db(
+    app_dir: &Path,
+    mut location: DbLocation,
+) -> Result<(SqlitePool, DbLocation), String> {
+    if let Some(dir) = location.db_path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("couldn't create {}: {e}", dir.display()))?;
+    }
+    let key = if location.portable {
+        load_or_create_portable_key(app_dir, &location.key_dir)?
+    } else {
+        load_or_create_key(app_dir)?
+    };
+    let key_pragma = key_pragma_value(&key);
+
+    let mut file = read_location_file(app_dir);
+    let previous = file.last.clone().unwrap_or_else(|| app_dir.join(DB_FILE));
+    if previous != location.db_path && previous.exists() && !location.db_path.exists() {
+        tracing::info!(
+            "Moving the database from {} to {}",
+            previous.display(),
+            location.db_path.display()
+        );
+        if let Err(e) = move_db(&previous, &location.db_path, &key_pragma).await {
+            tracing::error!(
+                "Database not moved ({}); still using {}",
+                e,
+                previous.display()
+            );
+            location.pending_dir = location.db_path.parent().map(Path::to_path_buf);
+            location.db_path = previous.clone();
+        }
+    }
+
+    // Only the database in the app's own folder is this install's to reset. One anywhere else
+    // (a share, a portable folder) may belong to another machine: it is left alone, and the
+    // previous database is opened instead.
+    let default_db = app_dir.join(DB_FILE);
+    let chosen = location.db_path.clone();
+    if chosen != default_db && chosen.exists() && !can_decrypt(&chosen, &key_pragma).await {
+        if location.portable {
+            return Err(format!(
+                "{} can't be decrypted with the key beside it",
+                chosen.display()
+            ));
+        }
+        let fallback = if previous != chosen {
+            previous
+        } else {
+            default_db.clone()
+        };
+        if fallback != default_db && fallback.exists() && !can_decrypt(&fallback, &key_pragma).await
+        {
+            return Err(format!(
+                "neither {} nor {} can be decrypted with this machine's key",
+                chosen.display(),
+                fallback.display()
+            ));
+        }
+        tracing::error!(
+            "{} can't be decrypted with this machine's key; leaving it untouched and using {}",
+            chosen.display(),
+            fallback.display()
+        );
+        location.pending_dir = chosen.parent().map(Path::to_path_buf);
+        location.db_path = fallback;
+    }
+    let db_path = location.db_path.clone();
+    let journal = location.journal_mode(app_dir);
+
+    if db_path == default_db && db_path.exists() && !can_decrypt(&db_path, &key_pragma).await {
         tracing::warn!("Existing database can't be decrypted — resetting to a fresh encrypted DB");
         remove_db_files(&db_path);
     }
@@ -191,7 +397,11 @@ pub async fn init_encrypted_db(app_dir: &Path) -> Result<SqlitePool, String> {
     {
         let mig_pool = SqlitePoolOptions::new()
             .max_connections(1)
-            .connect_with(base_opts(&db_path, &key_pragma).foreign_keys(false))
+            .connect_with(
+                base_opts(&db_path, &key_pragma)
+                    .journal_mode(journal)
+                    .foreign_keys(false),
+            )
             .await
             .map_err(|e| format!("open DB for migrations: {e}"))?;
         run_migrations(&mig_pool)
@@ -200,9 +410,90 @@ pub async fn init_encrypted_db(app_dir: &Path) -> Result<SqlitePool, String> {
         mig_pool.close().await;
     }
 
-    SqlitePool::connect_with(base_opts(&db_path, &key_pragma).foreign_keys(true))
-        .await
-        .map_err(|e| format!("open query pool: {e}"))
+    let pool = SqlitePool::connect_with(
+        base_opts(&db_path, &key_pragma)
+            .journal_mode(journal)
+            .foreign_keys(true),
+    )
+    .await
+    .map_err(|e| format!("open query pool: {e}"))?;
+
+    // Portable runs leave the machine's own record alone.
+    if !location.portable {
+        file.last = Some(db_path);
+        if let Err(e) = write_location_file(app_dir, &file) {
+            tracing::warn!("{}", e);
+        }
+    }
+    Ok((pool, location))
+}
+
+/// Where the database is, and any folder chosen for the next launch.
+#[tauri::command]
+pub async fn get_database_location(
+    app: tauri::AppHandle,
+    location: tauri::State<'_, DbLocation>,
+) -> AppResult<DbLocation> {
+    use tauri::Manager;
+    let mut current = location.inner().clone();
+    if !current.portable {
+        let app_dir = app
+            .path()
+            .app_config_dir()
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let chosen = resolve_location(&app_dir, None).db_path;
+        if chosen != current.db_path {
+            current.pending_dir = chosen.parent().map(Path::to_path_buf);
+        }
+    }
+    Ok(current)
+}
+
+/// Keep the database in `path` (a folder) from the next launch on, or back in the app's data
+/// folder with None. The existing database is moved there at startup; a folder that already
+/// holds a nutler.db is used as it is, or left untouched if this machine's key can't open it.
+#[tauri::command]
+pub async fn set_database_path(
+    app: tauri::AppHandle,
+    location: tauri::State<'_, DbLocation>,
+    path: Option<String>,
+) -> AppResult<DbLocation> {
+    use tauri::Manager;
+    if location.portable {
+        return Err(AppError::Validation(
+            "In portable mode the database stays beside the app".to_string(),
+        ));
+    }
+    let app_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let dir = match path.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(p) => {
+            let dir = PathBuf::from(p);
+            if !dir.is_absolute() {
+                return Err(AppError::Validation(
+                    "Choose a full folder path".to_string(),
+                ));
+            }
+            check_writable(&dir)
+                .map_err(|e| AppError::Validation(format!("Can't use {}: {}", dir.display(), e)))?;
+            Some(dir).filter(|d| d != &app_dir)
+        }
+        None => None,
+    };
+    let mut file = read_location_file(&app_dir);
+    file.dir = dir;
+    write_location_file(&app_dir, &file).map_err(AppError::Internal)?;
+    get_database_location(app, location).await
+}
+
+/// Whether files can be created in `dir` (creating it if needed).
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".nutler-probe-{}", random_hex_key()));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
 }
 
 #[cfg(test)]
@@ -263,6 +554,107 @@ mod tests {
         let _ = std::fs::remove_dir_all(&dir);
     }
 
+    #[tokio::test]
+    async fn database_moves_to_a_new_folder_and_back() {
+        let mut sfx = [0u8; 8];
+        getrandom::getrandom(&mut sfx).unwrap();
+        let root = std::env::temp_dir().join(format!(
+            "nutler-move-{}",
+            sfx.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+        ));
+        let app_dir = root.join("config");
+        let share = root.join("share");
+        std::fs::create_dir_all(&app_dir).unwrap();
+        // A key file, so the test never touches the real keychain.
+        std::fs::write(app_dir.join("nutler.key"), "cd".repeat(32)).unwrap();
+
+        let (pool, here) = init_encrypted_db(&app_dir, resolve_location(&app_dir, None))
+            .await
+            .unwrap();
+        assert_eq!(here.db_path, app_dir.join(DB_FILE));
+        sqlx::query("INSERT INTO departments (name) VALUES ('Moved')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let mut file = read_location_file(&app_dir);
+        file.dir = Some(share.clone());
+        write_location_file(&app_dir, &file).unwrap();
+        let (pool, there) = init_encrypted_db(&app_dir, resolve_location(&app_dir, None))
+            .await
+            .unwrap();
+        assert_eq!(there.db_path, share.join(DB_FILE));
+        let n: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM departments WHERE name = 'Moved'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(n, 1);
+        pool.close().await;
+        assert!(!app_dir.join(DB_FILE).exists());
+        assert!(app_dir.join("nutler.db.moved").exists());
+
+        // Portable: the folder gets the key alongside the moved DB.
+        let portable = root.join("usb");
+        let (pool, loc) = init_encrypted_db(&app_dir, resolve_location(&app_dir, Some(&portable)))
+            .await
+            .unwrap();
+        assert!(loc.portable && portable.join("nutler.key").exists());
+        pool.close().await;
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn another_machines_database_in_a_chosen_folder_is_left_alone() {
+        let mut sfx = [0u8; 8];
+        getrandom::getrandom(&mut sfx).unwrap();
+        let root = std::env::temp_dir().join(format!(
+            "nutler-foreign-{}",
+            sfx.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+        ));
+        let app_dir = root.join("config");
+        let share = root.join("share");
+        std::fs::create_dir_all(&app_dir).unwrap();
+        std::fs::create_dir_all(&share).unwrap();
+        std::fs::write(app_dir.join("nutler.key"), "cd".repeat(32)).unwrap();
+        // Another machine's database, under its own key, already on the share.
+        let foreign_key = key_pragma_value(&"ef".repeat(32));
+        let foreign = SqlitePool::connect_with(base_opts(&share.join(DB_FILE), &foreign_key))
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE t (v TEXT)")
+            .execute(&foreign)
+            .await
+            .unwrap();
+        foreign.close().await;
+
+        let mut file = read_location_file(&app_dir);
+        file.dir = Some(share.clone());
+        write_location_file(&app_dir, &file).unwrap();
+        let (pool, loc) = init_encrypted_db(&app_dir, resolve_location(&app_dir, None))
+            .await
+            .unwrap();
+        pool.close().await;
+        assert_eq!(loc.db_path, app_dir.join(DB_FILE));
+        assert_eq!(loc.pending_dir, Some(share.clone()));
+        assert!(can_decrypt(&share.join(DB_FILE), &foreign_key).await);
+
+        // Portable: the folder's own key doesn't open it either, so startup fails instead.
+        let portable = root.join("usb");
+        std::fs::create_dir_all(&portable).unwrap();
+        std::fs::write(portable.join("nutler.key"), "ab".repeat(32)).unwrap();
+        std::fs::copy(share.join(DB_FILE), portable.join(DB_FILE)).unwrap();
+        assert!(
+            init_encrypted_db(&app_dir, resolve_location(&app_dir, Some(&portable)))
+                .await
+                .is_err()
+        );
+        assert!(can_decrypt(&portable.join(DB_FILE), &foreign_key).await);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
     #[tokio::test]
     async fn sqlcipher_encrypts_on_disk_and_gates_on_key() {
         let mut sfx = [0u8; 8];
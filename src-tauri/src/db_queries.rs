@@ -1,5 +1,7 @@
 use crate::error::{AppError, AppResult};
+use crate::room_permissions;
 use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteRow;
 use sqlx::{Row, SqlitePool};
 use tauri::State;
 use uuid::Uuid;
@@ -37,10 +39,14 @@ pub struct ChatRoom {
     #[serde(default)]
     pub display_name: Option<String>,
     pub user_count: Option<i64>,
+    // Organizing tags (see set_room_tags), sorted.
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
-// Build a ChatRoom from a query row. `is_dm`, `display_name`, `department_name` and
-// `user_count` are optional columns — `try_get` yields the default when a query omits them.
+// Build a ChatRoom from a query row. `is_dm`, `display_name`, `department_name`,
+// `user_count` and `tags` are optional columns — `try_get` yields the default when a query
+// omits them. `tags` is a comma-joined list (tags can't contain commas).
 fn row_to_room(row: &sqlx::sqlite::SqliteRow) -> ChatRoom {
     ChatRoom {
         id: row.get::<Option<i64>, _>("id"),
@@ -56,6 +62,12 @@ fn row_to_room(row: &sqlx::sqlite::SqliteRow) -> ChatRoom {
             .try_get::<Option<String>, _>("display_name")
             .unwrap_or(None),
         user_count: row.try_get::<Option<i64>, _>("user_count").unwrap_or(None),
+        tags: row
+            .try_get::<Option<String>, _>("tags")
+            .ok()
+            .flatten()
+            .map(|t| t.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
     }
 }
 
@@ -72,6 +84,11 @@ pub struct Message {
     pub created_at: String,
     pub edited_at: Option<String>,
     pub deleted_at: Option<String>,
+    // Code snippets only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -88,7 +105,124 @@ pub async fn upsert_user(
     email: String,
     department_id: Option<i64>,
 ) -> Result<User, String> {
-    upsert_user_internal(&db, name, email, department_id).await
+    let user = upsert_user_internal(&db, name, email, department_id).await?;
+    if let Some(id) = user.id {
+        apply_auto_join_internal(&db, id).await?;
+    }
+    Ok(user)
+}
+
+/// Add `user_id` to every auto-join room that applies to them: rooms for their department, and
+/// company-wide ones (no department, or the 'General' department). Rooms they already belong
+/// to — or left on purpose (is_active = 0) — are untouched. Returns the newly joined room ids.
+pub async fn apply_auto_join_internal(pool: &SqlitePool, user_id: i64) -> Result<Vec<i64>, String> {
+    let rooms: Vec<i64> = sqlx::query_scalar(
+        "INSERT INTO user_rooms (user_id, room_id, is_active)
+         SELECT u.id, cr.id, 1
+         FROM users u JOIN chat_rooms cr ON cr.auto_join = 1 AND cr.is_dm = 0
+         LEFT JOIN departments d ON d.id = cr.department_id
+         WHERE u.id = $1
+           AND u.email NOT LIKE '%.invalid'
+           AND (cr.department_id IS NULL OR d.name = 'General' OR cr.department_id = u.department_id)
+         ON CONFLICT(user_id, room_id) DO NOTHING
+         RETURNING room_id",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to apply auto-join rooms: {}", e))?;
+    Ok(rooms)
+}
+
+/// Flag (or unflag) a room as auto-join. Turning it on also backfills every existing user it
+/// applies to; returns how many were added.
+pub async fn set_room_auto_join_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    enabled: bool,
+) -> AppResult<u64> {
+    let updated = sqlx::query("UPDATE chat_rooms SET auto_join = $1 WHERE id = $2 AND is_dm = 0")
+        .bind(enabled)
+        .bind(room_id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(AppError::Validation("Room not found".to_string()));
+    }
+    if !enabled {
+        return Ok(0);
+    }
+    let added = sqlx::query(
+        "INSERT INTO user_rooms (user_id, room_id, is_active)
+         SELECT u.id, cr.id, 1
+         FROM chat_rooms cr JOIN users u
+         LEFT JOIN departments d ON d.id = cr.department_id
+         WHERE cr.id = $1
+           AND u.email NOT LIKE '%.invalid'
+           AND (cr.department_id IS NULL OR d.name = 'General' OR cr.department_id = u.department_id)
+         ON CONFLICT(user_id, room_id) DO NOTHING",
+    )
+    .bind(room_id)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(added)
+}
+
+/// Names nobody may connect as: they'd read as the app itself speaking.
+const RESERVED_NAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "host",
+    "moderator",
+    "nutler",
+    "server",
+    "system",
+];
+
+/// Check a display name a client chose: 1–64 characters of letters, digits, spaces and
+/// `. _ - '`, not starting or ending with a space, and not a reserved name.
+pub fn validate_username(name: &str) -> Result<(), String> {
+    let len = name.chars().count();
+    if len == 0 || len > 64 {
+        return Err("Name must be between 1 and 64 characters".to_string());
+    }
+    if name.trim() != name {
+        return Err("Name can't start or end with a space".to_string());
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || matches!(c, ' ' | '.' | '_' | '-' | '\'')))
+    {
+        return Err(format!("Name can't contain '{}'", c));
+    }
+    if RESERVED_NAMES.contains(&name.to_lowercase().as_str()) {
+        return Err(format!("\"{}\" is a reserved name", name));
+    }
+    Ok(())
+}
+
+/// The one form an email is stored and looked up in: trimmed and lowercased.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Whether another user (a different email) already goes by `name`, ignoring case.
+pub async fn username_taken_internal(
+    pool: &SqlitePool,
+    name: &str,
+    email: &str,
+) -> Result<bool, String> {
+    let taken: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM users WHERE lower(name) = lower($1) AND email != $2)",
+    )
+    .bind(name.trim())
+    .bind(normalize_email(email))
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(taken)
 }
 
 /// Pool-based upsert so the socket layer can register a connecting client into the HOST's
@@ -103,13 +237,26 @@ pub async fn upsert_user_internal(
     // Normalize + validate so identity (the broadcast/attribution key) stays clean:
     // trim/lowercase email, reject blank/oversized values.
     let name = name.trim().to_string();
-    let email = email.trim().to_lowercase();
+    let email = normalize_email(&email);
     if name.is_empty() || name.chars().count() > 64 {
         return Err("Name must be between 1 and 64 characters".to_string());
     }
     if email.is_empty() || email.len() > 254 || !email.contains('@') {
         return Err("A valid email address is required".to_string());
     }
+    // Guest addresses are only issued by create_guest; one is good while its guest is live.
+    if email.ends_with(crate::guests::GUEST_EMAIL_SUFFIX) {
+        let live: Option<bool> = sqlx::query_scalar(
+            "SELECT guest_expires_at > datetime('now') FROM users WHERE email = $1 AND is_guest = 1",
+        )
+        .bind(&email)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        if live != Some(true) {
+            return Err("This guest pass has expired".to_string());
+        }
+    }
 
     //Try find existing
     if let Some(_row) = sqlx::query(
@@ -123,14 +270,17 @@ pub async fn upsert_user_internal(
     .await
     .map_err(|e| e.to_string())?
     {
-        // Optionally update display name/department if changed
-        sqlx::query("UPDATE users SET name=$1, department_id=$2 WHERE email=$3")
-            .bind(&name)
-            .bind(department_id)
-            .bind(&email)
-            .execute(pool)
-            .await
-            .map_err(|e| e.to_string())?;
+        // Optionally update display name/department if changed. A None department keeps the
+        // stored one: reconnects upsert without a department and mustn't clear a synced one.
+        sqlx::query(
+            "UPDATE users SET name=$1, department_id=COALESCE($2, department_id) WHERE email=$3",
+        )
+        .bind(&name)
+        .bind(department_id)
+        .bind(&email)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
     } else {
         // Create
         sqlx::query("INSERT INTO users (name, email, department_id) VALUES ($1, $2, $3)")
@@ -174,7 +324,7 @@ pub async fn create_user(
 ) -> Result<InsertResult, String> {
     let result = sqlx::query("INSERT INTO users (name, email, department_id) VALUES ($1, $2, $3)")
         .bind(&name)
-        .bind(&email)
+        .bind(normalize_email(&email))
         .bind(department_id)
         .execute(&*db)
         .await
@@ -186,15 +336,34 @@ pub async fn create_user(
     })
 }
 
+/// The users `user_id` may see.
 #[tauri::command]
-pub async fn get_users(db: State<'_, SqlitePool>) -> Result<Vec<User>, String> {
-    let result = sqlx::query(
+pub async fn get_users(db: State<'_, SqlitePool>, user_id: i64) -> Result<Vec<User>, String> {
+    get_users_internal(&db, user_id).await
+}
+
+/// Which users the viewer (`$1`) may see, given `users u` (the listed user), `departments d`
+/// (u's department) and `users v` (the viewer) in the query: their own department, company-wide
+/// users (no department, or General) and themselves, unless they're an admin, who sees everyone.
+const VISIBLE_USERS: &str = "u.id = $1
+            OR u.department_id IS NULL
+            OR d.name = 'General'
+            OR u.department_id = v.department_id
+            OR v.is_admin = 1";
+
+/// Users are department-scoped (`VISIBLE_USERS`).
+pub async fn get_users_internal(pool: &SqlitePool, viewer: i64) -> Result<Vec<User>, String> {
+    let result = sqlx::query(&format!(
         "SELECT u.id, u.name, u.email, u.department_id, u.is_online, u.last_seen, d.name as department_name 
          FROM users u 
          LEFT JOIN departments d ON u.department_id = d.id 
-         ORDER BY u.name"
-    )
-        .fetch_all(&*db)
+         LEFT JOIN users v ON v.id = $1
+         WHERE {}
+         ORDER BY u.name",
+        VISIBLE_USERS
+    ))
+        .bind(viewer)
+        .fetch_all(pool)
         .await
         .map_err(|e| format!("Failed to get users: {}", e))?;
 
@@ -215,6 +384,10 @@ pub async fn get_users(db: State<'_, SqlitePool>) -> Result<Vec<User>, String> {
 
 #[tauri::command]
 pub async fn get_user_by_id(db: State<'_, SqlitePool>, id: i64) -> Result<Option<User>, String> {
+    get_user_by_id_internal(&db, id).await
+}
+
+pub async fn get_user_by_id_internal(pool: &SqlitePool, id: i64) -> Result<Option<User>, String> {
     let result = sqlx::query(
         "SELECT u.id, u.name, u.email, u.department_id, u.is_online, u.last_seen, d.name as department_name 
          FROM users u 
@@ -222,7 +395,7 @@ pub async fn get_user_by_id(db: State<'_, SqlitePool>, id: i64) -> Result<Option
          WHERE u.id = $1"
     )
         .bind(id)
-        .fetch_optional(&*db)
+        .fetch_optional(pool)
         .await
         .map_err(|e| format!("Failed to get user by id: {}", e))?;
 
@@ -246,11 +419,19 @@ pub async fn update_user_online_status(
     db: State<'_, SqlitePool>,
     user_id: i64,
     is_online: bool,
+) -> Result<(), String> {
+    update_user_online_status_internal(&db, user_id, is_online).await
+}
+
+pub async fn update_user_online_status_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    is_online: bool,
 ) -> Result<(), String> {
     sqlx::query("UPDATE users SET is_online = $1, last_seen = CURRENT_TIMESTAMP WHERE id = $2")
         .bind(is_online)
         .bind(user_id)
-        .execute(&*db)
+        .execute(pool)
         .await
         .map_err(|e| format!("Failed to update user status: {}", e))?;
 
@@ -260,8 +441,12 @@ pub async fn update_user_online_status(
 // Department management
 #[tauri::command]
 pub async fn get_departments(db: State<'_, SqlitePool>) -> Result<Vec<Department>, String> {
+    get_departments_internal(&db).await
+}
+
+pub async fn get_departments_internal(pool: &SqlitePool) -> Result<Vec<Department>, String> {
     let result = sqlx::query("SELECT id, name, description FROM departments ORDER BY name")
-        .fetch_all(&*db)
+        .fetch_all(pool)
         .await
         .map_err(|e| format!("Failed to get departments: {}", e))?;
 
@@ -276,22 +461,48 @@ pub async fn get_departments(db: State<'_, SqlitePool>) -> Result<Vec<Department
     Ok(departments)
 }
 
+/// Id of the department called `name`, creating it if missing (directory sync maps external
+/// groups onto departments by name).
+pub async fn ensure_department_internal(pool: &SqlitePool, name: &str) -> Result<i64, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Department name is required".to_string());
+    }
+    sqlx::query("INSERT INTO departments (name) VALUES ($1) ON CONFLICT(name) DO NOTHING")
+        .bind(name)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to create department: {}", e))?;
+    sqlx::query("SELECT id FROM departments WHERE name = $1")
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .map(|row| row.get::<i64, _>("id"))
+        .map_err(|e| format!("Failed to get department: {}", e))
+}
+
 // Chat room management
 #[tauri::command]
 pub async fn get_chat_rooms(
     db: State<'_, SqlitePool>,
     user_id: i64,
+    tags: Option<Vec<String>>,
 ) -> Result<Vec<ChatRoom>, String> {
-    get_chat_rooms_internal(&db, user_id).await
+    get_chat_rooms_internal(&db, user_id, &tags.unwrap_or_default()).await
 }
 
 /// Pool-based room listing so the socket layer can compute a client's authoritative room list
 /// (public rooms + private rooms / DMs they belong to) on the HOST db and push it to them —
-/// clients keep no usable local copy of host-created rooms.
+/// clients keep no usable local copy of host-created rooms. A non-empty `tags` keeps only rooms
+/// carrying ALL of them. A private room that belongs to a department is only listed to that
+/// department's users and admins, member or not.
 pub async fn get_chat_rooms_internal(
     pool: &SqlitePool,
     user_id: i64,
+    tags: &[String],
 ) -> Result<Vec<ChatRoom>, String> {
+    let filter = normalize_tags(tags).map_err(|e| e.to_string())?;
+    let filter = serde_json::to_string(&filter).map_err(|e| e.to_string())?;
     let result = sqlx::query(
         "SELECT
   cr.id,
@@ -306,7 +517,9 @@ pub async fn get_chat_rooms_internal(
     FROM user_rooms ur2 JOIN users u ON u.id = ur2.user_id
     WHERE ur2.room_id = cr.id AND ur2.user_id != $1 AND ur2.is_active = 1
   ) ELSE NULL END AS display_name,
-  COALESCE(urc.user_count, 0) AS user_count
+  COALESCE(urc.user_count, 0) AS user_count,
+  (SELECT group_concat(tag, ',') FROM (
+     SELECT tag FROM room_tags WHERE room_id = cr.id ORDER BY tag)) AS tags
 FROM chat_rooms cr
 LEFT JOIN departments d
   ON cr.department_id = d.id
@@ -317,14 +530,22 @@ LEFT JOIN (
   GROUP BY room_id
 ) urc
   ON urc.room_id = cr.id
-WHERE cr.is_private = 0
+WHERE (cr.is_private = 0
    OR cr.created_by = $1
    OR EXISTS (SELECT 1 FROM user_rooms ur
-              WHERE ur.room_id = cr.id AND ur.user_id = $1 AND ur.is_active = 1)
+              WHERE ur.room_id = cr.id AND ur.user_id = $1 AND ur.is_active = 1))
+  AND (cr.is_private = 0
+       OR cr.department_id IS NULL
+       OR EXISTS (SELECT 1 FROM users v
+                  WHERE v.id = $1 AND (v.department_id = cr.department_id OR v.is_admin = 1)))
+  AND (SELECT COUNT(*) FROM json_each($2) f
+       WHERE EXISTS (SELECT 1 FROM room_tags rt WHERE rt.room_id = cr.id AND rt.tag = f.value))
+      = json_array_length($2)
 ORDER BY cr.is_dm, cr.name
 ",
     )
     .bind(user_id)
+    .bind(filter)
     .fetch_all(pool)
     .await
     .map_err(|e| format!("Failed to get chat rooms: {}", e))?;
@@ -360,9 +581,11 @@ LEFT JOIN (
   ON urc.room_id = cr.id
 WHERE cr.department_id = $1
   AND (cr.is_private = 0
-       OR cr.created_by = $2
-       OR EXISTS (SELECT 1 FROM user_rooms ur
-                  WHERE ur.room_id = cr.id AND ur.user_id = $2 AND ur.is_active = 1))
+       OR ((cr.created_by = $2
+            OR EXISTS (SELECT 1 FROM user_rooms ur
+                       WHERE ur.room_id = cr.id AND ur.user_id = $2 AND ur.is_active = 1))
+           AND EXISTS (SELECT 1 FROM users v
+                       WHERE v.id = $2 AND (v.department_id = $1 OR v.is_admin = 1))))
 ORDER BY cr.name
 ",
     )
@@ -384,6 +607,7 @@ pub async fn create_room(
     department_id: Option<i64>,
     is_private: Option<bool>,
     created_by: Option<i64>,
+    announcement_only: Option<bool>,
 ) -> AppResult<ChatRoom> {
     create_room_internal(
         &db,
@@ -392,12 +616,14 @@ pub async fn create_room(
         department_id,
         is_private,
         created_by,
+        announcement_only,
     )
     .await
 }
 
 /// Pool-based channel creation so the socket layer can create a room on the HOST DB on behalf
-/// of a connecting client (clients have no usable local copy of host rooms).
+/// of a connecting client (clients have no usable local copy of host rooms). An
+/// `announcement_only` room starts with posting kept to its moderators.
 pub async fn create_room_internal(
     pool: &SqlitePool,
     name: String,
@@ -405,6 +631,7 @@ pub async fn create_room_internal(
     department_id: Option<i64>,
     is_private: Option<bool>,
     created_by: Option<i64>,
+    announcement_only: Option<bool>,
 ) -> AppResult<ChatRoom> {
     let name = name.trim().to_string();
     if name.is_empty() || name.chars().count() > 64 {
@@ -413,6 +640,17 @@ pub async fn create_room_internal(
         ));
     }
     let is_private = is_private.unwrap_or(false);
+    if is_private {
+        let guest: Option<bool> = sqlx::query_scalar("SELECT is_guest FROM users WHERE id = $1")
+            .bind(created_by)
+            .fetch_optional(pool)
+            .await?;
+        if guest == Some(true) {
+            return Err(AppError::Validation(
+                "Guests can't create private channels".to_string(),
+            ));
+        }
+    }
 
     let result = sqlx::query(
         "INSERT INTO chat_rooms (name, description, department_id, is_private, created_by)
@@ -448,6 +686,14 @@ pub async fn create_room_internal(
         .await?; // sqlx::Error → AppError::Db
     }
 
+    if announcement_only.unwrap_or(false) {
+        let permissions = room_permissions::RoomPermissions {
+            post: room_permissions::Who::Moderators,
+            ..Default::default()
+        };
+        room_permissions::set_internal(pool, id, &permissions, created_by).await?;
+    }
+
     let row = sqlx::query(
         "SELECT cr.id, cr.name, cr.description, cr.department_id, cr.is_private,
                 d.name as department_name, 0 as user_count
@@ -461,6 +707,77 @@ pub async fn create_room_internal(
     Ok(row_to_room(&row))
 }
 
+/// Most tags a room can carry.
+const MAX_ROOM_TAGS: usize = 10;
+
+/// Normalize tags for storage and lookup: trimmed, lowercase, inner whitespace → `-`, deduped
+/// and sorted. Each is 1–32 characters and can't contain a comma (the list separator).
+fn normalize_tags(tags: &[String]) -> AppResult<Vec<String>> {
+    let mut out: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-")
+            .to_lowercase();
+        if tag.is_empty() || tag.chars().count() > 32 || tag.contains(',') {
+            return Err(AppError::Validation(
+                "Tags must be 1–32 characters with no commas".to_string(),
+            ));
+        }
+        if !out.contains(&tag) {
+            out.push(tag);
+        }
+    }
+    out.sort();
+    Ok(out)
+}
+
+/// Replace a room's tags. Returns the normalized set actually stored.
+#[tauri::command]
+pub async fn set_room_tags(
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    tags: Vec<String>,
+) -> AppResult<Vec<String>> {
+    set_room_tags_internal(&db, room_id, &tags).await
+}
+
+pub async fn set_room_tags_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    tags: &[String],
+) -> AppResult<Vec<String>> {
+    let tags = normalize_tags(tags)?;
+    if tags.len() > MAX_ROOM_TAGS {
+        return Err(AppError::Validation(format!(
+            "A room can have at most {} tags",
+            MAX_ROOM_TAGS
+        )));
+    }
+    let mut tx = pool.begin().await?;
+    let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM chat_rooms WHERE id = $1")
+        .bind(room_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if exists.is_none() {
+        return Err(AppError::Validation("Room not found".to_string()));
+    }
+    sqlx::query("DELETE FROM room_tags WHERE room_id = $1")
+        .bind(room_id)
+        .execute(&mut *tx)
+        .await?;
+    for tag in &tags {
+        sqlx::query("INSERT INTO room_tags (room_id, tag) VALUES ($1, $2)")
+            .bind(room_id)
+            .bind(tag)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(tags)
+}
+
 #[tauri::command]
 pub async fn join_room(
     db: State<'_, SqlitePool>,
@@ -481,16 +798,17 @@ pub async fn join_room(
     Ok(())
 }
 
-#[tauri::command]
-pub async fn leave_room(
-    db: State<'_, SqlitePool>,
+/// Mark `user_id`'s membership of `room_id` inactive. The `leave_room` command (sockets.rs)
+/// also tells the network.
+pub async fn leave_room_internal(
+    pool: &SqlitePool,
     user_id: i64,
     room_id: i64,
 ) -> Result<(), String> {
     sqlx::query("UPDATE user_rooms SET is_active = 0 WHERE user_id = $1 AND room_id = $2")
         .bind(user_id)
         .bind(room_id)
-        .execute(&*db)
+        .execute(pool)
         .await
         .map_err(|e| format!("Failed to leave room: {}", e))?;
 
@@ -498,17 +816,26 @@ pub async fn leave_room(
 }
 
 /// Whether `user_id` may open `room_id`: the room is public, or the user created it, or the
-/// user is an active member. Unknown room → not allowed. Used to enforce private channels.
+/// user is an active member — and, for a private room of a department, is in that department
+/// or an admin. Unknown room → not allowed. Used to enforce private channels.
 pub async fn room_join_allowed_internal(
     pool: &SqlitePool,
     user_id: i64,
     room_id: i64,
 ) -> Result<bool, String> {
+    // Guests only ever get public rooms, whatever memberships they hold.
     let allowed: Option<bool> = sqlx::query_scalar(
         "SELECT (cr.is_private = 0
-                 OR cr.created_by = $1
-                 OR EXISTS (SELECT 1 FROM user_rooms ur
-                            WHERE ur.room_id = cr.id AND ur.user_id = $1 AND ur.is_active = 1))
+                 OR (NOT EXISTS (SELECT 1 FROM users g WHERE g.id = $1 AND g.is_guest = 1)
+                     AND (cr.created_by = $1
+                          OR EXISTS (SELECT 1 FROM user_rooms ur
+                                     WHERE ur.room_id = cr.id AND ur.user_id = $1
+                                       AND ur.is_active = 1))
+                     AND (cr.department_id IS NULL
+                          OR EXISTS (SELECT 1 FROM users v
+                                     WHERE v.id = $1
+                                       AND (v.department_id = cr.department_id
+                                            OR v.is_admin = 1)))))
          FROM chat_rooms cr
          WHERE cr.id = $2",
     )
@@ -521,7 +848,8 @@ pub async fn room_join_allowed_internal(
 }
 
 /// Add `user_id` to `room_id` (an invite). Only someone who can already access the room
-/// (its creator or an active member) may add others.
+/// (its creator or an active member) may add others, and only a moderator if the room keeps
+/// invites to them.
 #[tauri::command]
 pub async fn add_room_member(
     db: State<'_, SqlitePool>,
@@ -529,6 +857,14 @@ pub async fn add_room_member(
     user_id: i64,
     actor_id: i64,
 ) -> Result<(), String> {
+    room_permissions::check(
+        &db,
+        room_id,
+        Some(actor_id),
+        room_permissions::Action::Invite,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
     add_room_member_internal(&db, room_id, user_id, actor_id).await
 }
 
@@ -542,6 +878,18 @@ pub async fn add_room_member_internal(
     if !room_join_allowed_internal(pool, actor_id, room_id).await? {
         return Err("Only members can add people to this channel".to_string());
     }
+    let guest_into_private: Option<bool> = sqlx::query_scalar(
+        "SELECT cr.is_private FROM chat_rooms cr, users u
+         WHERE cr.id = $1 AND u.id = $2 AND u.is_guest = 1",
+    )
+    .bind(room_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to add member: {}", e))?;
+    if guest_into_private == Some(true) {
+        return Err("Guests can only join public channels".to_string());
+    }
     sqlx::query(
         "INSERT INTO user_rooms (user_id, room_id, is_active) VALUES ($1, $2, 1)
          ON CONFLICT(user_id, room_id) DO UPDATE SET is_active = 1",
@@ -580,13 +928,15 @@ pub async fn get_or_create_dm_internal(
     // Validate every member exists up front so we never create a half-populated DM room
     // (a missing target would otherwise fail the FK insert *after* the room row is created).
     for m in &members {
-        let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM users WHERE id = $1")
+        let is_guest: Option<bool> = sqlx::query_scalar("SELECT is_guest FROM users WHERE id = $1")
             .bind(m)
             .fetch_optional(pool)
             .await
             .map_err(|e| format!("Failed to validate DM member: {}", e))?;
-        if exists.is_none() {
-            return Err(format!("Unknown user {}", m));
+        match is_guest {
+            None => return Err(format!("Unknown user {}", m)),
+            Some(true) => return Err("Guests can't take part in direct messages".to_string()),
+            Some(false) => {}
         }
     }
 
@@ -683,11 +1033,24 @@ pub struct DirectoryUser {
     pub is_online: bool,
 }
 
-pub async fn list_users_internal(pool: &SqlitePool) -> Result<Vec<DirectoryUser>, String> {
-    let rows = sqlx::query("SELECT id, name, is_online FROM users ORDER BY name")
-        .fetch_all(pool)
-        .await
-        .map_err(|e| format!("Failed to list users: {}", e))?;
+/// The invite/DM directory `viewer` gets: department-scoped like `get_users_internal`.
+pub async fn list_users_internal(
+    pool: &SqlitePool,
+    viewer: i64,
+) -> Result<Vec<DirectoryUser>, String> {
+    let rows = sqlx::query(&format!(
+        "SELECT u.id, u.name, u.is_online
+         FROM users u
+         LEFT JOIN departments d ON u.department_id = d.id
+         LEFT JOIN users v ON v.id = $1
+         WHERE {}
+         ORDER BY u.name",
+        VISIBLE_USERS
+    ))
+    .bind(viewer)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to list users: {}", e))?;
     Ok(rows
         .into_iter()
         .map(|row| DirectoryUser {
@@ -699,8 +1062,11 @@ pub async fn list_users_internal(pool: &SqlitePool) -> Result<Vec<DirectoryUser>
 }
 
 #[tauri::command]
-pub async fn list_users(db: State<'_, SqlitePool>) -> Result<Vec<DirectoryUser>, String> {
-    list_users_internal(&db).await
+pub async fn list_users(
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+) -> Result<Vec<DirectoryUser>, String> {
+    list_users_internal(&db, user_id).await
 }
 
 // Message management
@@ -727,6 +1093,7 @@ pub async fn save_message(
     .await
 }
 
+#[tracing::instrument(name = "persist", skip_all, fields(room_id = room_id, user_id = user_id, message_id = %message_id))]
 pub async fn save_message_internal(
     pool: &SqlitePool,
     room_id: i64,
@@ -737,9 +1104,11 @@ pub async fn save_message_internal(
     message_id: String,
 ) -> Result<InsertResult, String> {
     // ON CONFLICT(message_id) DO NOTHING makes retried/echoed saves idempotent.
+    let started = std::time::Instant::now();
+    let hlc = crate::message_log::stamp(pool).await;
     let result = sqlx::query(
-        "INSERT INTO messages (room_id, user_id, message, message_type, is_emoji, message_id)
-         VALUES ($1, $2, $3, $4, $5, $6)
+        "INSERT INTO messages (room_id, user_id, message, message_type, is_emoji, message_id, hlc)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
          ON CONFLICT(message_id) DO NOTHING",
     )
     .bind(room_id)
@@ -748,9 +1117,13 @@ pub async fn save_message_internal(
     .bind(&message_type)
     .bind(is_emoji)
     .bind(&message_id)
+    .bind(&hlc)
     .execute(pool)
     .await
     .map_err(|e| format!("Failed to save message: {}", e))?;
+    crate::metrics::METRICS
+        .db_write_latency
+        .observe(started.elapsed());
 
     Ok(InsertResult {
         rows_affected: result.rows_affected(),
@@ -780,7 +1153,7 @@ pub async fn get_room_messages_internal(
 ) -> Result<Vec<Message>, String> {
     let result = sqlx::query(
         "SELECT m.id, m.message_id, m.room_id, m.user_id, m.message, m.message_type, m.is_emoji, m.created_at,
-                m.edited_at, m.deleted_at, COALESCE(u.name, 'Unknown') as username
+                m.edited_at, m.deleted_at, m.language, m.filename, COALESCE(u.name, 'Unknown') as username
          FROM messages m
          LEFT JOIN users u ON m.user_id = u.id
          WHERE m.room_id = $1 AND ($2 IS NULL OR m.id < $2)
@@ -794,30 +1167,205 @@ pub async fn get_room_messages_internal(
     .await
     .map_err(|e| format!("Failed to get room messages: {}", e))?;
 
-    let mut messages = Vec::new();
-    for row in result {
-        messages.push(Message {
-            id: row.get::<Option<i64>, _>("id"),
-            message_id: row.get::<Option<String>, _>("message_id"),
-            room_id: row.get::<i64, _>("room_id"),
-            user_id: row.get::<i64, _>("user_id"),
-            username: row.get::<String, _>("username"),
-            message: row.get::<String, _>("message"),
-            message_type: row.get::<String, _>("message_type"),
-            is_emoji: row.get::<bool, _>("is_emoji"),
-            created_at: row.get::<String, _>("created_at"),
-            edited_at: row.get::<Option<String>, _>("edited_at"),
-            deleted_at: row.get::<Option<String>, _>("deleted_at"),
-        });
-    }
+    let mut messages: Vec<Message> = result.iter().map(message_from_row).collect();
 
     // Reverse to get chronological order
     messages.reverse();
     Ok(messages)
 }
 
-/// Edit a message's text, but only if `user_id` is the author and it isn't deleted.
-/// Returns the number of rows affected (0 = not found / not authorized).
+fn message_from_row(row: &SqliteRow) -> Message {
+    Message {
+        id: row.get::<Option<i64>, _>("id"),
+        message_id: row.get::<Option<String>, _>("message_id"),
+        room_id: row.get::<i64, _>("room_id"),
+        user_id: row.get::<i64, _>("user_id"),
+        username: row.get::<String, _>("username"),
+        message: row.get::<String, _>("message"),
+        message_type: row.get::<String, _>("message_type"),
+        is_emoji: row.get::<bool, _>("is_emoji"),
+        created_at: row.get::<String, _>("created_at"),
+        edited_at: row.get::<Option<String>, _>("edited_at"),
+        deleted_at: row.get::<Option<String>, _>("deleted_at"),
+        language: row.get::<Option<String>, _>("language"),
+        filename: row.get::<Option<String>, _>("filename"),
+    }
+}
+
+/// A window of a room's history centred on one message.
+#[derive(Serialize)]
+pub struct MessageContext {
+    pub room_id: i64,
+    // Oldest → newest, the target included.
+    pub messages: Vec<Message>,
+    // Whether the room has more messages outside the window (page older ones with
+    // get_room_messages' before_id).
+    pub has_older: bool,
+    pub has_newer: bool,
+}
+
+/// Open a room at a message (a search hit, a pin, a star) without loading the whole room: the
+/// message plus up to `before` older and `after` newer ones (25 each by default, at most 100).
+#[tauri::command]
+pub async fn get_message_context(
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+    message_id: String,
+    before: Option<i64>,
+    after: Option<i64>,
+) -> Result<MessageContext, String> {
+    get_message_context_internal(
+        &db,
+        user_id,
+        &message_id,
+        before.unwrap_or(25),
+        after.unwrap_or(25),
+    )
+    .await
+}
+
+pub async fn get_message_context_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    message_id: &str,
+    before: i64,
+    after: i64,
+) -> Result<MessageContext, String> {
+    let (before, after) = (before.clamp(0, 100), after.clamp(0, 100));
+    let target: Option<(i64, i64)> =
+        sqlx::query_as("SELECT id, room_id FROM messages WHERE message_id = $1")
+            .bind(message_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| format!("Failed to find message: {}", e))?;
+    // A message in a room the user can't open looks the same as one that doesn't exist.
+    let Some((id, room_id)) = target else {
+        return Err("Message not found".to_string());
+    };
+    if !room_join_allowed_internal(pool, user_id, room_id).await? {
+        return Err("Message not found".to_string());
+    }
+
+    // One past each limit, to tell whether there's more.
+    let older = sqlx::query(
+        "SELECT m.id, m.message_id, m.room_id, m.user_id, m.message, m.message_type, m.is_emoji, m.created_at,
+                m.edited_at, m.deleted_at, m.language, m.filename, COALESCE(u.name, 'Unknown') as username
+         FROM messages m
+         LEFT JOIN users u ON m.user_id = u.id
+         WHERE m.room_id = $1 AND m.id <= $2
+         ORDER BY m.id DESC
+         LIMIT $3",
+    )
+    .bind(room_id)
+    .bind(id)
+    .bind(before + 2)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to get message context: {}", e))?;
+    let newer = sqlx::query(
+        "SELECT m.id, m.message_id, m.room_id, m.user_id, m.message, m.message_type, m.is_emoji, m.created_at,
+                m.edited_at, m.deleted_at, m.language, m.filename, COALESCE(u.name, 'Unknown') as username
+         FROM messages m
+         LEFT JOIN users u ON m.user_id = u.id
+         WHERE m.room_id = $1 AND m.id > $2
+         ORDER BY m.id ASC
+         LIMIT $3",
+    )
+    .bind(room_id)
+    .bind(id)
+    .bind(after + 1)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to get message context: {}", e))?;
+
+    // `older` holds the target itself first, then up to before + 1 older rows.
+    let has_older = older.len() as i64 > before + 1;
+    let has_newer = newer.len() as i64 > after;
+    let mut messages: Vec<Message> = older
+        .iter()
+        .take(before as usize + 1)
+        .map(message_from_row)
+        .collect();
+    messages.reverse();
+    messages.extend(newer.iter().take(after as usize).map(message_from_row));
+    Ok(MessageContext {
+        room_id,
+        messages,
+        has_older,
+        has_newer,
+    })
+}
+
+/// Save a code snippet: like save_message_internal, plus its language and filename. The text is
+/// stored exactly as given.
+pub async fn save_code_snippet_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    user_id: i64,
+    code: &str,
+    language: Option<&str>,
+    filename: Option<&str>,
+    message_id: &str,
+) -> Result<InsertResult, String> {
+    let hlc = crate::message_log::stamp(pool).await;
+    let result = sqlx::query(
+        "INSERT INTO messages (room_id, user_id, message, message_type, is_emoji, message_id,
+                               language, filename, hlc)
+         VALUES ($1, $2, $3, 'Code', FALSE, $4, $5, $6, $7)
+         ON CONFLICT(message_id) DO NOTHING",
+    )
+    .bind(room_id)
+    .bind(user_id)
+    .bind(code)
+    .bind(message_id)
+    .bind(language)
+    .bind(filename)
+    .bind(&hlc)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save code snippet: {}", e))?;
+    Ok(InsertResult {
+        rows_affected: result.rows_affected(),
+        last_insert_id: result.last_insert_rowid(),
+    })
+}
+
+/// Save a shared location: like save_message_internal, with its coordinates (if any) in their
+/// own columns. `location` is the message's JSON.
+pub async fn save_location_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    user_id: i64,
+    location: &str,
+    message_id: &str,
+) -> Result<InsertResult, String> {
+    let coordinates = crate::location::parse(location);
+    let hlc = crate::message_log::stamp(pool).await;
+    let result = sqlx::query(
+        "INSERT INTO messages (room_id, user_id, message, message_type, is_emoji, message_id,
+                               latitude, longitude, hlc)
+         VALUES ($1, $2, $3, 'Location', FALSE, $4, $5, $6, $7)
+         ON CONFLICT(message_id) DO NOTHING",
+    )
+    .bind(room_id)
+    .bind(user_id)
+    .bind(location)
+    .bind(message_id)
+    .bind(coordinates.as_ref().and_then(|c| c.latitude))
+    .bind(coordinates.as_ref().and_then(|c| c.longitude))
+    .bind(&hlc)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save location: {}", e))?;
+    Ok(InsertResult {
+        rows_affected: result.rows_affected(),
+        last_insert_id: result.last_insert_rowid(),
+    })
+}
+
+/// Edit a message's text, but only if `user_id` is the author and it isn't deleted (or a
+/// sticker / code snippet). Returns the number of rows affected (0 = not found / not
+/// authorized).
 pub async fn edit_message_db(
     pool: &SqlitePool,
     message_id: &str,
@@ -827,7 +1375,8 @@ pub async fn edit_message_db(
     let res = sqlx::query(
         "UPDATE messages
             SET message = $1, edited_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
-          WHERE message_id = $2 AND user_id = $3 AND deleted_at IS NULL",
+          WHERE message_id = $2 AND user_id = $3 AND deleted_at IS NULL
+            AND message_type NOT IN ('Sticker', 'Code', 'Attachment', 'Contact', 'Location')",
     )
     .bind(new_text)
     .bind(message_id)
@@ -838,12 +1387,24 @@ pub async fn edit_message_db(
     Ok(res.rows_affected())
 }
 
-/// Soft-delete a message (clears text), only if `user_id` is the author.
+/// Soft-delete a message (clears text), only if `user_id` is the author. Refused outright for a
+/// room under legal hold.
 pub async fn delete_message_db(
     pool: &SqlitePool,
     message_id: &str,
     user_id: i64,
 ) -> Result<u64, String> {
+    let held: Option<bool> = sqlx::query_scalar(
+        "SELECT cr.legal_hold FROM messages m JOIN chat_rooms cr ON cr.id = m.room_id
+         WHERE m.message_id = $1",
+    )
+    .bind(message_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to delete message: {}", e))?;
+    if held == Some(true) {
+        return Err("Messages in this room are under legal hold and can't be deleted".to_string());
+    }
     let res = sqlx::query(
         "UPDATE messages
             SET message = '', deleted_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
@@ -857,23 +1418,81 @@ pub async fn delete_message_db(
     Ok(res.rows_affected())
 }
 
-#[derive(Serialize)]
-pub struct SearchResult {
-    pub message_id: Option<String>,
-    pub room_id: i64,
-    pub room_name: String,
-    pub username: String,
-    pub message: String,
-    pub created_at: String,
-}
-
-/// Full-text-ish search across non-deleted chat messages (case-insensitive LIKE).
-#[tauri::command]
-pub async fn search_messages(
-    db: State<'_, SqlitePool>,
-    query: String,
-    limit: Option<i64>,
-) -> Result<Vec<SearchResult>, String> {
+/// Unsend a message: remove it outright, with its reactions and queued notifications, if
+/// `user_id` wrote it less than `window_secs` ago (never when `window_secs` is 0). Refused for a
+/// room under legal hold. Returns the number of messages removed (0 = not the author, already
+/// deleted, or too old — tombstone it with delete_message_db instead).
+pub async fn unsend_message_db(
+    pool: &SqlitePool,
+    message_id: &str,
+    user_id: i64,
+    window_secs: u64,
+) -> Result<u64, String> {
+    let held: Option<bool> = sqlx::query_scalar(
+        "SELECT cr.legal_hold FROM messages m JOIN chat_rooms cr ON cr.id = m.room_id
+         WHERE m.message_id = $1",
+    )
+    .bind(message_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to unsend message: {}", e))?;
+    if held == Some(true) {
+        return Err("Messages in this room are under legal hold and can't be deleted".to_string());
+    }
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to unsend message: {}", e))?;
+    let removed = sqlx::query(
+        "DELETE FROM messages
+          WHERE message_id = $1 AND user_id = $2 AND deleted_at IS NULL AND $3 > 0
+            AND julianday(created_at) >= julianday('now', '-' || $3 || ' seconds')",
+    )
+    .bind(message_id)
+    .bind(user_id)
+    .bind(window_secs as i64)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to unsend message: {}", e))?
+    .rows_affected();
+    if removed > 0 {
+        for sql in [
+            "DELETE FROM reactions WHERE message_id = $1",
+            "DELETE FROM email_outbox WHERE message_id = $1",
+            "DELETE FROM announcement_receipts WHERE message_id = $1",
+        ] {
+            sqlx::query(sql)
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to unsend message: {}", e))?;
+        }
+    }
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to unsend message: {}", e))?;
+    Ok(removed)
+}
+
+#[derive(Serialize)]
+pub struct SearchResult {
+    pub message_id: Option<String>,
+    pub room_id: i64,
+    pub room_name: String,
+    pub username: String,
+    pub message: String,
+    pub created_at: String,
+    // For an image found by the text in it: that text (see ocr.rs).
+    pub image_text: Option<String>,
+}
+
+/// Full-text-ish search across non-deleted chat messages and the text read from shared images
+/// (case-insensitive LIKE).
+pub async fn search_messages_internal(
+    pool: &SqlitePool,
+    query: &str,
+    limit: Option<i64>,
+) -> Result<Vec<SearchResult>, String> {
     let q = query.trim();
     if q.is_empty() {
         return Ok(Vec::new());
@@ -887,20 +1506,21 @@ pub async fn search_messages(
     let pattern = format!("%{}%", escaped);
 
     let rows = sqlx::query(
-        "SELECT m.message_id, m.room_id, m.message, m.created_at,
+        "SELECT m.message_id, m.room_id, m.message, m.created_at, a.ocr_text,
                 COALESCE(u.name, 'Unknown') AS username, cr.name AS room_name
          FROM messages m
          LEFT JOIN users u ON m.user_id = u.id
          JOIN chat_rooms cr ON m.room_id = cr.id
+         LEFT JOIN attachments a ON a.message_id = m.message_id AND m.message_type = 'Attachment'
          WHERE m.deleted_at IS NULL
-           AND m.message_type = 'Chat'
-           AND m.message LIKE $1 ESCAPE '\\'
+           AND ((m.message_type IN ('Chat', 'Code') AND m.message LIKE $1 ESCAPE '\\')
+                OR a.ocr_text LIKE $1 ESCAPE '\\')
          ORDER BY m.created_at DESC, m.id DESC
          LIMIT $2",
     )
     .bind(&pattern)
     .bind(limit)
-    .fetch_all(&*db)
+    .fetch_all(pool)
     .await
     .map_err(|e| format!("Search failed: {}", e))?;
 
@@ -913,11 +1533,165 @@ pub async fn search_messages(
             username: row.get::<String, _>("username"),
             message: row.get::<String, _>("message"),
             created_at: row.get::<String, _>("created_at"),
+            image_text: row.get::<Option<String>, _>("ocr_text"),
         });
     }
     Ok(results)
 }
 
+#[tauri::command]
+pub async fn search_messages(
+    db: State<'_, SqlitePool>,
+    query: String,
+    limit: Option<i64>,
+) -> Result<Vec<SearchResult>, String> {
+    search_messages_internal(&db, &query, limit).await
+}
+
+#[derive(Serialize, Debug)]
+pub struct DirectoryHit {
+    // "user" or "room".
+    pub kind: &'static str,
+    pub id: i64,
+    pub name: String,
+    // Email for users, description for rooms.
+    pub detail: Option<String>,
+    // 0–1, higher is a better match; results come sorted by it.
+    pub score: f64,
+}
+
+// Padded character trigrams, so short words and word boundaries still contribute.
+fn trigrams(s: &str) -> Vec<[char; 3]> {
+    let chars: Vec<char> = format!("  {} ", s).chars().collect();
+    let mut grams: Vec<[char; 3]> = chars.windows(3).map(|w| [w[0], w[1], w[2]]).collect();
+    grams.sort();
+    grams.dedup();
+    grams
+}
+
+/// How well `candidate` matches the (lowercased, trimmed) query: exact > prefix > word prefix >
+/// substring, then trigram similarity for typos. None below the noise threshold.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<f64> {
+    let c = candidate.to_lowercase();
+    if c == query {
+        return Some(1.0);
+    }
+    if c.starts_with(query) {
+        return Some(0.9);
+    }
+    if c.split(|ch: char| !ch.is_alphanumeric())
+        .any(|word| word.starts_with(query))
+    {
+        return Some(0.8);
+    }
+    if c.contains(query) {
+        return Some(0.7);
+    }
+    // Dice coefficient over trigrams.
+    let (a, b) = (trigrams(query), trigrams(&c));
+    let shared = a.iter().filter(|g| b.binary_search(g).is_ok()).count();
+    let dice = 2.0 * shared as f64 / (a.len() + b.len()) as f64;
+    (dice >= 0.3).then_some(0.6 * dice)
+}
+
+/// Quick-switcher search: fuzzy-match users (name or email) and the rooms `user_id` can see.
+/// SQL narrows candidates to those sharing a character pair with the query; ranking is done
+/// here. Bots (`.invalid` addresses) and DMs are left out.
+#[tauri::command]
+pub async fn search_directory(
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<DirectoryHit>, String> {
+    search_directory_internal(&db, user_id, &query, limit.unwrap_or(20)).await
+}
+
+pub async fn search_directory_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<DirectoryHit>, String> {
+    let q = query.trim().to_lowercase();
+    if q.is_empty() {
+        return Ok(Vec::new());
+    }
+    let chars: Vec<char> = q.chars().collect();
+    let pairs: Vec<String> = if chars.len() < 2 {
+        vec![q.clone()]
+    } else {
+        chars.windows(2).map(|w| w.iter().collect()).collect()
+    };
+    let pairs = serde_json::to_string(&pairs).map_err(|e| e.to_string())?;
+
+    let users = sqlx::query(
+        "SELECT u.id, u.name, u.email FROM users u
+         WHERE u.email NOT LIKE '%.invalid'
+           AND EXISTS (SELECT 1 FROM json_each($1) g
+                       WHERE instr(lower(u.name), g.value) > 0
+                          OR instr(lower(u.email), g.value) > 0)",
+    )
+    .bind(&pairs)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Directory search failed: {}", e))?;
+    let rooms = sqlx::query(
+        "SELECT cr.id, cr.name, cr.description FROM chat_rooms cr
+         WHERE cr.is_dm = 0
+           AND (cr.is_private = 0
+                OR cr.created_by = $2
+                OR EXISTS (SELECT 1 FROM user_rooms ur
+                           WHERE ur.room_id = cr.id AND ur.user_id = $2 AND ur.is_active = 1))
+           AND EXISTS (SELECT 1 FROM json_each($1) g WHERE instr(lower(cr.name), g.value) > 0)",
+    )
+    .bind(&pairs)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Directory search failed: {}", e))?;
+
+    let mut hits = Vec::new();
+    for row in users {
+        let name: String = row.get("name");
+        let email: String = row.get("email");
+        // The email's local part only, so a shared domain doesn't match everyone.
+        let local = email.split('@').next().unwrap_or_default();
+        let score = match (fuzzy_score(&q, &name), fuzzy_score(&q, local)) {
+            (Some(a), Some(b)) => Some(a.max(b * 0.95)),
+            (a, b) => a.or(b.map(|b| b * 0.95)),
+        };
+        if let Some(score) = score {
+            hits.push(DirectoryHit {
+                kind: "user",
+                id: row.get("id"),
+                name,
+                detail: Some(email),
+                score,
+            });
+        }
+    }
+    for row in rooms {
+        let name: String = row.get("name");
+        if let Some(score) = fuzzy_score(&q, &name) {
+            hits.push(DirectoryHit {
+                kind: "room",
+                id: row.get("id"),
+                name,
+                detail: row.get("description"),
+                score,
+            });
+        }
+    }
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    hits.truncate(limit.clamp(1, 50));
+    Ok(hits)
+}
+
 #[derive(Serialize)]
 pub struct ReactionAggregate {
     pub message_id: String,
@@ -1060,7 +1834,7 @@ pub async fn get_unread_counts_internal(
          FROM messages m
          JOIN user_rooms ur ON ur.room_id = m.room_id AND ur.user_id = $1
          WHERE m.user_id != $1
-           AND m.message_type = 'Chat'
+           AND m.message_type IN ('Chat', 'Sticker', 'Code', 'Attachment', 'Contact', 'Location')
            AND m.deleted_at IS NULL
            AND (ur.last_read_at IS NULL OR m.created_at > ur.last_read_at)
          GROUP BY m.room_id
@@ -1088,6 +1862,97 @@ pub async fn get_unread_counts(
     get_unread_counts_internal(&db, user_id).await
 }
 
+// App settings (host-local key/value store; values are JSON text)
+
+pub async fn get_setting_internal(pool: &SqlitePool, key: &str) -> Result<Option<String>, String> {
+    sqlx::query("SELECT value FROM app_settings WHERE key = $1")
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+        .map(|row| row.map(|r| r.get::<String, _>("value")))
+        .map_err(|e| format!("Failed to read setting {}: {}", key, e))
+}
+
+pub async fn set_setting_internal(pool: &SqlitePool, key: &str, value: &str) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO app_settings (key, value) VALUES ($1, $2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(key)
+    .bind(value)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save setting {}: {}", key, e))?;
+    Ok(())
+}
+
+// Audit log (append-only record of admin actions)
+
+#[derive(Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor_user_id: Option<i64>,
+    pub actor_name: Option<String>,
+    pub action: String,
+    pub target: String,
+    pub detail: Option<String>,
+    pub created_at: String,
+}
+
+/// Append an audit entry. `actor_user_id` None = the system (scheduled jobs).
+pub async fn record_audit_internal(
+    pool: &SqlitePool,
+    actor_user_id: Option<i64>,
+    action: &str,
+    target: &str,
+    detail: Option<&str>,
+) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO audit_log (actor_user_id, action, target, detail) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(actor_user_id)
+    .bind(action)
+    .bind(target)
+    .bind(detail)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to write audit log: {}", e))?;
+    Ok(())
+}
+
+/// Newest-first page of the audit log; `before_id` continues from the last page.
+#[tauri::command]
+pub async fn get_audit_log(
+    db: State<'_, SqlitePool>,
+    limit: Option<i64>,
+    before_id: Option<i64>,
+) -> Result<Vec<AuditEntry>, String> {
+    let rows = sqlx::query(
+        "SELECT a.id, a.actor_user_id, u.name AS actor_name, a.action, a.target, a.detail,
+                a.created_at
+         FROM audit_log a LEFT JOIN users u ON u.id = a.actor_user_id
+         WHERE $1 IS NULL OR a.id < $1
+         ORDER BY a.id DESC LIMIT $2",
+    )
+    .bind(before_id)
+    .bind(limit.unwrap_or(100).clamp(1, 500))
+    .fetch_all(db.inner())
+    .await
+    .map_err(|e| format!("Failed to read audit log: {}", e))?;
+    Ok(rows
+        .into_iter()
+        .map(|row| AuditEntry {
+            id: row.get("id"),
+            actor_user_id: row.get("actor_user_id"),
+            actor_name: row.get("actor_name"),
+            action: row.get("action"),
+            target: row.get("target"),
+            detail: row.get("detail"),
+            created_at: row.get::<String, _>("created_at"),
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1176,6 +2041,50 @@ mod tests {
             .is_empty());
     }
 
+    #[tokio::test]
+    async fn code_snippets_round_trip_verbatim() {
+        let pool = setup().await;
+        let code = "server {\n    listen 80;\n\n\troot /srv;  \n}\n";
+        save_code_snippet_internal(&pool, 1, 1, code, Some("nginx"), Some("site.conf"), "c1")
+            .await
+            .unwrap();
+        let msgs = get_room_messages_internal(&pool, 1, 10, None)
+            .await
+            .unwrap();
+        let snippet = msgs
+            .iter()
+            .find(|m| m.message_id.as_deref() == Some("c1"))
+            .unwrap();
+        assert_eq!(snippet.message, code);
+        assert_eq!(snippet.message_type, "Code");
+        assert_eq!(snippet.language.as_deref(), Some("nginx"));
+        assert_eq!(snippet.filename.as_deref(), Some("site.conf"));
+    }
+
+    #[tokio::test]
+    async fn locations_keep_their_coordinates() {
+        let pool = setup().await;
+        let json = r#"{"label":"Dock 4","latitude":51.5,"longitude":-0.12}"#;
+        save_location_internal(&pool, 1, 1, json, "l1")
+            .await
+            .unwrap();
+        let (lat, lon): (Option<f64>, Option<f64>) =
+            sqlx::query_as("SELECT latitude, longitude FROM messages WHERE message_id = 'l1'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!((lat, lon), (Some(51.5), Some(-0.12)));
+        let msgs = get_room_messages_internal(&pool, 1, 10, None)
+            .await
+            .unwrap();
+        let shared = msgs
+            .iter()
+            .find(|m| m.message_id.as_deref() == Some("l1"))
+            .unwrap();
+        assert_eq!(shared.message_type, "Location");
+        assert_eq!(shared.message, json);
+    }
+
     #[tokio::test]
     async fn touch_last_read_upserts_and_sets_marker() {
         let pool = setup().await;
@@ -1214,6 +2123,52 @@ mod tests {
         assert_eq!(texts, ["one"]);
     }
 
+    #[tokio::test]
+    async fn message_context_windows_around_the_target() {
+        let pool = setup().await;
+        for i in 1..=6 {
+            add(&pool, 1, &format!("msg {}", i), &format!("m{}", i)).await;
+        }
+        let texts = |ctx: &MessageContext| {
+            ctx.messages
+                .iter()
+                .map(|m| m.message.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let ctx = get_message_context_internal(&pool, 1, "m3", 1, 2)
+            .await
+            .unwrap();
+        assert_eq!(ctx.room_id, 1);
+        assert_eq!(texts(&ctx), ["msg 2", "msg 3", "msg 4", "msg 5"]);
+        assert!(ctx.has_older && ctx.has_newer);
+
+        let ctx = get_message_context_internal(&pool, 1, "m2", 5, 0)
+            .await
+            .unwrap();
+        assert_eq!(texts(&ctx), ["msg 1", "msg 2"]);
+        assert!(!ctx.has_older && ctx.has_newer);
+
+        assert!(get_message_context_internal(&pool, 1, "nope", 5, 5)
+            .await
+            .is_err());
+        // A private room Alice isn't in hides its messages.
+        sqlx::raw_sql(
+            "INSERT INTO chat_rooms (id, name, is_private, created_by) VALUES (100, 'hush', 1, 2);
+             INSERT INTO messages (room_id, user_id, message, message_type, message_id)
+                 VALUES (100, 2, 'psst', 'Chat', 'p1');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert!(get_message_context_internal(&pool, 1, "p1", 5, 5)
+            .await
+            .is_err());
+        assert!(get_message_context_internal(&pool, 2, "p1", 5, 5)
+            .await
+            .is_ok());
+    }
+
     #[tokio::test]
     async fn edit_is_author_scoped_and_sets_edited_at() {
         let pool = setup().await;
@@ -1247,6 +2202,33 @@ mod tests {
         assert!(msgs[0].deleted_at.is_some());
     }
 
+    #[tokio::test]
+    async fn unsend_removes_only_recent_own_messages() {
+        let pool = setup().await;
+        add(&pool, 1, "oops", "m1").await;
+        insert_at(&pool, 1, "Chat", "2020-01-01 00:00:00", "old").await;
+        toggle_reaction_db(&pool, "m1", 2, "👍").await.unwrap();
+
+        assert_eq!(unsend_message_db(&pool, "m1", 2, 300).await.unwrap(), 0); // Bob can't
+        assert_eq!(unsend_message_db(&pool, "m1", 1, 0).await.unwrap(), 0); // window off
+        assert_eq!(unsend_message_db(&pool, "old", 1, 300).await.unwrap(), 0); // too late
+        assert_eq!(unsend_message_db(&pool, "m1", 1, 300).await.unwrap(), 1);
+
+        let msgs = get_room_messages_internal(&pool, 1, 50, None)
+            .await
+            .unwrap();
+        let ids: Vec<_> = msgs
+            .iter()
+            .filter_map(|m| m.message_id.as_deref())
+            .collect();
+        assert_eq!(ids, ["old"]);
+        let reactions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reactions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(reactions, 0);
+    }
+
     #[tokio::test]
     async fn reaction_toggle_updates_count_and_me_flag() {
         let pool = setup().await;
@@ -1403,4 +2385,294 @@ mod tests {
         assert!(allowed);
         assert!(!denied);
     }
+
+    #[tokio::test]
+    async fn settings_round_trip_and_overwrite() {
+        let pool = setup().await;
+        assert_eq!(get_setting_internal(&pool, "k").await.unwrap(), None);
+        set_setting_internal(&pool, "k", "1").await.unwrap();
+        set_setting_internal(&pool, "k", "2").await.unwrap();
+        assert_eq!(
+            get_setting_internal(&pool, "k").await.unwrap().as_deref(),
+            Some("2")
+        );
+    }
+
+    #[tokio::test]
+    async fn upsert_without_department_keeps_the_synced_one() {
+        let pool = setup().await;
+        let sales = ensure_department_internal(&pool, "Sales").await.unwrap();
+        assert_eq!(
+            ensure_department_internal(&pool, "Sales").await.unwrap(),
+            sales
+        );
+
+        upsert_user_internal(&pool, "Alice".into(), "a@x".into(), Some(sales))
+            .await
+            .unwrap();
+        // A reconnect upserts with no department; the directory-assigned one must survive.
+        let user = upsert_user_internal(&pool, "Alice".into(), "a@x".into(), None)
+            .await
+            .unwrap();
+        assert_eq!(user.department_id, Some(sales));
+    }
+
+    #[tokio::test]
+    async fn room_tags_are_normalized_and_filter_the_room_list() {
+        let pool = setup().await;
+        let tags =
+            set_room_tags_internal(&pool, 1, &["Ops".into(), " on call ".into(), "ops".into()])
+                .await
+                .unwrap();
+        assert_eq!(tags, ["on-call", "ops"]);
+        assert!(set_room_tags_internal(&pool, 1, &["a,b".into()])
+            .await
+            .is_err());
+
+        let all = get_chat_rooms_internal(&pool, 1, &[]).await.unwrap();
+        assert!(all.len() > 1);
+        let room = all.iter().find(|r| r.id == Some(1)).unwrap();
+        assert_eq!(room.tags, ["on-call", "ops"]);
+
+        // Every filter tag must match.
+        let ops = get_chat_rooms_internal(&pool, 1, &["OPS".into()])
+            .await
+            .unwrap();
+        assert_eq!(ops.iter().map(|r| r.id).collect::<Vec<_>>(), [Some(1)]);
+        let none = get_chat_rooms_internal(&pool, 1, &["ops".into(), "hr".into()])
+            .await
+            .unwrap();
+        assert!(none.is_empty());
+    }
+
+    #[tokio::test]
+    async fn private_department_rooms_and_users_are_department_scoped() {
+        let pool = setup().await;
+        let sales = ensure_department_internal(&pool, "Sales").await.unwrap();
+        sqlx::query(
+            "INSERT INTO users (id, name, email, department_id) VALUES (3, 'Carol', 'c@x', $1)",
+        )
+        .bind(sales)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let room = create_room_internal(
+            &pool,
+            "deals".into(),
+            None,
+            Some(sales),
+            Some(true),
+            Some(3),
+            None,
+        )
+        .await
+        .unwrap()
+        .id
+        .unwrap();
+        // Alice is a member, but not in Sales.
+        add_room_member_internal(&pool, room, 1, 3).await.unwrap();
+
+        let listed = |rooms: Vec<ChatRoom>| rooms.iter().any(|r| r.id == Some(room));
+        assert!(listed(
+            get_chat_rooms_internal(&pool, 3, &[]).await.unwrap()
+        ));
+        assert!(!listed(
+            get_chat_rooms_internal(&pool, 1, &[]).await.unwrap()
+        ));
+        // Membership alone doesn't open it either.
+        assert!(!room_join_allowed_internal(&pool, 1, room).await.unwrap());
+        assert!(room_join_allowed_internal(&pool, 3, room).await.unwrap());
+        let names = |users: Vec<User>| users.into_iter().map(|u| u.name).collect::<Vec<_>>();
+        assert_eq!(
+            names(get_users_internal(&pool, 1).await.unwrap()),
+            ["Alice", "Bob"]
+        );
+        // The host-pushed directory is scoped the same way.
+        let directory = list_users_internal(&pool, 1).await.unwrap();
+        assert_eq!(
+            directory.into_iter().map(|u| u.name).collect::<Vec<_>>(),
+            ["Alice", "Bob"]
+        );
+
+        // Admins see every department.
+        crate::moderation::set_user_admin_internal(&pool, 1, true, None)
+            .await
+            .unwrap();
+        assert!(listed(
+            get_chat_rooms_internal(&pool, 1, &[]).await.unwrap()
+        ));
+        assert_eq!(
+            names(get_users_internal(&pool, 1).await.unwrap()),
+            ["Alice", "Bob", "Carol"]
+        );
+        assert!(room_join_allowed_internal(&pool, 1, room).await.unwrap());
+    }
+
+    #[test]
+    fn fuzzy_score_ranks_prefixes_over_typos() {
+        let exact = fuzzy_score("alice", "Alice").unwrap();
+        let prefix = fuzzy_score("ali", "Alice").unwrap();
+        let word = fuzzy_score("gen", "Company General").unwrap();
+        let typo = fuzzy_score("alcie", "Alice").unwrap();
+        assert!(exact > prefix && prefix > word && word > typo);
+        assert!(fuzzy_score("zzz", "Alice").is_none());
+    }
+
+    #[tokio::test]
+    async fn search_directory_matches_users_and_visible_rooms() {
+        let pool = setup().await;
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email) VALUES (3, 'Alerts', 'mqtt-bridge@alerts.invalid');
+             INSERT INTO chat_rooms (id, name, is_private, created_by) VALUES (91, 'alice-secret', 1, 2);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let hits = search_directory_internal(&pool, 1, "alcie", 10)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].kind, hits[0].id), ("user", 1));
+        // Only Bob can see his private room; bots never show up.
+        let hits = search_directory_internal(&pool, 2, "secret", 10)
+            .await
+            .unwrap();
+        assert_eq!(hits.iter().map(|h| h.id).collect::<Vec<_>>(), [91]);
+        assert!(search_directory_internal(&pool, 1, "secret", 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(search_directory_internal(&pool, 1, "alerts", 10)
+            .await
+            .unwrap()
+            .iter()
+            .all(|h| h.name != "Alerts"));
+    }
+
+    #[tokio::test]
+    async fn auto_join_adds_department_and_company_rooms_once() {
+        let pool = setup().await;
+        let general: i64 = sqlx::query_scalar("SELECT id FROM departments WHERE name = 'General'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let other: i64 = sqlx::query_scalar(
+            "SELECT id FROM departments WHERE id NOT IN ($1, 1) ORDER BY id LIMIT 1",
+        )
+        .bind(general)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let expected: Vec<i64> = sqlx::query_scalar(
+            "SELECT cr.id FROM chat_rooms cr
+             WHERE cr.auto_join = 1 AND (cr.department_id IN ($1, $2) OR cr.department_id IS NULL)
+             ORDER BY cr.id",
+        )
+        .bind(general)
+        .bind(1)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert!(
+            expected.len() >= 2,
+            "Company Wide + Alice's department room"
+        );
+
+        let mut joined = apply_auto_join_internal(&pool, 1).await.unwrap();
+        joined.sort();
+        assert_eq!(joined, expected);
+        // Idempotent, and a room Alice left stays left.
+        sqlx::query("UPDATE user_rooms SET is_active = 0 WHERE user_id = 1 AND room_id = $1")
+            .bind(expected[0])
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(apply_auto_join_internal(&pool, 1).await.unwrap().is_empty());
+
+        // Flagging another department's room backfills only that department.
+        let room = create_room_internal(&pool, "ops".into(), None, Some(other), None, None, None)
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+        sqlx::query("UPDATE users SET department_id = $1 WHERE id = 2")
+            .bind(other)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            set_room_auto_join_internal(&pool, room, true)
+                .await
+                .unwrap(),
+            1
+        );
+        let members: Vec<i64> =
+            sqlx::query_scalar("SELECT user_id FROM user_rooms WHERE room_id = $1")
+                .bind(room)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(members, [2]);
+    }
+
+    #[tokio::test]
+    async fn usernames_are_validated_and_unique_per_user() {
+        let pool = setup().await;
+        assert!(validate_username("Jesse O'Neil-Smith").is_ok());
+        assert!(validate_username("").is_err());
+        assert!(validate_username(" Jesse").is_err());
+        assert!(validate_username("Jesse<script>").is_err());
+        assert!(validate_username("System").is_err());
+
+        // Alice is seeded as a@x: she may keep her name, nobody else may take it.
+        assert!(!username_taken_internal(&pool, "Alice", "a@x")
+            .await
+            .unwrap());
+        assert!(username_taken_internal(&pool, "alice", "c@x")
+            .await
+            .unwrap());
+        assert!(!username_taken_internal(&pool, "Carol", "c@x")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn search_finds_images_by_the_text_read_from_them() {
+        let pool = setup().await;
+        add(&pool, 1, "the build is red", "chat").await;
+        sqlx::raw_sql(
+            "INSERT INTO messages (room_id, user_id, message, message_type, message_id)
+                 VALUES (1, 2, '{\"id\":9,\"filename\":\"shot.png\"}', 'Attachment', 'img');
+             INSERT INTO attachments (id, room_id, message_id, uploaded_by, filename, mime, size, sha256)
+                 VALUES (9, 1, 'img', 2, 'shot.png', 'image/png', 4, 'x');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        // The attachment's JSON doesn't match by itself.
+        assert!(search_messages_internal(&pool, "shot", None)
+            .await
+            .unwrap()
+            .is_empty());
+
+        crate::ocr::store_text(&pool, 9, "ERROR: connection refused (os error 111)")
+            .await
+            .unwrap();
+        let found = search_messages_internal(&pool, "Connection REFUSED", None)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].message_id.as_deref(), Some("img"));
+        assert_eq!(
+            found[0].image_text.as_deref(),
+            Some("ERROR: connection refused (os error 111)")
+        );
+        assert_eq!(
+            search_messages_internal(&pool, "red", None)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
 }
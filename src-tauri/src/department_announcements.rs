@@ -0,0 +1,200 @@
+// Announcements to a whole department (host-side).
+//
+// `send_department_announcement` posts the host's text into every room of a department, one
+// ordinary host chat message per room (persisted and fanned out like any other). Department
+// members who are connected but looking at a room outside the department wouldn't see any of
+// them, so each is also sent a DepartmentNotice frame: `message` = JSON {department_id,
+// department, text}, with `room_id` / `room` naming the department's first room to open.
+
+use crate::error::{AppError, AppResult};
+use crate::limits;
+use crate::sockets::{frame, publish_host_message, send_to_client, AppState, Message, MessageType};
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tauri::State;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DepartmentAnnouncement {
+    // Rooms it was posted in.
+    pub rooms: usize,
+    // Members told directly, being connected elsewhere.
+    pub notified: usize,
+}
+
+/// A DepartmentNotice's `message`.
+#[derive(Serialize)]
+struct Notice<'a> {
+    department_id: i64,
+    department: &'a str,
+    text: &'a str,
+}
+
+/// A department's name and its rooms (id, name), oldest first.
+pub async fn department_rooms_internal(
+    pool: &SqlitePool,
+    department_id: i64,
+) -> AppResult<(String, Vec<(i64, String)>)> {
+    let name: Option<String> = sqlx::query_scalar("SELECT name FROM departments WHERE id = $1")
+        .bind(department_id)
+        .fetch_optional(pool)
+        .await?;
+    let Some(name) = name else {
+        return Err(AppError::Validation(
+            "That department doesn't exist".to_string(),
+        ));
+    };
+    let rooms = sqlx::query("SELECT id, name FROM chat_rooms WHERE department_id = $1 ORDER BY id")
+        .bind(department_id)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| (row.get("id"), row.get("name")))
+        .collect::<Vec<_>>();
+    if rooms.is_empty() {
+        return Err(AppError::Validation(format!("{} has no rooms", name)));
+    }
+    Ok((name, rooms))
+}
+
+/// Who to tell directly: connected department members (other than the sender) whose current
+/// room isn't one the announcement went to. `connected` is (user id, room id) per connection.
+fn notice_targets(
+    connected: &[(u64, u64)],
+    members: &HashSet<i64>,
+    rooms: &[(i64, String)],
+    sender: u64,
+) -> Vec<u64> {
+    let mut targets: Vec<u64> = connected
+        .iter()
+        .filter(|(uid, room_id)| {
+            *uid != sender
+                && members.contains(&(*uid as i64))
+                && !rooms.iter().any(|(id, _)| *id == *room_id as i64)
+        })
+        .map(|(uid, _)| *uid)
+        .collect();
+    targets.sort_unstable();
+    targets
+}
+
+/// Post `text` to every room of a department, and tell its members who are connected but in
+/// some other room.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn send_department_announcement(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    department_id: i64,
+    text: String,
+) -> AppResult<DepartmentAnnouncement> {
+    if !*state.is_server.read().await {
+        return Err(AppError::Validation(
+            "Only the host can announce to a department".to_string(),
+        ));
+    }
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return Err(AppError::Validation(
+            "Announcement cannot be empty".to_string(),
+        ));
+    }
+    limits::check_length(
+        &text,
+        state
+            .max_message_chars
+            .load(std::sync::atomic::Ordering::Relaxed),
+    )?;
+    let (department, rooms) = department_rooms_internal(&db, department_id).await?;
+
+    let username = state.username.read().await.clone();
+    let sender = state.user_id.read().await.unwrap_or(0);
+    for (room_id, room) in &rooms {
+        let chat_message = Message {
+            username: username.clone(),
+            room_id: *room_id as u64,
+            room: room.clone(),
+            ..frame(MessageType::Chat, sender, text.clone())
+        };
+        publish_host_message(&app, state.inner(), db.inner(), chat_message).await;
+    }
+
+    let members: HashSet<i64> =
+        sqlx::query_scalar::<_, i64>("SELECT id FROM users WHERE department_id = $1")
+            .bind(department_id)
+            .fetch_all(db.inner())
+            .await?
+            .into_iter()
+            .collect();
+    let connected: Vec<(u64, u64)> = state
+        .server_streams
+        .lock()
+        .await
+        .values()
+        .map(|c| (c.user_id, c.room_id))
+        .collect();
+    let targets = notice_targets(&connected, &members, &rooms, sender);
+    let json = serde_json::to_string(&Notice {
+        department_id,
+        department: &department,
+        text: &text,
+    })
+    .map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut notice = frame(MessageType::DepartmentNotice, sender, json);
+    notice.username = username;
+    notice.room_id = rooms[0].0 as u64;
+    notice.room = rooms[0].1.clone();
+    for uid in &targets {
+        send_to_client(state.inner(), *uid, &notice).await;
+    }
+    tracing::info!(
+        "📣 Announced to {} ({} rooms, {} told directly)",
+        department,
+        rooms.len(),
+        targets.len()
+    );
+    Ok(DepartmentAnnouncement {
+        rooms: rooms.len(),
+        notified: targets.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::memory_pool;
+
+    #[tokio::test]
+    async fn announcements_reach_department_rooms_and_members_elsewhere() {
+        let pool = memory_pool().await;
+        let it: i64 = sqlx::query_scalar("SELECT id FROM departments WHERE name = 'IT'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO chat_rooms (name, department_id, is_private) VALUES ('IT Oncall', $1, 1)",
+        )
+        .bind(it)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let (name, rooms) = department_rooms_internal(&pool, it).await.unwrap();
+        assert_eq!(name, "IT");
+        let names: Vec<&str> = rooms.iter().map(|(_, n)| n.as_str()).collect();
+        assert_eq!(names, ["IT General", "IT Oncall"]);
+        assert!(department_rooms_internal(&pool, 999).await.is_err());
+
+        let (it_general, elsewhere) = (rooms[0].0 as u64, 9_999);
+        let members: HashSet<i64> = [1, 2, 3, 4].into_iter().collect();
+        let connected = [
+            (1, elsewhere), // the sender
+            (2, it_general),
+            (3, elsewhere),
+            (4, rooms[1].0 as u64),
+            (5, elsewhere), // not in IT
+        ];
+        assert_eq!(notice_targets(&connected, &members, &rooms, 1), [3]);
+    }
+}
@@ -0,0 +1,431 @@
+// CSV import / export of the user directory (host-side).
+//
+// `import_users_csv` reads a spreadsheet export with `name`, `email` and (optionally)
+// `department` columns, in any order and any case. Every row is checked the way
+// `upsert_user_internal` checks a login; rows that fail, and later rows repeating an email
+// already seen in the file, are reported back rather than imported. The rest are written in one
+// transaction: users are matched by email (existing ones get the file's name and, if given,
+// department), and departments are created as needed. The exports write the same columns, so a
+// directory can be edited in a spreadsheet and imported back. Exported cells a spreadsheet
+// would run as a formula get a leading `'`, which the import strips again.
+
+use crate::db_queries::{apply_auto_join_internal, normalize_email};
+use crate::error::{AppError, AppResult};
+use crate::guests::GUEST_EMAIL_SUFFIX;
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+use tauri::State;
+
+/// Files larger than this are refused rather than read into memory.
+const MAX_CSV_BYTES: u64 = 16 * 1024 * 1024;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RejectedRow {
+    // The row's line in the file (the header is line 1).
+    pub line: u64,
+    pub email: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct CsvImportReport {
+    pub users_created: usize,
+    pub users_updated: usize,
+    pub departments_created: usize,
+    pub rejected: Vec<RejectedRow>,
+}
+
+struct CsvUser {
+    line: u64,
+    name: String,
+    email: String,
+    department: Option<String>,
+}
+
+/// A cell as written to an exported CSV. Text starting with `=`, `+`, `-` or `@` is read as a
+/// formula by spreadsheets, so it gets a leading `'` to keep it as text.
+pub(crate) fn spreadsheet_cell(value: &str) -> Cow<'_, str> {
+    if value.starts_with(['=', '+', '-', '@']) {
+        Cow::Owned(format!("'{}", value))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Undo `spreadsheet_cell` for a cell read back from a CSV.
+fn imported_cell(value: &str) -> &str {
+    match value.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(['=', '+', '-', '@']) => rest,
+        _ => value,
+    }
+}
+
+/// The rows worth importing, and the ones that aren't with the reason.
+fn parse_users(data: &[u8]) -> AppResult<(Vec<CsvUser>, Vec<RejectedRow>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data);
+    let headers = reader
+        .headers()
+        .map_err(|e| AppError::Validation(format!("Unreadable CSV: {}", e)))?
+        .clone();
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (Some(name_col), Some(email_col)) = (column("name"), column("email")) else {
+        return Err(AppError::Validation(
+            "The CSV needs a header row with name and email columns".to_string(),
+        ));
+    };
+    let department_col = column("department");
+
+    let mut users = Vec::new();
+    let mut rejected = Vec::new();
+    let mut seen: HashMap<String, u64> = HashMap::new();
+    // The reader's own line count skips blank lines, so count the newlines before each record.
+    let (mut counted_to, mut line) = (0, 1);
+    for record in reader.records() {
+        let record = record.map_err(|e| AppError::Validation(format!("Unreadable CSV: {}", e)))?;
+        let mut start = record.position().map_or(counted_to, |p| p.byte() as usize);
+        // A record after blank lines is positioned at the first of them.
+        while matches!(data.get(start), Some(b'\r' | b'\n')) {
+            start += 1;
+        }
+        line += data[counted_to..start]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count() as u64;
+        counted_to = start;
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        let name = imported_cell(record.get(name_col).unwrap_or("")).to_string();
+        let email = normalize_email(imported_cell(record.get(email_col).unwrap_or("")));
+        let reason = if name.is_empty() || name.chars().count() > 64 {
+            Some("Name must be between 1 and 64 characters".to_string())
+        } else if email.is_empty() || email.len() > 254 || !email.contains('@') {
+            Some("A valid email address is required".to_string())
+        } else if email.ends_with(GUEST_EMAIL_SUFFIX) {
+            Some("Guest addresses can't be imported".to_string())
+        } else {
+            seen.get(&email)
+                .map(|first| format!("Same email as line {}", first))
+        };
+        if let Some(reason) = reason {
+            rejected.push(RejectedRow {
+                line,
+                email,
+                reason,
+            });
+            continue;
+        }
+        seen.insert(email.clone(), line);
+        users.push(CsvUser {
+            line,
+            name,
+            email,
+            department: department_col
+                .and_then(|c| record.get(c))
+                .filter(|d| !d.is_empty())
+                .map(|d| imported_cell(d).to_string()),
+        });
+    }
+    Ok((users, rejected))
+}
+
+/// Write the parsed users in one transaction. Returns the report and the new users' ids.
+async fn import_users_internal(
+    pool: &SqlitePool,
+    users: Vec<CsvUser>,
+    rejected: Vec<RejectedRow>,
+) -> AppResult<(CsvImportReport, Vec<i64>)> {
+    let mut report = CsvImportReport {
+        rejected,
+        ..Default::default()
+    };
+    let mut created = Vec::new();
+    let mut departments: HashMap<String, i64> = HashMap::new();
+    let mut tx = pool.begin().await?;
+    for user in &users {
+        let department_id = match &user.department {
+            Some(name) => match departments.get(name) {
+                Some(&id) => Some(id),
+                None => {
+                    let inserted = sqlx::query(
+                        "INSERT INTO departments (name) VALUES ($1) ON CONFLICT(name) DO NOTHING",
+                    )
+                    .bind(name)
+                    .execute(&mut *tx)
+                    .await?;
+                    report.departments_created += inserted.rows_affected() as usize;
+                    let id: i64 = sqlx::query_scalar("SELECT id FROM departments WHERE name = $1")
+                        .bind(name)
+                        .fetch_one(&mut *tx)
+                        .await?;
+                    departments.insert(name.clone(), id);
+                    Some(id)
+                }
+            },
+            None => None,
+        };
+        let existing: Option<(i64, bool)> =
+            sqlx::query_as("SELECT id, is_guest FROM users WHERE email = $1")
+                .bind(&user.email)
+                .fetch_optional(&mut *tx)
+                .await?;
+        match existing {
+            Some((_, true)) => report.rejected.push(RejectedRow {
+                line: user.line,
+                email: user.email.clone(),
+                reason: "A guest already uses this email".to_string(),
+            }),
+            Some((id, false)) => {
+                sqlx::query(
+                    "UPDATE users SET name = $1, department_id = COALESCE($2, department_id)
+                     WHERE id = $3",
+                )
+                .bind(&user.name)
+                .bind(department_id)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+                report.users_updated += 1;
+            }
+            None => {
+                let id = sqlx::query(
+                    "INSERT INTO users (name, email, department_id) VALUES ($1, $2, $3)",
+                )
+                .bind(&user.name)
+                .bind(&user.email)
+                .bind(department_id)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid();
+                created.push(id);
+                report.users_created += 1;
+            }
+        }
+    }
+    tx.commit().await?;
+    report.rejected.sort_by_key(|r| r.line);
+    Ok((report, created))
+}
+
+async fn export_users_internal(pool: &SqlitePool) -> AppResult<(Vec<u8>, usize)> {
+    let rows = sqlx::query(
+        "SELECT u.name, u.email, d.name AS department
+         FROM users u LEFT JOIN departments d ON d.id = u.department_id
+         WHERE u.is_guest = 0 AND u.email IS NOT NULL
+         ORDER BY u.name COLLATE NOCASE, u.email",
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let csv_err = |e: csv::Error| AppError::Internal(format!("Failed to write CSV: {}", e));
+    writer
+        .write_record(["name", "email", "department"])
+        .map_err(csv_err)?;
+    for row in &rows {
+        let (name, email): (String, String) = (row.get("name"), row.get("email"));
+        let department: Option<String> = row.get("department");
+        writer
+            .write_record([
+                spreadsheet_cell(&name),
+                spreadsheet_cell(&email),
+                spreadsheet_cell(department.as_deref().unwrap_or("")),
+            ])
+            .map_err(csv_err)?;
+    }
+    let data = writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("Failed to write CSV: {}", e)))?;
+    Ok((data, rows.len()))
+}
+
+async fn export_departments_internal(pool: &SqlitePool) -> AppResult<(Vec<u8>, usize)> {
+    let rows: Vec<(String, Option<String>)> =
+        sqlx::query_as("SELECT name, description FROM departments ORDER BY name COLLATE NOCASE")
+            .fetch_all(pool)
+            .await?;
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let csv_err = |e: csv::Error| AppError::Internal(format!("Failed to write CSV: {}", e));
+    writer
+        .write_record(["name", "description"])
+        .map_err(csv_err)?;
+    for (name, description) in &rows {
+        writer
+            .write_record([
+                spreadsheet_cell(name),
+                spreadsheet_cell(description.as_deref().unwrap_or("")),
+            ])
+            .map_err(csv_err)?;
+    }
+    let data = writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("Failed to write CSV: {}", e)))?;
+    Ok((data, rows.len()))
+}
+
+async fn write_file(path: &str, data: &[u8]) -> AppResult<()> {
+    tokio::fs::write(path, data)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to save {}: {}", path, e)))
+}
+
+/// Import users from the CSV at `path`. Rows that can't be imported are listed in the report;
+/// the others are all written, or none if the database refuses.
+#[tauri::command]
+pub async fn import_users_csv(
+    db: State<'_, SqlitePool>,
+    path: String,
+) -> AppResult<CsvImportReport> {
+    let size = tokio::fs::metadata(&path)
+        .await
+        .map_err(|e| AppError::Validation(format!("Can't read {}: {}", path, e)))?
+        .len();
+    if size > MAX_CSV_BYTES {
+        return Err(AppError::Validation(format!(
+            "The file is larger than {} MB",
+            MAX_CSV_BYTES / (1024 * 1024)
+        )));
+    }
+    let data = tokio::fs::read(Path::new(&path))
+        .await
+        .map_err(|e| AppError::Validation(format!("Can't read {}: {}", path, e)))?;
+    let (users, rejected) = parse_users(&data)?;
+    let (report, created) = import_users_internal(&db, users, rejected).await?;
+    for id in created {
+        if let Err(e) = apply_auto_join_internal(&db, id).await {
+            tracing::warn!("CSV import: {}", e);
+        }
+    }
+    tracing::info!(
+        "📇 CSV import: {} created, {} updated, {} rejected",
+        report.users_created,
+        report.users_updated,
+        report.rejected.len()
+    );
+    Ok(report)
+}
+
+/// Write every (non-guest) user to `path` as CSV. Returns how many were written.
+#[tauri::command]
+pub async fn export_users_csv(db: State<'_, SqlitePool>, path: String) -> AppResult<usize> {
+    let (data, count) = export_users_internal(&db).await?;
+    write_file(&path, &data).await?;
+    Ok(count)
+}
+
+/// Write every department to `path` as CSV. Returns how many were written.
+#[tauri::command]
+pub async fn export_departments_csv(db: State<'_, SqlitePool>, path: String) -> AppResult<usize> {
+    let (data, count) = export_departments_internal(&db).await?;
+    write_file(&path, &data).await?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn users_import_with_rejects_and_export_back() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id) VALUES (1, 'Alice', 'a@x', 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // A spreadsheet export: BOM, odd header case and order, padding, a blank line.
+        let csv = "\u{feff}Email,Department,Name\n\
+                   A@X , Field Ops, Alice Smith\n\
+                   bob@x,Field Ops,Bob\n\
+                   \n\
+                   not-an-email,,Carol\n\
+                   BOB@x,,Bobby\n\
+                   dan@x,,\n\
+                   eve@x,,Eve\n";
+        let (users, rejected) = parse_users(csv.as_bytes()).unwrap();
+        assert_eq!(users.len(), 3);
+        let reasons: Vec<_> = rejected.iter().map(|r| r.line).collect();
+        assert_eq!(reasons, vec![5, 6, 7]);
+        assert_eq!(rejected[1].reason, "Same email as line 3");
+
+        let (report, created) = import_users_internal(&pool, users, rejected).await.unwrap();
+        assert_eq!(
+            (
+                report.users_created,
+                report.users_updated,
+                report.departments_created
+            ),
+            (2, 1, 1)
+        );
+        assert_eq!(created.len(), 2);
+        let (alice, dept): (String, String) = sqlx::query_as(
+            "SELECT u.name, d.name FROM users u JOIN departments d ON d.id = u.department_id
+             WHERE u.email = 'a@x'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            (alice.as_str(), dept.as_str()),
+            ("Alice Smith", "Field Ops")
+        );
+
+        let (data, count) = export_users_internal(&pool).await.unwrap();
+        assert_eq!(count, 3);
+        let text = String::from_utf8(data).unwrap();
+        assert!(text.starts_with("name,email,department\nAlice Smith,a@x,Field Ops\n"));
+        // What was exported imports back without changes or rejects.
+        let (again, rejected) = parse_users(text.as_bytes()).unwrap();
+        assert!(rejected.is_empty());
+        assert_eq!(again.len(), 3);
+
+        assert!(parse_users(b"full name,mail\nA,a@x\n").is_err());
+        let (data, _) = export_departments_internal(&pool).await.unwrap();
+        assert!(String::from_utf8(data).unwrap().contains("\nField Ops,\n"));
+    }
+
+    #[tokio::test]
+    async fn exported_formulas_stay_text_and_import_back() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO departments (id, name, description) VALUES (100, '+Ops', '@SUM(A1)');
+             INSERT INTO users (id, name, email, department_id) VALUES (1, '=1+1', 'a@x', 100);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let (data, _) = export_users_internal(&pool).await.unwrap();
+        let text = String::from_utf8(data).unwrap();
+        assert!(text.contains("\n'=1+1,a@x,'+Ops\n"));
+        let (users, rejected) = parse_users(text.as_bytes()).unwrap();
+        assert!(rejected.is_empty());
+        assert_eq!(users[0].name, "=1+1");
+        assert_eq!(users[0].department.as_deref(), Some("+Ops"));
+
+        let (data, _) = export_departments_internal(&pool).await.unwrap();
+        assert!(String::from_utf8(data)
+            .unwrap()
+            .contains("\n'+Ops,'@SUM(A1)\n"));
+        assert_eq!(spreadsheet_cell("-5"), "'-5");
+        assert_eq!(spreadsheet_cell("Field Ops"), "Field Ops");
+    }
+}
@@ -0,0 +1,239 @@
+// Where `discover_servers` probes, beyond the local broadcast.
+//
+// The broadcast probe only reaches the machine's own segment, and mDNS isn't everywhere yet.
+// Users can list extra IPv4 ranges (CIDR, e.g. "10.20.0.0/24") and the UDP ports hosts answer
+// discovery on; each address in those ranges is then sent the same probe, on each port.
+// `scan_own_subnet` adds the subnets of this machine's own interfaces (at most a /24 around the
+// address each). Only private and link-local ranges can be listed, as hosts only answer probes
+// from those, and a scan is capped at MAX_TARGETS probes.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::DISCOVERY_PORT;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::BTreeSet;
+use std::net::{Ipv4Addr, SocketAddr};
+use tauri::State;
+
+const SETTINGS_KEY: &str = "discovery";
+
+/// Most probes one scan sends (addresses times ports).
+pub const MAX_TARGETS: usize = 4096;
+
+/// Widest range that can be listed: a /20 is 4094 hosts.
+const MIN_PREFIX: u8 = 20;
+
+/// Own subnets wider than this are scanned as the /24 around our address.
+const OWN_SUBNET_PREFIX: u8 = 24;
+
+const MAX_PORTS: usize = 8;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DiscoveryConfig {
+    // Extra IPv4 ranges to probe, as CIDR.
+    #[serde(default)]
+    pub subnets: Vec<String>,
+    // UDP ports to probe them on.
+    #[serde(default = "default_ports")]
+    pub ports: Vec<u16>,
+    // Also probe the subnets of this machine's own interfaces.
+    #[serde(default)]
+    pub scan_own_subnet: bool,
+}
+
+fn default_ports() -> Vec<u16> {
+    vec![DISCOVERY_PORT]
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            subnets: Vec::new(),
+            ports: default_ports(),
+            scan_own_subnet: false,
+        }
+    }
+}
+
+/// A range like "192.168.1.0/24" (a bare address is a /32), as its network address and prefix.
+pub fn parse_cidr(text: &str) -> AppResult<(Ipv4Addr, u8)> {
+    let text = text.trim();
+    let (addr, prefix) = text.split_once('/').unwrap_or((text, "32"));
+    let invalid = || AppError::Validation(format!("\"{}\" isn't an IPv4 range", text));
+    let addr: Ipv4Addr = addr.trim().parse().map_err(|_| invalid())?;
+    let prefix: u8 = prefix.trim().parse().map_err(|_| invalid())?;
+    if prefix > 32 {
+        return Err(invalid());
+    }
+    if prefix < MIN_PREFIX {
+        return Err(AppError::Validation(format!(
+            "{} is too wide to scan (at most a /{})",
+            text, MIN_PREFIX
+        )));
+    }
+    if !(addr.is_private() || addr.is_link_local()) {
+        return Err(AppError::Validation(format!(
+            "{} isn't a private network range",
+            text
+        )));
+    }
+    Ok((network(addr, prefix), prefix))
+}
+
+fn network(addr: Ipv4Addr, prefix: u8) -> Ipv4Addr {
+    let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+    Ipv4Addr::from(u32::from(addr) & mask)
+}
+
+/// The addresses in a range, without its network and broadcast addresses (when it has them).
+fn hosts(net: Ipv4Addr, prefix: u8) -> impl Iterator<Item = Ipv4Addr> {
+    let first = u32::from(net);
+    let size = 1u64 << (32 - prefix as u32);
+    let (skip, take) = if size > 2 { (1, size - 2) } else { (0, size) };
+    (0..take).map(move |i| Ipv4Addr::from(first + (skip + i) as u32))
+}
+
+/// The subnets of this machine's up, non-loopback IPv4 interfaces.
+fn own_subnets() -> Vec<(Ipv4Addr, u8)> {
+    let Ok(addrs) = if_addrs::get_if_addrs() else {
+        return Vec::new();
+    };
+    addrs
+        .into_iter()
+        .filter_map(|i| match i.addr {
+            if_addrs::IfAddr::V4(v4) if !v4.ip.is_loopback() => {
+                let prefix = (u32::from(v4.netmask).count_ones() as u8).max(OWN_SUBNET_PREFIX);
+                Some((network(v4.ip, prefix), prefix))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Every address and port to probe for `config`, given our own subnets; at most MAX_TARGETS.
+fn targets(config: &DiscoveryConfig, own: &[(Ipv4Addr, u8)]) -> Vec<SocketAddr> {
+    let mut ranges: Vec<(Ipv4Addr, u8)> = config
+        .subnets
+        .iter()
+        .filter_map(|s| parse_cidr(s).ok())
+        .collect();
+    if config.scan_own_subnet {
+        ranges.extend_from_slice(own);
+    }
+    let addrs: BTreeSet<Ipv4Addr> = ranges
+        .into_iter()
+        .flat_map(|(net, prefix)| hosts(net, prefix))
+        .collect();
+    addrs
+        .into_iter()
+        .flat_map(|ip| config.ports.iter().map(move |&port| (ip, port).into()))
+        .take(MAX_TARGETS)
+        .collect()
+}
+
+/// Where this scan sends unicast probes, from the saved settings.
+pub async fn probe_targets(pool: &SqlitePool) -> Vec<SocketAddr> {
+    match load_config(pool).await {
+        Ok(config) if !config.subnets.is_empty() || config.scan_own_subnet => {
+            let own = if config.scan_own_subnet {
+                own_subnets()
+            } else {
+                Vec::new()
+            };
+            targets(&config, &own)
+        }
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            tracing::warn!("{}", e);
+            Vec::new()
+        }
+    }
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<DiscoveryConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt discovery settings: {}", e))),
+        None => Ok(DiscoveryConfig::default()),
+    }
+}
+
+#[tauri::command]
+pub async fn get_discovery_config(db: State<'_, SqlitePool>) -> AppResult<DiscoveryConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_discovery_config(
+    db: State<'_, SqlitePool>,
+    config: DiscoveryConfig,
+) -> AppResult<()> {
+    let mut subnets = Vec::new();
+    for subnet in &config.subnets {
+        let (net, prefix) = parse_cidr(subnet)?;
+        subnets.push(format!("{}/{}", net, prefix));
+    }
+    if config.ports.is_empty() || config.ports.len() > MAX_PORTS || config.ports.contains(&0) {
+        return Err(AppError::Validation(format!(
+            "List between 1 and {} discovery ports",
+            MAX_PORTS
+        )));
+    }
+    let config = DiscoveryConfig { subnets, ..config };
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_expand_to_probe_targets() {
+        assert_eq!(
+            parse_cidr("192.168.1.77/24").unwrap(),
+            (Ipv4Addr::new(192, 168, 1, 0), 24)
+        );
+        assert_eq!(parse_cidr(" 10.0.0.5 ").unwrap().1, 32);
+        assert!(parse_cidr("10.0.0.0/8").is_err());
+        assert!(parse_cidr("8.8.8.0/24").is_err());
+        assert!(parse_cidr("10.0.0.0/33").is_err());
+        assert!(parse_cidr("nonsense").is_err());
+
+        let config = DiscoveryConfig {
+            subnets: vec!["10.0.0.0/30".to_string(), "10.0.0.2".to_string()],
+            ports: vec![3626, 4626],
+            scan_own_subnet: true,
+        };
+        let own = [(Ipv4Addr::new(172, 16, 5, 0), 31)];
+        let found: Vec<String> = targets(&config, &own)
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "10.0.0.1:3626",
+                "10.0.0.1:4626",
+                "10.0.0.2:3626",
+                "10.0.0.2:4626",
+                "172.16.5.0:3626",
+                "172.16.5.0:4626",
+                "172.16.5.1:3626",
+                "172.16.5.1:4626",
+            ]
+        );
+
+        let wide = DiscoveryConfig {
+            subnets: vec!["10.1.0.0/20".to_string()],
+            ..config
+        };
+        assert_eq!(targets(&wide, &[]).len(), MAX_TARGETS);
+    }
+}
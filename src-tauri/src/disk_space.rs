@@ -0,0 +1,248 @@
+// Disk-space guard.
+//
+// SQLite and the attachment store fail badly on a full disk (a half-written file, a write the
+// database has to roll back), so the app keeps a margin free. An upload that would leave less
+// than the configured minimum in the attachments folder is refused with `AppError::DiskFull`,
+// before any of it is written, and so is a history import too big for the database's disk. A
+// watcher checks the database's and the attachments' folders every
+// minute and emits `disk_space` whenever they cross the minimum, so the UI can warn (and suggest
+// pruning old messages and files) before writes start failing. Chat itself keeps working: a
+// message is tiny, and dropping one would be worse than the warning.
+
+use crate::db::DbLocation;
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{Emitter, Runtime, State};
+
+const SETTINGS_KEY: &str = "disk_space_guard";
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+const HINT: &str = "Free up space, or prune old messages and attachments (retention settings)";
+
+// Whether the last check found space low, so the event only fires on a change.
+static LOW: AtomicBool = AtomicBool::new(false);
+
+fn default_min_free_mb() -> u64 {
+    500
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DiskGuardConfig {
+    // Keep at least this much free (MB); 0 turns the guard off.
+    #[serde(default = "default_min_free_mb")]
+    pub min_free_mb: u64,
+}
+
+impl Default for DiskGuardConfig {
+    fn default() -> Self {
+        Self {
+            min_free_mb: default_min_free_mb(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DiskSpace {
+    // The folder with the least space free.
+    pub path: String,
+    pub free_bytes: u64,
+    pub min_free_bytes: u64,
+    pub low: bool,
+    pub hint: Option<String>,
+}
+
+/// Bytes available to us on the disk holding `path`.
+#[cfg(unix)]
+fn free_bytes(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: `path` is NUL-terminated and `stat` is a writable statvfs.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    // The field widths differ between platforms (u32 on macOS, u64 on Linux).
+    #[allow(clippy::unnecessary_cast, clippy::useless_conversion)]
+    Some(u64::from(stat.f_bavail) * u64::from(stat.f_frsize))
+}
+
+#[cfg(windows)]
+fn free_bytes(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut free = 0u64;
+    // SAFETY: `wide` is NUL-terminated; the totals we don't need may be null.
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut free,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    (ok != 0).then_some(free)
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<DiskGuardConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt disk space settings: {}", e))),
+        None => Ok(DiskGuardConfig::default()),
+    }
+}
+
+fn human_mb(bytes: u64) -> String {
+    format!("{} MB", bytes / (1024 * 1024))
+}
+
+/// Whether `needed` more bytes fit on a disk with `free` available, keeping `min_free`.
+fn check(free: u64, needed: u64, min_free: u64) -> AppResult<()> {
+    if min_free > 0 && free.saturating_sub(needed) < min_free {
+        return Err(AppError::DiskFull(format!(
+            "Not enough disk space: {} free, and at least {} must stay free. {}",
+            human_mb(free),
+            human_mb(min_free),
+            HINT
+        )));
+    }
+    Ok(())
+}
+
+/// Refuse to write `needed` bytes into `dir` when that would leave too little free, telling the
+/// UI why. A disk that can't be measured isn't in the way.
+pub async fn ensure_room<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    pool: &SqlitePool,
+    dir: &Path,
+    needed: u64,
+) -> AppResult<()> {
+    let min_free = load_config(pool).await?.min_free_mb * 1024 * 1024;
+    let Some(free) = free_bytes(dir) else {
+        return Ok(());
+    };
+    let result = check(free, needed, min_free);
+    if result.is_err() {
+        tracing::warn!("💾 Refused a {}-byte write to {}", needed, dir.display());
+        let _ = app.emit("disk_space", status(dir, free, min_free, true));
+    }
+    result
+}
+
+fn status(path: &Path, free: u64, min_free: u64, low: bool) -> DiskSpace {
+    DiskSpace {
+        path: path.display().to_string(),
+        free_bytes: free,
+        min_free_bytes: min_free,
+        low,
+        hint: low.then(|| HINT.to_string()),
+    }
+}
+
+/// The watched folder with the least space free.
+async fn measure(pool: &SqlitePool, dirs: &[PathBuf]) -> AppResult<Option<DiskSpace>> {
+    let min_free = load_config(pool).await?.min_free_mb * 1024 * 1024;
+    Ok(dirs
+        .iter()
+        .filter_map(|dir| Some((dir, free_bytes(dir)?)))
+        .min_by_key(|(_, free)| *free)
+        .map(|(dir, free)| status(dir, free, min_free, check(free, 0, min_free).is_err())))
+}
+
+/// The folders the guard watches: the database's and the attachments'.
+fn watched_dirs(state: &AppState, location: &DbLocation) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = location
+        .db_path
+        .parent()
+        .map(Path::to_path_buf)
+        .into_iter()
+        .collect();
+    dirs.extend(state.attachments_dir.get().cloned());
+    dirs
+}
+
+pub fn spawn_watcher(
+    app: tauri::AppHandle,
+    state: Arc<AppState>,
+    pool: SqlitePool,
+    location: &DbLocation,
+) {
+    let dirs = watched_dirs(&state, location);
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            match measure(&pool, &dirs).await {
+                Ok(Some(space)) => {
+                    if LOW.swap(space.low, Ordering::Relaxed) != space.low {
+                        if space.low {
+                            tracing::warn!(
+                                "💾 Disk space low: {} free in {}",
+                                human_mb(space.free_bytes),
+                                space.path
+                            );
+                        }
+                        let _ = app.emit("disk_space", space);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("{}", e),
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_disk_space(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    location: State<'_, DbLocation>,
+) -> AppResult<Option<DiskSpace>> {
+    measure(&db, &watched_dirs(&state, &location)).await
+}
+
+#[tauri::command]
+pub async fn get_disk_guard_config(db: State<'_, SqlitePool>) -> AppResult<DiskGuardConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_disk_guard_config(
+    db: State<'_, SqlitePool>,
+    config: DiskGuardConfig,
+) -> AppResult<()> {
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_keep_the_minimum_free() {
+        const MB: u64 = 1024 * 1024;
+        assert!(check(600 * MB, 50 * MB, 500 * MB).is_ok());
+        assert!(matches!(
+            check(600 * MB, 150 * MB, 500 * MB),
+            Err(AppError::DiskFull(_))
+        ));
+        assert!(check(100 * MB, 0, 500 * MB).is_err());
+        // 0 turns the guard off.
+        assert!(check(0, 50 * MB, 0).is_ok());
+        assert!(free_bytes(&std::env::temp_dir()).is_some_and(|free| free > 0));
+    }
+}
@@ -0,0 +1,481 @@
+// Email digests for mentions / DMs missed while offline (optional, host-side, off by default).
+//
+// When a chat message @-mentions someone, or lands in a DM, and that recipient has no live
+// connection to this host, the host queues an `email_outbox` row. A background worker wakes
+// every `digest_minutes`, groups the pending rows per recipient and sends ONE summary email
+// each over SMTP — so a burst of messages is one email, not twenty. Recipients who have come
+// back online by then are skipped (they'll see it in the app), as are since-deleted messages.
+//
+// SMTP settings live in `app_settings`; the SMTP password lives in the OS keychain.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message as Email, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+const SETTINGS_KEY: &str = "smtp_config";
+const KEYRING_SMTP_USER: &str = "smtp-password-v1";
+
+const MIN_DIGEST_MINUTES: u64 = 5;
+
+/// Snippets longer than this are cut in the digest body.
+const SNIPPET_CHARS: usize = 200;
+
+/// Sent outbox rows are pruned after this many days.
+const OUTBOX_RETENTION_DAYS: i64 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    #[default]
+    Starttls,
+    // Implicit TLS (usually port 465).
+    Tls,
+    // Plaintext — only for a relay on a trusted LAN.
+    None,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SmtpConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    // Empty = unauthenticated relay.
+    #[serde(default)]
+    pub username: String,
+    // e.g. "Nutler <nutler@corp.example>"
+    pub from_address: String,
+    #[serde(default = "default_digest_minutes")]
+    pub digest_minutes: u64,
+}
+
+fn default_digest_minutes() -> u64 {
+    15
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: 587,
+            security: SmtpSecurity::Starttls,
+            username: String::new(),
+            from_address: String::new(),
+            digest_minutes: default_digest_minutes(),
+        }
+    }
+}
+
+/// One missed message in a recipient's digest.
+#[derive(Debug, Clone, PartialEq)]
+struct DigestItem {
+    sender: String,
+    // None for a DM (its stored room name is a synthetic key, not something to show).
+    room: Option<String>,
+    text: String,
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<SmtpConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt SMTP config: {}", e))),
+        None => Ok(SmtpConfig::default()),
+    }
+}
+
+fn keyring_entry() -> AppResult<keyring::Entry> {
+    keyring::Entry::new(crate::db::KEYRING_SERVICE, KEYRING_SMTP_USER)
+        .map_err(|e| AppError::Internal(format!("Keychain unavailable: {}", e)))
+}
+
+/// `@handles` mentioned in `text`, lowercased and de-duplicated. An `@` inside a word (an email
+/// address) isn't a mention.
+fn mention_handles(text: &str) -> Vec<String> {
+    let mut handles = Vec::new();
+    let mut prev: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        if c == '@' && !prev.is_some_and(|p| p.is_alphanumeric()) {
+            let rest = &text[i + 1..];
+            let end = rest
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '.' || ch == '_' || ch == '-'))
+                .unwrap_or(rest.len());
+            let handle = rest[..end].trim_end_matches('.').to_lowercase();
+            if !handle.is_empty() && !handles.contains(&handle) {
+                handles.push(handle);
+            }
+        }
+        prev = Some(c);
+    }
+    handles
+}
+
+/// Queue email notifications for a just-saved message: DM members and @-mentioned users who are
+/// not connected right now. No-op unless SMTP digests are enabled.
+pub async fn queue_offline_notifications(
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    sender_id: u64,
+    room_id: u64,
+    message_id: &str,
+    text: &str,
+) {
+    match load_config(pool).await {
+        Ok(config) if config.enabled => {}
+        _ => return,
+    }
+    let mut online: HashSet<i64> = state
+        .server_streams
+        .lock()
+        .await
+        .keys()
+        .map(|&id| id as i64)
+        .collect();
+    if let Some(host) = *state.user_id.read().await {
+        online.insert(host as i64);
+    }
+
+    let mut recipients: Vec<(i64, &str)> = Vec::new();
+    if let Ok(members) = sqlx::query_scalar::<_, i64>(
+        "SELECT ur.user_id FROM user_rooms ur JOIN chat_rooms cr ON cr.id = ur.room_id
+         WHERE ur.room_id = $1 AND cr.is_dm = 1",
+    )
+    .bind(room_id as i64)
+    .fetch_all(pool)
+    .await
+    {
+        recipients.extend(members.into_iter().map(|id| (id, "dm")));
+    }
+    for handle in mention_handles(text) {
+        // A handle matches a display name with spaces removed, or an email's local part.
+        if let Ok(ids) = sqlx::query_scalar::<_, i64>(
+            "SELECT id FROM users
+             WHERE lower(replace(name, ' ', '')) = $1
+                OR lower(substr(email, 1, instr(email, '@') - 1)) = $1",
+        )
+        .bind(&handle)
+        .fetch_all(pool)
+        .await
+        {
+            recipients.extend(ids.into_iter().map(|id| (id, "mention")));
+        }
+    }
+
+    for (user_id, reason) in recipients {
+        if user_id == sender_id as i64 || online.contains(&user_id) {
+            continue;
+        }
+        let _ = sqlx::query(
+            "INSERT INTO email_outbox (user_id, message_id, reason) VALUES ($1, $2, $3)
+             ON CONFLICT(user_id, message_id) DO NOTHING",
+        )
+        .bind(user_id)
+        .bind(message_id)
+        .bind(reason)
+        .execute(pool)
+        .await;
+    }
+}
+
+fn snippet(text: &str) -> String {
+    if text.chars().count() <= SNIPPET_CHARS {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(SNIPPET_CHARS).collect();
+        format!("{}…", cut.trim_end())
+    }
+}
+
+/// Subject + plain-text body for one recipient's digest.
+fn compose_digest(recipient: &str, items: &[DigestItem]) -> (String, String) {
+    let subject = if items.len() == 1 {
+        format!("{} sent you a message on Nutler", items[0].sender)
+    } else {
+        format!("You have {} unread messages on Nutler", items.len())
+    };
+    let mut body = format!(
+        "Hi {},\n\nHere's what you missed while you were away:\n\n",
+        recipient
+    );
+    for item in items {
+        let place = match &item.room {
+            Some(room) => format!("in #{}", room),
+            None => "in a direct message".to_string(),
+        };
+        body.push_str(&format!(
+            "• {} {}:\n  {}\n\n",
+            item.sender,
+            place,
+            snippet(&item.text)
+        ));
+    }
+    body.push_str("Open Nutler to reply.\n");
+    (subject, body)
+}
+
+fn build_transport(
+    config: &SmtpConfig,
+    password: Option<String>,
+) -> AppResult<AsyncSmtpTransport<Tokio1Executor>> {
+    let smtp_err = |e: lettre::transport::smtp::Error| AppError::Validation(e.to_string());
+    let mut builder = match config.security {
+        SmtpSecurity::Starttls => {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host).map_err(smtp_err)?
+        }
+        SmtpSecurity::Tls => {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host).map_err(smtp_err)?
+        }
+        SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+    }
+    .port(config.port)
+    .timeout(Some(Duration::from_secs(30)));
+    if !config.username.is_empty() {
+        builder = builder.credentials(Credentials::new(
+            config.username.clone(),
+            password.unwrap_or_default(),
+        ));
+    }
+    Ok(builder.build())
+}
+
+async fn send_email(
+    transport: &AsyncSmtpTransport<Tokio1Executor>,
+    config: &SmtpConfig,
+    to: &str,
+    subject: String,
+    body: String,
+) -> AppResult<()> {
+    let email = Email::builder()
+        .from(
+            config
+                .from_address
+                .parse()
+                .map_err(|e| AppError::Validation(format!("Invalid from address: {}", e)))?,
+        )
+        .to(to
+            .parse()
+            .map_err(|e| AppError::Validation(format!("Invalid recipient {}: {}", to, e)))?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    transport
+        .send(email)
+        .await
+        .map(|_| ())
+        .map_err(|e| AppError::Network(format!("SMTP send failed: {}", e)))
+}
+
+async fn mark_sent(pool: &SqlitePool, ids: &[i64]) {
+    for id in ids {
+        let _ = sqlx::query("UPDATE email_outbox SET sent_at = datetime('now') WHERE id = $1")
+            .bind(id)
+            .execute(pool)
+            .await;
+    }
+}
+
+/// Send one digest per recipient with pending rows. Rows for recipients now online, or for
+/// deleted messages, are retired without an email.
+async fn flush_outbox(state: &Arc<AppState>, pool: &SqlitePool, config: &SmtpConfig) {
+    let rows = match sqlx::query(
+        "SELECT o.id, o.user_id, u.email, u.name AS recipient, m.message, m.deleted_at,
+                s.name AS sender, cr.name AS room, cr.is_dm
+         FROM email_outbox o
+         JOIN users u ON u.id = o.user_id
+         JOIN messages m ON m.message_id = o.message_id
+         JOIN users s ON s.id = m.user_id
+         JOIN chat_rooms cr ON cr.id = m.room_id
+         WHERE o.sent_at IS NULL
+         ORDER BY o.user_id, o.id",
+    )
+    .fetch_all(pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::warn!("Email digest query failed: {}", e);
+            return;
+        }
+    };
+    if rows.is_empty() {
+        return;
+    }
+    let online: HashSet<i64> = state
+        .server_streams
+        .lock()
+        .await
+        .keys()
+        .map(|&id| id as i64)
+        .collect();
+
+    // recipient id → (email, name, row ids, items)
+    let mut digests: BTreeMap<i64, (String, String, Vec<i64>, Vec<DigestItem>)> = BTreeMap::new();
+    let mut retired = Vec::new();
+    for row in rows {
+        let id = row.get::<i64, _>("id");
+        let user_id = row.get::<i64, _>("user_id");
+        let deleted = row.get::<Option<String>, _>("deleted_at").is_some();
+        if deleted || online.contains(&user_id) {
+            retired.push(id);
+            continue;
+        }
+        let entry = digests.entry(user_id).or_insert_with(|| {
+            (
+                row.get::<String, _>("email"),
+                row.get::<String, _>("recipient"),
+                Vec::new(),
+                Vec::new(),
+            )
+        });
+        entry.2.push(id);
+        entry.3.push(DigestItem {
+            sender: row.get::<String, _>("sender"),
+            room: (!row.get::<bool, _>("is_dm")).then(|| row.get::<String, _>("room")),
+            text: row.get::<String, _>("message"),
+        });
+    }
+    mark_sent(pool, &retired).await;
+    if digests.is_empty() {
+        return;
+    }
+
+    let password = keyring_entry().ok().and_then(|e| e.get_password().ok());
+    let transport = match build_transport(config, password) {
+        Ok(t) => t,
+        Err(e) => {
+            tracing::warn!("Email digest: bad SMTP settings: {}", e);
+            return;
+        }
+    };
+    for (email, name, ids, items) in digests.into_values() {
+        let (subject, body) = compose_digest(&name, &items);
+        match send_email(&transport, config, &email, subject, body).await {
+            Ok(()) => mark_sent(pool, &ids).await,
+            // Left pending: retried on the next round.
+            Err(e) => tracing::warn!("Email digest to {} failed: {}", email, e),
+        }
+    }
+    let _ = sqlx::query(
+        "DELETE FROM email_outbox WHERE sent_at IS NOT NULL AND sent_at < datetime('now', $1)",
+    )
+    .bind(format!("-{} days", OUTBOX_RETENTION_DAYS))
+    .execute(pool)
+    .await;
+}
+
+/// Background digest loop, started once at startup. Re-reads the config every round.
+pub fn spawn_digest_worker(state: Arc<AppState>) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let mut minutes = MIN_DIGEST_MINUTES;
+            if let Some(pool) = state.pool.get() {
+                let config = load_config(pool).await.unwrap_or_default();
+                if config.enabled {
+                    flush_outbox(&state, pool, &config).await;
+                    minutes = config.digest_minutes.max(MIN_DIGEST_MINUTES);
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+        }
+    })
+}
+
+#[tauri::command]
+pub async fn get_smtp_config(db: State<'_, SqlitePool>) -> AppResult<SmtpConfig> {
+    load_config(&db).await
+}
+
+/// Save the SMTP settings. `password`, when given, goes to the OS keychain.
+#[tauri::command]
+pub async fn set_smtp_config(
+    db: State<'_, SqlitePool>,
+    config: SmtpConfig,
+    password: Option<String>,
+) -> AppResult<()> {
+    if config.enabled {
+        if config.host.trim().is_empty() {
+            return Err(AppError::Validation("An SMTP host is required".to_string()));
+        }
+        config
+            .from_address
+            .parse::<lettre::message::Mailbox>()
+            .map_err(|e| AppError::Validation(format!("Invalid from address: {}", e)))?;
+    }
+    if let Some(password) = password {
+        keyring_entry()?
+            .set_password(&password)
+            .map_err(|e| AppError::Internal(format!("Failed to save SMTP password: {}", e)))?;
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+/// Send a test email with the saved settings, so an admin can check them before relying on it.
+#[tauri::command]
+pub async fn send_test_email(db: State<'_, SqlitePool>, to: String) -> AppResult<()> {
+    let config = load_config(&db).await?;
+    let password = keyring_entry().ok().and_then(|e| e.get_password().ok());
+    let transport = build_transport(&config, password)?;
+    send_email(
+        &transport,
+        &config,
+        &to,
+        "Nutler test email".to_string(),
+        "Your Nutler email digest settings work.\n".to_string(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_skip_email_addresses() {
+        assert_eq!(
+            mention_handles("hey @Ada and @bob.smith, mail ops@corp.io. @ada again"),
+            vec!["ada".to_string(), "bob.smith".to_string()]
+        );
+        assert!(mention_handles("no mentions @ here").is_empty());
+    }
+
+    #[test]
+    fn digest_summarizes_every_item() {
+        let items = vec![
+            DigestItem {
+                sender: "Bob".into(),
+                room: Some("general".into()),
+                text: "@ada ping".into(),
+            },
+            DigestItem {
+                sender: "Carol".into(),
+                room: None,
+                text: "x".repeat(SNIPPET_CHARS + 10),
+            },
+        ];
+        let (subject, body) = compose_digest("Ada", &items);
+        assert_eq!(subject, "You have 2 unread messages on Nutler");
+        assert!(body.contains("Bob in #general"));
+        assert!(body.contains("Carol in a direct message"));
+        assert!(body.contains('…'));
+
+        let (single, _) = compose_digest("Ada", &items[..1]);
+        assert_eq!(single, "Bob sent you a message on Nutler");
+    }
+}
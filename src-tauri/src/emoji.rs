@@ -0,0 +1,362 @@
+// Emoji normalization for outgoing chat text.
+//
+// Every chat message passes through `normalize` before it is relayed or stored: the text is put
+// in Unicode NFC (so the same word typed on two platforms compares and searches the same),
+// `:shortcode:`s are expanded to the emoji they name, and `is_emoji` is set when what's left is
+// nothing but a few emoji — the UI shows those large. The host normalizes again whatever its
+// clients send, so older clients that don't still get it right. `list_emoji` serves the same
+// shortcode table to the emoji picker.
+
+use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A message of at most this many emoji (and nothing else) is flagged `is_emoji`.
+const MAX_JUMBO_EMOJI: usize = 3;
+
+/// The most `list_emoji` returns.
+const MAX_RESULTS: usize = 60;
+
+/// Shortcode → emoji, most used first (the picker's order when nothing is typed). Aliases
+/// follow their main name.
+const EMOJI: &[(&str, &str)] = &[
+    ("blush", "😊"),
+    ("thinking", "🤔"),
+    ("joy", "😂"),
+    ("rocket", "🚀"),
+    ("thumbsup", "👍"),
+    ("+1", "👍"),
+    ("thumbsdown", "👎"),
+    ("-1", "👎"),
+    ("heart", "❤️"),
+    ("tada", "🎉"),
+    ("fire", "🔥"),
+    ("100", "💯"),
+    ("eyes", "👀"),
+    ("raised_hands", "🙌"),
+    ("grinning", "😀"),
+    ("smiley", "😃"),
+    ("laughing", "😆"),
+    ("sweat_smile", "😅"),
+    ("rofl", "🤣"),
+    ("wink", "😉"),
+    ("smile", "😄"),
+    ("innocent", "😇"),
+    ("slightly_smiling_face", "🙂"),
+    ("upside_down_face", "🙃"),
+    ("heart_eyes", "😍"),
+    ("kissing_heart", "😘"),
+    ("yum", "😋"),
+    ("stuck_out_tongue", "😛"),
+    ("sunglasses", "😎"),
+    ("nerd_face", "🤓"),
+    ("partying_face", "🥳"),
+    ("smirk", "😏"),
+    ("unamused", "😒"),
+    ("roll_eyes", "🙄"),
+    ("grimacing", "😬"),
+    ("relieved", "😌"),
+    ("pensive", "😔"),
+    ("sleepy", "😪"),
+    ("sleeping", "😴"),
+    ("mask", "😷"),
+    ("face_with_thermometer", "🤒"),
+    ("nauseated_face", "🤢"),
+    ("exploding_head", "🤯"),
+    ("cowboy_hat_face", "🤠"),
+    ("confused", "😕"),
+    ("worried", "😟"),
+    ("open_mouth", "😮"),
+    ("astonished", "😲"),
+    ("flushed", "😳"),
+    ("pleading_face", "🥺"),
+    ("cry", "😢"),
+    ("sob", "😭"),
+    ("scream", "😱"),
+    ("confounded", "😖"),
+    ("disappointed", "😞"),
+    ("sweat", "😓"),
+    ("weary", "😩"),
+    ("tired_face", "😫"),
+    ("yawning_face", "🥱"),
+    ("triumph", "😤"),
+    ("rage", "😡"),
+    ("angry", "😠"),
+    ("skull", "💀"),
+    ("poop", "💩"),
+    ("clown_face", "🤡"),
+    ("ghost", "👻"),
+    ("alien", "👽"),
+    ("robot", "🤖"),
+    ("see_no_evil", "🙈"),
+    ("hear_no_evil", "🙉"),
+    ("speak_no_evil", "🙊"),
+    ("wave", "👋"),
+    ("ok_hand", "👌"),
+    ("v", "✌️"),
+    ("crossed_fingers", "🤞"),
+    ("point_up", "☝️"),
+    ("point_right", "👉"),
+    ("point_left", "👈"),
+    ("point_down", "👇"),
+    ("fist", "✊"),
+    ("punch", "👊"),
+    ("clap", "👏"),
+    ("pray", "🙏"),
+    ("handshake", "🤝"),
+    ("muscle", "💪"),
+    ("brain", "🧠"),
+    ("shrug", "🤷"),
+    ("facepalm", "🤦"),
+    ("broken_heart", "💔"),
+    ("sparkling_heart", "💖"),
+    ("blue_heart", "💙"),
+    ("green_heart", "💚"),
+    ("yellow_heart", "💛"),
+    ("purple_heart", "💜"),
+    ("sparkles", "✨"),
+    ("star", "⭐"),
+    ("star2", "🌟"),
+    ("zap", "⚡"),
+    ("boom", "💥"),
+    ("dizzy", "💫"),
+    ("zzz", "💤"),
+    ("speech_balloon", "💬"),
+    ("bulb", "💡"),
+    ("warning", "⚠️"),
+    ("no_entry", "⛔"),
+    ("x", "❌"),
+    ("white_check_mark", "✅"),
+    ("heavy_check_mark", "✔️"),
+    ("question", "❓"),
+    ("exclamation", "❗"),
+    ("bangbang", "‼️"),
+    ("rotating_light", "🚨"),
+    ("construction", "🚧"),
+    ("bug", "🐛"),
+    ("lock", "🔒"),
+    ("unlock", "🔓"),
+    ("key", "🔑"),
+    ("bell", "🔔"),
+    ("mag", "🔍"),
+    ("link", "🔗"),
+    ("hammer", "🔨"),
+    ("wrench", "🔧"),
+    ("gear", "⚙️"),
+    ("package", "📦"),
+    ("memo", "📝"),
+    ("pencil", "✏️"),
+    ("pushpin", "📌"),
+    ("paperclip", "📎"),
+    ("calendar", "📅"),
+    ("chart_with_upwards_trend", "📈"),
+    ("chart_with_downwards_trend", "📉"),
+    ("bar_chart", "📊"),
+    ("clipboard", "📋"),
+    ("email", "📧"),
+    ("phone", "📱"),
+    ("computer", "💻"),
+    ("keyboard", "⌨️"),
+    ("hourglass", "⌛"),
+    ("stopwatch", "⏱️"),
+    ("alarm_clock", "⏰"),
+    ("moneybag", "💰"),
+    ("trophy", "🏆"),
+    ("medal", "🏅"),
+    ("dart", "🎯"),
+    ("gift", "🎁"),
+    ("balloon", "🎈"),
+    ("confetti_ball", "🎊"),
+    ("coffee", "☕"),
+    ("tea", "🍵"),
+    ("beer", "🍺"),
+    ("beers", "🍻"),
+    ("wine_glass", "🍷"),
+    ("pizza", "🍕"),
+    ("hamburger", "🍔"),
+    ("cake", "🍰"),
+    ("birthday", "🎂"),
+    ("cookie", "🍪"),
+    ("doughnut", "🍩"),
+    ("apple", "🍎"),
+    ("sunny", "☀️"),
+    ("cloud", "☁️"),
+    ("umbrella", "☔"),
+    ("snowflake", "❄️"),
+    ("rainbow", "🌈"),
+    ("earth_africa", "🌍"),
+    ("seedling", "🌱"),
+    ("four_leaf_clover", "🍀"),
+    ("dog", "🐶"),
+    ("cat", "🐱"),
+    ("unicorn", "🦄"),
+    ("turtle", "🐢"),
+    ("snail", "🐌"),
+    ("car", "🚗"),
+    ("airplane", "✈️"),
+    ("house", "🏠"),
+    ("office", "🏢"),
+    ("checkered_flag", "🏁"),
+    ("triangular_flag_on_post", "🚩"),
+    ("arrow_up", "⬆️"),
+    ("arrow_down", "⬇️"),
+    ("arrow_right", "➡️"),
+    ("arrow_left", "⬅️"),
+    ("repeat", "🔁"),
+    ("heavy_plus_sign", "➕"),
+    ("heavy_minus_sign", "➖"),
+    ("red_circle", "🔴"),
+    ("green_circle", "🟢"),
+    ("large_blue_circle", "🔵"),
+];
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct EmojiEntry {
+    pub shortcode: &'static str,
+    pub emoji: &'static str,
+}
+
+pub(crate) fn lookup(shortcode: &str) -> Option<&'static str> {
+    EMOJI
+        .iter()
+        .find(|(name, _)| *name == shortcode)
+        .map(|(_, emoji)| *emoji)
+}
+
+/// Replace each known `:shortcode:` with its emoji. Unknown names (and times like `12:30:45`)
+/// are left alone, as is anything inside a `code span`.
+pub fn expand_shortcodes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_code = false;
+    let mut rest = text;
+    while let Some(i) = rest.find([':', '`']) {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        if rest.as_bytes()[i] == b'`' {
+            in_code = !in_code;
+            out.push('`');
+            rest = after;
+            continue;
+        }
+        let emoji = match after.find(':') {
+            Some(end) if !in_code => lookup(&after[..end]).map(|e| (e, end)),
+            _ => None,
+        };
+        match emoji {
+            Some((e, end)) => {
+                out.push_str(e);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push(':');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A pictographic base code point (or one that becomes an emoji with VS16, checked separately).
+fn is_pictographic(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF
+            | 0x2600..=0x27BF
+            | 0x2B00..=0x2BFF
+            | 0x231A..=0x231B
+            | 0x2328
+            | 0x23E9..=0x23FA
+            | 0x3030
+            | 0x303D
+            | 0x3297
+            | 0x3299
+    )
+}
+
+/// One user-perceived character that renders as an emoji: a pictograph (with any skin tone /
+/// ZWJ sequence), a flag, a keycap, or a symbol forced to emoji style with VS16.
+fn is_emoji_grapheme(g: &str) -> bool {
+    let Some(first) = g.chars().next() else {
+        return false;
+    };
+    is_pictographic(first) || g.contains('\u{20E3}') || g.contains('\u{FE0F}')
+}
+
+/// Whether `text` is only emoji (at most MAX_JUMBO_EMOJI of them), ignoring whitespace.
+pub fn is_emoji_only(text: &str) -> bool {
+    let mut count = 0;
+    for g in text.graphemes(true) {
+        if g.trim().is_empty() {
+            continue;
+        }
+        if !is_emoji_grapheme(g) {
+            return false;
+        }
+        count += 1;
+    }
+    (1..=MAX_JUMBO_EMOJI).contains(&count)
+}
+
+/// NFC-normalize `text` and expand its shortcodes; returns the text and whether it's emoji-only.
+pub fn normalize(text: &str) -> (String, bool) {
+    let nfc: String = text.nfc().collect();
+    let expanded = expand_shortcodes(&nfc);
+    let is_emoji = is_emoji_only(&expanded);
+    (expanded, is_emoji)
+}
+
+/// Emoji whose shortcode contains `query` (colons and case ignored), prefix matches first. An
+/// empty query lists the most used ones.
+#[tauri::command]
+pub fn list_emoji(query: String) -> Vec<EmojiEntry> {
+    let q = query.trim().trim_matches(':').to_lowercase();
+    let entries = EMOJI
+        .iter()
+        .map(|(shortcode, emoji)| EmojiEntry { shortcode, emoji });
+    if q.is_empty() {
+        return entries.take(MAX_RESULTS).collect();
+    }
+    let (mut prefix, rest): (Vec<_>, Vec<_>) = entries
+        .filter(|e| e.shortcode.contains(q.as_str()))
+        .partition(|e| e.shortcode.starts_with(q.as_str()));
+    prefix.extend(rest);
+    prefix.truncate(MAX_RESULTS);
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcodes_expand_outside_code_only() {
+        assert_eq!(expand_shortcodes("ship it :rocket: :+1:"), "ship it 🚀 👍");
+        assert_eq!(
+            expand_shortcodes("at 12:30:45 :nope:"),
+            "at 12:30:45 :nope:"
+        );
+        assert_eq!(expand_shortcodes("`:fire:` :fire:"), "`:fire:` 🔥");
+    }
+
+    #[test]
+    fn emoji_only_messages_are_flagged() {
+        assert_eq!(normalize(":tada:"), ("🎉".to_string(), true));
+        assert!(is_emoji_only(" 👍🏽 ❤️ "));
+        assert!(is_emoji_only("👨‍👩‍👧"));
+        assert!(is_emoji_only("🇫🇷"));
+        assert!(!is_emoji_only("👍 ok"));
+        assert!(!is_emoji_only("🔥🔥🔥🔥"));
+        assert!(!is_emoji_only("   "));
+        // Decomposed é is stored composed.
+        assert_eq!(normalize("cafe\u{301}").0, "café");
+    }
+
+    #[test]
+    fn list_emoji_puts_prefix_matches_first() {
+        let hits = list_emoji(":heart".to_string());
+        assert_eq!(hits[0].shortcode, "heart");
+        assert!(hits.iter().any(|e| e.shortcode == "broken_heart"));
+        assert_eq!(list_emoji(String::new()).len(), MAX_RESULTS);
+    }
+}
@@ -4,11 +4,11 @@
 // display. `From` conversions let existing `?`-based code keep working: a bare String or a
 // sqlx error becomes an `Internal`/`Db` error with its text preserved.
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 pub type AppResult<T> = Result<T, AppError>;
 
-#[derive(Debug, thiserror::Error, Serialize)]
+#[derive(Debug, thiserror::Error, Serialize, Deserialize)]
 #[serde(tag = "code", content = "message", rename_all = "snake_case")]
 pub enum AppError {
     /// Bad input (length/format/empty) — the user should correct and retry.
@@ -20,9 +20,22 @@ pub enum AppError {
     /// Authentication / authorization failure (wrong password, not a member).
     #[error("{0}")]
     Auth(String),
+    /// The session's role doesn't allow the command (see permissions.rs).
+    #[error("{0}")]
+    PermissionDenied(String),
     /// A networking/transport failure (couldn't connect, send, or discover).
     #[error("{0}")]
     Network(String),
+    /// A message longer than the host's configured limit (see limits.rs).
+    #[error("{0}")]
+    MessageTooLarge(String),
+    /// A file over the host's per-file limit, or one that won't fit in its attachment storage
+    /// (see attachments.rs).
+    #[error("{0}")]
+    StorageQuota(String),
+    /// Too little free disk space left for a write (see disk_space.rs).
+    #[error("{0}")]
+    DiskFull(String),
     /// A database error.
     #[error("{0}")]
     Db(String),
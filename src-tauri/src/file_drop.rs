@@ -0,0 +1,154 @@
+// Files dropped onto the window.
+//
+// A drop shares each file in the current room, one after another, the same way as a picked
+// file (attachments.rs): stored and posted on the host, uploaded by a client. Each is checked
+// first against this machine's scan policy (file_scan.rs: blocked extensions, size cap), and
+// folders and empty files are skipped, so a refused file never starts a transfer; the host's
+// storage limits apply as usual once it does. Every file reports through `file_drop` events
+// (queued, sending, then sent, refused or failed with the reason), and an upload's bytes as
+// they go through `attachment_progress`.
+
+use crate::attachments;
+use crate::error::{AppError, AppResult};
+use crate::file_scan;
+use crate::sockets::AppState;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{Emitter, Manager};
+use uuid::Uuid;
+
+/// The `file_drop` event, one or more per dropped file.
+#[derive(Serialize, Clone, Debug)]
+pub struct DropProgress {
+    pub drop_id: String,
+    pub filename: String,
+    // Position in the drop (from 1), and how many files it had.
+    pub index: usize,
+    pub count: usize,
+    // "queued", "sending", "sent", "refused" (by a check here) or "failed".
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The name to share a dropped file under and its size, or why it can't be shared.
+async fn check(pool: &SqlitePool, path: &Path) -> AppResult<(String, u64)> {
+    let meta = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| AppError::Validation(format!("Couldn't read the file: {}", e)))?;
+    if !meta.is_file() {
+        return Err(AppError::Validation(
+            "Folders can't be shared; drop the files in it instead".to_string(),
+        ));
+    }
+    if meta.len() == 0 {
+        return Err(AppError::Validation("The file is empty".to_string()));
+    }
+    let filename = attachments::clean_filename(&path.to_string_lossy())?;
+    let scan = file_scan::load_config(pool).await?;
+    file_scan::check_policy(&scan, &filename, meta.len()).map_err(AppError::Validation)?;
+    Ok((filename, meta.len()))
+}
+
+/// A window's drop: share `paths` in the current room, in the background.
+pub fn handle(app: &tauri::AppHandle, paths: Vec<PathBuf>) {
+    if paths.is_empty() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let (Some(state), Some(pool)) = (
+            app.try_state::<Arc<AppState>>(),
+            app.try_state::<SqlitePool>(),
+        ) else {
+            return;
+        };
+        let room_id = *state.current_room_id.read().await;
+        let drop_id = Uuid::new_v4().to_string();
+        let count = paths.len();
+        let report = |index: usize, path: &Path, status, error: Option<String>| {
+            let progress = DropProgress {
+                drop_id: drop_id.clone(),
+                filename: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                index,
+                count,
+                status,
+                error,
+            };
+            if let Err(e) = app.emit("file_drop", progress) {
+                tracing::warn!("Failed to emit file_drop: {}", e);
+            }
+        };
+        for (i, path) in paths.iter().enumerate() {
+            report(i + 1, path, "queued", None);
+        }
+        for (i, path) in paths.iter().enumerate() {
+            let Some(room_id) = room_id else {
+                report(
+                    i + 1,
+                    path,
+                    "refused",
+                    Some("Open a room to share files".to_string()),
+                );
+                continue;
+            };
+            let (filename, size) = match check(&pool, path).await {
+                Ok(checked) => checked,
+                Err(e) => {
+                    report(i + 1, path, "refused", Some(e.to_string()));
+                    continue;
+                }
+            };
+            report(i + 1, path, "sending", None);
+            tracing::info!("📎 Sharing dropped file {} ({} bytes)", filename, size);
+            match attachments::share_file(&app, state.inner(), &pool, room_id, path, filename).await
+            {
+                Ok(()) => report(i + 1, path, "sent", None),
+                Err(e) => report(i + 1, path, "failed", Some(e.to_string())),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_queries::set_setting_internal;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn dropped_files_are_checked_before_sending() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        set_setting_internal(&pool, "file_scan", r#"{"blocked_extensions": ["exe"]}"#)
+            .await
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("nutler-drop-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let notes = dir.join("notes.txt");
+        tokio::fs::write(&notes, b"hello").await.unwrap();
+        let setup = dir.join("setup.exe");
+        tokio::fs::write(&setup, b"MZ").await.unwrap();
+        let empty = dir.join("empty.txt");
+        tokio::fs::write(&empty, b"").await.unwrap();
+
+        assert_eq!(
+            check(&pool, &notes).await.unwrap(),
+            ("notes.txt".to_string(), 5)
+        );
+        assert!(check(&pool, &setup).await.is_err());
+        assert!(check(&pool, &empty).await.is_err());
+        assert!(check(&pool, &dir).await.is_err());
+        assert!(check(&pool, &dir.join("missing.txt")).await.is_err());
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+// Screening received files (local setting).
+//
+// Before a received file is kept (an upload on the host, a download on a client) it's checked
+// against this machine's scan policy: blocked extensions, an optional size cap, and an optional
+// external scanner such as `clamscan`, run as `<command> [args…] <file>`. Exit status 0 means
+// clean; anything else, a timeout, or a scanner that won't start refuses the file. A refused file
+// is moved to `<app config>/quarantine` rather than deleted, the recipient gets an
+// `attachment_quarantined` event, and every verdict goes to the audit log
+// (`file_scan.clean` / `file_scan.quarantined`).
+
+use crate::db_queries::{get_setting_internal, record_audit_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{Emitter, Manager, Runtime, State};
+use uuid::Uuid;
+
+const SETTINGS_KEY: &str = "file_scan";
+
+/// A scanner that hasn't answered by then counts as a failed scan.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ScanConfig {
+    // Path to the scanner; None = no external scan.
+    #[serde(default)]
+    pub command: Option<String>,
+    // Passed before the file's path, e.g. ["--no-summary"].
+    #[serde(default)]
+    pub args: Vec<String>,
+    // Extensions (without the dot, any case) that are always refused.
+    #[serde(default)]
+    pub blocked_extensions: Vec<String>,
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+}
+
+/// The `attachment_quarantined` event.
+#[derive(Serialize, Clone, Debug)]
+pub struct Quarantined {
+    // The attachment, when it already has an id (downloads).
+    pub attachment_id: Option<i64>,
+    pub filename: String,
+    pub reason: String,
+    // Where the file was put aside (None if even that failed and it was deleted).
+    pub path: Option<PathBuf>,
+}
+
+pub(crate) async fn load_config(pool: &SqlitePool) -> AppResult<ScanConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt scan settings: {}", e))),
+        None => Ok(ScanConfig::default()),
+    }
+}
+
+/// The extension and size rules; Err carries the reason a file is refused.
+pub fn check_policy(config: &ScanConfig, filename: &str, size: u64) -> Result<(), String> {
+    if let Some((_, ext)) = filename.rsplit_once('.') {
+        if config
+            .blocked_extensions
+            .iter()
+            .any(|b| b.trim_start_matches('.').eq_ignore_ascii_case(ext))
+        {
+            return Err(format!(".{} files aren't accepted", ext.to_lowercase()));
+        }
+    }
+    if let Some(max) = config.max_bytes {
+        if size > max {
+            return Err(format!("larger than the {} byte limit", max));
+        }
+    }
+    Ok(())
+}
+
+/// Run the external scanner on `path`.
+async fn run_scanner(command: &str, args: &[String], path: &Path) -> Result<(), String> {
+    let run = tokio::process::Command::new(command)
+        .args(args)
+        .arg(path)
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(SCAN_TIMEOUT, run).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(format!("the scanner couldn't run: {}", e)),
+        Err(_) => return Err("the scanner timed out".to_string()),
+    };
+    if output.status.success() {
+        return Ok(());
+    }
+    // clamscan and friends print "<path>: <signature> FOUND"; keep the finding, not the path.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let finding = stdout
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .map(|l| l.rsplit(": ").next().unwrap_or(l).trim().to_string());
+    Err(match (output.status.code(), finding) {
+        (Some(1), Some(finding)) => format!("flagged by the scanner ({})", finding),
+        (Some(1), None) => "flagged by the scanner".to_string(),
+        (code, _) => format!(
+            "the scanner failed (exit status {})",
+            code.map_or("none".to_string(), |c| c.to_string())
+        ),
+    })
+}
+
+/// Check a received file against `config`: the policy first, then the scanner.
+pub async fn scan_internal(
+    config: &ScanConfig,
+    path: &Path,
+    filename: &str,
+    size: u64,
+) -> Result<(), String> {
+    check_policy(config, filename, size)?;
+    match config.command.as_deref().filter(|c| !c.trim().is_empty()) {
+        Some(command) => run_scanner(command, &config.args, path).await,
+        None => Ok(()),
+    }
+}
+
+/// Move a refused file into `dir` under a unique name (deleting it if that fails).
+async fn quarantine(dir: &Path, path: &Path, filename: &str) -> Option<PathBuf> {
+    let dest = dir.join(format!("{}-{}", Uuid::new_v4(), filename));
+    let moved = async {
+        tokio::fs::create_dir_all(dir).await?;
+        tokio::fs::rename(path, &dest).await
+    }
+    .await;
+    match moved {
+        Ok(()) => Some(dest),
+        Err(e) => {
+            tracing::warn!("Couldn't quarantine {}: {}; deleting it", filename, e);
+            let _ = tokio::fs::remove_file(path).await;
+            None
+        }
+    }
+}
+
+/// Screen a received file at `path` before it's kept. A refused file is quarantined, reported
+/// with `attachment_quarantined`, and the reason returned; either way the verdict is audited
+/// under `actor`.
+pub(crate) async fn screen<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    pool: &SqlitePool,
+    path: &Path,
+    filename: &str,
+    size: u64,
+    attachment_id: Option<i64>,
+    actor: Option<i64>,
+) -> Result<(), String> {
+    let verdict = match load_config(pool).await {
+        Ok(config) => scan_internal(&config, path, filename, size).await,
+        Err(e) => Err(format!("the scan settings couldn't be read: {}", e)),
+    };
+    let (action, detail) = match &verdict {
+        Ok(()) => {
+            tracing::info!("File scan: {} is clean", filename);
+            ("file_scan.clean", None)
+        }
+        Err(reason) => {
+            tracing::warn!("File scan: quarantined {}: {}", filename, reason);
+            ("file_scan.quarantined", Some(reason.as_str()))
+        }
+    };
+    if let Err(e) = record_audit_internal(pool, actor, action, filename, detail).await {
+        tracing::warn!("{}", e);
+    }
+    let Err(reason) = verdict else {
+        return Ok(());
+    };
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map(|d| d.join("quarantine"))
+        .unwrap_or_else(|_| std::env::temp_dir().join("nutler-quarantine"));
+    let event = Quarantined {
+        attachment_id,
+        filename: filename.to_string(),
+        reason: reason.clone(),
+        path: quarantine(&dir, path, filename).await,
+    };
+    let _ = app.emit("attachment_quarantined", &event);
+    Err(reason)
+}
+
+#[tauri::command]
+pub async fn get_scan_config(db: State<'_, SqlitePool>) -> AppResult<ScanConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_scan_config(db: State<'_, SqlitePool>, config: ScanConfig) -> AppResult<()> {
+    if let Some(command) = config.command.as_deref().filter(|c| !c.trim().is_empty()) {
+        if !Path::new(command).is_file() {
+            return Err(AppError::Validation(format!(
+                "No scanner found at {}",
+                command
+            )));
+        }
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn policy_and_scanner_verdicts() {
+        let config = ScanConfig {
+            blocked_extensions: vec![".exe".to_string(), "bat".to_string()],
+            max_bytes: Some(100),
+            ..Default::default()
+        };
+        assert!(check_policy(&config, "notes.txt", 100).is_ok());
+        assert!(check_policy(&config, "Setup.EXE", 1).is_err());
+        assert!(check_policy(&config, "run.bat", 1).is_err());
+        assert!(check_policy(&config, "big.txt", 101).is_err());
+
+        let file = std::env::temp_dir().join(format!("nutler-scan-{}", Uuid::new_v4()));
+        tokio::fs::write(&file, b"hello").await.unwrap();
+        let with_script = |script: &str| ScanConfig {
+            command: Some("sh".to_string()),
+            args: vec!["-c".to_string(), script.to_string(), "sh".to_string()],
+            ..Default::default()
+        };
+        assert!(scan_internal(&with_script("exit 0"), &file, "a.txt", 5)
+            .await
+            .is_ok());
+        assert_eq!(
+            scan_internal(
+                &with_script("echo \"$1: Eicar-Signature FOUND\"; exit 1"),
+                &file,
+                "a.txt",
+                5
+            )
+            .await,
+            Err("flagged by the scanner (Eicar-Signature FOUND)".to_string())
+        );
+        assert_eq!(
+            scan_internal(&with_script("exit 2"), &file, "a.txt", 5).await,
+            Err("the scanner failed (exit status 2)".to_string())
+        );
+        let missing = ScanConfig {
+            command: Some("/nonexistent/scanner".to_string()),
+            ..Default::default()
+        };
+        assert!(scan_internal(&missing, &file, "a.txt", 5).await.is_err());
+
+        let dir = std::env::temp_dir().join(format!("nutler-quarantine-{}", Uuid::new_v4()));
+        let moved = quarantine(&dir, &file, "a.txt").await.unwrap();
+        assert!(moved.is_file() && !file.exists());
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
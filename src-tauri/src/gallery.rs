@@ -0,0 +1,310 @@
+// A room's files: what's been shared in it, newest first, for its "Files" tab.
+//
+// Listed from the host's `attachments` table with each file's uploader, size and date, leaving
+// out files whose message was deleted. The list can be narrowed to images, audio and video, or
+// everything else ("documents"), and comes a page of PAGE_SIZE at a time. On a client
+// `get_room_attachments` asks the host (GalleryRequest, answered by a Gallery frame carrying the
+// same `message_id`), which only lists a room the requester may join.
+
+use crate::db_queries::room_join_allowed_internal;
+use crate::error::{AppError, AppResult};
+use crate::sockets::{
+    frame, send_secure_client, send_to_client, server_now_secs, AppState, Message, MessageType,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+/// Files per page.
+pub const PAGE_SIZE: i64 = 50;
+
+/// How long a client waits for the host's Gallery answer.
+const GALLERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TypeFilter {
+    #[default]
+    All,
+    Images,
+    // Audio and video.
+    Media,
+    // Everything that isn't an image, audio or video.
+    Documents,
+}
+
+impl TypeFilter {
+    fn condition(self) -> &'static str {
+        match self {
+            TypeFilter::All => "1",
+            TypeFilter::Images => "a.mime LIKE 'image/%'",
+            TypeFilter::Media => "(a.mime LIKE 'audio/%' OR a.mime LIKE 'video/%')",
+            TypeFilter::Documents => {
+                "NOT (a.mime LIKE 'image/%' OR a.mime LIKE 'audio/%' OR a.mime LIKE 'video/%')"
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct RoomFile {
+    // The attachment id, for download_attachment.
+    pub id: i64,
+    pub message_id: String,
+    pub filename: String,
+    pub mime: String,
+    pub size: i64,
+    pub uploaded_by: Option<i64>,
+    // None once the uploader's account is gone.
+    pub uploader: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RoomFilesPage {
+    pub files: Vec<RoomFile>,
+    pub has_more: bool,
+}
+
+/// A GalleryRequest's `message`.
+#[derive(Serialize, Deserialize, Debug)]
+struct GalleryQuery {
+    room_id: i64,
+    #[serde(default)]
+    type_filter: TypeFilter,
+    #[serde(default)]
+    page: u32,
+}
+
+/// A Gallery frame's `message`: the page, or why there isn't one.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct GalleryReply {
+    #[serde(default)]
+    error: Option<AppError>,
+    #[serde(default)]
+    page: Option<RoomFilesPage>,
+}
+
+/// Page `page` (from 0) of the files shared in `room_id`, newest first.
+pub async fn room_files_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    filter: TypeFilter,
+    page: u32,
+) -> AppResult<RoomFilesPage> {
+    let sql = format!(
+        "SELECT a.id, a.message_id, a.filename, a.mime, a.size, a.uploaded_by,
+                u.name AS uploader, a.created_at
+         FROM attachments a LEFT JOIN users u ON u.id = a.uploaded_by
+         WHERE a.room_id = $1 AND {}
+           AND NOT EXISTS (SELECT 1 FROM messages m
+                           WHERE m.message_id = a.message_id AND m.deleted_at IS NOT NULL)
+         ORDER BY a.created_at DESC, a.id DESC
+         LIMIT $2 OFFSET $3",
+        filter.condition()
+    );
+    let mut files: Vec<RoomFile> = sqlx::query_as(&sql)
+        .bind(room_id)
+        .bind(PAGE_SIZE + 1)
+        .bind(page as i64 * PAGE_SIZE)
+        .fetch_all(pool)
+        .await?;
+    let has_more = files.len() as i64 > PAGE_SIZE;
+    files.truncate(PAGE_SIZE as usize);
+    Ok(RoomFilesPage { files, has_more })
+}
+
+/// Host: answer a client's GalleryRequest with a Gallery frame under the same `message_id`.
+pub(crate) async fn answer_request(
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    requester: u64,
+    request: &Message,
+) {
+    let reply = match serde_json::from_str::<GalleryQuery>(&request.message) {
+        Ok(query) => {
+            match room_join_allowed_internal(pool, requester as i64, query.room_id).await {
+                Ok(true) => {
+                    match room_files_internal(pool, query.room_id, query.type_filter, query.page)
+                        .await
+                    {
+                        Ok(page) => GalleryReply {
+                            page: Some(page),
+                            ..Default::default()
+                        },
+                        Err(e) => GalleryReply {
+                            error: Some(e),
+                            ..Default::default()
+                        },
+                    }
+                }
+                _ => GalleryReply {
+                    error: Some(AppError::PermissionDenied(
+                        "You can't see that room's files".to_string(),
+                    )),
+                    ..Default::default()
+                },
+            }
+        }
+        Err(e) => GalleryReply {
+            error: Some(AppError::Validation(format!(
+                "Bad file list request: {}",
+                e
+            ))),
+            ..Default::default()
+        },
+    };
+    let json = serde_json::to_string(&reply).unwrap_or_default();
+    let mut frame = frame(MessageType::Gallery, requester, json);
+    frame.message_id = request.message_id.clone();
+    send_to_client(state, requester, &frame).await;
+}
+
+/// Client: hand a Gallery frame from the primary connection's host to whoever asked for it.
+pub(crate) async fn handle_host_frame(state: &Arc<AppState>, message: &Message) {
+    if let Some(tx) = state
+        .gallery_replies
+        .lock()
+        .await
+        .remove(&message.message_id)
+    {
+        let _ = tx.send(message.message.clone());
+    }
+}
+
+/// Client: ask the host for a page of a room's files and wait for the answer.
+async fn request_room_files(
+    state: &Arc<AppState>,
+    query: &GalleryQuery,
+) -> AppResult<RoomFilesPage> {
+    let json = serde_json::to_string(query).map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut request = frame(
+        MessageType::GalleryRequest,
+        state.user_id.read().await.unwrap_or(0),
+        json,
+    );
+    request.created_at = server_now_secs(&state.clock_offset_ms);
+    let id = request.message_id.clone();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    state.gallery_replies.lock().await.insert(id.clone(), tx);
+    if let Err(e) = send_secure_client(state, &request).await {
+        state.gallery_replies.lock().await.remove(&id);
+        return Err(AppError::Network(e));
+    }
+    let json = match tokio::time::timeout(GALLERY_TIMEOUT, rx).await {
+        Ok(Ok(json)) => json,
+        _ => {
+            state.gallery_replies.lock().await.remove(&id);
+            return Err(AppError::Network(
+                "The host didn't answer the file list request".to_string(),
+            ));
+        }
+    };
+    let reply: GalleryReply = serde_json::from_str(&json)
+        .map_err(|e| AppError::Network(format!("The host's file list wasn't understood: {}", e)))?;
+    match reply {
+        GalleryReply { error: Some(e), .. } => Err(e),
+        GalleryReply {
+            page: Some(page), ..
+        } => Ok(page),
+        _ => Err(AppError::Network("The host sent no file list".to_string())),
+    }
+}
+
+/// The files shared in a room, newest first: from this host's store, or on a client from the
+/// host's. `type_filter` is "all" (the default), "images", "media" or "documents".
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_room_attachments(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    type_filter: Option<TypeFilter>,
+    page: Option<u32>,
+) -> AppResult<RoomFilesPage> {
+    let query = GalleryQuery {
+        room_id,
+        type_filter: type_filter.unwrap_or_default(),
+        page: page.unwrap_or(0),
+    };
+    if *state.is_server.read().await {
+        room_files_internal(&db, query.room_id, query.type_filter, query.page).await
+    } else {
+        request_room_files(state.inner(), &query).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::memory_pool;
+
+    #[tokio::test]
+    async fn room_files_are_filtered_and_paged() {
+        let pool = memory_pool().await;
+        sqlx::query("INSERT INTO users (id, name, email) VALUES (1, 'Alice', 'a@x')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let room: i64 = sqlx::query_scalar("SELECT id FROM chat_rooms WHERE name = 'Company Wide'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let files = [
+            ("a", "plan.pdf", "application/pdf"),
+            ("b", "shot.png", "image/png"),
+            ("c", "memo.m4a", "audio/mp4"),
+            ("d", "gone.png", "image/png"),
+        ];
+        for (i, (message_id, name, mime)) in files.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO attachments
+                     (room_id, message_id, uploaded_by, filename, mime, size, sha256, created_at)
+                 VALUES ($1, $2, 1, $3, $4, 100, '', datetime('now', $5))",
+            )
+            .bind(room)
+            .bind(message_id)
+            .bind(name)
+            .bind(mime)
+            .bind(format!("-{} minutes", 10 - i))
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO messages (room_id, user_id, message, message_type, message_id, deleted_at)
+             VALUES ($1, 1, '', 'Attachment', 'd', CURRENT_TIMESTAMP)",
+        )
+        .bind(room)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let names = |page: &RoomFilesPage| -> Vec<String> {
+            page.files.iter().map(|f| f.filename.clone()).collect()
+        };
+        let all = room_files_internal(&pool, room, TypeFilter::All, 0)
+            .await
+            .unwrap();
+        assert_eq!(names(&all), ["memo.m4a", "shot.png", "plan.pdf"]);
+        assert_eq!(all.files[0].uploader.as_deref(), Some("Alice"));
+        assert!(!all.has_more);
+        let images = room_files_internal(&pool, room, TypeFilter::Images, 0)
+            .await
+            .unwrap();
+        assert_eq!(names(&images), ["shot.png"]);
+        let media = room_files_internal(&pool, room, TypeFilter::Media, 0)
+            .await
+            .unwrap();
+        assert_eq!(names(&media), ["memo.m4a"]);
+        let documents = room_files_internal(&pool, room, TypeFilter::Documents, 0)
+            .await
+            .unwrap();
+        assert_eq!(names(&documents), ["plan.pdf"]);
+        let past_the_end = room_files_internal(&pool, room, TypeFilter::All, 1)
+            .await
+            .unwrap();
+        assert!(past_the_end.files.is_empty());
+    }
+}
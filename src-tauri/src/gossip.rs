@@ -0,0 +1,643 @@
+// Serverless mode: presence by gossip, messages over direct links (opt-in).
+//
+// For when nobody wants to host. Each peer multicasts a heartbeat on the LAN every few seconds
+// carrying its own entry (name, TCP port, rooms) and a sample of the peers it knows about, so
+// presence spreads even when some datagrams are lost; a peer whose heartbeat stops counting up
+// is dropped after PEER_TIMEOUT. Datagrams are tagged with a hash keyed by the shared passphrase,
+// so groups using different passphrases don't see each other. A message is delivered straight to
+// every live peer in its room over a one-off Noise link keyed the same way; the sender's name is
+// taken from the gossip entry its link named, never from the frame. There's no host, so nothing
+// is kept: messages are shown as they arrive (`serverless_message`), and the peer list is pushed
+// as `serverless_peers` whenever it changes. The passphrase lives in memory only.
+
+use crate::codec::{FramedReader, FramedWriter};
+use crate::error::{AppError, AppResult};
+use crate::limits;
+use crate::secure;
+use crate::sockets::{frame, is_lan_source, AppState, Message, MessageType};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, State};
+use uuid::Uuid;
+
+const GOSSIP_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 36, 27);
+const GOSSIP_PORT: u16 = 3627;
+const DEFAULT_TCP_PORT: u16 = 3628;
+const GOSSIP_VERSION: u16 = 1;
+/// Stay under a typical Ethernet MTU.
+const MAX_PACKET: usize = 1400;
+const TAG_LEN: usize = 32;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(3);
+/// A peer whose heartbeat hasn't moved for this long is gone.
+const PEER_TIMEOUT: Duration = Duration::from_secs(15);
+/// Known peers relayed in each heartbeat, at most.
+const RELAYED_PEERS: usize = 8;
+const MAX_ROOMS: usize = 32;
+const MAX_NAME_CHARS: usize = 64;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PeerEntry {
+    pub id: String,
+    pub name: String,
+    // Where the peer takes message links; the address is filled in from the datagram for a
+    // peer's own entry, and relayed as heard for the rest.
+    #[serde(default)]
+    pub ip: Option<IpAddr>,
+    pub port: u16,
+    pub rooms: Vec<String>,
+    pub heartbeat: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Heartbeat {
+    version: u16,
+    from: PeerEntry,
+    peers: Vec<PeerEntry>,
+}
+
+struct Known {
+    entry: PeerEntry,
+    // When its heartbeat last counted up.
+    seen: Instant,
+}
+
+/// This peer's serverless session, while it's on.
+pub struct Serverless {
+    me: PeerEntry,
+    psk: [u8; 32],
+    peers: HashMap<String, Known>,
+    tasks: Vec<tauri::async_runtime::JoinHandle<()>>,
+}
+
+#[derive(Serialize)]
+pub struct ServerlessDelivery {
+    pub message: Message,
+    // Names of the peers it reached, and of those it didn't.
+    pub delivered: Vec<String>,
+    pub unreachable: Vec<String>,
+}
+
+/// A stable numeric id for a peer, for the `user_id` of its messages.
+fn peer_user_id(peer_id: &str) -> u64 {
+    let digest = Sha256::digest(peer_id.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+fn tag(psk: &[u8; 32], body: &[u8]) -> [u8; TAG_LEN] {
+    let mut hasher = Sha256::new();
+    hasher.update(psk);
+    hasher.update(body);
+    hasher.update(psk);
+    hasher.finalize().into()
+}
+
+/// The datagram for `heartbeat`: its tag, then its JSON. Relayed peers are dropped from the end
+/// until it fits in a packet.
+fn seal(psk: &[u8; 32], heartbeat: &mut Heartbeat) -> Option<Vec<u8>> {
+    loop {
+        let body = serde_json::to_vec(heartbeat).ok()?;
+        if TAG_LEN + body.len() <= MAX_PACKET {
+            let mut packet = tag(psk, &body).to_vec();
+            packet.extend_from_slice(&body);
+            return Some(packet);
+        }
+        heartbeat.peers.pop()?;
+    }
+}
+
+/// The heartbeat in a datagram, if it's tagged with our passphrase.
+fn open(psk: &[u8; 32], packet: &[u8]) -> Option<Heartbeat> {
+    if packet.len() <= TAG_LEN {
+        return None;
+    }
+    let (got, body) = packet.split_at(TAG_LEN);
+    if got != tag(psk, body) {
+        return None;
+    }
+    let heartbeat: Heartbeat = serde_json::from_slice(body).ok()?;
+    (heartbeat.version == GOSSIP_VERSION).then_some(heartbeat)
+}
+
+fn entry_valid(entry: &PeerEntry) -> bool {
+    !entry.id.is_empty()
+        && entry.id.len() <= 64
+        && entry.name.chars().count() <= MAX_NAME_CHARS
+        && entry.rooms.len() <= MAX_ROOMS
+        && entry.port != 0
+        && entry.ip.is_some_and(is_lan_source)
+}
+
+/// Fold a heartbeat heard from `src` into `peers`. Returns whether anyone joined or changed
+/// their name or rooms.
+fn merge(
+    peers: &mut HashMap<String, Known>,
+    me: &str,
+    src: IpAddr,
+    heartbeat: Heartbeat,
+    now: Instant,
+) -> bool {
+    let mut from = heartbeat.from;
+    from.ip = Some(src);
+    let mut changed = false;
+    for entry in std::iter::once(from).chain(heartbeat.peers) {
+        if entry.id == me || !entry_valid(&entry) {
+            continue;
+        }
+        match peers.get_mut(&entry.id) {
+            Some(known) if known.entry.heartbeat >= entry.heartbeat => {}
+            Some(known) => {
+                changed |= known.entry.name != entry.name || known.entry.rooms != entry.rooms;
+                known.entry = entry;
+                known.seen = now;
+            }
+            None => {
+                peers.insert(entry.id.clone(), Known { entry, seen: now });
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// Drop peers that have gone quiet. Returns whether any did.
+fn expire(peers: &mut HashMap<String, Known>, now: Instant) -> bool {
+    let before = peers.len();
+    peers.retain(|_, known| now.duration_since(known.seen) < PEER_TIMEOUT);
+    peers.len() != before
+}
+
+fn check_rooms(rooms: Vec<String>) -> AppResult<Vec<String>> {
+    let mut rooms: Vec<String> = rooms
+        .into_iter()
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .collect();
+    rooms.sort();
+    rooms.dedup();
+    if rooms.len() > MAX_ROOMS {
+        return Err(AppError::Validation(format!(
+            "Join at most {} rooms in serverless mode",
+            MAX_ROOMS
+        )));
+    }
+    Ok(rooms)
+}
+
+/// The live peers, by name.
+fn peer_list(state: &AppState) -> Vec<PeerEntry> {
+    let guard = state.serverless.lock().unwrap_or_else(|e| e.into_inner());
+    let mut peers: Vec<PeerEntry> = guard
+        .as_ref()
+        .map(|s| s.peers.values().map(|k| k.entry.clone()).collect())
+        .unwrap_or_default();
+    peers.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    peers
+}
+
+/// Our next heartbeat: our entry (counted up) and the most recently heard peers.
+fn next_heartbeat(state: &AppState) -> Option<(Heartbeat, [u8; 32], bool)> {
+    let mut guard = state.serverless.lock().unwrap_or_else(|e| e.into_inner());
+    let serverless = guard.as_mut()?;
+    serverless.me.heartbeat += 1;
+    let expired = expire(&mut serverless.peers, Instant::now());
+    let mut known: Vec<&Known> = serverless.peers.values().collect();
+    known.sort_by_key(|k| std::cmp::Reverse(k.seen));
+    let heartbeat = Heartbeat {
+        version: GOSSIP_VERSION,
+        from: serverless.me.clone(),
+        peers: known
+            .into_iter()
+            .take(RELAYED_PEERS)
+            .map(|k| k.entry.clone())
+            .collect(),
+    };
+    Some((heartbeat, serverless.psk, expired))
+}
+
+fn spawn_gossip(
+    app: tauri::AppHandle,
+    state: Arc<AppState>,
+    socket: tokio::net::UdpSocket,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let mut buf = vec![0u8; MAX_PACKET];
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let Some((mut heartbeat, psk, expired)) = next_heartbeat(&state) else {
+                        return;
+                    };
+                    if let Some(packet) = seal(&psk, &mut heartbeat) {
+                        if let Err(e) = socket.send_to(&packet, (GOSSIP_GROUP, GOSSIP_PORT)).await {
+                            tracing::debug!("Gossip heartbeat not sent: {}", e);
+                        }
+                    }
+                    if expired {
+                        let _ = app.emit("serverless_peers", peer_list(&state));
+                    }
+                }
+                received = socket.recv_from(&mut buf) => {
+                    let Ok((len, src)) = received else {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        continue;
+                    };
+                    if !is_lan_source(src.ip()) {
+                        continue;
+                    }
+                    let changed = {
+                        let mut guard = state.serverless.lock().unwrap_or_else(|e| e.into_inner());
+                        let Some(serverless) = guard.as_mut() else {
+                            return;
+                        };
+                        match open(&serverless.psk, &buf[..len]) {
+                            Some(heartbeat) => merge(
+                                &mut serverless.peers,
+                                &serverless.me.id,
+                                src.ip(),
+                                heartbeat,
+                                Instant::now(),
+                            ),
+                            None => false,
+                        }
+                    };
+                    if changed {
+                        let _ = app.emit("serverless_peers", peer_list(&state));
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Take one message from a peer's link and show it, if it's for one of our rooms.
+async fn receive(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    stream: tokio::net::TcpStream,
+) -> Result<(), String> {
+    let psk = {
+        let guard = state.serverless.lock().unwrap_or_else(|e| e.into_inner());
+        guard.as_ref().ok_or("serverless mode is off")?.psk
+    };
+    let (mut reader, mut writer) = stream.into_split();
+    let (mut transport, hello) =
+        secure::responder_handshake(&mut reader, &mut writer, &psk).await?;
+    let mut reader = FramedReader::new(reader);
+    let sender = String::from_utf8(hello).map_err(|_| "bad peer id")?;
+    let mut reassembler = secure::Reassembler::default();
+    let plain = loop {
+        let frame = reader
+            .read_frame()
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("empty frame")?;
+        if let Some(plain) = reassembler.push(secure::decrypt(&mut transport, &frame)?)? {
+            break plain;
+        }
+    };
+    let mut msg: Message = serde_json::from_slice(&plain).map_err(|e| e.to_string())?;
+    if msg.message_type != MessageType::Chat {
+        return Err("not a chat message".to_string());
+    }
+    limits::check_length(
+        &msg.message,
+        state
+            .max_message_chars
+            .load(std::sync::atomic::Ordering::Relaxed),
+    )
+    .map_err(|e| e.to_string())?;
+    {
+        let guard = state.serverless.lock().unwrap_or_else(|e| e.into_inner());
+        let serverless = guard.as_ref().ok_or("serverless mode is off")?;
+        if !serverless.me.rooms.contains(&msg.room) {
+            return Ok(());
+        }
+        // The name comes from the link's gossip entry, not the frame.
+        let peer = serverless.peers.get(&sender).ok_or("unknown peer")?;
+        msg.username = peer.entry.name.clone();
+        msg.user_id = peer_user_id(&sender);
+    }
+    msg.room_id = 0;
+    if let Ok(payload) = serde_json::to_string(&msg) {
+        let _ = app.emit("serverless_message", payload);
+    }
+    Ok(())
+}
+
+fn spawn_listener(
+    app: tauri::AppHandle,
+    state: Arc<AppState>,
+    listener: tokio::net::TcpListener,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let (stream, src) = match listener.accept().await {
+                Ok(v) => v,
+                Err(e) => {
+                    tracing::warn!("Serverless listener accept error: {}", e);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    continue;
+                }
+            };
+            if !is_lan_source(src.ip()) {
+                continue;
+            }
+            let app = app.clone();
+            let state = Arc::clone(&state);
+            tauri::async_runtime::spawn(async move {
+                let received =
+                    tokio::time::timeout(CONNECT_TIMEOUT, receive(&app, &state, stream)).await;
+                match received {
+                    Ok(Err(e)) => tracing::debug!("Serverless message from {} dropped: {}", src, e),
+                    Err(_) => tracing::debug!("Serverless link from {} timed out", src),
+                    Ok(Ok(())) => {}
+                }
+            });
+        }
+    })
+}
+
+/// Deliver `msg` to one peer over a fresh link.
+async fn deliver(
+    addr: SocketAddr,
+    psk: [u8; 32],
+    me: String,
+    payload: Vec<u8>,
+) -> Result<(), String> {
+    let stream = tokio::net::TcpStream::connect(addr)
+        .await
+        .map_err(|e| e.to_string())?;
+    let (mut reader, mut writer) = stream.into_split();
+    let mut transport =
+        secure::initiator_handshake(&mut reader, &mut writer, &psk, me.as_bytes()).await?;
+    let mut writer = FramedWriter::new(writer);
+    for piece in secure::fragment(transport.codec, payload) {
+        let ciphertext = secure::encrypt(&mut transport, &piece)?;
+        writer
+            .write_frame(&ciphertext)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    writer.shutdown().await.map_err(|e| e.to_string())
+}
+
+/// Stop serverless mode, if it's on.
+pub(crate) fn stop(state: &AppState) {
+    let previous = state
+        .serverless
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    for task in previous.into_iter().flat_map(|s| s.tasks) {
+        task.abort();
+    }
+}
+
+/// Join the serverless group sharing `passphrase` as `username`, in `rooms`. Messages are taken
+/// on `port` (3628 by default).
+#[tauri::command]
+pub async fn serverless_start(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    username: String,
+    passphrase: String,
+    rooms: Vec<String>,
+    port: Option<u16>,
+) -> AppResult<()> {
+    if *state.is_server.read().await {
+        return Err(AppError::Validation(
+            "Stop hosting before going serverless".to_string(),
+        ));
+    }
+    let username = username.trim().to_string();
+    if username.is_empty() || username.chars().count() > MAX_NAME_CHARS {
+        return Err(AppError::Validation(format!(
+            "A name of 1 to {} characters is required",
+            MAX_NAME_CHARS
+        )));
+    }
+    if passphrase.is_empty() {
+        return Err(AppError::Validation(
+            "A passphrase is required; everyone in the group uses the same one".to_string(),
+        ));
+    }
+    let rooms = check_rooms(rooms)?;
+    stop(&state);
+
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port.unwrap_or(DEFAULT_TCP_PORT)))
+        .await
+        .map_err(|e| AppError::Network(format!("Couldn't listen for messages: {}", e)))?;
+    let tcp_port = listener
+        .local_addr()
+        .map_err(|e| AppError::Network(e.to_string()))?
+        .port();
+    let socket = tokio::net::UdpSocket::bind(("0.0.0.0", GOSSIP_PORT))
+        .await
+        .map_err(|e| AppError::Network(format!("Couldn't open udp/{}: {}", GOSSIP_PORT, e)))?;
+    socket
+        .join_multicast_v4(GOSSIP_GROUP, Ipv4Addr::UNSPECIFIED)
+        .map_err(|e| AppError::Network(format!("Couldn't join the gossip group: {}", e)))?;
+
+    let me = PeerEntry {
+        id: Uuid::new_v4().to_string(),
+        name: username,
+        ip: None,
+        port: tcp_port,
+        rooms,
+        heartbeat: 0,
+    };
+    tracing::info!(
+        "🕸️ Serverless mode on as {} (messages on tcp/{})",
+        me.id,
+        tcp_port
+    );
+    *state.serverless.lock().unwrap_or_else(|e| e.into_inner()) = Some(Serverless {
+        me,
+        psk: secure::derive_psk(&passphrase),
+        peers: HashMap::new(),
+        tasks: Vec::new(),
+    });
+    let tasks = vec![
+        spawn_gossip(app.clone(), Arc::clone(state.inner()), socket),
+        spawn_listener(app, Arc::clone(state.inner()), listener),
+    ];
+    if let Some(serverless) = state
+        .serverless
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+    {
+        serverless.tasks = tasks;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn serverless_stop(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> AppResult<()> {
+    stop(&state);
+    let _ = app.emit("serverless_peers", Vec::<PeerEntry>::new());
+    Ok(())
+}
+
+/// Change which rooms we're in; peers hear it with our next heartbeat.
+#[tauri::command]
+pub async fn serverless_set_rooms(
+    state: State<'_, Arc<AppState>>,
+    rooms: Vec<String>,
+) -> AppResult<()> {
+    let rooms = check_rooms(rooms)?;
+    let mut guard = state.serverless.lock().unwrap_or_else(|e| e.into_inner());
+    let serverless = guard
+        .as_mut()
+        .ok_or_else(|| AppError::Validation("Serverless mode is off".to_string()))?;
+    serverless.me.rooms = rooms;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn serverless_peers(state: State<'_, Arc<AppState>>) -> AppResult<Vec<PeerEntry>> {
+    Ok(peer_list(&state))
+}
+
+/// Send `message` to everyone live in `room`.
+#[tauri::command]
+pub async fn serverless_send(
+    state: State<'_, Arc<AppState>>,
+    room: String,
+    message: String,
+) -> AppResult<ServerlessDelivery> {
+    if message.trim().is_empty() {
+        return Err(AppError::Validation("Message cannot be empty".to_string()));
+    }
+    let (message, is_emoji) = crate::emoji::normalize(&message);
+    limits::check_length(
+        &message,
+        state
+            .max_message_chars
+            .load(std::sync::atomic::Ordering::Relaxed),
+    )?;
+    let (me, psk, targets) = {
+        let guard = state.serverless.lock().unwrap_or_else(|e| e.into_inner());
+        let serverless = guard
+            .as_ref()
+            .ok_or_else(|| AppError::Validation("Serverless mode is off".to_string()))?;
+        if !serverless.me.rooms.contains(&room) {
+            return Err(AppError::Validation(format!("You're not in {}", room)));
+        }
+        let targets: Vec<(String, SocketAddr)> = serverless
+            .peers
+            .values()
+            .filter(|k| k.entry.rooms.contains(&room))
+            .filter_map(|k| {
+                Some((
+                    k.entry.name.clone(),
+                    SocketAddr::new(k.entry.ip?, k.entry.port),
+                ))
+            })
+            .collect();
+        (serverless.me.clone(), serverless.psk, targets)
+    };
+    let msg = Message {
+        username: me.name.clone(),
+        room,
+        is_emoji,
+        ..frame(MessageType::Chat, peer_user_id(&me.id), message)
+    };
+    let payload = serde_json::to_vec(&msg).map_err(|e| AppError::Internal(e.to_string()))?;
+    let sends: Vec<_> = targets
+        .into_iter()
+        .map(|(name, addr)| {
+            let send = deliver(addr, psk, me.id.clone(), payload.clone());
+            let handle = tauri::async_runtime::spawn(tokio::time::timeout(CONNECT_TIMEOUT, send));
+            (name, handle)
+        })
+        .collect();
+    let mut delivery = ServerlessDelivery {
+        message: msg,
+        delivered: Vec::new(),
+        unreachable: Vec::new(),
+    };
+    for (name, handle) in sends {
+        match handle.await {
+            Ok(Ok(Ok(()))) => delivery.delivered.push(name),
+            result => {
+                tracing::debug!("Serverless message not delivered to {}: {:?}", name, result);
+                delivery.unreachable.push(name);
+            }
+        }
+    }
+    Ok(delivery)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, name: &str, heartbeat: u64) -> PeerEntry {
+        PeerEntry {
+            id: id.to_string(),
+            name: name.to_string(),
+            ip: Some(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20))),
+            port: DEFAULT_TCP_PORT,
+            rooms: vec!["general".to_string()],
+            heartbeat,
+        }
+    }
+
+    #[test]
+    fn heartbeats_are_sealed_and_merged() {
+        let psk = secure::derive_psk("shared");
+        let mut heartbeat = Heartbeat {
+            version: GOSSIP_VERSION,
+            from: PeerEntry {
+                ip: None,
+                ..entry("a", "Ann", 5)
+            },
+            peers: vec![entry("b", "Bob", 2), entry("me", "Me", 9)],
+        };
+        let packet = seal(&psk, &mut heartbeat).unwrap();
+        assert!(open(&secure::derive_psk("other"), &packet).is_none());
+        let heard = open(&psk, &packet).unwrap();
+        assert_eq!(heard, heartbeat);
+
+        let src = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+        let now = Instant::now();
+        let mut peers = HashMap::new();
+        assert!(merge(&mut peers, "me", src, heard, now));
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers["a"].entry.ip, Some(src));
+
+        // An older heartbeat changes nothing; a newer one with other rooms does.
+        let stale = Heartbeat {
+            version: GOSSIP_VERSION,
+            from: entry("b", "Bob", 1),
+            peers: Vec::new(),
+        };
+        assert!(!merge(&mut peers, "me", src, stale, now));
+        let moved = Heartbeat {
+            version: GOSSIP_VERSION,
+            from: PeerEntry {
+                rooms: vec!["ops".to_string()],
+                ..entry("b", "Bob", 3)
+            },
+            peers: Vec::new(),
+        };
+        assert!(merge(&mut peers, "me", src, moved, now));
+
+        assert!(!expire(&mut peers, now + PEER_TIMEOUT / 2));
+        assert!(expire(&mut peers, now + PEER_TIMEOUT));
+        assert!(peers.is_empty());
+    }
+}
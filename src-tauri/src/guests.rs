@@ -0,0 +1,257 @@
+// Guest users (host-side).
+//
+// A guest is a temporary user the host hands out — a contractor or visitor who needs a room for
+// an afternoon. `create_guest` returns a name + generated address the guest connects with like
+// any other client. Guests may only enter public rooms: they can't be invited into private
+// channels or DMs, or create private channels (enforced in db_queries). Each guest expires after
+// the configured period; the cleanup task then removes their memberships and the account.
+//
+// Guest addresses use a reserved `.invalid` domain, so they can't collide with a real user, and
+// an address in that domain is only accepted while its guest exists and hasn't expired.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::time::Duration;
+use tauri::State;
+
+const SETTINGS_KEY: &str = "guest_config";
+
+/// Every guest address ends with this.
+pub const GUEST_EMAIL_SUFFIX: &str = "@guests.invalid";
+
+/// How often expired guests are cleaned up.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+fn default_ttl_hours() -> u32 {
+    24
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GuestConfig {
+    // How long a guest account lasts (1 hour – 30 days).
+    #[serde(default = "default_ttl_hours")]
+    pub ttl_hours: u32,
+}
+
+impl Default for GuestConfig {
+    fn default() -> Self {
+        Self {
+            ttl_hours: default_ttl_hours(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct GuestPass {
+    pub user_id: i64,
+    pub name: String,
+    // The address the guest connects with.
+    pub email: String,
+    pub expires_at: String,
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<GuestConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt guest config: {}", e))),
+        None => Ok(GuestConfig::default()),
+    }
+}
+
+pub async fn create_guest_internal(
+    pool: &SqlitePool,
+    name: &str,
+    ttl_hours: u32,
+) -> AppResult<GuestPass> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(AppError::Validation(
+            "Name must be between 1 and 64 characters".to_string(),
+        ));
+    }
+    let email = format!(
+        "guest-{}{}",
+        &crate::db::random_hex_key()[..12],
+        GUEST_EMAIL_SUFFIX
+    );
+    let row = sqlx::query(
+        "INSERT INTO users (name, email, is_guest, guest_expires_at)
+         VALUES ($1, $2, 1, datetime('now', '+' || $3 || ' hours'))
+         RETURNING id, guest_expires_at",
+    )
+    .bind(name)
+    .bind(&email)
+    .bind(i64::from(ttl_hours))
+    .fetch_one(pool)
+    .await?;
+    Ok(GuestPass {
+        user_id: row.get("id"),
+        name: name.to_string(),
+        email,
+        expires_at: row.get("guest_expires_at"),
+    })
+}
+
+/// Remove expired guests: memberships always, and the account itself unless they wrote in a
+/// room under legal hold (deleting the user would cascade to those messages). Returns how many
+/// guests were cleaned up.
+pub async fn cleanup_expired_guests_internal(pool: &SqlitePool) -> Result<u64, String> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Guest cleanup failed: {}", e))?;
+    let expired = sqlx::query(
+        "DELETE FROM user_rooms WHERE user_id IN (
+             SELECT id FROM users WHERE is_guest = 1 AND guest_expires_at <= datetime('now'))",
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Guest cleanup failed: {}", e))?;
+    let removed = sqlx::query(
+        "DELETE FROM users
+         WHERE is_guest = 1 AND guest_expires_at <= datetime('now')
+           AND NOT EXISTS (SELECT 1 FROM messages m JOIN chat_rooms cr ON cr.id = m.room_id
+                           WHERE m.user_id = users.id AND cr.legal_hold = 1)",
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Guest cleanup failed: {}", e))?
+    .rows_affected();
+    tx.commit()
+        .await
+        .map_err(|e| format!("Guest cleanup failed: {}", e))?;
+    if expired.rows_affected() > 0 || removed > 0 {
+        tracing::info!("🧹 Removed {} expired guest account(s)", removed);
+    }
+    Ok(removed)
+}
+
+/// Clean up expired guests on a fixed interval for the life of the app.
+pub fn spawn_cleanup(pool: SqlitePool) {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = cleanup_expired_guests_internal(&pool).await {
+                tracing::warn!("{}", e);
+            }
+        }
+    });
+}
+
+/// Create a temporary guest user. Their connect address is in the returned pass.
+#[tauri::command]
+pub async fn create_guest(db: State<'_, SqlitePool>, name: String) -> AppResult<GuestPass> {
+    let config = load_config(&db).await?;
+    create_guest_internal(&db, &name, config.ttl_hours).await
+}
+
+#[tauri::command]
+pub async fn get_guest_config(db: State<'_, SqlitePool>) -> AppResult<GuestConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_guest_config(db: State<'_, SqlitePool>, config: GuestConfig) -> AppResult<()> {
+    if !(1..=24 * 30).contains(&config.ttl_hours) {
+        return Err(AppError::Validation(
+            "Guest accounts must last between 1 hour and 30 days".to_string(),
+        ));
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_queries::{
+        get_or_create_dm_internal, room_join_allowed_internal, upsert_user_internal,
+    };
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email) VALUES (1, 'Alice', 'a@x');
+             INSERT INTO chat_rooms (id, name, is_private, created_by) VALUES (90, 'hr', 1, 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn guests_are_limited_to_public_rooms() {
+        let pool = setup().await;
+        let guest = create_guest_internal(&pool, "Visitor", 24).await.unwrap();
+        assert!(guest.email.ends_with(GUEST_EMAIL_SUFFIX));
+        // Reconnecting with the guest address works while the pass is valid.
+        upsert_user_internal(&pool, "Visitor".into(), guest.email.clone(), None)
+            .await
+            .unwrap();
+
+        assert!(room_join_allowed_internal(&pool, guest.user_id, 1)
+            .await
+            .unwrap());
+        sqlx::query("INSERT INTO user_rooms (user_id, room_id) VALUES ($1, 90)")
+            .bind(guest.user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(!room_join_allowed_internal(&pool, guest.user_id, 90)
+            .await
+            .unwrap());
+        assert!(get_or_create_dm_internal(&pool, 1, vec![guest.user_id])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn expired_guests_are_removed_and_cannot_return() {
+        let pool = setup().await;
+        let guest = create_guest_internal(&pool, "Visitor", 1).await.unwrap();
+        sqlx::query("INSERT INTO user_rooms (user_id, room_id) VALUES ($1, 1)")
+            .bind(guest.user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(cleanup_expired_guests_internal(&pool).await.unwrap(), 0);
+
+        sqlx::query("UPDATE users SET guest_expires_at = datetime('now', '-1 minute')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(
+            upsert_user_internal(&pool, "Visitor".into(), guest.email.clone(), None)
+                .await
+                .is_err()
+        );
+        assert_eq!(cleanup_expired_guests_internal(&pool).await.unwrap(), 1);
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_rooms WHERE user_id = $1")
+            .bind(guest.user_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, 0);
+        // Once removed, the address can't be used to recreate the account.
+        assert!(
+            upsert_user_internal(&pool, "Visitor".into(), guest.email, None)
+                .await
+                .is_err()
+        );
+    }
+}
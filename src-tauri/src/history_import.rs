@@ -0,0 +1,627 @@
+// History import from Slack / Discord exports (host-side).
+//
+// Accepts a Slack workspace export (the .zip, or its extracted folder) or DiscordChatExporter
+// JSON (one .json file, or a folder of them). The archive is parsed into a neutral
+// `Archive` of users + messages, then written in ONE transaction: missing users and rooms are
+// created, and messages are inserted with their original timestamps. Message ids are derived
+// from the source ids, so re-importing the same archive skips what's already there.
+
+use crate::db::DbLocation;
+use crate::disk_space;
+use crate::error::{AppError, AppResult};
+use crate::sockets::{AppState, MAX_MESSAGE_CHARS};
+use crate::webhooks::unslack;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{Emitter, State};
+
+/// Archives larger than this (uncompressed JSON) are refused rather than read into memory.
+const MAX_ARCHIVE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// A progress event is emitted every this many messages.
+const PROGRESS_EVERY: usize = 500;
+
+/// How to map the export onto this server. Everything is optional.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct ImportMapping {
+    // Source user (Slack / Discord user id, or their name) → email of a local user.
+    pub users: HashMap<String, String>,
+    // Source channel → local room name. Unmapped channels import into a same-named room.
+    pub rooms: HashMap<String, String>,
+    // Only import these source channels; empty = all of them.
+    pub channels: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub format: String,
+    pub users_created: usize,
+    pub rooms_created: usize,
+    pub messages_imported: usize,
+    // Already imported (same source id) — re-runs are idempotent.
+    pub messages_skipped: usize,
+}
+
+#[derive(Serialize, Clone)]
+struct ImportProgress {
+    processed: usize,
+    total: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SourceTime {
+    // Slack `ts`: seconds since the epoch, as a decimal string.
+    Unix(f64),
+    // Discord: RFC 3339 with an offset.
+    Iso(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SourceUser {
+    name: String,
+    email: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SourceMessage {
+    // Globally unique within the source, e.g. "slack:general:1600000000.000100".
+    source_id: String,
+    channel: String,
+    private: bool,
+    author: String,
+    text: String,
+    at: SourceTime,
+}
+
+#[derive(Debug, Default)]
+struct Archive {
+    format: &'static str,
+    users: HashMap<String, SourceUser>,
+    messages: Vec<SourceMessage>,
+}
+
+/// Read every `.json` file under `path` (a .zip, a folder, or a single file), keyed by its path
+/// relative to the archive root with `/` separators.
+fn read_json_files(path: &Path) -> Result<BTreeMap<String, Vec<u8>>, String> {
+    let mut files = BTreeMap::new();
+    let mut total = 0u64;
+    let mut budget = |len: u64| {
+        total += len;
+        if total > MAX_ARCHIVE_BYTES {
+            Err("Archive is too large to import".to_string())
+        } else {
+            Ok(())
+        }
+    };
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    if path.is_dir() {
+        let mut stack = vec![path.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            for entry in std::fs::read_dir(&dir).map_err(|e| e.to_string())? {
+                let p = entry.map_err(|e| e.to_string())?.path();
+                if p.is_dir() {
+                    stack.push(p);
+                } else if p
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+                {
+                    budget(std::fs::metadata(&p).map_err(|e| e.to_string())?.len())?;
+                    let rel = p.strip_prefix(path).unwrap_or(&p);
+                    let key = rel
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    files.insert(key, std::fs::read(&p).map_err(|e| e.to_string())?);
+                }
+            }
+        }
+    } else if ext.as_deref() == Some("zip") {
+        let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("Not a zip: {}", e))?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i).map_err(|e| e.to_string())?;
+            if !entry.is_file() || !entry.name().to_ascii_lowercase().ends_with(".json") {
+                continue;
+            }
+            // Guard on the declared size, then read at most that much.
+            let size = entry.size();
+            budget(size)?;
+            let mut buf = Vec::with_capacity(size as usize);
+            (&mut entry)
+                .take(size)
+                .read_to_end(&mut buf)
+                .map_err(|e| e.to_string())?;
+            files.insert(entry.name().trim_start_matches('/').to_string(), buf);
+        }
+    } else {
+        budget(std::fs::metadata(path).map_err(|e| e.to_string())?.len())?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        files.insert(name, std::fs::read(path).map_err(|e| e.to_string())?);
+    }
+    Ok(files)
+}
+
+/// Slack escapes `&`, `<` and `>` in message text.
+fn slack_unescape(text: &str) -> String {
+    unslack(text)
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn parse_slack(files: &BTreeMap<String, Vec<u8>>) -> Result<Archive, String> {
+    let json = |name: &str| -> Result<Vec<Value>, String> {
+        match files.get(name) {
+            Some(bytes) => serde_json::from_slice(bytes).map_err(|e| format!("{}: {}", name, e)),
+            None => Ok(Vec::new()),
+        }
+    };
+    let mut archive = Archive {
+        format: "slack",
+        ..Default::default()
+    };
+    for u in json("users.json")? {
+        let Some(id) = u.get("id").and_then(Value::as_str) else {
+            continue;
+        };
+        let profile = |k: &str| {
+            u.pointer(&format!("/profile/{}", k))
+                .and_then(Value::as_str)
+                .filter(|s| !s.trim().is_empty())
+        };
+        let name = profile("real_name")
+            .or_else(|| u.get("real_name").and_then(Value::as_str))
+            .or_else(|| u.get("name").and_then(Value::as_str))
+            .unwrap_or(id);
+        archive.users.insert(
+            id.to_string(),
+            SourceUser {
+                name: name.to_string(),
+                email: profile("email").map(str::to_string),
+            },
+        );
+    }
+    // channels.json = public channels, groups.json = private ones. DMs aren't imported.
+    let mut channels = Vec::new();
+    for (list, private) in [("channels.json", false), ("groups.json", true)] {
+        for c in json(list)? {
+            if let Some(name) = c.get("name").and_then(Value::as_str) {
+                channels.push((name.to_string(), private));
+            }
+        }
+    }
+    for (channel, private) in channels {
+        let prefix = format!("{}/", channel);
+        for (path, bytes) in files.range(prefix.clone()..) {
+            if !path.starts_with(&prefix) {
+                break;
+            }
+            let day: Vec<Value> =
+                serde_json::from_slice(bytes).map_err(|e| format!("{}: {}", path, e))?;
+            for m in day {
+                // Joins, topic changes, etc. carry a subtype; bot posts and thread broadcasts
+                // are real content.
+                let subtype = m.get("subtype").and_then(Value::as_str);
+                if !matches!(subtype, None | Some("bot_message" | "thread_broadcast")) {
+                    continue;
+                }
+                let (Some(ts), Some(text)) = (
+                    m.get("ts").and_then(Value::as_str),
+                    m.get("text").and_then(Value::as_str),
+                ) else {
+                    continue;
+                };
+                let author = m
+                    .get("user")
+                    .or_else(|| m.get("bot_id"))
+                    .and_then(Value::as_str)
+                    .unwrap_or("unknown");
+                if !archive.users.contains_key(author) {
+                    let name = m.get("username").and_then(Value::as_str).unwrap_or(author);
+                    archive.users.insert(
+                        author.to_string(),
+                        SourceUser {
+                            name: name.to_string(),
+                            email: None,
+                        },
+                    );
+                }
+                archive.messages.push(SourceMessage {
+                    source_id: format!("slack:{}:{}", channel, ts),
+                    channel: channel.clone(),
+                    private,
+                    author: author.to_string(),
+                    text: slack_unescape(text),
+                    at: SourceTime::Unix(ts.parse().unwrap_or(0.0)),
+                });
+            }
+        }
+    }
+    Ok(archive)
+}
+
+/// DiscordChatExporter JSON: `{ "channel": {..}, "messages": [{ "author": {..}, .. }] }`.
+fn parse_discord(files: &BTreeMap<String, Vec<u8>>) -> Result<Archive, String> {
+    let mut archive = Archive {
+        format: "discord",
+        ..Default::default()
+    };
+    for (path, bytes) in files {
+        let doc: Value = serde_json::from_slice(bytes).map_err(|e| format!("{}: {}", path, e))?;
+        let (Some(channel), Some(messages)) = (
+            doc.pointer("/channel/name").and_then(Value::as_str),
+            doc.get("messages").and_then(Value::as_array),
+        ) else {
+            continue;
+        };
+        for m in messages {
+            let kind = m.get("type").and_then(Value::as_str).unwrap_or("Default");
+            if !matches!(kind, "Default" | "Reply") {
+                continue;
+            }
+            let (Some(id), Some(at), Some(author_id)) = (
+                m.get("id").and_then(Value::as_str),
+                m.get("timestamp").and_then(Value::as_str),
+                m.pointer("/author/id").and_then(Value::as_str),
+            ) else {
+                continue;
+            };
+            let name = ["/author/nickname", "/author/name"]
+                .iter()
+                .find_map(|p| m.pointer(p).and_then(Value::as_str))
+                .unwrap_or(author_id);
+            archive
+                .users
+                .entry(author_id.to_string())
+                .or_insert_with(|| SourceUser {
+                    name: name.to_string(),
+                    email: None,
+                });
+            // Attachments aren't imported; their URLs are kept inline.
+            let mut text = m
+                .get("content")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            for att in m
+                .get("attachments")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                if let Some(url) = att.get("url").and_then(Value::as_str) {
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    text.push_str(url);
+                }
+            }
+            archive.messages.push(SourceMessage {
+                source_id: format!("discord:{}", id),
+                channel: channel.to_string(),
+                private: false,
+                author: author_id.to_string(),
+                text,
+                at: SourceTime::Iso(at.to_string()),
+            });
+        }
+    }
+    Ok(archive)
+}
+
+fn parse_archive(files: &BTreeMap<String, Vec<u8>>) -> Result<Archive, String> {
+    if files.contains_key("channels.json") || files.contains_key("users.json") {
+        return parse_slack(files);
+    }
+    let archive = parse_discord(files)?;
+    if archive.messages.is_empty() {
+        return Err("Not a Slack or Discord export (no messages found)".to_string());
+    }
+    Ok(archive)
+}
+
+fn clamp_text(text: &str) -> String {
+    if text.chars().count() <= MAX_MESSAGE_CHARS {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(MAX_MESSAGE_CHARS - 1).collect();
+        format!("{}…", cut)
+    }
+}
+
+/// Write a parsed archive in one transaction. `progress(processed, total)` is called as messages
+/// are written.
+async fn import_archive_internal(
+    pool: &SqlitePool,
+    archive: Archive,
+    mapping: &ImportMapping,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<ImportReport, String> {
+    let db_err = |e: sqlx::Error| e.to_string();
+    let mut report = ImportReport {
+        format: archive.format.to_string(),
+        ..Default::default()
+    };
+    let messages: Vec<SourceMessage> = archive
+        .messages
+        .into_iter()
+        .filter(|m| mapping.channels.is_empty() || mapping.channels.contains(&m.channel))
+        .filter(|m| !m.text.trim().is_empty())
+        .collect();
+    let total = messages.len();
+    let mut tx = pool.begin().await.map_err(db_err)?;
+
+    let mut user_ids: HashMap<String, i64> = HashMap::new();
+    let mut room_ids: HashMap<String, i64> = HashMap::new();
+    for (i, m) in messages.iter().enumerate() {
+        let user_id = match user_ids.get(&m.author) {
+            Some(&id) => id,
+            None => {
+                let source = archive.users.get(&m.author);
+                let name = source.map(|u| u.name.as_str()).unwrap_or(&m.author);
+                // Mapping (by id, then by name) wins; then the export's own email; otherwise a
+                // reserved-TLD placeholder so the user is unique per source account.
+                let email = mapping
+                    .users
+                    .get(&m.author)
+                    .or_else(|| mapping.users.get(name))
+                    .cloned()
+                    .or_else(|| source.and_then(|u| u.email.clone()))
+                    .unwrap_or_else(|| format!("{}-{}@import.invalid", archive.format, m.author))
+                    .trim()
+                    .to_lowercase();
+                let inserted = sqlx::query(
+                    "INSERT INTO users (name, email) VALUES ($1, $2) ON CONFLICT(email) DO NOTHING",
+                )
+                .bind(name.trim())
+                .bind(&email)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_err)?;
+                report.users_created += inserted.rows_affected() as usize;
+                let id: i64 = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+                    .bind(&email)
+                    .fetch_one(&mut *tx)
+                    .await
+                    .map_err(db_err)?;
+                user_ids.insert(m.author.clone(), id);
+                id
+            }
+        };
+        let room_id = match room_ids.get(&m.channel) {
+            Some(&id) => id,
+            None => {
+                let name = mapping.rooms.get(&m.channel).unwrap_or(&m.channel);
+                let inserted = sqlx::query(
+                    "INSERT INTO chat_rooms (name, description, is_private) VALUES ($1, $2, $3)
+                     ON CONFLICT(name) DO NOTHING",
+                )
+                .bind(name)
+                .bind(format!("Imported from {}", archive.format))
+                .bind(m.private)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_err)?;
+                report.rooms_created += inserted.rows_affected() as usize;
+                let id: i64 = sqlx::query_scalar("SELECT id FROM chat_rooms WHERE name = $1")
+                    .bind(name)
+                    .fetch_one(&mut *tx)
+                    .await
+                    .map_err(db_err)?;
+                room_ids.insert(m.channel.clone(), id);
+                id
+            }
+        };
+        // Authors become members, so imported private rooms stay readable by them.
+        sqlx::query(
+            "INSERT INTO user_rooms (user_id, room_id, is_active) VALUES ($1, $2, 1)
+             ON CONFLICT(user_id, room_id) DO NOTHING",
+        )
+        .bind(user_id)
+        .bind(room_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+
+        // created_at in CURRENT_TIMESTAMP format (UTC), like live messages.
+        let (at, is_unix) = match &m.at {
+            SourceTime::Unix(secs) => (secs.to_string(), true),
+            SourceTime::Iso(s) => (s.clone(), false),
+        };
+        let inserted = sqlx::query(
+            "INSERT INTO messages (room_id, user_id, message, message_type, is_emoji, message_id, created_at)
+             VALUES ($1, $2, $3, 'Chat', 0, $4,
+                     CASE WHEN $6 THEN datetime($5, 'unixepoch') ELSE datetime($5) END)
+             ON CONFLICT(message_id) DO NOTHING",
+        )
+        .bind(room_id)
+        .bind(user_id)
+        .bind(clamp_text(&m.text))
+        .bind(&m.source_id)
+        .bind(at)
+        .bind(is_unix)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_err)?;
+        if inserted.rows_affected() == 1 {
+            report.messages_imported += 1;
+        } else {
+            report.messages_skipped += 1;
+        }
+        if (i + 1) % PROGRESS_EVERY == 0 {
+            progress(i + 1, total);
+        }
+    }
+    tx.commit().await.map_err(db_err)?;
+    progress(total, total);
+    Ok(report)
+}
+
+/// Import a Slack or Discord export at `path`. Emits `import_progress` `{processed, total}` while
+/// writing; nothing is written unless the whole import succeeds.
+#[tauri::command]
+pub async fn import_history(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    location: State<'_, DbLocation>,
+    path: String,
+    mapping: Option<ImportMapping>,
+) -> AppResult<ImportReport> {
+    // The imported messages take about as much room as the archive itself.
+    if let Some(dir) = location.db_path.parent() {
+        let size = tokio::fs::metadata(&path).await.map_or(0, |m| m.len());
+        disk_space::ensure_room(&app, &db, dir, size).await?;
+    }
+    let archive = tauri::async_runtime::spawn_blocking(move || {
+        read_json_files(Path::new(&path)).and_then(|files| parse_archive(&files))
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+    .map_err(AppError::Validation)?;
+
+    let mapping = mapping.unwrap_or_default();
+    let progress_app = app.clone();
+    let report = import_archive_internal(&db, archive, &mapping, |processed, total| {
+        let _ = progress_app.emit("import_progress", ImportProgress { processed, total });
+    })
+    .await
+    .map_err(AppError::Db)?;
+
+    if report.rooms_created > 0 {
+        let _ = app.emit("rooms_changed", ());
+        crate::sockets::broadcast_room_list(&app, state.inner(), &db).await;
+    }
+    tracing::info!(
+        "📥 Imported {} {} messages ({} skipped)",
+        report.messages_imported,
+        report.format,
+        report.messages_skipped
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn slack_files() -> BTreeMap<String, Vec<u8>> {
+        let mut files = BTreeMap::new();
+        files.insert(
+            "users.json".to_string(),
+            br#"[{"id": "U1", "name": "ada", "profile": {"real_name": "Ada L", "email": "ada@corp.io"}},
+                 {"id": "U2", "name": "bob", "profile": {"real_name": ""}}]"#
+                .to_vec(),
+        );
+        files.insert(
+            "channels.json".to_string(),
+            br#"[{"name": "general"}]"#.to_vec(),
+        );
+        files.insert(
+            "general/2020-09-13.json".to_string(),
+            br#"[{"type": "message", "user": "U1", "text": "hi &amp; <https://x.io|welcome>", "ts": "1600000000.000100"},
+                 {"type": "message", "subtype": "channel_join", "user": "U2", "text": "joined", "ts": "1600000001.000100"},
+                 {"type": "message", "user": "U2", "text": "hey", "ts": "1600000002.000100"}]"#
+                .to_vec(),
+        );
+        files
+    }
+
+    async fn setup() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("open in-memory db");
+        crate::db::run_migrations(&pool)
+            .await
+            .expect("run migrations");
+        pool
+    }
+
+    #[test]
+    fn slack_export_skips_system_messages() {
+        let archive = parse_archive(&slack_files()).unwrap();
+        assert_eq!(archive.format, "slack");
+        assert_eq!(archive.messages.len(), 2);
+        assert_eq!(archive.messages[0].text, "hi & welcome (https://x.io)");
+        assert_eq!(archive.messages[0].at, SourceTime::Unix(1600000000.0001));
+        assert_eq!(archive.users["U1"].email.as_deref(), Some("ada@corp.io"));
+        // Empty real_name falls back to the handle.
+        assert_eq!(archive.users["U2"].name, "bob");
+    }
+
+    #[test]
+    fn discord_export_keeps_attachment_urls() {
+        let mut files = BTreeMap::new();
+        files.insert(
+            "ops.json".to_string(),
+            br#"{"channel": {"id": "9", "name": "ops"}, "messages": [
+                {"id": "1", "type": "Default", "timestamp": "2021-01-02T03:04:05.000+00:00",
+                 "content": "graph", "author": {"id": "7", "name": "carol"},
+                 "attachments": [{"url": "https://cdn/x.png"}]},
+                {"id": "2", "type": "ChannelPinnedMessage", "timestamp": "2021-01-02T03:05:00+00:00",
+                 "content": "", "author": {"id": "7", "name": "carol"}}]}"#
+                .to_vec(),
+        );
+        let archive = parse_archive(&files).unwrap();
+        assert_eq!(archive.format, "discord");
+        assert_eq!(archive.messages.len(), 1);
+        assert_eq!(archive.messages[0].text, "graph\nhttps://cdn/x.png");
+        assert_eq!(archive.messages[0].source_id, "discord:1");
+    }
+
+    #[tokio::test]
+    async fn import_is_transactional_and_idempotent() {
+        let pool = setup().await;
+        let mut mapping = ImportMapping::default();
+        mapping.users.insert("bob".into(), "Bob@Corp.io".into());
+        mapping
+            .rooms
+            .insert("general".into(), "slack-general".into());
+
+        let archive = parse_archive(&slack_files()).unwrap();
+        let report = import_archive_internal(&pool, archive, &mapping, |_, _| {})
+            .await
+            .unwrap();
+        assert_eq!(report.users_created, 2);
+        assert_eq!(report.rooms_created, 1);
+        assert_eq!(report.messages_imported, 2);
+
+        let created: String = sqlx::query_scalar(
+            "SELECT m.created_at FROM messages m JOIN users u ON u.id = m.user_id
+             WHERE u.email = 'ada@corp.io'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(created, "2020-09-13 12:26:40");
+        let bob: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE email = 'bob@corp.io'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(bob, 1);
+
+        let again = parse_archive(&slack_files()).unwrap();
+        let report = import_archive_internal(&pool, again, &mapping, |_, _| {})
+            .await
+            .unwrap();
+        assert_eq!(report.messages_imported, 0);
+        assert_eq!(report.messages_skipped, 2);
+        assert_eq!(report.users_created, 0);
+    }
+}
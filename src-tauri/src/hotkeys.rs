@@ -0,0 +1,231 @@
+// Global shortcuts (desktop only).
+//
+// Two system-wide key combinations, kept in `app_settings`: one shows or hides the main window,
+// the other brings it up and asks the UI for a quick reply to the most recent direct message
+// (the `quick_reply` event; the UI knows which DM that is). Bindings use the accelerator syntax,
+// e.g. "CommandOrControl+Shift+Space"; an empty one is turned off.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tauri::State;
+#[cfg(desktop)]
+use tauri::{Emitter, Manager};
+#[cfg(desktop)]
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+const SETTINGS_KEY: &str = "hotkeys";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HotkeyConfig {
+    #[serde(default = "default_toggle_window")]
+    pub toggle_window: String,
+    #[serde(default = "default_quick_reply")]
+    pub quick_reply: String,
+}
+
+fn default_toggle_window() -> String {
+    "CommandOrControl+Shift+Space".to_string()
+}
+
+fn default_quick_reply() -> String {
+    "CommandOrControl+Shift+R".to_string()
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        Self {
+            toggle_window: default_toggle_window(),
+            quick_reply: default_quick_reply(),
+        }
+    }
+}
+
+#[cfg(desktop)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    ToggleWindow,
+    QuickReply,
+}
+
+/// The shortcuts registered now, and what each does.
+#[cfg(desktop)]
+static ACTIVE: std::sync::Mutex<Vec<(Shortcut, Action)>> = std::sync::Mutex::new(Vec::new());
+
+async fn load_config(pool: &SqlitePool) -> AppResult<HotkeyConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt hotkey settings: {}", e))),
+        None => Ok(HotkeyConfig::default()),
+    }
+}
+
+/// A binding as a shortcut; None if it's turned off.
+#[cfg(desktop)]
+fn parse(binding: &str) -> AppResult<Option<Shortcut>> {
+    let binding = binding.trim();
+    if binding.is_empty() {
+        return Ok(None);
+    }
+    binding
+        .parse::<Shortcut>()
+        .map(Some)
+        .map_err(|_| AppError::Validation(format!("\"{}\" isn't a key combination", binding)))
+}
+
+/// Both bindings valid, and not the same one.
+fn check(config: &HotkeyConfig) -> AppResult<()> {
+    let toggle = config.toggle_window.trim();
+    if !toggle.is_empty() && toggle.eq_ignore_ascii_case(config.quick_reply.trim()) {
+        return Err(AppError::Validation(
+            "The two shortcuts need different keys".to_string(),
+        ));
+    }
+    #[cfg(desktop)]
+    {
+        parse(&config.toggle_window)?;
+        parse(&config.quick_reply)?;
+    }
+    Ok(())
+}
+
+/// Swap the registered shortcuts for `config`'s. One another app already holds is skipped
+/// (the other still registers) and reported.
+#[cfg(desktop)]
+fn apply(app: &tauri::AppHandle, config: &HotkeyConfig) -> AppResult<()> {
+    let shortcuts = app.global_shortcut();
+    let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+    for (shortcut, _) in active.drain(..) {
+        let _ = shortcuts.unregister(shortcut);
+    }
+    let mut taken = Vec::new();
+    for (binding, action) in [
+        (&config.toggle_window, Action::ToggleWindow),
+        (&config.quick_reply, Action::QuickReply),
+    ] {
+        let Some(shortcut) = parse(binding)? else {
+            continue;
+        };
+        match shortcuts.register(shortcut) {
+            Ok(()) => active.push((shortcut, action)),
+            Err(e) => {
+                tracing::warn!("Couldn't register the shortcut {}: {}", binding, e);
+                taken.push(binding.trim().to_string());
+            }
+        }
+    }
+    if taken.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!(
+            "{} is already used by another app",
+            taken.join(" and ")
+        )))
+    }
+}
+
+#[cfg(desktop)]
+fn on_shortcut(app: &tauri::AppHandle, shortcut: &Shortcut) {
+    let action = ACTIVE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|(s, _)| s == shortcut)
+        .map(|(_, action)| *action);
+    let Some(action) = action else {
+        return;
+    };
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let shown = window.is_visible().unwrap_or(false) && window.is_focused().unwrap_or(false);
+    if action == Action::ToggleWindow && shown {
+        let _ = window.hide();
+        return;
+    }
+    let _ = window.show();
+    let _ = window.unminimize();
+    let _ = window.set_focus();
+    if action == Action::QuickReply {
+        let _ = app.emit("quick_reply", ());
+    }
+}
+
+/// At startup: install the shortcut plugin and register the saved bindings.
+#[cfg(desktop)]
+pub fn init(app: &tauri::AppHandle, pool: &SqlitePool) {
+    let plugin = tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, shortcut, event| {
+            if event.state() == ShortcutState::Pressed {
+                on_shortcut(app, shortcut);
+            }
+        })
+        .build();
+    if let Err(e) = app.plugin(plugin) {
+        tracing::warn!("Global shortcuts unavailable: {}", e);
+        return;
+    }
+    let config = match tauri::async_runtime::block_on(load_config(pool)) {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::warn!("{}", e);
+            HotkeyConfig::default()
+        }
+    };
+    if let Err(e) = apply(app, &config) {
+        tracing::warn!("{}", e);
+    }
+}
+
+#[tauri::command]
+pub async fn get_hotkeys(db: State<'_, SqlitePool>) -> AppResult<HotkeyConfig> {
+    load_config(&db).await
+}
+
+/// Saved even if a shortcut couldn't be registered (another app holds it); the error says so.
+#[tauri::command]
+pub async fn set_hotkeys(
+    app: tauri::AppHandle,
+    db: State<'_, SqlitePool>,
+    config: HotkeyConfig,
+) -> AppResult<()> {
+    check(&config)?;
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    #[cfg(desktop)]
+    apply(&app, &config)?;
+    #[cfg(mobile)]
+    let _ = app;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_are_checked() {
+        assert!(check(&HotkeyConfig::default()).is_ok());
+        let off = HotkeyConfig {
+            toggle_window: String::new(),
+            quick_reply: " ".to_string(),
+        };
+        assert!(check(&off).is_ok());
+        let same = HotkeyConfig {
+            toggle_window: "Alt+Space".to_string(),
+            quick_reply: "alt+space".to_string(),
+        };
+        assert!(check(&same).is_err());
+        let nonsense = HotkeyConfig {
+            toggle_window: "Ctrl+Nope".to_string(),
+            ..HotkeyConfig::default()
+        };
+        assert!(check(&nonsense).is_err());
+    }
+}
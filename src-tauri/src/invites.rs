@@ -0,0 +1,347 @@
+// Expiring invite links (host-side).
+//
+// `create_invite_link` issues a random token for a room, optionally expiring and/or limited to a
+// number of uses; only its SHA-256 is stored. The link is `nutler://invite/<token>`, or
+// `nutler://invite/<host:port>/<token>` when the host knows where a newcomer should connect: its
+// router's external address while remote access has the port mapped (see port_mapping.rs), else
+// the one address it listens on. The link or the bare token is redeemed with `redeem_invite`: a
+// client sends it to the host as an InviteRedeem frame, and the host — if the invite is still
+// good — makes the connection's user a member, counts the use, and switches them to the room
+// (DmReady). Redeeming for a room you're already in doesn't use the invite up. Guests can only
+// use invites to public rooms.
+
+use crate::accounts::token_hash;
+use crate::db_queries::{get_chat_rooms_internal, room_join_allowed_internal, ChatRoom};
+use crate::error::{AppError, AppResult};
+use crate::sockets::{
+    frame, push_rooms_update, send_dm_ready, send_error_notice, send_secure_client, AppState,
+    Message, MessageType,
+};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tauri::{Runtime, State};
+
+pub const LINK_PREFIX: &str = "nutler://invite/";
+
+/// Longest an invite can stay valid (30 days).
+const MAX_EXPIRES_IN_SECS: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct InviteLink {
+    pub token: String,
+    pub link: String,
+    pub room_id: i64,
+    pub room: String,
+    // Where to connect: the router's external address, or the one address the host is bound to.
+    pub host: Option<String>,
+    // UTC "YYYY-MM-DD HH:MM:SS"; None never expires.
+    pub expires_at: Option<String>,
+    // None is unlimited.
+    pub max_uses: Option<i64>,
+}
+
+/// The token in a pasted invite link (or a bare token).
+fn parse_token(input: &str) -> &str {
+    let input = input.trim();
+    let path = input.strip_prefix(LINK_PREFIX).unwrap_or(input);
+    path.trim_end_matches('/').rsplit('/').next().unwrap_or("")
+}
+
+/// The link for `token`, naming `host` when there is one.
+fn link_for(token: &str, host: Option<&str>) -> String {
+    match host {
+        Some(host) => format!("{}{}/{}", LINK_PREFIX, host, token),
+        None => format!("{}{}", LINK_PREFIX, token),
+    }
+}
+
+/// Issue an invite to `room_id`, valid for `expires_in` seconds (None: no expiry) and
+/// `max_uses` redemptions (None: unlimited).
+pub async fn create_invite_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    created_by: Option<i64>,
+    host: Option<String>,
+    expires_in: Option<u64>,
+    max_uses: Option<u32>,
+) -> AppResult<InviteLink> {
+    if let Some(secs) = expires_in {
+        if !(60..=MAX_EXPIRES_IN_SECS).contains(&secs) {
+            return Err(AppError::Validation(
+                "An invite can last from a minute to 30 days".to_string(),
+            ));
+        }
+    }
+    if max_uses == Some(0) {
+        return Err(AppError::Validation(
+            "An invite needs at least one use".to_string(),
+        ));
+    }
+    let room: Option<(String, bool)> =
+        sqlx::query_as("SELECT name, is_dm FROM chat_rooms WHERE id = $1")
+            .bind(room_id)
+            .fetch_optional(pool)
+            .await?;
+    let name = match room {
+        None => return Err(AppError::Validation("That room doesn't exist".to_string())),
+        Some((_, true)) => {
+            return Err(AppError::Validation(
+                "Direct messages can't have invite links".to_string(),
+            ))
+        }
+        Some((name, false)) => name,
+    };
+    let token = crate::db::random_hex_key();
+    let expires_at: Option<String> = sqlx::query_scalar(
+        "INSERT INTO invites (token_hash, room_id, created_by, expires_at, max_uses)
+         VALUES ($1, $2, $3, CASE WHEN $4 IS NULL THEN NULL
+                                  ELSE datetime('now', '+' || $4 || ' seconds') END, $5)
+         RETURNING expires_at",
+    )
+    .bind(token_hash(&token))
+    .bind(room_id)
+    .bind(created_by)
+    .bind(expires_in.map(|s| s as i64))
+    .bind(max_uses.map(i64::from))
+    .fetch_one(pool)
+    .await?;
+    Ok(InviteLink {
+        link: link_for(&token, host.as_deref()),
+        token,
+        room_id,
+        room: name,
+        host,
+        expires_at,
+        max_uses: max_uses.map(i64::from),
+    })
+}
+
+/// Redeem an invite link or token for `user_id`: add them to its room and count the use.
+/// Returns the room's id.
+pub async fn redeem_invite_internal(
+    pool: &SqlitePool,
+    input: &str,
+    user_id: i64,
+) -> AppResult<i64> {
+    let hash = token_hash(parse_token(input));
+    let invalid = || AppError::Validation("That invite link is invalid or has expired".to_string());
+    let invite: Option<(i64, bool)> = sqlx::query_as(
+        "SELECT room_id,
+                (expires_at IS NULL OR expires_at > datetime('now'))
+                AND (max_uses IS NULL OR uses < max_uses) AS usable
+         FROM invites WHERE token_hash = $1",
+    )
+    .bind(&hash)
+    .fetch_optional(pool)
+    .await?;
+    let Some((room_id, usable)) = invite else {
+        return Err(invalid());
+    };
+    if room_join_allowed_internal(pool, user_id, room_id)
+        .await
+        .map_err(AppError::Db)?
+    {
+        // Already a member: nothing to do, and the invite isn't used up.
+        let member: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM user_rooms
+                            WHERE user_id = $1 AND room_id = $2 AND is_active = 1)",
+        )
+        .bind(user_id)
+        .bind(room_id)
+        .fetch_one(pool)
+        .await?;
+        if member {
+            return Ok(room_id);
+        }
+    }
+    if !usable {
+        return Err(invalid());
+    }
+    let guest_into_private: bool = sqlx::query_scalar(
+        "SELECT cr.is_private AND u.is_guest FROM chat_rooms cr, users u
+         WHERE cr.id = $1 AND u.id = $2",
+    )
+    .bind(room_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    if guest_into_private {
+        return Err(AppError::Auth(
+            "Guests can only join public channels".to_string(),
+        ));
+    }
+    let mut tx = pool.begin().await?;
+    // Re-checked in the UPDATE so two redemptions can't both take the last use.
+    let counted = sqlx::query(
+        "UPDATE invites SET uses = uses + 1
+         WHERE token_hash = $1
+           AND (expires_at IS NULL OR expires_at > datetime('now'))
+           AND (max_uses IS NULL OR uses < max_uses)",
+    )
+    .bind(&hash)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if counted == 0 {
+        return Err(invalid());
+    }
+    sqlx::query(
+        "INSERT INTO user_rooms (user_id, room_id, is_active) VALUES ($1, $2, 1)
+         ON CONFLICT(user_id, room_id) DO UPDATE SET is_active = 1",
+    )
+    .bind(user_id)
+    .bind(room_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(room_id)
+}
+
+async fn room_for(pool: &SqlitePool, user_id: i64, room_id: i64) -> Option<ChatRoom> {
+    get_chat_rooms_internal(pool, user_id, &[])
+        .await
+        .ok()?
+        .into_iter()
+        .find(|r| r.id == Some(room_id))
+}
+
+/// Host: a client's InviteRedeem frame (`message` = the link or token), redeemed for the
+/// connection's user.
+pub(crate) async fn handle_client_frame<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    auth_user_id: u64,
+    message: &Message,
+) {
+    match redeem_invite_internal(pool, &message.message, auth_user_id as i64).await {
+        Ok(room_id) => {
+            push_rooms_update(app, state, pool, auth_user_id).await;
+            if let Some(room) = room_for(pool, auth_user_id as i64, room_id).await {
+                send_dm_ready(state, auth_user_id, &room).await;
+            }
+        }
+        Err(e) => send_error_notice(state, auth_user_id, &e.to_string()).await,
+    }
+}
+
+/// Host: create an invite link for a room.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn create_invite_link(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    expires_in: Option<u64>,
+    max_uses: Option<u32>,
+) -> AppResult<InviteLink> {
+    let created_by = state.user_id.read().await.map(|id| id as i64);
+    // Bound to every interface (0.0.0.0) and not mapped on the router, the host can't tell which
+    // address invitees can reach.
+    let bound = state
+        .server_addr
+        .read()
+        .await
+        .filter(|a| !a.ip().is_unspecified());
+    let host = crate::port_mapping::external_addr(&state)
+        .or(bound)
+        .map(|a| a.to_string());
+    create_invite_internal(&db, room_id, created_by, host, expires_in, max_uses).await
+}
+
+/// Use an invite link. On the host it's redeemed directly and the room returned; a client sends
+/// it to the host, whose reply switches the UI to the room (None here).
+#[tauri::command]
+pub async fn redeem_invite(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    token: String,
+) -> AppResult<Option<ChatRoom>> {
+    if parse_token(&token).is_empty() {
+        return Err(AppError::Validation("Paste an invite link".to_string()));
+    }
+    if *state.is_server.read().await {
+        let Some(user_id) = *state.user_id.read().await else {
+            return Err(AppError::Auth("Sign in to use an invite".to_string()));
+        };
+        let room_id = redeem_invite_internal(&db, &token, user_id as i64).await?;
+        return Ok(room_for(&db, user_id as i64, room_id).await);
+    }
+    let msg = Message {
+        username: state.username.read().await.clone(),
+        ..frame(
+            MessageType::InviteRedeem,
+            state.user_id.read().await.unwrap_or(0),
+            token,
+        )
+    };
+    send_secure_client(state.inner(), &msg)
+        .await
+        .map_err(AppError::Network)?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn invites_join_until_used_up_or_expired() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1), (3, 'Carol', 'c@x', 1);
+             INSERT INTO users (id, name, email, is_guest) VALUES (4, 'Gus', 'g@guests.invalid', 1);
+             INSERT INTO chat_rooms (id, name, is_private, created_by) VALUES (100, 'secret', 1, 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let invite = create_invite_internal(&pool, 100, Some(1), None, Some(3600), Some(1))
+            .await
+            .unwrap();
+        assert!(invite.expires_at.is_some());
+        assert!(invite.link.ends_with(&invite.token));
+        assert_eq!(
+            parse_token(&link_for(&invite.token, Some("10.0.0.5:3625"))),
+            invite.token
+        );
+        assert!(matches!(
+            redeem_invite_internal(&pool, &invite.link, 4).await,
+            Err(AppError::Auth(_))
+        ));
+        assert_eq!(
+            redeem_invite_internal(&pool, &invite.link, 2)
+                .await
+                .unwrap(),
+            100
+        );
+        assert!(room_join_allowed_internal(&pool, 2, 100).await.unwrap());
+        // Redeeming again as a member doesn't need a use; the single use is gone for others.
+        assert!(redeem_invite_internal(&pool, &invite.token, 2)
+            .await
+            .is_ok());
+        assert!(redeem_invite_internal(&pool, &invite.token, 3)
+            .await
+            .is_err());
+        assert!(redeem_invite_internal(&pool, "bogus", 3).await.is_err());
+
+        let expired = create_invite_internal(&pool, 100, Some(1), None, None, None)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE invites SET expires_at = datetime('now', '-1 minute')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(redeem_invite_internal(&pool, &expired.token, 3)
+            .await
+            .is_err());
+    }
+}
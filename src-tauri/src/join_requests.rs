@@ -0,0 +1,428 @@
+// Join approval for private channels (host-side).
+//
+// A RoomJoin from someone who isn't a member of a private channel (not a DM) no longer just
+// bounces: it files a pending request, and the requester hears back "pending". The channel's
+// moderators — its creator, and the host — get a JoinRequest frame (the host's own UI gets it as
+// a `message` event, like any other frame). A moderator approves or denies with
+// `approve_join_request` / `deny_join_request`: directly on the host, or as a JoinDecision frame
+// from a client, authorized by the connection's user. The outcome goes back out as a JoinDecision
+// to the requester and the moderators; an approved requester becomes a member and gets their
+// updated room list.
+
+use crate::db_queries::room_join_allowed_internal;
+use crate::error::{AppError, AppResult};
+use crate::message_events;
+use crate::sockets::{
+    frame, push_rooms_update, send_error_notice, send_secure_client, send_to_client, AppState,
+    Message, MessageType,
+};
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::sync::Arc;
+use tauri::{Runtime, State};
+
+pub const PENDING: &str = "pending";
+pub const APPROVED: &str = "approved";
+pub const DENIED: &str = "denied";
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct JoinRequest {
+    pub id: i64,
+    pub room_id: i64,
+    pub room: String,
+    pub user_id: i64,
+    pub username: String,
+    // PENDING, APPROVED or DENIED.
+    pub status: String,
+    pub created_at: String,
+}
+
+macro_rules! request_query {
+    ($tail:literal) => {
+        concat!(
+            "SELECT jr.id, jr.room_id, cr.name AS room, jr.user_id, u.name AS username,
+                    jr.status, jr.created_at
+             FROM room_join_requests jr
+             JOIN chat_rooms cr ON cr.id = jr.room_id
+             JOIN users u ON u.id = jr.user_id ",
+            $tail
+        )
+    };
+}
+
+fn from_row(row: &sqlx::sqlite::SqliteRow) -> JoinRequest {
+    JoinRequest {
+        id: row.get("id"),
+        room_id: row.get("room_id"),
+        room: row.get("room"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        status: row.get("status"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// File (or re-file, after a denial) `user_id`'s request to join private `room_id`. Errors if
+/// the room doesn't take requests: public rooms, DMs, guests, and existing members.
+pub async fn request_join_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    user_id: i64,
+) -> AppResult<JoinRequest> {
+    let room: Option<(bool, bool)> =
+        sqlx::query_as("SELECT is_private, is_dm FROM chat_rooms WHERE id = $1")
+            .bind(room_id)
+            .fetch_optional(pool)
+            .await?;
+    match room {
+        None => return Err(AppError::Validation("That room doesn't exist".to_string())),
+        Some((false, _)) | Some((_, true)) => {
+            return Err(AppError::Validation(
+                "Only private channels take join requests".to_string(),
+            ))
+        }
+        Some(_) => {}
+    }
+    let is_guest: bool = sqlx::query_scalar("SELECT is_guest FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    if is_guest {
+        return Err(AppError::Auth(
+            "Guests can only join public channels".to_string(),
+        ));
+    }
+    if room_join_allowed_internal(pool, user_id, room_id)
+        .await
+        .map_err(AppError::Db)?
+    {
+        return Err(AppError::Conflict(
+            "You're already a member of that channel".to_string(),
+        ));
+    }
+    sqlx::query(
+        "INSERT INTO room_join_requests (room_id, user_id) VALUES ($1, $2)
+         ON CONFLICT (room_id, user_id) DO UPDATE
+            SET status = 'pending', decided_by = NULL, decided_at = NULL,
+                created_at = CURRENT_TIMESTAMP
+          WHERE status != 'pending'",
+    )
+    .bind(room_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    let row = sqlx::query(request_query!("WHERE jr.room_id = $1 AND jr.user_id = $2"))
+        .bind(room_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(from_row(&row))
+}
+
+/// Pending requests, oldest first: for the rooms `moderator_id` created, or all of them (the
+/// host) with None.
+pub async fn pending_requests_internal(
+    pool: &SqlitePool,
+    moderator_id: Option<i64>,
+) -> AppResult<Vec<JoinRequest>> {
+    let rows = sqlx::query(request_query!(
+        "WHERE jr.status = 'pending' AND ($1 IS NULL OR cr.created_by = $1) ORDER BY jr.id"
+    ))
+    .bind(moderator_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(from_row).collect())
+}
+
+/// Approve or deny a pending request. `moderator_id` is the client deciding (who must have
+/// created the room), or None for the host. Approval makes the requester a member.
+pub async fn decide_internal(
+    pool: &SqlitePool,
+    request_id: i64,
+    moderator_id: Option<i64>,
+    approve: bool,
+) -> AppResult<JoinRequest> {
+    let row = sqlx::query(request_query!("WHERE jr.id = $1"))
+        .bind(request_id)
+        .fetch_optional(pool)
+        .await?;
+    let Some(mut request) = row.as_ref().map(from_row) else {
+        return Err(AppError::Validation(
+            "That join request doesn't exist".to_string(),
+        ));
+    };
+    if let Some(moderator) = moderator_id {
+        let created_by: Option<i64> =
+            sqlx::query_scalar("SELECT created_by FROM chat_rooms WHERE id = $1")
+                .bind(request.room_id)
+                .fetch_one(pool)
+                .await?;
+        if created_by != Some(moderator) {
+            return Err(AppError::Auth(
+                "Only the channel's creator can review join requests".to_string(),
+            ));
+        }
+    }
+    if request.status != PENDING {
+        return Err(AppError::Conflict(
+            "That request has already been decided".to_string(),
+        ));
+    }
+    let status = if approve { APPROVED } else { DENIED };
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE room_join_requests
+            SET status = $1, decided_by = $2, decided_at = CURRENT_TIMESTAMP
+          WHERE id = $3",
+    )
+    .bind(status)
+    .bind(moderator_id)
+    .bind(request_id)
+    .execute(&mut *tx)
+    .await?;
+    if approve {
+        sqlx::query(
+            "INSERT INTO user_rooms (user_id, room_id, is_active) VALUES ($1, $2, 1)
+             ON CONFLICT(user_id, room_id) DO UPDATE SET is_active = 1",
+        )
+        .bind(request.user_id)
+        .bind(request.room_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    request.status = status.to_string();
+    Ok(request)
+}
+
+/// A JoinRequest (`message` = "") or JoinDecision (`message` = the status) frame for `request`:
+/// `message_id` carries the request id, `user_id` / `username` the requester.
+fn request_frame(kind: MessageType, request: &JoinRequest, status: &str) -> Message {
+    Message {
+        username: request.username.clone(),
+        message_id: request.id.to_string(),
+        room: request.room.clone(),
+        room_id: request.room_id as u64,
+        ..frame(kind, request.user_id as u64, status.to_string())
+    }
+}
+
+async fn room_creator(pool: &SqlitePool, room_id: i64) -> Option<u64> {
+    sqlx::query_scalar::<_, Option<i64>>("SELECT created_by FROM chat_rooms WHERE id = $1")
+        .bind(room_id)
+        .fetch_optional(pool)
+        .await
+        .ok()
+        .flatten()
+        .flatten()
+        .map(|id| id as u64)
+}
+
+/// Host: send `msg` to the room's moderators — its creator (if connected) and the host's UI.
+async fn tell_moderators<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    room_id: i64,
+    msg: &Message,
+) {
+    if let Some(creator) = room_creator(pool, room_id).await {
+        send_to_client(state, creator, msg).await;
+    }
+    let _ = message_events::emit(app, msg);
+}
+
+/// Host: a non-member's RoomJoin to a private room. Files the request and tells the moderators,
+/// or explains to the requester why it can't be filed.
+pub(crate) async fn handle_denied_join<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    requester: u64,
+    room_id: u64,
+) {
+    match request_join_internal(pool, room_id as i64, requester as i64).await {
+        Ok(request) => {
+            send_to_client(
+                state,
+                requester,
+                &request_frame(MessageType::JoinDecision, &request, PENDING),
+            )
+            .await;
+            let msg = request_frame(MessageType::JoinRequest, &request, "");
+            tell_moderators(app, state, pool, request.room_id, &msg).await;
+        }
+        Err(e) => send_error_notice(state, requester, &e.to_string()).await,
+    }
+}
+
+/// Host: apply a decision (`moderator_id` None for the host itself) and announce it.
+async fn decide_on_host<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    request_id: i64,
+    moderator_id: Option<i64>,
+    approve: bool,
+) -> AppResult<()> {
+    let request = decide_internal(pool, request_id, moderator_id, approve).await?;
+    let requester = request.user_id as u64;
+    let msg = request_frame(MessageType::JoinDecision, &request, &request.status);
+    send_to_client(state, requester, &msg).await;
+    if approve {
+        push_rooms_update(app, state, pool, requester).await;
+    }
+    tell_moderators(app, state, pool, request.room_id, &msg).await;
+    Ok(())
+}
+
+/// Host: a client moderator's JoinDecision (`message_id` = request id, `message` = the status).
+pub(crate) async fn handle_client_decision<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    auth_user_id: u64,
+    message: &Message,
+) {
+    let Ok(request_id) = message.message_id.parse::<i64>() else {
+        return;
+    };
+    let approve = message.message == APPROVED;
+    if let Err(e) = decide_on_host(
+        app,
+        state,
+        pool,
+        request_id,
+        Some(auth_user_id as i64),
+        approve,
+    )
+    .await
+    {
+        send_error_notice(state, auth_user_id, &e.to_string()).await;
+    }
+}
+
+/// Host: send a newly registered client the requests waiting on rooms it created.
+pub(crate) async fn send_pending(state: &Arc<AppState>, pool: &SqlitePool, user_id: u64) {
+    let Ok(pending) = pending_requests_internal(pool, Some(user_id as i64)).await else {
+        return;
+    };
+    for request in pending {
+        send_to_client(
+            state,
+            user_id,
+            &request_frame(MessageType::JoinRequest, &request, ""),
+        )
+        .await;
+    }
+}
+
+async fn decide(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    db: &SqlitePool,
+    request_id: i64,
+    approve: bool,
+) -> AppResult<()> {
+    if *state.is_server.read().await {
+        return decide_on_host(app, state, db, request_id, None, approve).await;
+    }
+    let msg = Message {
+        username: state.username.read().await.clone(),
+        message_id: request_id.to_string(),
+        ..frame(
+            MessageType::JoinDecision,
+            state.user_id.read().await.unwrap_or(0),
+            if approve { APPROVED } else { DENIED }.to_string(),
+        )
+    };
+    send_secure_client(state, &msg)
+        .await
+        .map_err(AppError::Network)
+}
+
+/// Host: every pending join request. (A client moderator is sent its own as frames.)
+#[tauri::command]
+pub async fn list_join_requests(db: State<'_, SqlitePool>) -> AppResult<Vec<JoinRequest>> {
+    pending_requests_internal(&db, None).await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn approve_join_request(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    request_id: i64,
+) -> AppResult<()> {
+    decide(&app, state.inner(), &db, request_id, true).await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn deny_join_request(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    request_id: i64,
+) -> AppResult<()> {
+    decide(&app, state.inner(), &db, request_id, false).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn requests_are_filed_and_decided_by_the_creator() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1), (3, 'Carol', 'c@x', 1);
+             INSERT INTO chat_rooms (id, name, is_private, created_by)
+                 VALUES (100, 'secret', 1, 1), (101, 'open', 0, 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert!(request_join_internal(&pool, 101, 2).await.is_err());
+        assert!(request_join_internal(&pool, 100, 1).await.is_err());
+        let request = request_join_internal(&pool, 100, 2).await.unwrap();
+        assert_eq!(request.status, PENDING);
+        assert_eq!(
+            pending_requests_internal(&pool, Some(1)).await.unwrap(),
+            vec![request.clone()]
+        );
+        assert!(pending_requests_internal(&pool, Some(3))
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Only the creator (or the host) may decide.
+        assert!(matches!(
+            decide_internal(&pool, request.id, Some(3), true).await,
+            Err(AppError::Auth(_))
+        ));
+        let denied = decide_internal(&pool, request.id, Some(1), false)
+            .await
+            .unwrap();
+        assert_eq!(denied.status, DENIED);
+        assert!(!room_join_allowed_internal(&pool, 2, 100).await.unwrap());
+
+        // Asking again reopens it; the host approves and Bob is in.
+        let again = request_join_internal(&pool, 100, 2).await.unwrap();
+        assert_eq!((again.id, again.status.as_str()), (request.id, PENDING));
+        decide_internal(&pool, request.id, None, true)
+            .await
+            .unwrap();
+        assert!(room_join_allowed_internal(&pool, 2, 100).await.unwrap());
+        assert!(decide_internal(&pool, request.id, None, false)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,262 @@
+// Recent and favorite servers, for reconnecting without rediscovering.
+//
+// Every successful client connect records the server in `known_servers` (by the address it was
+// reached at, "host:port"), with its name when the UI knows it, and when we connected. Switching
+// rooms on it records the room too, so a reconnect can rejoin where we left off. Users can
+// favorite a server, known or not; favorites are listed first and kept, while only the newest
+// MAX_RECENT other servers are.
+//
+// At launch the UI asks `check_last_server` whether the server it last connected to is up: it
+// sends that host the discovery probe and waits briefly for its announce. A healthy one is
+// reconnected to (rejoining the last room); otherwise the UI falls back to discovery.
+
+use crate::error::{AppError, AppResult};
+use crate::sockets::{probe_host, ServerInfo};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::time::Duration;
+use tauri::State;
+
+/// Servers kept that aren't favorites (the most recently connected).
+const MAX_RECENT: i64 = 20;
+
+/// How long the last server has to answer the health check.
+const HEALTH_CHECK_WAIT: Duration = Duration::from_millis(1500);
+
+#[derive(Serialize, Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct KnownServer {
+    pub address: String,
+    pub name: Option<String>,
+    pub last_room: Option<String>,
+    pub last_room_id: Option<i64>,
+    pub favorite: bool,
+    // None for a favorite never connected to.
+    pub last_connected_at: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct LastServer {
+    // The server last connected to, if any.
+    pub server: Option<KnownServer>,
+    // Whether it answered the discovery probe, and what it announced.
+    pub healthy: bool,
+    pub info: Option<ServerInfo>,
+}
+
+/// Record a connection to `address` in `room`. A `name` of None keeps the one we had.
+pub(crate) async fn record_connection(
+    pool: &SqlitePool,
+    address: &str,
+    name: Option<&str>,
+    room: &str,
+    room_id: u64,
+) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO known_servers (address, name, last_room, last_room_id, last_connected_at)
+         VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP)
+         ON CONFLICT(address) DO UPDATE SET
+             name = COALESCE(excluded.name, name),
+             last_room = excluded.last_room,
+             last_room_id = excluded.last_room_id,
+             last_connected_at = excluded.last_connected_at",
+    )
+    .bind(address)
+    .bind(name)
+    .bind(room)
+    .bind(room_id as i64)
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "DELETE FROM known_servers WHERE favorite = 0 AND address NOT IN (
+             SELECT address FROM known_servers WHERE favorite = 0
+             ORDER BY last_connected_at DESC LIMIT $1)",
+    )
+    .bind(MAX_RECENT)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Remember the room we're in on `address`.
+pub(crate) async fn record_room(
+    pool: &SqlitePool,
+    address: &str,
+    room: &str,
+    room_id: u64,
+) -> AppResult<()> {
+    sqlx::query("UPDATE known_servers SET last_room = $1, last_room_id = $2 WHERE address = $3")
+        .bind(room)
+        .bind(room_id as i64)
+        .bind(address)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn list_known_servers_internal(pool: &SqlitePool) -> AppResult<Vec<KnownServer>> {
+    Ok(sqlx::query_as(
+        "SELECT address, name, last_room, last_room_id, favorite, last_connected_at
+         FROM known_servers
+         ORDER BY favorite DESC, last_connected_at IS NULL, last_connected_at DESC, address",
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+pub(crate) async fn favorite_server_internal(
+    pool: &SqlitePool,
+    address: &str,
+    name: Option<&str>,
+    favorite: bool,
+) -> AppResult<()> {
+    let address = address.trim();
+    if address.is_empty() {
+        return Err(AppError::Validation(
+            "Enter the server's address".to_string(),
+        ));
+    }
+    sqlx::query(
+        "INSERT INTO known_servers (address, name, favorite) VALUES ($1, $2, $3)
+         ON CONFLICT(address) DO UPDATE SET
+             name = COALESCE(excluded.name, name),
+             favorite = excluded.favorite",
+    )
+    .bind(address)
+    .bind(name)
+    .bind(favorite)
+    .execute(pool)
+    .await?;
+    // An unfavorited server we never connected to has nothing left to remember.
+    sqlx::query(
+        "DELETE FROM known_servers
+         WHERE address = $1 AND favorite = 0 AND last_connected_at IS NULL",
+    )
+    .bind(address)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn last_connected(pool: &SqlitePool) -> AppResult<Option<KnownServer>> {
+    Ok(sqlx::query_as(
+        "SELECT address, name, last_room, last_room_id, favorite, last_connected_at
+         FROM known_servers
+         WHERE last_connected_at IS NOT NULL
+         ORDER BY last_connected_at DESC
+         LIMIT 1",
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Favorites first, then the rest by when we last connected.
+#[tauri::command]
+pub async fn list_known_servers(db: State<'_, SqlitePool>) -> AppResult<Vec<KnownServer>> {
+    list_known_servers_internal(&db).await
+}
+
+/// Favorite (or stop favoriting) the server at `address`, "host:port".
+#[tauri::command]
+pub async fn favorite_server(
+    db: State<'_, SqlitePool>,
+    address: String,
+    name: Option<String>,
+    favorite: bool,
+) -> AppResult<()> {
+    favorite_server_internal(&db, &address, name.as_deref(), favorite).await
+}
+
+/// The server we last connected to, and whether it's up (it answers the discovery probe).
+#[tauri::command]
+pub async fn check_last_server(db: State<'_, SqlitePool>) -> AppResult<LastServer> {
+    let Some(server) = last_connected(&db).await? else {
+        return Ok(LastServer {
+            server: None,
+            healthy: false,
+            info: None,
+        });
+    };
+    let ip = tokio::net::lookup_host(server.address.as_str())
+        .await
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .map(|a| a.ip());
+    let info = match ip {
+        Some(ip) => probe_host(ip, HEALTH_CHECK_WAIT).await,
+        None => None,
+    };
+    Ok(LastServer {
+        server: Some(server),
+        healthy: info.is_some(),
+        info,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn servers_are_remembered_favorites_first() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+
+        record_connection(&pool, "10.0.0.5:3625", Some("Ops"), "Company Wide", 1)
+            .await
+            .unwrap();
+        record_room(&pool, "10.0.0.5:3625", "IT General", 2)
+            .await
+            .unwrap();
+        favorite_server_internal(&pool, "10.0.0.9:3625", Some("Lab"), true)
+            .await
+            .unwrap();
+        // Reconnecting without a name keeps the one we had.
+        record_connection(&pool, "10.0.0.5:3625", None, "IT General", 2)
+            .await
+            .unwrap();
+
+        let servers = list_known_servers_internal(&pool).await.unwrap();
+        let rows: Vec<(&str, Option<&str>, Option<&str>, bool)> = servers
+            .iter()
+            .map(|s| {
+                (
+                    s.address.as_str(),
+                    s.name.as_deref(),
+                    s.last_room.as_deref(),
+                    s.favorite,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("10.0.0.9:3625", Some("Lab"), None, true),
+                ("10.0.0.5:3625", Some("Ops"), Some("IT General"), false),
+            ]
+        );
+
+        // Unfavoriting one we never joined forgets it; a joined one stays as a recent.
+        favorite_server_internal(&pool, "10.0.0.9:3625", None, false)
+            .await
+            .unwrap();
+        favorite_server_internal(&pool, "10.0.0.5:3625", None, true)
+            .await
+            .unwrap();
+        favorite_server_internal(&pool, "10.0.0.5:3625", None, false)
+            .await
+            .unwrap();
+        let servers = list_known_servers_internal(&pool).await.unwrap();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].last_room_id, Some(2));
+        let last = last_connected(&pool).await.unwrap().unwrap();
+        assert_eq!(last.address, "10.0.0.5:3625");
+        assert!(favorite_server_internal(&pool, " ", None, true)
+            .await
+            .is_err());
+    }
+}
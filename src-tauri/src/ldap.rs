@@ -0,0 +1,383 @@
+// LDAP / Active Directory integration (optional, off by default).
+//
+// Two independent features driven by one host-side config:
+//   * Directory sync — on a schedule (and on demand), search the directory for users, upsert them
+//     into the local `users` table by email, and map their group memberships onto departments
+//     (creating departments as needed). Users are never deleted by a sync.
+//   * Connect authentication — when enabled, a client's Connect must carry its directory password
+//     (`Message.credential`); the host verifies it with a bind as that user before registering.
+//
+// The config (URL, DNs, filter, group → department map) is JSON in `app_settings`. The service
+// account's bind password is a secret, so it lives in the OS keychain, never in the DB.
+
+use crate::db_queries::{
+    apply_auto_join_internal, ensure_department_internal, get_setting_internal,
+    set_setting_internal, upsert_user_internal,
+};
+use crate::error::{AppError, AppResult};
+use ldap3::{ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::State;
+
+const SETTINGS_KEY: &str = "ldap_config";
+const KEYRING_BIND_USER: &str = "ldap-bind-v1";
+
+/// LDAP result code for a failed simple bind (wrong DN or password).
+const RC_INVALID_CREDENTIALS: u32 = 49;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Floor for the sync interval, and how often a disabled scheduler re-checks the config.
+const MIN_SYNC_INTERVAL_MINS: u64 = 5;
+
+fn default_user_filter() -> String {
+    "(&(objectClass=user)(mail=*))".to_string()
+}
+
+fn default_sync_interval() -> u64 {
+    60
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LdapConfig {
+    pub enabled: bool,
+    // ldap://dc.corp.local:389 or ldaps://dc.corp.local:636
+    pub url: String,
+    // Service account used for searches, e.g. CN=nutler-sync,OU=Service,DC=corp,DC=local
+    pub bind_dn: String,
+    pub base_dn: String,
+    #[serde(default = "default_user_filter")]
+    pub user_filter: String,
+    // Group (CN or full DN, case-insensitive) → department name. First matching group wins.
+    #[serde(default)]
+    pub group_departments: HashMap<String, String>,
+    #[serde(default = "default_sync_interval")]
+    pub sync_interval_mins: u64,
+    // Require directory credentials on every client Connect.
+    #[serde(default)]
+    pub authenticate_connects: bool,
+}
+
+impl Default for LdapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::new(),
+            bind_dn: String::new(),
+            base_dn: String::new(),
+            user_filter: default_user_filter(),
+            group_departments: HashMap::new(),
+            sync_interval_mins: default_sync_interval(),
+            authenticate_connects: false,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct LdapSyncReport {
+    pub users_synced: usize,
+    pub departments_assigned: usize,
+    // Entries without a usable mail/name, or rejected by the local upsert.
+    pub skipped: usize,
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<LdapConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt LDAP config: {}", e))),
+        None => Ok(LdapConfig::default()),
+    }
+}
+
+fn keyring_entry() -> AppResult<keyring::Entry> {
+    keyring::Entry::new(crate::db::KEYRING_SERVICE, KEYRING_BIND_USER)
+        .map_err(|e| AppError::Internal(format!("Keychain unavailable: {}", e)))
+}
+
+fn bind_password() -> AppResult<String> {
+    keyring_entry()?.get_password().map_err(|e| match e {
+        keyring::Error::NoEntry => {
+            AppError::Validation("No LDAP bind password has been saved".to_string())
+        }
+        e => AppError::Internal(format!("Failed to read LDAP bind password: {}", e)),
+    })
+}
+
+/// Case-insensitive attribute lookup (servers differ on `memberOf` vs `memberof`).
+fn attr<'a>(entry: &'a SearchEntry, name: &str) -> &'a [String] {
+    entry
+        .attrs
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_slice())
+        .unwrap_or(&[])
+}
+
+/// The CN of a DN (`CN=Sales,OU=Groups,DC=corp` → `Sales`), if it starts with one.
+fn common_name(dn: &str) -> Option<&str> {
+    let first = dn.split(',').next()?.trim();
+    let (key, value) = first.split_once('=')?;
+    key.trim()
+        .eq_ignore_ascii_case("cn")
+        .then_some(value.trim())
+}
+
+/// Department for a user's `memberOf` groups: the first group whose CN or full DN is mapped.
+fn department_for<'a>(member_of: &[String], map: &'a HashMap<String, String>) -> Option<&'a str> {
+    member_of.iter().find_map(|group| {
+        map.iter()
+            .find(|(key, _)| {
+                key.eq_ignore_ascii_case(group)
+                    || common_name(group).is_some_and(|cn| key.eq_ignore_ascii_case(cn))
+            })
+            .map(|(_, dept)| dept.as_str())
+    })
+}
+
+/// Open a connection and bind as the service account.
+async fn service_bind(config: &LdapConfig) -> AppResult<Ldap> {
+    let settings = LdapConnSettings::new().set_conn_timeout(CONNECT_TIMEOUT);
+    let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &config.url)
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to reach {}: {}", config.url, e)))?;
+    ldap3::drive!(conn);
+    let password = bind_password()?;
+    ldap.simple_bind(&config.bind_dn, &password)
+        .await
+        .and_then(|r| r.success())
+        .map_err(|e| AppError::Auth(format!("LDAP service bind failed: {}", e)))?;
+    Ok(ldap)
+}
+
+/// Import users from the directory into the local tables, mapping groups to departments.
+pub async fn sync_directory(pool: &SqlitePool, config: &LdapConfig) -> AppResult<LdapSyncReport> {
+    let mut ldap = service_bind(config).await?;
+    let (entries, _) = ldap
+        .search(
+            &config.base_dn,
+            Scope::Subtree,
+            &config.user_filter,
+            vec!["mail", "displayName", "cn", "memberOf"],
+        )
+        .await
+        .and_then(|r| r.success())
+        .map_err(|e| AppError::Network(format!("LDAP search failed: {}", e)))?;
+    let _ = ldap.unbind().await;
+
+    let mut report = LdapSyncReport::default();
+    for entry in entries.into_iter().map(SearchEntry::construct) {
+        let email = attr(&entry, "mail").first().cloned();
+        let name = attr(&entry, "displayName")
+            .first()
+            .or_else(|| attr(&entry, "cn").first())
+            .cloned();
+        let (Some(email), Some(name)) = (email, name) else {
+            report.skipped += 1;
+            continue;
+        };
+        let department_id =
+            match department_for(attr(&entry, "memberOf"), &config.group_departments) {
+                Some(dept) => Some(
+                    ensure_department_internal(pool, dept)
+                        .await
+                        .map_err(AppError::Db)?,
+                ),
+                None => None,
+            };
+        match upsert_user_internal(pool, name, email, department_id).await {
+            Ok(user) => {
+                if let Some(id) = user.id {
+                    if let Err(e) = apply_auto_join_internal(pool, id).await {
+                        tracing::warn!("LDAP sync: {}", e);
+                    }
+                }
+                report.users_synced += 1;
+                if department_id.is_some() {
+                    report.departments_assigned += 1;
+                }
+            }
+            Err(e) => {
+                tracing::warn!("LDAP sync skipped {}: {}", entry.dn, e);
+                report.skipped += 1;
+            }
+        }
+    }
+    tracing::info!("📇 LDAP sync finished: {:?}", report);
+    Ok(report)
+}
+
+/// Verify `password` for the directory user with mail `email`: find their DN with the service
+/// account, then bind as them. Ok(false) = wrong password or unknown user.
+async fn authenticate(config: &LdapConfig, email: &str, password: &str) -> AppResult<bool> {
+    // An empty password makes a simple bind "unauthenticated" and succeed on many servers.
+    if password.is_empty() {
+        return Ok(false);
+    }
+    let mut ldap = service_bind(config).await?;
+    let filter = format!(
+        "(&{}(mail={}))",
+        config.user_filter,
+        ldap_escape(email.trim())
+    );
+    let (entries, _) = ldap
+        .search(&config.base_dn, Scope::Subtree, &filter, vec!["1.1"])
+        .await
+        .and_then(|r| r.success())
+        .map_err(|e| AppError::Network(format!("LDAP search failed: {}", e)))?;
+    let Some(dn) = entries
+        .into_iter()
+        .next()
+        .map(|e| SearchEntry::construct(e).dn)
+    else {
+        let _ = ldap.unbind().await;
+        return Ok(false);
+    };
+    let result = ldap
+        .simple_bind(&dn, password)
+        .await
+        .map_err(|e| AppError::Network(format!("LDAP bind failed: {}", e)))?;
+    let _ = ldap.unbind().await;
+    match result.rc {
+        0 => Ok(true),
+        RC_INVALID_CREDENTIALS => Ok(false),
+        rc => Err(AppError::Auth(format!(
+            "LDAP bind returned {}: {}",
+            rc, result.text
+        ))),
+    }
+}
+
+/// Host-side Connect gate. Ok when directory authentication is off, or the credential checks
+/// out; Err carries the reason to show the rejected client.
+pub async fn check_connect(
+    pool: &SqlitePool,
+    email: Option<&str>,
+    credential: Option<&str>,
+) -> Result<(), String> {
+    let config = load_config(pool).await.map_err(|e| e.to_string())?;
+    if !(config.enabled && config.authenticate_connects) {
+        return Ok(());
+    }
+    let (Some(email), Some(credential)) = (email, credential) else {
+        return Err("This server requires your directory password".to_string());
+    };
+    match authenticate(&config, email, credential).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("Directory sign-in failed: wrong email or password".to_string()),
+        Err(e) => {
+            tracing::error!("LDAP authentication error: {}", e);
+            Err("Directory sign-in is unavailable right now".to_string())
+        }
+    }
+}
+
+/// Background sync loop, started once at startup. The config is re-read every round, so
+/// enabling/disabling or changing the interval takes effect without a restart.
+pub fn spawn_scheduler(pool: SqlitePool) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let config = load_config(&pool).await.unwrap_or_default();
+            if config.enabled {
+                if let Err(e) = sync_directory(&pool, &config).await {
+                    tracing::warn!("Scheduled LDAP sync failed: {}", e);
+                }
+            }
+            let mins = if config.enabled {
+                config.sync_interval_mins.max(MIN_SYNC_INTERVAL_MINS)
+            } else {
+                MIN_SYNC_INTERVAL_MINS
+            };
+            tokio::time::sleep(Duration::from_secs(mins * 60)).await;
+        }
+    })
+}
+
+#[tauri::command]
+pub async fn get_ldap_config(db: State<'_, SqlitePool>) -> AppResult<LdapConfig> {
+    load_config(&db).await
+}
+
+/// Save the LDAP config. `bind_password`, when given, goes to the OS keychain (never the DB);
+/// omit it to keep the stored one.
+#[tauri::command]
+pub async fn set_ldap_config(
+    db: State<'_, SqlitePool>,
+    config: LdapConfig,
+    bind_password: Option<String>,
+) -> AppResult<()> {
+    if config.enabled && (config.url.trim().is_empty() || config.base_dn.trim().is_empty()) {
+        return Err(AppError::Validation(
+            "An LDAP URL and base DN are required".to_string(),
+        ));
+    }
+    if let Some(password) = bind_password {
+        keyring_entry()?
+            .set_password(&password)
+            .map_err(|e| AppError::Internal(format!("Failed to save bind password: {}", e)))?;
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[tauri::command]
+pub async fn ldap_sync_now(db: State<'_, SqlitePool>) -> AppResult<LdapSyncReport> {
+    let config = load_config(&db).await?;
+    if !config.enabled {
+        return Err(AppError::Validation(
+            "LDAP integration is not enabled".to_string(),
+        ));
+    }
+    sync_directory(&db, &config).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_name_reads_the_leading_cn() {
+        assert_eq!(common_name("CN=Sales,OU=Groups,DC=corp"), Some("Sales"));
+        assert_eq!(common_name("cn = IT Ops , dc=corp"), Some("IT Ops"));
+        assert_eq!(common_name("OU=Groups,DC=corp"), None);
+    }
+
+    #[test]
+    fn groups_map_to_departments_by_cn_or_dn() {
+        let map = HashMap::from([
+            ("sales".to_string(), "Sales".to_string()),
+            (
+                "CN=Eng,OU=Groups,DC=corp".to_string(),
+                "Engineering".to_string(),
+            ),
+        ]);
+        let groups = |g: &[&str]| g.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            department_for(&groups(&["CN=Sales,OU=Groups,DC=corp"]), &map),
+            Some("Sales")
+        );
+        assert_eq!(
+            department_for(&groups(&["cn=eng,ou=groups,dc=corp"]), &map),
+            Some("Engineering")
+        );
+        assert_eq!(department_for(&groups(&["CN=Staff,DC=corp"]), &map), None);
+    }
+
+    #[test]
+    fn config_defaults_fill_missing_fields() {
+        let config: LdapConfig = serde_json::from_str(
+            r#"{"enabled":true,"url":"ldap://dc","bind_dn":"cn=svc","base_dn":"dc=corp"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.user_filter, default_user_filter());
+        assert_eq!(config.sync_interval_mins, 60);
+        assert!(!config.authenticate_connects);
+    }
+}
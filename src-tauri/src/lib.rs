@@ -1,61 +1,222 @@
+use crate::accounts::{
+    client_login, client_register, get_accounts_config, login, register, set_accounts_config,
+    set_user_password,
+};
+use crate::analytics::get_activity_report;
+use crate::attachments::{
+    download_attachment, get_storage_limits, get_storage_usage, send_attachment, set_storage_limits,
+};
+use crate::client_version::{get_client_versions, get_version_gate, set_version_gate};
+use crate::clipboard::paste_image_from_clipboard;
+use crate::collections::{
+    add_to_collection, create_collection, delete_collection, get_collection, list_collections,
+    remove_from_collection,
+};
+use crate::compression::{get_compression_config, set_compression_config};
+use crate::connection_log::get_connection_timeline;
+use crate::contacts::{get_user_profile, set_user_profile, share_contact};
+use crate::custom_emoji::{delete_custom_emoji, list_custom_emoji, upload_custom_emoji};
+use crate::db::{get_database_location, set_database_path};
 use crate::db_queries::{
-    add_room_member, create_room, create_user, get_chat_rooms, get_departments, get_room_messages,
-    get_room_reactions, get_rooms_by_department, get_unread_counts, get_user_by_id, get_users,
-    join_room, leave_room, list_users, save_message, search_messages, touch_last_read,
-    update_user_online_status, upsert_user,
+    add_room_member, create_room, create_user, get_audit_log, get_chat_rooms, get_departments,
+    get_message_context, get_room_messages, get_room_reactions, get_rooms_by_department,
+    get_unread_counts, get_user_by_id, get_users, join_room, list_users, save_message,
+    search_directory, search_messages, set_room_tags, touch_last_read, update_user_online_status,
+    upsert_user,
+};
+use crate::department_announcements::send_department_announcement;
+use crate::directory_csv::{export_departments_csv, export_users_csv, import_users_csv};
+use crate::discovery::{get_discovery_config, set_discovery_config};
+use crate::disk_space::{get_disk_guard_config, get_disk_space, set_disk_guard_config};
+use crate::email_digest::{get_smtp_config, send_test_email, set_smtp_config};
+use crate::emoji::list_emoji;
+use crate::file_scan::{get_scan_config, set_scan_config};
+use crate::gallery::get_room_attachments;
+use crate::gossip::{
+    serverless_peers, serverless_send, serverless_set_rooms, serverless_start, serverless_stop,
+};
+use crate::guests::{create_guest, get_guest_config, set_guest_config};
+use crate::history_import::import_history;
+use crate::hotkeys::{get_hotkeys, set_hotkeys};
+use crate::invites::{create_invite_link, redeem_invite};
+use crate::join_requests::{approve_join_request, deny_join_request, list_join_requests};
+use crate::known_servers::{check_last_server, favorite_server, list_known_servers};
+use crate::ldap::{get_ldap_config, ldap_sync_now, set_ldap_config};
+use crate::limits::{get_message_limits, set_message_limits};
+use crate::location::send_location;
+use crate::maintenance::{enter_maintenance_mode, exit_maintenance_mode};
+use crate::media_cache::{
+    clear_media_cache, get_attachment_thumbnail, get_media_cache_config, set_media_cache_config,
+};
+use crate::members::get_room_members;
+use crate::message_events::{get_message_events_config, set_message_events_config};
+use crate::message_log::{export_message_log, merge_message_log};
+use crate::metrics::{get_server_stats, start_metrics_exporter, stop_metrics_exporter};
+use crate::moderation::{
+    flag_message, get_flagged_messages, get_message_edit_history, get_muted_users, mute_user,
+    resolve_flag, set_user_admin, unmute_user,
 };
+use crate::mqtt::{get_mqtt_config, set_mqtt_config};
+use crate::network::list_network_interfaces;
+use crate::ocr::{get_ocr_config, set_ocr_config};
+use crate::oidc::{client_sso_login, get_oidc_config, oidc_login, set_oidc_config};
+use crate::pdf_export::export_room_pdf;
+use crate::port_mapping::{get_remote_access, set_remote_access};
+use crate::presence::{get_presence_config, report_activity, set_presence_config};
+use crate::profiles::{
+    create_profile, delete_profile, get_profile_credential, list_profiles, set_profile_credential,
+    switch_profile,
+};
+use crate::reach::get_announcement_reach;
+use crate::read_report::{export_read_report_csv, get_read_report, set_compliance_room};
+use crate::reminders::{cancel_reminder, list_reminders, remind_me};
+use crate::retention::{
+    get_retention_policy, prune_now, set_legal_hold, set_retention_policy, set_room_retention,
+};
+use crate::room_digest::{generate_digest, get_digest_schedule, set_digest_schedule};
+use crate::room_permissions::{get_room_permissions, set_room_permissions};
+use crate::room_welcome::{get_room_welcome, set_room_welcome};
+use crate::search::search_all;
+use crate::seed::seed_demo_data;
+use crate::server_events::get_server_events;
+use crate::setup::{complete_setup, get_setup_status};
+use crate::simulate::simulate_clients;
+use crate::snippets::send_code_snippet;
 use crate::sockets::{
     client_add_member, client_connect_to_server, client_create_dm, client_create_room,
     client_delete_message, client_disconnect, client_edit_message, client_join_room,
-    client_leave_room, client_toggle_reaction, client_typing, discover_servers, get_server_info,
-    request_history, send_as_client, send_as_server_participant, server_add_member,
-    server_create_dm, server_create_room, server_delete_message, server_edit_message,
-    server_leave_room, server_listen_as_participant, server_participant_disconnect,
-    server_participant_join_room, server_toggle_reaction, server_typing, AppState,
+    client_leave_room, client_toggle_reaction, client_typing, discover_servers, get_clock_offset,
+    get_server_info, leave_room, list_sessions, request_history, send_as_client,
+    send_as_server_participant, server_add_member, server_create_dm, server_create_room,
+    server_delete_message, server_edit_message, server_leave_room, server_listen_as_participant,
+    server_participant_disconnect, server_participant_join_room, server_set_room_auto_join,
+    server_toggle_reaction, server_typing, session_connect, session_disconnect, session_join_room,
+    session_send, AppState,
+};
+use crate::stickers::{
+    add_sticker, create_sticker_pack, delete_sticker, delete_sticker_pack, list_sticker_packs,
+    send_sticker,
+};
+use crate::throttle::{get_throttle_config, set_throttle_config};
+use crate::transcription::{get_transcription_config, set_transcription_config};
+use crate::translation::{get_translation_config, set_translation_config, translate_message};
+use crate::usage_stats::{
+    export_usage_stats, get_usage_stats, get_usage_stats_config, set_usage_stats_config,
+    submit_usage_stats,
+};
+use crate::user_settings::{get_user_settings, set_user_settings};
+use crate::webhooks::{
+    create_incoming_webhook, delete_incoming_webhook, list_incoming_webhooks,
+    start_webhook_listener, stop_webhook_listener,
 };
 use std::sync::Arc;
 use tauri::Manager;
 
+mod accounts;
+mod analytics;
+mod attachments;
+mod client_version;
+mod clipboard;
+mod codec;
+mod collections;
+mod compression;
+mod connection_log;
+mod connectivity;
+mod contacts;
+mod custom_emoji;
 mod db;
 mod db_queries;
+mod department_announcements;
+mod directory_csv;
+mod discovery;
+mod disk_space;
+mod email_digest;
+mod emoji;
 mod error;
+mod file_drop;
+mod file_scan;
+mod gallery;
+mod gossip;
+mod guests;
+mod history_import;
+mod hotkeys;
+mod invites;
+mod join_requests;
+mod known_servers;
+mod ldap;
+mod limits;
+mod location;
+mod maintenance;
 mod mdns;
+mod media_cache;
+mod members;
+mod message_events;
+mod message_log;
+mod metrics;
 mod migration;
+mod moderation;
+mod mqtt;
+mod network;
+mod ocr;
+mod oidc;
+mod pdf_export;
+mod permissions;
+mod port_mapping;
+mod presence;
+mod profiles;
+mod reach;
+mod read_report;
+mod reminders;
+mod retention;
+mod room_digest;
+mod room_permissions;
+mod room_welcome;
+mod search;
 mod secure;
+mod seed;
+mod server_events;
+mod setup;
+mod shutdown;
+mod simulate;
+mod snippets;
 mod sockets;
+mod stickers;
+mod storage;
+mod system_events;
+mod telemetry;
+#[cfg(test)]
+mod testing;
+mod throttle;
+mod transcription;
+mod translation;
+mod usage_stats;
+mod user_settings;
+mod wake;
+mod webhooks;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Structured logging. Default level is INFO (matching the prior println behavior);
     // override with e.g. `RUST_LOG=nutler_lib=debug`. try_init so tests/re-entry don't panic.
-    let _ = tracing_subscriber::fmt()
-        .with_env_filter(
+    // Spans also go to an OTLP collector when OTEL_EXPORTER_OTLP_ENDPOINT is set (telemetry.rs).
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    let _ = tracing_subscriber::registry()
+        .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
         )
+        .with(tracing_subscriber::fmt::layer())
+        .with(telemetry::otlp_layer())
+        .with(usage_stats::ProblemCounter)
         .try_init();
+    telemetry::warn_if_disabled();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_notification::init())
-        .manage(Arc::new(AppState {
-            server_streams: Arc::new(tokio::sync::Mutex::new(Default::default())),
-            client_stream: Arc::new(tokio::sync::Mutex::new(None)),
-            client_transport: Arc::new(tokio::sync::Mutex::new(None)),
-            client_listener: Arc::new(tokio::sync::Mutex::new(None)),
-            client_heartbeat: Arc::new(tokio::sync::Mutex::new(None)),
-            discovery_responder: Arc::new(tokio::sync::Mutex::new(None)),
-            room_clients: Arc::new(tokio::sync::Mutex::new(Default::default())),
-            ip_conn_counts: Arc::new(tokio::sync::Mutex::new(Default::default())),
-            username: tokio::sync::RwLock::new(String::new()),
-            user_id: tokio::sync::RwLock::new(None),
-            is_server: tokio::sync::RwLock::new(false),
-            current_room: tokio::sync::RwLock::new(String::new()),
-            current_room_id: tokio::sync::RwLock::new(None),
-            server_addr: tokio::sync::RwLock::new(None),
-            pool: std::sync::OnceLock::new(),
-            mdns: std::sync::Mutex::new(None),
-        }))
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .manage(Arc::new(AppState::default()))
         .plugin(tauri_plugin_opener::init())
         .setup(|app| {
             let app_config_dir = app
@@ -66,16 +227,21 @@ pub fn run() {
             std::fs::create_dir_all(&app_config_dir)
                 .expect("Failed to create app config directory");
 
-            let db_path = app_config_dir.join("nutler.db");
+            // Where the DB lives: beside the executable with --portable, else a chosen folder,
+            // else the app config dir.
+            let location = db::resolve_location(&app_config_dir, db::portable_dir().as_deref());
 
             // Open the SQLCipher-encrypted DB (key from the OS keychain) and run migrations,
             // synchronously so the pool is managed BEFORE any command can run. A locked/denied
             // keychain aborts here rather than regenerating the key (which would wipe the DB).
-            let pool = match tauri::async_runtime::block_on(db::init_encrypted_db(&app_config_dir))
-            {
-                Ok(pool) => pool,
+            let (pool, location) = match tauri::async_runtime::block_on(db::init_encrypted_db(
+                &app_config_dir,
+                location,
+            )) {
+                Ok(opened) => opened,
                 Err(e) => panic!("Database initialization failed: {e}"),
             };
+            let db_path = location.db_path.clone();
 
             // The DB is encrypted, but lock the files down on Unix anyway (defense in depth):
             // owner-only DB + sidecars + key file, and a 0700 parent dir.
@@ -90,7 +256,7 @@ pub fn run() {
                     db_path.clone(),
                     db_path.with_extension("db-wal"),
                     db_path.with_extension("db-shm"),
-                    app_config_dir.join("nutler.key"),
+                    location.key_dir.join("nutler.key"),
                 ] {
                     if p.exists() {
                         let _ =
@@ -104,65 +270,408 @@ pub fn run() {
                 .state::<std::sync::Arc<sockets::AppState>>()
                 .pool
                 .set(pool.clone());
+            // The saved message length limit, before any connection can be accepted.
+            tauri::async_runtime::block_on(limits::load_into(
+                app.state::<std::sync::Arc<sockets::AppState>>().inner(),
+                &pool,
+            ));
+            // The local profile in use.
+            tauri::async_runtime::block_on(profiles::load_into(
+                app.state::<std::sync::Arc<sockets::AppState>>().inner(),
+                &pool,
+            ));
+            // Saved auto-away period, and the timer that applies it.
+            tauri::async_runtime::block_on(presence::load_into(
+                app.state::<std::sync::Arc<sockets::AppState>>().inner(),
+                &pool,
+            ));
+            presence::spawn_idle_timer(
+                app.handle().clone(),
+                Arc::clone(app.state::<std::sync::Arc<sockets::AppState>>().inner()),
+            );
+            // A client connection is dropped (and redialled) as soon as its network goes away.
+            connectivity::spawn_network_watch(
+                app.handle().clone(),
+                Arc::clone(app.state::<std::sync::Arc<sockets::AppState>>().inner()),
+            );
+            // Optional LDAP directory sync; idles until enabled in the config.
+            ldap::spawn_scheduler(pool.clone());
+            // Optional email digests of missed mentions / DMs; idles until SMTP is enabled.
+            email_digest::spawn_digest_worker(Arc::clone(
+                app.state::<std::sync::Arc<sockets::AppState>>().inner(),
+            ));
+            // Weekly room digests; idle until a schedule is enabled.
+            room_digest::spawn_scheduler(
+                app.handle().clone(),
+                Arc::clone(app.state::<std::sync::Arc<sockets::AppState>>().inner()),
+                pool.clone(),
+            );
+            // Optional MQTT ingest; starts only if enabled in the saved config.
+            {
+                let handle = app.handle().clone();
+                let state = Arc::clone(app.state::<std::sync::Arc<sockets::AppState>>().inner());
+                let pool = pool.clone();
+                tauri::async_runtime::spawn(async move {
+                    mqtt::restart(&handle, &state, &pool).await;
+                });
+            }
+            // Retention pruning; a no-op until a policy or per-room period is set.
+            retention::spawn_pruner(pool.clone());
+            // Expired guest accounts are removed in the background.
+            guests::spawn_cleanup(pool.clone());
+            // Opt-in usage statistics: counted only once enabled, saved every few minutes.
+            tauri::async_runtime::block_on(usage_stats::load(&pool));
+            usage_stats::spawn_flusher(pool.clone());
+            // The file-transfer bandwidth limit, if one is set.
+            tauri::async_runtime::block_on(throttle::load(&pool));
+            // Whether large payloads are compressed, negotiated per connection.
+            tauri::async_runtime::block_on(compression::load(&pool));
+            // Whether frames also go to the UI on the old `message` event.
+            tauri::async_runtime::block_on(message_events::load(&pool));
+            // Message reminders fire from here (including any that came due while closed).
+            reminders::spawn_scheduler(app.handle().clone(), pool.clone());
+            // Shared files live beside the DB; orphans and abandoned uploads are swept hourly.
+            {
+                let state = Arc::clone(app.state::<std::sync::Arc<sockets::AppState>>().inner());
+                let dir = app_config_dir.join("attachments");
+                std::fs::create_dir_all(&dir).expect("Failed to create attachments directory");
+                let _ = state.attachments_dir.set(dir);
+                attachments::spawn_cleanup(Arc::clone(&state), pool.clone());
+                // Low disk space is reported as it happens; uploads are refused below the margin.
+                disk_space::spawn_watcher(app.handle().clone(), state, pool.clone(), &location);
+            }
+            // Thumbnails and other media that can be rebuilt go in the OS cache directory.
+            if let Ok(cache_dir) = app.path().app_cache_dir() {
+                let _ = app
+                    .state::<std::sync::Arc<sockets::AppState>>()
+                    .media_cache_dir
+                    .set(cache_dir.join("media"));
+            }
+            // Global shortcuts to show the window and to quick-reply (desktop).
+            #[cfg(desktop)]
+            hotkeys::init(app.handle(), &pool);
             app.manage(pool); // makes the pool available to commands
+            app.manage(location);
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
-            // User management
-            upsert_user,
-            create_user,
-            get_users,
-            get_user_by_id,
-            update_user_online_status,
-            // Department management
-            get_departments,
-            // Chat room management
-            get_chat_rooms,
-            get_rooms_by_department,
-            create_room,
-            add_room_member,
-            client_add_member,
-            client_create_dm,
-            client_create_room,
-            server_add_member,
-            server_create_dm,
-            server_create_room,
-            list_users,
-            join_room,
-            leave_room,
-            // Message management
-            save_message,
-            get_room_messages,
-            search_messages,
-            get_room_reactions,
-            get_unread_counts,
-            touch_last_read,
-            client_toggle_reaction,
-            server_toggle_reaction,
-            client_typing,
-            server_typing,
-            request_history,
-            // Socket management
-            get_server_info,
-            discover_servers,
-            server_listen_as_participant,
-            send_as_server_participant,
-            client_connect_to_server,
-            send_as_client,
-            server_participant_join_room,
-            client_join_room,
-            client_leave_room,
-            server_leave_room,
-            // Message edit/delete
-            client_edit_message,
-            server_edit_message,
-            client_delete_message,
-            server_delete_message,
-            // Logout/teardown
-            client_disconnect,
-            server_participant_disconnect
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application Jesse => ");
+        // Calls are counted for the opt-in usage statistics (names only), then checked against
+        // the session's role (permissions.rs).
+        .invoke_handler(usage_stats::counted(permissions::guarded(
+            tauri::generate_handler![
+                // User management
+                upsert_user,
+                create_user,
+                get_users,
+                get_user_by_id,
+                update_user_online_status,
+                // Department management
+                get_departments,
+                send_department_announcement,
+                // Chat room management
+                get_chat_rooms,
+                get_rooms_by_department,
+                create_room,
+                set_room_tags,
+                add_room_member,
+                get_room_members,
+                client_add_member,
+                client_create_dm,
+                client_create_room,
+                server_add_member,
+                server_create_dm,
+                server_create_room,
+                server_set_room_auto_join,
+                list_users,
+                join_room,
+                leave_room,
+                // Message management
+                save_message,
+                get_room_messages,
+                get_message_context,
+                search_messages,
+                search_directory,
+                search_all,
+                get_room_reactions,
+                get_unread_counts,
+                touch_last_read,
+                client_toggle_reaction,
+                server_toggle_reaction,
+                client_typing,
+                server_typing,
+                request_history,
+                import_history,
+                // Message log export / merge (host migration)
+                export_message_log,
+                merge_message_log,
+                // Room transcript as a PDF
+                export_room_pdf,
+                // Room digests
+                generate_digest,
+                get_digest_schedule,
+                set_digest_schedule,
+                // Admin analytics
+                get_activity_report,
+                // Compliance read reports
+                set_compliance_room,
+                get_read_report,
+                export_read_report_csv,
+                // Socket management
+                get_server_info,
+                get_clock_offset,
+                discover_servers,
+                // Where discovery probes beyond the local broadcast
+                get_discovery_config,
+                set_discovery_config,
+                // Recent and favorite servers
+                list_known_servers,
+                favorite_server,
+                check_last_server,
+                server_listen_as_participant,
+                send_as_server_participant,
+                client_connect_to_server,
+                send_as_client,
+                server_participant_join_room,
+                client_join_room,
+                client_leave_room,
+                server_leave_room,
+                // Message edit/delete
+                client_edit_message,
+                server_edit_message,
+                client_delete_message,
+                server_delete_message,
+                // Logout/teardown
+                client_disconnect,
+                server_participant_disconnect,
+                enter_maintenance_mode,
+                exit_maintenance_mode,
+                // Serverless mode (no host)
+                serverless_start,
+                serverless_stop,
+                serverless_set_rooms,
+                serverless_peers,
+                serverless_send,
+                // Extra server sessions (multiple workspaces)
+                session_connect,
+                session_send,
+                session_join_room,
+                session_disconnect,
+                list_sessions,
+                // Developer tools
+                simulate_clients,
+                seed_demo_data,
+                // Directory integration (LDAP / Active Directory)
+                get_ldap_config,
+                set_ldap_config,
+                ldap_sync_now,
+                // Local password accounts
+                register,
+                login,
+                set_user_password,
+                client_register,
+                client_login,
+                get_accounts_config,
+                set_accounts_config,
+                // SSO (OIDC) login
+                oidc_login,
+                client_sso_login,
+                get_oidc_config,
+                set_oidc_config,
+                // Email digests (SMTP)
+                get_smtp_config,
+                set_smtp_config,
+                send_test_email,
+                // Incoming webhooks (Slack-compatible)
+                create_incoming_webhook,
+                list_incoming_webhooks,
+                delete_incoming_webhook,
+                start_webhook_listener,
+                stop_webhook_listener,
+                // MQTT ingest
+                get_mqtt_config,
+                set_mqtt_config,
+                // Guest users
+                create_guest,
+                get_guest_config,
+                set_guest_config,
+                // Client version gate
+                get_version_gate,
+                set_version_gate,
+                get_client_versions,
+                // Retention, legal hold and audit log
+                get_retention_policy,
+                set_retention_policy,
+                set_room_retention,
+                set_legal_hold,
+                prune_now,
+                get_audit_log,
+                // Room permissions
+                get_room_permissions,
+                set_room_permissions,
+                // Room welcome messages
+                get_room_welcome,
+                set_room_welcome,
+                // Emoji picker
+                list_emoji,
+                // Custom emoji
+                upload_custom_emoji,
+                delete_custom_emoji,
+                list_custom_emoji,
+                // Stickers
+                create_sticker_pack,
+                add_sticker,
+                delete_sticker,
+                delete_sticker_pack,
+                list_sticker_packs,
+                send_sticker,
+                // Code snippets
+                send_code_snippet,
+                // Presence
+                report_activity,
+                get_presence_config,
+                set_presence_config,
+                // Reminders
+                remind_me,
+                list_reminders,
+                cancel_reminder,
+                // Bookmark collections
+                create_collection,
+                list_collections,
+                delete_collection,
+                add_to_collection,
+                remove_from_collection,
+                get_collection,
+                // Moderation
+                flag_message,
+                get_flagged_messages,
+                get_message_edit_history,
+                resolve_flag,
+                mute_user,
+                unmute_user,
+                get_muted_users,
+                set_user_admin,
+                // Network interfaces (host bind address)
+                list_network_interfaces,
+                // Remote access (router port mapping)
+                get_remote_access,
+                set_remote_access,
+                // Connection diagnostics
+                get_connection_timeline,
+                get_server_events,
+                // Payload compression
+                get_compression_config,
+                set_compression_config,
+                // Typed message events (and the legacy `message` event)
+                get_message_events_config,
+                set_message_events_config,
+                // Attachments
+                send_attachment,
+                paste_image_from_clipboard,
+                download_attachment,
+                get_room_attachments,
+                get_storage_usage,
+                get_storage_limits,
+                set_storage_limits,
+                // File transfer throttling
+                get_throttle_config,
+                set_throttle_config,
+                // Disk-space guard
+                get_disk_space,
+                get_disk_guard_config,
+                set_disk_guard_config,
+                get_scan_config,
+                set_scan_config,
+                get_attachment_thumbnail,
+                get_media_cache_config,
+                set_media_cache_config,
+                clear_media_cache,
+                // Per-user settings sync
+                get_user_settings,
+                set_user_settings,
+                // First-run setup wizard
+                get_setup_status,
+                complete_setup,
+                // Local profiles
+                list_profiles,
+                create_profile,
+                delete_profile,
+                switch_profile,
+                get_profile_credential,
+                set_profile_credential,
+                // Directory CSV import / export
+                import_users_csv,
+                export_users_csv,
+                export_departments_csv,
+                // Database location
+                get_database_location,
+                set_database_path,
+                // Invite links
+                create_invite_link,
+                redeem_invite,
+                // Private channel join requests
+                list_join_requests,
+                approve_join_request,
+                deny_join_request,
+                // Announcement reach
+                get_announcement_reach,
+                // Message size limit
+                get_message_limits,
+                set_message_limits,
+                // Monitoring
+                start_metrics_exporter,
+                stop_metrics_exporter,
+                get_server_stats,
+                // Usage statistics (opt-in)
+                get_usage_stats_config,
+                set_usage_stats_config,
+                get_usage_stats,
+                export_usage_stats,
+                submit_usage_stats,
+                // Message translation
+                get_translation_config,
+                set_translation_config,
+                translate_message,
+                // Voice message transcription
+                get_transcription_config,
+                set_transcription_config,
+                // Text in shared images
+                get_ocr_config,
+                set_ocr_config,
+                // Profiles and contact cards
+                get_user_profile,
+                set_user_profile,
+                share_contact,
+                // Location sharing
+                send_location,
+                // Global shortcuts
+                get_hotkeys,
+                set_hotkeys
+            ],
+        )))
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application Jesse => ")
+        .run(|app, event| {
+            // Mobile: pause the connection's heartbeat in the background, and check on it when
+            // the app comes back.
+            #[cfg(mobile)]
+            if let tauri::RunEvent::WindowEvent { event, .. } = &event {
+                match event {
+                    tauri::WindowEvent::Suspended => connectivity::on_suspend(app),
+                    tauri::WindowEvent::Resumed => connectivity::on_resume(app),
+                    _ => {}
+                }
+            }
+            // Files dropped onto the window are shared in the current room.
+            if let tauri::RunEvent::WindowEvent {
+                event: tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }),
+                ..
+            } = &event
+            {
+                file_drop::handle(app, paths.clone());
+            }
+            if let tauri::RunEvent::Exit = event {
+                shutdown::on_exit(app);
+                telemetry::shutdown();
+            }
+        });
 }
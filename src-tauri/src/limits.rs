@@ -0,0 +1,148 @@
+// Message size limit (host setting).
+//
+// One maximum message length, in characters, enforced everywhere a chat message passes: the
+// send commands (host participant, client, extra sessions), the host's read loop for frames
+// from clients, and each client's read loop for frames from its host. A host advertises its
+// limit to every client in the Identity frame, so a client checks outgoing messages against
+// the host it's talking to rather than its own setting. Oversized sends fail with
+// `AppError::MessageTooLarge`.
+//
+// The unsend window lives here too: for that many seconds after sending, deleting a message
+// removes it for everyone (the host drops the row and broadcasts a retraction); after it, a
+// delete only leaves a "message deleted" tombstone. The host applies it when the delete
+// reaches it, so a client can't stretch it.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::{AppState, MAX_MESSAGE_CHARS};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tauri::State;
+
+const SETTINGS_KEY: &str = "message_limits";
+
+/// The highest limit a host may set. A message this long (4 bytes per char at worst, plus the
+/// envelope) still fits in a single Noise message.
+pub const MAX_CHARS_CEILING: usize = 10_000;
+
+/// The longest unsend window a host may set (a day).
+pub const MAX_UNSEND_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+fn default_max_chars() -> usize {
+    MAX_MESSAGE_CHARS
+}
+
+fn default_unsend_window() -> u64 {
+    5 * 60
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MessageLimits {
+    #[serde(default = "default_max_chars")]
+    pub max_chars: usize,
+    // Seconds after sending during which a delete removes the message outright; 0 = never.
+    #[serde(default = "default_unsend_window")]
+    pub unsend_window_secs: u64,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            max_chars: default_max_chars(),
+            unsend_window_secs: default_unsend_window(),
+        }
+    }
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<MessageLimits> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt message limits: {}", e))),
+        None => Ok(MessageLimits::default()),
+    }
+}
+
+/// Load the saved limit into AppState at startup (the default if none is saved).
+pub async fn load_into(state: &AppState, pool: &SqlitePool) {
+    match load_config(pool).await {
+        Ok(limits) => state
+            .max_message_chars
+            .store(limits.max_chars, Ordering::Relaxed),
+        Err(e) => tracing::warn!("{}", e),
+    }
+}
+
+/// The host's unsend window (the default if the setting can't be read).
+pub async fn unsend_window_secs(pool: &SqlitePool) -> u64 {
+    match load_config(pool).await {
+        Ok(limits) => limits.unsend_window_secs,
+        Err(e) => {
+            tracing::warn!("{}", e);
+            default_unsend_window()
+        }
+    }
+}
+
+/// Reject a message longer than `max_chars` characters.
+pub fn check_length(message: &str, max_chars: usize) -> AppResult<()> {
+    if message.chars().count() > max_chars {
+        return Err(AppError::MessageTooLarge(format!(
+            "Message exceeds {} characters",
+            max_chars
+        )));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_message_limits(db: State<'_, SqlitePool>) -> AppResult<MessageLimits> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_message_limits(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    limits: MessageLimits,
+) -> AppResult<()> {
+    if !(1..=MAX_CHARS_CEILING).contains(&limits.max_chars) {
+        return Err(AppError::Validation(format!(
+            "The limit must be between 1 and {} characters",
+            MAX_CHARS_CEILING
+        )));
+    }
+    if limits.unsend_window_secs > MAX_UNSEND_WINDOW_SECS {
+        return Err(AppError::Validation(
+            "The unsend window can't be longer than a day".to_string(),
+        ));
+    }
+    let json = serde_json::to_string(&limits).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    state
+        .max_message_chars
+        .store(limits.max_chars, Ordering::Relaxed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn length_is_counted_in_characters() {
+        assert!(check_length("héllo", 5).is_ok());
+        let err = check_length("héllo!", 5).unwrap_err();
+        assert!(matches!(err, AppError::MessageTooLarge(_)));
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            r#"{"code":"message_too_large","message":"Message exceeds 5 characters"}"#
+        );
+    }
+}
@@ -0,0 +1,166 @@
+// Location sharing.
+//
+// A Location message's `message` is JSON {label, latitude, longitude}: a named place ("Meeting
+// room 3B"), a GPS fix from a mobile build, or both. Coordinates come as a pair or not at all.
+// The host checks the payload before relaying it, and stores the coordinates in their own
+// `messages` columns beside the JSON.
+
+use crate::error::{AppError, AppResult};
+use crate::sockets::{
+    frame, now_secs, publish_host_message, send_secure_client, server_now_secs, AppState, Message,
+    MessageType,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tauri::State;
+
+const MAX_LABEL_CHARS: usize = 200;
+
+/// A Location message's `message`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Location {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+}
+
+impl Location {
+    /// A trimmed label and in-range coordinates, or why not.
+    fn checked(mut self) -> AppResult<Location> {
+        self.label = self.label.trim().to_string();
+        if self.label.chars().count() > MAX_LABEL_CHARS {
+            return Err(AppError::Validation(format!(
+                "The place name is longer than {} characters",
+                MAX_LABEL_CHARS
+            )));
+        }
+        match (self.latitude, self.longitude) {
+            (Some(lat), Some(lon)) => {
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err(AppError::Validation(
+                        "Those coordinates are out of range".to_string(),
+                    ));
+                }
+            }
+            (None, None) if !self.label.is_empty() => {}
+            (None, None) => {
+                return Err(AppError::Validation(
+                    "A location needs a place name or coordinates".to_string(),
+                ))
+            }
+            _ => {
+                return Err(AppError::Validation(
+                    "Coordinates need both a latitude and a longitude".to_string(),
+                ))
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Validate a Location message in place, rewriting `message` as the checked JSON.
+pub fn check(message: &mut Message) -> AppResult<()> {
+    let location: Location = serde_json::from_str(&message.message)
+        .map_err(|_| AppError::Validation("That isn't a location".to_string()))?;
+    let location = location.checked()?;
+    message.message =
+        serde_json::to_string(&location).map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(())
+}
+
+/// Parse a stored or relayed Location message.
+pub fn parse(message: &str) -> Option<Location> {
+    serde_json::from_str(message).ok()
+}
+
+/// Notification / digest text for a location: its label, else its coordinates.
+pub fn preview(message: &str) -> String {
+    match parse(message) {
+        Some(l) if !l.label.is_empty() => format!("[Location: {}]", l.label),
+        Some(Location {
+            latitude: Some(lat),
+            longitude: Some(lon),
+            ..
+        }) => format!("[Location: {:.5}, {:.5}]", lat, lon),
+        _ => "[Location]".to_string(),
+    }
+}
+
+/// Share a place in the current room, as the host participant or over the client connection.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn send_location(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    label: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    user_id: u64,
+) -> AppResult<()> {
+    let location = Location {
+        label: label.unwrap_or_default(),
+        latitude,
+        longitude,
+    }
+    .checked()?;
+    let is_server = *state.is_server.read().await;
+    let message = Message {
+        username: state.username.read().await.clone(),
+        room: state.current_room.read().await.clone(),
+        room_id: state.current_room_id.read().await.unwrap_or(1),
+        created_at: if is_server {
+            now_secs()
+        } else {
+            server_now_secs(&state.clock_offset_ms)
+        },
+        ..frame(
+            MessageType::Location,
+            user_id,
+            serde_json::to_string(&location).map_err(|e| AppError::Internal(e.to_string()))?,
+        )
+    };
+    if is_server {
+        publish_host_message(&app, state.inner(), db.inner(), message).await;
+        Ok(())
+    } else {
+        send_secure_client(state.inner(), &message)
+            .await
+            .map_err(AppError::Network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(label: &str, latitude: Option<f64>, longitude: Option<f64>) -> Location {
+        Location {
+            label: label.to_string(),
+            latitude,
+            longitude,
+        }
+    }
+
+    #[test]
+    fn locations_need_a_label_or_a_coordinate_pair() {
+        let room = location(" Meeting room 3B ", None, None).checked().unwrap();
+        assert_eq!(room.label, "Meeting room 3B");
+        assert_eq!(
+            preview(&serde_json::to_string(&room).unwrap()),
+            "[Location: Meeting room 3B]"
+        );
+        let fix = location("", Some(52.52), Some(13.405)).checked().unwrap();
+        assert_eq!(
+            preview(&serde_json::to_string(&fix).unwrap()),
+            "[Location: 52.52000, 13.40500]"
+        );
+        assert!(location("", None, None).checked().is_err());
+        assert!(location("Dock", Some(52.0), None).checked().is_err());
+        assert!(location("", Some(91.0), Some(0.0)).checked().is_err());
+        assert!(location("", Some(0.0), Some(-181.0)).checked().is_err());
+    }
+}
@@ -0,0 +1,179 @@
+// Host maintenance mode, for planned reboots of the hosting machine.
+//
+// `enter_maintenance_mode` puts up a banner on every connected client (a Maintenance frame) and
+// turns away new connections: each one gets the same frame right after the handshake and is
+// closed. With a countdown, every client is disconnected once it runs out; their reconnect
+// attempts keep being turned away (and keep showing the banner) until the host is back.
+// `exit_maintenance_mode` takes the banner down and accepts connections again. Nothing is
+// persisted: restarting the app ends maintenance.
+
+use crate::error::{AppError, AppResult};
+use crate::message_events;
+use crate::sockets::{
+    drop_all_clients, frame, now_secs, send_to_client, AppState, Message, MessageType,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+/// The longest banner text.
+const MAX_TEXT_CHARS: usize = 280;
+/// The longest countdown before everyone is disconnected (a day).
+const MAX_COUNTDOWN_SECS: u64 = 24 * 60 * 60;
+
+pub struct Maintenance {
+    text: String,
+    // When clients are (or were) disconnected, Unix seconds; None = they stay connected.
+    disconnect_at: Option<u64>,
+    countdown: Option<tauri::async_runtime::JoinHandle<()>>,
+}
+
+#[derive(Serialize)]
+struct Banner<'a> {
+    active: bool,
+    text: &'a str,
+    disconnect_at: Option<u64>,
+}
+
+fn check_text(text: &str) -> AppResult<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(AppError::Validation(
+            "Say why the server is going down".to_string(),
+        ));
+    }
+    if text.chars().count() > MAX_TEXT_CHARS {
+        return Err(AppError::Validation(format!(
+            "The maintenance message can't be longer than {} characters",
+            MAX_TEXT_CHARS
+        )));
+    }
+    Ok(text.to_string())
+}
+
+fn banner_frame(active: bool, text: &str, disconnect_at: Option<u64>) -> Message {
+    let banner = Banner {
+        active,
+        text,
+        disconnect_at,
+    };
+    frame(
+        MessageType::Maintenance,
+        0,
+        serde_json::to_string(&banner).unwrap_or_default(),
+    )
+}
+
+/// The banner to turn a new connection away with, while maintenance is on.
+pub(crate) fn notice(state: &AppState) -> Option<Message> {
+    let guard = state.maintenance.lock().unwrap_or_else(|e| e.into_inner());
+    guard
+        .as_ref()
+        .map(|m| banner_frame(true, &m.text, m.disconnect_at))
+}
+
+/// Drop maintenance without telling anyone (hosting stopped).
+pub(crate) fn clear(state: &AppState) {
+    let previous = state
+        .maintenance
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    if let Some(handle) = previous.and_then(|m| m.countdown) {
+        handle.abort();
+    }
+}
+
+/// Send `msg` to every connected client and the host's own UI.
+async fn broadcast(app: &tauri::AppHandle, state: &Arc<AppState>, msg: &Message) {
+    let uids: Vec<u64> = state.server_streams.lock().await.keys().copied().collect();
+    for uid in uids {
+        send_to_client(state, uid, msg).await;
+    }
+    let _ = message_events::emit(app, msg);
+}
+
+/// Start (or update) maintenance: show `message` to everyone and stop accepting connections.
+/// With `disconnect_after_secs`, everyone is disconnected when that runs out.
+#[tauri::command]
+pub async fn enter_maintenance_mode(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    message: String,
+    disconnect_after_secs: Option<u64>,
+) -> AppResult<()> {
+    if !*state.is_server.read().await {
+        return Err(AppError::Validation(
+            "Only the host can enter maintenance mode".to_string(),
+        ));
+    }
+    let text = check_text(&message)?;
+    let secs = disconnect_after_secs.map(|s| s.min(MAX_COUNTDOWN_SECS));
+    let disconnect_at = secs.map(|s| now_secs() + s);
+    let banner = banner_frame(true, &text, disconnect_at);
+    let countdown = secs.map(|s| {
+        let app = app.clone();
+        let state = Arc::clone(state.inner());
+        let banner = banner.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(s)).await;
+            drop_all_clients(&app, &state, &banner).await;
+        })
+    });
+    let previous = state
+        .maintenance
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .replace(Maintenance {
+            text,
+            disconnect_at,
+            countdown,
+        });
+    if let Some(handle) = previous.and_then(|m| m.countdown) {
+        handle.abort();
+    }
+    tracing::info!("🚧 Maintenance mode on (disconnect in {:?}s)", secs);
+    broadcast(&app, state.inner(), &banner).await;
+    Ok(())
+}
+
+/// End maintenance: take the banner down, cancel any countdown, accept connections again.
+#[tauri::command]
+pub async fn exit_maintenance_mode(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> AppResult<()> {
+    if notice(&state).is_none() {
+        return Ok(());
+    }
+    clear(&state);
+    tracing::info!("🚧 Maintenance mode off");
+    broadcast(&app, state.inner(), &banner_frame(false, "", None)).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn banner_text_is_checked_and_framed() {
+        assert!(check_text("   ").is_err());
+        assert!(check_text(&"x".repeat(MAX_TEXT_CHARS + 1)).is_err());
+        let text = check_text("  Rebooting for updates  ").unwrap();
+        assert_eq!(text, "Rebooting for updates");
+
+        let msg = banner_frame(true, &text, Some(1_700_000_000));
+        assert_eq!(msg.message_type, MessageType::Maintenance);
+        let banner: serde_json::Value = serde_json::from_str(&msg.message).unwrap();
+        assert_eq!(
+            banner,
+            serde_json::json!({
+                "active": true,
+                "text": "Rebooting for updates",
+                "disconnect_at": 1_700_000_000u64
+            })
+        );
+    }
+}
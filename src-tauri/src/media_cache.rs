@@ -0,0 +1,338 @@
+// Media cache (local).
+//
+// Generated or fetched media that can be rebuilt at will (today, image-attachment thumbnails)
+// is kept under `<app cache>/media/<kind>/<key>`, so scrolling back through history serves it
+// from disk instead of decoding the image again (host) or fetching it again (client). The
+// directory is capped at `max_bytes` (a local setting): after every write the least recently
+// used entries, by modification time, which a cache hit refreshes, are evicted until it fits.
+// `clear_media_cache` empties it.
+//
+// Thumbnails: the host makes each one once from its stored copy (JPEG, at most THUMBNAIL_PX on
+// the long side) and caches it under its attachment id. A client asks for it with an
+// AttachmentRequest for "thumb:<id>", receives it like any other file, caches it under the
+// host's address and the id, and tells the UI with `thumbnail_ready`.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tauri::State;
+
+const SETTINGS_KEY: &str = "media_cache";
+
+pub const THUMBNAILS: &str = "thumbnails";
+
+/// Longest side of a thumbnail, in pixels.
+const THUMBNAIL_PX: u32 = 320;
+
+/// Images larger than this aren't decoded for a thumbnail.
+const MAX_SOURCE_BYTES: u64 = 20 * 1024 * 1024;
+
+fn default_max_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MediaCacheConfig {
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: u64,
+}
+
+impl Default for MediaCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: default_max_bytes(),
+        }
+    }
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<MediaCacheConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt media cache settings: {}", e))),
+        None => Ok(MediaCacheConfig::default()),
+    }
+}
+
+/// A cache key made safe as a file name.
+pub fn key(parts: &[&str]) -> String {
+    parts
+        .join("-")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+pub fn entry_path(dir: &Path, kind: &str, key: &str) -> PathBuf {
+    dir.join(kind).join(key)
+}
+
+/// A cached entry's bytes, marking it as just used.
+pub async fn get(dir: &Path, kind: &str, key: &str) -> Option<Vec<u8>> {
+    let path = entry_path(dir, kind, key);
+    let bytes = tokio::fs::read(&path).await.ok()?;
+    if let Ok(file) = std::fs::File::options().write(true).open(&path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+    Some(bytes)
+}
+
+/// Store an entry, then evict down to `max_bytes`.
+pub async fn put(
+    dir: &Path,
+    kind: &str,
+    key: &str,
+    bytes: &[u8],
+    max_bytes: u64,
+) -> std::io::Result<()> {
+    let path = entry_path(dir, kind, key);
+    tokio::fs::create_dir_all(dir.join(kind)).await?;
+    let part = path.with_extension("part");
+    tokio::fs::write(&part, bytes).await?;
+    tokio::fs::rename(&part, &path).await?;
+    evict(dir, max_bytes).await;
+    Ok(())
+}
+
+/// Every entry as (last used, size, path).
+async fn entries(dir: &Path) -> Vec<(SystemTime, u64, PathBuf)> {
+    let mut found = Vec::new();
+    let Ok(mut kinds) = tokio::fs::read_dir(dir).await else {
+        return found;
+    };
+    while let Ok(Some(kind)) = kinds.next_entry().await {
+        let Ok(mut files) = tokio::fs::read_dir(kind.path()).await else {
+            continue;
+        };
+        while let Ok(Some(file)) = files.next_entry().await {
+            if let Ok(meta) = file.metadata().await {
+                if meta.is_file() {
+                    let used = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    found.push((used, meta.len(), file.path()));
+                }
+            }
+        }
+    }
+    found
+}
+
+/// Remove least recently used entries until the cache is within `max_bytes`. Returns the bytes
+/// freed.
+pub async fn evict(dir: &Path, max_bytes: u64) -> u64 {
+    let mut all = entries(dir).await;
+    let mut total: u64 = all.iter().map(|(_, size, _)| size).sum();
+    all.sort_by_key(|(used, _, _)| *used);
+    let mut freed = 0;
+    for (_, size, path) in all {
+        if total <= max_bytes {
+            break;
+        }
+        if tokio::fs::remove_file(&path).await.is_ok() {
+            total -= size;
+            freed += size;
+        }
+    }
+    freed
+}
+
+/// A JPEG thumbnail of an image, or None if it isn't one we can decode.
+pub fn make_thumbnail(bytes: &[u8]) -> Option<Vec<u8>> {
+    let image = image::load_from_memory(bytes).ok()?;
+    let thumb = image.thumbnail(THUMBNAIL_PX, THUMBNAIL_PX).to_rgb8();
+    let mut out = std::io::Cursor::new(Vec::new());
+    thumb.write_to(&mut out, image::ImageFormat::Jpeg).ok()?;
+    Some(out.into_inner())
+}
+
+fn data_url(jpeg: &[u8]) -> String {
+    format!(
+        "data:image/jpeg;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(jpeg)
+    )
+}
+
+fn cache_dir(state: &AppState) -> AppResult<&PathBuf> {
+    state
+        .media_cache_dir
+        .get()
+        .ok_or_else(|| AppError::Internal("The media cache isn't ready".to_string()))
+}
+
+/// Host: attachment `id`'s thumbnail, from the cache or made (and cached) from the stored file.
+/// None for a file that isn't an image we can decode.
+pub(crate) async fn host_thumbnail(
+    state: &AppState,
+    pool: &SqlitePool,
+    id: i64,
+) -> AppResult<Option<PathBuf>> {
+    let dir = cache_dir(state)?;
+    let key = key(&["local", &id.to_string()]);
+    if get(dir, THUMBNAILS, &key).await.is_some() {
+        return Ok(Some(entry_path(dir, THUMBNAILS, &key)));
+    }
+    let row: Option<(String, i64)> =
+        sqlx::query_as("SELECT mime, size FROM attachments WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+    let Some((mime, size)) = row else {
+        return Ok(None);
+    };
+    if !mime.starts_with("image/") || size as u64 > MAX_SOURCE_BYTES {
+        return Ok(None);
+    }
+    let Some(store) = state.attachments_dir.get() else {
+        return Ok(None);
+    };
+    let Ok(source) = tokio::fs::read(store.join(id.to_string())).await else {
+        return Ok(None);
+    };
+    let thumb = tauri::async_runtime::spawn_blocking(move || make_thumbnail(&source))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let Some(thumb) = thumb else {
+        return Ok(None);
+    };
+    let max_bytes = load_config(pool).await?.max_bytes;
+    put(dir, THUMBNAILS, &key, &thumb, max_bytes)
+        .await
+        .map_err(|e| AppError::Internal(format!("Couldn't cache the thumbnail: {}", e)))?;
+    Ok(Some(entry_path(dir, THUMBNAILS, &key)))
+}
+
+/// Client: the cache key for a thumbnail from the current host.
+pub(crate) async fn client_thumbnail_key(state: &AppState, id: i64) -> String {
+    let host = state
+        .server_addr
+        .read()
+        .await
+        .map(|a| a.to_string())
+        .unwrap_or_default();
+    key(&[&host, &id.to_string()])
+}
+
+/// Client: a fetched thumbnail has arrived at `part`; move it into the cache and evict.
+pub(crate) async fn store_fetched(
+    state: &AppState,
+    part: &Path,
+    dest: &Path,
+) -> std::io::Result<()> {
+    tokio::fs::rename(part, dest).await?;
+    if let (Some(dir), Some(pool)) = (state.media_cache_dir.get(), state.pool.get()) {
+        let max_bytes = load_config(pool)
+            .await
+            .map(|c| c.max_bytes)
+            .unwrap_or_else(|_| default_max_bytes());
+        evict(dir, max_bytes).await;
+    }
+    Ok(())
+}
+
+/// An image attachment's thumbnail as a data URL. The host makes it on first use. A client
+/// serves it from its cache, and otherwise fetches it from the host and returns None; the
+/// thumbnail follows with `thumbnail_ready`. Fails for files that have no thumbnail.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_attachment_thumbnail(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    attachment_id: i64,
+) -> AppResult<Option<String>> {
+    let no_preview = || AppError::Validation("That file has no preview".to_string());
+    if *state.is_server.read().await {
+        let path = host_thumbnail(&state, &db, attachment_id)
+            .await?
+            .ok_or_else(no_preview)?;
+        let bytes = tokio::fs::read(&path)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        return Ok(Some(data_url(&bytes)));
+    }
+    let dir = cache_dir(&state)?;
+    let key = client_thumbnail_key(&state, attachment_id).await;
+    if let Some(bytes) = get(dir, THUMBNAILS, &key).await {
+        return Ok(Some(data_url(&bytes)));
+    }
+    tokio::fs::create_dir_all(dir.join(THUMBNAILS))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    crate::attachments::fetch_thumbnail(&state, attachment_id, entry_path(dir, THUMBNAILS, &key))
+        .await?;
+    Ok(None)
+}
+
+#[tauri::command]
+pub async fn get_media_cache_config(db: State<'_, SqlitePool>) -> AppResult<MediaCacheConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_media_cache_config(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    config: MediaCacheConfig,
+) -> AppResult<()> {
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    evict(cache_dir(&state)?, config.max_bytes).await;
+    Ok(())
+}
+
+/// Empty the media cache. Returns the bytes freed.
+#[tauri::command]
+pub async fn clear_media_cache(state: State<'_, Arc<AppState>>) -> AppResult<u64> {
+    Ok(evict(cache_dir(&state)?, 0).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn least_recently_used_entries_are_evicted_first() {
+        let dir = std::env::temp_dir().join(format!("nutler-media-{}", uuid::Uuid::new_v4()));
+        let ago = |secs| SystemTime::now() - Duration::from_secs(secs);
+        for (name, age) in [("a", 30), ("b", 20), ("c", 10)] {
+            put(&dir, THUMBNAILS, name, &[0u8; 10], u64::MAX)
+                .await
+                .unwrap();
+            let file = std::fs::File::options()
+                .write(true)
+                .open(entry_path(&dir, THUMBNAILS, name))
+                .unwrap();
+            file.set_modified(ago(age)).unwrap();
+        }
+        // Reading "a" makes it the most recently used, so "b" and "c" go first.
+        assert!(get(&dir, THUMBNAILS, "a").await.is_some());
+        put(&dir, THUMBNAILS, "d", &[0u8; 10], 25).await.unwrap();
+        let left = |name| entry_path(&dir, THUMBNAILS, name).exists();
+        assert!(left("a") && left("d") && !left("b") && !left("c"));
+        assert_eq!(evict(&dir, 0).await, 20);
+        assert!(!left("a") && !left("d"));
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+        assert_eq!(key(&["10.0.0.5:7878", "42"]), "10_0_0_5_7878_42");
+    }
+
+    #[test]
+    fn thumbnails_are_small_jpegs() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(1000, 500)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let thumb = make_thumbnail(png.get_ref()).unwrap();
+        let decoded = image::load_from_memory(&thumb).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (320, 160));
+        assert!(make_thumbnail(b"not an image").is_none());
+    }
+}
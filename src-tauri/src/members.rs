@@ -0,0 +1,173 @@
+// Room member lists, for the member sidebar.
+//
+// `get_room_members` lists a room's active members from `user_rooms`, with their department,
+// when they joined and their role in the room: "owner" (its creator), "guest", or "member".
+// Presence comes from the host's live connections when this instance is hosting (connected or
+// parked within the resume grace = online, and the host's away list); otherwise from the
+// last-known `users.is_online` flag.
+
+use crate::db_queries::room_join_allowed_internal;
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tauri::State;
+
+pub const ONLINE: &str = "online";
+pub const AWAY: &str = "away";
+pub const OFFLINE: &str = "offline";
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RoomMember {
+    pub user_id: i64,
+    pub name: String,
+    pub email: String,
+    pub department_name: Option<String>,
+    // "owner", "guest" or "member".
+    pub role: String,
+    pub joined_at: Option<String>,
+    // "online", "away" or "offline".
+    pub presence: String,
+    pub last_seen: Option<String>,
+}
+
+/// The room's members, owner first then by name, with presence from the DB. `viewer` must be
+/// allowed into the room.
+pub async fn get_room_members_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    viewer: i64,
+) -> AppResult<Vec<RoomMember>> {
+    if !room_join_allowed_internal(pool, viewer, room_id)
+        .await
+        .map_err(AppError::Db)?
+    {
+        return Err(AppError::Auth(
+            "Only members can see who's in this channel".to_string(),
+        ));
+    }
+    let rows = sqlx::query(
+        "SELECT u.id, u.name, u.email, d.name AS department_name, u.is_online, u.last_seen,
+                ur.joined_at,
+                CASE WHEN cr.created_by = u.id THEN 'owner'
+                     WHEN u.is_guest = 1 THEN 'guest'
+                     ELSE 'member' END AS role
+         FROM user_rooms ur
+         JOIN users u ON u.id = ur.user_id
+         JOIN chat_rooms cr ON cr.id = ur.room_id
+         LEFT JOIN departments d ON d.id = u.department_id
+         WHERE ur.room_id = $1 AND ur.is_active = 1
+         ORDER BY cr.created_by = u.id DESC, u.name COLLATE NOCASE, u.id",
+    )
+    .bind(room_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|r| RoomMember {
+            user_id: r.get("id"),
+            name: r.get("name"),
+            email: r.get::<Option<String>, _>("email").unwrap_or_default(),
+            department_name: r.get("department_name"),
+            role: r.get("role"),
+            joined_at: r.get("joined_at"),
+            presence: if r.get::<Option<bool>, _>("is_online").unwrap_or(false) {
+                ONLINE
+            } else {
+                OFFLINE
+            }
+            .to_string(),
+            last_seen: r.get("last_seen"),
+        })
+        .collect())
+}
+
+/// Host: replace DB presence with what the live connections say.
+async fn merge_live_presence(state: &AppState, members: &mut [RoomMember]) {
+    let mut online: HashSet<u64> = state.server_streams.lock().await.keys().copied().collect();
+    online.extend(state.parked.lock().await.keys().copied());
+    let mut away: HashSet<u64> = state.away_users.lock().await.keys().copied().collect();
+    if let Some(host) = *state.user_id.read().await {
+        online.insert(host);
+        if state.is_away.load(Ordering::Relaxed) {
+            away.insert(host);
+        }
+    }
+    for member in members {
+        let id = member.user_id as u64;
+        member.presence = if away.contains(&id) {
+            AWAY
+        } else if online.contains(&id) {
+            ONLINE
+        } else {
+            OFFLINE
+        }
+        .to_string();
+    }
+}
+
+/// The members of `room_id`, as `user_id` may see them.
+#[tauri::command]
+pub async fn get_room_members(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    user_id: i64,
+) -> AppResult<Vec<RoomMember>> {
+    let mut members = get_room_members_internal(&db, room_id, user_id).await?;
+    if *state.is_server.read().await {
+        merge_live_presence(&state, &mut members).await;
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn members_list_roles_and_respects_privacy() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id, is_online, is_guest)
+                 VALUES (1, 'Alice', 'a@x', 1, 1, 0), (2, 'Bob', 'b@x', 1, 0, 0),
+                        (3, 'Gus', 'g@x', NULL, 0, 1), (4, 'Zed', 'z@x', 1, 0, 0);
+             INSERT INTO chat_rooms (id, name, is_private, created_by)
+                 VALUES (100, 'ops', 1, 2);
+             INSERT INTO user_rooms (user_id, room_id, is_active)
+                 VALUES (1, 100, 1), (2, 100, 1), (3, 100, 1), (4, 100, 0);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let members = get_room_members_internal(&pool, 100, 1).await.unwrap();
+        let summary: Vec<_> = members
+            .iter()
+            .map(|m| (m.name.as_str(), m.role.as_str(), m.presence.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Bob", "owner", OFFLINE),
+                ("Alice", "member", ONLINE),
+                ("Gus", "guest", OFFLINE)
+            ]
+        );
+        assert!(members[0].joined_at.is_some());
+        // Zed left, so the private room's list is closed to them.
+        assert!(matches!(
+            get_room_members_internal(&pool, 100, 4).await,
+            Err(AppError::Auth(_))
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+// Typed message events for the UI.
+//
+// Every frame the UI sees used to go out as a pre-serialized JSON string on one generic
+// `message` event, which the frontend then parsed and sorted by `message_type`. Frames now go
+// out as structured payloads on a named event per kind (`chat_message`, `system_message`,
+// `presence`, `reaction`, ...), see `event_name`. The old `message` event is still emitted
+// alongside while `legacy_message_events` is on (the default), for anything that listens for it.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::{Message, MessageType};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+const SETTINGS_KEY: &str = "message_events";
+
+/// The pre-typed event: the frame as a JSON string.
+const LEGACY_EVENT: &str = "message";
+
+static LEGACY: AtomicBool = AtomicBool::new(true);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MessageEventsConfig {
+    // Also emit every frame on the old `message` event, as a JSON string.
+    #[serde(default = "default_legacy")]
+    pub legacy_message_events: bool,
+}
+
+fn default_legacy() -> bool {
+    true
+}
+
+impl Default for MessageEventsConfig {
+    fn default() -> Self {
+        Self {
+            legacy_message_events: true,
+        }
+    }
+}
+
+/// The event a frame of this kind is emitted on.
+pub fn event_name(message_type: MessageType) -> &'static str {
+    match message_type {
+        MessageType::Chat
+        | MessageType::Sticker
+        | MessageType::Code
+        | MessageType::Attachment
+        | MessageType::Contact
+        | MessageType::Location => "chat_message",
+        MessageType::Connect
+        | MessageType::Disconnect
+        | MessageType::RoomJoin
+        | MessageType::RoomLeave
+        | MessageType::ServerAck
+        | MessageType::ErrorNotice
+        | MessageType::Maintenance
+        | MessageType::Muted
+        | MessageType::ReadOnlyRoom
+        | MessageType::DepartmentNotice
+        | MessageType::SystemResponse => "system_message",
+        MessageType::Presence | MessageType::RoomOccupancy | MessageType::UserList => "presence",
+        MessageType::Reaction => "reaction",
+        MessageType::Edit => "message_edited",
+        MessageType::Delete => "message_deleted",
+        MessageType::Typing => "typing",
+        MessageType::HistoryResponse | MessageType::HistoryPage => "history",
+        MessageType::JoinRequest | MessageType::JoinDecision => "join_request",
+        // Everything else is protocol housekeeping (directories, manifests, transfers, ...).
+        _ => "control_message",
+    }
+}
+
+/// Every event `event_name` can return, for listeners that want them all.
+pub const EVENT_NAMES: [&str; 10] = [
+    "chat_message",
+    "system_message",
+    "presence",
+    "reaction",
+    "message_edited",
+    "message_deleted",
+    "typing",
+    "history",
+    "join_request",
+    "control_message",
+];
+
+/// Show `msg` in this machine's UI.
+pub fn emit<R: Runtime>(app: &AppHandle<R>, msg: &Message) -> tauri::Result<()> {
+    app.emit(event_name(msg.message_type), msg)?;
+    if LEGACY.load(Ordering::Relaxed) {
+        let payload = serde_json::to_string(msg)?;
+        app.emit(LEGACY_EVENT, payload)?;
+    }
+    Ok(())
+}
+
+/// Show a frame as it arrived from the host. One this build can't decode only goes out on the
+/// legacy event, if that's on.
+pub fn emit_raw<R: Runtime>(app: &AppHandle<R>, frame: String) -> tauri::Result<()> {
+    if let Ok(msg) = serde_json::from_str::<Message>(&frame) {
+        app.emit(event_name(msg.message_type), &msg)?;
+    }
+    if LEGACY.load(Ordering::Relaxed) {
+        app.emit(LEGACY_EVENT, frame)?;
+    }
+    Ok(())
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<MessageEventsConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt message event settings: {}", e))),
+        None => Ok(MessageEventsConfig::default()),
+    }
+}
+
+pub async fn load(pool: &SqlitePool) {
+    match load_config(pool).await {
+        Ok(config) => LEGACY.store(config.legacy_message_events, Ordering::Relaxed),
+        Err(e) => tracing::warn!("{}", e),
+    }
+}
+
+#[tauri::command]
+pub async fn get_message_events_config(
+    db: State<'_, SqlitePool>,
+) -> AppResult<MessageEventsConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_message_events_config(
+    db: State<'_, SqlitePool>,
+    config: MessageEventsConfig,
+) -> AppResult<()> {
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    LEGACY.store(config.legacy_message_events, Ordering::Relaxed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_named_by_kind() {
+        assert_eq!(event_name(MessageType::Chat), "chat_message");
+        assert_eq!(event_name(MessageType::Location), "chat_message");
+        assert_eq!(event_name(MessageType::RoomJoin), "system_message");
+        assert_eq!(event_name(MessageType::Presence), "presence");
+        assert_eq!(event_name(MessageType::Reaction), "reaction");
+        assert_eq!(event_name(MessageType::Delete), "message_deleted");
+        assert_eq!(event_name(MessageType::EmojiManifest), "control_message");
+        assert!(EVENT_NAMES.contains(&event_name(MessageType::HistoryPage)));
+
+        // Settings saved before the flag existed keep the old event.
+        let config: MessageEventsConfig = serde_json::from_str("{}").unwrap();
+        assert!(config.legacy_message_events);
+    }
+}
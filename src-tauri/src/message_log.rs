@@ -0,0 +1,455 @@
+// The message log: every message stamped with a hybrid logical clock, and a merge that
+// reconciles two machines' histories (host migration, serverless peers).
+//
+// A stamp is "<wall ms, 13 digits>-<counter, 5 digits>-<node>", so ordering the text orders
+// causally: the clock never runs backwards, and it's pushed past every stamp it sees in a merge.
+// `export_message_log` writes the log in stamp order; `merge_message_log` folds another
+// machine's export in. Messages are matched by message_id, and two copies of one message resolve
+// the same whichever side merges: a deletion anywhere wins (the earliest one is kept), otherwise
+// the later edit wins, ties going to the greater text. Merging the same log twice changes
+// nothing. Authors are matched by email (else name) and rooms by name, created when missing.
+
+use crate::db::DbLocation;
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::disk_space;
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{Emitter, State};
+
+const NODE_KEY: &str = "message_log_node";
+
+/// The counter rolls the wall clock forward a millisecond rather than grow past 5 digits.
+const MAX_COUNTER: u32 = 99_999;
+
+// The last stamp handed out (or seen): wall ms and counter.
+static CLOCK: Mutex<(u64, u32)> = Mutex::new((0, 0));
+static NODE: OnceLock<String> = OnceLock::new();
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct LogEntry {
+    pub hlc: String,
+    pub message_id: String,
+    pub room: String,
+    pub author_name: String,
+    pub author_email: Option<String>,
+    pub message: String,
+    pub message_type: String,
+    pub is_emoji: bool,
+    pub created_at: String,
+    pub edited_at: Option<String>,
+    pub deleted_at: Option<String>,
+    pub language: Option<String>,
+    pub filename: Option<String>,
+    // Location messages only; absent from older exports.
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct MergeReport {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub users_created: usize,
+    pub rooms_created: usize,
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn bump((wall, counter): (u64, u32)) -> (u64, u32) {
+    if counter >= MAX_COUNTER {
+        (wall + 1, 0)
+    } else {
+        (wall, counter + 1)
+    }
+}
+
+/// The clock after a local event at `now`.
+fn tick(last: (u64, u32), now: u64) -> (u64, u32) {
+    if now > last.0 {
+        (now, 0)
+    } else {
+        bump(last)
+    }
+}
+
+/// The clock after seeing `remote` at `now`: past both it and everything before.
+fn observe(last: (u64, u32), remote: (u64, u32), now: u64) -> (u64, u32) {
+    let latest = last.max(remote);
+    if now > latest.0 {
+        (now, 0)
+    } else {
+        bump(latest)
+    }
+}
+
+fn format_stamp((wall, counter): (u64, u32), node: &str) -> String {
+    format!("{:013}-{:05}-{}", wall, counter, node)
+}
+
+fn parse_stamp(stamp: &str) -> Option<(u64, u32)> {
+    let mut parts = stamp.splitn(3, '-');
+    let wall = parts.next()?.parse().ok()?;
+    let counter = parts.next()?.parse().ok()?;
+    Some((wall, counter))
+}
+
+/// This install's node id, made up once and kept in settings.
+async fn node(pool: &SqlitePool) -> String {
+    if let Some(node) = NODE.get() {
+        return node.clone();
+    }
+    let node = match get_setting_internal(pool, NODE_KEY).await {
+        Ok(Some(node)) => node,
+        _ => {
+            let node = crate::db::random_hex_key()[..12].to_string();
+            if let Err(e) = set_setting_internal(pool, NODE_KEY, &node).await {
+                tracing::warn!("Message log node id not saved: {}", e);
+            }
+            node
+        }
+    };
+    NODE.get_or_init(|| node).clone()
+}
+
+/// A fresh stamp for a message saved now.
+pub async fn stamp(pool: &SqlitePool) -> String {
+    let node = node(pool).await;
+    let mut clock = CLOCK.lock().unwrap_or_else(|e| e.into_inner());
+    *clock = tick(*clock, now_ms());
+    format_stamp(*clock, &node)
+}
+
+fn witness(stamp: &str) {
+    if let Some(remote) = parse_stamp(stamp) {
+        let mut clock = CLOCK.lock().unwrap_or_else(|e| e.into_inner());
+        *clock = observe(*clock, remote, now_ms());
+    }
+}
+
+/// The whole log, in stamp order.
+pub async fn log_entries(pool: &SqlitePool) -> Result<Vec<LogEntry>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT m.hlc, m.message_id, r.name AS room, COALESCE(u.name, 'Unknown') AS author_name,
+                u.email AS author_email, m.message, COALESCE(m.message_type, 'chat') AS message_type,
+                COALESCE(m.is_emoji, FALSE) AS is_emoji, m.created_at, m.edited_at, m.deleted_at,
+                m.language, m.filename, m.latitude, m.longitude
+         FROM messages m
+         JOIN chat_rooms r ON r.id = m.room_id
+         LEFT JOIN users u ON u.id = m.user_id
+         WHERE m.message_id IS NOT NULL AND m.hlc IS NOT NULL
+         ORDER BY m.hlc, m.message_id",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Which of two copies of one message's text stands: the later edit, then the greater text.
+fn remote_text_wins(local: &LogEntry, remote: &LogEntry) -> bool {
+    (&remote.edited_at, &remote.message) > (&local.edited_at, &local.message)
+}
+
+/// The earliest of two deletions, if either copy is deleted.
+fn earliest_deletion(a: &Option<String>, b: &Option<String>) -> Option<String> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b).clone()),
+        (a, b) => a.clone().or_else(|| b.clone()),
+    }
+}
+
+/// Fold `entries` (another machine's log) into ours, in one transaction.
+pub async fn merge_entries(
+    pool: &SqlitePool,
+    mut entries: Vec<LogEntry>,
+) -> Result<MergeReport, sqlx::Error> {
+    entries.sort_by(|a, b| (&a.hlc, &a.message_id).cmp(&(&b.hlc, &b.message_id)));
+    let mut report = MergeReport::default();
+    let mut tx = pool.begin().await?;
+    for remote in entries {
+        witness(&remote.hlc);
+        let local: Option<LogEntry> = sqlx::query_as(
+            "SELECT m.hlc, m.message_id, r.name AS room, COALESCE(u.name, 'Unknown') AS author_name,
+                    u.email AS author_email, m.message,
+                    COALESCE(m.message_type, 'chat') AS message_type,
+                    COALESCE(m.is_emoji, FALSE) AS is_emoji, m.created_at, m.edited_at,
+                    m.deleted_at, m.language, m.filename, m.latitude, m.longitude
+             FROM messages m
+             JOIN chat_rooms r ON r.id = m.room_id
+             LEFT JOIN users u ON u.id = m.user_id
+             WHERE m.message_id = $1",
+        )
+        .bind(&remote.message_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(local) = local {
+            let text = if remote_text_wins(&local, &remote) {
+                &remote
+            } else {
+                &local
+            };
+            let merged = LogEntry {
+                hlc: local.hlc.clone().min(remote.hlc.clone()),
+                message: text.message.clone(),
+                edited_at: text.edited_at.clone(),
+                language: text.language.clone(),
+                filename: text.filename.clone(),
+                deleted_at: earliest_deletion(&local.deleted_at, &remote.deleted_at),
+                ..local.clone()
+            };
+            if merged == local {
+                report.unchanged += 1;
+                continue;
+            }
+            sqlx::query(
+                "UPDATE messages SET hlc = $1, message = $2, edited_at = $3, deleted_at = $4,
+                                     language = $5, filename = $6
+                 WHERE message_id = $7",
+            )
+            .bind(&merged.hlc)
+            .bind(&merged.message)
+            .bind(&merged.edited_at)
+            .bind(&merged.deleted_at)
+            .bind(&merged.language)
+            .bind(&merged.filename)
+            .bind(&merged.message_id)
+            .execute(&mut *tx)
+            .await?;
+            report.updated += 1;
+            continue;
+        }
+
+        let existing_user: Option<i64> =
+            match &remote.author_email {
+                Some(email) => {
+                    sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+                        .bind(email)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                }
+                None => sqlx::query_scalar(
+                    "SELECT id FROM users WHERE name = $1 AND email IS NULL ORDER BY id LIMIT 1",
+                )
+                .bind(&remote.author_name)
+                .fetch_optional(&mut *tx)
+                .await?,
+            };
+        let user_id = match existing_user {
+            Some(id) => id,
+            None => {
+                report.users_created += 1;
+                sqlx::query("INSERT INTO users (name, email) VALUES ($1, $2)")
+                    .bind(&remote.author_name)
+                    .bind(&remote.author_email)
+                    .execute(&mut *tx)
+                    .await?
+                    .last_insert_rowid()
+            }
+        };
+        let inserted = sqlx::query(
+            "INSERT INTO chat_rooms (name, description) VALUES ($1, 'Merged from another machine')
+             ON CONFLICT(name) DO NOTHING",
+        )
+        .bind(&remote.room)
+        .execute(&mut *tx)
+        .await?;
+        report.rooms_created += inserted.rows_affected() as usize;
+        let room_id: i64 = sqlx::query_scalar("SELECT id FROM chat_rooms WHERE name = $1")
+            .bind(&remote.room)
+            .fetch_one(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO messages (room_id, user_id, message, message_type, is_emoji, message_id,
+                                   created_at, edited_at, deleted_at, language, filename,
+                                   latitude, longitude, hlc)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
+        )
+        .bind(room_id)
+        .bind(user_id)
+        .bind(&remote.message)
+        .bind(&remote.message_type)
+        .bind(remote.is_emoji)
+        .bind(&remote.message_id)
+        .bind(&remote.created_at)
+        .bind(&remote.edited_at)
+        .bind(&remote.deleted_at)
+        .bind(&remote.language)
+        .bind(&remote.filename)
+        .bind(remote.latitude)
+        .bind(remote.longitude)
+        .bind(&remote.hlc)
+        .execute(&mut *tx)
+        .await?;
+        report.added += 1;
+    }
+    tx.commit().await?;
+    Ok(report)
+}
+
+/// Write the message log to `path` as JSON, in causal order.
+#[tauri::command]
+pub async fn export_message_log(db: State<'_, SqlitePool>, path: String) -> AppResult<usize> {
+    let entries = log_entries(&db).await?;
+    let json = serde_json::to_vec(&entries).map_err(|e| AppError::Internal(e.to_string()))?;
+    tokio::fs::write(&path, json)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to save {}: {}", path, e)))?;
+    Ok(entries.len())
+}
+
+/// Merge another machine's exported log at `path` into this database.
+#[tauri::command]
+pub async fn merge_message_log(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    location: State<'_, DbLocation>,
+    path: String,
+) -> AppResult<MergeReport> {
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|e| AppError::Validation(format!("Couldn't read {}: {}", path, e)))?;
+    if let Some(dir) = location.db_path.parent() {
+        disk_space::ensure_room(&app, &db, dir, bytes.len() as u64).await?;
+    }
+    let entries: Vec<LogEntry> = serde_json::from_slice(&bytes)
+        .map_err(|e| AppError::Validation(format!("That isn't a message log: {}", e)))?;
+    let report = merge_entries(&db, entries).await?;
+    if report.rooms_created > 0 {
+        let _ = app.emit("rooms_changed", ());
+        crate::sockets::broadcast_room_list(&app, state.inner(), &db).await;
+    }
+    tracing::info!(
+        "🔀 Merged message log: {} added, {} updated, {} unchanged",
+        report.added,
+        report.updated,
+        report.unchanged
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[test]
+    fn the_clock_only_moves_forward() {
+        assert_eq!(tick((1_000, 4), 2_000), (2_000, 0));
+        // A clock stepped backwards keeps counting from the last stamp.
+        assert_eq!(tick((2_000, 0), 1_500), (2_000, 1));
+        assert_eq!(tick((2_000, MAX_COUNTER), 2_000), (2_001, 0));
+        assert_eq!(observe((1_000, 2), (3_000, 7), 2_000), (3_000, 8));
+        assert_eq!(observe((1_000, 2), (900, 7), 5_000), (5_000, 0));
+
+        let a = format_stamp((999, 3), "n1");
+        let b = format_stamp((1_000, 0), "n0");
+        assert!(a < b);
+        assert_eq!(parse_stamp(&b), Some((1_000, 0)));
+    }
+
+    // (hlc, message_id, text, edited_at, deleted_at)
+    type Row<'a> = (&'a str, &'a str, &'a str, Option<&'a str>, Option<&'a str>);
+
+    async fn machine(messages: &[Row<'_>]) -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email) VALUES (900, 'Ann', 'ann@x');
+             INSERT INTO chat_rooms (id, name) VALUES (900, 'ops');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        for (hlc, id, text, edited, deleted) in messages {
+            sqlx::query(
+                "INSERT INTO messages (room_id, user_id, message, message_id, hlc, edited_at,
+                                       deleted_at)
+                 VALUES (900, 900, $1, $2, $3, $4, $5)",
+            )
+            .bind(text)
+            .bind(id)
+            .bind(hlc)
+            .bind(edited)
+            .bind(deleted)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        pool
+    }
+
+    fn summary(entries: Vec<LogEntry>) -> Vec<(String, String, String, bool)> {
+        entries
+            .into_iter()
+            .map(|e| (e.hlc, e.message_id, e.message, e.deleted_at.is_some()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn merging_either_way_gives_the_same_log() {
+        let a = machine(&[
+            ("0000000001000-00000-a", "m1", "hello", None, None),
+            (
+                "0000000002000-00000-a",
+                "m2",
+                "edited on a",
+                Some("2026-01-01 10:00:00"),
+                None,
+            ),
+            ("0000000003000-00000-a", "m3", "only on a", None, None),
+        ])
+        .await;
+        let b = machine(&[
+            (
+                "0000000001000-00000-a",
+                "m1",
+                "hello",
+                None,
+                Some("2026-01-01 09:00:00"),
+            ),
+            ("0000000002000-00000-a", "m2", "original", None, None),
+            ("0000000002500-00000-b", "m4", "only on b", None, None),
+        ])
+        .await;
+        let from_a = log_entries(&a).await.unwrap();
+        let from_b = log_entries(&b).await.unwrap();
+
+        let report = merge_entries(&a, from_b.clone()).await.unwrap();
+        assert_eq!((report.added, report.updated, report.unchanged), (1, 1, 1));
+        merge_entries(&b, from_a).await.unwrap();
+
+        let merged = summary(log_entries(&a).await.unwrap());
+        assert_eq!(merged, summary(log_entries(&b).await.unwrap()));
+        let texts: Vec<(&str, &str, bool)> = merged
+            .iter()
+            .map(|(_, id, text, deleted)| (id.as_str(), text.as_str(), *deleted))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                ("m1", "hello", true),
+                ("m2", "edited on a", false),
+                ("m4", "only on b", false),
+                ("m3", "only on a", false),
+            ]
+        );
+
+        // Merging again changes nothing.
+        let again = merge_entries(&a, from_b).await.unwrap();
+        assert_eq!((again.added, again.updated), (0, 0));
+    }
+}
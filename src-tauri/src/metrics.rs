@@ -0,0 +1,298 @@
+// Prometheus metrics exporter (optional, off by default).
+//
+// Process-wide counters and fixed-bucket histograms, updated lock-free from the hot paths
+// (room fan-out, message persistence), plus a tiny HTTP responder serving them in the Prometheus
+// text exposition format at `GET /metrics`. It binds 127.0.0.1 only: a local Prometheus agent
+// scrapes it and Grafana graphs the result. Nothing here is sent anywhere unless scraped.
+//
+// Gauges that are cheap to read from live state (connected clients, queued fan-out frames) are
+// computed at scrape time rather than tracked incrementally. `get_server_stats` gives the host's
+// UI the same, plus how each connection is draining (see sockets::DrainStats).
+
+use crate::sockets::AppState;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Default scrape port when `start_metrics_exporter` is called without one.
+const DEFAULT_METRICS_PORT: u16 = 9464;
+
+/// Request heads larger than this are rejected; a scrape is a single short GET.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// Upper bounds (seconds) shared by the latency histograms: 0.5 ms … 5 s.
+const LATENCY_BUCKETS: [f64; 10] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 5.0,
+];
+
+/// Cumulative-on-render histogram: each observation lands in its first matching bucket.
+pub struct Histogram {
+    counts: [AtomicU64; LATENCY_BUCKETS.len()],
+    overflow: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            counts: [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len()],
+            overflow: AtomicU64::new(0),
+            sum_us: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        match LATENCY_BUCKETS.iter().position(|&le| secs <= le) {
+            Some(i) => self.counts[i].fetch_add(1, Ordering::Relaxed),
+            None => self.overflow.fetch_add(1, Ordering::Relaxed),
+        };
+        self.sum_us
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (le, count) in LATENCY_BUCKETS.iter().zip(&self.counts) {
+            cumulative += count.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+        }
+        cumulative += self.overflow.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, cumulative);
+        let sum = self.sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, cumulative);
+    }
+}
+
+pub struct Metrics {
+    // Frames fanned out to a room (chat, edits, joins, …), for messages/sec via rate().
+    pub messages_broadcast: AtomicU64,
+    // Time from a frame being queued on a room channel to its write completing, per recipient.
+    pub broadcast_latency: Histogram,
+    // Time taken by a message INSERT on the host DB.
+    pub db_write_latency: Histogram,
+}
+
+pub static METRICS: Metrics = Metrics {
+    messages_broadcast: AtomicU64::new(0),
+    broadcast_latency: Histogram::new(),
+    db_write_latency: Histogram::new(),
+};
+
+/// Render every metric in the Prometheus text format. The live gauges are passed in.
+fn render(connected_clients: usize, outbox_depth: usize) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# HELP nutler_connected_clients Clients currently connected to this host."
+    );
+    let _ = writeln!(out, "# TYPE nutler_connected_clients gauge");
+    let _ = writeln!(out, "nutler_connected_clients {}", connected_clients);
+    let _ = writeln!(
+        out,
+        "# HELP nutler_outbox_depth Frames queued on room channels, not yet sent to every subscriber."
+    );
+    let _ = writeln!(out, "# TYPE nutler_outbox_depth gauge");
+    let _ = writeln!(out, "nutler_outbox_depth {}", outbox_depth);
+    let _ = writeln!(
+        out,
+        "# HELP nutler_messages_broadcast_total Frames fanned out to a room."
+    );
+    let _ = writeln!(out, "# TYPE nutler_messages_broadcast_total counter");
+    let _ = writeln!(
+        out,
+        "nutler_messages_broadcast_total {}",
+        METRICS.messages_broadcast.load(Ordering::Relaxed)
+    );
+    METRICS.broadcast_latency.render(
+        &mut out,
+        "nutler_broadcast_latency_seconds",
+        "Queue-to-socket latency of room fan-out, per recipient.",
+    );
+    METRICS.db_write_latency.render(
+        &mut out,
+        "nutler_db_write_latency_seconds",
+        "Latency of message writes to the host database.",
+    );
+    out
+}
+
+async fn outbox_depth(state: &Arc<AppState>) -> usize {
+    state
+        .room_channels
+        .lock()
+        .await
+        .values()
+        .map(|tx| tx.len())
+        .sum()
+}
+
+async fn scrape(state: &Arc<AppState>) -> String {
+    let connected = state.server_streams.lock().await.len();
+    render(connected, outbox_depth(state).await)
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ConnectionStats {
+    pub user_id: u64,
+    pub username: String,
+    pub room: String,
+    // Frames on its room channel not yet taken for sending.
+    pub queued: usize,
+    // How long since a frame last reached its socket.
+    pub last_write_age_ms: i64,
+    pub lagging: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ServerStats {
+    pub connected_clients: usize,
+    pub outbox_depth: usize,
+    pub messages_broadcast: u64,
+    // Most backed-up first.
+    pub connections: Vec<ConnectionStats>,
+}
+
+pub(crate) async fn server_stats(state: &Arc<AppState>) -> ServerStats {
+    let mut connections: Vec<ConnectionStats> = state
+        .server_streams
+        .lock()
+        .await
+        .values()
+        .map(|c| ConnectionStats {
+            user_id: c.user_id,
+            username: c.username.clone(),
+            room: c.current_room.clone(),
+            queued: c.drain.queued.load(Ordering::Relaxed),
+            last_write_age_ms: c.drain.last_write_age_ms(),
+            lagging: c.drain.is_lagging(),
+        })
+        .collect();
+    connections.sort_by(|a, b| {
+        (b.queued, b.last_write_age_ms)
+            .cmp(&(a.queued, a.last_write_age_ms))
+            .then_with(|| a.username.cmp(&b.username))
+    });
+    ServerStats {
+        connected_clients: connections.len(),
+        outbox_depth: outbox_depth(state).await,
+        messages_broadcast: METRICS.messages_broadcast.load(Ordering::Relaxed),
+        connections,
+    }
+}
+
+/// Answer one HTTP/1.x request: `GET /metrics` → 200 with the exposition, anything else → 404.
+async fn serve_one(state: Arc<AppState>, mut stream: tokio::net::TcpStream) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    // Read just the request head; scrapes carry no body.
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        match tokio::time::timeout(Duration::from_secs(5), stream.read(&mut chunk)).await {
+            Ok(Ok(n)) if n > 0 => buf.extend_from_slice(&chunk[..n]),
+            _ => return,
+        }
+        if buf.len() > MAX_REQUEST_BYTES {
+            return;
+        }
+    }
+    let is_metrics = buf.starts_with(b"GET /metrics ") || buf.starts_with(b"GET /metrics?");
+    let (status, body) = if is_metrics {
+        ("200 OK", scrape(&state).await)
+    } else {
+        ("404 Not Found", "not found\n".to_string())
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Start serving `/metrics` on 127.0.0.1:`port` (default 9464), replacing a running exporter.
+/// Returns the bound address.
+#[tauri::command]
+pub async fn start_metrics_exporter(
+    state: State<'_, Arc<AppState>>,
+    port: Option<u16>,
+) -> Result<String, String> {
+    // Stop any previous exporter first so re-starting on the same port can re-bind.
+    if let Some(old) = state.metrics_server.lock().await.take() {
+        old.abort();
+    }
+    let bind_addr = format!("127.0.0.1:{}", port.unwrap_or(DEFAULT_METRICS_PORT));
+    let listener = tokio::net::TcpListener::bind(&bind_addr)
+        .await
+        .map_err(|e| format!("Failed to bind metrics exporter to {}: {}", bind_addr, e))?;
+    let local = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to get metrics address: {}", e))?;
+
+    let state_clone = Arc::clone(state.inner());
+    let handle = tauri::async_runtime::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tauri::async_runtime::spawn(serve_one(Arc::clone(&state_clone), stream));
+                }
+                Err(e) => tracing::warn!("Metrics exporter accept failed: {}", e),
+            }
+        }
+    });
+    *state.metrics_server.lock().await = Some(handle);
+    tracing::info!("📈 Metrics exporter listening on http://{}/metrics", local);
+    Ok(local.to_string())
+}
+
+/// The host's live delivery stats: per-connection queue depth and last-write age.
+#[tauri::command]
+pub async fn get_server_stats(state: State<'_, Arc<AppState>>) -> Result<ServerStats, String> {
+    Ok(server_stats(state.inner()).await)
+}
+
+#[tauri::command]
+pub async fn stop_metrics_exporter(state: State<'_, Arc<AppState>>) -> Result<(), String> {
+    if let Some(handle) = state.metrics_server.lock().await.take() {
+        handle.abort();
+        tracing::info!("📈 Metrics exporter stopped");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let h = Histogram::new();
+        h.observe(Duration::from_micros(300)); // ≤ 0.5 ms
+        h.observe(Duration::from_millis(20)); // ≤ 25 ms
+        h.observe(Duration::from_secs(10)); // overflow → only +Inf
+        let mut out = String::new();
+        h.render(&mut out, "t", "test");
+        assert!(out.contains("t_bucket{le=\"0.0005\"} 1\n"));
+        assert!(out.contains("t_bucket{le=\"0.025\"} 2\n"));
+        assert!(out.contains("t_bucket{le=\"5\"} 2\n"));
+        assert!(out.contains("t_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("t_count 3\n"));
+    }
+
+    #[test]
+    fn render_includes_live_gauges() {
+        let out = render(7, 3);
+        assert!(out.contains("nutler_connected_clients 7\n"));
+        assert!(out.contains("nutler_outbox_depth 3\n"));
+        assert!(out.contains("# TYPE nutler_broadcast_latency_seconds histogram"));
+        assert!(out.contains("# TYPE nutler_db_write_latency_seconds histogram"));
+    }
+}
@@ -241,6 +241,607 @@ pub fn get_migrations() -> Vec<Migration> {
             sql: "ALTER TABLE chat_rooms ADD COLUMN is_dm BOOLEAN NOT NULL DEFAULT 0;",
             kind: MigrationKind::Up,
         },
+        // Migration 14: host-local key/value settings (integration configs etc.), values as
+        // JSON text. Secrets never go here — they live in the OS keychain.
+        Migration {
+            version: 14,
+            description: "create_app_settings_table",
+            sql: "CREATE TABLE app_settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 15: optional local password accounts. password_hash is an argon2id PHC
+        // string (NULL = no password set); failed_logins/locked_until drive the lockout.
+        // auth_sessions keys on a SHA-256 of the session token, never the token itself.
+        Migration {
+            version: 15,
+            description: "add_local_accounts",
+            sql: "ALTER TABLE users ADD COLUMN password_hash TEXT;
+                ALTER TABLE users ADD COLUMN failed_logins INTEGER NOT NULL DEFAULT 0;
+                ALTER TABLE users ADD COLUMN locked_until TIMESTAMP;
+                CREATE TABLE auth_sessions (
+                    token_hash TEXT PRIMARY KEY,
+                    user_id INTEGER NOT NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    expires_at TIMESTAMP NOT NULL,
+                    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+                );
+                CREATE INDEX idx_auth_sessions_user ON auth_sessions(user_id);",
+            kind: MigrationKind::Up,
+        },
+        // Migration 16: queue of mentions / DMs missed while offline, drained into email
+        // digests. UNIQUE(user_id, message_id) so a DM that also @-mentions queues once.
+        Migration {
+            version: 16,
+            description: "add_email_outbox",
+            sql: "CREATE TABLE email_outbox (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    user_id INTEGER NOT NULL,
+                    message_id TEXT NOT NULL,
+                    reason TEXT NOT NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    sent_at TIMESTAMP,
+                    UNIQUE (user_id, message_id),
+                    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+                );
+                CREATE INDEX idx_email_outbox_pending ON email_outbox(sent_at, user_id);",
+            kind: MigrationKind::Up,
+        },
+        // Migration 17: incoming webhooks. Each posts into room_id as its own bot user_id and is
+        // addressed by a token; only the token's SHA-256 is stored.
+        Migration {
+            version: 17,
+            description: "add_incoming_webhooks",
+            sql: "CREATE TABLE incoming_webhooks (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL,
+                    room_id INTEGER NOT NULL,
+                    user_id INTEGER NOT NULL,
+                    token_hash TEXT NOT NULL UNIQUE,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    FOREIGN KEY (room_id) REFERENCES chat_rooms(id) ON DELETE CASCADE,
+                    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 18: per-room retention override (NULL = the global policy) and legal hold
+        // (exempts the room from pruning and deletes), plus an append-only audit log for admin
+        // actions. actor_user_id is NULL for the system (e.g. the scheduled pruner).
+        Migration {
+            version: 18,
+            description: "add_room_retention_legal_hold_and_audit_log",
+            sql: "ALTER TABLE chat_rooms ADD COLUMN retention_days INTEGER;
+                ALTER TABLE chat_rooms ADD COLUMN legal_hold BOOLEAN NOT NULL DEFAULT 0;
+                CREATE TABLE audit_log (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    actor_user_id INTEGER,
+                    action TEXT NOT NULL,
+                    target TEXT NOT NULL,
+                    detail TEXT,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+                CREATE INDEX idx_audit_log_created ON audit_log(created_at);",
+            kind: MigrationKind::Up,
+        },
+        // Migration 19: free-form room tags, for organizing rooms beyond the single department.
+        // Stored normalized (lowercase), one row per (room, tag).
+        Migration {
+            version: 19,
+            description: "add_room_tags",
+            sql: "CREATE TABLE room_tags (
+                    room_id INTEGER NOT NULL,
+                    tag TEXT NOT NULL,
+                    PRIMARY KEY (room_id, tag),
+                    FOREIGN KEY (room_id) REFERENCES chat_rooms(id) ON DELETE CASCADE
+                );
+                CREATE INDEX idx_room_tags_tag ON room_tags(tag);",
+            kind: MigrationKind::Up,
+        },
+        // Migration 20: auto-join rooms. A flagged room with a department gets that department's
+        // users; one with no department (or the seeded 'General' one, i.e. Company Wide) gets
+        // everyone. The seeded default rooms start flagged.
+        Migration {
+            version: 20,
+            description: "add_room_auto_join",
+            sql: "ALTER TABLE chat_rooms ADD COLUMN auto_join BOOLEAN NOT NULL DEFAULT 0;
+                UPDATE chat_rooms SET auto_join = 1
+                 WHERE name = 'Company Wide'
+                    OR (department_id IS NOT NULL
+                        AND name = (SELECT d.name || ' General' FROM departments d
+                                    WHERE d.id = chat_rooms.department_id));",
+            kind: MigrationKind::Up,
+        },
+        // Migration 21: temporary guest users, limited to public rooms and removed by the cleanup
+        // task once guest_expires_at passes.
+        Migration {
+            version: 21,
+            description: "add_guest_users",
+            sql: "ALTER TABLE users ADD COLUMN is_guest BOOLEAN NOT NULL DEFAULT 0;
+                ALTER TABLE users ADD COLUMN guest_expires_at TIMESTAMP;
+                CREATE INDEX idx_users_guest_expiry ON users(guest_expires_at) WHERE is_guest = 1;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 22: custom emoji. The host's own uploads, and on a client its cache of the
+        // host's set (synced by hash on connect).
+        Migration {
+            version: 22,
+            description: "add_custom_emoji",
+            sql: "CREATE TABLE custom_emoji (
+                    name TEXT PRIMARY KEY,
+                    mime TEXT NOT NULL,
+                    data BLOB NOT NULL,
+                    hash TEXT NOT NULL,
+                    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 23: sticker packs. Like custom_emoji, the host's own set or a client's cache of
+        // it; a client keeps the host's ids so Sticker messages resolve the same everywhere.
+        Migration {
+            version: 23,
+            description: "add_sticker_packs",
+            sql: "CREATE TABLE sticker_packs (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE,
+                    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+                CREATE TABLE stickers (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    pack_id INTEGER NOT NULL REFERENCES sticker_packs(id) ON DELETE CASCADE,
+                    name TEXT NOT NULL,
+                    mime TEXT NOT NULL,
+                    data BLOB NOT NULL,
+                    hash TEXT NOT NULL,
+                    UNIQUE (pack_id, name)
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 24: code snippet messages (message_type 'Code') carry a highlighting language
+        // and the file they came from.
+        Migration {
+            version: 24,
+            description: "add_code_snippet_columns",
+            sql: "ALTER TABLE messages ADD COLUMN language TEXT;
+                ALTER TABLE messages ADD COLUMN filename TEXT;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 25: personal message reminders, kept on the machine that set them. No FK to
+        // messages: a client has no local copy of the room's history.
+        Migration {
+            version: 25,
+            description: "add_reminders",
+            sql: "CREATE TABLE reminders (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    message_id TEXT NOT NULL,
+                    room_id INTEGER NOT NULL,
+                    room TEXT NOT NULL,
+                    preview TEXT NOT NULL,
+                    remind_at TIMESTAMP NOT NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+                CREATE INDEX idx_reminders_remind_at ON reminders (remind_at);",
+            kind: MigrationKind::Up,
+        },
+        // Migration 26: flagged messages awaiting (or past) moderator review. One flag per reporter
+        // per message; resolving settles every open flag on that message.
+        Migration {
+            version: 26,
+            description: "add_message_flags",
+            sql: "CREATE TABLE message_flags (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    message_id TEXT NOT NULL REFERENCES messages(message_id) ON DELETE CASCADE,
+                    reporter_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                    reason TEXT NOT NULL,
+                    status TEXT NOT NULL DEFAULT 'open',
+                    resolved_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    resolved_at TIMESTAMP,
+                    UNIQUE (message_id, reporter_id)
+                );
+                CREATE INDEX idx_message_flags_status ON message_flags (status);",
+            kind: MigrationKind::Up,
+        },
+        // Migration 27: who each announcement (@room message) was expected to reach, and who
+        // acknowledged it. No FK to messages: the rows are written before the message is saved.
+        Migration {
+            version: 27,
+            description: "add_announcement_receipts",
+            sql: "CREATE TABLE announcement_receipts (
+                    message_id TEXT NOT NULL,
+                    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                    username TEXT NOT NULL,
+                    delivered_at TIMESTAMP,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (message_id, user_id)
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 28: requests to join private channels, awaiting (or past) a moderator's
+        // decision. One row per user and room; asking again after a denial reopens it.
+        Migration {
+            version: 28,
+            description: "add_room_join_requests",
+            sql: "CREATE TABLE room_join_requests (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    room_id INTEGER NOT NULL REFERENCES chat_rooms(id) ON DELETE CASCADE,
+                    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                    status TEXT NOT NULL DEFAULT 'pending',
+                    decided_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    decided_at TIMESTAMP,
+                    UNIQUE (room_id, user_id)
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 29: invite links. Only the token's SHA-256 is kept; NULL expires_at / max_uses
+        // mean no expiry / unlimited uses.
+        Migration {
+            version: 29,
+            description: "add_invites",
+            sql: "CREATE TABLE invites (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    token_hash TEXT NOT NULL UNIQUE,
+                    room_id INTEGER NOT NULL REFERENCES chat_rooms(id) ON DELETE CASCADE,
+                    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                    expires_at TIMESTAMP,
+                    max_uses INTEGER,
+                    uses INTEGER NOT NULL DEFAULT 0,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 30: per-user connection lifecycle events (connect / reconnect / disconnect /
+        // error) recorded by the host, for diagnosing dropped connections.
+        Migration {
+            version: 30,
+            description: "add_connection_log",
+            sql: "CREATE TABLE connection_log (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                    event TEXT NOT NULL,
+                    peer TEXT,
+                    detail TEXT,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+                CREATE INDEX idx_connection_log_user ON connection_log(user_id, id);",
+            kind: MigrationKind::Up,
+        },
+        // Migration 31: files shared in rooms. The bytes live in the host's attachments directory
+        // under the row id; `message_id` is the Attachment message that posted it.
+        Migration {
+            version: 31,
+            description: "add_attachments",
+            sql: "CREATE TABLE attachments (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    room_id INTEGER NOT NULL REFERENCES chat_rooms(id) ON DELETE CASCADE,
+                    message_id TEXT NOT NULL UNIQUE,
+                    uploaded_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                    filename TEXT NOT NULL,
+                    mime TEXT NOT NULL,
+                    size INTEGER NOT NULL,
+                    sha256 TEXT NOT NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+                CREATE INDEX idx_attachments_room ON attachments(room_id);",
+            kind: MigrationKind::Up,
+        },
+        // Migration 32: full-text index over message text for cross-room search. An external-
+        // content FTS5 table over `messages`, kept in step by triggers, and built from what's
+        // already there.
+        Migration {
+            version: 32,
+            description: "add_messages_fts",
+            sql: "CREATE VIRTUAL TABLE messages_fts USING fts5(
+                    message, content='messages', content_rowid='id',
+                    tokenize='unicode61 remove_diacritics 2'
+                );
+                CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+                    INSERT INTO messages_fts(rowid, message) VALUES (new.id, new.message);
+                END;
+                CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+                    INSERT INTO messages_fts(messages_fts, rowid, message)
+                        VALUES ('delete', old.id, old.message);
+                END;
+                CREATE TRIGGER messages_fts_update AFTER UPDATE OF message ON messages BEGIN
+                    INSERT INTO messages_fts(messages_fts, rowid, message)
+                        VALUES ('delete', old.id, old.message);
+                    INSERT INTO messages_fts(rowid, message) VALUES (new.id, new.message);
+                END;
+                INSERT INTO messages_fts(messages_fts) VALUES ('rebuild');",
+            kind: MigrationKind::Up,
+        },
+        // Migration 33: every earlier version of an edited message, recorded by trigger whatever
+        // path the edit took. Deleting the message (soft or hard) drops its history too.
+        Migration {
+            version: 33,
+            description: "add_message_edits",
+            sql: "CREATE TABLE message_edits (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    message_id TEXT NOT NULL REFERENCES messages(message_id) ON DELETE CASCADE,
+                    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+                    message TEXT NOT NULL,
+                    edited_at TIMESTAMP NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now'))
+                );
+                CREATE INDEX idx_message_edits_message ON message_edits(message_id);
+                CREATE TRIGGER message_edits_record AFTER UPDATE OF message ON messages
+                WHEN old.deleted_at IS NULL AND new.deleted_at IS NULL
+                     AND old.message IS NOT new.message AND old.message_id IS NOT NULL
+                BEGIN
+                    INSERT INTO message_edits (message_id, user_id, message)
+                        VALUES (old.message_id, old.user_id, old.message);
+                END;
+                CREATE TRIGGER message_edits_forget AFTER UPDATE OF deleted_at ON messages
+                WHEN new.deleted_at IS NOT NULL
+                BEGIN
+                    DELETE FROM message_edits WHERE message_id = new.message_id;
+                END;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 34: each user's UI settings (a JSON object the UI owns), kept on the host so
+        // they follow the user between machines.
+        Migration {
+            version: 34,
+            description: "add_user_settings",
+            sql: "CREATE TABLE user_settings (
+                    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                    settings TEXT NOT NULL,
+                    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 35: the app version each user last connected with, for the host's view of
+        // which client versions are still in use.
+        Migration {
+            version: 35,
+            description: "add_user_app_version",
+            sql: "ALTER TABLE users ADD COLUMN app_version TEXT;
+                ALTER TABLE users ADD COLUMN app_version_seen_at TIMESTAMP;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 36: opt-in usage statistics: how often each command is called and each
+        // module logs a problem. Names and counts only.
+        Migration {
+            version: 36,
+            description: "add_usage_counters",
+            sql: "CREATE TABLE usage_counters (
+                    kind TEXT NOT NULL,
+                    name TEXT NOT NULL,
+                    count INTEGER NOT NULL DEFAULT 0,
+                    first_seen TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (kind, name)
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 37: a hybrid logical clock stamp per message ("<wall ms>-<counter>-<node>",
+        // ordered as text), so two machines' logs merge in one causal order. Live saves stamp
+        // their own; every other insert, and the existing rows, get one from created_at with the
+        // message id as the tiebreak.
+        Migration {
+            version: 37,
+            description: "add_message_hlc",
+            sql: "ALTER TABLE messages ADD COLUMN hlc TEXT;
+                UPDATE messages SET hlc =
+                    printf('%013d-00000-', CAST(strftime('%s', created_at) AS INTEGER) * 1000)
+                    || COALESCE(message_id, id);
+                CREATE INDEX idx_messages_hlc ON messages(hlc);
+                CREATE TRIGGER messages_hlc_default AFTER INSERT ON messages
+                WHEN NEW.hlc IS NULL BEGIN
+                    UPDATE messages SET hlc =
+                        printf('%013d-00000-', CAST(strftime('%s', NEW.created_at) AS INTEGER) * 1000)
+                        || COALESCE(NEW.message_id, NEW.id)
+                    WHERE id = NEW.id;
+                END;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 38: host-enforced mutes. A muted user's posts are dropped by the host.
+        Migration {
+            version: 38,
+            description: "add_user_mutes",
+            sql: "ALTER TABLE users ADD COLUMN muted_at TIMESTAMP;
+                ALTER TABLE users ADD COLUMN mute_reason TEXT;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 39: per-room permissions: who may post, invite and pin ('members' or
+        // 'moderators'). Rooms without a row are open to their members.
+        Migration {
+            version: 39,
+            description: "add_room_permissions",
+            sql: "CREATE TABLE room_permissions (
+                    room_id INTEGER PRIMARY KEY REFERENCES chat_rooms(id) ON DELETE CASCADE,
+                    post TEXT NOT NULL DEFAULT 'members',
+                    invite TEXT NOT NULL DEFAULT 'members',
+                    pin TEXT NOT NULL DEFAULT 'members',
+                    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 40: an admin role. Admins see every department's private rooms and users.
+        Migration {
+            version: 40,
+            description: "add_user_admin",
+            sql: "ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT 0;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 41: machine translations of messages, one per target language. The source
+        // text is kept so an edited message is translated again.
+        Migration {
+            version: 41,
+            description: "add_translations",
+            sql: "CREATE TABLE translations (
+                    message_id TEXT NOT NULL,
+                    target_lang TEXT NOT NULL,
+                    source_text TEXT NOT NULL,
+                    source_lang TEXT,
+                    text TEXT NOT NULL,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (message_id, target_lang)
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 42: voice message transcripts, stored on the attachment and indexed for search
+        // in an external-content FTS5 table kept in step by triggers. Only attachments that have
+        // a transcript are in the index.
+        Migration {
+            version: 42,
+            description: "add_attachment_transcripts",
+            sql: "ALTER TABLE attachments ADD COLUMN transcript TEXT;
+                CREATE VIRTUAL TABLE attachments_fts USING fts5(
+                    transcript, content='attachments', content_rowid='id',
+                    tokenize='unicode61 remove_diacritics 2'
+                );
+                CREATE TRIGGER attachments_fts_update AFTER UPDATE OF transcript ON attachments
+                BEGIN
+                    INSERT INTO attachments_fts(attachments_fts, rowid, transcript)
+                        SELECT 'delete', old.id, old.transcript WHERE old.transcript IS NOT NULL;
+                    INSERT INTO attachments_fts(rowid, transcript)
+                        SELECT new.id, new.transcript WHERE new.transcript IS NOT NULL;
+                END;
+                CREATE TRIGGER attachments_fts_delete AFTER DELETE ON attachments
+                WHEN old.transcript IS NOT NULL BEGIN
+                    INSERT INTO attachments_fts(attachments_fts, rowid, transcript)
+                        VALUES ('delete', old.id, old.transcript);
+                END;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 43: text read from shared images (OCR). The attachment index is rebuilt with
+        // it as a second column, still holding only attachments with some text.
+        Migration {
+            version: 43,
+            description: "add_attachment_ocr_text",
+            sql: "ALTER TABLE attachments ADD COLUMN ocr_text TEXT;
+                DROP TRIGGER attachments_fts_update;
+                DROP TRIGGER attachments_fts_delete;
+                DROP TABLE attachments_fts;
+                CREATE VIRTUAL TABLE attachments_fts USING fts5(
+                    transcript, ocr_text, content='attachments', content_rowid='id',
+                    tokenize='unicode61 remove_diacritics 2'
+                );
+                CREATE TRIGGER attachments_fts_update
+                AFTER UPDATE OF transcript, ocr_text ON attachments BEGIN
+                    INSERT INTO attachments_fts(attachments_fts, rowid, transcript, ocr_text)
+                        SELECT 'delete', old.id, old.transcript, old.ocr_text
+                        WHERE old.transcript IS NOT NULL OR old.ocr_text IS NOT NULL;
+                    INSERT INTO attachments_fts(rowid, transcript, ocr_text)
+                        SELECT new.id, new.transcript, new.ocr_text
+                        WHERE new.transcript IS NOT NULL OR new.ocr_text IS NOT NULL;
+                END;
+                CREATE TRIGGER attachments_fts_delete AFTER DELETE ON attachments
+                WHEN old.transcript IS NOT NULL OR old.ocr_text IS NOT NULL BEGIN
+                    INSERT INTO attachments_fts(attachments_fts, rowid, transcript, ocr_text)
+                        VALUES ('delete', old.id, old.transcript, old.ocr_text);
+                END;
+                INSERT INTO attachments_fts(rowid, transcript, ocr_text)
+                    SELECT id, transcript, ocr_text FROM attachments
+                    WHERE transcript IS NOT NULL OR ocr_text IS NOT NULL;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 44: contact details for the user profile card. `timezone` is a UTC offset
+        // ("+02:00"); `avatar` a data: URL.
+        Migration {
+            version: 44,
+            description: "add_user_profile_fields",
+            sql: "ALTER TABLE users ADD COLUMN title TEXT;
+                ALTER TABLE users ADD COLUMN phone TEXT;
+                ALTER TABLE users ADD COLUMN avatar TEXT;
+                ALTER TABLE users ADD COLUMN timezone TEXT;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 45: a shared location's coordinates (Location messages; the label and
+        // coordinates are also in the message's JSON).
+        Migration {
+            version: 45,
+            description: "add_message_coordinates",
+            sql: "ALTER TABLE messages ADD COLUMN latitude REAL;
+                ALTER TABLE messages ADD COLUMN longitude REAL;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 46: rooms designated for compliance, whose announcements get read reports.
+        Migration {
+            version: 46,
+            description: "add_chat_rooms_compliance",
+            sql: "ALTER TABLE chat_rooms ADD COLUMN compliance BOOLEAN NOT NULL DEFAULT 0;",
+            kind: MigrationKind::Up,
+        },
+        // Migration 47: servers this machine has joined or favorited, for one-click reconnects.
+        // `address` is "host:port" as connected to.
+        Migration {
+            version: 47,
+            description: "create_known_servers",
+            sql: "CREATE TABLE known_servers (
+                address TEXT PRIMARY KEY,
+                name TEXT,
+                last_room TEXT,
+                last_room_id INTEGER,
+                favorite BOOLEAN NOT NULL DEFAULT 0,
+                last_connected_at TIMESTAMP
+            );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 48: named collections of bookmarked messages, kept on this machine like
+        // reminders (no FK to messages, so the room and an excerpt are stored with each one).
+        Migration {
+            version: 48,
+            description: "create_bookmark_collections",
+            sql: "CREATE TABLE bookmark_collections (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+                CREATE TABLE bookmarks (
+                    collection_id INTEGER NOT NULL
+                        REFERENCES bookmark_collections(id) ON DELETE CASCADE,
+                    message_id TEXT NOT NULL,
+                    room_id INTEGER NOT NULL,
+                    room TEXT NOT NULL,
+                    author TEXT NOT NULL,
+                    preview TEXT NOT NULL,
+                    added_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (collection_id, message_id)
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 49: per-room welcome messages, sent privately to each user on their first
+        // join; `room_welcomed` records who has had one.
+        Migration {
+            version: 49,
+            description: "create_room_welcomes",
+            sql: "CREATE TABLE room_welcomes (
+                    room_id INTEGER PRIMARY KEY REFERENCES chat_rooms(id) ON DELETE CASCADE,
+                    template TEXT NOT NULL,
+                    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+                );
+                CREATE TABLE room_welcomed (
+                    room_id INTEGER NOT NULL REFERENCES chat_rooms(id) ON DELETE CASCADE,
+                    user_id INTEGER NOT NULL,
+                    welcomed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                    PRIMARY KEY (room_id, user_id)
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 50: nonces of SSO ID tokens already exchanged for a session, so each token
+        // is single-use; rows are pruned once their token is too old to be accepted anyway.
+        Migration {
+            version: 50,
+            description: "create_sso_token_uses",
+            sql: "CREATE TABLE sso_token_uses (
+                    nonce TEXT PRIMARY KEY,
+                    issued_at INTEGER NOT NULL
+                );",
+            kind: MigrationKind::Up,
+        },
+        // Migration 51: how each session was opened, so a host requiring SSO only accepts
+        // sessions from an ID token exchange and not from a password login.
+        Migration {
+            version: 51,
+            description: "add_auth_session_origin",
+            sql: "ALTER TABLE auth_sessions ADD COLUMN origin TEXT NOT NULL DEFAULT 'password';",
+            kind: MigrationKind::Up,
+        },
         // Down for v7: remove default chat rooms created in v7
         Migration {
             version: 7,
@@ -0,0 +1,743 @@
+// Message flagging and the moderation queue (host-side).
+//
+// Anyone can flag a message with a reason (`flag_message`). A client's flag travels to the host
+// as a Flag frame and is recorded under the connection's user; the host's own flags are recorded
+// directly. Each new flag is announced to the host's UI (`message_flagged`) and as an OS
+// notification. The host reviews open flags with `get_flagged_messages` and settles them with
+// `resolve_flag`, optionally deleting the message for everyone (legal hold still applies). Every
+// flag, dismissal and removal is written to the audit log.
+//
+// Edits keep the text they replaced (`message_edits`, filled by a trigger), so
+// `get_message_edit_history` can show what a message said before. The host, as moderator, sees
+// any message's history; anyone else only their own.
+//
+// The host can also mute a user (`mute_user` / `unmute_user`). The host enforces it: a muted
+// user's Chat, Code and Sticker frames are dropped instead of relayed, and the sender gets a
+// Muted frame back naming the message that was dropped. Mutes and unmutes are audited too.
+//
+// Rooms and users are department-scoped: private rooms of another department, and that
+// department's people, aren't listed. The host can make a user an admin (`set_user_admin`,
+// audited), who sees every department.
+
+use crate::db_queries::record_audit_internal;
+use crate::error::{AppError, AppResult};
+use crate::server_events;
+use crate::sockets::{
+    distribute_message_to_all, edit_event, frame, send_error_notice, send_secure_client, AppState,
+    Message, MessageType,
+};
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::sync::Arc;
+use tauri::{Emitter, Runtime, State};
+use tauri_plugin_notification::NotificationExt;
+
+/// Longest flag or mute reason kept.
+const MAX_REASON_CHARS: usize = 500;
+
+/// An earlier version of an edited message.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MessageEdit {
+    pub user_id: Option<i64>,
+    pub author: Option<String>,
+    pub message: String,
+    // When this version was replaced.
+    pub edited_at: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FlaggedMessage {
+    pub id: i64,
+    pub message_id: String,
+    pub room_id: i64,
+    pub room: String,
+    pub author: Option<String>,
+    // Empty once the message has been deleted.
+    pub message: String,
+    pub reason: String,
+    pub reporter: Option<String>,
+    pub created_at: String,
+    // 'open', 'dismissed' or 'removed'.
+    pub status: String,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<String>,
+}
+
+// The flag listing query with `$tail` (a WHERE / ORDER BY clause) appended.
+macro_rules! flag_query {
+    ($tail:literal) => {
+        concat!(
+            "SELECT f.id, f.message_id, m.room_id, cr.name AS room, a.name AS author,
+                    m.message, f.reason, r.name AS reporter, f.created_at, f.status,
+                    rb.name AS resolved_by, f.resolved_at
+             FROM message_flags f
+             JOIN messages m ON m.message_id = f.message_id
+             JOIN chat_rooms cr ON cr.id = m.room_id
+             LEFT JOIN users a ON a.id = m.user_id
+             LEFT JOIN users r ON r.id = f.reporter_id
+             LEFT JOIN users rb ON rb.id = f.resolved_by ",
+            $tail
+        )
+    };
+}
+
+fn from_row(row: &sqlx::sqlite::SqliteRow) -> FlaggedMessage {
+    FlaggedMessage {
+        id: row.get("id"),
+        message_id: row.get("message_id"),
+        room_id: row.get("room_id"),
+        room: row.get("room"),
+        author: row.get("author"),
+        message: row.get("message"),
+        reason: row.get("reason"),
+        reporter: row.get("reporter"),
+        created_at: row.get("created_at"),
+        status: row.get("status"),
+        resolved_by: row.get("resolved_by"),
+        resolved_at: row.get("resolved_at"),
+    }
+}
+
+/// Record `reporter_id`'s flag on a message and audit it.
+pub async fn flag_message_internal(
+    pool: &SqlitePool,
+    message_id: &str,
+    reporter_id: i64,
+    reason: &str,
+) -> AppResult<FlaggedMessage> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(AppError::Validation(
+            "Say why you're flagging this message".to_string(),
+        ));
+    }
+    let reason: String = reason.chars().take(MAX_REASON_CHARS).collect();
+    let exists: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM messages WHERE message_id = $1 AND deleted_at IS NULL")
+            .bind(message_id)
+            .fetch_optional(pool)
+            .await?;
+    if exists.is_none() {
+        return Err(AppError::Validation(
+            "That message no longer exists".to_string(),
+        ));
+    }
+    let id: Option<i64> = sqlx::query_scalar(
+        "INSERT INTO message_flags (message_id, reporter_id, reason) VALUES ($1, $2, $3)
+         ON CONFLICT (message_id, reporter_id) DO NOTHING
+         RETURNING id",
+    )
+    .bind(message_id)
+    .bind(reporter_id)
+    .bind(&reason)
+    .fetch_optional(pool)
+    .await?;
+    let Some(id) = id else {
+        return Err(AppError::Conflict(
+            "You've already flagged this message".to_string(),
+        ));
+    };
+    record_audit_internal(
+        pool,
+        Some(reporter_id),
+        "flag.create",
+        message_id,
+        Some(&reason),
+    )
+    .await
+    .map_err(AppError::Db)?;
+    let row = sqlx::query(flag_query!("WHERE f.id = $1"))
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+    Ok(from_row(&row))
+}
+
+/// Open flags (oldest first), or every flag (newest first) with `include_resolved`.
+pub async fn get_flagged_messages_internal(
+    pool: &SqlitePool,
+    include_resolved: bool,
+) -> AppResult<Vec<FlaggedMessage>> {
+    let sql = if include_resolved {
+        flag_query!("ORDER BY f.id DESC LIMIT 500")
+    } else {
+        flag_query!("WHERE f.status = 'open' ORDER BY f.id")
+    };
+    let rows = sqlx::query(sql).fetch_all(pool).await?;
+    Ok(rows.iter().map(from_row).collect())
+}
+
+/// Settle every open flag on the flagged message: 'removed' after deleting it, else
+/// 'dismissed'. Returns the flag as it was (for the room to broadcast a removal to).
+pub async fn resolve_flag_internal(
+    pool: &SqlitePool,
+    flag_id: i64,
+    moderator_id: Option<i64>,
+    remove: bool,
+    note: Option<&str>,
+) -> AppResult<FlaggedMessage> {
+    let row = sqlx::query(flag_query!("WHERE f.id = $1"))
+        .bind(flag_id)
+        .fetch_optional(pool)
+        .await?;
+    let Some(flag) = row.as_ref().map(from_row) else {
+        return Err(AppError::Validation("That flag doesn't exist".to_string()));
+    };
+    if flag.status != "open" {
+        return Err(AppError::Conflict(
+            "That flag has already been resolved".to_string(),
+        ));
+    }
+    let mut tx = pool.begin().await?;
+    if remove {
+        let held: bool = sqlx::query_scalar("SELECT legal_hold FROM chat_rooms WHERE id = $1")
+            .bind(flag.room_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if held {
+            return Err(AppError::Validation(
+                "Messages in this room are under legal hold and can't be deleted".to_string(),
+            ));
+        }
+        sqlx::query(
+            "UPDATE messages
+                SET message = '', deleted_at = strftime('%Y-%m-%dT%H:%M:%fZ','now')
+              WHERE message_id = $1 AND deleted_at IS NULL",
+        )
+        .bind(&flag.message_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query(
+        "UPDATE message_flags
+            SET status = $1, resolved_by = $2, resolved_at = CURRENT_TIMESTAMP
+          WHERE message_id = $3 AND status = 'open'",
+    )
+    .bind(if remove { "removed" } else { "dismissed" })
+    .bind(moderator_id)
+    .bind(&flag.message_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    let action = if remove {
+        "flag.remove"
+    } else {
+        "flag.dismiss"
+    };
+    record_audit_internal(pool, moderator_id, action, &flag.message_id, note)
+        .await
+        .map_err(AppError::Db)?;
+    Ok(flag)
+}
+
+/// The earlier versions of a message, oldest first. `viewer` None is the moderator (any
+/// message); Some(user) only sees the history of their own messages.
+pub async fn get_message_edit_history_internal(
+    pool: &SqlitePool,
+    message_id: &str,
+    viewer: Option<i64>,
+) -> AppResult<Vec<MessageEdit>> {
+    let author: Option<i64> =
+        sqlx::query_scalar("SELECT user_id FROM messages WHERE message_id = $1")
+            .bind(message_id)
+            .fetch_optional(pool)
+            .await?;
+    let Some(author) = author else {
+        return Err(AppError::Validation(
+            "That message no longer exists".to_string(),
+        ));
+    };
+    if viewer.is_some_and(|v| v != author) {
+        return Err(AppError::Auth(
+            "Only the author and moderators can see a message's edits".to_string(),
+        ));
+    }
+    let rows = sqlx::query(
+        "SELECT e.user_id, u.name AS author, e.message, e.edited_at
+         FROM message_edits e LEFT JOIN users u ON u.id = e.user_id
+         WHERE e.message_id = $1
+         ORDER BY e.id",
+    )
+    .bind(message_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| MessageEdit {
+            user_id: row.get("user_id"),
+            author: row.get("author"),
+            message: row.get("message"),
+            edited_at: row.get("edited_at"),
+        })
+        .collect())
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MutedUser {
+    pub user_id: i64,
+    pub name: String,
+    pub muted_at: String,
+    pub reason: Option<String>,
+}
+
+/// Mute (or unmute) a user, recording who did it. Unmuting someone who isn't muted is a no-op.
+pub async fn set_muted_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    muted: bool,
+    moderator_id: Option<i64>,
+    reason: Option<&str>,
+) -> AppResult<()> {
+    if moderator_id == Some(user_id) {
+        return Err(AppError::Validation("You can't mute yourself".to_string()));
+    }
+    let reason = reason
+        .map(|r| r.trim().chars().take(MAX_REASON_CHARS).collect::<String>())
+        .filter(|r| !r.is_empty());
+    let name: Option<String> = if muted {
+        sqlx::query_scalar(
+            "UPDATE users SET muted_at = CURRENT_TIMESTAMP, mute_reason = $1
+              WHERE id = $2 RETURNING name",
+        )
+        .bind(&reason)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+    } else {
+        sqlx::query_scalar(
+            "UPDATE users SET muted_at = NULL, mute_reason = NULL
+              WHERE id = $1 AND muted_at IS NOT NULL RETURNING name",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+    };
+    let Some(name) = name else {
+        return if muted {
+            Err(AppError::Validation("That user doesn't exist".to_string()))
+        } else {
+            Ok(())
+        };
+    };
+    let action = if muted { "user.mute" } else { "user.unmute" };
+    record_audit_internal(pool, moderator_id, action, &name, reason.as_deref())
+        .await
+        .map_err(AppError::Db)
+}
+
+/// Muted users, most recently muted first.
+pub async fn get_muted_users_internal(pool: &SqlitePool) -> AppResult<Vec<MutedUser>> {
+    let rows = sqlx::query(
+        "SELECT id, name, muted_at, mute_reason FROM users
+          WHERE muted_at IS NOT NULL ORDER BY muted_at DESC, id",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| MutedUser {
+            user_id: row.get("id"),
+            name: row.get("name"),
+            muted_at: row.get("muted_at"),
+            reason: row.get("mute_reason"),
+        })
+        .collect())
+}
+
+/// Host: what to tell `user_id` when they try to post, if they're muted.
+pub(crate) async fn mute_notice(pool: &SqlitePool, user_id: u64) -> Option<String> {
+    let muted: Option<Option<String>> = match sqlx::query_scalar(
+        "SELECT mute_reason FROM users WHERE id = $1 AND muted_at IS NOT NULL",
+    )
+    .bind(user_id as i64)
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(muted) => muted,
+        Err(e) => {
+            tracing::warn!("Couldn't check mute for user {}: {}", user_id, e);
+            None
+        }
+    };
+    let reason = muted?;
+    Some(match reason {
+        Some(reason) => format!("You've been muted by the host: {}", reason),
+        None => "You've been muted by the host".to_string(),
+    })
+}
+
+/// Host: the Muted frame to answer a post from `user_id` with, if they're muted.
+pub(crate) async fn muted_reply(
+    pool: &SqlitePool,
+    user_id: u64,
+    message: &Message,
+) -> Option<Message> {
+    let text = mute_notice(pool, user_id).await?;
+    Some(Message {
+        message_id: message.message_id.clone(),
+        room: message.room.clone(),
+        room_id: message.room_id,
+        ..frame(MessageType::Muted, user_id, text)
+    })
+}
+
+/// Tell the host's UI (and the OS) about a new flag.
+fn notify_host<R: Runtime>(app: &tauri::AppHandle<R>, flag: &FlaggedMessage) {
+    if let Err(e) = app.emit("message_flagged", flag) {
+        tracing::warn!("Failed to emit flag: {}", e);
+    }
+    let reporter = flag.reporter.as_deref().unwrap_or("Someone");
+    if let Err(e) = app
+        .notification()
+        .builder()
+        .title(format!("Message flagged in #{}", flag.room))
+        .body(format!("{}: {}", reporter, flag.reason))
+        .show()
+    {
+        tracing::warn!("Failed to show flag notification: {}", e);
+    }
+}
+
+/// Host: a client's Flag frame (`message_id` = the target, `message` = the reason), recorded
+/// under the connection's user.
+pub(crate) async fn handle_client_frame<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    auth_user_id: u64,
+    message: &Message,
+) {
+    match flag_message_internal(
+        pool,
+        &message.message_id,
+        auth_user_id as i64,
+        &message.message,
+    )
+    .await
+    {
+        Ok(flag) => notify_host(app, &flag),
+        Err(e) => send_error_notice(state, auth_user_id, &e.to_string()).await,
+    }
+}
+
+/// Report a message to the host's moderators.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn flag_message(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    message_id: String,
+    reason: String,
+) -> AppResult<()> {
+    if *state.is_server.read().await {
+        let Some(reporter) = *state.user_id.read().await else {
+            return Err(AppError::Auth("Sign in to flag messages".to_string()));
+        };
+        let flag = flag_message_internal(&db, &message_id, reporter as i64, &reason).await?;
+        notify_host(&app, &flag);
+        Ok(())
+    } else {
+        if reason.trim().is_empty() {
+            return Err(AppError::Validation(
+                "Say why you're flagging this message".to_string(),
+            ));
+        }
+        let user_id = state.user_id.read().await.unwrap_or(0);
+        let username = state.username.read().await.clone();
+        let frame = edit_event(
+            username,
+            user_id,
+            message_id,
+            reason,
+            String::new(),
+            0,
+            MessageType::Flag,
+        );
+        send_secure_client(state.inner(), &frame)
+            .await
+            .map_err(AppError::Network)
+    }
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_flagged_messages(
+    db: State<'_, SqlitePool>,
+    include_resolved: Option<bool>,
+) -> AppResult<Vec<FlaggedMessage>> {
+    get_flagged_messages_internal(&db, include_resolved.unwrap_or(false)).await
+}
+
+/// What a message said before each edit. The host sees any message's history; a signed-in user
+/// their own.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_message_edit_history(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    message_id: String,
+) -> AppResult<Vec<MessageEdit>> {
+    let viewer = if *state.is_server.read().await {
+        None
+    } else {
+        match *state.user_id.read().await {
+            Some(id) => Some(id as i64),
+            None => return Err(AppError::Auth("Sign in to see edit history".to_string())),
+        }
+    };
+    get_message_edit_history_internal(&db, &message_id, viewer).await
+}
+
+/// Settle a flag. With `delete_message` the message is deleted for everyone in its room.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn resolve_flag(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    flag_id: i64,
+    delete_message: bool,
+    note: Option<String>,
+) -> AppResult<()> {
+    let moderator = state.user_id.read().await.map(|id| id as i64);
+    let note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    let flag =
+        resolve_flag_internal(&db, flag_id, moderator, delete_message, note.as_deref()).await?;
+    if delete_message {
+        let del = edit_event(
+            state.username.read().await.clone(),
+            moderator.unwrap_or(0) as u64,
+            flag.message_id,
+            String::new(),
+            flag.room.clone(),
+            flag.room_id as u64,
+            MessageType::Delete,
+        );
+        distribute_message_to_all(&app, state.inner(), &flag.room, &del, None).await;
+    }
+    Ok(())
+}
+
+/// Stop a user's messages from reaching anyone, until unmuted.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn mute_user(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+    reason: Option<String>,
+) -> AppResult<()> {
+    let moderator = state.user_id.read().await.map(|id| id as i64);
+    set_muted_internal(&db, user_id, true, moderator, reason.as_deref()).await?;
+    server_events::emit_for_user(&app, &db, server_events::MUTE, Some(user_id), None, reason).await;
+    Ok(())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn unmute_user(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+) -> AppResult<()> {
+    let moderator = state.user_id.read().await.map(|id| id as i64);
+    set_muted_internal(&db, user_id, false, moderator, None).await?;
+    server_events::emit_for_user(&app, &db, server_events::UNMUTE, Some(user_id), None, None).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_muted_users(db: State<'_, SqlitePool>) -> AppResult<Vec<MutedUser>> {
+    get_muted_users_internal(&db).await
+}
+
+/// Grant or revoke the admin role, which lifts the department scoping on rooms and users.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_user_admin(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+    admin: bool,
+) -> AppResult<()> {
+    let actor = state.user_id.read().await.map(|id| id as i64);
+    set_user_admin_internal(&db, user_id, admin, actor).await
+}
+
+pub async fn set_user_admin_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    admin: bool,
+    actor: Option<i64>,
+) -> AppResult<()> {
+    let name: Option<String> =
+        sqlx::query_scalar("UPDATE users SET is_admin = $1 WHERE id = $2 RETURNING name")
+            .bind(admin)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+    let Some(name) = name else {
+        return Err(AppError::Validation("That user doesn't exist".to_string()));
+    };
+    let action = if admin { "user.admin" } else { "user.unadmin" };
+    record_audit_internal(pool, actor, action, &name, None)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_queries::{delete_message_db, edit_message_db, save_message_internal};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        save_message_internal(
+            &pool,
+            1,
+            2,
+            "spam".into(),
+            "Chat".into(),
+            false,
+            "m1".into(),
+        )
+        .await
+        .unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn flags_are_queued_resolved_and_audited() {
+        let pool = setup().await;
+        let flag = flag_message_internal(&pool, "m1", 1, " spam ")
+            .await
+            .unwrap();
+        assert_eq!(flag.reason, "spam");
+        assert_eq!(flag.author.as_deref(), Some("Bob"));
+        assert!(matches!(
+            flag_message_internal(&pool, "m1", 1, "again").await,
+            Err(AppError::Conflict(_))
+        ));
+        assert!(flag_message_internal(&pool, "nope", 1, "x").await.is_err());
+        assert_eq!(
+            get_flagged_messages_internal(&pool, false).await.unwrap(),
+            vec![flag.clone()]
+        );
+
+        resolve_flag_internal(&pool, flag.id, Some(1), true, Some("off-topic"))
+            .await
+            .unwrap();
+        assert!(get_flagged_messages_internal(&pool, false)
+            .await
+            .unwrap()
+            .is_empty());
+        let all = get_flagged_messages_internal(&pool, true).await.unwrap();
+        assert_eq!(all[0].status, "removed");
+        assert_eq!(all[0].message, "");
+        assert!(resolve_flag_internal(&pool, flag.id, Some(1), false, None)
+            .await
+            .is_err());
+
+        let actions: Vec<String> = sqlx::query_scalar("SELECT action FROM audit_log ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(actions, ["flag.create", "flag.remove"]);
+    }
+
+    #[tokio::test]
+    async fn muted_users_posts_are_refused_and_audited() {
+        let pool = setup().await;
+        let post = edit_event(
+            "Bob".into(),
+            2,
+            "m2".into(),
+            "more spam".into(),
+            "general".into(),
+            1,
+            MessageType::Chat,
+        );
+        assert!(muted_reply(&pool, 2, &post).await.is_none());
+
+        set_muted_internal(&pool, 2, true, Some(1), Some(" spamming "))
+            .await
+            .unwrap();
+        let reply = muted_reply(&pool, 2, &post).await.unwrap();
+        assert_eq!(reply.message_type, MessageType::Muted);
+        assert_eq!(reply.message_id, "m2");
+        assert_eq!(reply.message, "You've been muted by the host: spamming");
+        let muted = get_muted_users_internal(&pool).await.unwrap();
+        assert_eq!(muted.len(), 1);
+        assert_eq!(muted[0].name, "Bob");
+        assert_eq!(muted[0].reason.as_deref(), Some("spamming"));
+        assert!(set_muted_internal(&pool, 1, true, Some(1), None)
+            .await
+            .is_err());
+        assert!(set_muted_internal(&pool, 99, true, Some(1), None)
+            .await
+            .is_err());
+
+        set_muted_internal(&pool, 2, false, Some(1), None)
+            .await
+            .unwrap();
+        // Unmuting again changes nothing and isn't audited twice.
+        set_muted_internal(&pool, 2, false, Some(1), None)
+            .await
+            .unwrap();
+        assert!(muted_reply(&pool, 2, &post).await.is_none());
+
+        let audit: Vec<(String, String)> =
+            sqlx::query_as("SELECT action, target FROM audit_log ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            audit,
+            [
+                ("user.mute".to_string(), "Bob".to_string()),
+                ("user.unmute".to_string(), "Bob".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn edits_keep_earlier_versions_until_deleted() {
+        let pool = setup().await;
+        edit_message_db(&pool, "m1", "spam, edited", 2)
+            .await
+            .unwrap();
+        edit_message_db(&pool, "m1", "hello", 2).await.unwrap();
+
+        let history = get_message_edit_history_internal(&pool, "m1", None)
+            .await
+            .unwrap();
+        let texts: Vec<_> = history.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(texts, ["spam", "spam, edited"]);
+        assert_eq!(history[0].author.as_deref(), Some("Bob"));
+        assert_eq!(
+            get_message_edit_history_internal(&pool, "m1", Some(2))
+                .await
+                .unwrap(),
+            history
+        );
+        assert!(matches!(
+            get_message_edit_history_internal(&pool, "m1", Some(1)).await,
+            Err(AppError::Auth(_))
+        ));
+
+        delete_message_db(&pool, "m1", 2).await.unwrap();
+        assert!(get_message_edit_history_internal(&pool, "m1", None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,339 @@
+// MQTT ingest (optional, host-side, off by default).
+//
+// Subscribes to configured topics on a LAN broker and turns each publish into a chat message in
+// a designated room ("Alerts" by default), posted by an "Alerts" bot user. Payloads may be plain
+// text or JSON; JSON `text` / `message` plus optional `title` and `severity` are formatted into
+// one line, anything else is shown as-is. Ingest is rate-limited like a client connection so a
+// chatty sensor can't flood the room.
+//
+// The config is JSON in `app_settings`; the broker password lives in the OS keychain.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal, upsert_user_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::{
+    frame, publish_host_message, AppState, Message, MessageType, RateLimiter, MAX_MESSAGE_CHARS,
+};
+use rumqttc::{
+    AsyncClient, Event, MqttOptions, Packet, QoS, SubscribeFilter, TlsConfiguration, Transport,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+const SETTINGS_KEY: &str = "mqtt_config";
+const KEYRING_MQTT_USER: &str = "mqtt-password-v1";
+
+// The bot user alerts are attributed to. `.invalid` can't collide with a real address.
+const BOT_NAME: &str = "Alerts";
+const BOT_EMAIL: &str = "mqtt-bridge@alerts.invalid";
+
+/// Pause before polling again after a broker error (the poll itself reconnects).
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+fn default_port() -> u16 {
+    1883
+}
+
+fn default_client_id() -> String {
+    "nutler".to_string()
+}
+
+fn default_topics() -> Vec<String> {
+    vec!["alerts/#".to_string()]
+}
+
+fn default_room() -> String {
+    "Alerts".to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    // TLS with the OS trust store (usually port 8883).
+    #[serde(default)]
+    pub tls: bool,
+    // Empty = anonymous.
+    #[serde(default)]
+    pub username: String,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    // Topic filters; `+` / `#` wildcards allowed.
+    #[serde(default = "default_topics")]
+    pub topics: Vec<String>,
+    // Room the messages land in; created (public) if missing.
+    #[serde(default = "default_room")]
+    pub room: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: default_port(),
+            tls: false,
+            username: String::new(),
+            client_id: default_client_id(),
+            topics: default_topics(),
+            room: default_room(),
+        }
+    }
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<MqttConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt MQTT config: {}", e))),
+        None => Ok(MqttConfig::default()),
+    }
+}
+
+fn keyring_entry() -> AppResult<keyring::Entry> {
+    keyring::Entry::new(crate::db::KEYRING_SERVICE, KEYRING_MQTT_USER)
+        .map_err(|e| AppError::Internal(format!("Keychain unavailable: {}", e)))
+}
+
+/// Turn one publish into message text, or None for an empty / non-UTF-8 payload.
+fn payload_to_text(topic: &str, payload: &[u8]) -> Option<String> {
+    let raw = std::str::from_utf8(payload).ok()?.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    let body = match serde_json::from_str::<Value>(raw) {
+        Ok(Value::Object(obj)) => {
+            let field = |keys: &[&str]| {
+                keys.iter()
+                    .find_map(|k| obj.get(*k).and_then(Value::as_str))
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+            };
+            match field(&["text", "message", "msg", "summary"]) {
+                Some(text) => {
+                    let mut line = String::new();
+                    if let Some(level) = field(&["severity", "level"]) {
+                        line.push_str(&format!("[{}] ", level.to_uppercase()));
+                    }
+                    if let Some(title) = field(&["title"]) {
+                        line.push_str(&format!("{}: ", title));
+                    }
+                    line.push_str(text);
+                    line
+                }
+                None => raw.to_string(),
+            }
+        }
+        _ => raw.to_string(),
+    };
+    let text = format!("{} · {}", topic, body);
+    if text.chars().count() <= MAX_MESSAGE_CHARS {
+        Some(text)
+    } else {
+        let cut: String = text.chars().take(MAX_MESSAGE_CHARS - 1).collect();
+        Some(format!("{}…", cut))
+    }
+}
+
+/// The bot user and target room, created if missing. Returns (user_id, room_id).
+async fn ensure_target(pool: &SqlitePool, room: &str) -> Result<(i64, i64), String> {
+    let bot = upsert_user_internal(pool, BOT_NAME.to_string(), BOT_EMAIL.to_string(), None)
+        .await?
+        .id
+        .ok_or("Alerts user has no id")?;
+    sqlx::query(
+        "INSERT INTO chat_rooms (name, description, is_private, created_by) VALUES ($1, $2, 0, $3)
+         ON CONFLICT(name) DO NOTHING",
+    )
+    .bind(room)
+    .bind("Machine alerts (MQTT)")
+    .bind(bot)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to create alerts room: {}", e))?;
+    let room_id: i64 = sqlx::query_scalar("SELECT id FROM chat_rooms WHERE name = $1")
+        .bind(room)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| format!("Failed to find alerts room: {}", e))?;
+    Ok((bot, room_id))
+}
+
+/// Connect, subscribe and forward publishes until aborted. Broker errors are logged and retried.
+async fn run_subscriber(
+    app: tauri::AppHandle,
+    state: Arc<AppState>,
+    pool: SqlitePool,
+    config: MqttConfig,
+    password: Option<String>,
+) {
+    let (bot_id, room_id) = match ensure_target(&pool, &config.room).await {
+        Ok(t) => t,
+        Err(e) => {
+            tracing::error!("MQTT ingest disabled: {}", e);
+            return;
+        }
+    };
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if !config.username.is_empty() {
+        options.set_credentials(&config.username, password.unwrap_or_default());
+    }
+    if config.tls {
+        options.set_transport(Transport::tls_with_config(TlsConfiguration::Native));
+    }
+    let (client, mut eventloop) = AsyncClient::new(options, 64);
+    let filters: Vec<SubscribeFilter> = config
+        .topics
+        .iter()
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .map(|t| SubscribeFilter::new(t.to_string(), QoS::AtLeastOnce))
+        .collect();
+    let mut limiter = RateLimiter::new(tokio::time::Instant::now());
+    let mut dropped = 0u64;
+
+    loop {
+        match eventloop.poll().await {
+            // Clean sessions forget subscriptions, so (re-)subscribe on every connect.
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                tracing::info!("📡 MQTT connected to {}:{}", config.host, config.port);
+                if let Err(e) = client.subscribe_many(filters.clone()).await {
+                    tracing::warn!("MQTT subscribe failed: {}", e);
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                if !limiter.allow(tokio::time::Instant::now()) {
+                    dropped += 1;
+                    if dropped.is_power_of_two() {
+                        tracing::warn!("MQTT ingest rate-limited: {} messages dropped", dropped);
+                    }
+                    continue;
+                }
+                let Some(text) = payload_to_text(&publish.topic, &publish.payload) else {
+                    continue;
+                };
+                let message = Message {
+                    username: BOT_NAME.to_string(),
+                    room_id: room_id as u64,
+                    room: config.room.clone(),
+                    ..frame(MessageType::Chat, bot_id as u64, text)
+                };
+                publish_host_message(&app, &state, &pool, message).await;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!("MQTT connection error: {}", e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+/// (Re)start the subscriber from the saved config, stopping any running one first.
+pub async fn restart(app: &tauri::AppHandle, state: &Arc<AppState>, pool: &SqlitePool) {
+    if let Some(old) = state.mqtt_subscriber.lock().await.take() {
+        old.abort();
+    }
+    let config = match load_config(pool).await {
+        Ok(c) if c.enabled => c,
+        Ok(_) => return,
+        Err(e) => {
+            tracing::warn!("MQTT ingest not started: {}", e);
+            return;
+        }
+    };
+    let password = keyring_entry().ok().and_then(|e| e.get_password().ok());
+    let handle = tauri::async_runtime::spawn(run_subscriber(
+        app.clone(),
+        Arc::clone(state),
+        pool.clone(),
+        config,
+        password,
+    ));
+    *state.mqtt_subscriber.lock().await = Some(handle);
+}
+
+#[tauri::command]
+pub async fn get_mqtt_config(db: State<'_, SqlitePool>) -> AppResult<MqttConfig> {
+    load_config(&db).await
+}
+
+/// Save the MQTT settings and restart the subscriber. `password`, when given, goes to the OS
+/// keychain.
+#[tauri::command]
+pub async fn set_mqtt_config(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    config: MqttConfig,
+    password: Option<String>,
+) -> AppResult<()> {
+    if config.enabled {
+        if config.host.trim().is_empty() {
+            return Err(AppError::Validation(
+                "A broker host is required".to_string(),
+            ));
+        }
+        if config.topics.iter().all(|t| t.trim().is_empty()) {
+            return Err(AppError::Validation(
+                "At least one topic is required".to_string(),
+            ));
+        }
+        if config.room.trim().is_empty() {
+            return Err(AppError::Validation(
+                "A target room is required".to_string(),
+            ));
+        }
+    }
+    if let Some(password) = password {
+        keyring_entry()?
+            .set_password(&password)
+            .map_err(|e| AppError::Internal(format!("Failed to save MQTT password: {}", e)))?;
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    restart(&app, state.inner(), &db).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_payloads_are_formatted() {
+        assert_eq!(
+            payload_to_text(
+                "alerts/line3",
+                br#"{"severity": "crit", "title": "Press 4", "message": "hydraulic pressure low"}"#
+            )
+            .unwrap(),
+            "alerts/line3 · [CRIT] Press 4: hydraulic pressure low"
+        );
+        // JSON without a text field is shown verbatim.
+        assert_eq!(
+            payload_to_text("sensors/t1", br#"{"temp": 71.5}"#).unwrap(),
+            r#"sensors/t1 · {"temp": 71.5}"#
+        );
+    }
+
+    #[test]
+    fn plain_and_empty_payloads() {
+        assert_eq!(
+            payload_to_text("alerts/door", b" door 2 open \n").unwrap(),
+            "alerts/door · door 2 open"
+        );
+        assert!(payload_to_text("alerts/door", b"   ").is_none());
+        assert!(payload_to_text("alerts/door", &[0xff, 0xfe]).is_none());
+    }
+}
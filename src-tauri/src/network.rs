@@ -0,0 +1,151 @@
+// Network interfaces on this machine, for choosing where the host listens.
+//
+// `list_network_interfaces` groups the OS's interface addresses by NIC. A host that binds one
+// address (instead of every interface via 0.0.0.0) also gets that address in its invite links.
+
+use crate::error::{AppError, AppResult};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub ipv4: Vec<String>,
+    pub ipv6: Vec<String>,
+    pub is_up: bool,
+    pub is_loopback: bool,
+}
+
+/// Group `(interface, address)` pairs by interface, in name order. `names` adds interfaces that
+/// have no address at all (they're still worth listing, usually as down).
+fn group(
+    addrs: impl IntoIterator<Item = (String, IpAddr)>,
+    names: impl IntoIterator<Item = String>,
+    is_up: impl Fn(&str, bool) -> bool,
+) -> Vec<NetworkInterface> {
+    let mut by_name: BTreeMap<String, Vec<IpAddr>> = BTreeMap::new();
+    for name in names {
+        by_name.entry(name).or_default();
+    }
+    for (name, ip) in addrs {
+        by_name.entry(name).or_default().push(ip);
+    }
+    by_name
+        .into_iter()
+        .map(|(name, ips)| {
+            let has_addr = !ips.is_empty();
+            NetworkInterface {
+                is_up: is_up(&name, has_addr),
+                is_loopback: has_addr && ips.iter().all(|ip| ip.is_loopback()),
+                ipv4: ips
+                    .iter()
+                    .filter(|ip| ip.is_ipv4())
+                    .map(IpAddr::to_string)
+                    .collect(),
+                ipv6: ips
+                    .iter()
+                    .filter(|ip| ip.is_ipv6())
+                    .map(IpAddr::to_string)
+                    .collect(),
+                name,
+            }
+        })
+        .collect()
+}
+
+/// Linux reports each NIC's operational state in sysfs ("unknown" for loopback and most
+/// tunnels, which are up whenever they carry an address).
+#[cfg(target_os = "linux")]
+fn is_up(name: &str, has_addr: bool) -> bool {
+    match std::fs::read_to_string(format!("/sys/class/net/{}/operstate", name)) {
+        Ok(state) => match state.trim() {
+            "up" => true,
+            "unknown" => has_addr,
+            _ => false,
+        },
+        Err(_) => has_addr,
+    }
+}
+
+/// Elsewhere the OS only lists addresses that are usable, so having one means up.
+#[cfg(not(target_os = "linux"))]
+fn is_up(_name: &str, has_addr: bool) -> bool {
+    has_addr
+}
+
+/// Interfaces without an address (only discoverable on Linux).
+fn bare_interface_names() -> Vec<String> {
+    std::fs::read_dir("/sys/class/net")
+        .map(|dir| {
+            dir.filter_map(|e| e.ok()?.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn list_network_interfaces_internal() -> AppResult<Vec<NetworkInterface>> {
+    let addrs = if_addrs::get_if_addrs()
+        .map_err(|e| AppError::Network(format!("Couldn't list network interfaces: {}", e)))?;
+    Ok(group(
+        addrs.into_iter().map(|i| {
+            let ip = i.ip();
+            (i.name, ip)
+        }),
+        bare_interface_names(),
+        is_up,
+    ))
+}
+
+/// Check a bind address picked from `list_network_interfaces` (None / empty: every interface).
+pub fn parse_bind_address(address: Option<&str>) -> AppResult<IpAddr> {
+    match address.map(str::trim).filter(|a| !a.is_empty()) {
+        None => Ok(IpAddr::from([0, 0, 0, 0])),
+        Some(a) => a
+            .parse()
+            .map_err(|_| AppError::Validation(format!("\"{}\" isn't an IP address", a))),
+    }
+}
+
+/// Each network interface with its addresses and whether it's up.
+#[tauri::command]
+pub async fn list_network_interfaces() -> AppResult<Vec<NetworkInterface>> {
+    list_network_interfaces_internal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interfaces_are_grouped_by_name() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let list = group(
+            [
+                ("eth0".to_string(), ip("192.168.1.20")),
+                ("lo".to_string(), ip("127.0.0.1")),
+                ("eth0".to_string(), ip("fe80::1")),
+                ("lo".to_string(), ip("::1")),
+            ],
+            ["wlan0".to_string(), "eth0".to_string()],
+            |_, has_addr| has_addr,
+        );
+        let names: Vec<_> = list.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["eth0", "lo", "wlan0"]);
+        assert_eq!(list[0].ipv4, ["192.168.1.20"]);
+        assert_eq!(list[0].ipv6, ["fe80::1"]);
+        assert!(list[0].is_up && !list[0].is_loopback);
+        assert!(list[1].is_loopback);
+        assert!(!list[2].is_up && !list[2].is_loopback && list[2].ipv4.is_empty());
+
+        assert_eq!(
+            parse_bind_address(None).unwrap(),
+            IpAddr::from([0, 0, 0, 0])
+        );
+        assert_eq!(
+            parse_bind_address(Some(" 10.0.0.5 ")).unwrap(),
+            ip("10.0.0.5")
+        );
+        assert!(parse_bind_address(Some("eth0")).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+// Text recognition in shared images (optional, host-side, off by default).
+//
+// When an image attachment is filed, the host runs an OCR program on it in the background and
+// stores the text it finds on the `attachments` row, so a screenshot of an error message can be
+// found later: `search_messages` matches it, and it's indexed in `attachments_fts` beside voice
+// message transcripts for `search_all`. The program is run as `<command> [args…]`, with `{file}`
+// in the args replaced by the image's path (or the path added last if no arg has it), and prints
+// the text; for Tesseract that's `tesseract` with `["{file}", "stdout"]`.
+//
+// The settings live in `app_settings`.
+
+use crate::attachments::AttachmentRef;
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+const SETTINGS_KEY: &str = "ocr";
+
+/// An OCR run still going after this has failed.
+const OCR_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+/// Stands for the image's path in the configured args.
+const FILE_PLACEHOLDER: &str = "{file}";
+
+/// Image types worth reading (not SVG: its text is already text).
+const OCR_MIMES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct OcrConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Path to the OCR program, e.g. `tesseract`.
+    #[serde(default)]
+    pub command: Option<String>,
+    // e.g. ["{file}", "stdout", "-l", "eng+deu"].
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<OcrConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt OCR settings: {}", e))),
+        None => Ok(OcrConfig::default()),
+    }
+}
+
+/// The program's arguments for `path`.
+fn command_args(args: &[String], path: &Path) -> Vec<OsString> {
+    let mut out: Vec<OsString> = args
+        .iter()
+        .map(|a| {
+            if a == FILE_PLACEHOLDER {
+                path.as_os_str().to_owned()
+            } else {
+                OsString::from(a)
+            }
+        })
+        .collect();
+    if !args.iter().any(|a| a == FILE_PLACEHOLDER) {
+        out.push(path.as_os_str().to_owned());
+    }
+    out
+}
+
+/// OCR output cut down to its words: whitespace runs (page breaks, layout gaps) become one space.
+fn clean_text(raw: &str) -> String {
+    raw.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+async fn recognize(command: &str, args: &[String], path: &Path) -> AppResult<String> {
+    let run = tokio::process::Command::new(command)
+        .args(command_args(args, path))
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(OCR_TIMEOUT, run).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return Err(AppError::Internal(format!(
+                "The OCR program couldn't run: {}",
+                e
+            )))
+        }
+        Err(_) => return Err(AppError::Internal("The OCR program timed out".to_string())),
+    };
+    if !output.status.success() {
+        return Err(AppError::Internal(format!(
+            "The OCR program failed (exit status {})",
+            output
+                .status
+                .code()
+                .map_or("none".to_string(), |c| c.to_string())
+        )));
+    }
+    Ok(clean_text(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Store the text found in attachment `id`; the index follows by trigger.
+pub(crate) async fn store_text(pool: &SqlitePool, id: i64, text: &str) -> AppResult<()> {
+    sqlx::query("UPDATE attachments SET ocr_text = $1 WHERE id = $2")
+        .bind(text)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+async fn read_image(pool: &SqlitePool, path: PathBuf, attachment: &AttachmentRef) -> AppResult<()> {
+    let config = load_config(pool).await?;
+    let Some(command) = config
+        .command
+        .as_deref()
+        .filter(|c| config.enabled && !c.trim().is_empty())
+    else {
+        return Ok(());
+    };
+    let text = recognize(command, &config.args, &path).await?;
+    if text.is_empty() {
+        return Ok(());
+    }
+    store_text(pool, attachment.id, &text).await?;
+    tracing::info!("Read the text in {}", attachment.filename);
+    Ok(())
+}
+
+/// Host: read the text in a just-filed image in the background, when OCR is on.
+pub(crate) fn after_upload(state: &Arc<AppState>, pool: &SqlitePool, attachment: &AttachmentRef) {
+    if !OCR_MIMES.contains(&attachment.mime.as_str()) {
+        return;
+    }
+    let Some(dir) = state.attachments_dir.get() else {
+        return;
+    };
+    let path = dir.join(attachment.id.to_string());
+    let pool = pool.clone();
+    let attachment = attachment.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = read_image(&pool, path, &attachment).await {
+            tracing::warn!("Couldn't read the text in {}: {}", attachment.filename, e);
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_ocr_config(db: State<'_, SqlitePool>) -> AppResult<OcrConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_ocr_config(db: State<'_, SqlitePool>, config: OcrConfig) -> AppResult<()> {
+    if config.enabled {
+        let command = config.command.as_deref().unwrap_or_default();
+        if !Path::new(command).is_file() {
+            return Err(AppError::Validation(format!(
+                "No OCR program at {}",
+                command
+            )));
+        }
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_image_path_goes_where_the_args_say() {
+        let path = Path::new("/store/7");
+        let args = vec!["{file}".to_string(), "stdout".to_string()];
+        assert_eq!(command_args(&args, path), vec!["/store/7", "stdout"]);
+        let args = vec!["--json".to_string()];
+        assert_eq!(command_args(&args, path), vec!["--json", "/store/7"]);
+    }
+
+    #[test]
+    fn ocr_layout_is_flattened() {
+        assert_eq!(
+            clean_text("Error: connection\n  refused\n\n\u{c}"),
+            "Error: connection refused"
+        );
+        assert_eq!(clean_text(" \n\u{c}"), "");
+    }
+}
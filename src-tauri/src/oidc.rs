@@ -0,0 +1,509 @@
+// OIDC / SSO login (optional, off by default).
+//
+// `oidc_login` runs the OAuth2 authorization-code flow with PKCE against the organization's
+// OpenID provider (Google Workspace, Azure AD, …): it opens the system browser, catches the
+// redirect on a one-shot loopback listener (http://127.0.0.1:<random port>/callback), exchanges
+// the code, verifies the ID token's signature/issuer/audience/nonce, and upserts the verified
+// email + name as the local user — instead of trusting whatever identity was typed in.
+//
+// Hosts can also require SSO on connect. Right after signing in, the client exchanges its ID
+// token for a host session (`client_sso_login`, an `SsoLogin` frame): the host verifies it (same
+// provider + client id, issued for that email), only accepts it within a few minutes of issue,
+// and spends its nonce so it can't be exchanged twice. The Connect `credential` is then the
+// session token, as with password accounts; ID tokens are held in memory only, never stored.
+
+use crate::accounts::{
+    open_session_internal, remote_auth, session_owned_by, LoginResult, SessionOrigin,
+};
+use crate::db_queries::{
+    get_setting_internal, normalize_email, set_setting_internal, upsert_user_internal, User,
+};
+use crate::error::{AppError, AppResult};
+use crate::sockets::{now_secs, MessageType};
+use openidconnect::core::{CoreClient, CoreIdToken, CoreProviderMetadata, CoreResponseType};
+use openidconnect::url::Url;
+use openidconnect::{
+    reqwest, AuthenticationFlow, AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl,
+    Nonce, PkceCodeChallenge, RedirectUrl, Scope,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tauri::State;
+use tauri_plugin_opener::OpenerExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const SETTINGS_KEY: &str = "oidc_config";
+const KEYRING_CLIENT_SECRET_USER: &str = "oidc-client-secret-v1";
+
+/// How long the loopback listener waits for the browser to come back.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Provider metadata (incl. signing keys) is cached this long on the host, so a burst of
+/// reconnects doesn't re-fetch discovery + JWKS for every Connect.
+const METADATA_TTL: Duration = Duration::from_secs(3600);
+
+/// A host only exchanges ID tokens issued this recently (either side of its clock): the client
+/// presents one right after signing in, so an older one is most likely a captured copy.
+const TOKEN_MAX_AGE_SECS: i64 = 300;
+
+const CALLBACK_HTML: &str = "<!doctype html><html><body style=\"font-family:sans-serif\">\
+<h3>Signed in to Nutler</h3><p>You can close this window and return to the app.</p></body></html>";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct OidcConfig {
+    pub enabled: bool,
+    // e.g. https://accounts.google.com or https://login.microsoftonline.com/<tenant>/v2.0
+    pub issuer_url: String,
+    pub client_id: String,
+    // Host side: reject Connects that don't carry a live SSO session for the registering email.
+    #[serde(default)]
+    pub require_on_connect: bool,
+}
+
+/// Result of a successful SSO login: the verified local user, plus the raw ID token to exchange
+/// for a session (`client_sso_login`) on a host that requires SSO.
+#[derive(Serialize)]
+pub struct SsoLogin {
+    pub user: User,
+    pub id_token: String,
+}
+
+static METADATA_CACHE: tokio::sync::Mutex<Option<(String, CoreProviderMetadata, Instant)>> =
+    tokio::sync::Mutex::const_new(None);
+
+async fn load_config(pool: &SqlitePool) -> AppResult<OidcConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt SSO config: {}", e))),
+        None => Ok(OidcConfig::default()),
+    }
+}
+
+fn keyring_entry() -> AppResult<keyring::Entry> {
+    keyring::Entry::new(crate::db::KEYRING_SERVICE, KEYRING_CLIENT_SECRET_USER)
+        .map_err(|e| AppError::Internal(format!("Keychain unavailable: {}", e)))
+}
+
+/// Desktop (public) clients usually have no secret; some providers issue one anyway.
+fn client_secret() -> Option<ClientSecret> {
+    keyring_entry()
+        .ok()?
+        .get_password()
+        .ok()
+        .map(ClientSecret::new)
+}
+
+fn http_client() -> AppResult<reqwest::Client> {
+    reqwest::ClientBuilder::new()
+        // Following redirects opens the client up to SSRF.
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(Duration::from_secs(15))
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to build HTTP client: {}", e)))
+}
+
+async fn provider_metadata(
+    config: &OidcConfig,
+    http: &reqwest::Client,
+) -> AppResult<CoreProviderMetadata> {
+    let mut cache = METADATA_CACHE.lock().await;
+    if let Some((issuer, metadata, fetched)) = cache.as_ref() {
+        if *issuer == config.issuer_url && fetched.elapsed() < METADATA_TTL {
+            return Ok(metadata.clone());
+        }
+    }
+    let issuer = IssuerUrl::new(config.issuer_url.clone())
+        .map_err(|e| AppError::Validation(format!("Invalid issuer URL: {}", e)))?;
+    let metadata = CoreProviderMetadata::discover_async(issuer, http)
+        .await
+        .map_err(|e| AppError::Network(format!("OIDC discovery failed: {}", e)))?;
+    *cache = Some((config.issuer_url.clone(), metadata.clone(), Instant::now()));
+    Ok(metadata)
+}
+
+/// Pull `code` + `state` out of the redirect's request line (`GET /callback?code=…&state=… HTTP/1.1`).
+/// Err(reason) if the provider reported an error instead.
+fn parse_callback(request_line: &str) -> Option<Result<(String, String), String>> {
+    let target = request_line.split_whitespace().nth(1)?;
+    let url = Url::parse(&format!("http://127.0.0.1{}", target)).ok()?;
+    if url.path() != "/callback" {
+        return None;
+    }
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    };
+    if let Some(error) = param("error") {
+        let detail = param("error_description").unwrap_or_default();
+        return Some(Err(format!("{} {}", error, detail).trim().to_string()));
+    }
+    Some(Ok((param("code")?, param("state")?)))
+}
+
+/// Serve the loopback listener until the browser hits /callback (other requests, e.g. a
+/// favicon probe, get a 404), answer it with a "you can close this window" page.
+async fn await_callback(listener: tokio::net::TcpListener) -> AppResult<(String, String)> {
+    loop {
+        let (mut stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| AppError::Network(format!("Login callback failed: {}", e)))?;
+        let mut buf = vec![0u8; 8 * 1024];
+        let n = stream.read(&mut buf).await.unwrap_or(0);
+        let head = String::from_utf8_lossy(&buf[..n]);
+        let request_line = head.lines().next().unwrap_or_default();
+        match parse_callback(request_line) {
+            Some(result) => {
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    CALLBACK_HTML.len(),
+                    CALLBACK_HTML
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+                return result
+                    .map_err(|e| AppError::Auth(format!("Sign-in was not completed: {}", e)));
+            }
+            None => {
+                let _ = stream
+                    .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                    .await;
+            }
+        }
+    }
+}
+
+/// Display name from the ID token: `name`, else `preferred_username`, else the email's local part.
+fn display_name(name: Option<&str>, preferred: Option<&str>, email: &str) -> String {
+    name.or(preferred)
+        .map(str::to_string)
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| email.split('@').next().unwrap_or(email).to_string())
+}
+
+/// Run the browser login and return the verified user (upserted into the local DB).
+#[tauri::command]
+pub async fn oidc_login(app: tauri::AppHandle, db: State<'_, SqlitePool>) -> AppResult<SsoLogin> {
+    let config = load_config(&db).await?;
+    if !config.enabled {
+        return Err(AppError::Validation("SSO login is not enabled".to_string()));
+    }
+    let http = http_client()?;
+    let metadata = provider_metadata(&config, &http).await?;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to open login callback: {}", e)))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| AppError::Network(e.to_string()))?
+        .port();
+    let redirect = RedirectUrl::new(format!("http://127.0.0.1:{}/callback", port))
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let client = CoreClient::from_provider_metadata(
+        metadata,
+        ClientId::new(config.client_id.clone()),
+        client_secret(),
+    )
+    .set_redirect_uri(redirect);
+
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let (auth_url, csrf, nonce) = client
+        .authorize_url(
+            AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        )
+        .add_scope(Scope::new("email".to_string()))
+        .add_scope(Scope::new("profile".to_string()))
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+
+    app.opener()
+        .open_url(auth_url.as_str(), None::<&str>)
+        .map_err(|e| AppError::Internal(format!("Failed to open the browser: {}", e)))?;
+
+    let (code, state) = tokio::time::timeout(LOGIN_TIMEOUT, await_callback(listener))
+        .await
+        .map_err(|_| AppError::Auth("Sign-in timed out".to_string()))??;
+    if state != *csrf.secret() {
+        return Err(AppError::Auth("Sign-in state mismatch".to_string()));
+    }
+
+    let token = client
+        .exchange_code(AuthorizationCode::new(code))
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .set_pkce_verifier(pkce_verifier)
+        .request_async(&http)
+        .await
+        .map_err(|e| AppError::Auth(format!("Token exchange failed: {}", e)))?;
+    let id_token = token
+        .extra_fields()
+        .id_token()
+        .ok_or_else(|| AppError::Auth("The provider returned no ID token".to_string()))?;
+    let claims = id_token
+        .claims(&client.id_token_verifier(), &nonce)
+        .map_err(|e| AppError::Auth(format!("ID token rejected: {}", e)))?;
+
+    if claims.email_verified() == Some(false) {
+        return Err(AppError::Auth(
+            "Your SSO account's email is not verified".to_string(),
+        ));
+    }
+    let email = claims
+        .email()
+        .map(|e| e.as_str().to_string())
+        .ok_or_else(|| AppError::Auth("The provider did not share an email".to_string()))?;
+    let name = display_name(
+        claims.name().and_then(|n| n.get(None)).map(|n| n.as_str()),
+        claims.preferred_username().map(|u| u.as_str()),
+        &email,
+    );
+
+    let user = upsert_user_internal(&db, name, email, None)
+        .await
+        .map_err(AppError::Validation)?;
+    tracing::info!("🔑 SSO login verified for {}", user.email);
+    Ok(SsoLogin {
+        user,
+        id_token: id_token.to_string(),
+    })
+}
+
+/// Host side: spend an ID token's nonce. The token must have been issued within
+/// `TOKEN_MAX_AGE_SECS` of `now`, and its nonce not spent before; spent nonces are forgotten once
+/// no token carrying them could pass the age check anyway.
+async fn spend_nonce(pool: &SqlitePool, nonce: &str, issued_at: i64, now: i64) -> AppResult<()> {
+    if nonce.is_empty() {
+        return Err(AppError::Auth(
+            "SSO sign-in failed: token has no nonce".to_string(),
+        ));
+    }
+    if (now - issued_at).abs() > TOKEN_MAX_AGE_SECS {
+        return Err(AppError::Auth(
+            "Your SSO sign-in is too old; please sign in again".to_string(),
+        ));
+    }
+    sqlx::query("DELETE FROM sso_token_uses WHERE issued_at < $1")
+        .bind(now - 2 * TOKEN_MAX_AGE_SECS)
+        .execute(pool)
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    let spent = sqlx::query(
+        "INSERT INTO sso_token_uses (nonce, issued_at) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(nonce)
+    .bind(issued_at)
+    .execute(pool)
+    .await
+    .map_err(|e| AppError::Db(e.to_string()))?;
+    if spent.rows_affected() == 0 {
+        return Err(AppError::Auth(
+            "SSO sign-in failed: token was already used".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Host side: answer an `SsoLogin` frame. The ID token must be from the configured provider +
+/// client id, issued for `email` within the freshness window, with an unspent nonce; the user is
+/// upserted and gets a session token, which is what later Connects present.
+pub async fn exchange_id_token(
+    pool: &SqlitePool,
+    email: &str,
+    id_token: &str,
+) -> AppResult<LoginResult> {
+    let config = load_config(pool).await?;
+    if !config.enabled {
+        return Err(AppError::Validation(
+            "SSO login is not enabled on this server".to_string(),
+        ));
+    }
+    let token = CoreIdToken::from_str(id_token)
+        .map_err(|_| AppError::Auth("SSO sign-in failed: malformed token".to_string()))?;
+    let http = http_client()?;
+    let metadata = provider_metadata(&config, &http).await.map_err(|e| {
+        tracing::error!("SSO verification unavailable: {}", e);
+        AppError::Network("SSO sign-in is unavailable right now".to_string())
+    })?;
+    let client =
+        CoreClient::from_provider_metadata(metadata, ClientId::new(config.client_id), None);
+    // The nonce bound the token to the client's own login; the host never saw its value, so it
+    // requires one and spends it below, on top of signature, issuer, audience and expiry.
+    let claims = token
+        .claims(&client.id_token_verifier(), |nonce: Option<&Nonce>| {
+            nonce.map(|_| ()).ok_or_else(|| "missing nonce".to_string())
+        })
+        .map_err(|e| AppError::Auth(format!("SSO sign-in failed: {}", e)))?;
+    let token_email = claims.email().map(|e| normalize_email(e.as_str()));
+    if token_email.as_deref() != Some(normalize_email(email).as_str())
+        || claims.email_verified() == Some(false)
+    {
+        return Err(AppError::Auth(
+            "SSO sign-in failed: token is for a different account".to_string(),
+        ));
+    }
+    let nonce = claims
+        .nonce()
+        .map(|n| n.secret().as_str())
+        .unwrap_or_default();
+    spend_nonce(
+        pool,
+        nonce,
+        claims.issue_time().timestamp(),
+        now_secs() as i64,
+    )
+    .await?;
+
+    let name = display_name(
+        claims.name().and_then(|n| n.get(None)).map(|n| n.as_str()),
+        claims.preferred_username().map(|u| u.as_str()),
+        email,
+    );
+    let user = upsert_user_internal(pool, name, email.to_string(), None)
+        .await
+        .map_err(AppError::Validation)?;
+    let user_id = user
+        .id
+        .ok_or_else(|| AppError::Internal("User was saved without an id".to_string()))?;
+    let token = open_session_internal(pool, user_id, SessionOrigin::Sso).await?;
+    tracing::info!("🔑 SSO session opened for {}", user.email);
+    Ok(LoginResult { user, token })
+}
+
+/// Host-side Connect gate: when SSO is required, `credential` must be a live SSO session token
+/// from `exchange_id_token` for `email`. Err carries the reason for the client.
+pub async fn check_connect(
+    pool: &SqlitePool,
+    email: Option<&str>,
+    credential: Option<&str>,
+) -> Result<(), String> {
+    let config = load_config(pool).await.map_err(|e| e.to_string())?;
+    if !(config.enabled && config.require_on_connect) {
+        return Ok(());
+    }
+    let (Some(email), Some(credential)) = (email, credential) else {
+        return Err("This server requires SSO sign-in".to_string());
+    };
+    // Only a session from `exchange_id_token` counts: a password session (e.g. from a Register
+    // for an unclaimed email) would let a peer skip SSO.
+    match session_owned_by(pool, credential, email, Some(SessionOrigin::Sso)).await? {
+        true => Ok(()),
+        false => Err("Your SSO session has expired; please sign in again".to_string()),
+    }
+}
+
+/// Exchange the ID token from `oidc_login` for a session on a remote host that requires SSO.
+/// Returns the session token to pass as `credential`.
+#[tauri::command]
+pub async fn client_sso_login(
+    host: String,
+    password: String,
+    email: String,
+    id_token: String,
+) -> AppResult<String> {
+    remote_auth(
+        &host,
+        &password,
+        MessageType::SsoLogin,
+        String::new(),
+        email,
+        id_token,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn get_oidc_config(db: State<'_, SqlitePool>) -> AppResult<OidcConfig> {
+    load_config(&db).await
+}
+
+/// Save the SSO config. `client_secret`, when the provider issued one, goes to the OS keychain.
+#[tauri::command]
+pub async fn set_oidc_config(
+    db: State<'_, SqlitePool>,
+    config: OidcConfig,
+    client_secret: Option<String>,
+) -> AppResult<()> {
+    if config.enabled {
+        IssuerUrl::new(config.issuer_url.clone())
+            .map_err(|e| AppError::Validation(format!("Invalid issuer URL: {}", e)))?;
+        if config.client_id.trim().is_empty() {
+            return Err(AppError::Validation("A client id is required".to_string()));
+        }
+    }
+    if let Some(secret) = client_secret {
+        keyring_entry()?
+            .set_password(&secret)
+            .map_err(|e| AppError::Internal(format!("Failed to save client secret: {}", e)))?;
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::memory_pool;
+
+    #[test]
+    fn callback_yields_code_and_state() {
+        let parsed = parse_callback("GET /callback?state=s1&code=c%2F1 HTTP/1.1");
+        assert_eq!(parsed, Some(Ok(("c/1".to_string(), "s1".to_string()))));
+    }
+
+    #[test]
+    fn callback_surfaces_provider_errors_and_ignores_other_paths() {
+        let denied = parse_callback("GET /callback?error=access_denied&state=s HTTP/1.1");
+        assert_eq!(denied, Some(Err("access_denied".to_string())));
+        assert_eq!(parse_callback("GET /favicon.ico HTTP/1.1"), None);
+        assert_eq!(parse_callback("GET /callback?state=s HTTP/1.1"), None);
+    }
+
+    #[test]
+    fn display_name_falls_back_to_email_local_part() {
+        assert_eq!(display_name(Some("Ada"), Some("ada1"), "a@x"), "Ada");
+        assert_eq!(display_name(None, Some("ada1"), "a@x"), "ada1");
+        assert_eq!(display_name(Some(" "), None, "ada@corp"), "ada");
+    }
+
+    #[tokio::test]
+    async fn an_id_token_is_exchanged_once_and_only_while_fresh() {
+        let pool = memory_pool().await;
+        let now = 1_000_000;
+        spend_nonce(&pool, "n1", now - 60, now).await.unwrap();
+        // A replay of the same token is refused, however quickly it comes.
+        assert!(matches!(
+            spend_nonce(&pool, "n1", now - 60, now + 1).await,
+            Err(AppError::Auth(_))
+        ));
+        // A captured token that is still unexpired but no longer fresh is refused too.
+        assert!(matches!(
+            spend_nonce(&pool, "n2", now - TOKEN_MAX_AGE_SECS - 1, now).await,
+            Err(AppError::Auth(_))
+        ));
+        assert!(spend_nonce(&pool, "", now, now).await.is_err());
+        // Spent nonces are pruned once their tokens have aged out of the window.
+        spend_nonce(
+            &pool,
+            "n3",
+            now + 3 * TOKEN_MAX_AGE_SECS,
+            now + 3 * TOKEN_MAX_AGE_SECS,
+        )
+        .await
+        .unwrap();
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sso_token_uses")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, 1);
+    }
+}
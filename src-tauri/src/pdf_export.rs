@@ -0,0 +1,734 @@
+// A room's transcript as a PDF (meeting minutes, compliance archives).
+//
+// The messages come from this machine's database: every posted message in the room (optionally
+// within a date range), oldest first, each with its author and time, and deleted ones marked as
+// such. Stickers, contacts and locations read as their one-line previews, code keeps a fixed-width
+// font, and on the host an image attachment shows its thumbnail (media_cache.rs).
+//
+// The document uses only the PDF base fonts (Helvetica, Courier) in WinAnsi encoding, so nothing
+// is embedded and any reader shows it; text outside Windows-1252 (most emoji, CJK) prints as "?".
+// Lines are wrapped with approximate glyph widths, and every A4 page gets a "Page i of n" footer.
+
+use crate::error::{AppError, AppResult};
+use crate::media_cache;
+use crate::sockets::AppState;
+use crate::{attachments, contacts, location, stickers};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use std::io::Write;
+use std::sync::Arc;
+use tauri::State;
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const BODY_SIZE: f32 = 10.0;
+const BODY_LEADING: f32 = 13.0;
+const META_SIZE: f32 = 8.5;
+const THUMB_MAX_WIDTH: f32 = 180.0;
+const THUMB_MAX_HEIGHT: f32 = 135.0;
+const GREY: f32 = 0.45;
+
+/// Inclusive "YYYY-MM-DD" bounds (UTC); either may be left open.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct DateRange {
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+}
+
+/// One message as it goes on the page.
+#[derive(Clone, Debug)]
+pub(crate) struct TranscriptEntry {
+    pub author: String,
+    // "YYYY-MM-DD HH:MM:SS", UTC.
+    pub created_at: String,
+    pub body: String,
+    pub code: bool,
+    pub edited: bool,
+    pub deleted: bool,
+    pub thumbnail: Option<Thumbnail>,
+    // The attachment behind an image message, for its thumbnail.
+    attachment_id: Option<i64>,
+}
+
+/// Decoded RGB pixels of an attachment's thumbnail.
+#[derive(Clone, Debug)]
+pub(crate) struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub rgb: Vec<u8>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Font {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Font {
+    fn resource(self) -> Name<'static> {
+        match self {
+            Font::Regular => Name(b"F1"),
+            Font::Bold => Name(b"F2"),
+            Font::Mono => Name(b"F3"),
+        }
+    }
+}
+
+/// Something drawn on a page.
+enum Op {
+    Text {
+        font: Font,
+        size: f32,
+        grey: f32,
+        x: f32,
+        y: f32,
+        text: Vec<u8>,
+    },
+    // `image` indexes the document's thumbnails.
+    Image {
+        image: usize,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+}
+
+fn check_date(date: &str) -> AppResult<()> {
+    let bytes = date.as_bytes();
+    let digits = |range: std::ops::Range<usize>| bytes[range].iter().all(u8::is_ascii_digit);
+    let valid = bytes.len() == 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && digits(0..4)
+        && digits(5..7)
+        && digits(8..10)
+        && (1..=12).contains(&date[5..7].parse::<u32>().unwrap_or(0))
+        && (1..=31).contains(&date[8..10].parse::<u32>().unwrap_or(0));
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!(
+            "\"{}\" isn't a date (YYYY-MM-DD)",
+            date
+        )))
+    }
+}
+
+/// Check the range, and describe it for the title block.
+fn check_range(range: &DateRange) -> AppResult<String> {
+    for date in [&range.from, &range.to].into_iter().flatten() {
+        check_date(date)?;
+    }
+    match (&range.from, &range.to) {
+        (Some(from), Some(to)) if from > to => Err(AppError::Validation(
+            "The start date is after the end date".to_string(),
+        )),
+        (Some(from), Some(to)) => Ok(format!("{} to {}", from, to)),
+        (Some(from), None) => Ok(format!("From {}", from)),
+        (None, Some(to)) => Ok(format!("Until {}", to)),
+        (None, None) => Ok("All messages".to_string()),
+    }
+}
+
+/// The room's posted messages in the range, oldest first (without thumbnails).
+pub(crate) async fn load_entries(
+    pool: &SqlitePool,
+    room_id: i64,
+    range: &DateRange,
+) -> AppResult<Vec<TranscriptEntry>> {
+    let rows: Vec<(String, String, String, String, Option<String>, Option<String>)> =
+        sqlx::query_as(
+            "SELECT COALESCE(u.name, 'Unknown'), m.message, COALESCE(m.message_type, 'Chat'),
+                    COALESCE(m.created_at, ''), m.edited_at, m.deleted_at
+             FROM messages m
+             LEFT JOIN users u ON m.user_id = u.id
+             WHERE m.room_id = $1
+               AND m.message_type IN ('Chat', 'Sticker', 'Code', 'Attachment', 'Contact', 'Location')
+               AND ($2 IS NULL OR m.created_at >= $2)
+               AND ($3 IS NULL OR m.created_at < date($3, '+1 day'))
+             ORDER BY m.created_at, m.id",
+        )
+        .bind(room_id)
+        .bind(range.from.as_deref())
+        .bind(range.to.as_deref())
+        .fetch_all(pool)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(author, message, message_type, created_at, edited_at, deleted_at)| {
+                let mut attachment_id = None;
+                let body = if deleted_at.is_some() {
+                    "This message was deleted.".to_string()
+                } else {
+                    match message_type.as_str() {
+                        "Sticker" => stickers::preview(&message),
+                        "Contact" => contacts::preview(&message),
+                        "Location" => location::preview(&message),
+                        "Attachment" => {
+                            match serde_json::from_str::<attachments::AttachmentRef>(&message) {
+                                Ok(a) => {
+                                    if a.mime.starts_with("image/") {
+                                        attachment_id = Some(a.id);
+                                    }
+                                    format!("[File: {}]", a.filename)
+                                }
+                                Err(_) => "[File]".to_string(),
+                            }
+                        }
+                        _ => message,
+                    }
+                };
+                TranscriptEntry {
+                    author,
+                    created_at,
+                    body,
+                    code: message_type == "Code" && deleted_at.is_none(),
+                    edited: edited_at.is_some() && deleted_at.is_none(),
+                    deleted: deleted_at.is_some(),
+                    thumbnail: None,
+                    attachment_id,
+                }
+            },
+        )
+        .collect())
+}
+
+/// An attachment's thumbnail as RGB pixels, if this machine has (or can make) one.
+async fn load_thumbnail(state: &AppState, pool: &SqlitePool, id: i64) -> Option<Thumbnail> {
+    let path = media_cache::host_thumbnail(state, pool, id).await.ok()??;
+    let bytes = tokio::fs::read(path).await.ok()?;
+    let rgb = image::load_from_memory(&bytes).ok()?.to_rgb8();
+    Some(Thumbnail {
+        width: rgb.width(),
+        height: rgb.height(),
+        rgb: rgb.into_raw(),
+    })
+}
+
+/// A character as Windows-1252 (the WinAnsi encoding), "?" if it has no code there.
+fn win_ansi(c: char) -> Option<u8> {
+    let code = match c {
+        '\t' => b' ',
+        ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+        '€' => 0x80,
+        '‚' => 0x82,
+        'ƒ' => 0x83,
+        '„' => 0x84,
+        '…' => 0x85,
+        '†' => 0x86,
+        '‡' => 0x87,
+        'ˆ' => 0x88,
+        '‰' => 0x89,
+        'Š' => 0x8a,
+        '‹' => 0x8b,
+        'Œ' => 0x8c,
+        'Ž' => 0x8e,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '˜' => 0x98,
+        '™' => 0x99,
+        'š' => 0x9a,
+        '›' => 0x9b,
+        'œ' => 0x9c,
+        'ž' => 0x9e,
+        'Ÿ' => 0x9f,
+        // Joiners and variation selectors inside emoji: no mark of their own.
+        '\u{200b}'..='\u{200f}' | '\u{fe00}'..='\u{fe0f}' => return None,
+        _ => b'?',
+    };
+    Some(code)
+}
+
+fn encode(text: &str) -> Vec<u8> {
+    text.chars().filter_map(win_ansi).collect()
+}
+
+/// Approximate advance of a WinAnsi byte, in ems.
+fn char_width(font: Font, byte: u8) -> f32 {
+    if font == Font::Mono {
+        return 0.6;
+    }
+    let width = match byte {
+        b'i' | b'j' | b'l' | b'\'' | b'|' | b'.' | b',' | b':' | b';' | b'!' => 0.24,
+        b' ' | b'f' | b't' | b'r' | b'I' | b'(' | b')' | b'[' | b']' | b'/' | b'-' => 0.32,
+        b'm' | b'w' | b'M' | b'W' | b'@' => 0.85,
+        b'A'..=b'Z' | b'%' | b'&' => 0.68,
+        _ => 0.55,
+    };
+    if font == Font::Bold {
+        width + 0.04
+    } else {
+        width
+    }
+}
+
+fn text_width(font: Font, size: f32, text: &[u8]) -> f32 {
+    text.iter().map(|b| char_width(font, *b)).sum::<f32>() * size
+}
+
+/// Break encoded text into lines at most `width` wide: at spaces where possible, mid-word for
+/// a word longer than a line, and always at newlines.
+fn wrap(font: Font, size: f32, width: f32, text: &[u8]) -> Vec<Vec<u8>> {
+    let mut lines = Vec::new();
+    for paragraph in text.split(|b| *b == b'\n') {
+        let paragraph = paragraph.strip_suffix(b"\r").unwrap_or(paragraph);
+        let mut line: Vec<u8> = Vec::new();
+        let mut line_width = 0.0;
+        for word in paragraph.split(|b| *b == b' ') {
+            let word_width = text_width(font, size, word);
+            let space = if line.is_empty() {
+                0.0
+            } else {
+                char_width(font, b' ') * size
+            };
+            if line_width + space + word_width <= width {
+                if !line.is_empty() {
+                    line.push(b' ');
+                }
+                line.extend_from_slice(word);
+                line_width += space + word_width;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            line_width = 0.0;
+            for byte in word {
+                let w = char_width(font, *byte) * size;
+                if line_width + w > width && !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                    line_width = 0.0;
+                }
+                line.push(*byte);
+                line_width += w;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Fit an image inside the thumbnail box, never enlarging it.
+fn thumb_size(thumb: &Thumbnail) -> (f32, f32) {
+    let (w, h) = (thumb.width as f32, thumb.height as f32);
+    let scale = (THUMB_MAX_WIDTH / w).min(THUMB_MAX_HEIGHT / h).min(1.0);
+    (w * scale, h * scale)
+}
+
+struct Layout {
+    pages: Vec<Vec<Op>>,
+    y: f32,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Self {
+            pages: vec![Vec::new()],
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    /// Start a new page if `height` doesn't fit on this one (and it isn't empty).
+    fn make_room(&mut self, height: f32) {
+        if self.y - height < MARGIN && self.y < PAGE_HEIGHT - MARGIN {
+            self.pages.push(Vec::new());
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+
+    /// Move down `height`, onto a new page if it doesn't fit on this one.
+    fn advance(&mut self, height: f32) -> f32 {
+        self.make_room(height);
+        self.y -= height;
+        self.y
+    }
+
+    fn push(&mut self, op: Op) {
+        self.pages.last_mut().expect("a page").push(op);
+    }
+
+    fn text(&mut self, font: Font, size: f32, grey: f32, x: f32, y: f32, text: Vec<u8>) {
+        self.push(Op::Text {
+            font,
+            size,
+            grey,
+            x,
+            y,
+            text,
+        });
+    }
+}
+
+/// The time a message is shown with: "YYYY-MM-DD HH:MM UTC".
+fn time_label(created_at: &str) -> String {
+    let minutes = created_at.get(..16).unwrap_or(created_at);
+    format!("{} UTC", minutes.replace('T', " "))
+}
+
+/// Render a transcript. `exported_at` is "YYYY-MM-DD HH:MM" (UTC).
+pub(crate) fn build_pdf(
+    room: &str,
+    range_label: &str,
+    exported_at: &str,
+    entries: &[TranscriptEntry],
+) -> AppResult<Vec<u8>> {
+    let text_width_max = PAGE_WIDTH - 2.0 * MARGIN;
+    let mut layout = Layout::new();
+    let mut images: Vec<&Thumbnail> = Vec::new();
+
+    let title = format!("#{}", room);
+    let y = layout.advance(18.0);
+    layout.text(Font::Bold, 16.0, 0.0, MARGIN, y, encode(&title));
+    let y = layout.advance(14.0);
+    layout.text(Font::Regular, 9.0, GREY, MARGIN, y, encode(range_label));
+    let y = layout.advance(12.0);
+    let exported = format!(
+        "Exported {} UTC · {} message{}",
+        exported_at,
+        entries.len(),
+        if entries.len() == 1 { "" } else { "s" }
+    );
+    layout.text(Font::Regular, 9.0, GREY, MARGIN, y, encode(&exported));
+    layout.advance(14.0);
+
+    for entry in entries {
+        let font = if entry.code {
+            Font::Mono
+        } else {
+            Font::Regular
+        };
+        let size = if entry.code {
+            BODY_SIZE - 1.0
+        } else {
+            BODY_SIZE
+        };
+        let lines = wrap(font, size, text_width_max, &encode(&entry.body));
+        // Keep the author line with the first line of the message.
+        layout.make_room(META_SIZE + 4.0 + BODY_LEADING);
+        let y = layout.advance(META_SIZE + 4.0);
+        let author = encode(&entry.author);
+        let author_width = text_width(Font::Bold, META_SIZE, &author);
+        layout.text(Font::Bold, META_SIZE, 0.0, MARGIN, y, author);
+        let mut time = time_label(&entry.created_at);
+        if entry.edited {
+            time.push_str(" (edited)");
+        }
+        layout.text(
+            Font::Regular,
+            META_SIZE,
+            GREY,
+            MARGIN + author_width + 6.0,
+            y,
+            encode(&time),
+        );
+        let grey = if entry.deleted { GREY } else { 0.0 };
+        for line in lines {
+            let y = layout.advance(BODY_LEADING);
+            layout.text(font, size, grey, MARGIN, y - 2.0, line);
+        }
+        if let Some(thumb) = entry.thumbnail.as_ref().filter(|_| !entry.deleted) {
+            let (width, height) = thumb_size(thumb);
+            let y = layout.advance(height + 4.0);
+            layout.push(Op::Image {
+                image: images.len(),
+                x: MARGIN,
+                y,
+                width,
+                height,
+            });
+            images.push(thumb);
+        }
+        layout.advance(10.0);
+    }
+
+    write_pdf(room, &layout.pages, &images)
+}
+
+fn write_pdf(room: &str, pages: &[Vec<Op>], images: &[&Thumbnail]) -> AppResult<Vec<u8>> {
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let pages_id = alloc.bump();
+    let regular_id = alloc.bump();
+    let bold_id = alloc.bump();
+    let mono_id = alloc.bump();
+    let image_ids: Vec<Ref> = images.iter().map(|_| alloc.bump()).collect();
+    let page_ids: Vec<(Ref, Ref)> = pages.iter().map(|_| (alloc.bump(), alloc.bump())).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(pages_id);
+    pdf.pages(pages_id)
+        .kids(page_ids.iter().map(|(page, _)| *page))
+        .count(page_ids.len() as i32);
+    for (id, base) in [
+        (regular_id, Name(b"Helvetica")),
+        (bold_id, Name(b"Helvetica-Bold")),
+        (mono_id, Name(b"Courier")),
+    ] {
+        pdf.type1_font(id)
+            .base_font(base)
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+    }
+
+    for (thumb, id) in images.iter().zip(&image_ids) {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(&thumb.rgb)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let data = encoder
+            .finish()
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let mut image = pdf.image_xobject(*id, &data);
+        image.filter(Filter::FlateDecode);
+        image.width(thumb.width as i32);
+        image.height(thumb.height as i32);
+        image.color_space().device_rgb();
+        image.bits_per_component(8);
+        image.finish();
+    }
+
+    let image_names: Vec<String> = (0..images.len()).map(|i| format!("Im{}", i)).collect();
+    for (index, (ops, (page_id, content_id))) in pages.iter().zip(&page_ids).enumerate() {
+        let mut content = Content::new();
+        let mut used = Vec::new();
+        for op in ops {
+            match op {
+                Op::Text {
+                    font,
+                    size,
+                    grey,
+                    x,
+                    y,
+                    text,
+                } => {
+                    content.set_fill_gray(*grey);
+                    content.begin_text();
+                    content.set_font(font.resource(), *size);
+                    content.next_line(*x, *y);
+                    content.show(Str(text));
+                    content.end_text();
+                }
+                Op::Image {
+                    image,
+                    x,
+                    y,
+                    width,
+                    height,
+                } => {
+                    content.save_state();
+                    content.transform([*width, 0.0, 0.0, *height, *x, *y]);
+                    content.x_object(Name(image_names[*image].as_bytes()));
+                    content.restore_state();
+                    used.push(*image);
+                }
+            }
+        }
+        let footer = encode(&format!(
+            "#{} · Page {} of {}",
+            room,
+            index + 1,
+            pages.len()
+        ));
+        let footer_x = PAGE_WIDTH - MARGIN - text_width(Font::Regular, 8.0, &footer);
+        content.set_fill_gray(GREY);
+        content.begin_text();
+        content.set_font(Font::Regular.resource(), 8.0);
+        content.next_line(footer_x, MARGIN / 2.0);
+        content.show(Str(&footer));
+        content.end_text();
+        pdf.stream(*content_id, &content.finish());
+
+        let mut page = pdf.page(*page_id);
+        page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+        page.parent(pages_id);
+        page.contents(*content_id);
+        let mut resources = page.resources();
+        resources
+            .fonts()
+            .pair(Font::Regular.resource(), regular_id)
+            .pair(Font::Bold.resource(), bold_id)
+            .pair(Font::Mono.resource(), mono_id);
+        if !used.is_empty() {
+            let mut x_objects = resources.x_objects();
+            for image in used {
+                x_objects.pair(Name(image_names[image].as_bytes()), image_ids[image]);
+            }
+        }
+        resources.finish();
+        page.finish();
+    }
+    Ok(pdf.finish())
+}
+
+/// Save room `room_id`'s transcript (within `date_range`, if given) as a PDF at `path`. Returns
+/// how many messages it holds.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_room_pdf(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    date_range: Option<DateRange>,
+    path: String,
+) -> AppResult<usize> {
+    let range = date_range.unwrap_or_default();
+    let range_label = check_range(&range)?;
+    let room: String = sqlx::query_scalar("SELECT name FROM chat_rooms WHERE id = $1")
+        .bind(room_id)
+        .fetch_optional(&*db)
+        .await?
+        .ok_or_else(|| AppError::Validation("That room doesn't exist".to_string()))?;
+    let mut entries = load_entries(&db, room_id, &range).await?;
+    for entry in &mut entries {
+        if let Some(id) = entry.attachment_id {
+            entry.thumbnail = load_thumbnail(&state, &db, id).await;
+        }
+    }
+    let exported_at: String = sqlx::query_scalar("SELECT strftime('%Y-%m-%d %H:%M', 'now')")
+        .fetch_one(&*db)
+        .await?;
+    let count = entries.len();
+    let pdf = tauri::async_runtime::spawn_blocking(move || {
+        build_pdf(&room, &range_label, &exported_at, &entries)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))??;
+    tokio::fs::write(&path, pdf)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to save {}: {}", path, e)))?;
+    tracing::info!(
+        "📄 Exported {} messages from room {} as PDF",
+        count,
+        room_id
+    );
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn entry(body: &str) -> TranscriptEntry {
+        TranscriptEntry {
+            author: "Alice".to_string(),
+            created_at: "2026-03-02 09:15:00".to_string(),
+            body: body.to_string(),
+            code: false,
+            edited: false,
+            deleted: false,
+            thumbnail: None,
+            attachment_id: None,
+        }
+    }
+
+    #[test]
+    fn transcripts_paginate() {
+        let short = build_pdf("ops", "All messages", "2026-03-02 10:00", &[entry("hi")]).unwrap();
+        assert!(short.starts_with(b"%PDF-"));
+        assert!(String::from_utf8_lossy(&short).contains("Page 1 of 1"));
+
+        let mut long: Vec<TranscriptEntry> = (0..120)
+            .map(|i| entry(&format!("minute item {} ", i).repeat(12)))
+            .collect();
+        long[3].thumbnail = Some(Thumbnail {
+            width: 4,
+            height: 2,
+            rgb: vec![200; 4 * 2 * 3],
+        });
+        let pdf = build_pdf("ops", "All messages", "2026-03-02 10:00", &long).unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/Im0"));
+        let pages = text.matches("/MediaBox").count();
+        assert!(pages > 3, "{} pages", pages);
+        assert!(text.contains(&format!("Page {} of {}", pages, pages)));
+    }
+
+    #[test]
+    fn text_is_encoded_and_wrapped() {
+        assert_eq!(encode("café – “ok” €5"), b"caf\xe9 \x96 \x93ok\x94 \x805");
+        // An emoji with a variation selector is one "?".
+        assert_eq!(encode("hi ❤️"), b"hi ?");
+        let lines = wrap(
+            Font::Regular,
+            10.0,
+            100.0,
+            b"one two three four five six\nseven",
+        );
+        assert!(lines.len() > 2);
+        assert_eq!(lines.last().unwrap(), b"seven");
+        assert!(lines
+            .iter()
+            .all(|l| text_width(Font::Regular, 10.0, l) <= 100.0));
+        // A long unbroken word still fits.
+        let url = wrap(Font::Mono, 10.0, 60.0, &[b'x'; 25]);
+        assert_eq!(url.len(), 3);
+    }
+
+    #[test]
+    fn date_ranges_are_checked() {
+        let range = |from: Option<&str>, to: Option<&str>| DateRange {
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+        };
+        assert_eq!(check_range(&range(None, None)).unwrap(), "All messages");
+        assert_eq!(
+            check_range(&range(Some("2026-01-01"), Some("2026-01-31"))).unwrap(),
+            "2026-01-01 to 2026-01-31"
+        );
+        assert!(check_range(&range(Some("2026-02-01"), Some("2026-01-31"))).is_err());
+        assert!(check_range(&range(Some("2026-13-01"), None)).is_err());
+        assert!(check_range(&range(None, Some("yesterday"))).is_err());
+    }
+
+    #[tokio::test]
+    async fn entries_cover_the_range() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id) VALUES (1, 'Alice', 'a@x', 1);
+             INSERT INTO chat_rooms (id, name, is_private, created_by) VALUES (100, 'ops', 0, 1);
+             INSERT INTO messages (room_id, user_id, message, message_type, message_id, created_at) VALUES
+                 (100, 1, 'before', 'Chat', 'a', '2026-02-28 23:59:00'),
+                 (100, 1, 'fn main() {}', 'Code', 'b', '2026-03-01 08:00:00'),
+                 (100, 1, '{\"id\":7,\"filename\":\"plan.png\",\"size\":3,\"mime\":\"image/png\"}',
+                  'Attachment', 'c', '2026-03-02 23:30:00'),
+                 (100, 1, 'joined', 'System', 'd', '2026-03-02 09:00:00'),
+                 (100, 1, 'after', 'Chat', 'e', '2026-03-03 00:00:00');
+             UPDATE messages SET message = '', deleted_at = '2026-03-04T00:00:00Z'
+                 WHERE message_id = 'b';",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let range = DateRange {
+            from: Some("2026-03-01".to_string()),
+            to: Some("2026-03-02".to_string()),
+        };
+        let entries = load_entries(&pool, 100, &range).await.unwrap();
+        let bodies: Vec<&str> = entries.iter().map(|e| e.body.as_str()).collect();
+        assert_eq!(bodies, ["This message was deleted.", "[File: plan.png]"]);
+        assert!(entries[0].deleted && !entries[0].code);
+        assert_eq!(entries[1].attachment_id, Some(7));
+        let all = load_entries(&pool, 100, &DateRange::default())
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 4);
+    }
+}
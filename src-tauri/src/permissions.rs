@@ -0,0 +1,215 @@
+// Per-command authorization.
+//
+// Every command the UI invokes passes through `guarded`, wrapped around the app's handler in
+// lib.rs. A command listed in PERMISSIONS needs at least that role; the rest are open to anyone.
+// The role is the session's: hosting makes us the Host, and so does not being connected to
+// anyone else's server (the data and settings are then our own). A client whose user is flagged
+// admin here (`set_user_admin`) is an Admin; any other client a Member. A call without the role
+// gets AppError::PermissionDenied and the command never runs.
+
+use crate::error::AppError;
+use crate::sockets::AppState;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Manager, Runtime};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Member,
+    Admin,
+    Host,
+}
+
+/// The least role each restricted command needs.
+const PERMISSIONS: &[(&str, Role)] = &[
+    // Moderation.
+    ("get_flagged_messages", Role::Admin),
+    ("resolve_flag", Role::Admin),
+    ("mute_user", Role::Admin),
+    ("unmute_user", Role::Admin),
+    ("get_muted_users", Role::Admin),
+    ("list_join_requests", Role::Admin),
+    ("approve_join_request", Role::Admin),
+    ("deny_join_request", Role::Admin),
+    // Reports and records.
+    ("get_audit_log", Role::Admin),
+    ("get_activity_report", Role::Admin),
+    ("get_read_report", Role::Admin),
+    ("export_read_report_csv", Role::Admin),
+    ("import_users_csv", Role::Admin),
+    ("export_users_csv", Role::Admin),
+    ("export_departments_csv", Role::Admin),
+    ("get_server_stats", Role::Admin),
+    // Shared emoji and stickers.
+    ("upload_custom_emoji", Role::Admin),
+    ("delete_sticker_pack", Role::Admin),
+    // Running the server.
+    ("set_user_admin", Role::Host),
+    ("set_user_password", Role::Host),
+    ("set_compliance_room", Role::Host),
+    ("set_room_permissions", Role::Host),
+    ("set_room_welcome", Role::Host),
+    ("set_retention_policy", Role::Host),
+    ("set_room_retention", Role::Host),
+    ("set_legal_hold", Role::Host),
+    ("prune_now", Role::Host),
+    ("set_version_gate", Role::Host),
+    ("enter_maintenance_mode", Role::Host),
+    ("exit_maintenance_mode", Role::Host),
+    ("send_department_announcement", Role::Host),
+    ("simulate_clients", Role::Host),
+    ("seed_demo_data", Role::Host),
+    ("import_history", Role::Host),
+    ("set_guest_config", Role::Host),
+    ("set_storage_limits", Role::Host),
+    ("set_message_limits", Role::Host),
+    ("set_digest_schedule", Role::Host),
+    ("set_transcription_config", Role::Host),
+    ("set_ocr_config", Role::Host),
+    // Sign-in and integrations.
+    ("set_accounts_config", Role::Host),
+    ("set_oidc_config", Role::Host),
+    ("set_ldap_config", Role::Host),
+    ("ldap_sync_now", Role::Host),
+    ("set_smtp_config", Role::Host),
+    ("set_mqtt_config", Role::Host),
+    ("create_incoming_webhook", Role::Host),
+    ("delete_incoming_webhook", Role::Host),
+    ("start_webhook_listener", Role::Host),
+    ("set_remote_access", Role::Host),
+    ("start_metrics_exporter", Role::Host),
+];
+
+/// The role `command` needs, if it's restricted.
+pub fn required_role(command: &str) -> Option<Role> {
+    PERMISSIONS
+        .iter()
+        .find(|(name, _)| *name == command)
+        .map(|(_, role)| *role)
+}
+
+/// Whether `role` may run `command`.
+pub fn check(role: Role, command: &str) -> Result<(), AppError> {
+    match required_role(command) {
+        Some(required) if role < required => Err(AppError::PermissionDenied(format!(
+            "{} needs the {:?} role",
+            command, required
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// This session's role.
+pub async fn session_role<R: Runtime>(app: &AppHandle<R>) -> Role {
+    let state = app.state::<Arc<AppState>>();
+    if *state.is_server.read().await || state.client_transport.lock().await.is_none() {
+        return Role::Host;
+    }
+    let Some(user_id) = *state.user_id.read().await else {
+        return Role::Member;
+    };
+    let pool = app.state::<SqlitePool>();
+    let admin: Option<bool> = sqlx::query_scalar("SELECT is_admin FROM users WHERE id = $1")
+        .bind(user_id as i64)
+        .fetch_optional(pool.inner())
+        .await
+        .unwrap_or(None);
+    if admin == Some(true) {
+        Role::Admin
+    } else {
+        Role::Member
+    }
+}
+
+/// Wrap the app's command handler so restricted commands only run for a role that may.
+pub fn guarded<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    let handler = Arc::new(handler);
+    move |invoke| {
+        if required_role(invoke.message.command()).is_none() {
+            return handler(invoke);
+        }
+        // The role takes the session's locks (and maybe a query), so decide off this thread.
+        let app = invoke.message.webview().app_handle().clone();
+        let handler = Arc::clone(&handler);
+        tauri::async_runtime::spawn(async move {
+            let role = session_role(&app).await;
+            match check(role, invoke.message.command()) {
+                Ok(()) => {
+                    handler(invoke);
+                }
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    invoke.resolver.reject(e);
+                }
+            }
+        });
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `set_*` commands that configure only this machine or its user (or tag a room), so any
+    /// role may run them. Every other `set_*` command is host configuration.
+    const LOCAL_SETTERS: &[&str] = &[
+        "set_room_tags",
+        "set_discovery_config",
+        "set_presence_config",
+        "set_compression_config",
+        "set_message_events_config",
+        "set_throttle_config",
+        "set_disk_guard_config",
+        "set_scan_config",
+        "set_media_cache_config",
+        "set_usage_stats_config",
+        "set_translation_config",
+        "set_user_settings",
+        "set_user_profile",
+        "set_profile_credential",
+        "set_hotkeys",
+        "set_database_path",
+    ];
+
+    #[test]
+    fn every_host_config_setter_is_restricted() {
+        let registered = include_str!("lib.rs")
+            .split("generate_handler![")
+            .nth(1)
+            .and_then(|rest| rest.split(']').next())
+            .expect("lib.rs registers its commands with generate_handler!");
+        let unrestricted: Vec<&str> = registered
+            .lines()
+            .map(|line| line.trim().trim_end_matches(','))
+            .filter(|name| name.starts_with("set_"))
+            .filter(|name| required_role(name).is_none() && !LOCAL_SETTERS.contains(name))
+            .collect();
+        assert!(
+            unrestricted.is_empty(),
+            "add these to PERMISSIONS (or LOCAL_SETTERS if they only touch this machine): {:?}",
+            unrestricted
+        );
+    }
+
+    #[test]
+    fn restricted_commands_need_their_role() {
+        assert!(check(Role::Member, "send_as_client").is_ok());
+        assert!(matches!(
+            check(Role::Member, "mute_user"),
+            Err(AppError::PermissionDenied(_))
+        ));
+        assert!(check(Role::Admin, "mute_user").is_ok());
+        assert!(check(Role::Admin, "set_user_admin").is_err());
+        assert!(check(Role::Host, "set_user_admin").is_ok());
+
+        let json = serde_json::to_string(&check(Role::Member, "prune_now").unwrap_err()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"code":"permission_denied","message":"prune_now needs the Host role"}"#
+        );
+    }
+}
@@ -0,0 +1,452 @@
+// Remote access through the home router (host-side, opt-in).
+//
+// With remote access allowed, hosting asks the router to forward the listen port: UPnP IGD
+// first, then NAT-PMP (RFC 6886) for routers that only speak that. The mapping is leased for an
+// hour and renewed at half-time, and removed when hosting stops. While it's up, invite links name
+// the router's external address, so a teammate working from home can connect. A router with both
+// protocols off (or a carrier-grade NAT in front of it) can't be mapped; the `remote_access`
+// event and `get_remote_access` say why, and mapping is retried every few minutes.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use igd_next::aio::tokio::Tokio;
+use igd_next::aio::Gateway;
+use igd_next::{PortMappingProtocol, SearchOptions};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{Emitter, State};
+
+const SETTINGS_KEY: &str = "remote_access";
+
+/// How long the router keeps a mapping; it's renewed at half that.
+const LEASE_SECS: u32 = 60 * 60;
+/// How soon a failed mapping is tried again.
+const RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+/// How long to wait for a UPnP router to answer the search.
+const UPNP_SEARCH_TIMEOUT: Duration = Duration::from_secs(3);
+/// Shown in the router's port forwarding table.
+const DESCRIPTION: &str = "Nutler chat";
+
+const NATPMP_PORT: u16 = 5351;
+const NATPMP_OP_EXTERNAL: u8 = 0;
+const NATPMP_OP_MAP_TCP: u8 = 2;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RemoteAccessConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    Upnp,
+    NatPmp,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct RemoteAccess {
+    pub enabled: bool,
+    // The router's address teammates connect to, while the port is mapped.
+    pub external_addr: Option<String>,
+    pub protocol: Option<Protocol>,
+    // Why the port couldn't be mapped, when it couldn't.
+    pub error: Option<String>,
+}
+
+#[derive(Clone)]
+enum Router {
+    Upnp(Gateway<Tokio>),
+    NatPmp(Ipv4Addr),
+}
+
+#[derive(Clone)]
+struct Mapped {
+    router: Router,
+    external: SocketAddr,
+    internal_port: u16,
+}
+
+/// The host's port mapping, from when hosting starts with remote access on until it stops.
+#[derive(Default)]
+pub struct PortMapping {
+    // The task mapping and renewing the port.
+    task: Option<tauri::async_runtime::JoinHandle<()>>,
+    mapped: Option<Mapped>,
+    error: Option<String>,
+}
+
+impl Mapped {
+    fn protocol(&self) -> Protocol {
+        match self.router {
+            Router::Upnp(_) => Protocol::Upnp,
+            Router::NatPmp(_) => Protocol::NatPmp,
+        }
+    }
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<RemoteAccessConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt remote access settings: {}", e))),
+        None => Ok(RemoteAccessConfig::default()),
+    }
+}
+
+/// The LAN address the router should forward to: the one we're bound to, or (bound to every
+/// interface) the one our traffic to `toward` leaves from.
+fn local_ipv4(bind_ip: IpAddr, toward: SocketAddr) -> Result<Ipv4Addr, String> {
+    let ip = if bind_ip.is_unspecified() {
+        let probe = std::net::UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+        // Connecting a UDP socket only picks a route; nothing is sent.
+        probe.connect(toward).map_err(|e| e.to_string())?;
+        probe.local_addr().map_err(|e| e.to_string())?.ip()
+    } else {
+        bind_ip
+    };
+    match ip {
+        IpAddr::V4(ip) if !ip.is_loopback() => Ok(ip),
+        _ => Err("the host must listen on a LAN (IPv4) address".to_string()),
+    }
+}
+
+async fn map_upnp(bind_ip: IpAddr, port: u16) -> Result<Mapped, String> {
+    let options = SearchOptions {
+        timeout: Some(UPNP_SEARCH_TIMEOUT),
+        ..Default::default()
+    };
+    let gateway = igd_next::aio::tokio::search_gateway(options)
+        .await
+        .map_err(|e| format!("no UPnP router found ({})", e))?;
+    let local = SocketAddr::new(IpAddr::V4(local_ipv4(bind_ip, gateway.addr)?), port);
+    // The same port outside as inside, unless the router has it taken.
+    let external_port = match gateway
+        .add_port(
+            PortMappingProtocol::TCP,
+            port,
+            local,
+            LEASE_SECS,
+            DESCRIPTION,
+        )
+        .await
+    {
+        Ok(()) => port,
+        Err(_) => gateway
+            .add_any_port(PortMappingProtocol::TCP, local, LEASE_SECS, DESCRIPTION)
+            .await
+            .map_err(|e| format!("the UPnP router refused the mapping ({})", e))?,
+    };
+    let ip = gateway
+        .get_external_ip()
+        .await
+        .map_err(|e| format!("the UPnP router has no external address ({})", e))?;
+    Ok(Mapped {
+        external: SocketAddr::new(ip, external_port),
+        router: Router::Upnp(gateway),
+        internal_port: port,
+    })
+}
+
+/// The default IPv4 gateway in a /proc/net/route table (addresses are little-endian hex).
+fn parse_route_table(table: &str) -> Option<Ipv4Addr> {
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(1) != Some(&"00000000") {
+            return None;
+        }
+        let gateway = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        (gateway != 0).then(|| Ipv4Addr::from(gateway.to_le_bytes()))
+    })
+}
+
+/// The router to ask for a NAT-PMP mapping. Linux tells us; elsewhere it's guessed as the `.1`
+/// of our LAN address, which is what home routers almost always use.
+fn default_gateway(bind_ip: IpAddr) -> Result<Ipv4Addr, String> {
+    if let Some(gateway) = std::fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|table| parse_route_table(&table))
+    {
+        return Ok(gateway);
+    }
+    let [a, b, c, _] = local_ipv4(bind_ip, SocketAddr::from(([1, 1, 1, 1], 80)))?.octets();
+    Ok(Ipv4Addr::new(a, b, c, 1))
+}
+
+/// A NAT-PMP TCP mapping request; a lifetime of 0 deletes the mapping.
+fn natpmp_map_request(internal_port: u16, external_port: u16, lifetime: u32) -> [u8; 12] {
+    let mut req = [0u8; 12];
+    req[1] = NATPMP_OP_MAP_TCP;
+    req[4..6].copy_from_slice(&internal_port.to_be_bytes());
+    req[6..8].copy_from_slice(&external_port.to_be_bytes());
+    req[8..12].copy_from_slice(&lifetime.to_be_bytes());
+    req
+}
+
+/// Check a NAT-PMP response's header: version 0, the reply to `op`, result code 0.
+fn natpmp_check(resp: &[u8], op: u8, len: usize) -> Result<(), String> {
+    if resp.len() < len || resp[0] != 0 || resp[1] != 128 + op {
+        return Err("the router sent a malformed NAT-PMP reply".to_string());
+    }
+    match u16::from_be_bytes([resp[2], resp[3]]) {
+        0 => Ok(()),
+        2 => Err("the router has NAT-PMP turned off".to_string()),
+        3 => Err("the router isn't connected to the internet".to_string()),
+        code => Err(format!(
+            "the router refused the NAT-PMP request (code {})",
+            code
+        )),
+    }
+}
+
+/// The external address in a NAT-PMP reply to an external-address request.
+fn parse_natpmp_external(resp: &[u8]) -> Result<Ipv4Addr, String> {
+    natpmp_check(resp, NATPMP_OP_EXTERNAL, 12)?;
+    Ok(Ipv4Addr::new(resp[8], resp[9], resp[10], resp[11]))
+}
+
+/// The external port the router granted in a NAT-PMP reply to a mapping request.
+fn parse_natpmp_mapping(resp: &[u8]) -> Result<u16, String> {
+    natpmp_check(resp, NATPMP_OP_MAP_TCP, 16)?;
+    Ok(u16::from_be_bytes([resp[10], resp[11]]))
+}
+
+/// Send a NAT-PMP request to `gateway`, retrying as the RFC suggests (250 ms, doubling).
+async fn natpmp_request(gateway: Ipv4Addr, req: &[u8]) -> Result<Vec<u8>, String> {
+    let socket = tokio::net::UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| e.to_string())?;
+    socket
+        .connect((gateway, NATPMP_PORT))
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut wait = Duration::from_millis(250);
+    let mut buf = [0u8; 16];
+    for _ in 0..4 {
+        socket.send(req).await.map_err(|e| e.to_string())?;
+        if let Ok(Ok(n)) = tokio::time::timeout(wait, socket.recv(&mut buf)).await {
+            return Ok(buf[..n].to_vec());
+        }
+        wait *= 2;
+    }
+    Err("no NAT-PMP router answered".to_string())
+}
+
+async fn map_natpmp(bind_ip: IpAddr, port: u16) -> Result<Mapped, String> {
+    let gateway = default_gateway(bind_ip)?;
+    let ip = parse_natpmp_external(&natpmp_request(gateway, &[0, NATPMP_OP_EXTERNAL]).await?)?;
+    let req = natpmp_map_request(port, port, LEASE_SECS);
+    let external_port = parse_natpmp_mapping(&natpmp_request(gateway, &req).await?)?;
+    Ok(Mapped {
+        router: Router::NatPmp(gateway),
+        external: SocketAddr::new(IpAddr::V4(ip), external_port),
+        internal_port: port,
+    })
+}
+
+async fn map_port(bind_ip: IpAddr, port: u16) -> Result<Mapped, String> {
+    match map_upnp(bind_ip, port).await {
+        Ok(mapped) => Ok(mapped),
+        Err(upnp) => map_natpmp(bind_ip, port)
+            .await
+            .map_err(|natpmp| format!("Couldn't map the port: {}; {}", upnp, natpmp)),
+    }
+}
+
+async fn unmap(mapped: Mapped) {
+    let result = match &mapped.router {
+        Router::Upnp(gateway) => gateway
+            .remove_port(PortMappingProtocol::TCP, mapped.external.port())
+            .await
+            .map_err(|e| e.to_string()),
+        Router::NatPmp(gateway) => {
+            let req = natpmp_map_request(mapped.internal_port, 0, 0);
+            natpmp_request(*gateway, &req).await.map(|_| ())
+        }
+    };
+    match result {
+        Ok(()) => tracing::info!("🌐 Removed the port mapping for {}", mapped.external),
+        Err(e) => tracing::warn!("Couldn't remove the port mapping: {}", e),
+    }
+}
+
+fn status(state: &AppState, enabled: bool) -> RemoteAccess {
+    let guard = state.port_mapping.lock().unwrap_or_else(|e| e.into_inner());
+    let mapped = guard.as_ref().and_then(|m| m.mapped.as_ref());
+    RemoteAccess {
+        enabled,
+        external_addr: mapped.map(|m| m.external.to_string()),
+        protocol: mapped.map(Mapped::protocol),
+        error: guard.as_ref().and_then(|m| m.error.clone()),
+    }
+}
+
+/// The router's address for this host, while the port is mapped.
+pub(crate) fn external_addr(state: &AppState) -> Option<SocketAddr> {
+    let guard = state.port_mapping.lock().unwrap_or_else(|e| e.into_inner());
+    guard.as_ref()?.mapped.as_ref().map(|m| m.external)
+}
+
+/// Host: map `listen_addr`'s port on the router, and keep it mapped, if remote access is on.
+pub(crate) fn start(
+    app: tauri::AppHandle,
+    state: Arc<AppState>,
+    pool: SqlitePool,
+    listen_addr: SocketAddr,
+) {
+    let task_state = Arc::clone(&state);
+    let task = tauri::async_runtime::spawn(async move {
+        let state = task_state;
+        match load_config(&pool).await {
+            Ok(config) if config.enabled => {}
+            Ok(_) => return,
+            Err(e) => {
+                tracing::warn!("{}", e);
+                return;
+            }
+        }
+        loop {
+            let result = map_port(listen_addr.ip(), listen_addr.port()).await;
+            let retry = match &result {
+                Ok(mapped) => {
+                    tracing::info!(
+                        "🌐 Port {} mapped to {} ({:?})",
+                        listen_addr.port(),
+                        mapped.external,
+                        mapped.protocol()
+                    );
+                    Duration::from_secs(u64::from(LEASE_SECS / 2))
+                }
+                Err(e) => {
+                    tracing::warn!("🌐 {}", e);
+                    RETRY_AFTER
+                }
+            };
+            {
+                let mut guard = state.port_mapping.lock().unwrap_or_else(|e| e.into_inner());
+                let Some(slot) = guard.as_mut() else {
+                    // Hosting stopped while we were asking; don't leave the mapping behind.
+                    if let Ok(mapped) = result {
+                        tauri::async_runtime::spawn(unmap(mapped));
+                    }
+                    return;
+                };
+                (slot.mapped, slot.error) = match result {
+                    Ok(mapped) => (Some(mapped), None),
+                    Err(e) => (None, Some(e)),
+                };
+            }
+            let _ = app.emit("remote_access", status(&state, true));
+            tokio::time::sleep(retry).await;
+        }
+    });
+    let previous = state
+        .port_mapping
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .replace(PortMapping {
+            task: Some(task),
+            ..Default::default()
+        });
+    if let Some(handle) = previous.and_then(|m| m.task) {
+        handle.abort();
+    }
+}
+
+/// Host: stop renewing the mapping and remove it from the router (hosting stopped).
+pub(crate) fn stop(state: &AppState) {
+    let previous = state
+        .port_mapping
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    let Some(previous) = previous else {
+        return;
+    };
+    if let Some(handle) = previous.task {
+        handle.abort();
+    }
+    if let Some(mapped) = previous.mapped {
+        tauri::async_runtime::spawn(unmap(mapped));
+    }
+}
+
+#[tauri::command]
+pub async fn get_remote_access(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+) -> AppResult<RemoteAccess> {
+    let enabled = load_config(&db).await?.enabled;
+    Ok(status(&state, enabled))
+}
+
+/// Allow or stop remote access. While hosting, the port is mapped (or unmapped) right away; the
+/// outcome arrives as a `remote_access` event.
+#[tauri::command]
+pub async fn set_remote_access(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    enabled: bool,
+) -> AppResult<()> {
+    let json = serde_json::to_string(&RemoteAccessConfig { enabled })
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    let listen_addr = *state.server_addr.read().await;
+    match listen_addr {
+        Some(addr) if enabled && *state.is_server.read().await => {
+            start(app, Arc::clone(state.inner()), db.inner().clone(), addr)
+        }
+        _ => {
+            stop(&state);
+            let _ = app.emit("remote_access", status(&state, enabled));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natpmp_messages_round_trip() {
+        assert_eq!(
+            natpmp_map_request(3625, 3625, 3600),
+            [0, 2, 0, 0, 0x0e, 0x29, 0x0e, 0x29, 0, 0, 0x0e, 0x10]
+        );
+        let external = [0, 128, 0, 0, 0, 0, 0, 9, 203, 0, 113, 7];
+        assert_eq!(
+            parse_natpmp_external(&external),
+            Ok(Ipv4Addr::new(203, 0, 113, 7))
+        );
+        let mapping = [
+            0, 130, 0, 0, 0, 0, 0, 9, 0x0e, 0x29, 0x13, 0x88, 0, 0, 0x0e, 0x10,
+        ];
+        assert_eq!(parse_natpmp_mapping(&mapping), Ok(5000));
+        // Result code 2: NAT-PMP is turned off.
+        let refused = [0, 130, 0, 2, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(parse_natpmp_mapping(&refused).is_err());
+        assert!(parse_natpmp_mapping(&external).is_err());
+    }
+
+    #[test]
+    fn default_gateway_is_read_from_the_route_table() {
+        let table = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\n\
+                     eth0\t0000A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\n\
+                     eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\n";
+        assert_eq!(
+            parse_route_table(table),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(parse_route_table("Iface\tDestination\tGateway\n"), None);
+    }
+}
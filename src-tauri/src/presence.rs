@@ -0,0 +1,328 @@
+// Presence: Online / Away.
+//
+// The UI calls `report_activity` on user interaction. A timer marks this user Away once they've
+// been idle for the configured period, and the next activity marks them Online again. Each change
+// goes out as a Presence frame (`message` is "Away" or "Online"): a client sends its own change
+// to the host, which relays it under the connection's user id to every connected client and its
+// own UI. The host's own change takes the same path, as a frame from the hosting user on no
+// connection (sockets::handle_participant_frame). The host remembers who is away, so a client
+// that registers later is sent the current state. The host also keeps `users.is_online` (the
+// directory's online flag) for its clients: set when one connects, and cleared when its
+// connection is gone for good, at which point a Presence of "Offline" goes out so everyone's
+// directory catches up.
+
+use crate::db_queries::{
+    get_setting_internal, set_setting_internal, update_user_online_status_internal,
+};
+use crate::error::{AppError, AppResult};
+use crate::message_events;
+use crate::sockets::{
+    frame, handle_participant_frame, now_secs, send_secure, send_secure_client, send_to_client,
+    AppState, Message, MessageType,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{Manager, Runtime, State};
+
+const SETTINGS_KEY: &str = "presence_config";
+
+pub const AWAY: &str = "Away";
+pub const ONLINE: &str = "Online";
+pub const OFFLINE: &str = "Offline";
+
+/// How often the idle timer checks.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// The longest idle period that can be configured (8 hours).
+const MAX_IDLE_MINUTES: u32 = 8 * 60;
+
+pub fn default_idle_minutes() -> u32 {
+    10
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PresenceConfig {
+    // Minutes without activity before going Away; 0 turns auto-away off.
+    #[serde(default = "default_idle_minutes")]
+    pub idle_minutes: u32,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            idle_minutes: default_idle_minutes(),
+        }
+    }
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<PresenceConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt presence config: {}", e))),
+        None => Ok(PresenceConfig::default()),
+    }
+}
+
+/// Load the saved idle period into AppState at startup (the default if none is saved).
+pub async fn load_into(state: &AppState, pool: &SqlitePool) {
+    match load_config(pool).await {
+        Ok(config) => state
+            .idle_minutes
+            .store(config.idle_minutes, Ordering::Relaxed),
+        Err(e) => tracing::warn!("{}", e),
+    }
+}
+
+/// Whether someone last active at `last_activity` is idle at `now` (both Unix seconds).
+fn is_idle(last_activity: u64, now: u64, idle_minutes: u32) -> bool {
+    idle_minutes > 0 && now.saturating_sub(last_activity) >= u64::from(idle_minutes) * 60
+}
+
+fn presence_frame(user_id: u64, username: &str, away: bool) -> Message {
+    status_frame(user_id, username, if away { AWAY } else { ONLINE })
+}
+
+fn status_frame(user_id: u64, username: &str, status: &str) -> Message {
+    Message {
+        username: username.to_string(),
+        ..frame(MessageType::Presence, user_id, status.to_string())
+    }
+}
+
+/// Host: record `user_id`'s presence and relay it to every connected client and the local UI.
+async fn set_on_host<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    user_id: u64,
+    username: &str,
+    away: bool,
+) {
+    {
+        let mut away_users = state.away_users.lock().await;
+        if away {
+            away_users.insert(user_id, username.to_string());
+        } else {
+            away_users.remove(&user_id);
+        }
+    }
+    relay(app, state, &presence_frame(user_id, username, away)).await;
+}
+
+/// Host: send a Presence frame to every connected client and the local UI.
+async fn relay<R: Runtime>(app: &tauri::AppHandle<R>, state: &Arc<AppState>, msg: &Message) {
+    let conns: Vec<_> = {
+        let streams = state.server_streams.lock().await;
+        streams
+            .values()
+            .map(|c| (Arc::clone(&c.writer), Arc::clone(&c.transport)))
+            .collect()
+    };
+    for (writer, transport) in conns {
+        let _ = send_secure(&writer, &transport, msg).await;
+    }
+    let _ = message_events::emit(app, msg);
+}
+
+/// Host: a client's (or the host's own) Presence frame, applied to the connection's own user.
+pub(crate) async fn handle_client_frame<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    auth_user_id: u64,
+    message: &Message,
+) {
+    let connected = {
+        let streams = state.server_streams.lock().await;
+        streams.get(&auth_user_id).map(|c| c.username.clone())
+    };
+    // The host's own frames come in on no connection (sockets::handle_participant_frame).
+    let username = match connected {
+        Some(name) => name,
+        None if *state.user_id.read().await == Some(auth_user_id) => {
+            state.username.read().await.clone()
+        }
+        None => return,
+    };
+    set_on_host(app, state, auth_user_id, &username, message.message == AWAY).await;
+}
+
+/// Host: tell a newly registered client who is currently away.
+pub(crate) async fn send_snapshot(state: &Arc<AppState>, user_id: u64) {
+    let away: Vec<(u64, String)> = {
+        let away_users = state.away_users.lock().await;
+        away_users
+            .iter()
+            .filter(|(id, _)| **id != user_id)
+            .map(|(id, name)| (*id, name.clone()))
+            .collect()
+    };
+    for (id, name) in away {
+        send_to_client(state, user_id, &presence_frame(id, &name, true)).await;
+    }
+}
+
+async fn store_online(pool: &SqlitePool, user_id: u64, online: bool) {
+    if let Err(e) = update_user_online_status_internal(pool, user_id as i64, online).await {
+        tracing::warn!("{}", e);
+    }
+}
+
+/// Host: a client connected, so it's online in the directory.
+pub(crate) async fn connected(pool: &SqlitePool, user_id: u64) {
+    store_online(pool, user_id, true).await;
+}
+
+/// Host: a client's connection is gone for good (not parked for a resume): it's no longer away,
+/// and it's offline in the DB and in everyone's directory.
+pub(crate) async fn departed<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    user_id: u64,
+    username: &str,
+) {
+    state.away_users.lock().await.remove(&user_id);
+    store_online(pool, user_id, false).await;
+    relay(app, state, &status_frame(user_id, username, OFFLINE)).await;
+}
+
+/// Announce this user's own presence: through the host's own frame path as the host, or to the
+/// host as a client.
+async fn announce(app: &tauri::AppHandle, state: &Arc<AppState>, away: bool) {
+    let Some(user_id) = *state.user_id.read().await else {
+        return;
+    };
+    let username = state.username.read().await.clone();
+    if *state.is_server.read().await {
+        let pool = app.state::<SqlitePool>();
+        handle_participant_frame(
+            app,
+            state,
+            pool.inner(),
+            presence_frame(user_id, &username, away),
+        )
+        .await;
+    } else if let Err(e) =
+        send_secure_client(state, &presence_frame(user_id, &username, away)).await
+    {
+        tracing::debug!("Presence not sent: {}", e);
+    }
+}
+
+/// Client: after (re)connecting, repeat an Away the new host connection hasn't heard.
+pub(crate) async fn reannounce(state: &Arc<AppState>) {
+    if !state.is_away.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(user_id) = *state.user_id.read().await else {
+        return;
+    };
+    let username = state.username.read().await.clone();
+    let _ = send_secure_client(state, &presence_frame(user_id, &username, true)).await;
+}
+
+/// Mark this user Away once idle, for the life of the app.
+pub fn spawn_idle_timer(app: tauri::AppHandle, state: Arc<AppState>) {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let idle = is_idle(
+                state.last_activity_secs.load(Ordering::Relaxed),
+                now_secs(),
+                state.idle_minutes.load(Ordering::Relaxed),
+            );
+            if idle && !state.is_away.swap(true, Ordering::Relaxed) {
+                announce(&app, &state, true).await;
+            }
+        }
+    });
+}
+
+/// The user interacted with the UI: reset the idle clock, and come back Online if Away.
+#[tauri::command]
+pub async fn report_activity(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+) -> AppResult<()> {
+    state
+        .last_activity_secs
+        .store(now_secs(), Ordering::Relaxed);
+    if state.is_away.swap(false, Ordering::Relaxed) {
+        announce(&app, state.inner(), false).await;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_presence_config(db: State<'_, SqlitePool>) -> AppResult<PresenceConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_presence_config(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    config: PresenceConfig,
+) -> AppResult<()> {
+    if config.idle_minutes > MAX_IDLE_MINUTES {
+        return Err(AppError::Validation(format!(
+            "The idle period can be at most {} minutes (0 turns auto-away off)",
+            MAX_IDLE_MINUTES
+        )));
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    state
+        .idle_minutes
+        .store(config.idle_minutes, Ordering::Relaxed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_after_the_configured_minutes() {
+        assert!(!is_idle(1_000, 1_000 + 599, 10));
+        assert!(is_idle(1_000, 1_000 + 600, 10));
+        // 0 turns auto-away off; a clock that went backwards isn't idle.
+        assert!(!is_idle(0, 1_000_000, 0));
+        assert!(!is_idle(2_000, 1_000, 10));
+    }
+
+    #[tokio::test]
+    async fn connections_keep_the_online_flag() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id) VALUES (1, 'Alice', 'a@x', 1)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let online = || async {
+            sqlx::query_scalar::<_, bool>("SELECT is_online FROM users WHERE id = 1")
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        };
+        connected(&pool, 1).await;
+        assert!(online().await);
+        // What `departed` stores, before relaying the Offline frame.
+        store_online(&pool, 1, false).await;
+        assert!(!online().await);
+    }
+}
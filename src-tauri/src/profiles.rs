@@ -0,0 +1,273 @@
+// Local profiles (this machine).
+//
+// Several people can share one install (a front-desk machine, say), each under their own
+// profile. The list of profiles and the active one are kept in the local settings; there's
+// always a "default" profile. The UI keeps its remembered login, preferences and the like under
+// keys namespaced by the active profile, and each profile has its own keychain entry for a
+// remembered password. Switching is only allowed while signed out: it clears what AppState
+// holds about the signed-in person, so nothing carries over to the next profile.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tauri::{Emitter, State};
+use uuid::Uuid;
+
+const SETTINGS_KEY: &str = "profiles";
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Longest profile name kept.
+const MAX_NAME_CHARS: usize = 40;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Profiles {
+    // The default profile first, then the others in the order they were created.
+    pub profiles: Vec<Profile>,
+    pub active: String,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self {
+            profiles: vec![Profile {
+                id: DEFAULT_PROFILE.to_string(),
+                name: "Default".to_string(),
+            }],
+            active: DEFAULT_PROFILE.to_string(),
+        }
+    }
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<Profiles> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt profile list: {}", e))),
+        None => Ok(Profiles::default()),
+    }
+}
+
+async fn save_config(pool: &SqlitePool, profiles: &Profiles) -> AppResult<()> {
+    let json = serde_json::to_string(profiles).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(pool, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+/// Load the active profile into AppState at startup.
+pub async fn load_into(state: &AppState, pool: &SqlitePool) {
+    match load_config(pool).await {
+        Ok(profiles) => *state.active_profile.write().await = profiles.active,
+        Err(e) => tracing::warn!("{}", e),
+    }
+}
+
+pub async fn create_profile_internal(pool: &SqlitePool, name: &str) -> AppResult<Profile> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::Validation("Give the profile a name".to_string()));
+    }
+    let name: String = name.chars().take(MAX_NAME_CHARS).collect();
+    let mut profiles = load_config(pool).await?;
+    if profiles
+        .profiles
+        .iter()
+        .any(|p| p.name.eq_ignore_ascii_case(&name))
+    {
+        return Err(AppError::Conflict(format!(
+            "There's already a profile called {}",
+            name
+        )));
+    }
+    let profile = Profile {
+        id: Uuid::new_v4().to_string(),
+        name,
+    };
+    profiles.profiles.push(profile.clone());
+    save_config(pool, &profiles).await?;
+    Ok(profile)
+}
+
+/// Remove a profile (not the default or the active one). Returns whether it existed.
+pub async fn delete_profile_internal(pool: &SqlitePool, id: &str) -> AppResult<bool> {
+    let mut profiles = load_config(pool).await?;
+    if id == DEFAULT_PROFILE || id == profiles.active {
+        return Err(AppError::Validation(
+            "The default and active profiles can't be deleted".to_string(),
+        ));
+    }
+    let before = profiles.profiles.len();
+    profiles.profiles.retain(|p| p.id != id);
+    if profiles.profiles.len() == before {
+        return Ok(false);
+    }
+    save_config(pool, &profiles).await?;
+    Ok(true)
+}
+
+/// Make `id` the active profile.
+pub async fn switch_profile_internal(pool: &SqlitePool, id: &str) -> AppResult<Profiles> {
+    let mut profiles = load_config(pool).await?;
+    if !profiles.profiles.iter().any(|p| p.id == id) {
+        return Err(AppError::Validation(
+            "That profile doesn't exist".to_string(),
+        ));
+    }
+    profiles.active = id.to_string();
+    save_config(pool, &profiles).await?;
+    Ok(profiles)
+}
+
+/// The keychain entry holding `profile`'s remembered password.
+fn credential_entry(profile: &str) -> AppResult<keyring::Entry> {
+    keyring::Entry::new(
+        crate::db::KEYRING_SERVICE,
+        &format!("profile-{}-credential", profile),
+    )
+    .map_err(|e| AppError::Internal(format!("Keychain unavailable: {}", e)))
+}
+
+#[tauri::command]
+pub async fn list_profiles(db: State<'_, SqlitePool>) -> AppResult<Profiles> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn create_profile(db: State<'_, SqlitePool>, name: String) -> AppResult<Profile> {
+    create_profile_internal(&db, &name).await
+}
+
+/// Delete a profile and its remembered password.
+#[tauri::command]
+pub async fn delete_profile(db: State<'_, SqlitePool>, id: String) -> AppResult<()> {
+    if delete_profile_internal(&db, &id).await? {
+        match credential_entry(&id)?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => tracing::warn!("Couldn't remove profile {}'s password: {}", id, e),
+        }
+    }
+    Ok(())
+}
+
+/// Switch to another profile. Only while signed out (not connected to a host, not hosting, no
+/// extra workspaces open); emits `profile_switched` with the profile list.
+#[tauri::command]
+pub async fn switch_profile(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    id: String,
+) -> AppResult<Profiles> {
+    if state.user_id.read().await.is_some()
+        || *state.is_server.read().await
+        || !state.sessions.lock().await.is_empty()
+    {
+        return Err(AppError::Validation(
+            "Sign out before switching profiles".to_string(),
+        ));
+    }
+    let profiles = switch_profile_internal(&db, &id).await?;
+    *state.active_profile.write().await = profiles.active.clone();
+    // Nothing of the last person's session may carry over.
+    *state.username.write().await = String::new();
+    *state.current_room.write().await = String::new();
+    *state.current_room_id.write().await = None;
+    *state.server_addr.write().await = None;
+    *state.synced_settings.write().await = None;
+    state.is_away.store(false, Ordering::Relaxed);
+    let _ = app.emit("profile_switched", &profiles);
+    Ok(profiles)
+}
+
+/// The active profile's remembered password, if one was saved.
+#[tauri::command]
+pub async fn get_profile_credential(state: State<'_, Arc<AppState>>) -> AppResult<Option<String>> {
+    let profile = state.active_profile.read().await.clone();
+    match credential_entry(&profile)?.get_password() {
+        Ok(password) => Ok(Some(password)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(AppError::Internal(format!(
+            "Failed to read the saved password: {}",
+            e
+        ))),
+    }
+}
+
+/// Remember a password for the active profile, or forget it (None).
+#[tauri::command]
+pub async fn set_profile_credential(
+    state: State<'_, Arc<AppState>>,
+    credential: Option<String>,
+) -> AppResult<()> {
+    let profile = state.active_profile.read().await.clone();
+    let entry = credential_entry(&profile)?;
+    match credential.filter(|c| !c.is_empty()) {
+        Some(password) => entry
+            .set_password(&password)
+            .map_err(|e| AppError::Internal(format!("Failed to save the password: {}", e))),
+        None => match entry.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(AppError::Internal(format!(
+                "Failed to forget the password: {}",
+                e
+            ))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn profiles_are_created_switched_and_deleted() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+
+        assert_eq!(load_config(&pool).await.unwrap(), Profiles::default());
+        let desk = create_profile_internal(&pool, " Front desk ")
+            .await
+            .unwrap();
+        assert_eq!(desk.name, "Front desk");
+        assert!(matches!(
+            create_profile_internal(&pool, "front DESK").await,
+            Err(AppError::Conflict(_))
+        ));
+        assert!(create_profile_internal(&pool, "  ").await.is_err());
+
+        let profiles = switch_profile_internal(&pool, &desk.id).await.unwrap();
+        assert_eq!(profiles.active, desk.id);
+        assert_eq!(profiles.profiles.len(), 2);
+        assert!(switch_profile_internal(&pool, "nope").await.is_err());
+
+        // Neither the active nor the default profile can go.
+        assert!(delete_profile_internal(&pool, &desk.id).await.is_err());
+        assert!(delete_profile_internal(&pool, DEFAULT_PROFILE)
+            .await
+            .is_err());
+        switch_profile_internal(&pool, DEFAULT_PROFILE)
+            .await
+            .unwrap();
+        assert!(delete_profile_internal(&pool, &desk.id).await.unwrap());
+        assert!(!delete_profile_internal(&pool, &desk.id).await.unwrap());
+        assert_eq!(load_config(&pool).await.unwrap(), Profiles::default());
+    }
+}
@@ -0,0 +1,259 @@
+// Delivery reach for announcements (host-side).
+//
+// A chat message that mentions @room is an announcement. Before the host fans one out it writes a
+// receipt row for every connected user allowed in the room (except the sender). A client's
+// listener acknowledges each announcement frame it receives with a DeliveryAck, which marks that
+// user's row delivered. Anyone connected but looking at another room never gets the frame, so
+// their row stays undelivered — `get_announcement_reach` lists both sides for the host.
+
+use crate::db_queries::room_join_allowed_internal;
+use crate::error::{AppError, AppResult};
+use crate::sockets::{edit_event, send_secure_client, AppState, Message, MessageType};
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::sync::Arc;
+use tauri::{Manager, State};
+
+/// Whether `text` mentions the whole room: `@room` as a token of its own (not part of an email
+/// address or a longer name like `@roomba`), in any case.
+pub fn mentions_room(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let is_word = |b: &u8| b.is_ascii_alphanumeric() || *b == b'_';
+    let is_name = |b: &u8| is_word(b) || matches!(b, b'.' | b'-');
+    bytes.windows(5).enumerate().any(|(i, w)| {
+        // A '.' or '-' right after only continues the name if more of it follows ("@room.").
+        let ends = match bytes.get(i + 5) {
+            Some(b'.' | b'-') => !bytes.get(i + 6).is_some_and(is_word),
+            next => !next.is_some_and(is_word),
+        };
+        w.eq_ignore_ascii_case(b"@room") && (i == 0 || !is_name(&bytes[i - 1])) && ends
+    })
+}
+
+fn is_announcement(message: &Message) -> bool {
+    message.message_type == MessageType::Chat && mentions_room(&message.message)
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ReachEntry {
+    pub user_id: i64,
+    pub username: String,
+    // When the ack arrived; None in the undelivered list.
+    pub delivered_at: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AnnouncementReach {
+    pub message_id: String,
+    pub delivered: Vec<ReachEntry>,
+    pub undelivered: Vec<ReachEntry>,
+}
+
+/// Record who an announcement is expected to reach.
+pub async fn record_recipients_internal(
+    pool: &SqlitePool,
+    message_id: &str,
+    recipients: &[(u64, String)],
+) -> Result<(), String> {
+    for (user_id, username) in recipients {
+        sqlx::query(
+            "INSERT INTO announcement_receipts (message_id, user_id, username)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (message_id, user_id) DO NOTHING",
+        )
+        .bind(message_id)
+        .bind(*user_id as i64)
+        .bind(username)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to record announcement recipients: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Mark `user_id`'s copy of an announcement delivered. Acks for anything that wasn't tracked
+/// for that user are ignored.
+pub async fn acknowledge_internal(
+    pool: &SqlitePool,
+    message_id: &str,
+    user_id: u64,
+) -> Result<(), String> {
+    sqlx::query(
+        "UPDATE announcement_receipts SET delivered_at = CURRENT_TIMESTAMP
+          WHERE message_id = $1 AND user_id = $2 AND delivered_at IS NULL",
+    )
+    .bind(message_id)
+    .bind(user_id as i64)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to record delivery: {}", e))?;
+    Ok(())
+}
+
+pub async fn get_announcement_reach_internal(
+    pool: &SqlitePool,
+    message_id: &str,
+) -> AppResult<AnnouncementReach> {
+    let rows = sqlx::query(
+        "SELECT user_id, username, delivered_at FROM announcement_receipts
+         WHERE message_id = $1 ORDER BY username COLLATE NOCASE, user_id",
+    )
+    .bind(message_id)
+    .fetch_all(pool)
+    .await?;
+    if rows.is_empty() {
+        let exists: Option<i64> =
+            sqlx::query_scalar("SELECT id FROM messages WHERE message_id = $1")
+                .bind(message_id)
+                .fetch_optional(pool)
+                .await?;
+        if exists.is_none() {
+            return Err(AppError::Validation(
+                "That message doesn't exist".to_string(),
+            ));
+        }
+    }
+    let (delivered, undelivered) = rows
+        .iter()
+        .map(|row| ReachEntry {
+            user_id: row.get("user_id"),
+            username: row.get("username"),
+            delivered_at: row.get("delivered_at"),
+        })
+        .partition(|e| e.delivered_at.is_some());
+    Ok(AnnouncementReach {
+        message_id: message_id.to_string(),
+        delivered,
+        undelivered,
+    })
+}
+
+/// Host: if `message` is an announcement, record every connected user allowed in its room
+/// (but `exclude_user_id`, the sender) as a recipient. Runs before the fan-out so no ack can
+/// arrive ahead of its row.
+pub(crate) async fn track(
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    message: &Message,
+    exclude_user_id: Option<u64>,
+) {
+    if !is_announcement(message) {
+        return;
+    }
+    let connected: Vec<(u64, String)> = {
+        let streams = state.server_streams.lock().await;
+        streams
+            .iter()
+            .filter(|(id, _)| Some(**id) != exclude_user_id)
+            .map(|(id, c)| (*id, c.username.clone()))
+            .collect()
+    };
+    let mut recipients = Vec::with_capacity(connected.len());
+    for (id, name) in connected {
+        if room_join_allowed_internal(pool, id as i64, message.room_id as i64)
+            .await
+            .unwrap_or(false)
+        {
+            recipients.push((id, name));
+        }
+    }
+    if let Err(e) = record_recipients_internal(pool, &message.message_id, &recipients).await {
+        tracing::warn!("{}", e);
+    }
+}
+
+/// Client (primary connection): acknowledge an announcement frame from the host. `frame` is the
+/// raw JSON; anything without an @room in it is skipped before parsing.
+pub(crate) async fn acknowledge(app: &tauri::AppHandle, frame: &str) {
+    if !frame
+        .as_bytes()
+        .windows(5)
+        .any(|w| w.eq_ignore_ascii_case(b"@room"))
+    {
+        return;
+    }
+    let Ok(msg) = serde_json::from_str::<Message>(frame) else {
+        return;
+    };
+    if !is_announcement(&msg) {
+        return;
+    }
+    let Some(state) = app.try_state::<Arc<AppState>>() else {
+        return;
+    };
+    let user_id = state.user_id.read().await.unwrap_or(0);
+    let username = state.username.read().await.clone();
+    let ack = edit_event(
+        username,
+        user_id,
+        msg.message_id,
+        String::new(),
+        msg.room,
+        msg.room_id,
+        MessageType::DeliveryAck,
+    );
+    if let Err(e) = send_secure_client(state.inner(), &ack).await {
+        tracing::debug!("Delivery ack not sent: {}", e);
+    }
+}
+
+/// Who an announcement reached, and which connected users it didn't.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_announcement_reach(
+    db: State<'_, SqlitePool>,
+    message_id: String,
+) -> AppResult<AnnouncementReach> {
+    get_announcement_reach_internal(&db, &message_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[test]
+    fn room_mentions_are_whole_tokens() {
+        assert!(mentions_room("@room standup in 5"));
+        assert!(mentions_room("heads up, @Room!"));
+        assert!(mentions_room("(@ROOM)"));
+        assert!(mentions_room("deploy at 5, @room."));
+        assert!(!mentions_room("@room.ops"));
+        assert!(!mentions_room("ops@room.example"));
+        assert!(!mentions_room("@roomba is loose"));
+        assert!(!mentions_room("@rooms"));
+        assert!(!mentions_room("room"));
+    }
+
+    #[tokio::test]
+    async fn reach_splits_acked_from_missed() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1), (3, 'Carol', 'c@x', 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let recipients = [(2, "Bob".to_string()), (3, "Carol".to_string())];
+        record_recipients_internal(&pool, "m1", &recipients)
+            .await
+            .unwrap();
+        acknowledge_internal(&pool, "m1", 3).await.unwrap();
+        // Acks for untracked messages or users change nothing.
+        acknowledge_internal(&pool, "m1", 1).await.unwrap();
+        acknowledge_internal(&pool, "other", 2).await.unwrap();
+
+        let reach = get_announcement_reach_internal(&pool, "m1").await.unwrap();
+        let names = |v: &[ReachEntry]| v.iter().map(|e| e.username.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&reach.delivered), ["Carol"]);
+        assert_eq!(names(&reach.undelivered), ["Bob"]);
+        assert!(get_announcement_reach_internal(&pool, "nope")
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,331 @@
+// Who-read-what reports for compliance rooms (host-side).
+//
+// A room the host designates for compliance gets a read report for each announcement (a
+// message mentioning @room, see reach.rs): every member of the room but the author, split into
+// those who have read it and those who haven't. "Read" comes from the read receipts the room
+// already keeps: a member has read an announcement once their `user_rooms.last_read_at` for the
+// room is at or past it. Delivery to their device (`announcement_receipts`) is shown alongside,
+// for members who were connected when it went out. The report can be saved as CSV, one row per
+// announcement and member.
+
+use crate::db_queries::record_audit_internal;
+use crate::directory_csv::spreadsheet_cell;
+use crate::error::{AppError, AppResult};
+use crate::reach::mentions_room;
+use crate::sockets::AppState;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tauri::State;
+
+/// Most announcements in one report (the newest).
+const MAX_ANNOUNCEMENTS: usize = 100;
+const SNIPPET_CHARS: usize = 120;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MemberRead {
+    pub user_id: i64,
+    pub name: String,
+    // When they last read the room; at or past the announcement for a reader.
+    pub last_read_at: Option<String>,
+    // When their device acknowledged the announcement, if it was tracked.
+    pub delivered_at: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AnnouncementReads {
+    pub message_id: String,
+    pub author: String,
+    pub created_at: String,
+    pub snippet: String,
+    pub read: Vec<MemberRead>,
+    pub unread: Vec<MemberRead>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ReadReport {
+    pub room_id: i64,
+    pub room: String,
+    // Newest first.
+    pub announcements: Vec<AnnouncementReads>,
+}
+
+fn snippet(text: &str) -> String {
+    if text.chars().count() <= SNIPPET_CHARS {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(SNIPPET_CHARS).collect();
+        format!("{}…", cut.trim_end())
+    }
+}
+
+/// Who has and hasn't read the announcements in compliance room `room_id`: just `message_id`,
+/// or the newest MAX_ANNOUNCEMENTS.
+pub(crate) async fn read_report_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    message_id: Option<&str>,
+) -> AppResult<ReadReport> {
+    let (room, compliance): (String, bool) =
+        sqlx::query_as("SELECT name, compliance FROM chat_rooms WHERE id = $1")
+            .bind(room_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::Validation("That room doesn't exist".to_string()))?;
+    if !compliance {
+        return Err(AppError::Validation(format!(
+            "#{} isn't a compliance room",
+            room
+        )));
+    }
+
+    // `LIKE` narrows to candidates; mentions_room decides.
+    let candidates: Vec<(String, i64, String, String, String)> = sqlx::query_as(
+        "SELECT m.message_id, m.user_id, COALESCE(u.name, 'Unknown'), m.message, m.created_at
+         FROM messages m
+         LEFT JOIN users u ON u.id = m.user_id
+         WHERE m.room_id = $1 AND m.message_type = 'Chat' AND m.deleted_at IS NULL
+           AND m.message_id IS NOT NULL AND m.message LIKE '%@room%'
+           AND ($2 IS NULL OR m.message_id = $2)
+         ORDER BY m.created_at DESC, m.id DESC",
+    )
+    .bind(room_id)
+    .bind(message_id)
+    .fetch_all(pool)
+    .await?;
+    let announcements: Vec<_> = candidates
+        .into_iter()
+        .filter(|(_, _, _, message, _)| mentions_room(message))
+        .take(MAX_ANNOUNCEMENTS)
+        .collect();
+    if message_id.is_some() && announcements.is_empty() {
+        return Err(AppError::Validation(
+            "That message isn't an announcement in this room".to_string(),
+        ));
+    }
+
+    let members: Vec<(i64, String, Option<String>)> = sqlx::query_as(
+        "SELECT u.id, u.name, ur.last_read_at
+         FROM user_rooms ur JOIN users u ON u.id = ur.user_id
+         WHERE ur.room_id = $1
+         ORDER BY u.name COLLATE NOCASE, u.id",
+    )
+    .bind(room_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut report = Vec::with_capacity(announcements.len());
+    for (message_id, author_id, author, message, created_at) in announcements {
+        let delivered: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT user_id, delivered_at FROM announcement_receipts
+             WHERE message_id = $1 AND delivered_at IS NOT NULL",
+        )
+        .bind(&message_id)
+        .fetch_all(pool)
+        .await?;
+        let (read, unread) = members
+            .iter()
+            .filter(|(user_id, _, _)| *user_id != author_id)
+            .map(|(user_id, name, last_read_at)| MemberRead {
+                user_id: *user_id,
+                name: name.clone(),
+                last_read_at: last_read_at.clone(),
+                delivered_at: delivered
+                    .iter()
+                    .find(|(id, _)| id == user_id)
+                    .map(|(_, at)| at.clone()),
+            })
+            .partition(|m| {
+                m.last_read_at
+                    .as_deref()
+                    .is_some_and(|at| at >= created_at.as_str())
+            });
+        report.push(AnnouncementReads {
+            message_id,
+            author,
+            created_at,
+            snippet: snippet(&message),
+            read,
+            unread,
+        });
+    }
+    Ok(ReadReport {
+        room_id,
+        room,
+        announcements: report,
+    })
+}
+
+/// The report as CSV: one row per announcement and member. Names and snippets are escaped
+/// against spreadsheet formulas.
+fn to_csv(report: &ReadReport) -> AppResult<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let csv_err = |e: csv::Error| AppError::Internal(format!("Failed to write CSV: {}", e));
+    writer
+        .write_record([
+            "room",
+            "message_id",
+            "announced_at",
+            "author",
+            "announcement",
+            "member",
+            "read",
+            "last_read_at",
+            "delivered_at",
+        ])
+        .map_err(csv_err)?;
+    for a in &report.announcements {
+        let rows = a
+            .read
+            .iter()
+            .map(|m| (m, "yes"))
+            .chain(a.unread.iter().map(|m| (m, "no")));
+        for (member, read) in rows {
+            writer
+                .write_record([
+                    spreadsheet_cell(&report.room),
+                    spreadsheet_cell(&a.message_id),
+                    spreadsheet_cell(&a.created_at),
+                    spreadsheet_cell(&a.author),
+                    spreadsheet_cell(&a.snippet),
+                    spreadsheet_cell(&member.name),
+                    read.into(),
+                    member.last_read_at.as_deref().unwrap_or("").into(),
+                    member.delivered_at.as_deref().unwrap_or("").into(),
+                ])
+                .map_err(csv_err)?;
+        }
+    }
+    writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("Failed to write CSV: {}", e)))
+}
+
+/// Designate a room for compliance read reports (or stop).
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_compliance_room(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    enabled: bool,
+) -> AppResult<()> {
+    let name: String = sqlx::query_scalar(
+        "UPDATE chat_rooms SET compliance = $1 WHERE id = $2 AND is_dm = 0 RETURNING name",
+    )
+    .bind(enabled)
+    .bind(room_id)
+    .fetch_optional(db.inner())
+    .await?
+    .ok_or_else(|| AppError::Validation("That room doesn't exist".to_string()))?;
+    let action = if enabled {
+        "compliance.designate"
+    } else {
+        "compliance.release"
+    };
+    let actor = state.user_id.read().await.map(|id| id as i64);
+    record_audit_internal(&db, actor, action, &name, None)
+        .await
+        .map_err(AppError::Db)
+}
+
+/// Who has and hasn't read a compliance room's announcements (or just `message_id`).
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_read_report(
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    message_id: Option<String>,
+) -> AppResult<ReadReport> {
+    read_report_internal(&db, room_id, message_id.as_deref()).await
+}
+
+/// Save the read report as CSV at `path`. Returns how many rows it holds.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_read_report_csv(
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    message_id: Option<String>,
+    path: String,
+) -> AppResult<usize> {
+    let report = read_report_internal(&db, room_id, message_id.as_deref()).await?;
+    let rows = report
+        .announcements
+        .iter()
+        .map(|a| a.read.len() + a.unread.len())
+        .sum();
+    tokio::fs::write(&path, to_csv(&report)?)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to save {}: {}", path, e)))?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn announcements_split_readers_from_the_rest() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1), (3, 'Carol', 'c@x', 1);
+             INSERT INTO chat_rooms (id, name, is_private, created_by) VALUES (100, 'policy', 1, 1);
+             INSERT INTO user_rooms (user_id, room_id, is_active, last_read_at) VALUES
+                 (1, 100, 1, '2026-03-01 09:00:00'),
+                 (2, 100, 1, '2026-03-02 12:00:00'),
+                 (3, 100, 1, NULL);
+             INSERT INTO messages (room_id, user_id, message, message_type, message_id, created_at) VALUES
+                 (100, 1, '@room new travel policy', 'Chat', 'a', '2026-03-02 10:00:00'),
+                 (100, 1, 'email me at x@roomba.io', 'Chat', 'b', '2026-03-02 11:00:00'),
+                 (100, 1, '@room reminder: sign by Friday', 'Chat', 'c', '2026-03-03 10:00:00');
+             INSERT INTO announcement_receipts (message_id, user_id, username, delivered_at)
+                 VALUES ('a', 3, 'Carol', '2026-03-02 10:00:01');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // Not designated yet.
+        assert!(read_report_internal(&pool, 100, None).await.is_err());
+        sqlx::query("UPDATE chat_rooms SET compliance = 1 WHERE id = 100")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let report = read_report_internal(&pool, 100, None).await.unwrap();
+        let ids: Vec<&str> = report
+            .announcements
+            .iter()
+            .map(|a| a.message_id.as_str())
+            .collect();
+        assert_eq!(ids, ["c", "a"]);
+        let names = |members: &[MemberRead]| -> Vec<String> {
+            members.iter().map(|m| m.name.clone()).collect()
+        };
+        let travel = &report.announcements[1];
+        assert_eq!(names(&travel.read), ["Bob"]);
+        assert_eq!(names(&travel.unread), ["Carol"]);
+        assert_eq!(
+            travel.unread[0].delivered_at.as_deref(),
+            Some("2026-03-02 10:00:01")
+        );
+        assert!(report.announcements[0].read.is_empty());
+
+        let one = read_report_internal(&pool, 100, Some("a")).await.unwrap();
+        assert_eq!(one.announcements.len(), 1);
+        assert!(read_report_internal(&pool, 100, Some("b")).await.is_err());
+
+        let csv = String::from_utf8(to_csv(&report).unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 1 + 4);
+        assert!(lines[0].starts_with("room,message_id,announced_at"));
+        assert!(csv.contains(",Bob,yes,2026-03-02 12:00:00,"));
+        // An @room announcement would read as a formula in a spreadsheet.
+        assert!(csv.contains(",'@room new travel policy,Bob,"));
+    }
+}
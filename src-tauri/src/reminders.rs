@@ -0,0 +1,221 @@
+// Message reminders.
+//
+// "Remind me about this" on a message stores a row in this machine's own database (reminders are
+// personal, so the host never sees them). A scheduler checks for due reminders every half
+// minute; each one fires once — a `reminder` event the UI turns into a jump back to the message,
+// plus an OS notification — and is then removed. Reminders that came due while the app was
+// closed fire on the next start.
+
+use crate::error::{AppError, AppResult};
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use std::time::Duration;
+use tauri::{Emitter, State};
+use tauri_plugin_notification::NotificationExt;
+
+/// How often due reminders are checked for.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Furthest ahead a reminder may be set.
+const MAX_AHEAD_SECS: i64 = 366 * 24 * 60 * 60;
+
+/// Longest message excerpt kept with a reminder.
+const PREVIEW_CHARS: usize = 140;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Reminder {
+    pub id: i64,
+    pub message_id: String,
+    pub room_id: i64,
+    pub room: String,
+    pub preview: String,
+    // UTC, "YYYY-MM-DD HH:MM:SS".
+    pub remind_at: String,
+}
+
+fn from_row(row: &sqlx::sqlite::SqliteRow) -> Reminder {
+    Reminder {
+        id: row.get("id"),
+        message_id: row.get("message_id"),
+        room_id: row.get("room_id"),
+        room: row.get("room"),
+        preview: row.get("preview"),
+        remind_at: row.get("remind_at"),
+    }
+}
+
+/// Store a reminder for `at` (Unix seconds), which must be in the future and within a year of
+/// `now`.
+pub async fn remind_me_internal(
+    pool: &SqlitePool,
+    message_id: &str,
+    at: i64,
+    now: i64,
+    room_id: i64,
+    room: &str,
+    preview: &str,
+) -> AppResult<Reminder> {
+    if message_id.trim().is_empty() {
+        return Err(AppError::Validation(
+            "No message to be reminded of".to_string(),
+        ));
+    }
+    if at <= now {
+        return Err(AppError::Validation(
+            "Pick a time in the future".to_string(),
+        ));
+    }
+    if at - now > MAX_AHEAD_SECS {
+        return Err(AppError::Validation(
+            "Reminders can be set at most a year ahead".to_string(),
+        ));
+    }
+    let preview: String = preview.trim().chars().take(PREVIEW_CHARS).collect();
+    let row = sqlx::query(
+        "INSERT INTO reminders (message_id, room_id, room, preview, remind_at)
+         VALUES ($1, $2, $3, $4, datetime($5, 'unixepoch'))
+         RETURNING id, message_id, room_id, room, preview, remind_at",
+    )
+    .bind(message_id)
+    .bind(room_id)
+    .bind(room)
+    .bind(&preview)
+    .bind(at)
+    .fetch_one(pool)
+    .await?;
+    Ok(from_row(&row))
+}
+
+/// Pending reminders, soonest first.
+pub async fn list_reminders_internal(pool: &SqlitePool) -> AppResult<Vec<Reminder>> {
+    let rows = sqlx::query(
+        "SELECT id, message_id, room_id, room, preview, remind_at
+         FROM reminders ORDER BY remind_at, id",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.iter().map(from_row).collect())
+}
+
+/// Remove and return every reminder that is due. Deleting in the same statement means a
+/// reminder can only ever be handed out once.
+pub async fn take_due_internal(pool: &SqlitePool) -> Result<Vec<Reminder>, String> {
+    let rows = sqlx::query(
+        "DELETE FROM reminders WHERE remind_at <= datetime('now')
+         RETURNING id, message_id, room_id, room, preview, remind_at",
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to read due reminders: {}", e))?;
+    let mut due: Vec<Reminder> = rows.iter().map(from_row).collect();
+    due.sort_by(|a, b| a.remind_at.cmp(&b.remind_at).then(a.id.cmp(&b.id)));
+    Ok(due)
+}
+
+fn fire(app: &tauri::AppHandle, reminder: &Reminder) {
+    if let Err(e) = app.emit("reminder", reminder) {
+        tracing::warn!("Failed to emit reminder: {}", e);
+    }
+    let body = if reminder.preview.is_empty() {
+        "You asked to be reminded about a message".to_string()
+    } else {
+        reminder.preview.clone()
+    };
+    if let Err(e) = app
+        .notification()
+        .builder()
+        .title(format!("Reminder · #{}", reminder.room))
+        .body(body)
+        .show()
+    {
+        tracing::warn!("Failed to show reminder notification: {}", e);
+    }
+}
+
+/// Fire due reminders on a fixed interval for the life of the app (first pass at startup).
+pub fn spawn_scheduler(app: tauri::AppHandle, pool: SqlitePool) {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            match take_due_internal(&pool).await {
+                Ok(due) => due.iter().for_each(|r| fire(&app, r)),
+                Err(e) => tracing::warn!("{}", e),
+            }
+        }
+    });
+}
+
+/// Be reminded about a message at `at` (Unix seconds). The room and an excerpt come from the UI,
+/// which already has the message (a client keeps no local copy to look it up in).
+#[tauri::command(rename_all = "snake_case")]
+pub async fn remind_me(
+    db: State<'_, SqlitePool>,
+    message_id: String,
+    at: i64,
+    room_id: i64,
+    room: String,
+    preview: String,
+) -> AppResult<Reminder> {
+    let now = crate::sockets::now_secs() as i64;
+    remind_me_internal(&db, &message_id, at, now, room_id, &room, &preview).await
+}
+
+#[tauri::command]
+pub async fn list_reminders(db: State<'_, SqlitePool>) -> AppResult<Vec<Reminder>> {
+    list_reminders_internal(&db).await
+}
+
+#[tauri::command]
+pub async fn cancel_reminder(db: State<'_, SqlitePool>, id: i64) -> AppResult<()> {
+    let deleted = sqlx::query("DELETE FROM reminders WHERE id = $1")
+        .bind(id)
+        .execute(db.inner())
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(AppError::Validation(
+            "That reminder no longer exists".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn due_reminders_fire_once() {
+        let pool = setup().await;
+        let now = crate::sockets::now_secs() as i64;
+        assert!(
+            remind_me_internal(&pool, "m1", now - 5, now, 1, "general", "hi")
+                .await
+                .is_err()
+        );
+        let later = remind_me_internal(&pool, "m2", now + 3600, now, 1, "general", "later")
+            .await
+            .unwrap();
+        // Set "in the future" relative to an earlier clock, so it is already due.
+        let due = remind_me_internal(&pool, "m1", now - 5, now - 60, 1, "general", "deploy")
+            .await
+            .unwrap();
+        assert_eq!(list_reminders_internal(&pool).await.unwrap().len(), 2);
+
+        assert_eq!(take_due_internal(&pool).await.unwrap(), vec![due]);
+        assert!(take_due_internal(&pool).await.unwrap().is_empty());
+        assert_eq!(list_reminders_internal(&pool).await.unwrap(), vec![later]);
+    }
+}
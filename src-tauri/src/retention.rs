@@ -0,0 +1,319 @@
+// Message retention and legal hold (host-side).
+//
+// A global policy (off by default) deletes messages older than N days; each room can override
+// it with its own period (0 = keep forever) or fall back to the global one. A room under legal
+// hold is never pruned and its messages can't be deleted, whatever the policy says. Hold and
+// retention changes, and every prune that removed something, are written to the audit log.
+//
+// The global policy is JSON in `app_settings`; per-room settings live on `chat_rooms`.
+
+use crate::db_queries::{get_setting_internal, record_audit_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+const SETTINGS_KEY: &str = "retention_config";
+
+/// How often the pruner runs.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RetentionConfig {
+    // None = keep messages forever (unless a room sets its own period).
+    #[serde(default)]
+    pub days: Option<u32>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct PruneReport {
+    pub messages: u64,
+    pub reactions: u64,
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<RetentionConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt retention config: {}", e))),
+        None => Ok(RetentionConfig::default()),
+    }
+}
+
+/// The acting host user, for the audit log.
+async fn actor(state: &AppState) -> Option<i64> {
+    state.user_id.read().await.map(|id| id as i64)
+}
+
+async fn room_name(pool: &SqlitePool, room_id: i64) -> AppResult<String> {
+    sqlx::query_scalar("SELECT name FROM chat_rooms WHERE id = $1")
+        .bind(room_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?
+        .ok_or_else(|| AppError::Validation("Room not found".to_string()))
+}
+
+/// Delete messages past their room's effective retention period, skipping rooms under legal
+/// hold, then drop reactions and queued digest entries left pointing at them.
+pub async fn prune_expired_internal(
+    pool: &SqlitePool,
+    global_days: Option<u32>,
+) -> Result<PruneReport, String> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to prune messages: {}", e))?;
+    let messages = sqlx::query(
+        "DELETE FROM messages WHERE id IN (
+             SELECT m.id FROM messages m JOIN chat_rooms cr ON cr.id = m.room_id
+             WHERE cr.legal_hold = 0
+               AND COALESCE(cr.retention_days, $1) > 0
+               AND m.created_at < datetime('now', '-' || COALESCE(cr.retention_days, $1) || ' days'))",
+    )
+    .bind(global_days.map(i64::from))
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to prune messages: {}", e))?
+    .rows_affected();
+    let reactions = sqlx::query(
+        "DELETE FROM reactions
+         WHERE NOT EXISTS (SELECT 1 FROM messages m WHERE m.message_id = reactions.message_id)",
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to prune reactions: {}", e))?
+    .rows_affected();
+    sqlx::query(
+        "DELETE FROM email_outbox
+         WHERE NOT EXISTS (SELECT 1 FROM messages m WHERE m.message_id = email_outbox.message_id)",
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to prune email outbox: {}", e))?;
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to prune messages: {}", e))?;
+    Ok(PruneReport {
+        messages,
+        reactions,
+    })
+}
+
+async fn prune_and_audit(pool: &SqlitePool, actor: Option<i64>) -> AppResult<PruneReport> {
+    let config = load_config(pool).await?;
+    let report = prune_expired_internal(pool, config.days)
+        .await
+        .map_err(AppError::Db)?;
+    if report.messages > 0 {
+        let detail = format!(
+            "{} messages, {} reactions",
+            report.messages, report.reactions
+        );
+        record_audit_internal(pool, actor, "retention.prune", "messages", Some(&detail))
+            .await
+            .map_err(AppError::Db)?;
+    }
+    Ok(report)
+}
+
+/// Prune on a fixed interval for the life of the app (first pass shortly after startup).
+pub fn spawn_pruner(pool: SqlitePool) {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = prune_and_audit(&pool, None).await {
+                tracing::warn!("Retention prune failed: {}", e);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_retention_policy(db: State<'_, SqlitePool>) -> AppResult<RetentionConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_retention_policy(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    config: RetentionConfig,
+) -> AppResult<()> {
+    if config.days == Some(0) {
+        return Err(AppError::Validation(
+            "Retention must be at least one day".to_string(),
+        ));
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    let detail = match config.days {
+        Some(days) => format!("{} days", days),
+        None => "keep forever".to_string(),
+    };
+    record_audit_internal(
+        &db,
+        actor(&state).await,
+        "retention.global",
+        "all rooms",
+        Some(&detail),
+    )
+    .await
+    .map_err(AppError::Db)
+}
+
+/// Override the retention period for one room. `days`: None = follow the global policy,
+/// 0 = keep forever.
+#[tauri::command]
+pub async fn set_room_retention(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    days: Option<u32>,
+) -> AppResult<()> {
+    let name = room_name(&db, room_id).await?;
+    sqlx::query("UPDATE chat_rooms SET retention_days = $1 WHERE id = $2")
+        .bind(days.map(i64::from))
+        .bind(room_id)
+        .execute(db.inner())
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    let detail = match days {
+        Some(0) => "keep forever".to_string(),
+        Some(days) => format!("{} days", days),
+        None => "global policy".to_string(),
+    };
+    record_audit_internal(
+        &db,
+        actor(&state).await,
+        "retention.room",
+        &name,
+        Some(&detail),
+    )
+    .await
+    .map_err(AppError::Db)
+}
+
+/// Place a room under legal hold (or release it). A reason is required to place a hold.
+#[tauri::command]
+pub async fn set_legal_hold(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    enabled: bool,
+    reason: Option<String>,
+) -> AppResult<()> {
+    let reason = reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if enabled && reason.is_none() {
+        return Err(AppError::Validation(
+            "A reason is required to place a legal hold".to_string(),
+        ));
+    }
+    let name = room_name(&db, room_id).await?;
+    sqlx::query("UPDATE chat_rooms SET legal_hold = $1 WHERE id = $2")
+        .bind(enabled)
+        .bind(room_id)
+        .execute(db.inner())
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    let action = if enabled {
+        "legal_hold.place"
+    } else {
+        "legal_hold.release"
+    };
+    record_audit_internal(&db, actor(&state).await, action, &name, reason.as_deref())
+        .await
+        .map_err(AppError::Db)
+}
+
+/// Run the pruner now rather than waiting for the next pass.
+#[tauri::command]
+pub async fn prune_now(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+) -> AppResult<PruneReport> {
+    prune_and_audit(&db, actor(&state).await).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_queries::delete_message_db;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    // Room 1 is seeded by the migrations; add an HR room and age one message in each.
+    async fn setup() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id) VALUES (1, 'Alice', 'a@x', 1);
+             INSERT INTO chat_rooms (id, name, created_by) VALUES (90, 'HR', 1);
+             INSERT INTO messages (room_id, user_id, message, message_type, message_id, created_at)
+                 VALUES (1, 1, 'old', 'Chat', 'm1', datetime('now', '-40 days')),
+                        (1, 1, 'new', 'Chat', 'm2', datetime('now')),
+                        (90, 1, 'old', 'Chat', 'm3', datetime('now', '-40 days'));
+             INSERT INTO reactions (message_id, user_id, emoji) VALUES ('m1', 1, '👍');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    async fn remaining(pool: &SqlitePool) -> Vec<String> {
+        sqlx::query_scalar("SELECT message_id FROM messages ORDER BY message_id")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn room_override_beats_global_policy() {
+        let pool = setup().await;
+        assert_eq!(
+            prune_expired_internal(&pool, None).await.unwrap(),
+            PruneReport::default()
+        );
+        // HR keeps forever; room 1 follows the 30-day global policy.
+        sqlx::query("UPDATE chat_rooms SET retention_days = 0 WHERE id = 90")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let report = prune_expired_internal(&pool, Some(30)).await.unwrap();
+        assert_eq!(
+            report,
+            PruneReport {
+                messages: 1,
+                reactions: 1
+            }
+        );
+        assert_eq!(remaining(&pool).await, ["m2", "m3"]);
+    }
+
+    #[tokio::test]
+    async fn legal_hold_blocks_pruning_and_deletion() {
+        let pool = setup().await;
+        sqlx::query("UPDATE chat_rooms SET legal_hold = 1, retention_days = 7 WHERE id = 90")
+            .execute(&pool)
+            .await
+            .unwrap();
+        prune_expired_internal(&pool, Some(30)).await.unwrap();
+        assert_eq!(remaining(&pool).await, ["m2", "m3"]);
+        assert!(delete_message_db(&pool, "m3", 1).await.is_err());
+        assert_eq!(delete_message_db(&pool, "m2", 1).await, Ok(1));
+    }
+}
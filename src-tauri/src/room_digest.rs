@@ -0,0 +1,794 @@
+// Room digests: a summary of a room over the last day, week or month.
+//
+// A digest counts the room's posted messages (in all and per person and day), picks out its
+// busiest conversations and most-reacted messages, and lists the files shared. The room has no
+// threads, so a "conversation" is a run of messages with no gap longer than CONVERSATION_GAP.
+// It can be looked at as data, saved as a standalone HTML page, or posted into the room as a
+// chat message from the "Digest" bot user.
+//
+// The host can also post a weekly digest automatically: every Monday (UTC), from the configured
+// hour, to each room on the schedule that had any messages that week.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal, upsert_user_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::{frame, publish_host_message, AppState, Message, MessageType};
+use crate::{attachments, contacts, location, stickers};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+const SCHEDULE_KEY: &str = "room_digest";
+// The UTC date the last scheduled digests went out, so each Monday posts once.
+const LAST_POSTED_KEY: &str = "room_digest_last";
+
+// The bot user digests are posted as. `.invalid` can't collide with a real address.
+const BOT_NAME: &str = "Digest";
+const BOT_EMAIL: &str = "digest@digest.invalid";
+
+/// Messages further apart than this belong to different conversations.
+const CONVERSATION_GAP: i64 = 30 * 60;
+/// Entries per list (people, conversations, reactions); files listed in a posted digest.
+const TOP_N: usize = 5;
+const FILES_LISTED: usize = 10;
+const SNIPPET_CHARS: usize = 120;
+
+/// How often the scheduler checks whether a weekly digest is due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+impl DigestPeriod {
+    /// The period as an SQLite date modifier, counting back from now.
+    pub(crate) fn modifier(self) -> &'static str {
+        match self {
+            DigestPeriod::Day => "-1 day",
+            DigestPeriod::Week => "-7 days",
+            DigestPeriod::Month => "-1 month",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            DigestPeriod::Day => "Daily",
+            DigestPeriod::Week => "Weekly",
+            DigestPeriod::Month => "Monthly",
+        }
+    }
+}
+
+/// What to do with a digest besides returning it.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DigestDelivery {
+    // Post it into the room (host only).
+    Post,
+    // Save it as an HTML page.
+    Html { path: String },
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PosterCount {
+    pub name: String,
+    pub count: i64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DayCount {
+    // "YYYY-MM-DD", UTC.
+    pub day: String,
+    pub count: i64,
+}
+
+/// A run of messages without a long pause.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Conversation {
+    pub started_at: String,
+    pub ended_at: String,
+    pub message_count: i64,
+    // In order of first message.
+    pub participants: Vec<String>,
+    // The message that started it.
+    pub opening: String,
+    pub message_id: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ReactedMessage {
+    pub message_id: String,
+    pub author: String,
+    pub created_at: String,
+    pub snippet: String,
+    pub reactions: i64,
+    pub emoji: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DigestFile {
+    pub id: i64,
+    pub filename: String,
+    pub size: i64,
+    pub uploader: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RoomDigest {
+    pub room_id: i64,
+    pub room: String,
+    pub period: DigestPeriod,
+    // UTC, "YYYY-MM-DD HH:MM:SS"; `from` inclusive, `to` exclusive.
+    pub from: String,
+    pub to: String,
+    pub message_count: i64,
+    pub active_members: i64,
+    pub top_posters: Vec<PosterCount>,
+    pub daily_counts: Vec<DayCount>,
+    pub top_conversations: Vec<Conversation>,
+    pub most_reacted: Vec<ReactedMessage>,
+    pub files: Vec<DigestFile>,
+}
+
+/// Automatic weekly digests, posted by the host.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DigestSchedule {
+    pub enabled: bool,
+    #[serde(default)]
+    pub rooms: Vec<i64>,
+    // The UTC hour on Monday from which they go out.
+    #[serde(default = "default_hour")]
+    pub hour_utc: u8,
+}
+
+fn default_hour() -> u8 {
+    8
+}
+
+impl Default for DigestSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rooms: Vec::new(),
+            hour_utc: default_hour(),
+        }
+    }
+}
+
+async fn load_schedule(pool: &SqlitePool) -> AppResult<DigestSchedule> {
+    match get_setting_internal(pool, SCHEDULE_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt digest schedule: {}", e))),
+        None => Ok(DigestSchedule::default()),
+    }
+}
+
+fn snippet(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= SNIPPET_CHARS {
+        text
+    } else {
+        let cut: String = text.chars().take(SNIPPET_CHARS).collect();
+        format!("{}…", cut.trim_end())
+    }
+}
+
+/// A stored message as one line of text.
+fn preview(message_type: &str, message: &str) -> String {
+    let text = match message_type {
+        "Sticker" => stickers::preview(message),
+        "Contact" => contacts::preview(message),
+        "Location" => location::preview(message),
+        "Attachment" => attachments::preview(message),
+        _ => message.to_string(),
+    };
+    snippet(&text)
+}
+
+fn size_label(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Split messages (in time order) into conversations and keep the busiest.
+fn conversations(rows: &[MessageRow]) -> Vec<Conversation> {
+    let mut found: Vec<Conversation> = Vec::new();
+    let mut last_secs: Option<i64> = None;
+    for row in rows {
+        let continues = last_secs.is_some_and(|last| row.secs - last <= CONVERSATION_GAP);
+        last_secs = Some(row.secs);
+        match found.last_mut() {
+            Some(current) if continues => {
+                current.ended_at = row.created_at.clone();
+                current.message_count += 1;
+                if !current.participants.contains(&row.author) {
+                    current.participants.push(row.author.clone());
+                }
+            }
+            _ => found.push(Conversation {
+                started_at: row.created_at.clone(),
+                ended_at: row.created_at.clone(),
+                message_count: 1,
+                participants: vec![row.author.clone()],
+                opening: preview(&row.message_type, &row.message),
+                message_id: row.message_id.clone(),
+            }),
+        }
+    }
+    // A lone message isn't a conversation. Stable, so ties keep time order.
+    found.retain(|c| c.message_count > 1);
+    found.sort_by(|a, b| b.message_count.cmp(&a.message_count));
+    found.truncate(TOP_N);
+    found
+}
+
+#[derive(sqlx::FromRow)]
+struct MessageRow {
+    message_id: Option<String>,
+    author: String,
+    message: String,
+    message_type: String,
+    created_at: String,
+    secs: i64,
+}
+
+/// The digest of room `room_id` between `from` (inclusive) and `to` (exclusive).
+pub(crate) async fn build_digest(
+    pool: &SqlitePool,
+    room_id: i64,
+    period: DigestPeriod,
+    from: &str,
+    to: &str,
+) -> AppResult<RoomDigest> {
+    let room: String = sqlx::query_scalar("SELECT name FROM chat_rooms WHERE id = $1")
+        .bind(room_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Validation("That room doesn't exist".to_string()))?;
+
+    let rows: Vec<MessageRow> = sqlx::query_as(
+        "SELECT m.message_id, COALESCE(u.name, 'Unknown') AS author, m.message,
+                COALESCE(m.message_type, 'Chat') AS message_type, m.created_at,
+                CAST(strftime('%s', m.created_at) AS INTEGER) AS secs
+         FROM messages m
+         LEFT JOIN users u ON m.user_id = u.id
+         WHERE m.room_id = $1
+           AND m.message_type IN ('Chat', 'Sticker', 'Code', 'Attachment', 'Contact', 'Location')
+           AND m.deleted_at IS NULL
+           AND m.created_at >= $2 AND m.created_at < $3
+         ORDER BY m.created_at, m.id",
+    )
+    .bind(room_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    let mut per_author: HashMap<&str, i64> = HashMap::new();
+    let mut per_day: Vec<DayCount> = Vec::new();
+    for row in &rows {
+        *per_author.entry(&row.author).or_default() += 1;
+        let day = row.created_at.get(..10).unwrap_or(&row.created_at);
+        match per_day.last_mut() {
+            Some(last) if last.day == day => last.count += 1,
+            _ => per_day.push(DayCount {
+                day: day.to_string(),
+                count: 1,
+            }),
+        }
+    }
+    let active_members = per_author.len() as i64;
+    let mut top_posters: Vec<PosterCount> = per_author
+        .into_iter()
+        .map(|(name, count)| PosterCount {
+            name: name.to_string(),
+            count,
+        })
+        .collect();
+    top_posters.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    top_posters.truncate(TOP_N);
+
+    let reacted: Vec<(String, String, String, String, String, i64, Option<String>)> =
+        sqlx::query_as(
+            "SELECT m.message_id, COALESCE(u.name, 'Unknown'), m.message,
+                    COALESCE(m.message_type, 'Chat'), m.created_at, COUNT(r.id) AS n,
+                    GROUP_CONCAT(DISTINCT r.emoji)
+             FROM messages m
+             JOIN reactions r ON r.message_id = m.message_id
+             LEFT JOIN users u ON m.user_id = u.id
+             WHERE m.room_id = $1 AND m.deleted_at IS NULL
+               AND m.created_at >= $2 AND m.created_at < $3
+             GROUP BY m.id
+             ORDER BY n DESC, m.id
+             LIMIT $4",
+        )
+        .bind(room_id)
+        .bind(from)
+        .bind(to)
+        .bind(TOP_N as i64)
+        .fetch_all(pool)
+        .await?;
+    let most_reacted = reacted
+        .into_iter()
+        .map(
+            |(message_id, author, message, message_type, created_at, reactions, emoji)| {
+                ReactedMessage {
+                    message_id,
+                    author,
+                    created_at,
+                    snippet: preview(&message_type, &message),
+                    reactions,
+                    emoji: emoji
+                        .map(|e| e.split(',').map(str::to_string).collect())
+                        .unwrap_or_default(),
+                }
+            },
+        )
+        .collect();
+
+    let files: Vec<(i64, String, i64, String, String)> = sqlx::query_as(
+        "SELECT a.id, a.filename, a.size, COALESCE(u.name, 'Unknown'), m.created_at
+         FROM attachments a
+         JOIN messages m ON m.message_id = a.message_id
+         LEFT JOIN users u ON u.id = a.uploaded_by
+         WHERE a.room_id = $1 AND m.deleted_at IS NULL
+           AND m.created_at >= $2 AND m.created_at < $3
+         ORDER BY m.created_at, a.id",
+    )
+    .bind(room_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(RoomDigest {
+        room_id,
+        room,
+        period,
+        from: from.to_string(),
+        to: to.to_string(),
+        message_count: rows.len() as i64,
+        active_members,
+        top_posters,
+        daily_counts: per_day,
+        top_conversations: conversations(&rows),
+        most_reacted,
+        files: files
+            .into_iter()
+            .map(|(id, filename, size, uploader, created_at)| DigestFile {
+                id,
+                filename,
+                size,
+                uploader,
+                created_at,
+            })
+            .collect(),
+    })
+}
+
+/// "YYYY-MM-DD HH:MM" from a stored timestamp.
+fn minutes(timestamp: &str) -> &str {
+    timestamp.get(..16).unwrap_or(timestamp)
+}
+
+fn day(timestamp: &str) -> &str {
+    timestamp.get(..10).unwrap_or(timestamp)
+}
+
+/// The digest as a chat message.
+fn render_text(digest: &RoomDigest) -> String {
+    let mut text = format!(
+        "📊 {} digest for #{} ({} – {})\n",
+        digest.period.label(),
+        digest.room,
+        day(&digest.from),
+        day(&digest.to)
+    );
+    if digest.message_count == 0 {
+        text.push_str("No messages in this period.");
+        return text;
+    }
+    text.push_str(&format!(
+        "{} message{} from {} {}.\n",
+        digest.message_count,
+        if digest.message_count == 1 { "" } else { "s" },
+        digest.active_members,
+        if digest.active_members == 1 {
+            "person"
+        } else {
+            "people"
+        }
+    ));
+    let posters: Vec<String> = digest
+        .top_posters
+        .iter()
+        .map(|p| format!("{} ({})", p.name, p.count))
+        .collect();
+    text.push_str(&format!("Most active: {}\n", posters.join(", ")));
+    if !digest.top_conversations.is_empty() {
+        text.push_str("\nBusiest conversations:\n");
+        for c in &digest.top_conversations {
+            text.push_str(&format!(
+                "• {} — {} messages, {}: \"{}\"\n",
+                minutes(&c.started_at),
+                c.message_count,
+                c.participants.join(", "),
+                c.opening
+            ));
+        }
+    }
+    if !digest.most_reacted.is_empty() {
+        text.push_str("\nMost reacted:\n");
+        for m in &digest.most_reacted {
+            text.push_str(&format!(
+                "• {}: \"{}\" ({} {})\n",
+                m.author,
+                m.snippet,
+                m.emoji.join(" "),
+                m.reactions
+            ));
+        }
+    }
+    if !digest.files.is_empty() {
+        text.push_str(&format!("\nFiles shared ({}):\n", digest.files.len()));
+        for f in digest.files.iter().take(FILES_LISTED) {
+            text.push_str(&format!(
+                "• {} ({}) by {}\n",
+                f.filename,
+                size_label(f.size),
+                f.uploader
+            ));
+        }
+        if digest.files.len() > FILES_LISTED {
+            text.push_str(&format!(
+                "…and {} more\n",
+                digest.files.len() - FILES_LISTED
+            ));
+        }
+    }
+    text.trim_end().to_string()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The digest as a standalone HTML page.
+fn render_html(digest: &RoomDigest) -> String {
+    let e = escape_html;
+    let title = format!("{} digest for #{}", digest.period.label(), digest.room);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{t}</title>\n<style>\
+         body{{font-family:system-ui,sans-serif;max-width:720px;margin:2em auto;color:#222}}\
+         h2{{margin-top:1.6em;font-size:1.1em}}td,th{{padding:2px 12px 2px 0;text-align:left}}\
+         .dim{{color:#777}}</style></head><body>\n<h1>{t}</h1>\n\
+         <p class=\"dim\">{from} – {to} (UTC)</p>\n<p>{count} messages from {people} people.</p>\n",
+        t = e(&title),
+        from = e(minutes(&digest.from)),
+        to = e(minutes(&digest.to)),
+        count = digest.message_count,
+        people = digest.active_members,
+    );
+    if !digest.top_posters.is_empty() {
+        html.push_str("<h2>Most active</h2>\n<table>\n");
+        for p in &digest.top_posters {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td></tr>\n",
+                e(&p.name),
+                p.count
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    if !digest.daily_counts.is_empty() {
+        html.push_str("<h2>Messages per day</h2>\n<table>\n");
+        for d in &digest.daily_counts {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td></tr>\n",
+                e(&d.day),
+                d.count
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    if !digest.top_conversations.is_empty() {
+        html.push_str("<h2>Busiest conversations</h2>\n<ul>\n");
+        for c in &digest.top_conversations {
+            html.push_str(&format!(
+                "<li><span class=\"dim\">{} – {}</span> · {} messages · {}<br>“{}”</li>\n",
+                e(minutes(&c.started_at)),
+                e(minutes(&c.ended_at)),
+                c.message_count,
+                e(&c.participants.join(", ")),
+                e(&c.opening)
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+    if !digest.most_reacted.is_empty() {
+        html.push_str("<h2>Most reacted</h2>\n<ul>\n");
+        for m in &digest.most_reacted {
+            html.push_str(&format!(
+                "<li><b>{}</b> <span class=\"dim\">{}</span><br>“{}” · {} {}</li>\n",
+                e(&m.author),
+                e(minutes(&m.created_at)),
+                e(&m.snippet),
+                e(&m.emoji.join(" ")),
+                m.reactions
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+    if !digest.files.is_empty() {
+        html.push_str("<h2>Files shared</h2>\n<table>\n");
+        for f in &digest.files {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"dim\">{}</td></tr>\n",
+                e(&f.filename),
+                size_label(f.size),
+                e(&f.uploader),
+                e(minutes(&f.created_at))
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+/// The digest of `room_id` for the period ending now.
+async fn digest_until_now(
+    pool: &SqlitePool,
+    room_id: i64,
+    period: DigestPeriod,
+) -> AppResult<RoomDigest> {
+    let (from, to): (String, String) =
+        sqlx::query_as("SELECT datetime('now', $1), datetime('now')")
+            .bind(period.modifier())
+            .fetch_one(pool)
+            .await?;
+    build_digest(pool, room_id, period, &from, &to).await
+}
+
+/// Host: post a digest into its room as the Digest bot.
+async fn post(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    digest: &RoomDigest,
+) -> AppResult<()> {
+    if !*state.is_server.read().await {
+        return Err(AppError::Validation(
+            "Only the host can post a digest".to_string(),
+        ));
+    }
+    let bot = upsert_user_internal(pool, BOT_NAME.to_string(), BOT_EMAIL.to_string(), None)
+        .await?
+        .id
+        .ok_or_else(|| AppError::Internal("Digest user has no id".to_string()))?;
+    let message = Message {
+        username: BOT_NAME.to_string(),
+        room_id: digest.room_id as u64,
+        room: digest.room.clone(),
+        ..frame(MessageType::Chat, bot as u64, render_text(digest))
+    };
+    publish_host_message(app, state, pool, message).await;
+    Ok(())
+}
+
+/// Post this week's digests if it's Monday (UTC), past the hour, and not done yet today.
+async fn post_scheduled(app: &tauri::AppHandle, state: &Arc<AppState>, pool: &SqlitePool) {
+    let schedule = match load_schedule(pool).await {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::warn!("{}", e);
+            return;
+        }
+    };
+    if !schedule.enabled || schedule.rooms.is_empty() || !*state.is_server.read().await {
+        return;
+    }
+    let Ok((today, weekday, hour)) = sqlx::query_as::<_, (String, i64, i64)>(
+        "SELECT date('now'), CAST(strftime('%w', 'now') AS INTEGER),
+                CAST(strftime('%H', 'now') AS INTEGER)",
+    )
+    .fetch_one(pool)
+    .await
+    else {
+        return;
+    };
+    // %w counts from Sunday = 0.
+    if weekday != 1 || hour < schedule.hour_utc as i64 {
+        return;
+    }
+    let last = get_setting_internal(pool, LAST_POSTED_KEY)
+        .await
+        .ok()
+        .flatten();
+    if last.as_deref() == Some(today.as_str()) {
+        return;
+    }
+    if let Err(e) = set_setting_internal(pool, LAST_POSTED_KEY, &today).await {
+        tracing::warn!("Couldn't record the digest run: {}", e);
+        return;
+    }
+    for room_id in schedule.rooms {
+        let digest = match digest_until_now(pool, room_id, DigestPeriod::Week).await {
+            Ok(d) if d.message_count > 0 => d,
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::warn!("Weekly digest for room {} failed: {}", room_id, e);
+                continue;
+            }
+        };
+        match post(app, state, pool, &digest).await {
+            Ok(()) => tracing::info!("📊 Posted the weekly digest to #{}", digest.room),
+            Err(e) => tracing::warn!("Weekly digest for #{} not posted: {}", digest.room, e),
+        }
+    }
+}
+
+/// Background loop for scheduled digests; idles until a schedule is enabled. The schedule is
+/// re-read every round, so changes apply without a restart.
+pub fn spawn_scheduler(app: tauri::AppHandle, state: Arc<AppState>, pool: SqlitePool) {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            post_scheduled(&app, &state, &pool).await;
+        }
+    });
+}
+
+/// Summarize room `room_id` over the `period` ending now (a week by default), and optionally
+/// post the digest into the room or save it as HTML.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn generate_digest(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    period: Option<DigestPeriod>,
+    delivery: Option<DigestDelivery>,
+) -> AppResult<RoomDigest> {
+    let digest = digest_until_now(&db, room_id, period.unwrap_or_default()).await?;
+    match delivery {
+        Some(DigestDelivery::Post) => post(&app, state.inner(), &db, &digest).await?,
+        Some(DigestDelivery::Html { path }) => tokio::fs::write(&path, render_html(&digest))
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to save {}: {}", path, e)))?,
+        None => {}
+    }
+    Ok(digest)
+}
+
+#[tauri::command]
+pub async fn get_digest_schedule(db: State<'_, SqlitePool>) -> AppResult<DigestSchedule> {
+    load_schedule(&db).await
+}
+
+#[tauri::command]
+pub async fn set_digest_schedule(
+    db: State<'_, SqlitePool>,
+    schedule: DigestSchedule,
+) -> AppResult<()> {
+    if schedule.hour_utc > 23 {
+        return Err(AppError::Validation(
+            "The hour must be between 0 and 23".to_string(),
+        ));
+    }
+    if schedule.enabled && schedule.rooms.is_empty() {
+        return Err(AppError::Validation(
+            "Pick at least one room for the weekly digest".to_string(),
+        ));
+    }
+    let json = serde_json::to_string(&schedule).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SCHEDULE_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn digests_summarize_the_period() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1);
+             INSERT INTO chat_rooms (id, name, is_private, created_by) VALUES (100, 'ops', 0, 1);
+             INSERT INTO messages (room_id, user_id, message, message_type, message_id, created_at) VALUES
+                 (100, 1, 'last week', 'Chat', 'old', '2026-03-01 09:00:00'),
+                 (100, 1, 'deploy at ten?', 'Chat', 'a', '2026-03-03 09:00:00'),
+                 (100, 2, 'yes', 'Chat', 'b', '2026-03-03 09:10:00'),
+                 (100, 1, 'done', 'Chat', 'c', '2026-03-03 09:35:00'),
+                 (100, 2, 'lunch?', 'Chat', 'd', '2026-03-04 12:00:00'),
+                 (100, 2, '{\"id\":7,\"filename\":\"plan.pdf\",\"size\":2048,\"mime\":\"application/pdf\"}',
+                  'Attachment', 'e', '2026-03-05 08:00:00'),
+                 (100, 2, 'gone', 'Chat', 'f', '2026-03-05 09:00:00');
+             UPDATE messages SET deleted_at = '2026-03-05T10:00:00Z' WHERE message_id = 'f';
+             INSERT INTO attachments (id, room_id, message_id, uploaded_by, filename, mime, size, sha256)
+                 VALUES (7, 100, 'e', 2, 'plan.pdf', 'application/pdf', 2048, 'x');
+             INSERT INTO reactions (message_id, user_id, emoji) VALUES
+                 ('c', 2, '🎉'), ('c', 1, '🎉'), ('c', 2, '👍'), ('b', 1, '👍');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let digest = build_digest(
+            &pool,
+            100,
+            DigestPeriod::Week,
+            "2026-03-02 00:00:00",
+            "2026-03-09 00:00:00",
+        )
+        .await
+        .unwrap();
+        assert_eq!(digest.message_count, 5);
+        assert_eq!(digest.active_members, 2);
+        let posters: Vec<(&str, i64)> = digest
+            .top_posters
+            .iter()
+            .map(|p| (p.name.as_str(), p.count))
+            .collect();
+        assert_eq!(posters, [("Bob", 3), ("Alice", 2)]);
+        assert_eq!(digest.daily_counts.len(), 3);
+        // 09:00, 09:10 and 09:35 are one conversation; the rest are lone messages.
+        assert_eq!(digest.top_conversations.len(), 1);
+        assert_eq!(digest.top_conversations[0].message_count, 3);
+        assert_eq!(digest.top_conversations[0].participants, ["Alice", "Bob"]);
+        assert_eq!(digest.top_conversations[0].opening, "deploy at ten?");
+        assert_eq!(digest.most_reacted[0].message_id, "c");
+        assert_eq!(digest.most_reacted[0].reactions, 3);
+        assert_eq!(digest.files.len(), 1);
+        assert_eq!(digest.files[0].uploader, "Bob");
+
+        let text = render_text(&digest);
+        assert!(text.starts_with("📊 Weekly digest for #ops (2026-03-02 – 2026-03-09)"));
+        assert!(text.contains("plan.pdf (2.0 KB) by Bob"));
+        let html = render_html(&digest);
+        assert!(html.contains("<h1>Weekly digest for #ops</h1>"));
+        assert!(!html.contains("<script"));
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        assert_eq!(
+            escape_html("<b>\"a\" & b</b>"),
+            "&lt;b&gt;&quot;a&quot; &amp; b&lt;/b&gt;"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+// Per-room permissions (host-side).
+//
+// Each room can leave posting, inviting and pinning open to its members (the default) or keep
+// them to its moderators: the room's creator and the host. A room where only moderators post is
+// a read-only announcement room; `create_room` can start one that way, and the host answers
+// anyone else's post there with a ReadOnlyRoom frame. Settings live in `room_permissions`, one
+// row per room that changed anything. The host checks them on clients' frames (posts, file
+// offers, invites) and in the commands that act for a named user; its own actions are always
+// allowed. Pinning isn't in yet, so `pin` is only stored for now.
+
+use crate::db_queries::record_audit_internal;
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::sync::Arc;
+use tauri::State;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Who {
+    #[default]
+    Members,
+    Moderators,
+}
+
+impl Who {
+    fn as_str(self) -> &'static str {
+        match self {
+            Who::Members => "members",
+            Who::Moderators => "moderators",
+        }
+    }
+
+    fn parse(text: Option<&str>) -> Who {
+        match text {
+            Some("moderators") => Who::Moderators,
+            _ => Who::Members,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RoomPermissions {
+    #[serde(default)]
+    pub post: Who,
+    #[serde(default)]
+    pub invite: Who,
+    #[serde(default)]
+    pub pin: Who,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Post,
+    Invite,
+}
+
+/// A room's name, creator and permissions.
+async fn load(
+    pool: &SqlitePool,
+    room_id: i64,
+) -> AppResult<(String, Option<i64>, RoomPermissions)> {
+    let row = sqlx::query(
+        "SELECT cr.name, cr.created_by, rp.post, rp.invite, rp.pin
+         FROM chat_rooms cr LEFT JOIN room_permissions rp ON rp.room_id = cr.id
+         WHERE cr.id = $1",
+    )
+    .bind(room_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::Validation("Room not found".to_string()))?;
+    let permissions = RoomPermissions {
+        post: Who::parse(row.get("post")),
+        invite: Who::parse(row.get("invite")),
+        pin: Who::parse(row.get("pin")),
+    };
+    Ok((row.get("name"), row.get("created_by"), permissions))
+}
+
+pub async fn get_internal(pool: &SqlitePool, room_id: i64) -> AppResult<RoomPermissions> {
+    Ok(load(pool, room_id).await?.2)
+}
+
+pub async fn set_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    permissions: &RoomPermissions,
+    actor: Option<i64>,
+) -> AppResult<()> {
+    let (name, _, _) = load(pool, room_id).await?;
+    sqlx::query(
+        "INSERT INTO room_permissions (room_id, post, invite, pin) VALUES ($1, $2, $3, $4)
+         ON CONFLICT(room_id) DO UPDATE SET post = excluded.post, invite = excluded.invite,
+             pin = excluded.pin, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(room_id)
+    .bind(permissions.post.as_str())
+    .bind(permissions.invite.as_str())
+    .bind(permissions.pin.as_str())
+    .execute(pool)
+    .await?;
+    let detail = format!(
+        "post: {}, invite: {}, pin: {}",
+        permissions.post.as_str(),
+        permissions.invite.as_str(),
+        permissions.pin.as_str()
+    );
+    record_audit_internal(pool, actor, "room.permissions", &name, Some(&detail))
+        .await
+        .map_err(AppError::Db)
+}
+
+/// Whether `user` may do `action` in `room_id`; `user` None is the host, who always may.
+pub async fn check(
+    pool: &SqlitePool,
+    room_id: i64,
+    user: Option<i64>,
+    action: Action,
+) -> AppResult<()> {
+    let Some(user) = user else {
+        return Ok(());
+    };
+    let (name, created_by, permissions) = load(pool, room_id).await?;
+    let who = match action {
+        Action::Post => permissions.post,
+        Action::Invite => permissions.invite,
+    };
+    if who == Who::Members || created_by == Some(user) {
+        return Ok(());
+    }
+    let what = match action {
+        Action::Post => "post",
+        Action::Invite => "invite people",
+    };
+    Err(AppError::Auth(format!(
+        "Only moderators can {} in #{}",
+        what, name
+    )))
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_room_permissions(
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+) -> AppResult<RoomPermissions> {
+    get_internal(&db, room_id).await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_room_permissions(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    permissions: RoomPermissions,
+) -> AppResult<()> {
+    let actor = state.user_id.read().await.map(|id| id as i64);
+    set_internal(&db, room_id, &permissions, actor).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn announcement_rooms_only_take_posts_from_moderators() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1);
+             INSERT INTO chat_rooms (id, name, created_by) VALUES (100, 'news', 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // Open to members until changed.
+        assert_eq!(
+            get_internal(&pool, 100).await.unwrap(),
+            RoomPermissions::default()
+        );
+        assert!(check(&pool, 100, Some(2), Action::Post).await.is_ok());
+
+        let announcements = RoomPermissions {
+            post: Who::Moderators,
+            ..Default::default()
+        };
+        set_internal(&pool, 100, &announcements, None)
+            .await
+            .unwrap();
+        assert_eq!(get_internal(&pool, 100).await.unwrap(), announcements);
+        assert!(matches!(
+            check(&pool, 100, Some(2), Action::Post).await,
+            Err(AppError::Auth(_))
+        ));
+        assert!(check(&pool, 100, Some(1), Action::Post).await.is_ok());
+        assert!(check(&pool, 100, None, Action::Post).await.is_ok());
+        assert!(check(&pool, 100, Some(2), Action::Invite).await.is_ok());
+        assert!(get_internal(&pool, 999).await.is_err());
+
+        let audit: Vec<String> = sqlx::query_scalar("SELECT detail FROM audit_log")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(audit, ["post: moderators, invite: members, pin: members"]);
+
+        // An announcement-only room starts out that way.
+        let room = crate::db_queries::create_room_internal(
+            &pool,
+            "all-hands".into(),
+            None,
+            None,
+            None,
+            Some(1),
+            Some(true),
+        )
+        .await
+        .unwrap();
+        let room_id = room.id.unwrap();
+        assert!(check(&pool, room_id, Some(1), Action::Post).await.is_ok());
+        assert!(check(&pool, room_id, Some(2), Action::Post).await.is_err());
+    }
+}
@@ -0,0 +1,192 @@
+// Room welcome messages (host-side).
+//
+// A room can have a welcome template (`set_room_welcome`): its rules, where the pinned resources
+// are, and so on. The first time a user joins the room (by Connect or RoomJoin), the host sends
+// it to them alone as a SystemResponse frame, `message` = the text with `{user}` and `{room}`
+// filled in. It's never posted to the room or stored as a message; `room_welcomed` remembers who
+// has had it, so changing the template doesn't welcome everyone again.
+
+use crate::db_queries::record_audit_internal;
+use crate::error::{AppError, AppResult};
+use crate::sockets::{frame, send_to_client, AppState, MessageType};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tauri::State;
+
+/// Longest welcome template.
+pub const MAX_WELCOME_CHARS: usize = 2000;
+
+/// The welcome as `user` sees it in `room`.
+fn render(template: &str, user: &str, room: &str) -> String {
+    template.replace("{user}", user).replace("{room}", room)
+}
+
+pub async fn get_welcome_internal(pool: &SqlitePool, room_id: i64) -> AppResult<Option<String>> {
+    Ok(
+        sqlx::query_scalar("SELECT template FROM room_welcomes WHERE room_id = $1")
+            .bind(room_id)
+            .fetch_optional(pool)
+            .await?,
+    )
+}
+
+/// Set a room's welcome; an empty `template` removes it.
+pub async fn set_welcome_internal(
+    pool: &SqlitePool,
+    room_id: i64,
+    template: &str,
+    actor: Option<i64>,
+) -> AppResult<()> {
+    let name: Option<String> = sqlx::query_scalar("SELECT name FROM chat_rooms WHERE id = $1")
+        .bind(room_id)
+        .fetch_optional(pool)
+        .await?;
+    let Some(name) = name else {
+        return Err(AppError::Validation("Room not found".to_string()));
+    };
+    let template = template.trim();
+    if template.chars().count() > MAX_WELCOME_CHARS {
+        return Err(AppError::Validation(format!(
+            "A welcome message can be at most {} characters",
+            MAX_WELCOME_CHARS
+        )));
+    }
+    if template.is_empty() {
+        sqlx::query("DELETE FROM room_welcomes WHERE room_id = $1")
+            .bind(room_id)
+            .execute(pool)
+            .await?;
+    } else {
+        sqlx::query(
+            "INSERT INTO room_welcomes (room_id, template) VALUES ($1, $2)
+             ON CONFLICT(room_id) DO UPDATE SET
+                 template = excluded.template, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(room_id)
+        .bind(template)
+        .execute(pool)
+        .await?;
+    }
+    let detail = if template.is_empty() {
+        "removed"
+    } else {
+        "set"
+    };
+    record_audit_internal(pool, actor, "room.welcome", &name, Some(detail))
+        .await
+        .map_err(AppError::Db)
+}
+
+/// The welcome for `user_id` joining `room_id`, if the room has one and this is their first
+/// join; it's marked as had either way.
+pub async fn first_join_welcome(
+    pool: &SqlitePool,
+    room_id: i64,
+    user_id: i64,
+    username: &str,
+    room: &str,
+) -> AppResult<Option<String>> {
+    let Some(template) = get_welcome_internal(pool, room_id).await? else {
+        return Ok(None);
+    };
+    let first = sqlx::query(
+        "INSERT INTO room_welcomed (room_id, user_id) VALUES ($1, $2)
+         ON CONFLICT(room_id, user_id) DO NOTHING",
+    )
+    .bind(room_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?
+    .rows_affected()
+        == 1;
+    Ok(first.then(|| render(&template, username, room)))
+}
+
+/// Host: send `user_id` the room's welcome if they've never joined it before.
+pub(crate) async fn welcome(
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    user_id: u64,
+    username: &str,
+    room: &str,
+    room_id: u64,
+) {
+    match first_join_welcome(pool, room_id as i64, user_id as i64, username, room).await {
+        Ok(Some(text)) => {
+            let mut msg = frame(MessageType::SystemResponse, user_id, text);
+            msg.room = room.to_string();
+            msg.room_id = room_id;
+            send_to_client(state, user_id, &msg).await;
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Room welcome for {} failed: {}", user_id, e),
+    }
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_room_welcome(
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+) -> AppResult<Option<String>> {
+    get_welcome_internal(&db, room_id).await
+}
+
+/// Set the message each user gets privately on first joining the room; `{user}` and `{room}`
+/// are filled in. An empty `template` removes it.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_room_welcome(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    template: String,
+) -> AppResult<()> {
+    let actor = state.user_id.read().await.map(|id| id as i64);
+    set_welcome_internal(&db, room_id, &template, actor).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::memory_pool;
+
+    #[tokio::test]
+    async fn each_user_is_welcomed_once_per_room() {
+        let pool = memory_pool().await;
+        let room: i64 = sqlx::query_scalar("SELECT id FROM chat_rooms WHERE name = 'IT General'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        // No template, no welcome (and no record, so a later one still reaches them).
+        assert_eq!(
+            first_join_welcome(&pool, room, 2, "Bob", "IT General")
+                .await
+                .unwrap(),
+            None
+        );
+        set_welcome_internal(&pool, room, "  Hi {user}, read the #{room} rules  ", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            first_join_welcome(&pool, room, 2, "Bob", "IT General")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("Hi Bob, read the #IT General rules")
+        );
+        assert_eq!(
+            first_join_welcome(&pool, room, 2, "Bob", "IT General")
+                .await
+                .unwrap(),
+            None
+        );
+
+        assert!(set_welcome_internal(&pool, 999, "Hi", None).await.is_err());
+        let too_long = "x".repeat(MAX_WELCOME_CHARS + 1);
+        assert!(set_welcome_internal(&pool, room, &too_long, None)
+            .await
+            .is_err());
+        set_welcome_internal(&pool, room, " ", None).await.unwrap();
+        assert_eq!(get_welcome_internal(&pool, room).await.unwrap(), None);
+    }
+}
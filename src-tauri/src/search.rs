@@ -0,0 +1,328 @@
+// Cross-room search (host-side).
+//
+// `search_all` runs a query against the full-text index of messages (`messages_fts`, an FTS5
+// table kept in step with `messages` by triggers) across every room the user may open: public
+// rooms, plus private rooms they created or belong to, the same rule as
+// `room_join_allowed_internal`. Voice messages are found by their transcripts and images by the
+// text in them (`attachments_fts`, see transcription.rs and ocr.rs). Hits come back grouped by room, best room first, each with the messages
+// just before and after it for context.
+
+use crate::error::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use tauri::State;
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 200;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct SearchFilters {
+    // Only this room.
+    #[serde(default)]
+    pub room_id: Option<i64>,
+    // Only messages by this user.
+    #[serde(default)]
+    pub author_id: Option<i64>,
+    // Only messages at or after / before these times ("YYYY-MM-DD" or an ISO-8601 timestamp).
+    #[serde(default)]
+    pub after: Option<String>,
+    #[serde(default)]
+    pub before: Option<String>,
+    // Most hits overall (50 by default, at most 200).
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ContextMessage {
+    pub message_id: Option<String>,
+    pub username: String,
+    pub message: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub message_id: Option<String>,
+    pub username: String,
+    pub message: String,
+    pub created_at: String,
+    // For a voice message found by what was said: its transcript.
+    pub transcript: Option<String>,
+    // For an image found by the text in it: that text (see ocr.rs).
+    pub image_text: Option<String>,
+    // The room's previous and next messages.
+    pub previous: Option<ContextMessage>,
+    pub next: Option<ContextMessage>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RoomHits {
+    pub room_id: i64,
+    pub room_name: String,
+    pub hits: Vec<SearchHit>,
+}
+
+/// The user's words as an FTS5 query: every word must appear, each matched as a prefix. Quoted,
+/// so FTS5 operators typed into the box are searched for rather than interpreted.
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|w| format!("\"{}\"*", w.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+async fn context(
+    pool: &SqlitePool,
+    room_id: i64,
+    id: i64,
+    next: bool,
+) -> AppResult<Option<ContextMessage>> {
+    // Two statements rather than a format!, so the SQL stays static.
+    let sql = if next {
+        "SELECT m.message_id, m.message, m.created_at, COALESCE(u.name, 'Unknown') AS username
+         FROM messages m LEFT JOIN users u ON u.id = m.user_id
+         WHERE m.room_id = $1 AND m.id > $2 AND m.deleted_at IS NULL
+         ORDER BY m.id ASC LIMIT 1"
+    } else {
+        "SELECT m.message_id, m.message, m.created_at, COALESCE(u.name, 'Unknown') AS username
+         FROM messages m LEFT JOIN users u ON u.id = m.user_id
+         WHERE m.room_id = $1 AND m.id < $2 AND m.deleted_at IS NULL
+         ORDER BY m.id DESC LIMIT 1"
+    };
+    let row = sqlx::query(sql)
+        .bind(room_id)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|r| ContextMessage {
+        message_id: r.get("message_id"),
+        username: r.get("username"),
+        message: r.get("message"),
+        created_at: r.get("created_at"),
+    }))
+}
+
+pub async fn search_all_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    query: &str,
+    filters: &SearchFilters,
+) -> AppResult<Vec<RoomHits>> {
+    let Some(fts) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    let limit = filters.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let rows = sqlx::query(
+        "WITH matched(id, rank, from_file, transcript, image_text) AS (
+             SELECT rowid, rank, 0, NULL, NULL FROM messages_fts WHERE messages_fts MATCH $2
+             UNION ALL
+             SELECT m.id, attachments_fts.rank, 1, a.transcript, a.ocr_text
+             FROM attachments_fts
+             JOIN attachments a ON a.id = attachments_fts.rowid
+             JOIN messages m ON m.message_id = a.message_id
+             WHERE attachments_fts MATCH $2
+         )
+         SELECT m.id, m.message_id, m.room_id, m.message, m.created_at, matched.transcript,
+                matched.image_text,
+                COALESCE(u.name, 'Unknown') AS username, cr.name AS room_name
+         FROM matched
+         JOIN messages m ON m.id = matched.id
+         JOIN chat_rooms cr ON cr.id = m.room_id
+         LEFT JOIN users u ON u.id = m.user_id
+         WHERE m.deleted_at IS NULL
+           AND (m.message_type IN ('Chat', 'Code') OR matched.from_file = 1)
+           AND (cr.is_private = 0
+                OR (NOT EXISTS (SELECT 1 FROM users g WHERE g.id = $1 AND g.is_guest = 1)
+                    AND (cr.created_by = $1
+                         OR EXISTS (SELECT 1 FROM user_rooms ur
+                                    WHERE ur.room_id = cr.id AND ur.user_id = $1
+                                      AND ur.is_active = 1))))
+           AND ($3 IS NULL OR m.room_id = $3)
+           AND ($4 IS NULL OR m.user_id = $4)
+           AND ($5 IS NULL OR m.created_at >= $5)
+           AND ($6 IS NULL OR m.created_at < $6)
+         ORDER BY matched.rank, m.id DESC
+         LIMIT $7",
+    )
+    .bind(user_id)
+    .bind(&fts)
+    .bind(filters.room_id)
+    .bind(filters.author_id)
+    .bind(filters.after.as_deref())
+    .bind(filters.before.as_deref())
+    .bind(i64::from(limit))
+    .fetch_all(pool)
+    .await
+    .map_err(|e| AppError::Db(format!("Search failed: {}", e)))?;
+
+    // Rooms in the order of their best hit.
+    let mut grouped: Vec<RoomHits> = Vec::new();
+    for row in rows {
+        let (id, room_id): (i64, i64) = (row.get("id"), row.get("room_id"));
+        let hit = SearchHit {
+            message_id: row.get("message_id"),
+            username: row.get("username"),
+            message: row.get("message"),
+            created_at: row.get("created_at"),
+            transcript: row.get("transcript"),
+            image_text: row.get("image_text"),
+            previous: context(pool, room_id, id, false).await?,
+            next: context(pool, room_id, id, true).await?,
+        };
+        match grouped.iter_mut().find(|g| g.room_id == room_id) {
+            Some(group) => group.hits.push(hit),
+            None => grouped.push(RoomHits {
+                room_id,
+                room_name: row.get("room_name"),
+                hits: vec![hit],
+            }),
+        }
+    }
+    Ok(grouped)
+}
+
+/// Search every room `user_id` may open, grouped by room.
+#[tauri::command]
+pub async fn search_all(
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+    query: String,
+    filters: Option<SearchFilters>,
+) -> AppResult<Vec<RoomHits>> {
+    search_all_internal(&db, user_id, &query, &filters.unwrap_or_default()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn searches_visible_rooms_grouped_with_context() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1);
+             INSERT INTO chat_rooms (id, name, is_private, created_by)
+                 VALUES (100, 'ops', 0, 2), (101, 'secret', 1, 2), (102, 'infra', 1, 2);
+             INSERT INTO user_rooms (user_id, room_id, is_active) VALUES (1, 102, 1);
+             INSERT INTO messages (room_id, user_id, message, message_type, message_id) VALUES
+                 (100, 2, 'good morning', 'Chat', 'a'),
+                 (100, 2, 'the deploy failed again', 'Chat', 'b'),
+                 (100, 1, 'looking now', 'Chat', 'c'),
+                 (101, 2, 'deploy keys rotated', 'Chat', 'd'),
+                 (102, 2, 'deploying the fix', 'Chat', 'e'),
+                 (102, 2, 'deploy \"quoted\" OR', 'Chat', 'f');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let search = |q: &'static str, filters: SearchFilters| {
+            let pool = pool.clone();
+            async move { search_all_internal(&pool, 1, q, &filters).await.unwrap() }
+        };
+
+        // 'secret' is private and Alice isn't in it; prefixes match ("deploying").
+        let found = search("deploy", SearchFilters::default()).await;
+        let rooms: Vec<_> = found.iter().map(|g| g.room_name.as_str()).collect();
+        assert_eq!(rooms.len(), 2);
+        assert!(rooms.contains(&"ops") && rooms.contains(&"infra"));
+        let ops = found.iter().find(|g| g.room_id == 100).unwrap();
+        assert_eq!(ops.hits.len(), 1);
+        let hit = &ops.hits[0];
+        assert_eq!(hit.message_id.as_deref(), Some("b"));
+        assert_eq!(hit.previous.as_ref().unwrap().message, "good morning");
+        assert_eq!(hit.next.as_ref().unwrap().username, "Alice");
+
+        // Every word must match; FTS syntax in the query is taken literally.
+        assert_eq!(
+            search("deploy again", SearchFilters::default()).await.len(),
+            1
+        );
+        let quoted = search("\"quoted\" OR", SearchFilters::default()).await;
+        assert_eq!(quoted[0].hits[0].message_id.as_deref(), Some("f"));
+
+        let only_ops = SearchFilters {
+            room_id: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(search("deploy", only_ops).await.len(), 1);
+        let by_alice = SearchFilters {
+            author_id: Some(1),
+            ..Default::default()
+        };
+        assert!(search("deploy", by_alice).await.is_empty());
+
+        // Edits and deletes keep the index in step.
+        sqlx::raw_sql(
+            "UPDATE messages SET message = 'all green' WHERE message_id = 'e';
+             UPDATE messages SET message = '', deleted_at = CURRENT_TIMESTAMP
+                 WHERE message_id = 'b';",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let found = search("deploy", SearchFilters::default()).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].hits[0].message_id.as_deref(), Some("f"));
+        assert_eq!(search("green", SearchFilters::default()).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn voice_messages_are_found_by_their_transcript() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id) VALUES (1, 'Alice', 'a@x', 1);
+             INSERT INTO chat_rooms (id, name, is_private, created_by) VALUES (100, 'ops', 0, 1);
+             INSERT INTO messages (room_id, user_id, message, message_type, message_id) VALUES
+                 (100, 1, '{\"id\":7,\"filename\":\"memo.ogg\"}', 'Attachment', 'v');
+             INSERT INTO attachments (id, room_id, message_id, uploaded_by, filename, mime, size, sha256)
+                 VALUES (7, 100, 'v', 1, 'memo.ogg', 'audio/ogg', 4, 'x');",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        // The attachment's JSON is no hit by itself.
+        assert!(
+            search_all_internal(&pool, 1, "memo", &SearchFilters::default())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        crate::transcription::store_transcript(&pool, 7, "the printer on floor two is jammed")
+            .await
+            .unwrap();
+        let found = search_all_internal(&pool, 1, "printer", &SearchFilters::default())
+            .await
+            .unwrap();
+        let hit = &found[0].hits[0];
+        assert_eq!(hit.message_id.as_deref(), Some("v"));
+        assert_eq!(
+            hit.transcript.as_deref(),
+            Some("the printer on floor two is jammed")
+        );
+
+        crate::transcription::store_transcript(&pool, 7, "all fixed")
+            .await
+            .unwrap();
+        assert!(
+            search_all_internal(&pool, 1, "printer", &SearchFilters::default())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -9,17 +9,55 @@
 // Handshake messages and transport messages are framed identically to the rest of the
 // protocol (a 4-byte big-endian length prefix), so this layers cleanly on top of TCP.
 // Wired into sockets.rs: responder on accept, initiator on connect.
+//
+// A Noise message holds under 64 KB, so larger payloads (a big pasted log) are split into
+// fragments, each its own transport message, and reassembled on the far side. Senders write
+// one fragment at a time, letting other frames on the same connection slip in between.
+//
+// The handshake also settles payload compression (see compression.rs): the host's reply
+// names the codec it picked from the client's hello, and the session carries it.
 
+use crate::codec::{FramedReader, FramedWriter};
+use crate::compression::{self, Codec};
 use sha2::{Digest, Sha256};
 use snow::{Builder, TransportState};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tokio::io::{AsyncRead, AsyncWrite};
 
 /// Noise handshake pattern: no static keys, pre-shared key mixed in at position 0.
 const NOISE_PARAMS: &str = "Noise_NNpsk0_25519_ChaChaPoly_BLAKE2s";
 
-/// Noise caps a single message at 65535 bytes; chat messages are far smaller.
+/// Noise caps a single message at 65535 bytes; larger payloads go as fragments.
 const NOISE_MAX_MESSAGE: usize = 65535;
 
+/// Payloads larger than this are sent as fragments.
+const FRAGMENT_BYTES: usize = 48 * 1024;
+
+/// First byte of a fragment. Whole payloads are JSON objects, so they never start with it.
+const FRAGMENT_MARKER: u8 = 0x1e;
+
+/// Marker, 4-byte stream id, flags.
+const FRAGMENT_HEADER: usize = 6;
+
+/// Set in the flags byte of a payload's final fragment.
+const FRAGMENT_LAST: u8 = 1;
+
+/// Same ceiling as a single frame on the wire.
+const MAX_REASSEMBLED_BYTES: usize = 10 * 1024 * 1024;
+
+/// Partly received payloads one connection may have in flight at once.
+const MAX_OPEN_STREAMS: usize = 16;
+
+static NEXT_STREAM: AtomicU32 = AtomicU32::new(1);
+
+/// An established session: the Noise transport plus what the handshake agreed on.
+pub struct Transport {
+    noise: TransportState,
+    /// How to compress large payloads to this peer, if the handshake settled on a codec.
+    pub codec: Option<Codec>,
+}
+
 /// Derive a 32-byte pre-shared key from the room password. A domain-separation
 /// prefix keeps this key distinct from any other use of the same password.
 pub fn derive_psk(password: &str) -> [u8; 32] {
@@ -32,46 +70,42 @@ pub fn derive_psk(password: &str) -> [u8; 32] {
     psk
 }
 
-/// Write a length-prefixed frame in the clear (used during the handshake).
-async fn write_frame<W>(writer: &mut W, data: &[u8]) -> std::io::Result<()>
+/// Read a handshake frame, in the clear and capped at one Noise message.
+async fn read_frame<R>(reader: &mut R) -> std::io::Result<Vec<u8>>
 where
-    W: AsyncWriteExt + Unpin,
+    R: AsyncRead + Unpin,
 {
-    writer.write_all(&(data.len() as u32).to_be_bytes()).await?;
-    writer.write_all(data).await?;
-    Ok(())
+    FramedReader::with_max(reader, NOISE_MAX_MESSAGE)
+        .read_frame()
+        .await?
+        .ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "empty handshake frame")
+        })
 }
 
-/// Read a length-prefixed frame in the clear (used during the handshake).
-async fn read_frame<R>(reader: &mut R) -> std::io::Result<Vec<u8>>
+/// Write a handshake frame, in the clear.
+async fn write_frame<W>(writer: &mut W, data: &[u8]) -> std::io::Result<()>
 where
-    R: AsyncReadExt + Unpin,
+    W: AsyncWrite + Unpin,
 {
-    let mut len_bytes = [0u8; 4];
-    reader.read_exact(&mut len_bytes).await?;
-    let len = u32::from_be_bytes(len_bytes) as usize;
-    if len > NOISE_MAX_MESSAGE {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "handshake frame too large",
-        ));
-    }
-    let mut buf = vec![0u8; len];
-    reader.read_exact(&mut buf).await?;
-    Ok(buf)
+    FramedWriter::with_max(writer, NOISE_MAX_MESSAGE)
+        .write_frame(data)
+        .await
 }
 
 /// Perform the Noise handshake as the responder (the host side, per accepted client).
-/// Returns the established transport state, or an error if the peer used the wrong
-/// password (the AEAD tag on the first message fails to verify).
+/// Returns the established transport and the payload the initiator sent with its first
+/// message (its hello; empty from older peers), or an error if the peer used the wrong
+/// password (the AEAD tag on the first message fails to verify). Our reply names the
+/// compression codec picked from the hello's offer.
 pub async fn responder_handshake<R, W>(
     reader: &mut R,
     writer: &mut W,
     psk: &[u8; 32],
-) -> Result<TransportState, String>
+) -> Result<(Transport, Vec<u8>), String>
 where
-    R: AsyncReadExt + Unpin,
-    W: AsyncWriteExt + Unpin,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
 {
     let params = NOISE_PARAMS
         .parse()
@@ -88,32 +122,38 @@ where
     let msg1 = read_frame(reader)
         .await
         .map_err(|e| format!("read handshake msg1: {e}"))?;
-    handshake
+    let hello_len = handshake
         .read_message(&msg1, &mut buf)
         .map_err(|_| "handshake failed (wrong password?)".to_string())?;
+    let hello = buf[..hello_len].to_vec();
+    let codec = compression::choose(&hello);
 
     // -> e, ee  (respond)
     let n = handshake
-        .write_message(&[], &mut buf)
+        .write_message(compression::answer(codec), &mut buf)
         .map_err(|e| format!("write handshake msg2: {e}"))?;
     write_frame(writer, &buf[..n])
         .await
         .map_err(|e| format!("send handshake msg2: {e}"))?;
 
-    handshake
+    let noise = handshake
         .into_transport_mode()
-        .map_err(|e| format!("enter transport mode: {e}"))
+        .map_err(|e| format!("enter transport mode: {e}"))?;
+    Ok((Transport { noise, codec }, hello))
 }
 
-/// Perform the Noise handshake as the initiator (a client connecting to a host).
+/// Perform the Noise handshake as the initiator (a client connecting to a host). `hello` rides
+/// (psk-encrypted) in the first message, e.g. our app version; the responder's reply may
+/// name a compression codec.
 pub async fn initiator_handshake<R, W>(
     reader: &mut R,
     writer: &mut W,
     psk: &[u8; 32],
-) -> Result<TransportState, String>
+    hello: &[u8],
+) -> Result<Transport, String>
 where
-    R: AsyncReadExt + Unpin,
-    W: AsyncWriteExt + Unpin,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
 {
     let params = NOISE_PARAMS
         .parse()
@@ -128,7 +168,7 @@ where
 
     // -> e
     let n = handshake
-        .write_message(&[], &mut buf)
+        .write_message(hello, &mut buf)
         .map_err(|e| format!("write handshake msg1: {e}"))?;
     write_frame(writer, &buf[..n])
         .await
@@ -138,23 +178,26 @@ where
     let msg2 = read_frame(reader)
         .await
         .map_err(|e| format!("read handshake msg2: {e}"))?;
-    handshake
+    let reply_len = handshake
         .read_message(&msg2, &mut buf)
         .map_err(|_| "handshake failed (wrong password?)".to_string())?;
+    let codec = compression::accepted(&buf[..reply_len]);
 
-    handshake
+    let noise = handshake
         .into_transport_mode()
-        .map_err(|e| format!("enter transport mode: {e}"))
+        .map_err(|e| format!("enter transport mode: {e}"))?;
+    Ok(Transport { noise, codec })
 }
 
 /// Encrypt one plaintext message into a Noise transport message. The caller frames
 /// the result (length-prefix) before sending.
-pub fn encrypt(transport: &mut TransportState, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+pub fn encrypt(transport: &mut Transport, plaintext: &[u8]) -> Result<Vec<u8>, String> {
     if plaintext.len() + 16 > NOISE_MAX_MESSAGE {
         return Err("message too large to encrypt in a single Noise message".to_string());
     }
     let mut buf = vec![0u8; plaintext.len() + 16]; // + AEAD tag
     let n = transport
+        .noise
         .write_message(plaintext, &mut buf)
         .map_err(|e| format!("encrypt: {e}"))?;
     buf.truncate(n);
@@ -162,15 +205,75 @@ pub fn encrypt(transport: &mut TransportState, plaintext: &[u8]) -> Result<Vec<u
 }
 
 /// Decrypt one Noise transport message back into plaintext.
-pub fn decrypt(transport: &mut TransportState, ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+pub fn decrypt(transport: &mut Transport, ciphertext: &[u8]) -> Result<Vec<u8>, String> {
     let mut buf = vec![0u8; ciphertext.len()];
     let n = transport
+        .noise
         .read_message(ciphertext, &mut buf)
         .map_err(|e| format!("decrypt: {e}"))?;
     buf.truncate(n);
     Ok(buf)
 }
 
+/// Split a payload into the plaintexts to encrypt and send, in order, compressing it first if
+/// the session agreed on a codec. Small payloads go as-is.
+pub fn fragment(codec: Option<Codec>, payload: Vec<u8>) -> Vec<Vec<u8>> {
+    let payload = compression::compress(codec, payload);
+    if payload.len() <= FRAGMENT_BYTES {
+        return vec![payload];
+    }
+    let stream = NEXT_STREAM.fetch_add(1, Ordering::Relaxed);
+    let count = payload.len().div_ceil(FRAGMENT_BYTES);
+    payload
+        .chunks(FRAGMENT_BYTES)
+        .enumerate()
+        .map(|(i, piece)| {
+            let mut out = Vec::with_capacity(FRAGMENT_HEADER + piece.len());
+            out.push(FRAGMENT_MARKER);
+            out.extend_from_slice(&stream.to_be_bytes());
+            out.push(if i + 1 == count { FRAGMENT_LAST } else { 0 });
+            out.extend_from_slice(piece);
+            out
+        })
+        .collect()
+}
+
+/// Per-connection reassembly of fragmented payloads.
+#[derive(Default)]
+pub struct Reassembler {
+    open: HashMap<u32, Vec<u8>>,
+}
+
+impl Reassembler {
+    /// Feed one decrypted plaintext. Returns the whole (decompressed) payload once it's
+    /// complete, `None` while a fragmented one is still arriving, or an error if the peer
+    /// overruns the limits.
+    pub fn push(&mut self, plaintext: Vec<u8>) -> Result<Option<Vec<u8>>, String> {
+        if plaintext.first() != Some(&FRAGMENT_MARKER) {
+            return compression::decompress(plaintext).map(Some);
+        }
+        if plaintext.len() < FRAGMENT_HEADER {
+            return Err("truncated fragment".to_string());
+        }
+        let stream = u32::from_be_bytes([plaintext[1], plaintext[2], plaintext[3], plaintext[4]]);
+        let last = plaintext[5] & FRAGMENT_LAST != 0;
+        if !self.open.contains_key(&stream) && self.open.len() >= MAX_OPEN_STREAMS {
+            return Err("too many fragmented payloads in flight".to_string());
+        }
+        let buf = self.open.entry(stream).or_default();
+        if buf.len() + plaintext.len() - FRAGMENT_HEADER > MAX_REASSEMBLED_BYTES {
+            self.open.remove(&stream);
+            return Err("fragmented payload too large".to_string());
+        }
+        buf.extend_from_slice(&plaintext[FRAGMENT_HEADER..]);
+        if !last {
+            return Ok(None);
+        }
+        let payload = self.open.remove(&stream).unwrap_or_default();
+        compression::decompress(payload).map(Some)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,9 +287,10 @@ mod tests {
         let server = tokio::spawn(async move {
             let (sock, _) = listener.accept().await.unwrap();
             let (mut r, mut w) = sock.into_split();
-            let mut ts = responder_handshake(&mut r, &mut w, &derive_psk("s3cret"))
+            let (mut ts, hello) = responder_handshake(&mut r, &mut w, &derive_psk("s3cret"))
                 .await
                 .expect("responder handshake");
+            assert_eq!(hello, b"nutler/1.2.3");
 
             // Two messages each way to exercise nonce increment beyond the first.
             for expected in ["hello 1", "hello 2"] {
@@ -200,7 +304,7 @@ mod tests {
 
         let sock = TcpStream::connect(addr).await.unwrap();
         let (mut r, mut w) = sock.into_split();
-        let mut ts = initiator_handshake(&mut r, &mut w, &derive_psk("s3cret"))
+        let mut ts = initiator_handshake(&mut r, &mut w, &derive_psk("s3cret"), b"nutler/1.2.3")
             .await
             .expect("initiator handshake");
 
@@ -215,6 +319,27 @@ mod tests {
         server.await.unwrap();
     }
 
+    #[test]
+    fn large_payloads_are_fragmented_and_reassembled() {
+        let small = b"{\"message\":\"hi\"}".to_vec();
+        assert_eq!(fragment(None, small.clone()), vec![small.clone()]);
+
+        let big: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let pieces = fragment(None, big.clone());
+        assert_eq!(pieces.len(), 5);
+        assert!(pieces.iter().all(|p| p.len() + 16 <= NOISE_MAX_MESSAGE));
+
+        // Another payload arriving mid-stream is passed straight through.
+        let mut reassembler = Reassembler::default();
+        let (last, rest) = pieces.split_last().unwrap();
+        for piece in rest {
+            assert_eq!(reassembler.push(piece.clone()).unwrap(), None);
+        }
+        assert_eq!(reassembler.push(small.clone()).unwrap(), Some(small));
+        assert_eq!(reassembler.push(last.clone()).unwrap(), Some(big));
+        assert!(reassembler.open.is_empty());
+    }
+
     #[tokio::test]
     async fn wrong_password_is_rejected() {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -230,7 +355,7 @@ mod tests {
 
         let sock = TcpStream::connect(addr).await.unwrap();
         let (mut r, mut w) = sock.into_split();
-        let res = initiator_handshake(&mut r, &mut w, &derive_psk("wrong-password")).await;
+        let res = initiator_handshake(&mut r, &mut w, &derive_psk("wrong-password"), b"").await;
         assert!(res.is_err(), "initiator should fail with wrong password");
 
         let _ = server.await;
@@ -0,0 +1,396 @@
+// Demo data (developer tool).
+//
+// `seed_demo_data` fills the local DB with a believable office: a few dozen people spread over
+// the default departments, a public and a private channel per department plus a couple of
+// company-wide ones, and a few thousand messages written over the past weeks. It's for trying
+// pagination, search and performance work without typing it all in. Runs are repeatable: the
+// same people and channels are reused (matched by email / name), and each run adds another batch
+// of messages. Demo people use `@demo.invalid` addresses.
+
+use crate::error::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tauri::State;
+use uuid::Uuid;
+
+const DEFAULT_USERS: usize = 40;
+const MAX_USERS: usize = 500;
+const DEFAULT_MESSAGES: usize = 3000;
+const MAX_MESSAGES: usize = 100_000;
+const DEFAULT_DAYS: u32 = 30;
+const MAX_DAYS: u32 = 365;
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada",
+    "Grace",
+    "Alan",
+    "Linus",
+    "Margaret",
+    "Ken",
+    "Barbara",
+    "Dennis",
+    "Frances",
+    "Edsger",
+    "Radia",
+    "Tim",
+    "Hedy",
+    "John",
+    "Katherine",
+    "Niklaus",
+    "Sophie",
+    "Guido",
+    "Joan",
+    "Bjarne",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Lovelace",
+    "Hopper",
+    "Turing",
+    "Torvalds",
+    "Hamilton",
+    "Thompson",
+    "Liskov",
+    "Ritchie",
+    "Allen",
+    "Dijkstra",
+    "Perlman",
+    "Berners-Lee",
+    "Lamarr",
+    "McCarthy",
+    "Johnson",
+    "Wirth",
+    "Wilson",
+    "van Rossum",
+    "Clarke",
+    "Stroustrup",
+];
+
+// Sentence parts combined into messages, so search has repeated words to find and pages don't
+// all look alike.
+const OPENERS: &[&str] = &[
+    "Quick update:",
+    "Heads up,",
+    "FYI",
+    "Morning all!",
+    "Question for the team:",
+    "Reminder:",
+    "Following up on yesterday,",
+    "Good news:",
+];
+
+const TOPICS: &[&str] = &[
+    "the quarterly report",
+    "the printer on the second floor",
+    "the new onboarding checklist",
+    "the server migration",
+    "Friday's release",
+    "the budget review",
+    "the customer call at 3pm",
+    "the VPN outage",
+    "the office move",
+    "the invoice backlog",
+    "the holiday rota",
+    "the shared drive cleanup",
+];
+
+const CLOSERS: &[&str] = &[
+    "is done.",
+    "needs another pair of eyes.",
+    "is delayed until next week.",
+    "looks good to me.",
+    "has a few open questions.",
+    "is on the agenda for the standup.",
+    "should be sorted by end of day.",
+    "- can someone take a look?",
+];
+
+const SHORT_REPLIES: &[&str] = &[
+    "Thanks!",
+    "On it.",
+    "👍",
+    "Sounds good.",
+    "Will do.",
+    "Can we talk after lunch?",
+    "Done ✅",
+    "Good catch, thanks.",
+];
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct SeedOptions {
+    // People to create (40 by default, at most 500).
+    pub users: Option<usize>,
+    // Messages to add across all channels (3000 by default, at most 100,000).
+    pub messages: Option<usize>,
+    // How far back the messages go, in days (30 by default).
+    pub days: Option<u32>,
+}
+
+struct DemoChannel {
+    name: String,
+    description: String,
+    department_id: Option<i64>,
+    private: bool,
+    // Who's in it (and posts there); the first one created it.
+    members: Vec<i64>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct SeedReport {
+    pub users_created: usize,
+    pub rooms_created: usize,
+    pub messages_created: usize,
+}
+
+/// A small xorshift generator: demo data doesn't need good randomness, and a fixed seed keeps
+/// runs comparable.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+fn demo_message(rng: &mut Rng) -> String {
+    if rng.below(4) == 0 {
+        rng.pick(SHORT_REPLIES).to_string()
+    } else {
+        format!(
+            "{} {} {}",
+            rng.pick(OPENERS),
+            rng.pick(TOPICS),
+            rng.pick(CLOSERS)
+        )
+    }
+}
+
+pub async fn seed_demo_data_internal(
+    pool: &SqlitePool,
+    options: &SeedOptions,
+) -> AppResult<SeedReport> {
+    let users = options.users.unwrap_or(DEFAULT_USERS).clamp(1, MAX_USERS);
+    let messages = options
+        .messages
+        .unwrap_or(DEFAULT_MESSAGES)
+        .min(MAX_MESSAGES);
+    let days = options.days.unwrap_or(DEFAULT_DAYS).clamp(1, MAX_DAYS);
+    let mut report = SeedReport::default();
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let mut tx = pool.begin().await?;
+
+    let departments: Vec<(i64, String)> =
+        sqlx::query_as("SELECT id, name FROM departments ORDER BY id")
+            .fetch_all(&mut *tx)
+            .await?;
+    if departments.is_empty() {
+        return Err(AppError::Validation(
+            "There are no departments to put people in".to_string(),
+        ));
+    }
+
+    // People, dealt round-robin into departments.
+    let mut people: Vec<(i64, i64)> = Vec::with_capacity(users);
+    for n in 0..users {
+        let first = FIRST_NAMES[n % FIRST_NAMES.len()];
+        let last = LAST_NAMES[(n / FIRST_NAMES.len() + n) % LAST_NAMES.len()];
+        let email = format!(
+            "{}.{}{}@demo.invalid",
+            first.to_lowercase(),
+            last.to_lowercase().replace([' ', '-'], ""),
+            n
+        );
+        let (department_id, _) = &departments[n % departments.len()];
+        let inserted = sqlx::query(
+            "INSERT INTO users (name, email, department_id) VALUES ($1, $2, $3)
+             ON CONFLICT(email) DO NOTHING",
+        )
+        .bind(format!("{} {}", first, last))
+        .bind(&email)
+        .bind(department_id)
+        .execute(&mut *tx)
+        .await?;
+        report.users_created += inserted.rows_affected() as usize;
+        let id: i64 = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_one(&mut *tx)
+            .await?;
+        people.push((id, *department_id));
+    }
+
+    // Channels: (room id, the people who post there).
+    let mut rooms: Vec<(i64, Vec<i64>)> = Vec::new();
+    let everyone: Vec<i64> = people.iter().map(|(id, _)| *id).collect();
+    let mut channels = vec![
+        DemoChannel {
+            name: "demo-announcements".to_string(),
+            description: "Company news".to_string(),
+            department_id: None,
+            private: false,
+            members: everyone.clone(),
+        },
+        DemoChannel {
+            name: "demo-random".to_string(),
+            description: "Off-topic chat".to_string(),
+            department_id: None,
+            private: false,
+            members: everyone,
+        },
+    ];
+    for (department_id, name) in &departments {
+        let members: Vec<i64> = people
+            .iter()
+            .filter(|(_, d)| d == department_id)
+            .map(|(id, _)| *id)
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        let slug = name.to_lowercase().replace(' ', "-");
+        channels.push(DemoChannel {
+            name: format!("demo-{}", slug),
+            description: format!("{} team chat", name),
+            department_id: Some(*department_id),
+            private: false,
+            members: members.clone(),
+        });
+        channels.push(DemoChannel {
+            name: format!("demo-{}-leads", slug),
+            description: format!("{} leads (private)", name),
+            department_id: Some(*department_id),
+            private: true,
+            members: members.into_iter().take(3).collect(),
+        });
+    }
+    for channel in channels {
+        let inserted = sqlx::query(
+            "INSERT INTO chat_rooms (name, description, department_id, is_private, created_by)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT(name) DO NOTHING",
+        )
+        .bind(&channel.name)
+        .bind(&channel.description)
+        .bind(channel.department_id)
+        .bind(channel.private)
+        .bind(channel.members[0])
+        .execute(&mut *tx)
+        .await?;
+        report.rooms_created += inserted.rows_affected() as usize;
+        let room_id: i64 = sqlx::query_scalar("SELECT id FROM chat_rooms WHERE name = $1")
+            .bind(&channel.name)
+            .fetch_one(&mut *tx)
+            .await?;
+        for user_id in &channel.members {
+            sqlx::query(
+                "INSERT INTO user_rooms (user_id, room_id, is_active) VALUES ($1, $2, 1)
+                 ON CONFLICT(user_id, room_id) DO NOTHING",
+            )
+            .bind(user_id)
+            .bind(room_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        rooms.push((room_id, channel.members));
+    }
+
+    // Messages spread evenly back over `days`, oldest first so ids follow time.
+    let span_secs = i64::from(days) * 86_400;
+    for i in 0..messages {
+        let (room_id, members) = &rooms[rng.below(rooms.len())];
+        let user_id = members[rng.below(members.len())];
+        let ago = span_secs - span_secs * i as i64 / messages as i64;
+        sqlx::query(
+            "INSERT INTO messages (room_id, user_id, message, message_type, is_emoji, message_id, created_at)
+             VALUES ($1, $2, $3, 'Chat', 0, $4, datetime('now', '-' || $5 || ' seconds'))",
+        )
+        .bind(room_id)
+        .bind(user_id)
+        .bind(demo_message(&mut rng))
+        .bind(Uuid::new_v4().to_string())
+        .bind(ago)
+        .execute(&mut *tx)
+        .await?;
+        report.messages_created += 1;
+    }
+    tx.commit().await?;
+    Ok(report)
+}
+
+/// Fill the DB with demo people, channels and messages.
+#[tauri::command]
+pub async fn seed_demo_data(
+    app: tauri::AppHandle,
+    db: State<'_, SqlitePool>,
+    options: Option<SeedOptions>,
+) -> AppResult<SeedReport> {
+    use tauri::Emitter;
+    let report = seed_demo_data_internal(&db, &options.unwrap_or_default()).await?;
+    if report.rooms_created > 0 {
+        let _ = app.emit("rooms_changed", ());
+    }
+    tracing::info!("🌱 Seeded demo data: {:?}", report);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn seeding_is_repeatable() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        let options = SeedOptions {
+            users: Some(12),
+            messages: Some(500),
+            days: Some(7),
+        };
+
+        let first = seed_demo_data_internal(&pool, &options).await.unwrap();
+        assert_eq!(first.users_created, 12);
+        assert_eq!(first.messages_created, 500);
+        assert!(first.rooms_created >= 4);
+        // Private channels only hold (and hear from) their members.
+        let outsiders: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM messages m JOIN chat_rooms cr ON cr.id = m.room_id
+             WHERE cr.is_private = 1 AND NOT EXISTS (
+                 SELECT 1 FROM user_rooms ur WHERE ur.room_id = m.room_id AND ur.user_id = m.user_id)",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(outsiders, 0);
+        let span: f64 = sqlx::query_scalar(
+            "SELECT julianday('now') - julianday(MIN(created_at)) FROM messages",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert!(span > 6.99 && span < 7.01);
+
+        let again = seed_demo_data_internal(&pool, &options).await.unwrap();
+        assert_eq!((again.users_created, again.rooms_created), (0, 0));
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(total, 1000);
+    }
+}
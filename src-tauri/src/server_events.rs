@@ -0,0 +1,295 @@
+// Host console event feed (host-side).
+//
+// Besides chat, the host's UI gets a `server_event` stream for its admin panel: clients
+// connecting, reconnecting and disconnecting, connection errors and refused sign-ins, mutes and
+// unmutes, clients falling behind on delivery, and while hosting a throughput snapshot every
+// SNAPSHOT_INTERVAL. Connection events are written to the connection log as they're emitted, so
+// `get_server_events` can fill the panel from there when it opens.
+
+use crate::connection_log;
+use crate::db_queries::normalize_email;
+use crate::error::AppResult;
+use crate::metrics::METRICS;
+use crate::sockets::{now_secs, AppState, DrainStats};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{Emitter, Runtime, State};
+
+pub const MUTE: &str = "mute";
+pub const UNMUTE: &str = "unmute";
+pub const THROUGHPUT: &str = "throughput";
+pub const LAGGING: &str = "lagging";
+
+/// How often a throughput snapshot is emitted while hosting.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_LIMIT: u32 = 100;
+
+#[derive(Serialize, Clone, Debug, PartialEq, sqlx::FromRow)]
+pub struct ServerEvent {
+    // A connection_log event, or MUTE / UNMUTE / THROUGHPUT.
+    pub kind: String,
+    pub user_id: Option<i64>,
+    pub user_name: Option<String>,
+    // The peer's address as the host saw it.
+    pub peer: Option<String>,
+    pub detail: Option<String>,
+    // Unix seconds.
+    pub created_at: i64,
+}
+
+fn emit<R: Runtime>(app: &tauri::AppHandle<R>, event: ServerEvent) {
+    if let Err(e) = app.emit("server_event", event) {
+        tracing::warn!("Couldn't emit a server event: {}", e);
+    }
+}
+
+/// Emit an event about a user, named from the DB.
+pub(crate) async fn emit_for_user<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    pool: &SqlitePool,
+    kind: &str,
+    user_id: Option<i64>,
+    peer: Option<String>,
+    detail: Option<String>,
+) {
+    let user_name: Option<String> = match user_id {
+        Some(id) => sqlx::query_scalar("SELECT name FROM users WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .unwrap_or(None),
+        None => None,
+    };
+    emit(
+        app,
+        ServerEvent {
+            kind: kind.to_string(),
+            user_id,
+            user_name,
+            peer,
+            detail,
+            created_at: now_secs() as i64,
+        },
+    );
+}
+
+/// The `client_lagging` event: a client whose delivery is behind, or stuck on a write.
+#[derive(Serialize, Clone, Debug)]
+pub struct ClientLagging {
+    pub user_id: u64,
+    pub username: String,
+    pub room: String,
+    // Frames waiting to be sent to it.
+    pub queued: usize,
+    pub last_write_age_ms: i64,
+}
+
+/// Emit `client_lagging`, and the same as a LAGGING event for the console.
+pub(crate) fn lagging<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    user_id: u64,
+    username: &str,
+    room: &str,
+    drain: &DrainStats,
+) {
+    let lag = ClientLagging {
+        user_id,
+        username: username.to_string(),
+        room: room.to_string(),
+        queued: drain.queued.load(Ordering::Relaxed),
+        last_write_age_ms: drain.last_write_age_ms(),
+    };
+    if let Err(e) = app.emit("client_lagging", &lag) {
+        tracing::warn!("Couldn't emit client_lagging: {}", e);
+    }
+    let detail = format!(
+        "{} frames queued in {}, last write {}s ago",
+        lag.queued,
+        lag.room,
+        lag.last_write_age_ms / 1000
+    );
+    emit(
+        app,
+        ServerEvent {
+            kind: LAGGING.to_string(),
+            user_id: Some(user_id as i64),
+            user_name: Some(lag.username),
+            peer: None,
+            detail: Some(detail),
+            created_at: now_secs() as i64,
+        },
+    );
+}
+
+/// Record a connection event in the connection log and emit it.
+pub(crate) async fn connection<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    pool: &SqlitePool,
+    user_id: u64,
+    event: &str,
+    peer: &SocketAddr,
+    detail: Option<&str>,
+) {
+    connection_log::record(pool, user_id, event, peer, detail).await;
+    emit_for_user(
+        app,
+        pool,
+        event,
+        Some(user_id as i64),
+        Some(peer.to_string()),
+        detail.map(str::to_string),
+    )
+    .await;
+}
+
+/// Record a refused sign-in (see connection_log::record_rejection) and emit it, against the
+/// account it was for when that's one we know.
+pub(crate) async fn rejection<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    pool: &SqlitePool,
+    email: Option<&str>,
+    peer: &SocketAddr,
+    reason: &str,
+) {
+    connection_log::record_rejection(pool, email, peer, reason).await;
+    let user_id: Option<i64> = match email {
+        Some(email) => sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(normalize_email(email))
+            .fetch_optional(pool)
+            .await
+            .unwrap_or(None),
+        None => None,
+    };
+    let detail = format!("sign-in rejected: {}", reason);
+    emit_for_user(
+        app,
+        pool,
+        connection_log::ERROR,
+        user_id,
+        Some(peer.to_string()),
+        Some(detail),
+    )
+    .await;
+}
+
+fn snapshot_text(connected: usize, frames: u64) -> String {
+    format!(
+        "{} connected, {} frames in the last {}s",
+        connected,
+        frames,
+        SNAPSHOT_INTERVAL.as_secs()
+    )
+}
+
+/// Start (or restart) the throughput snapshots for this hosting session.
+pub(crate) async fn start(app: tauri::AppHandle, state: Arc<AppState>) {
+    let ticker_state = Arc::clone(&state);
+    let ticker = tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(SNAPSHOT_INTERVAL);
+        ticker.tick().await;
+        let mut last = METRICS.messages_broadcast.load(Ordering::Relaxed);
+        loop {
+            ticker.tick().await;
+            let sent = METRICS.messages_broadcast.load(Ordering::Relaxed);
+            let connected = ticker_state.server_streams.lock().await.len();
+            emit(
+                &app,
+                ServerEvent {
+                    kind: THROUGHPUT.to_string(),
+                    user_id: None,
+                    user_name: None,
+                    peer: None,
+                    detail: Some(snapshot_text(connected, sent.saturating_sub(last))),
+                    created_at: now_secs() as i64,
+                },
+            );
+            last = sent;
+        }
+    });
+    if let Some(old) = state.server_event_ticker.lock().await.replace(ticker) {
+        old.abort();
+    }
+}
+
+pub(crate) async fn stop(state: &AppState) {
+    if let Some(ticker) = state.server_event_ticker.lock().await.take() {
+        ticker.abort();
+    }
+}
+
+pub async fn get_server_events_internal(
+    pool: &SqlitePool,
+    limit: u32,
+) -> AppResult<Vec<ServerEvent>> {
+    Ok(sqlx::query_as(
+        "SELECT cl.event AS kind, cl.user_id, u.name AS user_name, cl.peer, cl.detail,
+                CAST(strftime('%s', cl.created_at) AS INTEGER) AS created_at
+         FROM connection_log cl LEFT JOIN users u ON u.id = cl.user_id
+         ORDER BY cl.id DESC LIMIT $1",
+    )
+    .bind(i64::from(limit))
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Host: the latest connection events across all users, newest first (100 unless `limit` says
+/// otherwise). Live events arrive as `server_event`.
+#[tauri::command]
+pub async fn get_server_events(
+    db: State<'_, SqlitePool>,
+    limit: Option<u32>,
+) -> AppResult<Vec<ServerEvent>> {
+    get_server_events_internal(&db, limit.unwrap_or(DEFAULT_LIMIT).min(1000)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn events_come_from_the_connection_log_newest_first() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let peer: SocketAddr = "10.0.0.7:5123".parse().unwrap();
+        connection_log::record(&pool, 1, connection_log::CONNECT, &peer, None).await;
+        connection_log::record(&pool, 2, connection_log::CONNECT, &peer, None).await;
+        connection_log::record(&pool, 1, connection_log::DISCONNECT, &peer, Some("dropped")).await;
+
+        let events = get_server_events_internal(&pool, 10).await.unwrap();
+        let seen: Vec<_> = events
+            .iter()
+            .map(|e| (e.kind.as_str(), e.user_name.as_deref()))
+            .collect();
+        assert_eq!(
+            seen,
+            [
+                (connection_log::DISCONNECT, Some("Alice")),
+                (connection_log::CONNECT, Some("Bob")),
+                (connection_log::CONNECT, Some("Alice")),
+            ]
+        );
+        assert_eq!(events[0].detail.as_deref(), Some("dropped"));
+        assert!((events[0].created_at - now_secs() as i64).abs() < 60);
+        assert_eq!(get_server_events_internal(&pool, 1).await.unwrap().len(), 1);
+        assert_eq!(
+            snapshot_text(3, 42),
+            "3 connected, 42 frames in the last 10s"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+// First-run setup.
+//
+// On launch the UI asks `get_setup_status` whether the active profile has been set up. If not,
+// it walks the user through the wizard: their name and email, their department (an existing one
+// or a new one), and whether this machine hosts a server or joins one. `complete_setup` then
+// saves it all in one transaction: the department, the user and the profile's setup record (and
+// for "join", the server as a favorite), so a wizard cut short leaves nothing half done. The
+// record lives in the settings under `setup.<profile id>`; after it the UI goes straight to
+// hosting or connecting.
+
+use crate::db_queries::{
+    apply_auto_join_internal, get_departments_internal, get_setting_internal,
+    get_user_by_id_internal, normalize_email, validate_username, Department, User,
+};
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tauri::State;
+
+/// Longest department name the wizard creates.
+const MAX_DEPARTMENT_CHARS: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SetupMode {
+    // This machine hosts a server.
+    Host,
+    // It joins someone else's.
+    Join,
+}
+
+/// A profile's saved setup.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetupRecord {
+    pub user_id: i64,
+    pub mode: SetupMode,
+    // The server to join ("host:port"), for Join.
+    #[serde(default)]
+    pub server: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SetupStatus {
+    // The active profile.
+    pub profile: String,
+    // No setup saved for it yet: show the wizard.
+    pub needs_setup: bool,
+    pub setup: Option<SetupRecord>,
+    pub user: Option<User>,
+    // For the wizard's department step.
+    pub departments: Vec<Department>,
+}
+
+/// What the wizard collected. The department is `department_id`, or a new one called
+/// `new_department` (reused if it already exists).
+#[derive(Deserialize, Clone, Debug)]
+pub struct SetupChoices {
+    pub name: String,
+    pub email: String,
+    #[serde(default)]
+    pub department_id: Option<i64>,
+    #[serde(default)]
+    pub new_department: Option<String>,
+    pub mode: SetupMode,
+    #[serde(default)]
+    pub server: Option<String>,
+}
+
+fn settings_key(profile: &str) -> String {
+    format!("setup.{}", profile)
+}
+
+pub async fn load_record(pool: &SqlitePool, profile: &str) -> AppResult<Option<SetupRecord>> {
+    match get_setting_internal(pool, &settings_key(profile))
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| AppError::Internal(format!("Corrupt setup record: {}", e))),
+        None => Ok(None),
+    }
+}
+
+/// Check the wizard's answers before anything is written.
+fn validate(choices: &SetupChoices) -> AppResult<()> {
+    validate_username(choices.name.trim()).map_err(AppError::Validation)?;
+    let email = choices.email.trim();
+    if email.is_empty() || email.len() > 254 || !email.contains('@') {
+        return Err(AppError::Validation(
+            "A valid email address is required".to_string(),
+        ));
+    }
+    if normalize_email(email).ends_with(crate::guests::GUEST_EMAIL_SUFFIX) {
+        return Err(AppError::Validation(
+            "Guest addresses can't be used to set up".to_string(),
+        ));
+    }
+    if let Some(name) = &choices.new_department {
+        let len = name.trim().chars().count();
+        if len == 0 || len > MAX_DEPARTMENT_CHARS {
+            return Err(AppError::Validation(format!(
+                "Department names must be between 1 and {} characters",
+                MAX_DEPARTMENT_CHARS
+            )));
+        }
+    }
+    if choices.mode == SetupMode::Join && choices.server.as_deref().unwrap_or("").trim().is_empty()
+    {
+        return Err(AppError::Validation(
+            "Enter the address of the server to join".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Save the wizard's choices for `profile` in one transaction; returns the record.
+pub async fn complete_setup_internal(
+    pool: &SqlitePool,
+    profile: &str,
+    choices: &SetupChoices,
+) -> AppResult<SetupRecord> {
+    validate(choices)?;
+    let mut tx = pool.begin().await?;
+    let department_id = match (&choices.new_department, choices.department_id) {
+        (Some(name), _) => {
+            let name = name.trim();
+            sqlx::query("INSERT INTO departments (name) VALUES ($1) ON CONFLICT(name) DO NOTHING")
+                .bind(name)
+                .execute(&mut *tx)
+                .await?;
+            Some(
+                sqlx::query_scalar::<_, i64>("SELECT id FROM departments WHERE name = $1")
+                    .bind(name)
+                    .fetch_one(&mut *tx)
+                    .await?,
+            )
+        }
+        (None, Some(id)) => {
+            let known: Option<i64> = sqlx::query_scalar("SELECT id FROM departments WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            if known.is_none() {
+                return Err(AppError::Validation(
+                    "That department doesn't exist".to_string(),
+                ));
+            }
+            Some(id)
+        }
+        (None, None) => None,
+    };
+    let user_id: i64 = sqlx::query_scalar(
+        "INSERT INTO users (name, email, department_id) VALUES ($1, $2, $3)
+         ON CONFLICT(email) DO UPDATE SET
+             name = excluded.name,
+             department_id = COALESCE(excluded.department_id, department_id)
+         RETURNING id",
+    )
+    .bind(choices.name.trim())
+    .bind(normalize_email(&choices.email))
+    .bind(department_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let server = match choices.mode {
+        SetupMode::Host => None,
+        SetupMode::Join => choices.server.as_deref().map(|s| s.trim().to_string()),
+    };
+    if let Some(address) = &server {
+        sqlx::query(
+            "INSERT INTO known_servers (address, favorite) VALUES ($1, 1)
+             ON CONFLICT(address) DO UPDATE SET favorite = 1",
+        )
+        .bind(address)
+        .execute(&mut *tx)
+        .await?;
+    }
+    let record = SetupRecord {
+        user_id,
+        mode: choices.mode,
+        server,
+    };
+    let json = serde_json::to_string(&record).map_err(|e| AppError::Internal(e.to_string()))?;
+    sqlx::query(
+        "INSERT INTO app_settings (key, value) VALUES ($1, $2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(settings_key(profile))
+    .bind(&json)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    // Joining the department's auto-join rooms is redone on every sign-in, so it needn't be in
+    // the transaction.
+    if let Err(e) = apply_auto_join_internal(pool, user_id).await {
+        tracing::warn!("{}", e);
+    }
+    Ok(record)
+}
+
+/// Whether the active profile still needs the setup wizard, and what it needs to show.
+#[tauri::command]
+pub async fn get_setup_status(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+) -> AppResult<SetupStatus> {
+    let profile = state.active_profile.read().await.clone();
+    let setup = load_record(&db, &profile).await?;
+    let user = match &setup {
+        Some(record) => get_user_by_id_internal(&db, record.user_id)
+            .await
+            .map_err(AppError::Db)?,
+        None => None,
+    };
+    let departments = get_departments_internal(&db).await.map_err(AppError::Db)?;
+    Ok(SetupStatus {
+        profile,
+        needs_setup: setup.is_none(),
+        setup,
+        user,
+        departments,
+    })
+}
+
+/// Finish the wizard for the active profile: create (or update) the user and department and
+/// remember whether this machine hosts or joins.
+#[tauri::command]
+pub async fn complete_setup(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    choices: SetupChoices,
+) -> AppResult<SetupRecord> {
+    let profile = state.active_profile.read().await.clone();
+    complete_setup_internal(&db, &profile, &choices).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::memory_pool;
+
+    #[tokio::test]
+    async fn setup_is_saved_all_at_once() {
+        let pool = memory_pool().await;
+        let choices = SetupChoices {
+            name: "Dana".to_string(),
+            email: " Dana@Example.com ".to_string(),
+            department_id: Some(999),
+            new_department: None,
+            mode: SetupMode::Join,
+            server: Some("10.0.0.5:3625".to_string()),
+        };
+
+        // An unknown department fails the whole thing: no user, no record.
+        assert!(complete_setup_internal(&pool, "default", &choices)
+            .await
+            .is_err());
+        let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(users, 0);
+        assert_eq!(load_record(&pool, "default").await.unwrap(), None);
+        let no_server = SetupChoices {
+            server: None,
+            ..choices.clone()
+        };
+        assert!(complete_setup_internal(&pool, "default", &no_server)
+            .await
+            .is_err());
+
+        let choices = SetupChoices {
+            department_id: None,
+            new_department: Some(" Facilities ".to_string()),
+            ..choices
+        };
+        let record = complete_setup_internal(&pool, "default", &choices)
+            .await
+            .unwrap();
+        assert_eq!(
+            load_record(&pool, "default").await.unwrap().as_ref(),
+            Some(&record)
+        );
+        assert_eq!(record.server.as_deref(), Some("10.0.0.5:3625"));
+        let user = get_user_by_id_internal(&pool, record.user_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.email, "dana@example.com");
+        assert_eq!(user.department_name.as_deref(), Some("Facilities"));
+        let favorite: bool = sqlx::query_scalar(
+            "SELECT favorite FROM known_servers WHERE address = '10.0.0.5:3625'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert!(favorite);
+        assert_eq!(load_record(&pool, "other-profile").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,116 @@
+// Clean shutdown on exit.
+//
+// However the app goes away (quitting, or force-closing its window), it ends in
+// RunEvent::Exit, and `on_exit` signs off the same way the disconnect buttons do before the
+// process ends. A host tells every client it's stopping and lets them go. A client sends its
+// host a Disconnect, and so does each extra workspace session. Then the user is marked offline
+// in the local DB (while hosting, everyone is, since nobody is connected any more) and the
+// usage counters still in memory are saved. Messages are written as they arrive, so nothing
+// else is pending. All of it is bounded by SHUTDOWN_GRACE, so an unreachable host can't keep
+// the app from closing.
+
+use crate::sockets::{
+    client_disconnect, server_participant_disconnect, session_disconnect, AppState,
+};
+use crate::usage_stats;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Manager;
+
+/// The longest exit waits for goodbyes to be sent and state to be saved.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(3);
+
+/// Mark `user_id` offline, or everyone when the host is going down.
+async fn mark_offline(pool: &SqlitePool, user_id: Option<u64>, hosting: bool) {
+    let result = if hosting {
+        sqlx::query(
+            "UPDATE users SET is_online = 0, last_seen = CURRENT_TIMESTAMP WHERE is_online = 1",
+        )
+        .execute(pool)
+        .await
+    } else if let Some(id) = user_id {
+        sqlx::query("UPDATE users SET is_online = 0, last_seen = CURRENT_TIMESTAMP WHERE id = $1")
+            .bind(id as i64)
+            .execute(pool)
+            .await
+    } else {
+        return;
+    };
+    if let Err(e) = result {
+        tracing::warn!("Couldn't mark the user offline on exit: {}", e);
+    }
+}
+
+async fn shut_down(app: &tauri::AppHandle) {
+    let state = app.state::<Arc<AppState>>();
+    let user_id = *state.user_id.read().await;
+    let hosting = *state.is_server.read().await;
+    let sessions: Vec<String> = state.sessions.lock().await.keys().cloned().collect();
+    for session_id in sessions {
+        let _ = session_disconnect(app.clone(), app.state(), session_id).await;
+    }
+    if hosting {
+        let _ = server_participant_disconnect(app.clone(), app.state()).await;
+    } else if user_id.is_some() {
+        let _ = client_disconnect(app.clone(), app.state()).await;
+    }
+    // The pool is managed in setup, so it's missing if setup failed.
+    if let Some(pool) = app.try_state::<SqlitePool>() {
+        mark_offline(&pool, user_id, hosting).await;
+        if let Err(e) = usage_stats::flush(&pool).await {
+            tracing::debug!("Usage statistics not saved on exit: {}", e);
+        }
+    }
+}
+
+/// Sign off and save state before the process exits.
+pub fn on_exit(app: &tauri::AppHandle) {
+    tauri::async_runtime::block_on(async {
+        if tokio::time::timeout(SHUTDOWN_GRACE, shut_down(app))
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "Clean shutdown took over {:?}; exiting anyway",
+                SHUTDOWN_GRACE
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn exit_marks_the_user_offline_or_everyone_when_hosting() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id, is_online)
+                 VALUES (1, 'Alice', 'a@x', 1, 1), (2, 'Bob', 'b@x', 1, 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let online = || async {
+            sqlx::query_scalar::<_, i64>("SELECT id FROM users WHERE is_online = 1 ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+        };
+
+        mark_offline(&pool, None, false).await;
+        assert_eq!(online().await, [1, 2]);
+        mark_offline(&pool, Some(1), false).await;
+        assert_eq!(online().await, [2]);
+        mark_offline(&pool, Some(1), true).await;
+        assert!(online().await.is_empty());
+    }
+}
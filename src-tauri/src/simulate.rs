@@ -0,0 +1,398 @@
+// Load-test / simulation mode (developer tool).
+//
+// `simulate_clients` spins up synthetic in-process clients that connect to THIS machine's
+// running host over loopback — through the real Noise handshake, Connect registration, rate
+// limiter, room fan-out and DB writes — each sending chat at a fixed rate into a dedicated
+// `load-test` channel. Every other synthetic client in the room should receive each message, so
+// the run reports throughput, end-to-end broadcast latency (send → receipt at a peer) and how
+// many expected deliveries never arrived (rate-limited, lagged or evicted).
+//
+// Synthetic users (`sim-N@loadtest.invalid`) and their messages are written to the host DB like
+// any other traffic. The room password is passed in per run and never stored.
+
+use crate::codec::FramedWriter;
+use crate::db_queries::{create_room_internal, get_chat_rooms_internal};
+use crate::error::{AppError, AppResult};
+use crate::secure;
+use crate::sockets::{
+    frame, open_secure_client, send_secure, AppState, Message, MessageType, PeerWriter,
+};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+/// Channel the synthetic clients chat in, created on first run.
+const SIM_ROOM: &str = "load-test";
+
+/// Upper bound on synthetic clients (stays under the host's MAX_CONCURRENT_CLIENTS).
+const MAX_SIM_CLIENTS: usize = 200;
+
+/// Per-client send rate bounds. The floor keeps every client sending well inside the host's
+/// READ_TIMEOUT, so an idle synthetic client is never evicted mid-run.
+const MIN_MSGS_PER_SEC: f64 = 0.1;
+const MAX_MSGS_PER_SEC: f64 = 1000.0;
+
+const DEFAULT_DURATION_SECS: u64 = 10;
+const MAX_DURATION_SECS: u64 = 300;
+
+/// Time allowed for Connect registration before sending, and for in-flight frames to land after.
+const SETTLE: Duration = Duration::from_millis(500);
+const DRAIN: Duration = Duration::from_secs(2);
+
+/// Set while a simulation runs. Loopback peers are exempt from the per-IP connection cap only
+/// then, so a run can exceed MAX_CONN_PER_IP without loosening the cap for normal hosting.
+static SIMULATION_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn active() -> bool {
+    SIMULATION_ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Clears SIMULATION_ACTIVE however the run ends (including an early `?` return).
+struct ActiveGuard;
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        SIMULATION_ACTIVE.store(false, Ordering::SeqCst);
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SimulationReport {
+    pub clients_requested: usize,
+    pub clients_connected: usize,
+    pub duration_ms: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    // Each sent message should reach every OTHER connected synthetic client.
+    pub expected_deliveries: u64,
+    pub dropped: u64,
+    // Deliveries per second across all receivers.
+    pub throughput_per_sec: f64,
+    pub latency_avg_ms: f64,
+    pub latency_p50_ms: f64,
+    pub latency_p99_ms: f64,
+    pub latency_max_ms: f64,
+}
+
+/// Counters shared by every synthetic client in one run.
+struct SimStats {
+    started: Instant,
+    sent: AtomicU64,
+    received: AtomicU64,
+    // Broadcast latencies in microseconds, one per delivery.
+    latencies_us: std::sync::Mutex<Vec<u64>>,
+}
+
+/// Synthetic messages carry `sim:<sent_at_us>` (µs since the run started), so a receiver can
+/// compute latency without any clock sync — every client lives in this process.
+fn encode_payload(sent_at_us: u64) -> String {
+    format!("sim:{}", sent_at_us)
+}
+
+fn decode_payload(text: &str) -> Option<u64> {
+    text.strip_prefix("sim:")?.parse().ok()
+}
+
+/// Nearest-rank percentile over a sorted slice, in milliseconds.
+fn percentile_ms(sorted_us: &[u64], pct: f64) -> f64 {
+    if sorted_us.is_empty() {
+        return 0.0;
+    }
+    let rank = ((pct / 100.0) * sorted_us.len() as f64).ceil() as usize;
+    sorted_us[rank.clamp(1, sorted_us.len()) - 1] as f64 / 1000.0
+}
+
+fn sim_message(
+    message_type: MessageType,
+    n: usize,
+    room_id: u64,
+    text: String,
+    email: Option<String>,
+) -> Message {
+    // user_id 0: the host assigns the canonical id from the email on Connect.
+    Message {
+        username: format!("sim-{}", n),
+        room: SIM_ROOM.to_string(),
+        room_id,
+        created_at: 0,
+        email,
+        ..frame(message_type, 0, text)
+    }
+}
+
+/// Find or create the public load-test channel on the host DB.
+async fn ensure_sim_room(pool: &sqlx::SqlitePool, host_user_id: u64) -> AppResult<u64> {
+    let rooms = get_chat_rooms_internal(pool, host_user_id as i64, &[])
+        .await
+        .map_err(AppError::Db)?;
+    if let Some(id) = rooms.iter().find(|r| r.name == SIM_ROOM).and_then(|r| r.id) {
+        return Ok(id as u64);
+    }
+    let room = create_room_internal(
+        pool,
+        SIM_ROOM.to_string(),
+        Some("Synthetic traffic from simulate_clients".to_string()),
+        None,
+        Some(false),
+        Some(host_user_id as i64),
+        None,
+    )
+    .await?;
+    room.id
+        .map(|id| id as u64)
+        .ok_or_else(|| AppError::Internal("Load-test channel has no id".to_string()))
+}
+
+/// Connect one synthetic client, register it, and start its receive loop.
+async fn connect_sim_client(
+    host: &str,
+    password: &str,
+    n: usize,
+    room_id: u64,
+    stats: Arc<SimStats>,
+) -> AppResult<(
+    Arc<PeerWriter<FramedWriter<tokio::net::tcp::OwnedWriteHalf>>>,
+    Arc<tokio::sync::Mutex<crate::secure::Transport>>,
+    tauri::async_runtime::JoinHandle<()>,
+)> {
+    let (mut reader, writer, transport) = open_secure_client(host, password).await?;
+    let writer = Arc::new(PeerWriter::new(writer));
+    let transport = Arc::new(tokio::sync::Mutex::new(transport));
+
+    let connect = sim_message(
+        MessageType::Connect,
+        n,
+        room_id,
+        String::new(), // the host composes the join notice
+        Some(format!("sim-{}@loadtest.invalid", n)),
+    );
+    send_secure(&writer, &transport, &connect)
+        .await
+        .map_err(AppError::Network)?;
+
+    let reader_transport = Arc::clone(&transport);
+    let me = format!("sim-{}", n);
+    let reader_task = tauri::async_runtime::spawn(async move {
+        // Keep-alives and non-chat frames (rosters, history, unread) are read and ignored.
+        while let Ok(frame) = reader.read_frame().await {
+            let Some(ciphertext) = frame else { continue };
+            let plaintext = {
+                let mut ts = reader_transport.lock().await;
+                match secure::decrypt(&mut ts, &ciphertext) {
+                    Ok(p) => p,
+                    Err(_) => break,
+                }
+            };
+            let Ok(msg) = serde_json::from_slice::<Message>(&plaintext) else {
+                continue;
+            };
+            // The host excludes senders by canonical id, which a synthetic frame doesn't carry,
+            // so our own messages come back too; only peers' deliveries count.
+            if msg.message_type != MessageType::Chat || msg.username == me {
+                continue;
+            }
+            if let Some(sent_at_us) = decode_payload(&msg.message) {
+                let now_us = stats.started.elapsed().as_micros() as u64;
+                stats.received.fetch_add(1, Ordering::Relaxed);
+                if let Ok(mut l) = stats.latencies_us.lock() {
+                    l.push(now_us.saturating_sub(sent_at_us));
+                }
+            }
+        }
+    });
+    Ok((writer, transport, reader_task))
+}
+
+/// Developer command: spawn `count` synthetic clients against the local host, each sending
+/// `msgs_per_sec` chat messages for `duration_secs` (default 10), and report throughput,
+/// broadcast latency and dropped deliveries. Requires hosting; `password` is the room password.
+#[tauri::command]
+pub async fn simulate_clients(
+    state: State<'_, Arc<AppState>>,
+    count: usize,
+    msgs_per_sec: f64,
+    password: String,
+    duration_secs: Option<u64>,
+) -> AppResult<SimulationReport> {
+    if !(1..=MAX_SIM_CLIENTS).contains(&count) {
+        return Err(AppError::Validation(format!(
+            "count must be between 1 and {}",
+            MAX_SIM_CLIENTS
+        )));
+    }
+    if !(MIN_MSGS_PER_SEC..=MAX_MSGS_PER_SEC).contains(&msgs_per_sec) {
+        return Err(AppError::Validation(format!(
+            "msgs_per_sec must be between {} and {}",
+            MIN_MSGS_PER_SEC, MAX_MSGS_PER_SEC
+        )));
+    }
+    let duration = Duration::from_secs(
+        duration_secs
+            .unwrap_or(DEFAULT_DURATION_SECS)
+            .clamp(1, MAX_DURATION_SECS),
+    );
+
+    let is_server = *state.is_server.read().await;
+    let server_addr = *state.server_addr.read().await;
+    let host_user_id = *state.user_id.read().await;
+    let (Some(addr), Some(host_user_id), true) = (server_addr, host_user_id, is_server) else {
+        return Err(AppError::Validation(
+            "Start hosting before running a simulation".to_string(),
+        ));
+    };
+    let pool = state
+        .pool
+        .get()
+        .cloned()
+        .ok_or_else(|| AppError::Internal("Database not ready".to_string()))?;
+
+    if SIMULATION_ACTIVE.swap(true, Ordering::SeqCst) {
+        return Err(AppError::Conflict(
+            "A simulation is already running".to_string(),
+        ));
+    }
+    let _guard = ActiveGuard;
+
+    let room_id = ensure_sim_room(&pool, host_user_id).await?;
+    let host = format!("127.0.0.1:{}", addr.port());
+    tracing::info!(
+        "🧪 Simulating {} clients at {} msg/s for {:?} against {}",
+        count,
+        msgs_per_sec,
+        duration,
+        host
+    );
+
+    let stats = Arc::new(SimStats {
+        started: Instant::now(),
+        sent: AtomicU64::new(0),
+        received: AtomicU64::new(0),
+        latencies_us: std::sync::Mutex::new(Vec::new()),
+    });
+
+    // Connect everyone concurrently; a failed connect just shrinks the run.
+    let connects: Vec<_> = (0..count)
+        .map(|n| {
+            let (host, password, stats) = (host.clone(), password.clone(), Arc::clone(&stats));
+            tauri::async_runtime::spawn(async move {
+                connect_sim_client(&host, &password, n, room_id, stats).await
+            })
+        })
+        .collect();
+    let mut clients = Vec::new();
+    for (n, task) in connects.into_iter().enumerate() {
+        match task.await {
+            Ok(Ok(c)) => clients.push((n, c)),
+            Ok(Err(e)) => tracing::warn!("🧪 sim-{} failed to connect: {}", n, e),
+            Err(e) => tracing::warn!("🧪 sim-{} connect task failed: {}", n, e),
+        }
+    }
+    if clients.is_empty() {
+        return Err(AppError::Network(
+            "No synthetic client could connect".to_string(),
+        ));
+    }
+    tokio::time::sleep(SETTLE).await;
+
+    // Throughput covers the send phase plus drain; latency is timed against `stats.started`.
+    let send_start = Instant::now();
+    let period = Duration::from_secs_f64(1.0 / msgs_per_sec);
+    let mut senders = Vec::new();
+    let mut readers = Vec::new();
+    for (n, (writer, transport, reader_task)) in clients {
+        readers.push(reader_task);
+        let stats = Arc::clone(&stats);
+        senders.push(tauri::async_runtime::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            while send_start.elapsed() < duration {
+                ticker.tick().await;
+                let sent_at_us = stats.started.elapsed().as_micros() as u64;
+                let msg = sim_message(
+                    MessageType::Chat,
+                    n,
+                    room_id,
+                    encode_payload(sent_at_us),
+                    None,
+                );
+                if send_secure(&writer, &transport, &msg).await.is_err() {
+                    break;
+                }
+                stats.sent.fetch_add(1, Ordering::Relaxed);
+            }
+            // Returning drops the write half; the host tears the connection down.
+            (writer, transport)
+        }));
+    }
+    let mut links = Vec::new();
+    for s in senders {
+        if let Ok(link) = s.await {
+            links.push(link);
+        }
+    }
+    tokio::time::sleep(DRAIN).await;
+    for r in &readers {
+        r.abort();
+    }
+    drop(links);
+    let elapsed = send_start.elapsed();
+
+    let connected = readers.len();
+    let sent = stats.sent.load(Ordering::Relaxed);
+    let received = stats.received.load(Ordering::Relaxed);
+    let expected = sent * (connected as u64).saturating_sub(1);
+    let mut latencies = stats
+        .latencies_us
+        .lock()
+        .map(|l| l.clone())
+        .unwrap_or_default();
+    latencies.sort_unstable();
+    let avg_ms = if latencies.is_empty() {
+        0.0
+    } else {
+        latencies.iter().sum::<u64>() as f64 / latencies.len() as f64 / 1000.0
+    };
+
+    let report = SimulationReport {
+        clients_requested: count,
+        clients_connected: connected,
+        duration_ms: elapsed.as_millis() as u64,
+        messages_sent: sent,
+        messages_received: received,
+        expected_deliveries: expected,
+        dropped: expected.saturating_sub(received),
+        throughput_per_sec: received as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        latency_avg_ms: avg_ms,
+        latency_p50_ms: percentile_ms(&latencies, 50.0),
+        latency_p99_ms: percentile_ms(&latencies, 99.0),
+        latency_max_ms: latencies
+            .last()
+            .map(|&us| us as f64 / 1000.0)
+            .unwrap_or(0.0),
+    };
+    tracing::info!("🧪 Simulation finished: {:?}", report);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_round_trips_send_time() {
+        assert_eq!(decode_payload(&encode_payload(123_456)), Some(123_456));
+        assert_eq!(decode_payload("hello"), None);
+        assert_eq!(decode_payload("sim:abc"), None);
+    }
+
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        let us: Vec<u64> = (1..=100).map(|i| i * 1000).collect();
+        assert_eq!(percentile_ms(&us, 50.0), 50.0);
+        assert_eq!(percentile_ms(&us, 99.0), 99.0);
+        assert_eq!(percentile_ms(&us, 100.0), 100.0);
+        assert_eq!(percentile_ms(&[], 99.0), 0.0);
+    }
+}
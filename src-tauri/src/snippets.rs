@@ -0,0 +1,140 @@
+// Code snippets.
+//
+// A Code message carries the code in `message` exactly as pasted: it skips emoji normalization
+// and shortcode expansion everywhere, so indentation, trailing spaces and blank lines survive to
+// every reader. The optional `language` (a highlighter hint) and `filename` travel in their own
+// fields and are stored in the matching `messages` columns.
+
+use crate::error::{AppError, AppResult};
+use crate::limits;
+use crate::sockets::{
+    frame, now_secs, publish_host_message, send_secure_client, server_now_secs, AppState, Message,
+    MessageType,
+};
+use sqlx::SqlitePool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tauri::State;
+
+const MAX_LANGUAGE_CHARS: usize = 32;
+const MAX_FILENAME_CHARS: usize = 255;
+
+/// Lowercase a language hint; blank means none. Only names like `rust`, `c++`, `c#` or
+/// `objective-c` are accepted.
+fn clean_language(language: Option<&str>) -> AppResult<Option<String>> {
+    let Some(language) = language.map(str::trim).filter(|l| !l.is_empty()) else {
+        return Ok(None);
+    };
+    if language.chars().count() > MAX_LANGUAGE_CHARS
+        || !language
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+#._-".contains(c))
+    {
+        return Err(AppError::Validation(format!(
+            "'{}' isn't a valid language",
+            language
+        )));
+    }
+    Ok(Some(language.to_ascii_lowercase()))
+}
+
+/// A bare file name (no directories); blank means none.
+fn clean_filename(filename: Option<&str>) -> AppResult<Option<String>> {
+    let Some(filename) = filename.map(str::trim).filter(|f| !f.is_empty()) else {
+        return Ok(None);
+    };
+    if filename.chars().count() > MAX_FILENAME_CHARS
+        || filename
+            .chars()
+            .any(|c| c == '/' || c == '\\' || c.is_control())
+    {
+        return Err(AppError::Validation(
+            "File names can't contain slashes or control characters".to_string(),
+        ));
+    }
+    Ok(Some(filename.to_string()))
+}
+
+/// Validate a Code message in place: non-blank code (left untouched) and a clean
+/// `language` / `filename`. The caller still applies the length limit.
+pub fn check(message: &mut Message) -> AppResult<()> {
+    if message.message.trim().is_empty() {
+        return Err(AppError::Validation("The snippet is empty".to_string()));
+    }
+    message.language = clean_language(message.language.as_deref())?;
+    message.filename = clean_filename(message.filename.as_deref())?;
+    Ok(())
+}
+
+/// Notification / digest text for a snippet: its file name or language rather than the code.
+pub fn preview(message: &Message) -> String {
+    match (&message.filename, &message.language) {
+        (Some(name), _) => format!("[Code: {}]", name),
+        (None, Some(language)) => format!("[Code: {}]", language),
+        (None, None) => "[Code]".to_string(),
+    }
+}
+
+/// Post a code snippet to the current room, as the host participant or over the client
+/// connection.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn send_code_snippet(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    code: String,
+    language: Option<String>,
+    filename: Option<String>,
+    user_id: u64,
+) -> AppResult<()> {
+    let is_server = *state.is_server.read().await;
+    let mut message = Message {
+        username: state.username.read().await.clone(),
+        room: state.current_room.read().await.clone(),
+        room_id: state.current_room_id.read().await.unwrap_or(1),
+        created_at: if is_server {
+            now_secs()
+        } else {
+            server_now_secs(&state.clock_offset_ms)
+        },
+        language,
+        filename,
+        ..frame(MessageType::Code, user_id, code)
+    };
+    check(&mut message)?;
+    let limit = if is_server {
+        state.max_message_chars.load(Ordering::Relaxed)
+    } else {
+        state.client_max_message_chars.load(Ordering::Relaxed)
+    };
+    limits::check_length(&message.message, limit)?;
+    if is_server {
+        publish_host_message(&app, state.inner(), db.inner(), message).await;
+        Ok(())
+    } else {
+        send_secure_client(state.inner(), &message)
+            .await
+            .map_err(AppError::Network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_and_filename_are_checked() {
+        assert_eq!(
+            clean_language(Some(" C++ ")).unwrap().as_deref(),
+            Some("c++")
+        );
+        assert_eq!(clean_language(Some("")).unwrap(), None);
+        assert!(clean_language(Some("rust; drop")).is_err());
+        assert_eq!(
+            clean_filename(Some("nginx.conf")).unwrap().as_deref(),
+            Some("nginx.conf")
+        );
+        assert!(clean_filename(Some("../etc/passwd")).is_err());
+        assert!(clean_filename(Some("a\nb")).is_err());
+    }
+}
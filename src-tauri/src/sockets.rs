@@ -1,30 +1,55 @@
+use crate::attachments;
+use crate::client_version;
+use crate::codec::{FramedReader, FramedWriter};
+use crate::connection_log;
+use crate::connectivity;
+use crate::contacts;
+use crate::custom_emoji;
 use crate::db_queries::{
-    add_room_member_internal, create_room_internal, delete_message_db, edit_message_db,
-    get_chat_rooms_internal, get_or_create_dm_internal, get_room_messages_internal,
-    get_room_reactions_internal, get_unread_counts_internal, list_users_internal,
-    room_join_allowed_internal, save_message_internal, toggle_reaction_db,
-    touch_last_read_internal, upsert_user_internal, ChatRoom,
+    add_room_member_internal, apply_auto_join_internal, create_room_internal, delete_message_db,
+    edit_message_db, get_chat_rooms_internal, get_or_create_dm_internal,
+    get_room_messages_internal, get_room_reactions_internal, get_unread_counts_internal,
+    leave_room_internal, list_users_internal, room_join_allowed_internal,
+    set_room_auto_join_internal, toggle_reaction_db, touch_last_read_internal, unsend_message_db,
+    upsert_user_internal, username_taken_internal, validate_username, ChatRoom,
 };
+use crate::emoji;
 use crate::error::{AppError, AppResult};
-use crate::secure;
+use crate::gallery;
+use crate::invites;
+use crate::join_requests;
+use crate::known_servers;
+use crate::limits;
+use crate::location;
+use crate::maintenance;
+use crate::message_events;
+use crate::metrics::METRICS;
+use crate::moderation;
+use crate::port_mapping;
+use crate::presence;
+use crate::reach;
+use crate::room_permissions;
+use crate::room_welcome;
+use crate::secure::{self, Transport};
+use crate::server_events;
+use crate::snippets;
+use crate::stickers;
+use crate::storage::{MessageStore, SqliteStore};
+use crate::system_events::SystemEvent;
+use crate::user_settings;
+use crate::wake;
 use serde::{Deserialize, Serialize};
-use snow::TransportState;
 use sqlx::SqlitePool;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
-use tauri::{Emitter, State};
-use tokio::io::AsyncReadExt;
-use tokio::io::AsyncWriteExt;
+use tauri::{Emitter, Manager, Runtime, State};
 use tokio::net::TcpStream;
 use tokio::sync::Semaphore;
+use tracing::Instrument;
 use uuid::Uuid;
 
-/// Maximum size of a single length-prefixed frame payload (10 MiB).
-/// Shared by both the server and client read paths so the cap can never drift.
-const MAX_FRAME_BYTES: usize = 10 * 1024 * 1024;
-
 /// Maximum number of client connections a host will handle concurrently.
 const MAX_CONCURRENT_CLIENTS: usize = 256;
 
@@ -35,20 +60,20 @@ const RATE_LIMIT_BURST: f64 = 20.0;
 
 /// Token-bucket rate limiter. `allow(now)` refills by elapsed time and consumes a token,
 /// returning false when the bucket is empty (caller drops the message).
-struct RateLimiter {
+pub(crate) struct RateLimiter {
     tokens: f64,
     last: tokio::time::Instant,
 }
 
 impl RateLimiter {
-    fn new(now: tokio::time::Instant) -> Self {
+    pub(crate) fn new(now: tokio::time::Instant) -> Self {
         Self {
             tokens: RATE_LIMIT_BURST,
             last: now,
         }
     }
 
-    fn allow(&mut self, now: tokio::time::Instant) -> bool {
+    pub(crate) fn allow(&mut self, now: tokio::time::Instant) -> bool {
         self.tokens = (self.tokens
             + now.duration_since(self.last).as_secs_f64() * RATE_LIMIT_PER_SEC)
             .min(RATE_LIMIT_BURST);
@@ -61,51 +86,151 @@ impl RateLimiter {
     }
 }
 
-/// Maximum length (in characters) of a single chat message.
-const MAX_MESSAGE_CHARS: usize = 4000;
+/// Default maximum length (in characters) of a single chat message. Hosts can change the
+/// limit they enforce (see limits.rs).
+pub(crate) const MAX_MESSAGE_CHARS: usize = 4000;
 
-/// How often each side sends a zero-length keepalive frame.
+/// How often the host sends each client a keepalive. Clients send theirs at a pace that
+/// depends on the platform (see connectivity.rs).
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
 
-/// If no frame (including a keepalive) arrives within this window, the peer is
-/// treated as dead and the connection is closed. ~3 missed heartbeats.
+/// If no frame (including a keepalive) arrives from the host within this window, the client
+/// treats it as dead and the connection is closed. ~3 missed heartbeats.
 const READ_TIMEOUT: Duration = Duration::from_secs(45);
 
+/// The host's counterpart: ~3 missed heartbeats from the slowest client, a phone.
+pub(crate) const CLIENT_READ_TIMEOUT: Duration =
+    Duration::from_secs(3 * connectivity::MOBILE_HEARTBEAT_SECS);
+
 /// Maximum concurrent connections allowed from a single remote IP address.
 const MAX_CONN_PER_IP: usize = 16;
 
-/// Spawn a task that sends a zero-length keepalive frame to `writer` every
-/// HEARTBEAT_INTERVAL. Zero-length frames are read as `Ok(None)` and skipped before
-/// decryption, so they never touch the Noise transport / nonce sequence.
+/// A frame the peer hasn't taken within this long fails to send. A peer gone without a FIN (a
+/// pulled cable, a sleeping laptop) stops taking writes once the socket buffer fills, and the
+/// failed send evicts it instead of leaving it on the roster.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// TCP keepalive on chat connections: the first probe after this much silence, then one every
+/// TCP_KEEPALIVE_INTERVAL, so the OS drops a half-open connection even while nothing is sent.
+const TCP_KEEPALIVE_IDLE: Duration = Duration::from_secs(30);
+const TCP_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Turn on TCP keepalive for a chat connection (accepted or dialed).
+fn set_tcp_keepalive(stream: &TcpStream) {
+    let keepalive = socket2::TcpKeepalive::new()
+        .with_time(TCP_KEEPALIVE_IDLE)
+        .with_interval(TCP_KEEPALIVE_INTERVAL);
+    if let Err(e) = socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+        tracing::warn!("Couldn't enable TCP keepalive: {}", e);
+    }
+}
+
+/// A connection's write half, shared by everything that sends to the peer. Frames go in one of
+/// two lanes: file chunks take the writer only once no other frame is waiting for it, so chat,
+/// acks, presence and heartbeats never queue behind a transfer (a heartbeat stuck behind a
+/// backlog of chunks would make the peer think the connection had died).
+pub struct PeerWriter<W> {
+    half: tokio::sync::Mutex<W>,
+    // Control frames waiting for (or writing to) this connection.
+    urgent: std::sync::atomic::AtomicUsize,
+    // Signalled when `urgent` drops back to zero, to wake parked file chunks.
+    idle: tokio::sync::Notify,
+}
+
+impl<W> PeerWriter<W> {
+    pub fn new(half: W) -> Self {
+        Self {
+            half: tokio::sync::Mutex::new(half),
+            urgent: std::sync::atomic::AtomicUsize::new(0),
+            idle: tokio::sync::Notify::new(),
+        }
+    }
+
+    pub async fn lock(&self) -> tokio::sync::MutexGuard<'_, W> {
+        self.half.lock().await
+    }
+
+    /// Claim the control lane: file chunks stay off the wire until the guard drops.
+    fn urgent(&self) -> UrgentLane<'_, W> {
+        self.urgent
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        UrgentLane(self)
+    }
+
+    /// Wait (as a file chunk) until no control frame is pending.
+    async fn bulk_turn(&self) {
+        loop {
+            let idle = self.idle.notified();
+            if self.urgent.load(std::sync::atomic::Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+struct UrgentLane<'a, W>(&'a PeerWriter<W>);
+
+impl<W> Drop for UrgentLane<'_, W> {
+    fn drop(&mut self) {
+        if self
+            .0
+            .urgent
+            .fetch_sub(1, std::sync::atomic::Ordering::SeqCst)
+            == 1
+        {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+/// Take `message`'s lane: a file chunk waits its turn; anything else holds the control lane
+/// until the returned guard drops.
+async fn lane<'a, W>(writer: &'a PeerWriter<W>, message: &Message) -> Option<UrgentLane<'a, W>> {
+    if message.message_type == MessageType::AttachmentChunk {
+        writer.bulk_turn().await;
+        None
+    } else {
+        Some(writer.urgent())
+    }
+}
+
+/// Host-side keepalive: every HEARTBEAT_INTERVAL (and once right away, acking the handshake),
+/// send the client an encrypted ServerAck carrying the host's clock in milliseconds, which
+/// clients use to correct for their own clock skew. Any frame resets the peer's read timeout.
 fn spawn_heartbeat(
-    writer: Arc<tokio::sync::Mutex<tokio::net::tcp::OwnedWriteHalf>>,
+    writer: Arc<PeerWriter<FramedWriter<tokio::net::tcp::OwnedWriteHalf>>>,
+    transport: Arc<tokio::sync::Mutex<Transport>>,
 ) -> tauri::async_runtime::JoinHandle<()> {
     tauri::async_runtime::spawn(async move {
         let mut ticker = tokio::time::interval(HEARTBEAT_INTERVAL);
         loop {
             ticker.tick().await;
-            let mut w = writer.lock().await;
-            if w.write_all(&0u32.to_be_bytes()).await.is_err() {
+            let ack = frame(MessageType::ServerAck, 0, now_millis().to_string());
+            if send_secure(&writer, &transport, &ack).await.is_err() {
                 break; // peer gone; the read side will handle cleanup
             }
         }
     })
 }
 
-/// Client-side keepalive: same idea, but the client's writer lives behind an Option.
+/// Client-side keepalive: same idea, but the client's writer lives behind an Option, and the
+/// pace is the platform's (slower on a phone).
 fn spawn_client_heartbeat(
-    client_stream: Arc<tokio::sync::Mutex<Option<tokio::net::tcp::OwnedWriteHalf>>>,
+    client_stream: Arc<PeerWriter<Option<FramedWriter<tokio::net::tcp::OwnedWriteHalf>>>>,
 ) -> tauri::async_runtime::JoinHandle<()> {
     tauri::async_runtime::spawn(async move {
-        let mut ticker = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let mut ticker = tokio::time::interval(connectivity::client_heartbeat_interval());
         loop {
             ticker.tick().await;
+            let _lane = client_stream.urgent();
             let mut guard = client_stream.lock().await;
             let stop = match guard.as_mut() {
-                Some(w) => w.write_all(&0u32.to_be_bytes()).await.is_err(),
+                Some(w) => w.write_keepalive().await.is_err(),
                 None => true, // disconnected
             };
             drop(guard);
+            drop(_lane);
             if stop {
                 break;
             }
@@ -113,44 +238,45 @@ fn spawn_client_heartbeat(
     })
 }
 
-/// Read one length-prefixed frame: a 4-byte big-endian length header followed by
-/// that many payload bytes. Returns `Ok(None)` for a zero-length keep-alive frame.
-/// Rejects oversized frames so a malicious peer cannot trigger a huge allocation.
-async fn read_frame<R>(reader: &mut R) -> std::io::Result<Option<Vec<u8>>>
-where
-    R: AsyncReadExt + Unpin,
-{
-    let mut len_bytes = [0u8; 4];
-    reader.read_exact(&mut len_bytes).await?;
-    let msg_len = u32::from_be_bytes(len_bytes) as usize;
-
-    if msg_len == 0 {
-        return Ok(None);
-    }
-    if msg_len > MAX_FRAME_BYTES {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!(
-                "Frame too large: {} bytes (max {})",
-                msg_len, MAX_FRAME_BYTES
-            ),
-        ));
-    }
-
-    let mut buf = vec![0u8; msg_len];
-    reader.read_exact(&mut buf).await?;
-    Ok(Some(buf))
-}
-
 /// Current UNIX time in seconds. Returns 0 if the system clock is before the epoch
 /// instead of panicking — these run on network-triggered code paths.
-fn now_secs() -> u64 {
+pub(crate) fn now_secs() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .map(|d| d.as_secs())
         .unwrap_or(0)
 }
 
+fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Marks a client clock offset that hasn't been measured yet (no ServerAck seen).
+pub(crate) const CLOCK_OFFSET_UNSET: i64 = i64::MIN;
+
+/// Fold one host-clock sample (host ms − local ms at receipt) into the running offset. The first
+/// sample is taken as-is; later ones move it a quarter of the way, smoothing network jitter.
+fn next_clock_offset(current: i64, sample: i64) -> i64 {
+    if current == CLOCK_OFFSET_UNSET {
+        sample
+    } else {
+        current + (sample - current) / 4
+    }
+}
+
+/// "Now" on the host's clock, in seconds, from a client's measured offset (local clock until the
+/// first ServerAck arrives). Stamped on frames a client creates so its own optimistic echo
+/// shows the same time everyone else sees.
+pub(crate) fn server_now_secs(offset_ms: &std::sync::atomic::AtomicI64) -> u64 {
+    match offset_ms.load(std::sync::atomic::Ordering::Relaxed) {
+        CLOCK_OFFSET_UNSET => now_secs(),
+        offset => (now_millis().saturating_add(offset).max(0) / 1000) as u64,
+    }
+}
+
 /// Monotonic id assigned to each accepted connection, so a stale connection's
 /// teardown can tell whether it still owns the server_streams entry for its user_id
 /// (a reconnect may have replaced it).
@@ -165,21 +291,128 @@ static CLIENT_GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::Atom
 /// A peer's write half + its Noise transport — together, enough to send one
 /// encrypted frame. Snapshotted under the streams lock, then used after it drops.
 type ClientLink = (
-    Arc<tokio::sync::Mutex<tokio::net::tcp::OwnedWriteHalf>>,
-    Arc<tokio::sync::Mutex<TransportState>>,
+    Arc<PeerWriter<FramedWriter<tokio::net::tcp::OwnedWriteHalf>>>,
+    Arc<tokio::sync::Mutex<Transport>>,
 );
 
 //Better indexing and room management
 #[derive(Clone)]
 pub struct ClientConnection {
     // Write half + per-connection Noise transport, used together to send/broadcast.
-    pub writer: Arc<tokio::sync::Mutex<tokio::net::tcp::OwnedWriteHalf>>,
-    pub transport: Arc<tokio::sync::Mutex<TransportState>>,
+    pub writer: Arc<PeerWriter<FramedWriter<tokio::net::tcp::OwnedWriteHalf>>>,
+    pub transport: Arc<tokio::sync::Mutex<Transport>>,
     pub username: String,
     pub current_room: String,
     pub room_id: u64,
     pub user_id: u64,
     pub conn_id: u64,
+    // The task relaying this connection's current room channel to its socket. Shared across
+    // clones so a room switch or teardown can stop whichever forwarder is live.
+    pub forwarder: Arc<std::sync::Mutex<Option<Forwarder>>>,
+    // Issued to the client at Connect; presenting it in a Resume frame after a drop picks the
+    // parked connection back up (see ParkedConnection).
+    pub resume_token: String,
+    // How its forwarder is keeping up, for get_server_stats and client_lagging.
+    pub drain: Arc<DrainStats>,
+}
+
+/// A running forwarder. It's never aborted: a task killed mid-`send_secure` could leave half a
+/// frame on the socket or a nonce the client never sees, and the client could no longer
+/// decrypt. `stop` asks it to finish, which it checks only between whole frames; a stopped
+/// forwarder's task stays in the slot so the next one can wait for it.
+pub struct Forwarder {
+    stop: Option<tokio::sync::oneshot::Sender<()>>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+impl Forwarder {
+    fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+/// A forwarder this many frames behind its room channel reports the client as lagging.
+const LAG_THRESHOLD: usize = ROOM_CHANNEL_CAPACITY / 4;
+
+/// So does a single write still not done after this long (the socket has stopped draining).
+const STUCK_SEND_AFTER: Duration = Duration::from_secs(2);
+
+/// How a connection's forwarder is draining its room channel.
+pub struct DrainStats {
+    // Frames waiting on the room channel for the forwarder, as of its last receive.
+    pub queued: std::sync::atomic::AtomicUsize,
+    // When a frame last reached the socket (or the connection opened), in Unix ms.
+    last_write_ms: std::sync::atomic::AtomicI64,
+    // Set while reported as lagging, so client_lagging fires once until it catches up.
+    lagging: std::sync::atomic::AtomicBool,
+}
+
+impl DrainStats {
+    fn new() -> Self {
+        Self {
+            queued: Default::default(),
+            last_write_ms: std::sync::atomic::AtomicI64::new(now_millis()),
+            lagging: Default::default(),
+        }
+    }
+
+    /// How long since a frame last reached the socket.
+    pub fn last_write_age_ms(&self) -> i64 {
+        (now_millis()
+            - self
+                .last_write_ms
+                .load(std::sync::atomic::Ordering::Relaxed))
+        .max(0)
+    }
+
+    pub fn is_lagging(&self) -> bool {
+        self.lagging.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Mark the client lagging; true if it wasn't already.
+    fn start_lagging(&self) -> bool {
+        !self
+            .lagging
+            .swap(true, std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn wrote(&self) {
+        self.last_write_ms
+            .store(now_millis(), std::sync::atomic::Ordering::Relaxed);
+        if self.queued.load(std::sync::atomic::Ordering::Relaxed) == 0 {
+            self.lagging
+                .store(false, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+}
+
+/// How long a dropped connection stays parked, waiting for its client to resume it.
+const RESUME_GRACE: Duration = Duration::from_secs(30);
+
+/// A connection that dropped without saying goodbye. For RESUME_GRACE its user stays on the
+/// room roster (no leave notice) and `rx` keeps collecting the room's frames (up to
+/// ROOM_CHANNEL_CAPACITY), so a client that resumes in time gets what it missed and the room
+/// sees no leave/join noise. If nobody resumes, `expiry` announces the departure.
+pub struct ParkedConnection {
+    pub conn: ClientConnection,
+    pub rx: tokio::sync::broadcast::Receiver<RoomFrame>,
+    pub expiry: tauri::async_runtime::JoinHandle<()>,
+}
+
+/// Per-room broadcast buffer. A forwarder that falls this far behind skips the oldest frames
+/// (logged as lag) instead of stalling delivery to everyone else in the room.
+const ROOM_CHANNEL_CAPACITY: usize = 256;
+
+/// One frame fanned out on a room channel. Each forwarder skips frames excluding its connection.
+#[derive(Clone)]
+pub struct RoomFrame {
+    pub message: Arc<Message>,
+    // The conn_id the frame came in on (usually the sender's), which doesn't get it back.
+    pub exclude_conn_id: Option<u64>,
+    // When the frame was queued, for the broadcast-latency histogram.
+    pub queued_at: std::time::Instant,
 }
 
 pub struct AppState {
@@ -187,18 +420,31 @@ pub struct AppState {
     // Use user_id as key for O(1) lookups
     pub server_streams: Arc<tokio::sync::Mutex<HashMap<u64, ClientConnection>>>,
     // Separate client stream management (write half + matching Noise transport)
-    pub client_stream: Arc<tokio::sync::Mutex<Option<tokio::net::tcp::OwnedWriteHalf>>>,
-    pub client_transport: Arc<tokio::sync::Mutex<Option<TransportState>>>,
+    pub client_stream: Arc<PeerWriter<Option<FramedWriter<tokio::net::tcp::OwnedWriteHalf>>>>,
+    pub client_transport: Arc<tokio::sync::Mutex<Option<Transport>>>,
     // Handles to the current client read-listener + heartbeat tasks, so reconnect/
     // disconnect can cancel the stale tasks before starting new ones.
     pub client_listener: Arc<tokio::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
     pub client_heartbeat: Arc<tokio::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
     // Host-side LAN discovery responder task, so hosting teardown can abort it (frees udp/3626).
     pub discovery_responder: Arc<tokio::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
-    // Track which users are in which rooms for efficient broadcasting
+    // Host-side throughput snapshots for the host console (see server_events.rs), while hosting.
+    pub server_event_ticker: Arc<tokio::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    // Room roster: which users are in which rooms (member lists, presence counts)
     pub room_clients: Arc<tokio::sync::Mutex<HashMap<String, Vec<u64>>>>,
+    // One broadcast channel per room; each connection's forwarder subscribes to its room.
+    pub room_channels:
+        Arc<tokio::sync::Mutex<HashMap<String, tokio::sync::broadcast::Sender<RoomFrame>>>>,
+    // The optional local Prometheus `/metrics` responder (see metrics.rs), while running.
+    pub metrics_server: Arc<tokio::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    // The optional incoming-webhook HTTP listener (see webhooks.rs), while running.
+    pub webhook_server: Arc<tokio::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    // The optional MQTT ingest subscriber (see mqtt.rs), while enabled.
+    pub mqtt_subscriber: Arc<tokio::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
     // Live connection count per remote IP, for the per-IP connection cap.
     pub ip_conn_counts: Arc<tokio::sync::Mutex<HashMap<IpAddr, usize>>>,
+    // Dropped connections waiting to be resumed, keyed by canonical user id.
+    pub parked: Arc<tokio::sync::Mutex<HashMap<u64, ParkedConnection>>>,
 
     // Use RwLock for frequently-read scalar fields
     pub username: tokio::sync::RwLock<String>,
@@ -210,8 +456,221 @@ pub struct AppState {
     // The query pool, set once at startup — lets broadcast-time eviction reach clean_client
     // without threading the pool through every distribute_message_to_all call site.
     pub pool: std::sync::OnceLock<SqlitePool>,
+    // Where the host saves room messages, when something other than `pool` was injected (the
+    // test harness). See storage.rs and message_store().
+    pub message_store: std::sync::OnceLock<Arc<dyn MessageStore>>,
     // The host's mDNS service daemon while hosting (best-effort), shut down on teardown.
     pub mdns: std::sync::Mutex<Option<mdns_sd::ServiceDaemon>>,
+    // Extra joined servers (workspaces) beyond the primary connection above, keyed by session
+    // id. The primary client/host path keeps its own fields; each session carries its own.
+    pub sessions: Arc<tokio::sync::Mutex<HashMap<String, Arc<ClientSession>>>>,
+    // Client side: host clock minus ours, in ms, measured from the host's ServerAck frames
+    // (CLOCK_OFFSET_UNSET until the first arrives). Reset on every primary connect.
+    pub clock_offset_ms: Arc<std::sync::atomic::AtomicI64>,
+    // This instance's configured message length limit, enforced on the host side.
+    pub max_message_chars: std::sync::atomic::AtomicUsize,
+    // Client side: the limit the primary connection's host advertised in its Identity frame
+    // (our own limit until it arrives). Reset on every primary connect.
+    pub client_max_message_chars: Arc<std::sync::atomic::AtomicUsize>,
+    // Client side: chat messages already shown in our UI as they were sent (send_as_client),
+    // whose echo from the host is dropped.
+    pub emitted_ids: Arc<std::sync::Mutex<EmittedIds>>,
+    // Host side: planned maintenance while it's on (new connections are turned away).
+    pub maintenance: std::sync::Mutex<Option<maintenance::Maintenance>>,
+    // Host side: the router port mapping for remote access, while hosting with it allowed.
+    pub port_mapping: std::sync::Mutex<Option<port_mapping::PortMapping>>,
+    // Serverless mode (gossip presence, direct message links) while it's on. See gossip.rs.
+    pub serverless: std::sync::Mutex<Option<crate::gossip::Serverless>>,
+    // Host side: who is Away right now (user id → name), for clients that register later.
+    pub away_users: Arc<tokio::sync::Mutex<HashMap<u64, String>>>,
+    // This user's last UI interaction (Unix seconds), whether they're currently Away, and the
+    // idle period (minutes, 0 = never) after which they go Away. See presence.rs.
+    pub last_activity_secs: std::sync::atomic::AtomicU64,
+    pub is_away: std::sync::atomic::AtomicBool,
+    pub idle_minutes: std::sync::atomic::AtomicU32,
+    // Where the host keeps attachment files (set at startup).
+    pub attachments_dir: std::sync::OnceLock<std::path::PathBuf>,
+    // Thumbnails and other rebuildable media (set at startup). See media_cache.rs.
+    pub media_cache_dir: std::sync::OnceLock<std::path::PathBuf>,
+    // File transfers in progress: uploads on the host (keyed "<user id>:<transfer id>"),
+    // downloads on a client (keyed by transfer id). See attachments.rs.
+    pub transfers: Arc<tokio::sync::Mutex<HashMap<String, attachments::Transfer>>>,
+    // Client: callers waiting for the host's AttachmentAck, by transfer id.
+    pub transfer_acks: Arc<
+        tokio::sync::Mutex<HashMap<String, tokio::sync::oneshot::Sender<attachments::TransferAck>>>,
+    >,
+    // Client: callers waiting for the host's Profile answer, by request id. See contacts.rs.
+    pub profile_replies:
+        Arc<tokio::sync::Mutex<HashMap<String, tokio::sync::oneshot::Sender<String>>>>,
+    // Client: callers waiting for the host's Gallery answer, by request id. See gallery.rs.
+    pub gallery_replies:
+        Arc<tokio::sync::Mutex<HashMap<String, tokio::sync::oneshot::Sender<String>>>>,
+    // When each connection's file-transfer allowance is next free, by peer user id (TO_HOST on
+    // a client). See throttle.rs.
+    pub transfer_pacing: std::sync::Mutex<HashMap<u64, tokio::time::Instant>>,
+    // Client (mobile): when the app went to the background, while it's there. See
+    // connectivity.rs.
+    pub suspended_at: std::sync::Mutex<Option<tokio::time::Instant>>,
+    // Host: wake tokens handed out while hosting (token → user id). Client (mobile): the
+    // background poll with ours, and whether it found something new. See wake.rs.
+    pub wake_tokens: Arc<tokio::sync::Mutex<HashMap<String, u64>>>,
+    pub wake_poller: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    pub woken: std::sync::atomic::AtomicBool,
+    // Client: this user's settings as the primary host last synced them. See user_settings.rs.
+    pub synced_settings: tokio::sync::RwLock<Option<serde_json::Value>>,
+    // The local profile in use (set at startup). See profiles.rs.
+    pub active_profile: tokio::sync::RwLock<String>,
+    // Client: the address the primary connection was made to, while connected. See
+    // known_servers.rs.
+    pub client_host: tokio::sync::RwLock<Option<String>>,
+}
+
+impl Default for AppState {
+    /// Nothing connected or hosted yet, and this instance's default limits.
+    fn default() -> Self {
+        Self {
+            server_streams: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            client_stream: Arc::new(PeerWriter::new(None)),
+            client_transport: Arc::new(tokio::sync::Mutex::new(None)),
+            client_listener: Arc::new(tokio::sync::Mutex::new(None)),
+            client_heartbeat: Arc::new(tokio::sync::Mutex::new(None)),
+            discovery_responder: Arc::new(tokio::sync::Mutex::new(None)),
+            server_event_ticker: Arc::new(tokio::sync::Mutex::new(None)),
+            room_clients: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            room_channels: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            metrics_server: Arc::new(tokio::sync::Mutex::new(None)),
+            webhook_server: Arc::new(tokio::sync::Mutex::new(None)),
+            mqtt_subscriber: Arc::new(tokio::sync::Mutex::new(None)),
+            ip_conn_counts: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            parked: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            username: tokio::sync::RwLock::new(String::new()),
+            user_id: tokio::sync::RwLock::new(None),
+            is_server: tokio::sync::RwLock::new(false),
+            current_room: tokio::sync::RwLock::new(String::new()),
+            current_room_id: tokio::sync::RwLock::new(None),
+            server_addr: tokio::sync::RwLock::new(None),
+            pool: std::sync::OnceLock::new(),
+            message_store: std::sync::OnceLock::new(),
+            mdns: std::sync::Mutex::new(None),
+            sessions: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            clock_offset_ms: Arc::new(std::sync::atomic::AtomicI64::new(CLOCK_OFFSET_UNSET)),
+            max_message_chars: std::sync::atomic::AtomicUsize::new(MAX_MESSAGE_CHARS),
+            client_max_message_chars: Arc::new(std::sync::atomic::AtomicUsize::new(
+                MAX_MESSAGE_CHARS,
+            )),
+            emitted_ids: Arc::new(std::sync::Mutex::new(Default::default())),
+            maintenance: std::sync::Mutex::new(None),
+            port_mapping: std::sync::Mutex::new(None),
+            serverless: std::sync::Mutex::new(None),
+            away_users: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            last_activity_secs: std::sync::atomic::AtomicU64::new(now_secs()),
+            is_away: std::sync::atomic::AtomicBool::new(false),
+            idle_minutes: std::sync::atomic::AtomicU32::new(presence::default_idle_minutes()),
+            attachments_dir: std::sync::OnceLock::new(),
+            media_cache_dir: std::sync::OnceLock::new(),
+            transfers: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            transfer_acks: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            profile_replies: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            gallery_replies: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            transfer_pacing: std::sync::Mutex::new(Default::default()),
+            suspended_at: std::sync::Mutex::new(None),
+            wake_tokens: Arc::new(tokio::sync::Mutex::new(Default::default())),
+            wake_poller: std::sync::Mutex::new(None),
+            woken: std::sync::atomic::AtomicBool::new(false),
+            synced_settings: tokio::sync::RwLock::new(None),
+            active_profile: tokio::sync::RwLock::new(crate::profiles::DEFAULT_PROFILE.to_string()),
+            client_host: tokio::sync::RwLock::new(None),
+        }
+    }
+}
+
+impl AppState {
+    /// Where the host saves room messages: the injected store, else this instance's database.
+    pub(crate) fn message_store(&self, pool: &SqlitePool) -> Arc<dyn MessageStore> {
+        match self.message_store.get() {
+            Some(store) => Arc::clone(store),
+            None => Arc::new(SqliteStore::new(pool.clone())),
+        }
+    }
+}
+
+/// One additional joined server, alongside the primary connection. Carries the same
+/// per-connection pieces the primary client path keeps directly on AppState (write half, Noise
+/// transport, listener + heartbeat handles, identity, current room), so several workspaces can be
+/// open at once. Its listener tags every emitted event with `session_id`.
+///
+/// Sessions are client-only chat workspaces: hosting stays with the primary connection, so at
+/// most one server is hosted at a time. Custom emoji and sticker sync, file transfers, settings
+/// sync, profiles, the gallery and wake tokens keep one host's state on AppState and only work
+/// on the primary connection; a session drops those frames.
+pub struct ClientSession {
+    pub session_id: String,
+    pub host: String,
+    pub username: String,
+    pub user_id: u64,
+    pub current_room: tokio::sync::RwLock<String>,
+    pub current_room_id: tokio::sync::RwLock<u64>,
+    pub stream: Arc<PeerWriter<Option<FramedWriter<tokio::net::tcp::OwnedWriteHalf>>>>,
+    pub transport: Arc<tokio::sync::Mutex<Option<Transport>>>,
+    pub listener: tokio::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    pub heartbeat: tokio::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    // Same as AppState::clock_offset_ms, for this session's host.
+    pub clock_offset_ms: Arc<std::sync::atomic::AtomicI64>,
+    // Same as AppState::client_max_message_chars, for this session's host.
+    pub max_message_chars: Arc<std::sync::atomic::AtomicUsize>,
+    // Same as AppState::emitted_ids, for this session.
+    pub emitted_ids: Arc<std::sync::Mutex<EmittedIds>>,
+}
+
+/// Ids of the chat messages a client showed in its own UI as it sent them, so the host's echo
+/// of one is dropped rather than shown twice. Only the most recent EMITTED_IDS_KEPT are kept:
+/// an echo arrives within a round trip, if at all.
+#[derive(Default)]
+pub struct EmittedIds {
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+const EMITTED_IDS_KEPT: usize = 256;
+
+impl EmittedIds {
+    pub fn insert(&mut self, id: String) {
+        if self.order.len() == EMITTED_IDS_KEPT {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        self.ids.insert(id.clone());
+        self.order.push_back(id);
+    }
+
+    /// Whether `id` was emitted already. It's forgotten once matched: one echo per message.
+    pub fn take(&mut self, id: &str) -> bool {
+        self.ids.remove(id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+/// A session's public summary, for the UI's workspace switcher.
+#[derive(Serialize, Clone)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub host: String,
+    pub username: String,
+    pub user_id: u64,
+    pub current_room: String,
+    pub current_room_id: u64,
+}
+
+/// Payload of the session-scoped `session_message` event: the raw frame JSON (the same string
+/// the primary connection emits on `message`) tagged with the session it arrived on.
+#[derive(Serialize, Clone)]
+pub struct SessionEvent {
+    pub session_id: String,
+    pub payload: String,
 }
 
 /// Wire-protocol version of the message envelope. Bump when the envelope/payload format
@@ -231,10 +690,10 @@ fn default_protocol_version() -> u16 {
 // A distinct UDP port + magic + version means only real Nutler hosts are ever listed, which
 // closes the false-positive port-scan problem (IMPROVEMENTS.md finding 2.10). The discovery
 // format versions independently of the TCP envelope (PROTOCOL_VERSION).
-const DISCOVERY_PORT: u16 = 3626;
-const DISCOVERY_MAGIC: &str = "nutler-disc";
-const DISCOVERY_VERSION: u16 = 1;
-const DISCOVERY_MAX_PACKET: usize = 1500;
+pub(crate) const DISCOVERY_PORT: u16 = 3626;
+pub(crate) const DISCOVERY_MAGIC: &str = "nutler-disc";
+pub(crate) const DISCOVERY_VERSION: u16 = 1;
+pub(crate) const DISCOVERY_MAX_PACKET: usize = 1500;
 const DISCOVERY_WINDOW_MS: u64 = 600;
 // Cap the advertised name so a long host username can't inflate the announce — bounds the
 // reflection-amplification factor (the responder replies are kept close to the probe size).
@@ -270,7 +729,7 @@ fn discovery_packet_valid(pkt: &DiscoveryPacket, kind: &str) -> bool {
 
 /// Only answer probes whose source is on the local segment (RFC1918 / loopback / link-local),
 /// so the responder can never be used to reflect traffic at an arbitrary off-LAN address.
-fn is_lan_source(ip: IpAddr) -> bool {
+pub(crate) fn is_lan_source(ip: IpAddr) -> bool {
     match ip {
         IpAddr::V4(v4) => v4.is_private() || v4.is_loopback() || v4.is_link_local(),
         IpAddr::V6(v6) => v6.is_loopback(),
@@ -349,6 +808,37 @@ pub struct Message {
     // (the identity authority) and assign a globally-unique id. Defaulted/omitted otherwise.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
+    // Sign-in proof, carried only on the Connect frame to a host that requires one: a directory
+    // password (ldap.rs) or an SSO ID token (oidc.rs). Noise-encrypted; the host never stores it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+    // Code snippets only (MessageType::Code): the language to highlight as, and the file the
+    // snippet came from, both optional. Stored in messages.language / messages.filename.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+}
+
+/// A `message_type` frame from `user_id` with a fresh id and timestamp, and no room or author
+/// name. Anything else is set with struct update syntax: `Message { room, ..frame(...) }`.
+pub(crate) fn frame(message_type: MessageType, user_id: u64, message: String) -> Message {
+    Message {
+        version: PROTOCOL_VERSION,
+        message_type,
+        username: String::new(),
+        user_id,
+        message,
+        message_id: Uuid::new_v4().to_string(),
+        room: String::new(),
+        room_id: 0,
+        created_at: now_secs(),
+        is_emoji: false,
+        email: None,
+        credential: None,
+        language: None,
+        filename: None,
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
@@ -388,18 +878,135 @@ pub enum MessageType {
     // user ids. The host authorizes the creator by the connection's canonical id.
     DmRequest,
     // Host → the requesting client: a room (JSON ChatRoom) to switch straight to — a freshly
-    // opened DM or a channel the client just created.
+    // opened DM, a channel the client just created, or one it joined by invite.
     DmReady,
     // Client → host: create a channel. `message` carries JSON {name, description,
     // department_id, is_private}. Created on the host DB under the connection's canonical id.
     RoomCreate,
     // Host → a single client on connect: their canonical user id (in `user_id`). A client's
     // local id differs from the host-assigned canonical id, so the client needs this to tell
-    // which messages are its own (history carries the canonical author id).
+    // which messages are its own (history carries the canonical author id). `message` carries
+    // the host's message length limit.
     Identity,
     // Host → a single client: a human-readable error (in `message`) for a request that failed
     // host-side (e.g. a duplicate channel name), so the client can surface it.
     ErrorNotice,
+    // Client → host, before Connect: create a local account / log in. `email` + `credential`
+    // (the account password) identify it; `username` is the display name for Register.
+    Register,
+    Login,
+    // Client → host, before Connect: exchange a freshly issued SSO ID token (`credential`) for
+    // `email` for a session, answered like Login. The token itself is never a Connect credential.
+    SsoLogin,
+    // Host → that client: login succeeded. `message` is the session token to send as the
+    // Connect `credential`; `user_id` is the canonical id. Failures come back as ErrorNotice.
+    AuthResult,
+    // Host → a client after Connect: a resume token (in `message`) for its next reconnect.
+    ResumeToken,
+    // Client → host: a Connect after a brief drop, with the last resume token in `message`.
+    // Authenticated like Connect; if the token matches a parked connection the client picks up
+    // where it left off, otherwise it's treated as a plain Connect.
+    Resume,
+    // Host → a client whose Connect was refused because another user already goes by that
+    // name. `message` carries the reason; the connection is closed after it.
+    NameInUse,
+    // Host → a client after Connect, and again whenever the set changes: the host's custom
+    // emoji as JSON [{name, hash}]. See custom_emoji.rs.
+    EmojiManifest,
+    // Client → host: the custom emoji it lacks or has stale, as a JSON array of names.
+    EmojiRequest,
+    // Host → that client: one requested emoji, JSON {name, mime, hash, data (base64)}.
+    EmojiData,
+    // A chat message that is a sticker: `message` is JSON {pack_id, sticker_id, name}, ids from
+    // the host's packs. Persisted with message_type 'Sticker'. See stickers.rs.
+    Sticker,
+    // The sticker counterparts of EmojiManifest / EmojiRequest / EmojiData, keyed by sticker id:
+    // manifest JSON {packs: [{id, name}], stickers: [{id, pack_id, name, hash}]}, request JSON
+    // [ids], data JSON {id, pack_id, name, mime, hash, data (base64)}.
+    StickerManifest,
+    StickerRequest,
+    StickerData,
+    // A code snippet posted to a room: `message` is the code, verbatim (no emoji shortcodes or
+    // normalization), with the optional `language` / `filename` fields. Persisted as 'Code'.
+    Code,
+    // A user went Away or came back Online (`message`). Client → host for its own user, host →
+    // everyone. Ephemeral; see presence.rs.
+    Presence,
+    // Client → host: report a message to the moderators. `message_id` is the TARGET message,
+    // `message` the reason; recorded under the connection's user. See moderation.rs.
+    Flag,
+    // Client → host: an announcement (`message_id`) arrived. See reach.rs.
+    DeliveryAck,
+    // Host → a private channel's moderators: someone asked to join (`message_id` = request id,
+    // `user_id` / `username` = the requester). See join_requests.rs.
+    JoinRequest,
+    // A decision on a join request (`message_id`), with `message` "approved" / "denied" (or
+    // "pending" to the requester when it's filed). Client moderator → host, host → requester
+    // and moderators.
+    JoinDecision,
+    // Client → host: redeem an invite link (`message`) for the connection's user. See invites.rs.
+    InviteRedeem,
+    // A file shared in a room: `message` is JSON {id, filename, size, mime}, the id of the host's
+    // stored copy. Only the host creates these. Persisted as 'Attachment'. See attachments.rs.
+    Attachment,
+    // Client → host: offer a file for `room_id`, JSON {filename, size, sha256}; `message_id` is
+    // the transfer id its chunks will carry.
+    AttachmentOffer,
+    // Host → client: JSON {error, size, sha256} answering an offer or request (`message_id`).
+    AttachmentAck,
+    // Either way: one base64 piece of a file in transfer `message_id`.
+    AttachmentChunk,
+    // Client → host: fetch attachment `message` (its id, or "thumb:<id>" for its thumbnail) as
+    // transfer `message_id`.
+    AttachmentRequest,
+    // A user's UI settings, `message` = the JSON object. Host → client after Connect and after
+    // each save; client → host to save its user's. See user_settings.rs.
+    SettingsSync,
+    // Host → clients: how many people are in a room right now (`room_id`, `message` = the
+    // count), sent whenever that changes and for every public room after Connect.
+    RoomOccupancy,
+    // Host → clients: planned maintenance, `message` = JSON {active, text, disconnect_at}. Also
+    // sent to a connection turned away while it lasts. See maintenance.rs.
+    Maintenance,
+    // Host → a connecting client below the host's minimum app version, before closing on it:
+    // JSON {min_version, version, download_url}. See client_version.rs.
+    UpgradeRequired,
+    // Host → a muted sender, in place of relaying their post: `message_id` / `room` name the
+    // dropped message, `message` says why. See moderation.rs.
+    Muted,
+    // Host → a sender who may not post in the room (a read-only announcement room), in place of
+    // relaying their post: `message_id` / `room` name the dropped message, `message` says why.
+    // See room_permissions.rs.
+    ReadOnlyRoom,
+    // A colleague's contact card shared in a room: `message` is JSON {user_id, name, email,
+    // title, phone, department}, filled in by the host from its directory. Persisted as
+    // 'Contact'. See contacts.rs.
+    Contact,
+    // Client → host: a user's profile (`message` = their id). Host → that client: a Profile with
+    // the same `message_id`, `message` = the JSON profile, or empty if there's no such user.
+    ProfileRequest,
+    Profile,
+    // A place shared in a room: `message` is JSON {label, latitude, longitude}, a label, a
+    // coordinate pair, or both. Persisted as 'Location', the coordinates also in their own
+    // columns. See location.rs.
+    Location,
+    // Client → host, on going to the background (mobile): ask for a wake token. Host → that
+    // client: WakeToken, `message` = the token to poll with. See wake.rs.
+    WakeRequest,
+    WakeToken,
+    // Client → host: a page of a room's files, `message` = JSON {room_id, type_filter, page}.
+    // Host → that client: Gallery with the same `message_id`, `message` = JSON {error, page}.
+    // See gallery.rs.
+    GalleryRequest,
+    Gallery,
+    // Host → a department member connected in some other room: an announcement was posted to
+    // the department's rooms. `message` = JSON {department_id, department, text}, `room_id` /
+    // `room` the room to open. See department_announcements.rs.
+    DepartmentNotice,
+    // Host → a single client: a system message for them alone, `message` = the text, `room` /
+    // `room_id` the room it's about. Shown, never stored. Sent for room welcomes; see
+    // room_welcome.rs.
+    SystemResponse,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -412,12 +1019,16 @@ pub struct ServerInfo {
 
 /// Discover Nutler hosts on the LAN by UDP broadcast — replaces the old TCP port-scan.
 ///
-/// Broadcasts one versioned probe to the local segment (no hardcoded IP ranges, no per-host
-/// scan) and collects `announce` replies for a short window, deduped by source IP. Only
-/// well-formed Nutler announces of a known version are listed, so an unrelated open port is
-/// never reported as a server (IMPROVEMENTS.md 2.10). User-triggered, so it's consent-gated.
+/// Broadcasts one versioned probe to the local segment, plus one to each address in the ranges
+/// the user configured (see discovery.rs), and collects `announce` replies for a short window,
+/// deduped by source IP. Only well-formed Nutler announces of a known version are listed, so an
+/// unrelated open port is never reported as a server (IMPROVEMENTS.md 2.10). User-triggered, so
+/// it's consent-gated.
 #[tauri::command]
-pub async fn discover_servers(_app: tauri::AppHandle) -> Result<Vec<ServerInfo>, String> {
+pub async fn discover_servers(
+    _app: tauri::AppHandle,
+    db: State<'_, SqlitePool>,
+) -> Result<Vec<ServerInfo>, String> {
     let socket = tokio::net::UdpSocket::bind(("0.0.0.0", 0))
         .await
         .map_err(|e| format!("Couldn't open a discovery socket: {}", e))?;
@@ -434,6 +1045,12 @@ pub async fn discover_servers(_app: tauri::AppHandle) -> Result<Vec<ServerInfo>,
         .send_to(&bytes, (std::net::Ipv4Addr::BROADCAST, DISCOVERY_PORT))
         .await
         .map_err(|e| format!("Couldn't broadcast on the network: {}", e))?;
+    // Configured ranges get the same probe, unicast. An unreachable address is no error.
+    for target in crate::discovery::probe_targets(&db).await {
+        if let Err(e) = socket.send_to(&bytes, target).await {
+            tracing::debug!("Discovery: probe to {} failed: {}", target, e);
+        }
+    }
 
     // Collect announces until the window closes, one entry per host.
     let deadline = tokio::time::Instant::now() + Duration::from_millis(DISCOVERY_WINDOW_MS);
@@ -463,6 +1080,34 @@ pub async fn discover_servers(_app: tauri::AppHandle) -> Result<Vec<ServerInfo>,
     Ok(servers)
 }
 
+/// Ask the host at `ip` for its discovery info: the announce it sends back to a probe, or None
+/// if none comes within `wait`.
+pub(crate) async fn probe_host(ip: IpAddr, wait: Duration) -> Option<ServerInfo> {
+    let bind: SocketAddr = match ip {
+        IpAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        IpAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = tokio::net::UdpSocket::bind(bind).await.ok()?;
+    let nonce = Uuid::new_v4().to_string();
+    let bytes = serde_json::to_vec(&build_probe(&nonce)).ok()?;
+    socket.send_to(&bytes, (ip, DISCOVERY_PORT)).await.ok()?;
+    let deadline = tokio::time::Instant::now() + wait;
+    let mut buf = vec![0u8; DISCOVERY_MAX_PACKET];
+    // The host echoes our nonce, which parse_announce would take for discovery's self-reply
+    // filter, so it isn't passed: here the one host asked is the one wanted.
+    while let Ok(Ok((len, src))) =
+        tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await
+    {
+        if src.ip() != ip {
+            continue;
+        }
+        if let Some((_, info)) = parse_announce(&buf[..len], src.ip(), "") {
+            return Some(info);
+        }
+    }
+    None
+}
+
 /// Best-effort LAN discovery responder for the host: answers Nutler probes with this host's
 /// name, TCP port, and live user count. Plaintext + advisory; the password handshake still
 /// gates the actual connection. Spawned alongside the TCP listener, aborted on host teardown.
@@ -498,24 +1143,27 @@ fn spawn_discovery_responder(
                     continue;
                 }
             };
-            // Only answer well-formed Nutler probes from the local segment.
-            let pkt = match parse_probe(&buf[..len]) {
-                Some(p) => p,
-                None => continue,
-            };
-            if !is_lan_source(src.ip()) {
-                continue;
-            }
             let now = tokio::time::Instant::now();
             if let Some(prev) = last_reply.get(&src.ip()) {
                 if now.duration_since(*prev) < DISCOVERY_REPLY_COOLDOWN {
                     continue;
                 }
             }
-            let name = state.username.read().await.clone();
-            // Connected clients + the host's own participant.
-            let user_count = state.server_streams.lock().await.len() + 1;
-            let out = build_announce(&name, tcp_port, user_count, pkt.nonce);
+            // Only answer well-formed Nutler probes (and wake polls, see wake.rs) from the
+            // local segment.
+            let out = match parse_probe(&buf[..len]) {
+                Some(pkt) if is_lan_source(src.ip()) => {
+                    let name = state.username.read().await.clone();
+                    // Connected clients + the host's own participant.
+                    let user_count = state.server_streams.lock().await.len() + 1;
+                    build_announce(&name, tcp_port, user_count, pkt.nonce)
+                }
+                Some(_) => continue,
+                None => match wake::answer(&state, &buf[..len], src.ip()).await {
+                    Some(out) => out,
+                    None => continue,
+                },
+            };
             // Record + opportunistically prune expired cooldown entries so the map stays bounded.
             last_reply.insert(src.ip(), now);
             if last_reply.len() > 1024 {
@@ -541,15 +1189,19 @@ pub async fn server_listen_as_participant(
     room: String,
     room_id: u64,
     password: String,
+    bind_address: Option<String>,
 ) -> Result<(), String> {
     if password.is_empty() {
         return Err("A room password is required to host".to_string());
     }
+    // One interface (picked from list_network_interfaces), or all of them by default.
+    let bind_ip =
+        crate::network::parse_bind_address(bind_address.as_deref()).map_err(|e| e.to_string())?;
     // PSK derived from the room password; every client must present the same password.
     let psk = secure::derive_psk(&password);
 
     let port = port.unwrap_or(3625);
-    let bind_addr = format!("0.0.0.0:{}", port); // Bind to all interfaces for network access
+    let bind_addr = std::net::SocketAddr::new(bind_ip, port);
 
     let listener = tokio::net::TcpListener::bind(&bind_addr)
         .await
@@ -559,6 +1211,8 @@ pub async fn server_listen_as_participant(
         .map_err(|e| format!("Failed to get server address: {}", e))?;
 
     tracing::info!("🟢 Server (as participant) listening on: {}", server_addr);
+    // Someone hosting is what serverless mode stands in for.
+    crate::gossip::stop(&state);
 
     // Update state - Server is BOTH server AND participant
     {
@@ -583,6 +1237,7 @@ pub async fn server_listen_as_participant(
             old.abort();
         }
     }
+    server_events::start(app.clone(), Arc::clone(state.inner())).await;
     // Also advertise via mDNS (best-effort; the UDP responder is the reliable path). The host
     // is the sole participant at start, so user_count = 1.
     if let Ok(mut guard) = state.mdns.lock() {
@@ -591,52 +1246,61 @@ pub async fn server_listen_as_participant(
         }
         *guard = crate::mdns::register(&username, port, 1);
     }
+    // Ask the router to forward the port, when remote access is allowed.
+    port_mapping::start(
+        app.clone(),
+        Arc::clone(state.inner()),
+        db.inner().clone(),
+        server_addr,
+    );
     // Send server join message to its own UI immediately
+    let text = SystemEvent::ServerStarted {
+        user: username.clone(),
+    }
+    .to_wire();
     let join_message = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::Connect,
         username: username.clone(),
-        user_id,
-        message: format!("🟢 {} started the server and joined the chat", username),
-        message_id: Uuid::new_v4().to_string(),
         room: room.clone(),
         room_id,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
+        ..frame(MessageType::Connect, user_id, text)
     };
 
     // Save server join to database //Use tauri::async_runtime::spawn for database operations
-    let pool_clone = db.inner().clone();
+    let store = state.message_store(db.inner());
     let msg_clone = join_message.clone();
     tauri::async_runtime::spawn(async move {
-        if let Err(e) = save_message_internal(
-            &pool_clone,
-            room_id as i64, // room_id
-            msg_clone.user_id as i64,
-            msg_clone.message,
-            "Connect".to_string(),
-            false,
-            msg_clone.message_id,
-        )
-        .await
-        {
+        if let Err(e) = store.save(&msg_clone).await {
             tracing::error!("Failed to save server join message: {}", e);
         }
     });
 
     // Emit join message to server's own UI
-    if let Ok(payload) = serde_json::to_string(&join_message) {
-        if let Err(e) = app.emit("message", payload) {
-            tracing::error!("Failed to emit server join message: {}", e);
-        }
+    if let Err(e) = message_events::emit(&app, &join_message) {
+        tracing::error!("Failed to emit server join message: {}", e);
     }
 
     // Start accepting client connections
-    // Use tauri::async_runtime::spawn for the main server loop since it needs app context
-    let app_clone = app.clone();
-    let state_clone = Arc::clone(state.inner());
-    let pool_clone = db.inner().clone();
+    spawn_accept_loop(
+        app,
+        Arc::clone(state.inner()),
+        db.inner().clone(),
+        listener,
+        psk,
+    );
+
+    Ok(())
+}
+
+/// The host's accept loop: every connection on `listener` is handshaken with `psk` and served
+/// by handle_client_connection until it goes away. Generic over the runtime so the testing
+/// module can run it under tauri's mock runtime.
+pub(crate) fn spawn_accept_loop<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    state: Arc<AppState>,
+    pool: SqlitePool,
+    listener: tokio::net::TcpListener,
+    psk: [u8; 32],
+) -> tauri::async_runtime::JoinHandle<()> {
     // Bound concurrently-handled connections so a flood can't exhaust FDs/memory.
     let conn_limiter = Arc::new(Semaphore::new(MAX_CONCURRENT_CLIENTS));
 
@@ -657,9 +1321,11 @@ pub async fn server_listen_as_participant(
                     // Per-IP cap: don't let one host monopolize the connection budget.
                     let ip = addr.ip();
                     let over_ip_limit = {
-                        let mut counts = state_clone.ip_conn_counts.lock().await;
+                        let mut counts = state.ip_conn_counts.lock().await;
                         let c = counts.entry(ip).or_insert(0);
-                        if *c >= MAX_CONN_PER_IP {
+                        // Loopback is exempt while a load test runs (see simulate.rs).
+                        if *c >= MAX_CONN_PER_IP && !(ip.is_loopback() && crate::simulate::active())
+                        {
                             true
                         } else {
                             *c += 1;
@@ -673,10 +1339,10 @@ pub async fn server_listen_as_participant(
                     }
 
                     tracing::info!("🔵 New client connecting from: {}", addr);
-                    let app_handle = app_clone.clone();
-                    let state_handle = state_clone.clone();
-                    let state_dec = state_clone.clone();
-                    let pool_handle = pool_clone.clone();
+                    let app_handle = app.clone();
+                    let state_handle = Arc::clone(&state);
+                    let state_dec = Arc::clone(&state);
+                    let pool_handle = pool.clone();
 
                     // Use tokio::spawn for individual client handling (pure network I/O)
                     tokio::spawn(async move {
@@ -707,14 +1373,12 @@ pub async fn server_listen_as_participant(
                 }
             }
         }
-    });
-
-    Ok(())
+    })
 }
 
 // Client handler - uses tokio::spawn internally but can use tauri for DB/events
-async fn handle_client_connection(
-    app: tauri::AppHandle,
+async fn handle_client_connection<R: Runtime>(
+    app: tauri::AppHandle<R>,
     state: Arc<AppState>,
     stream: TcpStream,
     pool: SqlitePool,
@@ -722,13 +1386,15 @@ async fn handle_client_connection(
 ) -> Result<(), Box<dyn std::error::Error>> {
     let peer_addr = stream.peer_addr()?;
     tracing::info!("New client connection from: {}", peer_addr);
+    set_tcp_keepalive(&stream);
 
     // Split once into owned halves: reader for this loop, writer for broadcasts.
     let (mut reader, mut writer) = stream.into_split();
 
     // Authenticate + establish encryption BEFORE trusting anything from this peer.
     // A wrong password fails the handshake and the connection is dropped.
-    let transport = match secure::responder_handshake(&mut reader, &mut writer, &psk).await {
+    let (transport, hello) = match secure::responder_handshake(&mut reader, &mut writer, &psk).await
+    {
         Ok(t) => t,
         Err(e) => {
             tracing::error!("🔒 Rejected {}: {}", peer_addr, e);
@@ -737,28 +1403,59 @@ async fn handle_client_connection(
     };
     tracing::info!("🔒 Secure session established with {}", peer_addr);
 
-    let writer_arc = Arc::new(tokio::sync::Mutex::new(writer));
+    let mut reader = FramedReader::new(reader);
+    let writer_arc = Arc::new(PeerWriter::new(
+        FramedWriter::new(writer).with_timeout(WRITE_TIMEOUT),
+    ));
     let transport_arc = Arc::new(tokio::sync::Mutex::new(transport));
+    // During planned maintenance, tell the peer why before closing on it.
+    if let Some(notice) = maintenance::notice(&state) {
+        tracing::info!("🚧 Maintenance: turning away {}", peer_addr);
+        let _ = send_secure(&writer_arc, &transport_arc, &notice).await;
+        return Ok(());
+    }
+    let app_version = client_version::version_from_hello(&hello);
+    tracing::info!(
+        "📦 {} runs version {}",
+        peer_addr,
+        app_version.as_deref().unwrap_or("unknown")
+    );
+    if let Some(upgrade) = client_version::check(&pool, app_version.as_deref()).await {
+        tracing::warn!(
+            "Rejecting {}: client version {} is below the minimum",
+            peer_addr,
+            app_version.as_deref().unwrap_or("unknown")
+        );
+        let _ = send_secure(&writer_arc, &transport_arc, &upgrade).await;
+        return Ok(());
+    }
     let conn_id = NEXT_CONN_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
     // Keep the connection alive and let the read-timeout below detect a dead peer.
-    let heartbeat = spawn_heartbeat(Arc::clone(&writer_arc));
+    let heartbeat = spawn_heartbeat(Arc::clone(&writer_arc), Arc::clone(&transport_arc));
 
     // Per-connection inbound rate limit (token bucket): a burst allowance that refills at a
     // sustained rate, so one peer can't flood the host with messages.
     let mut rate_limiter = RateLimiter::new(tokio::time::Instant::now());
+    // Large payloads arrive in fragments; they're only handled once complete.
+    let mut reassembler = secure::Reassembler::default();
 
     let mut client_info: Option<ClientConnection> = None;
+    // Set when the client says goodbye (a Disconnect frame) rather than just dropping.
+    let mut leaving = false;
+    // Why the read loop gave up on the peer, when that's an error (for the connection log).
+    let mut failure: Option<String> = None;
     loop {
-        // Read one encrypted frame (capped at MAX_FRAME_BYTES), then decrypt it.
+        // Read one encrypted frame (capped at codec::MAX_FRAME_BYTES), then decrypt it.
         // A timeout means we stopped hearing even keepalives → treat the peer as dead.
-        let framed = tokio::time::timeout(READ_TIMEOUT, read_frame(&mut reader)).await;
+        let framed = tokio::time::timeout(CLIENT_READ_TIMEOUT, reader.read_frame()).await;
         match framed {
             Err(_elapsed) => {
                 tracing::error!(
                     "⏱️  Read timeout from {} (no heartbeat); closing",
                     peer_addr
                 );
+                failure = Some("read timeout (no heartbeat)".to_string());
                 break;
             }
             // Recoverable: empty keep-alive frame → ignore and wait for next
@@ -776,6 +1473,14 @@ async fn handle_client_connection(
                         }
                     }
                 };
+                let plaintext = match reassembler.push(plaintext) {
+                    Ok(Some(p)) => p,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        tracing::error!("Bad fragment from {}: {}", peer_addr, e);
+                        break;
+                    }
+                };
                 let message_str = match std::str::from_utf8(&plaintext) {
                     Ok(s) => s,
                     Err(e) => {
@@ -783,7 +1488,7 @@ async fn handle_client_connection(
                         break;
                     }
                 };
-                let message: Message = match serde_json::from_str(message_str) {
+                let mut message: Message = match serde_json::from_str(message_str) {
                     Ok(m) => m,
                     Err(e) => {
                         tracing::error!("Malformed message from {}: {}", peer_addr, e);
@@ -791,8 +1496,12 @@ async fn handle_client_connection(
                     }
                 };
 
-                // Rate-limit: drop the frame if this connection is over its message budget.
-                if !rate_limiter.allow(tokio::time::Instant::now()) {
+                // Rate-limit: drop the frame if this connection is over its message budget. File
+                // chunks are exempt (a file is many frames); each upload is bounded by the size
+                // the host accepted in its offer.
+                if message.message_type != MessageType::AttachmentChunk
+                    && !rate_limiter.allow(tokio::time::Instant::now())
+                {
                     tracing::warn!(
                         "Rate limit exceeded by {}; dropping {:?}",
                         peer_addr,
@@ -801,8 +1510,44 @@ async fn handle_client_connection(
                     continue;
                 }
 
-                //Handle client registration
-                if message.message_type == MessageType::Connect {
+                // Account register/login happens before Connect, on a not-yet-registered
+                // connection; answer it directly (the rate limiter above bounds guessing).
+                if matches!(
+                    message.message_type,
+                    MessageType::Register | MessageType::Login | MessageType::SsoLogin
+                ) {
+                    let reply = crate::accounts::handle_auth_frame(&pool, &message).await;
+                    let _ = send_secure(&writer_arc, &transport_arc, &reply).await;
+                    continue;
+                }
+
+                //Handle client registration (a Resume is a Connect that may pick up a parked one)
+                if matches!(
+                    message.message_type,
+                    MessageType::Connect | MessageType::Resume
+                ) {
+                    // Directory / SSO sign-in (when the host enables one) gates registration
+                    // entirely: a rejected peer is told why and dropped before any identity exists.
+                    let (email, credential) =
+                        (message.email.as_deref(), message.credential.as_deref());
+                    let signed_in = async {
+                        crate::ldap::check_connect(&pool, email, credential).await?;
+                        crate::oidc::check_connect(&pool, email, credential).await?;
+                        crate::accounts::check_connect(&pool, email, credential).await
+                    }
+                    .instrument(tracing::info_span!("connect.authenticate", %peer_addr))
+                    .await;
+                    let rejection = match signed_in {
+                        Err(reason) => Some((MessageType::ErrorNotice, reason)),
+                        Ok(()) => check_username(&pool, &message.username, email).await.err(),
+                    };
+                    if let Some((kind, reason)) = rejection {
+                        tracing::warn!("Rejecting Connect from {}: {}", peer_addr, reason);
+                        server_events::rejection(&app, &pool, email, &peer_addr, &reason).await;
+                        let notice = frame(kind, 0, reason);
+                        let _ = send_secure(&writer_arc, &transport_arc, &notice).await;
+                        break;
+                    }
                     // The host is the identity authority: upsert the connecting user into the
                     // host's OWN DB by email and use that globally-unique id, because the id
                     // the client asserts is assigned by its local DB and collides across
@@ -823,7 +1568,38 @@ async fn handle_client_connection(
                         .and_then(|u| u.id),
                         None => None,
                     };
+                    // Department / company-wide auto-join rooms; only a first connect (or a
+                    // newly flagged room) actually adds memberships.
+                    let auto_joined = match canonical {
+                        Some(uid) => {
+                            apply_auto_join_internal(&pool, uid)
+                                .await
+                                .unwrap_or_else(|e| {
+                                    tracing::warn!("{}", e);
+                                    Vec::new()
+                                })
+                        }
+                        None => Vec::new(),
+                    };
                     if let Some(uid) = canonical.map(|id| id as u64) {
+                        let asked_to_resume = message.message_type == MessageType::Resume;
+                        // Any parked connection of this user is superseded now. It's resumed
+                        // only if the client presents its token; otherwise this is a fresh
+                        // Connect (with a join notice) and the parked one just goes away.
+                        let parked = unpark(&state, uid).await;
+                        let parked_room = parked.as_ref().map(|p| p.conn.current_room.clone());
+                        let resumed = match parked {
+                            Some(p)
+                                if message.message_type == MessageType::Resume
+                                    && p.conn.resume_token == message.message =>
+                            {
+                                Some(p)
+                            }
+                            _ => {
+                                message.message_type = MessageType::Connect;
+                                None
+                            }
+                        };
                         let conn = ClientConnection {
                             writer: Arc::clone(&writer_arc),
                             transport: Arc::clone(&transport_arc),
@@ -832,24 +1608,32 @@ async fn handle_client_connection(
                             room_id: message.room_id,
                             user_id: uid,
                             conn_id,
+                            forwarder: Arc::new(std::sync::Mutex::new(None)),
+                            resume_token: crate::db::random_hex_key(),
+                            drain: Arc::new(DrainStats::new()),
                         };
                         client_info = Some(conn.clone());
 
                         //Add to the server's stream list using the canonical user_id as the key
-                        {
+                        let known = {
                             let mut streams = state.server_streams.lock().await;
                             let mut rooms = state.room_clients.lock().await;
 
                             // Idempotent (re)registration: if this user is already known
-                            // (reconnect / duplicate Connect), drop it from every room first so
-                            // membership can't accumulate duplicates that double-deliver.
-                            if streams.contains_key(&uid) {
+                            // (reconnect / duplicate Connect / parked), drop it from every room
+                            // first so membership can't accumulate duplicates.
+                            let known = streams.contains_key(&uid) || parked_room.is_some();
+                            if known {
                                 for users in rooms.values_mut() {
                                     users.retain(|&id| id != uid);
                                 }
                             }
 
-                            streams.insert(uid, conn);
+                            // A replaced connection's forwarder would keep writing to the
+                            // old socket; stop it before the new one subscribes.
+                            if let Some(old) = streams.insert(uid, conn.clone()) {
+                                stop_forwarder(&old);
+                            }
 
                             //Add to room tracking (deduped)
                             let room_vec =
@@ -857,6 +1641,46 @@ async fn handle_client_connection(
                             if !room_vec.contains(&uid) {
                                 room_vec.push(uid);
                             }
+                            known
+                        };
+                        let (event, detail) = match (resumed.is_some(), asked_to_resume, known) {
+                            (true, _, _) => (connection_log::RECONNECT, Some("resumed")),
+                            (false, true, _) => (
+                                connection_log::RECONNECT,
+                                Some("resume not possible; started a new session"),
+                            ),
+                            (false, false, true) => (
+                                connection_log::RECONNECT,
+                                Some("replaced an earlier connection"),
+                            ),
+                            _ => (connection_log::CONNECT, None),
+                        };
+                        server_events::connection(&app, &pool, uid, event, &peer_addr, detail)
+                            .await;
+                        client_version::record(&pool, uid, app_version.as_deref()).await;
+                        // A resumed connection in the same room carries on from its parked
+                        // receiver, so the frames it missed are delivered first.
+                        match resumed {
+                            Some(p) if p.conn.current_room == message.room => {
+                                spawn_forwarder(&app, &state, &conn, p.rx)
+                            }
+                            _ => subscribe_to_room(&app, &state, &conn, &message.room).await,
+                        }
+                        // Back in a different room: the old room's roster lost them.
+                        if let Some(old_room) = parked_room.filter(|r| *r != message.room) {
+                            broadcast_user_list(&app, &state, &old_room).await;
+                        }
+                        send_resume_token(&state, uid).await;
+                        custom_emoji::send_manifest(&state, &pool, uid).await;
+                        stickers::send_manifest(&state, &pool, uid).await;
+                        presence::connected(&pool, uid).await;
+                        presence::send_snapshot(&state, uid).await;
+                        join_requests::send_pending(&state, &pool, uid).await;
+                        user_settings::send_saved(&state, &pool, uid).await;
+                        send_occupancy(&state, &pool, uid).await;
+                        // New members change those rooms' counts in everyone's room list.
+                        if !auto_joined.is_empty() {
+                            broadcast_room_list(&app, &state, &pool).await;
                         }
                         tracing::info!(
                             "Client registered: {} (id {}) in room {}",
@@ -883,15 +1707,75 @@ async fn handle_client_connection(
                     );
                     continue;
                 }
+                // A client that says goodbye isn't coming back: skip parking on cleanup.
+                if message.message_type == MessageType::Disconnect {
+                    leaving = true;
+                    break;
+                }
                 // A single bad message shouldn't kill the connection. Pass the
                 // connection's authenticated user_id so edit/delete can't be spoofed.
                 let auth_user_id = client_info.as_ref().map(|c| c.user_id);
+                let auth_conn_id = client_info.as_ref().map(|c| c.conn_id);
+                // The host enforces mutes: a muted user's post or edit goes no further. (File
+                // offers are refused in attachments::accept_offer, so the uploader's ack says why.)
+                if let (
+                    Some(uid),
+                    MessageType::Chat
+                    | MessageType::Edit
+                    | MessageType::Code
+                    | MessageType::Sticker
+                    | MessageType::Contact
+                    | MessageType::Location,
+                ) = (auth_user_id, message.message_type)
+                {
+                    if let Some(reply) = moderation::muted_reply(&pool, uid, &message).await {
+                        let _ = send_secure(&writer_arc, &transport_arc, &reply).await;
+                        continue;
+                    }
+                }
+                if matches!(
+                    message.message_type,
+                    MessageType::Chat | MessageType::Edit | MessageType::Code
+                ) {
+                    // Normalize here too: a client may not have (older builds don't). Code is
+                    // kept verbatim; only its language / filename are checked.
+                    let checked = if message.message_type == MessageType::Code {
+                        snippets::check(&mut message)
+                    } else {
+                        let (text, is_emoji) = emoji::normalize(&message.message);
+                        message.message = text;
+                        if message.message_type == MessageType::Chat {
+                            message.is_emoji = is_emoji;
+                        }
+                        Ok(())
+                    };
+                    let limit = state
+                        .max_message_chars
+                        .load(std::sync::atomic::Ordering::Relaxed);
+                    if let Err(e) =
+                        checked.and_then(|()| limits::check_length(&message.message, limit))
+                    {
+                        if let Some(uid) = auth_user_id {
+                            send_error_notice(&state, uid, &e.to_string()).await;
+                        }
+                        continue;
+                    }
+                }
+                if message.message_type == MessageType::Location {
+                    if let Err(e) = location::check(&mut message) {
+                        if let Some(uid) = auth_user_id {
+                            send_error_notice(&state, uid, &e.to_string()).await;
+                        }
+                        continue;
+                    }
+                }
                 if let Err(e) = handle_server_message(
                     app.clone(),
                     state.clone(),
                     message,
                     pool.clone(),
                     auth_user_id,
+                    auth_conn_id,
                 )
                 .await
                 {
@@ -905,6 +1789,7 @@ async fn handle_client_connection(
             }
             Ok(Err(e)) => {
                 tracing::error!("Connection closed: {} - {}", peer_addr, e);
+                failure = Some(e.to_string());
                 break;
             }
         }
@@ -913,20 +1798,44 @@ async fn handle_client_connection(
 
     //Clean up with proper error handling
     if let Some(client) = client_info {
-        if let Err(e) = clean_client(&state, &app, client.user_id, conn_id, &pool).await {
+        let uid = client.user_id;
+        if let Some(reason) = &failure {
+            server_events::connection(
+                &app,
+                &pool,
+                uid,
+                connection_log::ERROR,
+                &peer_addr,
+                Some(reason),
+            )
+            .await;
+        }
+        let how = if leaving { "signed off" } else { "dropped" };
+        server_events::connection(
+            &app,
+            &pool,
+            uid,
+            connection_log::DISCONNECT,
+            &peer_addr,
+            Some(how),
+        )
+        .await;
+        if let Err(e) = clean_client(&state, &app, client.user_id, conn_id, &pool, !leaving).await {
             tracing::error!("Cleanup error: {}", e);
         }
     }
 
     Ok(())
 }
-//Separate cleanup function
-async fn clean_client(
+//Separate cleanup function. A `resumable` drop (no goodbye from the client) is parked for
+// RESUME_GRACE instead of announced right away.
+async fn clean_client<R: Runtime>(
     state: &Arc<AppState>,
-    app: &tauri::AppHandle,
+    app: &tauri::AppHandle<R>,
     user_id: u64,
     conn_id: u64,
     pool: &SqlitePool,
+    resumable: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Remove the LIVE entry ONLY if it's still THIS connection: a reconnect may have
     // replaced server_streams[user_id] with a newer, live connection — tearing that one
@@ -941,12 +1850,28 @@ async fn clean_client(
     let Some(client) = removed else {
         return Ok(());
     };
+    stop_forwarder(&client);
+    if resumable {
+        park_client(app, state, client).await;
+        return Ok(());
+    }
+    announce_departure(state, app, &client, pool).await
+}
+
+/// Take `client` off the roster and tell its room it left.
+async fn announce_departure<R: Runtime>(
+    state: &Arc<AppState>,
+    app: &tauri::AppHandle<R>,
+    client: &ClientConnection,
+    pool: &SqlitePool,
+) -> Result<(), Box<dyn std::error::Error>> {
     {
         let mut rooms = state.room_clients.lock().await;
         if let Some(users) = rooms.get_mut(&client.current_room) {
             users.retain(|&id| id != client.user_id);
         }
     }
+    presence::departed(app, state, pool, client.user_id, &client.username).await;
     tracing::info!(
         "Client disconnected: {} (ID: {})",
         client.username,
@@ -954,31 +1879,19 @@ async fn clean_client(
     );
 
     //Save the disconnect message to the database
+    let text = SystemEvent::Left {
+        user: client.username.clone(),
+    }
+    .to_wire();
     let disconnect_msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::Disconnect,
         username: client.username.clone(),
-        user_id: client.user_id,
-        message: format!("{} left the chat", client.username),
-        message_id: Uuid::new_v4().to_string(),
         room: client.current_room.clone(),
         room_id: client.room_id,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
+        ..frame(MessageType::Disconnect, client.user_id, text)
     };
 
     //Save the disconnect message to the database
-    save_message_internal(
-        pool,
-        client.room_id as i64,
-        client.user_id as i64,
-        disconnect_msg.message.clone(),
-        "Disconnect".to_string(),
-        false,
-        disconnect_msg.message_id.clone(),
-    )
-    .await?;
+    state.message_store(pool).save(&disconnect_msg).await?;
 
     //Broadcast disconnect + the updated roster
     distribute_message_to_all(
@@ -986,7 +1899,7 @@ async fn clean_client(
         state,
         &client.current_room,
         &disconnect_msg,
-        Some(client.user_id),
+        Some(client.conn_id),
     )
     .await;
     broadcast_user_list(app, state, &client.current_room).await;
@@ -994,94 +1907,255 @@ async fn clean_client(
     Ok(())
 }
 
-// ENHANCED MESSAGE DISTRIBUTION - Handles both network + local UI now async to await tokio locks
-// Returns a boxed future (rather than `async fn`) so its return type is concrete, not opaque:
-// the eviction path below spawns `clean_client`, which broadcasts via this function — a mutual
-// async recursion that an opaque `async fn` return type can't resolve (`Send`/opaque cycle).
-fn distribute_message_to_all<'a>(
-    app: &'a tauri::AppHandle,
-    state: &'a Arc<AppState>,
-    target_room: &'a str,
-    message: &'a Message,
-    exclude_user_id: Option<u64>,
-) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send + 'a>> {
-    Box::pin(async move {
-        // Briefly hold the collection locks to snapshot the target writers, then release ALL
-        // locks before any network I/O or emit (avoids holding mutexes across .await fan-out).
-        type Target = (
-            Arc<tokio::sync::Mutex<tokio::net::tcp::OwnedWriteHalf>>,
-            Arc<tokio::sync::Mutex<TransportState>>,
-            String,
-            u64,
-            u64,
-        );
-        let targets: Vec<Target> = {
-            let streams = state.server_streams.lock().await;
-            let room_clients = state.room_clients.lock().await;
-            let mut v = Vec::new();
-            if let Some(user_ids) = room_clients.get(target_room) {
-                for &user_id in user_ids {
-                    //Skip the excluded user (usually the sender)
-                    if Some(user_id) == exclude_user_id {
-                        continue;
-                    }
-                    // NOTE: we intentionally do NOT skip "the server's own user_id" here. The
-                    // host is never in server_streams, so a host-id lookup below already returns
-                    // None (no network send). But user_ids are assigned by each instance's local
-                    // DB and so collide across machines — a client can legitimately share the
-                    // host's id, and `streams.get` then resolves to that real client. Skipping by
-                    // id dropped such a client from delivery (they could send but never receive).
-                    if let Some(conn) = streams.get(&user_id) {
-                        v.push((
-                            Arc::clone(&conn.writer),
-                            Arc::clone(&conn.transport),
-                            conn.username.clone(),
-                            user_id,
-                            conn.conn_id,
-                        ));
-                    }
-                }
+/// Hold a dropped `client` for RESUME_GRACE: keep it on the roster, keep queueing its room's
+/// frames, and announce the departure only if it hasn't resumed by then.
+async fn park_client<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    client: ClientConnection,
+) {
+    let rx = {
+        let mut channels = state.room_channels.lock().await;
+        channels
+            .entry(client.current_room.clone())
+            .or_insert_with(|| tokio::sync::broadcast::channel(ROOM_CHANNEL_CAPACITY).0)
+            .subscribe()
+    };
+    let (user_id, conn_id) = (client.user_id, client.conn_id);
+    let (app2, state2) = (app.clone(), Arc::clone(state));
+    let expiry = tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(RESUME_GRACE).await;
+        // Only our own parking: a later drop of the same user re-parks under a new conn_id.
+        let expired = {
+            let mut parked = state2.parked.lock().await;
+            match parked.get(&user_id) {
+                Some(p) if p.conn.conn_id == conn_id => parked.remove(&user_id),
+                _ => None,
             }
-            v
-        }; // locks released here
-
-        tracing::info!("📡 Broadcasting to {} network clients", targets.len());
-        // Pool for eviction (set once at startup); cloned per failed send.
-        let evict_pool = state.pool.get().cloned();
-        for (writer, transport, username, user_id, conn_id) in targets {
-            let msg = message.clone();
-            let state = Arc::clone(state);
-            let app = app.clone();
-            let evict_pool = evict_pool.clone();
-            tauri::async_runtime::spawn(async move {
-                match send_secure(&writer, &transport, &msg).await {
-                    Ok(_) => tracing::info!(" ✅ Sent to {} ({})", username, user_id),
-                    Err(e) => {
-                        // A send failure means the socket is broken → evict the ghost now, instead
-                        // of waiting for the read side to notice at READ_TIMEOUT. clean_client is
-                        // idempotent (compare-and-remove by conn_id), so a live reconnect is safe.
+        };
+        if let (Some(p), Some(pool)) = (expired, state2.pool.get()) {
+            if let Err(e) = announce_departure(&state2, &app2, &p.conn, pool).await {
+                tracing::error!("Cleanup error: {}", e);
+            }
+        }
+    });
+    tracing::info!(
+        "Parked {} (ID: {}) for {:?} awaiting resume",
+        client.username,
+        user_id,
+        RESUME_GRACE
+    );
+    let old = state.parked.lock().await.insert(
+        user_id,
+        ParkedConnection {
+            conn: client,
+            rx,
+            expiry,
+        },
+    );
+    if let Some(old) = old {
+        old.expiry.abort();
+    }
+}
+
+/// Take `user_id`'s parked connection, if any, cancelling its pending departure notice.
+async fn unpark(state: &Arc<AppState>, user_id: u64) -> Option<ParkedConnection> {
+    let parked = state.parked.lock().await.remove(&user_id)?;
+    parked.expiry.abort();
+    Some(parked)
+}
+
+// ENHANCED MESSAGE DISTRIBUTION - Handles both network + local UI now async to await tokio locks
+// Network delivery is a single send on the room's broadcast channel: each connected client's
+// forwarder task (see subscribe_to_room) writes its own socket, so no stream lock is taken here
+// and a slow peer only delays itself.
+#[tracing::instrument(
+    name = "broadcast",
+    skip_all,
+    fields(room = %target_room, message_id = %message.message_id, subscribers)
+)]
+pub(crate) async fn distribute_message_to_all<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    target_room: &str,
+    message: &Message,
+    exclude_conn_id: Option<u64>,
+) {
+    // Exclusion is by connection, not user_id: user_ids collide across machines and one user can
+    // be on several devices, so skipping a user_id dropped other connections from delivery.
+    // The host never subscribes to a room channel, so its own posts need no exclusion at all.
+    let subscribers = {
+        let mut channels = state.room_channels.lock().await;
+        let frame = RoomFrame {
+            message: Arc::new(message.clone()),
+            exclude_conn_id,
+            queued_at: std::time::Instant::now(),
+        };
+        match channels.get(target_room).map(|tx| tx.send(frame)) {
+            Some(Ok(n)) => n,
+            // Nobody subscribed any more → drop the idle channel; the next join recreates it.
+            Some(Err(_)) => {
+                channels.remove(target_room);
+                0
+            }
+            None => 0,
+        }
+    }; // lock released here
+    tracing::Span::current().record("subscribers", subscribers);
+    METRICS
+        .messages_broadcast
+        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+    tracing::info!("📡 Broadcasting to {} room subscribers", subscribers);
+    // ALWAYS send it to local UI too (this machine's interface)
+    match tracing::info_span!("emit_local").in_scope(|| message_events::emit(app, message)) {
+        Ok(_) => tracing::info!("📱 Emitted to local UI successfully"),
+        Err(e) => tracing::error!("📱 Failed to emit to local UI: {}", e),
+    }
+}
+
+/// Point `conn`'s forwarder at `room`: subscribe to the room's broadcast channel (creating it on
+/// first use) and spawn a task relaying each frame to the socket, replacing any previous
+/// forwarder. A failed send evicts the ghost right away instead of waiting for CLIENT_READ_TIMEOUT.
+async fn subscribe_to_room<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    conn: &ClientConnection,
+    room: &str,
+) {
+    let rx = {
+        let mut channels = state.room_channels.lock().await;
+        channels
+            .entry(room.to_string())
+            .or_insert_with(|| tokio::sync::broadcast::channel(ROOM_CHANNEL_CAPACITY).0)
+            .subscribe()
+    };
+    spawn_forwarder(app, state, conn, rx);
+}
+
+/// Relay frames from `rx` to `conn`'s socket on a new forwarder task, replacing any previous one.
+/// The new task waits for the old one to finish its frame and exit before it writes, so the two
+/// never share the socket; frames for the new room wait in `rx` meanwhile.
+fn spawn_forwarder<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    conn: &ClientConnection,
+    mut rx: tokio::sync::broadcast::Receiver<RoomFrame>,
+) {
+    let Ok(mut slot) = conn.forwarder.lock() else {
+        return;
+    };
+    let previous = slot.take().map(|mut old| {
+        old.stop();
+        old.task
+    });
+    let (stop, mut stopped) = tokio::sync::oneshot::channel::<()>();
+    let writer = Arc::clone(&conn.writer);
+    let transport = Arc::clone(&conn.transport);
+    let (username, user_id, conn_id) = (conn.username.clone(), conn.user_id, conn.conn_id);
+    let drain = Arc::clone(&conn.drain);
+    let conn_room = conn.current_room.clone();
+    let (app, state) = (app.clone(), Arc::clone(state));
+    let task = tauri::async_runtime::spawn(async move {
+        use std::sync::atomic::Ordering;
+        use tokio::sync::broadcast::error::RecvError;
+        if let Some(previous) = previous {
+            let _ = previous.await;
+        }
+        loop {
+            // Stopping is only noticed here, between frames (a dropped sender stops it too).
+            let received = tokio::select! {
+                biased;
+                _ = &mut stopped => break,
+                received = rx.recv() => received,
+            };
+            match received {
+                Ok(frame) => {
+                    drain.queued.store(rx.len(), Ordering::Relaxed);
+                    //Skip the excluded connection (usually the sender's)
+                    if frame.exclude_conn_id == Some(conn_id) {
+                        continue;
+                    }
+                    if rx.len() >= LAG_THRESHOLD {
+                        report_lag(&app, &drain, user_id, &username, &frame.message.room);
+                    }
+                    let span = tracing::info_span!(
+                        "deliver",
+                        user_id,
+                        message_id = %frame.message.message_id
+                    );
+                    let send = send_secure(&writer, &transport, &frame.message).instrument(span);
+                    tokio::pin!(send);
+                    let sent = tokio::select! {
+                        sent = &mut send => sent,
+                        _ = tokio::time::sleep(STUCK_SEND_AFTER) => {
+                            report_lag(&app, &drain, user_id, &username, &frame.message.room);
+                            send.await
+                        }
+                    };
+                    METRICS.broadcast_latency.observe(frame.queued_at.elapsed());
+                    if let Err(e) = sent {
                         tracing::info!(
                             "   ❌ Failed to send to {} ({}): {}; evicting",
                             username,
                             user_id,
                             e
                         );
-                        if let Some(pool) = evict_pool {
-                            let _ = clean_client(&state, &app, user_id, conn_id, &pool).await;
+                        // Evict on its own task: clean_client stops this forwarder, which
+                        // must not cancel the cleanup halfway. It's idempotent (compare-and-
+                        // remove by conn_id), so a live reconnect is safe.
+                        if let Some(pool) = state.pool.get().cloned() {
+                            tauri::async_runtime::spawn(async move {
+                                let _ =
+                                    clean_client(&state, &app, user_id, conn_id, &pool, true).await;
+                            });
                         }
+                        break;
                     }
+                    drain.wrote();
                 }
-            });
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(
+                        "{} ({}) fell behind; skipped {} frames",
+                        username,
+                        user_id,
+                        skipped
+                    );
+                    drain.queued.store(rx.len(), Ordering::Relaxed);
+                    report_lag(&app, &drain, user_id, &username, &conn_room);
+                }
+                Err(RecvError::Closed) => break,
+            }
         }
-        // 2. ALWAYS send it to local UI (this machine's interface)
-        match serde_json::to_string(message) {
-            Ok(payload) => match app.emit("message", payload) {
-                Ok(_) => tracing::info!("📱 Emitted to local UI successfully"),
-                Err(e) => tracing::error!("📱 Failed to emit to local UI: {}", e),
-            },
-            Err(e) => tracing::error!("📱 Failed to serialize message for local UI: {}", e),
+    });
+    *slot = Some(Forwarder {
+        stop: Some(stop),
+        task,
+    });
+}
+
+/// Tell the host's UI a client is falling behind, once until it catches up.
+fn report_lag<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    drain: &DrainStats,
+    user_id: u64,
+    username: &str,
+    room: &str,
+) {
+    if drain.start_lagging() {
+        tracing::warn!("{} ({}) is lagging in {}", username, user_id, room);
+        server_events::lagging(app, user_id, username, room, drain);
+    }
+}
+
+/// Stop relaying any room to `conn` (room leave, replaced connection, teardown). The forwarder
+/// finishes the frame it's writing, if any, and exits.
+fn stop_forwarder(conn: &ClientConnection) {
+    if let Ok(mut slot) = conn.forwarder.lock() {
+        if let Some(old) = slot.as_mut() {
+            old.stop();
         }
-    })
+    }
 }
 
 /// Build the list of usernames currently present in `room` (server truth, from the
@@ -1107,23 +2181,85 @@ async fn room_member_names(state: &Arc<AppState>, room: &str) -> Vec<String> {
 
 /// Broadcast the live roster of `room` to everyone in it (and the host's own UI) as a
 /// UserList message, so member panels reflect server truth on every membership change.
-async fn broadcast_user_list(app: &tauri::AppHandle, state: &Arc<AppState>, room: &str) {
+async fn broadcast_user_list<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    room: &str,
+) {
     let names = room_member_names(state, room).await;
     let payload = serde_json::to_string(&names).unwrap_or_else(|_| "[]".to_string());
     let msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::UserList,
-        username: String::new(),
-        user_id: 0,
-        message: payload,
-        message_id: Uuid::new_v4().to_string(),
         room: room.to_string(),
-        room_id: 0,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
+        ..frame(MessageType::UserList, 0, payload)
     };
     distribute_message_to_all(app, state, room, &msg, None).await;
+    broadcast_occupancy(app, state, room, names.len()).await;
+}
+
+fn occupancy_frame(room: &str, room_id: i64, count: usize) -> Message {
+    Message {
+        room: room.to_string(),
+        room_id: room_id as u64,
+        ..frame(MessageType::RoomOccupancy, 0, count.to_string())
+    }
+}
+
+/// Tell every client (and the host's own UI) how many people are in `room` now, so room lists
+/// show live counts. A private room's count only goes to the people in it.
+async fn broadcast_occupancy<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    room: &str,
+    count: usize,
+) {
+    let Some(pool) = state.pool.get() else {
+        return;
+    };
+    let found: Option<(i64, bool)> =
+        sqlx::query_as("SELECT id, COALESCE(is_private, 0) FROM chat_rooms WHERE name = $1")
+            .bind(room)
+            .fetch_optional(pool)
+            .await
+            .unwrap_or(None);
+    let Some((room_id, is_private)) = found else {
+        return;
+    };
+    let msg = occupancy_frame(room, room_id, count);
+    if is_private {
+        distribute_message_to_all(app, state, room, &msg, None).await;
+        return;
+    }
+    let uids: Vec<u64> = state.server_streams.lock().await.keys().copied().collect();
+    for uid in uids {
+        send_to_client(state, uid, &msg).await;
+    }
+    let _ = message_events::emit(app, &msg);
+}
+
+/// Send a newly registered client the current count of every public room.
+async fn send_occupancy(state: &Arc<AppState>, pool: &SqlitePool, user_id: u64) {
+    let rooms: Vec<(i64, String)> =
+        match sqlx::query_as("SELECT id, name FROM chat_rooms WHERE COALESCE(is_private, 0) = 0")
+            .fetch_all(pool)
+            .await
+        {
+            Ok(rooms) => rooms,
+            Err(e) => {
+                tracing::warn!("Couldn't list rooms for occupancy: {}", e);
+                return;
+            }
+        };
+    let counts: HashMap<String, usize> = state
+        .room_clients
+        .lock()
+        .await
+        .iter()
+        .map(|(room, ids)| (room.clone(), ids.len()))
+        .collect();
+    for (room_id, room) in rooms {
+        let count = counts.get(&room).copied().unwrap_or(0);
+        send_to_client(state, user_id, &occupancy_frame(&room, room_id, count)).await;
+    }
 }
 
 /// Send one client the recent history (messages + reactions) of a room from the host's
@@ -1169,17 +2305,9 @@ async fn send_room_history(
             .collect();
         let payload = serde_json::json!({ "messages": msgs, "reactions": reactions }).to_string();
         Message {
-            version: PROTOCOL_VERSION,
-            message_type: msg_type,
-            username: String::new(),
-            user_id: 0,
-            message: payload,
-            message_id: Uuid::new_v4().to_string(),
             room: room.to_string(),
             room_id,
-            created_at: now_secs(),
-            is_emoji: false,
-            email: None,
+            ..frame(msg_type, 0, payload)
         }
     };
 
@@ -1221,61 +2349,46 @@ async fn push_unread(state: &Arc<AppState>, pool: &SqlitePool, user_id: u64) {
         .await
         .unwrap_or_default();
     let payload = serde_json::to_string(&counts).unwrap_or_else(|_| "[]".to_string());
-    let msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::UnreadCounts,
-        username: String::new(),
-        user_id: 0,
-        message: payload,
-        message_id: Uuid::new_v4().to_string(),
-        room: String::new(),
-        room_id: 0,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
-    };
+    let msg = frame(MessageType::UnreadCounts, 0, payload);
     let _ = send_secure(&writer, &transport, &msg).await;
 }
 
-/// Push the user directory (everyone in the host DB) to every connected client + the host's
-/// own UI, so invite/DM pickers have someone to choose. Cheap; called when the roster changes.
-async fn push_user_directory(app: &tauri::AppHandle, state: &Arc<AppState>, pool: &SqlitePool) {
-    let users = list_users_internal(pool).await.unwrap_or_default();
-    let payload = serde_json::to_string(&users).unwrap_or_else(|_| "[]".to_string());
-    let msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::UserDirectory,
-        username: String::new(),
-        user_id: 0,
-        message: payload,
-        message_id: Uuid::new_v4().to_string(),
-        room: String::new(),
-        room_id: 0,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
+/// Push the user directory (the users in the host DB each recipient may see) to every connected
+/// client + the host's own UI, so invite/DM pickers have someone to choose. Called when the
+/// roster changes.
+async fn push_user_directory<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+) {
+    // Each recipient gets the directory they may see (department scoping).
+    let directory = |viewer: u64| async move {
+        let users = list_users_internal(pool, viewer as i64)
+            .await
+            .unwrap_or_default();
+        let payload = serde_json::to_string(&users).unwrap_or_else(|_| "[]".to_string());
+        frame(MessageType::UserDirectory, 0, payload)
     };
     let conns: Vec<_> = {
         let streams = state.server_streams.lock().await;
         streams
-            .values()
-            .map(|c| (Arc::clone(&c.writer), Arc::clone(&c.transport)))
+            .iter()
+            .map(|(uid, c)| (*uid, Arc::clone(&c.writer), Arc::clone(&c.transport)))
             .collect()
     };
-    for (writer, transport) in conns {
-        let _ = send_secure(&writer, &transport, &msg).await;
-    }
-    if let Ok(s) = serde_json::to_string(&msg) {
-        let _ = app.emit("message", s);
+    for (uid, writer, transport) in conns {
+        let _ = send_secure(&writer, &transport, &directory(uid).await).await;
     }
+    let host = state.user_id.read().await.unwrap_or(0);
+    let _ = message_events::emit(app, &directory(host).await);
 }
 
 /// Push `user_id` their authoritative room list after a membership change (invited to a private
 /// channel, added to a DM). For a connected client this sends a RoomList frame computed on the
 /// host DB; for the host's own participant it emits a local `rooms_changed` event so the host UI
 /// reloads. An offline client picks up the change via the RoomList push on its next connect.
-async fn push_rooms_update(
-    app: &tauri::AppHandle,
+pub(crate) async fn push_rooms_update<R: Runtime>(
+    app: &tauri::AppHandle<R>,
     state: &Arc<AppState>,
     pool: &SqlitePool,
     user_id: u64,
@@ -1287,23 +2400,11 @@ async fn push_rooms_update(
             .map(|c| (Arc::clone(&c.writer), Arc::clone(&c.transport)))
     };
     if let Some((writer, transport)) = conn {
-        let rooms = get_chat_rooms_internal(pool, user_id as i64)
+        let rooms = get_chat_rooms_internal(pool, user_id as i64, &[])
             .await
             .unwrap_or_default();
         let payload = serde_json::to_string(&rooms).unwrap_or_else(|_| "[]".to_string());
-        let msg = Message {
-            version: PROTOCOL_VERSION,
-            message_type: MessageType::RoomList,
-            username: String::new(),
-            user_id: 0,
-            message: payload,
-            message_id: Uuid::new_v4().to_string(),
-            room: String::new(),
-            room_id: 0,
-            created_at: now_secs(),
-            is_emoji: false,
-            email: None,
-        };
+        let msg = frame(MessageType::RoomList, 0, payload);
         let _ = send_secure(&writer, &transport, &msg).await;
     } else if Some(user_id) == *state.user_id.read().await {
         // The affected member is the host's own participant (not in server_streams) — nudge
@@ -1315,7 +2416,11 @@ async fn push_rooms_update(
 /// Push every connected client (and the host's own UI) a fresh room list — used when a PUBLIC
 /// channel is created, since it becomes visible to everyone. Each recipient gets the list
 /// computed for their own id, so private rooms stay scoped.
-async fn broadcast_room_list(app: &tauri::AppHandle, state: &Arc<AppState>, pool: &SqlitePool) {
+pub(crate) async fn broadcast_room_list<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+) {
     let uids: Vec<u64> = {
         let streams = state.server_streams.lock().await;
         streams.keys().copied().collect()
@@ -1328,9 +2433,72 @@ async fn broadcast_room_list(app: &tauri::AppHandle, state: &Arc<AppState>, pool
     }
 }
 
+/// Vet the name a Connect asks for: a malformed or reserved name is an ErrorNotice, and one
+/// another user (by email) already goes by is a NameInUse — two people can't share a name.
+/// Without an email there is no identity to compare against; registration is skipped anyway.
+async fn check_username(
+    pool: &SqlitePool,
+    username: &str,
+    email: Option<&str>,
+) -> Result<(), (MessageType, String)> {
+    validate_username(username).map_err(|e| (MessageType::ErrorNotice, e))?;
+    let Some(email) = email else {
+        return Ok(());
+    };
+    match username_taken_internal(pool, username, email).await {
+        Ok(false) => Ok(()),
+        Ok(true) => Err((
+            MessageType::NameInUse,
+            format!("\"{}\" is already taken — pick a different name", username),
+        )),
+        Err(e) => Err((MessageType::ErrorNotice, e)),
+    }
+}
+
 /// Send one client a human-readable error (in `message`) for a request that failed host-side,
 /// so it can surface feedback instead of silently doing nothing.
-async fn send_error_notice(state: &Arc<AppState>, user_id: u64, text: &str) {
+pub(crate) async fn send_error_notice(state: &Arc<AppState>, user_id: u64, text: &str) {
+    send_notice(
+        state,
+        user_id,
+        MessageType::ErrorNotice,
+        text,
+        Uuid::new_v4().to_string(),
+        String::new(),
+        0,
+    )
+    .await;
+}
+
+/// Turn away a client's post in a room it may only read: a ReadOnlyRoom carrying the post's id
+/// and room, so the client can take back the copy it already showed.
+pub(crate) async fn send_read_only(
+    state: &Arc<AppState>,
+    user_id: u64,
+    refused: &Message,
+    text: &str,
+) {
+    send_notice(
+        state,
+        user_id,
+        MessageType::ReadOnlyRoom,
+        text,
+        refused.message_id.clone(),
+        refused.room.clone(),
+        refused.room_id,
+    )
+    .await;
+}
+
+async fn send_notice(
+    state: &Arc<AppState>,
+    user_id: u64,
+    message_type: MessageType,
+    text: &str,
+    message_id: String,
+    room: String,
+    room_id: u64,
+) {
     let conn = {
         let streams = state.server_streams.lock().await;
         streams
@@ -1339,17 +2507,10 @@ async fn send_error_notice(state: &Arc<AppState>, user_id: u64, text: &str) {
     };
     if let Some((writer, transport)) = conn {
         let msg = Message {
-            version: PROTOCOL_VERSION,
-            message_type: MessageType::ErrorNotice,
-            username: String::new(),
-            user_id: 0,
-            message: text.to_string(),
-            message_id: Uuid::new_v4().to_string(),
-            room: String::new(),
-            room_id: 0,
-            created_at: now_secs(),
-            is_emoji: false,
-            email: None,
+            message_id,
+            room,
+            room_id,
+            ..frame(message_type, 0, text.to_string())
         };
         let _ = send_secure(&writer, &transport, &msg).await;
     }
@@ -1366,26 +2527,37 @@ async fn send_identity(state: &Arc<AppState>, user_id: u64) {
             .map(|c| (Arc::clone(&c.writer), Arc::clone(&c.transport)))
     };
     if let Some((writer, transport)) = conn {
-        let msg = Message {
-            version: PROTOCOL_VERSION,
-            message_type: MessageType::Identity,
-            username: String::new(),
-            user_id,
-            message: String::new(),
-            message_id: Uuid::new_v4().to_string(),
-            room: String::new(),
-            room_id: 0,
-            created_at: now_secs(),
-            is_emoji: false,
-            email: None,
-        };
+        // Our message length limit, so the client checks its sends against it.
+        let limit = state
+            .max_message_chars
+            .load(std::sync::atomic::Ordering::Relaxed);
+        let msg = frame(MessageType::Identity, user_id, limit.to_string());
+        let _ = send_secure(&writer, &transport, &msg).await;
+    }
+}
+
+/// Hand a freshly-registered client the token to present (in a Resume frame) if it drops and
+/// reconnects within RESUME_GRACE.
+async fn send_resume_token(state: &Arc<AppState>, user_id: u64) {
+    let conn = {
+        let streams = state.server_streams.lock().await;
+        streams.get(&user_id).map(|c| {
+            (
+                Arc::clone(&c.writer),
+                Arc::clone(&c.transport),
+                c.resume_token.clone(),
+            )
+        })
+    };
+    if let Some((writer, transport, token)) = conn {
+        let msg = frame(MessageType::ResumeToken, user_id, token);
         let _ = send_secure(&writer, &transport, &msg).await;
     }
 }
 
 /// Send the requesting client the DM room they just opened (as JSON in `message`), so their
 /// client can switch to it immediately rather than hunting for it after a rooms reload.
-async fn send_dm_ready(state: &Arc<AppState>, user_id: u64, room: &ChatRoom) {
+pub(crate) async fn send_dm_ready(state: &Arc<AppState>, user_id: u64, room: &ChatRoom) {
     let conn = {
         let streams = state.server_streams.lock().await;
         streams
@@ -1394,19 +2566,7 @@ async fn send_dm_ready(state: &Arc<AppState>, user_id: u64, room: &ChatRoom) {
     };
     if let Some((writer, transport)) = conn {
         let payload = serde_json::to_string(room).unwrap_or_else(|_| "{}".to_string());
-        let msg = Message {
-            version: PROTOCOL_VERSION,
-            message_type: MessageType::DmReady,
-            username: String::new(),
-            user_id: 0,
-            message: payload,
-            message_id: Uuid::new_v4().to_string(),
-            room: String::new(),
-            room_id: 0,
-            created_at: now_secs(),
-            is_emoji: false,
-            email: None,
-        };
+        let msg = frame(MessageType::DmReady, 0, payload);
         let _ = send_secure(&writer, &transport, &msg).await;
     }
 }
@@ -1415,8 +2575,8 @@ async fn send_dm_ready(state: &Arc<AppState>, user_id: u64, room: &ChatRoom) {
 /// clients currently viewing the room have it marked read (they see it live); clients
 /// elsewhere get a fresh unread push (their badge for this room may have grown). This is
 /// what makes background-room badges work despite the host only relaying the active room.
-async fn notify_unread_for_room(
-    app: &tauri::AppHandle,
+async fn notify_unread_for_room<R: Runtime>(
+    app: &tauri::AppHandle<R>,
     state: &Arc<AppState>,
     pool: &SqlitePool,
     room: &str,
@@ -1448,35 +2608,55 @@ async fn notify_unread_for_room(
                 .await
                 .unwrap_or_default();
             if let Ok(payload) = serde_json::to_string(&counts) {
-                let msg = Message {
-                    version: PROTOCOL_VERSION,
-                    message_type: MessageType::UnreadCounts,
-                    username: String::new(),
-                    user_id: 0,
-                    message: payload,
-                    message_id: Uuid::new_v4().to_string(),
-                    room: String::new(),
-                    room_id: 0,
-                    created_at: now_secs(),
-                    is_emoji: false,
-                    email: None,
-                };
-                if let Ok(s) = serde_json::to_string(&msg) {
-                    let _ = app.emit("message", s);
-                }
+                let msg = frame(MessageType::UnreadCounts, 0, payload);
+                let _ = message_events::emit(app, &msg);
             }
         }
     }
 }
 
-async fn handle_server_message(
-    app: tauri::AppHandle,
+/// The host's own activity as a participant (typing, presence) goes through the same dispatcher
+/// as a client's frames, as the hosting user on no connection. Whatever a client's frame gets
+/// (settings checks, relaying, the host's bookkeeping), the host's gets too.
+pub(crate) async fn handle_participant_frame<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    message: Message,
+) {
+    let Some(host_id) = *state.user_id.read().await else {
+        return;
+    };
+    let kind = message.message_type;
+    if let Err(e) = handle_server_message(
+        app.clone(),
+        Arc::clone(state),
+        message,
+        pool.clone(),
+        Some(host_id),
+        None,
+    )
+    .await
+    {
+        tracing::warn!("Host {:?} frame failed: {}", kind, e);
+    }
+}
+
+#[tracing::instrument(
+    name = "handle_frame",
+    skip_all,
+    fields(kind = ?message.message_type, message_id = %message.message_id, user_id = auth_user_id)
+)]
+async fn handle_server_message<R: Runtime>(
+    app: tauri::AppHandle<R>,
     state: Arc<AppState>,
     message: Message,
     pool: SqlitePool,
     // The canonical user_id bound to THIS connection at Connect; used for authorship
     // checks so a peer can't act as another user by spoofing message.user_id.
     auth_user_id: Option<u64>,
+    // The connection the frame came in on, which relayed copies skip.
+    auth_conn_id: Option<u64>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Re-stamp the frame with the connection's canonical id (the client asserts a
     // per-instance id that collides across machines, and the host's DB is the authority).
@@ -1485,6 +2665,9 @@ async fn handle_server_message(
     if let Some(uid) = auth_user_id {
         message.user_id = uid;
     }
+    // Likewise the timestamp: the host's clock is authoritative, so a peer with a skewed clock
+    // can't relay messages "from the future" (or the past) to everyone else.
+    message.created_at = now_secs();
     // The email was already consumed during Connect registration (above, in the read loop);
     // drop it so it's never relayed to other clients in the distributed Connect notice.
     message.email = None;
@@ -1497,24 +2680,23 @@ async fn handle_server_message(
 
     match message.message_type {
         MessageType::Connect => {
+            // Presence notices are composed here from the frame, not taken from the sender's
+            // text, so every row is a structured event (older clients still send prose).
+            message.message = SystemEvent::Joined {
+                user: message.username.clone(),
+            }
+            .to_wire();
             //Save connect the message to the db
-            let pool_clone = pool.clone();
+            let store = state.message_store(&pool);
             let msg_clone = message.clone();
-            tauri::async_runtime::spawn(async move {
-                if let Err(e) = save_message_internal(
-                    &pool_clone,
-                    msg_clone.room_id as i64,
-                    msg_clone.user_id as i64,
-                    msg_clone.message,
-                    "Connect".to_string(),
-                    false,
-                    msg_clone.message_id,
-                )
-                .await
-                {
-                    tracing::error!("Failed to save connect message to db: {}", e);
+            tauri::async_runtime::spawn(
+                async move {
+                    if let Err(e) = store.save(&msg_clone).await {
+                        tracing::error!("Failed to save connect message to db: {}", e);
+                    }
                 }
-            });
+                .in_current_span(),
+            );
             // Distribute to all participants
             distribute_message_to_all(&app, &state, &message.room, &message, None).await;
             broadcast_user_list(&app, &state, &message.room).await;
@@ -1525,11 +2707,60 @@ async fn handle_server_message(
                 send_identity(&state, requester).await;
                 push_unread(&state, &pool, requester).await;
                 push_rooms_update(&app, &state, &pool, requester).await;
+                room_welcome::welcome(
+                    &state,
+                    &pool,
+                    requester,
+                    &message.username,
+                    &message.room,
+                    message.room_id,
+                )
+                .await;
             }
             // The roster grew → refresh everyone's invite/DM directory.
             push_user_directory(&app, &state, &pool).await;
         }
-        MessageType::Chat => {
+        // A resumed connection (the read loop turns a failed Resume into a Connect): the room
+        // never saw it leave, so no notice — just re-seed the client and refresh the roster in
+        // case it came back in another room.
+        MessageType::Resume => {
+            broadcast_user_list(&app, &state, &message.room).await;
+            if let Some(requester) = auth_user_id {
+                send_identity(&state, requester).await;
+                push_unread(&state, &pool, requester).await;
+                push_rooms_update(&app, &state, &pool, requester).await;
+            }
+        }
+        MessageType::EmojiRequest => {
+            if let Some(requester) = auth_user_id {
+                custom_emoji::answer_request(&state, &pool, requester, &message.message).await;
+            }
+        }
+        MessageType::StickerRequest => {
+            if let Some(requester) = auth_user_id {
+                stickers::answer_request(&state, &pool, requester, &message.message).await;
+            }
+        }
+        MessageType::ProfileRequest => {
+            if let Some(requester) = auth_user_id {
+                contacts::answer_request(&state, &pool, requester, &message).await;
+            }
+        }
+        MessageType::GalleryRequest => {
+            if let Some(requester) = auth_user_id {
+                gallery::answer_request(&state, &pool, requester, &message).await;
+            }
+        }
+        MessageType::WakeRequest => {
+            if let Some(requester) = auth_user_id {
+                wake::send_token(&state, requester).await;
+            }
+        }
+        MessageType::Chat
+        | MessageType::Sticker
+        | MessageType::Code
+        | MessageType::Contact
+        | MessageType::Location => {
             // Enforce private-channel membership before persisting OR distributing: without
             // this, a peer could inject a message into a private room / DM history (or push it
             // to that room's members) with a crafted Chat frame, never having joined. Authorized
@@ -1546,34 +2777,83 @@ async fn handle_server_message(
                 );
                 return Ok(());
             }
+            // Announcement rooms and the like: only moderators may post.
+            if let Some(poster) = auth_user_id {
+                if let Err(e) = room_permissions::check(
+                    &pool,
+                    message.room_id as i64,
+                    Some(poster as i64),
+                    room_permissions::Action::Post,
+                )
+                .await
+                {
+                    send_read_only(&state, poster, &message, &e.to_string()).await;
+                    return Ok(());
+                }
+            }
+            // A sticker must be one of this host's; the name is taken from our copy.
+            let mut message = message;
+            if message.message_type == MessageType::Sticker {
+                match stickers::resolve_internal(&pool, &message.message).await {
+                    Some(sticker) => message.message = sticker,
+                    None => {
+                        if let Some(requester) = auth_user_id {
+                            send_error_notice(&state, requester, "That sticker isn't available")
+                                .await;
+                        }
+                        return Ok(());
+                    }
+                }
+            }
+            // Likewise a contact card is filled in from our directory, not the sender's say-so.
+            if message.message_type == MessageType::Contact {
+                match contacts::resolve_card(&pool, &message.message).await {
+                    Some(card) => message.message = card,
+                    None => {
+                        if let Some(requester) = auth_user_id {
+                            send_error_notice(
+                                &state,
+                                requester,
+                                "That person isn't in the directory",
+                            )
+                            .await;
+                        }
+                        return Ok(());
+                    }
+                }
+            }
             // Distribute first (live delivery to in-room clients), then persist and refresh
             // unread badges in a single task so the unread recompute sees the saved row.
-            distribute_message_to_all(&app, &state, &message.room, &message, Some(message.user_id))
-                .await;
+            reach::track(&state, &pool, &message, Some(actor)).await;
+            distribute_message_to_all(&app, &state, &message.room, &message, auth_conn_id).await;
 
             let pool_clone = pool.clone();
             let state_clone = Arc::clone(&state);
+            let store = state.message_store(&pool);
             let app_clone = app.clone();
             let room = message.room.clone();
             let room_id = message.room_id;
             let msg_clone = message.clone();
-            tauri::async_runtime::spawn(async move {
-                if let Err(e) = save_message_internal(
-                    &pool_clone,
-                    msg_clone.room_id as i64,
-                    msg_clone.user_id as i64,
-                    msg_clone.message,
-                    "Chat".to_string(),
-                    msg_clone.is_emoji,
-                    msg_clone.message_id,
-                )
-                .await
-                {
-                    tracing::error!("Failed to save chat message to db: {}", e);
-                    return;
+            tauri::async_runtime::spawn(
+                async move {
+                    if let Err(e) = store.save(&msg_clone).await {
+                        tracing::error!("Failed to save chat message to db: {}", e);
+                        return;
+                    }
+                    notify_unread_for_room(&app_clone, &state_clone, &pool_clone, &room, room_id)
+                        .await;
+                    crate::email_digest::queue_offline_notifications(
+                        &state_clone,
+                        &pool_clone,
+                        msg_clone.user_id,
+                        room_id,
+                        &msg_clone.message_id,
+                        &preview_text(&msg_clone),
+                    )
+                    .await;
                 }
-                notify_unread_for_room(&app_clone, &state_clone, &pool_clone, &room, room_id).await;
-            });
+                .in_current_span(),
+            );
         }
         MessageType::RoomJoin => {
             // Use the connection-bound id, never the (spoofable) one in the frame, so a
@@ -1590,10 +2870,22 @@ async fn handle_server_message(
                     message.room_id,
                     actor
                 );
+                // Not a member: ask the channel's moderators instead.
+                if let Some(requester) = auth_user_id {
+                    join_requests::handle_denied_join(
+                        &app,
+                        &state,
+                        &pool,
+                        requester,
+                        message.room_id,
+                    )
+                    .await;
+                }
                 return Ok(());
             }
             //Update client's room and room tracking
             let mut old_room: Option<String> = None;
+            let mut joined: Option<ClientConnection> = None;
             {
                 let mut server_streams_guard = state.server_streams.lock().await;
                 let mut room_clients_guard = state.room_clients.lock().await;
@@ -1616,27 +2908,30 @@ async fn handle_server_message(
                     if !room_vec.contains(&actor) {
                         room_vec.push(actor);
                     }
+                    joined = Some(client.clone());
                 }
             }
+            if let Some(conn) = &joined {
+                subscribe_to_room(&app, &state, conn, &message.room).await;
+            }
+            message.message = SystemEvent::MovedRoom {
+                user: message.username.clone(),
+                from: old_room.clone().unwrap_or_default(),
+                to: message.room.clone(),
+            }
+            .to_wire();
             //Save room join to db
 
-            let pool_clone = pool.clone();
+            let store = state.message_store(&pool);
             let msg_clone = message.clone();
-            tauri::async_runtime::spawn(async move {
-                if let Err(e) = save_message_internal(
-                    &pool_clone,
-                    msg_clone.room_id as i64,
-                    msg_clone.user_id as i64,
-                    msg_clone.message,
-                    "RoomJoin".to_string(),
-                    false,
-                    msg_clone.message_id,
-                )
-                .await
-                {
-                    tracing::error!("Failed to save room join message to db: {}", e);
+            tauri::async_runtime::spawn(
+                async move {
+                    if let Err(e) = store.save(&msg_clone).await {
+                        tracing::error!("Failed to save room join message to db: {}", e);
+                    }
                 }
-            });
+                .in_current_span(),
+            );
             distribute_message_to_all(&app, &state, &message.room, &message, None).await;
             broadcast_user_list(&app, &state, &message.room).await;
             if let Some(old) = old_room {
@@ -1661,11 +2956,24 @@ async fn handle_server_message(
                 let _ =
                     touch_last_read_internal(&pool, requester as i64, message.room_id as i64).await;
                 push_unread(&state, &pool, requester).await;
+                room_welcome::welcome(
+                    &state,
+                    &pool,
+                    requester,
+                    &message.username,
+                    &message.room,
+                    message.room_id,
+                )
+                .await;
             }
         }
         MessageType::RoomLeave => {
             // Remove the user from the room they are leaving so the host stops relaying
-            // that room to them, then tell the remaining members.
+            // that room to them, then tell the remaining members. The leaver is the
+            // connection's user, whatever the frame claims.
+            if let Some(uid) = auth_user_id {
+                message.user_id = uid;
+            }
             {
                 let mut streams = state.server_streams.lock().await;
                 let mut rooms = state.room_clients.lock().await;
@@ -1673,30 +2981,28 @@ async fn handle_server_message(
                 // disconnect doesn't broadcast a stale "left" into the room they left.
                 if let Some(conn) = streams.get_mut(&message.user_id) {
                     conn.current_room = String::new();
+                    stop_forwarder(conn);
                 }
                 if let Some(users) = rooms.get_mut(&message.room) {
                     users.retain(|&id| id != message.user_id);
                 }
             }
-            let pool_clone = pool.clone();
+            message.message = SystemEvent::LeftRoom {
+                user: message.username.clone(),
+                room: message.room.clone(),
+            }
+            .to_wire();
+            let store = state.message_store(&pool);
             let msg_clone = message.clone();
-            tauri::async_runtime::spawn(async move {
-                if let Err(e) = save_message_internal(
-                    &pool_clone,
-                    msg_clone.room_id as i64,
-                    msg_clone.user_id as i64,
-                    msg_clone.message,
-                    "RoomLeave".to_string(),
-                    false,
-                    msg_clone.message_id,
-                )
-                .await
-                {
-                    tracing::error!("Failed to save room leave message to db: {}", e);
+            tauri::async_runtime::spawn(
+                async move {
+                    if let Err(e) = store.save(&msg_clone).await {
+                        tracing::error!("Failed to save room leave message to db: {}", e);
+                    }
                 }
-            });
-            distribute_message_to_all(&app, &state, &message.room, &message, Some(message.user_id))
-                .await;
+                .in_current_span(),
+            );
+            distribute_message_to_all(&app, &state, &message.room, &message, auth_conn_id).await;
             broadcast_user_list(&app, &state, &message.room).await;
         }
         // Edit/Delete events use `message_id` as the TARGET message id. Authorize with
@@ -1714,12 +3020,15 @@ async fn handle_server_message(
         }
         MessageType::Delete => {
             let editor = auth_user_id.unwrap_or(message.user_id) as i64;
-            if let Ok(rows) = delete_message_db(&pool, &message.message_id, editor).await {
-                if rows > 0 {
+            match remove_message(&pool, &message.message_id, editor).await {
+                Ok(Some(unsent)) => {
                     let mut del = message.clone();
                     del.message = String::new();
+                    del.is_emoji = unsent;
                     distribute_message_to_all(&app, &state, &message.room, &del, None).await;
                 }
+                Ok(None) => {}
+                Err(e) => send_error_notice(&state, editor as u64, &e).await,
             }
         }
         // Reaction: message_id = TARGET, message = emoji, reactor = bound user_id. The
@@ -1736,11 +3045,55 @@ async fn handle_server_message(
                 distribute_message_to_all(&app, &state, &message.room, &evt, None).await;
             }
         }
-        // Typing is ephemeral: relay to the rest of the room (never persisted), keyed
-        // off the bound user_id so the sender is correctly excluded.
+        // Typing is ephemeral: relay to the rest of the room (never persisted), skipping the
+        // connection it came in on.
         MessageType::Typing => {
             let actor = auth_user_id.unwrap_or(message.user_id);
-            distribute_message_to_all(&app, &state, &message.room, &message, Some(actor)).await;
+            if message.is_emoji
+                && !user_settings::typing_allowed_for(&pool, actor, message.room_id).await
+            {
+                return Ok(());
+            }
+            distribute_message_to_all(&app, &state, &message.room, &message, auth_conn_id).await;
+        }
+        MessageType::Presence => {
+            if let Some(uid) = auth_user_id {
+                presence::handle_client_frame(&app, &state, uid, &message).await;
+            }
+        }
+        MessageType::Flag => {
+            if let Some(uid) = auth_user_id {
+                moderation::handle_client_frame(&app, &state, &pool, uid, &message).await;
+            }
+        }
+        MessageType::InviteRedeem => {
+            if let Some(uid) = auth_user_id {
+                invites::handle_client_frame(&app, &state, &pool, uid, &message).await;
+            }
+        }
+        MessageType::SettingsSync => {
+            if let Some(uid) = auth_user_id {
+                user_settings::handle_client_frame(&state, &pool, uid, &message).await;
+            }
+        }
+        MessageType::AttachmentOffer
+        | MessageType::AttachmentChunk
+        | MessageType::AttachmentRequest => {
+            if let Some(uid) = auth_user_id {
+                attachments::handle_client_frame(&app, &state, &pool, uid, &message).await;
+            }
+        }
+        MessageType::JoinDecision => {
+            if let Some(uid) = auth_user_id {
+                join_requests::handle_client_decision(&app, &state, &pool, uid, &message).await;
+            }
+        }
+        MessageType::DeliveryAck => {
+            if let Some(uid) = auth_user_id {
+                if let Err(e) = reach::acknowledge_internal(&pool, &message.message_id, uid).await {
+                    tracing::warn!("{}", e);
+                }
+            }
         }
         // Client wants an older page of a room; `message` carries the before_id cursor.
         // Reply (only to the authenticated requester) with a HistoryPage to prepend.
@@ -1777,6 +3130,17 @@ async fn handle_server_message(
         // connection's canonical id; the invited user (if online) is told to reload rooms.
         MessageType::AddMember => {
             if let Some(actor) = auth_user_id {
+                if let Err(e) = room_permissions::check(
+                    &pool,
+                    message.room_id as i64,
+                    Some(actor as i64),
+                    room_permissions::Action::Invite,
+                )
+                .await
+                {
+                    send_error_notice(&state, actor, &e.to_string()).await;
+                    return Ok(());
+                }
                 if let Ok(target) = message.message.parse::<i64>() {
                     if add_room_member_internal(&pool, message.room_id as i64, target, actor as i64)
                         .await
@@ -1842,6 +3206,7 @@ async fn handle_server_message(
                     .get("is_private")
                     .and_then(|x| x.as_bool())
                     .unwrap_or(false);
+                let announcement_only = v.get("announcement_only").and_then(|x| x.as_bool());
                 match create_room_internal(
                     &pool,
                     name,
@@ -1849,6 +3214,7 @@ async fn handle_server_message(
                     department_id,
                     Some(is_private),
                     Some(actor as i64),
+                    announcement_only,
                 )
                 .await
                 {
@@ -1873,6 +3239,58 @@ async fn handle_server_message(
     Ok(())
 }
 
+/// A room message as plain text, for places that can't show a sticker.
+fn preview_text(message: &Message) -> String {
+    match message.message_type {
+        MessageType::Sticker => stickers::preview(&message.message),
+        MessageType::Code => snippets::preview(message),
+        MessageType::Attachment => attachments::preview(&message.message),
+        MessageType::Contact => contacts::preview(&message.message),
+        MessageType::Location => location::preview(&message.message),
+        _ => message.message.clone(),
+    }
+}
+
+/// Fan a host-originated chat message out to its room, then persist it and refresh unread
+/// badges / email digests in the background. Shared by the host participant and ingest paths
+/// (webhooks) that post on the host's behalf.
+pub(crate) async fn publish_host_message<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    chat_message: Message,
+) {
+    // Distribute to everyone, no exclusions for server messages
+    reach::track(state, pool, &chat_message, None).await;
+    distribute_message_to_all(app, state, &chat_message.room, &chat_message, None).await;
+
+    let pool_clone = pool.clone();
+    let state_clone = Arc::clone(state);
+    let store = state.message_store(pool);
+    let app_clone = app.clone();
+    let room = chat_message.room.clone();
+    let room_id = chat_message.room_id;
+    tauri::async_runtime::spawn(
+        async move {
+            if let Err(e) = store.save(&chat_message).await {
+                tracing::error!("Failed to save host message to DB: {}", e);
+                return;
+            }
+            notify_unread_for_room(&app_clone, &state_clone, &pool_clone, &room, room_id).await;
+            crate::email_digest::queue_offline_notifications(
+                &state_clone,
+                &pool_clone,
+                chat_message.user_id,
+                room_id,
+                &chat_message.message_id,
+                &preview_text(&chat_message),
+            )
+            .await;
+        }
+        .in_current_span(),
+    );
+}
+
 // ENHANCED SEND FUNCTION - Server as Participant
 #[tauri::command(rename_all = "snake_case")]
 pub async fn send_as_server_participant(
@@ -1881,62 +3299,66 @@ pub async fn send_as_server_participant(
     db: State<'_, SqlitePool>,
     message: String,
     user_id: u64,
-    is_emoji: bool,
-) -> Result<(), String> {
+) -> AppResult<()> {
     if message.trim().is_empty() {
-        return Err("Message cannot be empty".to_string());
-    }
-    if message.chars().count() > MAX_MESSAGE_CHARS {
-        return Err(format!("Message exceeds {} characters", MAX_MESSAGE_CHARS));
+        return Err(AppError::Validation("Message cannot be empty".to_string()));
     }
+    // Shortcodes expanded and `is_emoji` decided here, whatever the UI sent.
+    let (message, is_emoji) = emoji::normalize(&message);
+    limits::check_length(
+        &message,
+        state
+            .max_message_chars
+            .load(std::sync::atomic::Ordering::Relaxed),
+    )?;
 
     let username = state.username.read().await.clone();
     let room = state.current_room.read().await.clone();
     let room_id = state.current_room_id.read().await.unwrap_or(1);
 
     let chat_message = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::Chat,
         username: username.clone(),
-        user_id,
-        message: message.clone(),
         room_id,
         room,
-        created_at: now_secs(),
         is_emoji,
-        email: None,
-        message_id: Uuid::new_v4().to_string(),
+        ..frame(MessageType::Chat, user_id, message.clone())
     };
 
-    // Save to database
-    // Distribute to everyone, no exclusions for server messages
-    distribute_message_to_all(&app, state.inner(), &chat_message.room, &chat_message, None).await;
+    publish_host_message(&app, state.inner(), db.inner(), chat_message).await;
+    Ok(())
+}
 
-    let pool_clone = db.inner().clone();
-    let state_clone = Arc::clone(state.inner());
-    let app_clone = app.clone();
-    let room = chat_message.room.clone();
-    let room_id = chat_message.room_id;
-    let msg_clone = chat_message.clone();
-    tauri::async_runtime::spawn(async move {
-        if let Err(e) = save_message_internal(
-            &pool_clone,
-            msg_clone.room_id as i64,
-            msg_clone.user_id as i64,
-            msg_clone.message,
-            "Chat".to_string(),
-            msg_clone.is_emoji,
-            msg_clone.message_id,
-        )
+/// Dial `host` and run the Noise initiator handshake. Shared by the primary connect and the
+/// extra-session connect; a wrong password surfaces as `AppError::Auth`.
+pub(crate) async fn open_secure_client(
+    host: &str,
+    password: &str,
+) -> AppResult<(
+    FramedReader<tokio::net::tcp::OwnedReadHalf>,
+    FramedWriter<tokio::net::tcp::OwnedWriteHalf>,
+    Transport,
+)> {
+    let stream = TcpStream::connect(host)
         .await
-        {
-            tracing::error!("Failed to save server message to DB: {}", e);
-            return;
-        }
-        notify_unread_for_room(&app_clone, &state_clone, &pool_clone, &room, room_id).await;
-    });
+        .map_err(|e| AppError::Network(format!("Failed to connect to {}: {}", host, e)))?;
+    set_tcp_keepalive(&stream);
 
-    Ok(())
+    let (mut reader, mut writer) = stream.into_split();
+
+    // Authenticate + establish encryption. A wrong password fails the handshake here.
+    let psk = secure::derive_psk(password);
+    let transport =
+        secure::initiator_handshake(&mut reader, &mut writer, &psk, &client_version::hello())
+            .await
+            .map_err(|e| {
+                AppError::Auth(format!("Secure handshake failed (wrong password?): {}", e))
+            })?;
+    tracing::info!("🔒 Secure session established with {}", host);
+    Ok((
+        FramedReader::new(reader),
+        FramedWriter::new(writer).with_timeout(WRITE_TIMEOUT),
+        transport,
+    ))
 }
 
 // CLIENT CONNECT FUNCTION - For external clients joining server
@@ -1952,21 +3374,13 @@ pub async fn client_connect_to_server(
     room: String,
     room_id: u64,
     password: String,
+    credential: Option<String>,
+    // The host's last ResumeToken, when reconnecting after a drop.
+    resume_token: Option<String>,
 ) -> AppResult<()> {
     tracing::info!("🔵 Client connecting to server at {}", host);
 
-    let stream = TcpStream::connect(&host)
-        .await
-        .map_err(|e| AppError::Network(format!("Failed to connect to {}: {}", host, e)))?;
-
-    let (mut reader, mut writer) = stream.into_split();
-
-    // Authenticate + establish encryption. A wrong password fails the handshake here.
-    let psk = secure::derive_psk(&password);
-    let transport = secure::initiator_handshake(&mut reader, &mut writer, &psk)
-        .await
-        .map_err(|e| AppError::Auth(format!("Secure handshake failed (wrong password?): {}", e)))?;
-    tracing::info!("🔒 Secure session established with {}", host);
+    let (reader, writer, transport) = open_secure_client(&host, &password).await?;
 
     // Update client state
     {
@@ -1987,19 +3401,25 @@ pub async fn client_connect_to_server(
         *guard = Some(transport);
     }
 
-    // Send the (encrypted) connect message now that the transport is stored.
+    // Send the (encrypted) connect message now that the transport is stored. With a resume
+    // token it's a Resume, so the host can pick up our parked connection.
+    let (message_type, text) = match resume_token {
+        Some(token) => (MessageType::Resume, token),
+        None => (
+            MessageType::Connect,
+            SystemEvent::Joined {
+                user: username.clone(),
+            }
+            .to_wire(),
+        ),
+    };
     let connect_message = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::Connect,
         username: username.clone(),
-        user_id,
-        message: format!("🔵 {} joined the chat", username),
         room: room.clone(),
         room_id,
-        created_at: now_secs(),
-        is_emoji: false,
         email: Some(email.clone()),
-        message_id: Uuid::new_v4().to_string(),
+        credential,
+        ..frame(message_type, user_id, text)
     };
     send_secure_client(state.inner(), &connect_message)
         .await
@@ -2027,11 +3447,38 @@ pub async fn client_connect_to_server(
             old.abort();
         }
     }
-    let listener =
-        start_client_listener(app, reader, Arc::clone(&state.client_transport), generation);
+    state
+        .clock_offset_ms
+        .store(CLOCK_OFFSET_UNSET, std::sync::atomic::Ordering::Relaxed);
+    *state.synced_settings.write().await = None;
+    state.client_max_message_chars.store(
+        state
+            .max_message_chars
+            .load(std::sync::atomic::Ordering::Relaxed),
+        std::sync::atomic::Ordering::Relaxed,
+    );
+    let listener = start_client_listener(
+        app,
+        reader,
+        Arc::clone(&state.client_transport),
+        generation,
+        None,
+        ListenerShared {
+            clock_offset_ms: Arc::clone(&state.clock_offset_ms),
+            max_message_chars: Arc::clone(&state.client_max_message_chars),
+            emitted_ids: Arc::clone(&state.emitted_ids),
+        },
+    );
     *state.client_listener.lock().await = Some(listener);
     let heartbeat = spawn_client_heartbeat(Arc::clone(&state.client_stream));
     *state.client_heartbeat.lock().await = Some(heartbeat);
+    presence::reannounce(state.inner()).await;
+    *state.client_host.write().await = Some(host.clone());
+    if let Some(pool) = state.pool.get() {
+        if let Err(e) = known_servers::record_connection(pool, &host, None, &room, room_id).await {
+            tracing::warn!("Couldn't remember {}: {}", host, e);
+        }
+    }
 
     tracing::info!("✅ Client connected successfully");
     Ok(())
@@ -2044,14 +3491,18 @@ pub async fn send_as_client(
     state: State<'_, Arc<AppState>>,
     message: String,
     user_id: u64,
-    is_emoji: bool,
-) -> Result<(), String> {
+) -> AppResult<()> {
     if message.trim().is_empty() {
-        return Err("Message cannot be empty".to_string());
-    }
-    if message.chars().count() > MAX_MESSAGE_CHARS {
-        return Err(format!("Message exceeds {} characters", MAX_MESSAGE_CHARS));
+        return Err(AppError::Validation("Message cannot be empty".to_string()));
     }
+    let (message, is_emoji) = emoji::normalize(&message);
+    // Checked against the host's limit (from its Identity frame), not our own.
+    limits::check_length(
+        &message,
+        state
+            .client_max_message_chars
+            .load(std::sync::atomic::Ordering::Relaxed),
+    )?;
 
     let username = state.username.read().await.clone();
     let room = state.current_room.read().await.clone();
@@ -2060,17 +3511,12 @@ pub async fn send_as_client(
     tracing::info!("🔵 Client sending: '{}'", message);
 
     let chat_message = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::Chat,
         username: username.clone(),
-        user_id,
-        message: message.clone(),
         room_id,
         room,
-        created_at: now_secs(),
+        created_at: server_now_secs(&state.clock_offset_ms),
         is_emoji,
-        email: None,
-        message_id: Uuid::new_v4().to_string(),
+        ..frame(MessageType::Chat, user_id, message.clone())
     };
 
     // Send to server over the encrypted channel.
@@ -2078,27 +3524,53 @@ pub async fn send_as_client(
         .await
         .map_err(|e| format!("Failed to send message to server: {}", e))?;
 
-    // Show in own UI immediately (don't wait for server echo)
-    if let Ok(payload) = serde_json::to_string(&chat_message) {
-        if let Err(e) = app.emit("message", payload) {
-            tracing::error!("Failed to emit own message to UI: {}", e);
-        }
+    // Show in own UI immediately (don't wait for server echo); the echo, if any, is dropped
+    // by the listener.
+    state
+        .emitted_ids
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(chat_message.message_id.clone());
+    if let Err(e) = message_events::emit(&app, &chat_message) {
+        tracing::error!("Failed to emit own message to UI: {}", e);
     }
 
     Ok(())
 }
 
+/// The connection state a client listener keeps up to date (or consults) for the senders.
+struct ListenerShared {
+    // Updated from the host's ServerAck frames; see next_clock_offset.
+    clock_offset_ms: Arc<std::sync::atomic::AtomicI64>,
+    // The host's message length limit, updated from its Identity frame.
+    max_message_chars: Arc<std::sync::atomic::AtomicUsize>,
+    // Our own sent messages, already shown; their echoes are dropped.
+    emitted_ids: Arc<std::sync::Mutex<EmittedIds>>,
+}
+
 fn start_client_listener(
     app: tauri::AppHandle,
-    mut reader: tokio::net::tcp::OwnedReadHalf,
-    transport: Arc<tokio::sync::Mutex<Option<TransportState>>>,
+    mut reader: FramedReader<tokio::net::tcp::OwnedReadHalf>,
+    transport: Arc<tokio::sync::Mutex<Option<Transport>>>,
     generation: u64,
+    // None for the primary connection (untagged `message` / `connection_lost` events); an
+    // extra session's id tags its events as `session_message` / `session_connection_lost`.
+    session_id: Option<String>,
+    shared: ListenerShared,
 ) -> tauri::async_runtime::JoinHandle<()> {
+    let ListenerShared {
+        clock_offset_ms,
+        max_message_chars,
+        emitted_ids,
+    } = shared;
     // Emit connection_lost only if THIS listener is still the active generation — a newer
     // connect bumps CLIENT_GENERATION, marking us stale so we don't trigger a reconnect on
-    // top of a healthy connection.
+    // top of a healthy connection. Sessions are aborted on teardown, so need no generation.
+    let lost_session = session_id.clone();
     let emit_lost = move |app: &tauri::AppHandle| {
-        if generation == CLIENT_GENERATION.load(std::sync::atomic::Ordering::SeqCst) {
+        if let Some(id) = &lost_session {
+            let _ = app.emit("session_connection_lost", id.clone());
+        } else if generation == CLIENT_GENERATION.load(std::sync::atomic::Ordering::SeqCst) {
             let _ = app.emit("connection_lost", ());
         } else {
             tracing::info!("Suppressed stale connection_lost (gen {})", generation);
@@ -2107,11 +3579,12 @@ fn start_client_listener(
     tauri::async_runtime::spawn(async move {
         tracing::info!("🎧 Client listener started");
 
+        let mut reassembler = secure::Reassembler::default();
         loop {
             // Same capped framing as the server path; each frame is then decrypted with
             // the shared client transport. A read-timeout (no frame, not even a keepalive)
             // means the host is gone → surface connection_lost so reconnect can kick in.
-            let framed = tokio::time::timeout(READ_TIMEOUT, read_frame(&mut reader)).await;
+            let framed = tokio::time::timeout(READ_TIMEOUT, reader.read_frame()).await;
             let ciphertext = match framed {
                 Err(_elapsed) => {
                     tracing::info!("⏱️  Client read timeout (host gone)");
@@ -2143,16 +3616,175 @@ fn start_client_listener(
                     }
                 }
             };
+            let plaintext = match reassembler.push(plaintext) {
+                Ok(Some(p)) => p,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::error!("Bad fragment from host: {}", e);
+                    break;
+                }
+            };
             match String::from_utf8(plaintext) {
                 Ok(message_str) => {
+                    // Clock samples are consumed here, not shown in the UI.
+                    if message_str.contains("\"ServerAck\"") {
+                        if let Ok(ack) = serde_json::from_str::<Message>(&message_str) {
+                            if let (MessageType::ServerAck, Ok(host_ms)) =
+                                (&ack.message_type, ack.message.parse::<i64>())
+                            {
+                                let sample = host_ms - now_millis();
+                                let prev =
+                                    clock_offset_ms.load(std::sync::atomic::Ordering::Relaxed);
+                                let next = next_clock_offset(prev, sample);
+                                clock_offset_ms.store(next, std::sync::atomic::Ordering::Relaxed);
+                                if prev == CLOCK_OFFSET_UNSET {
+                                    tracing::info!("🕒 Host clock offset: {} ms", next);
+                                }
+                                continue;
+                            }
+                        }
+                    }
+                    // The primary host's custom emoji + sticker sync and file transfers are
+                    // handled here, not by the UI. Their handlers keep one host's state on
+                    // AppState, so an extra session (chat only) drops these frames instead.
+                    if [
+                        "\"EmojiManifest\"",
+                        "\"EmojiData\"",
+                        "\"StickerManifest\"",
+                        "\"StickerData\"",
+                        "\"AttachmentAck\"",
+                        "\"AttachmentChunk\"",
+                        "\"SettingsSync\"",
+                        "\"Profile\"",
+                        "\"Gallery\"",
+                        "\"WakeToken\"",
+                    ]
+                    .iter()
+                    .any(|kind| message_str.contains(kind))
+                    {
+                        if let Ok(msg) = serde_json::from_str::<Message>(&message_str) {
+                            match msg.message_type {
+                                MessageType::EmojiManifest
+                                | MessageType::EmojiData
+                                | MessageType::StickerManifest
+                                | MessageType::StickerData
+                                | MessageType::AttachmentAck
+                                | MessageType::AttachmentChunk
+                                | MessageType::SettingsSync
+                                | MessageType::Profile
+                                | MessageType::Gallery
+                                | MessageType::WakeToken
+                                    if session_id.is_some() =>
+                                {
+                                    tracing::debug!(
+                                        "Session ignoring {:?}: not supported on extra sessions",
+                                        msg.message_type
+                                    );
+                                    continue;
+                                }
+                                MessageType::EmojiManifest | MessageType::EmojiData => {
+                                    custom_emoji::handle_host_frame(&app, &msg).await;
+                                    continue;
+                                }
+                                MessageType::StickerManifest | MessageType::StickerData => {
+                                    stickers::handle_host_frame(&app, &msg).await;
+                                    continue;
+                                }
+                                MessageType::AttachmentAck | MessageType::AttachmentChunk => {
+                                    attachments::handle_host_frame(&app, &msg).await;
+                                    continue;
+                                }
+                                MessageType::SettingsSync => {
+                                    user_settings::handle_host_frame(&app, &msg).await;
+                                    continue;
+                                }
+                                MessageType::Profile => {
+                                    if let Some(state) = app.try_state::<Arc<AppState>>() {
+                                        contacts::handle_host_frame(state.inner(), &msg).await;
+                                    }
+                                    continue;
+                                }
+                                MessageType::Gallery => {
+                                    if let Some(state) = app.try_state::<Arc<AppState>>() {
+                                        gallery::handle_host_frame(state.inner(), &msg).await;
+                                    }
+                                    continue;
+                                }
+                                MessageType::WakeToken => {
+                                    wake::handle_host_frame(&app, &msg).await;
+                                    continue;
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    // Learn the host's message limit, and drop chat frames that exceed it.
+                    // The envelope is longer than its text, so short frames skip the parse.
+                    let limit = max_message_chars.load(std::sync::atomic::Ordering::Relaxed);
+                    if message_str.len() > limit || message_str.contains("\"Identity\"") {
+                        if let Ok(msg) = serde_json::from_str::<Message>(&message_str) {
+                            match msg.message_type {
+                                MessageType::Identity => {
+                                    if let Ok(host_limit) = msg.message.parse::<usize>() {
+                                        max_message_chars.store(
+                                            host_limit,
+                                            std::sync::atomic::Ordering::Relaxed,
+                                        );
+                                    }
+                                }
+                                MessageType::Chat | MessageType::Edit | MessageType::Code
+                                    if limits::check_length(&msg.message, limit).is_err() =>
+                                {
+                                    tracing::warn!(
+                                        "Dropping oversized {:?} from host ({} chars)",
+                                        msg.message_type,
+                                        msg.message.chars().count()
+                                    );
+                                    continue;
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    // Our own chat message, already shown when we sent it.
+                    let echoed = {
+                        let mut emitted = emitted_ids.lock().unwrap_or_else(|e| e.into_inner());
+                        !emitted.is_empty()
+                            && serde_json::from_str::<Message>(&message_str).is_ok_and(|m| {
+                                m.message_type == MessageType::Chat && emitted.take(&m.message_id)
+                            })
+                    };
+                    if echoed {
+                        continue;
+                    }
                     tracing::info!("🎧 Client received: {}", message_str);
-                    if let Err(e) = app.emit("message", message_str) {
+                    if session_id.is_none() {
+                        reach::acknowledge(&app, &message_str).await;
+                    }
+                    let emitted = match &session_id {
+                        Some(id) => app.emit(
+                            "session_message",
+                            SessionEvent {
+                                session_id: id.clone(),
+                                payload: message_str,
+                            },
+                        ),
+                        None => message_events::emit_raw(&app, message_str),
+                    };
+                    if let Err(e) = emitted {
                         tracing::error!("Failed to emit received message: {}", e);
                     }
                 }
                 Err(e) => tracing::error!("🔒 Invalid UTF-8 after decrypt: {}", e),
             }
         }
+        // An extra session whose connection ended is over: forget it, as clean_client does a
+        // server connection, rather than leave it in `sessions` for good.
+        if let Some(id) = &session_id {
+            if let Some(state) = app.try_state::<Arc<AppState>>() {
+                forget_session(state.inner(), id).await;
+            }
+        }
     })
 }
 
@@ -2173,18 +3805,17 @@ pub async fn server_participant_join_room(
     let username = state.username.read().await.clone();
 
     //create room join message
+    let text = SystemEvent::MovedRoom {
+        user: username.clone(),
+        from: old_room.clone(),
+        to: new_room.clone(),
+    }
+    .to_wire();
     let room_join_msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::RoomJoin,
         username: username.clone(),
-        user_id,
-        message: format!("📍 {} moved from {} to {}", username, old_room, new_room),
         room: new_room.clone(),
         room_id: new_room_id,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
-        message_id: Uuid::new_v4().to_string(),
+        ..frame(MessageType::RoomJoin, user_id, text)
     };
 
     {
@@ -2208,20 +3839,10 @@ pub async fn server_participant_join_room(
     }
 
     // Save to database
-    let pool_clone = db.inner().clone();
+    let store = state.message_store(db.inner());
     let msg_clone = room_join_msg.clone();
     tauri::async_runtime::spawn(async move {
-        if let Err(e) = save_message_internal(
-            &pool_clone,
-            msg_clone.room_id as i64,
-            msg_clone.user_id as i64,
-            msg_clone.message,
-            "RoomJoin".to_string(),
-            false,
-            msg_clone.message_id,
-        )
-        .await
-        {
+        if let Err(e) = store.save(&msg_clone).await {
             tracing::error!("Failed to save room join: {}", e);
         }
     });
@@ -2252,20 +3873,24 @@ pub async fn client_join_room(
         *state.current_room.write().await = new_room.clone();
         *state.current_room_id.write().await = Some(new_room_id);
     }
+    if let (Some(host), Some(pool)) = (state.client_host.read().await.clone(), state.pool.get()) {
+        if let Err(e) = known_servers::record_room(pool, &host, &new_room, new_room_id).await {
+            tracing::warn!("Couldn't remember the room on {}: {}", host, e);
+        }
+    }
 
     // Send room join to server (server will handle the room tracking update)
+    let text = SystemEvent::MovedRoom {
+        user: username.clone(),
+        from: old_room.clone(),
+        to: new_room.clone(),
+    }
+    .to_wire();
     let room_join_msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::RoomJoin,
         username: username.clone(),
-        user_id,
-        message: format!("📍 {} moved from {} to {}", username, old_room, new_room),
         room: new_room.clone(),
         room_id: new_room_id,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
-        message_id: Uuid::new_v4().to_string(),
+        ..frame(MessageType::RoomJoin, user_id, text)
     };
 
     // Send room join to server over the encrypted channel.
@@ -2292,18 +3917,16 @@ pub async fn client_leave_room(
     room_id: u64,
 ) -> Result<(), String> {
     let username = state.username.read().await.clone();
+    let text = SystemEvent::LeftRoom {
+        user: username.clone(),
+        room: room.clone(),
+    }
+    .to_wire();
     let leave_msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::RoomLeave,
         username: username.clone(),
-        user_id,
-        message: format!("👋 {} left {}", username, room),
         room: room.clone(),
         room_id,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
-        message_id: Uuid::new_v4().to_string(),
+        ..frame(MessageType::RoomLeave, user_id, text)
     };
     send_secure_client(state.inner(), &leave_msg)
         .await
@@ -2321,19 +3944,29 @@ pub async fn server_leave_room(
     room: String,
     room_id: u64,
 ) -> Result<(), String> {
+    host_leave_room(&app, state.inner(), db.inner(), user_id, room, room_id).await;
+    Ok(())
+}
+
+async fn host_leave_room(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    user_id: u64,
+    room: String,
+    room_id: u64,
+) {
     let username = state.username.read().await.clone();
+    let text = SystemEvent::LeftRoom {
+        user: username.clone(),
+        room: room.clone(),
+    }
+    .to_wire();
     let leave_msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::RoomLeave,
         username: username.clone(),
-        user_id,
-        message: format!("👋 {} left {}", username, room),
         room: room.clone(),
         room_id,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
-        message_id: Uuid::new_v4().to_string(),
+        ..frame(MessageType::RoomLeave, user_id, text)
     };
 
     {
@@ -2343,26 +3976,64 @@ pub async fn server_leave_room(
         }
     }
 
-    let pool_clone = db.inner().clone();
+    let store = state.message_store(pool);
     let msg_clone = leave_msg.clone();
     tauri::async_runtime::spawn(async move {
-        if let Err(e) = save_message_internal(
-            &pool_clone,
-            msg_clone.room_id as i64,
-            msg_clone.user_id as i64,
-            msg_clone.message,
-            "RoomLeave".to_string(),
-            false,
-            msg_clone.message_id,
-        )
-        .await
-        {
+        if let Err(e) = store.save(&msg_clone).await {
             tracing::error!("Failed to save room leave: {}", e);
         }
     });
 
-    distribute_message_to_all(&app, state.inner(), &room, &leave_msg, Some(user_id)).await;
-    broadcast_user_list(&app, state.inner(), &room).await;
+    distribute_message_to_all(app, state, &room, &leave_msg, None).await;
+    broadcast_user_list(app, state, &room).await;
+}
+
+/// Leave a room: the membership is marked inactive, and if we're in the room on the network
+/// the room hears about it too. Hosting, that's a local RoomLeave; as a client, a RoomLeave to
+/// the host (which stops relaying the room to us). Offline (or already out of the room on
+/// the network), it's only the membership.
+#[tauri::command]
+pub async fn leave_room(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    user_id: i64,
+    room_id: i64,
+) -> Result<(), String> {
+    leave_room_internal(&db, user_id, room_id).await?;
+
+    if *state.is_server.read().await {
+        let room: Option<String> = sqlx::query_scalar("SELECT name FROM chat_rooms WHERE id = $1")
+            .bind(room_id)
+            .fetch_optional(db.inner())
+            .await
+            .map_err(|e| format!("Failed to leave room: {}", e))?;
+        let Some(room) = room else { return Ok(()) };
+        let present = state
+            .room_clients
+            .lock()
+            .await
+            .get(&room)
+            .is_some_and(|ids| ids.contains(&(user_id as u64)));
+        if present {
+            host_leave_room(
+                &app,
+                state.inner(),
+                db.inner(),
+                user_id as u64,
+                room,
+                room_id as u64,
+            )
+            .await;
+        }
+    } else if *state.current_room_id.read().await == Some(room_id as u64) {
+        let room = state.current_room.read().await.clone();
+        if let Err(e) = client_leave_room(state.clone(), user_id as u64, room, room_id as u64).await
+        {
+            // Not connected: nobody to tell.
+            tracing::debug!("Room leave not sent: {}", e);
+        }
+    }
     Ok(())
 }
 
@@ -2371,7 +4042,7 @@ pub async fn server_leave_room(
 // event to the host (which applies + broadcasts via handle_server_message); the host
 // participant applies + broadcasts directly.
 
-fn edit_event(
+pub(crate) fn edit_event(
     username: String,
     user_id: u64,
     target_id: String,
@@ -2381,17 +4052,11 @@ fn edit_event(
     kind: MessageType,
 ) -> Message {
     Message {
-        version: PROTOCOL_VERSION,
-        message_type: kind,
         username,
-        user_id,
-        message: text,
         message_id: target_id,
         room,
         room_id,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
+        ..frame(kind, user_id, text)
     }
 }
 
@@ -2407,6 +4072,14 @@ pub async fn client_edit_message(
     if new_text.trim().is_empty() {
         return Err("Message cannot be empty".to_string());
     }
+    let new_text = emoji::normalize(&new_text).0;
+    limits::check_length(
+        &new_text,
+        state
+            .client_max_message_chars
+            .load(std::sync::atomic::Ordering::Relaxed),
+    )
+    .map_err(|e| e.to_string())?;
     let username = state.username.read().await.clone();
     let msg = edit_event(
         username,
@@ -2437,6 +4110,14 @@ pub async fn server_edit_message(
     if new_text.trim().is_empty() {
         return Err("Message cannot be empty".to_string());
     }
+    let new_text = emoji::normalize(&new_text).0;
+    limits::check_length(
+        &new_text,
+        state
+            .max_message_chars
+            .load(std::sync::atomic::Ordering::Relaxed),
+    )
+    .map_err(|e| e.to_string())?;
     let rows = edit_message_db(db.inner(), &target_id, &new_text, user_id as i64).await?;
     if rows == 0 {
         return Err("You can only edit your own messages".to_string());
@@ -2488,12 +4169,11 @@ pub async fn server_delete_message(
     room: String,
     room_id: u64,
 ) -> Result<(), String> {
-    let rows = delete_message_db(db.inner(), &target_id, user_id as i64).await?;
-    if rows == 0 {
+    let Some(unsent) = remove_message(db.inner(), &target_id, user_id as i64).await? else {
         return Err("You can only delete your own messages".to_string());
-    }
+    };
     let username = state.username.read().await.clone();
-    let msg = edit_event(
+    let mut msg = edit_event(
         username,
         user_id,
         target_id,
@@ -2502,10 +4182,28 @@ pub async fn server_delete_message(
         room_id,
         MessageType::Delete,
     );
+    msg.is_emoji = unsent;
     distribute_message_to_all(&app, state.inner(), &room, &msg, None).await;
     Ok(())
 }
 
+/// Apply `user_id`'s delete on the host: inside the unsend window the message is removed
+/// outright, after it tombstoned. Some(true) = unsent, Some(false) = tombstoned, None = nothing
+/// the user may delete. The Delete frame broadcast for it carries the first case in `is_emoji`,
+/// so clients drop the message rather than show "message deleted".
+async fn remove_message(
+    pool: &SqlitePool,
+    message_id: &str,
+    user_id: i64,
+) -> Result<Option<bool>, String> {
+    let window = limits::unsend_window_secs(pool).await;
+    if unsend_message_db(pool, message_id, user_id, window).await? > 0 {
+        return Ok(Some(true));
+    }
+    let rows = delete_message_db(pool, message_id, user_id).await?;
+    Ok((rows > 0).then_some(false))
+}
+
 // ---- Emoji reactions ----
 // A client sends the toggle to the host (which applies + broadcasts the result via
 // handle_server_message); the host participant applies + broadcasts directly.
@@ -2572,6 +4270,13 @@ pub async fn client_typing(
     room_id: u64,
     typing: bool,
 ) -> Result<(), String> {
+    let allowed = {
+        let settings = state.synced_settings.read().await;
+        user_settings::typing_allowed(settings.as_ref(), room_id)
+    };
+    if typing && !allowed {
+        return Ok(());
+    }
     let username = state.username.read().await.clone();
     let mut msg = edit_event(
         username,
@@ -2597,17 +4302,9 @@ pub async fn request_history(
     before_id: i64,
 ) -> Result<(), String> {
     let msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::HistoryRequest,
-        username: String::new(),
-        user_id: 0,
-        message: before_id.to_string(),
-        message_id: Uuid::new_v4().to_string(),
         room,
         room_id,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
+        ..frame(MessageType::HistoryRequest, 0, before_id.to_string())
     };
     send_secure_client(state.inner(), &msg)
         .await
@@ -2623,17 +4320,8 @@ pub async fn client_add_member(
     target_id: i64,
 ) -> Result<(), String> {
     let msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::AddMember,
-        username: String::new(),
-        user_id: 0,
-        message: target_id.to_string(),
-        message_id: Uuid::new_v4().to_string(),
-        room: String::new(),
         room_id,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
+        ..frame(MessageType::AddMember, 0, target_id.to_string())
     };
     send_secure_client(state.inner(), &msg)
         .await
@@ -2648,19 +4336,7 @@ pub async fn client_create_dm(
     target_ids: Vec<i64>,
 ) -> Result<(), String> {
     let payload = serde_json::to_string(&target_ids).unwrap_or_else(|_| "[]".to_string());
-    let msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::DmRequest,
-        username: String::new(),
-        user_id: 0,
-        message: payload,
-        message_id: Uuid::new_v4().to_string(),
-        room: String::new(),
-        room_id: 0,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
-    };
+    let msg = frame(MessageType::DmRequest, 0, payload);
     send_secure_client(state.inner(), &msg)
         .await
         .map_err(|e| format!("Failed to start direct message: {}", e))
@@ -2675,27 +4351,17 @@ pub async fn client_create_room(
     description: Option<String>,
     department_id: Option<i64>,
     is_private: bool,
+    announcement_only: bool,
 ) -> Result<(), String> {
     let payload = serde_json::json!({
         "name": name,
         "description": description,
         "department_id": department_id,
         "is_private": is_private,
+        "announcement_only": announcement_only,
     })
     .to_string();
-    let msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::RoomCreate,
-        username: String::new(),
-        user_id: 0,
-        message: payload,
-        message_id: Uuid::new_v4().to_string(),
-        room: String::new(),
-        room_id: 0,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
-    };
+    let msg = frame(MessageType::RoomCreate, 0, payload);
     send_secure_client(state.inner(), &msg)
         .await
         .map_err(|e| format!("Failed to create channel: {}", e))
@@ -2713,6 +4379,7 @@ pub async fn server_create_room(
     description: Option<String>,
     department_id: Option<i64>,
     is_private: bool,
+    announcement_only: bool,
     actor_id: i64,
 ) -> AppResult<ChatRoom> {
     let room = create_room_internal(
@@ -2722,6 +4389,7 @@ pub async fn server_create_room(
         department_id,
         Some(is_private),
         Some(actor_id),
+        Some(announcement_only),
     )
     .await?;
     if !is_private {
@@ -2730,6 +4398,23 @@ pub async fn server_create_room(
     Ok(room)
 }
 
+/// Host flags a room as auto-join (or not). Turning it on backfills matching users, and every
+/// connected client gets a fresh room list so the room appears without a reconnect.
+#[tauri::command]
+pub async fn server_set_room_auto_join(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    room_id: i64,
+    enabled: bool,
+) -> AppResult<u64> {
+    let added = set_room_auto_join_internal(db.inner(), room_id, enabled).await?;
+    if added > 0 {
+        broadcast_room_list(&app, state.inner(), db.inner()).await;
+    }
+    Ok(added)
+}
+
 /// Host participant invites a user directly against its own DB, then pushes the invitee their
 /// updated room list so the channel appears for them.
 #[tauri::command]
@@ -2774,12 +4459,13 @@ pub async fn server_create_dm(
     Ok(room)
 }
 
-/// Host participant typing: relay straight to the room's clients (and the local UI
-/// ignores its own via the sender's user_id).
+/// Host participant typing: relay straight to the room's clients (the local UI ignores its own
+/// via the sender's user_id).
 #[tauri::command]
 pub async fn server_typing(
     app: tauri::AppHandle,
     state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
     user_id: u64,
     room: String,
     room_id: u64,
@@ -2791,15 +4477,30 @@ pub async fn server_typing(
         user_id,
         String::new(),
         String::new(),
-        room.clone(),
+        room,
         room_id,
         MessageType::Typing,
     );
     msg.is_emoji = typing;
-    distribute_message_to_all(&app, state.inner(), &room, &msg, Some(user_id)).await;
+    handle_participant_frame(&app, state.inner(), db.inner(), msg).await;
     Ok(())
 }
 
+/// Host clock minus this client's, in ms, as last measured on the primary connection (None
+/// before the first ServerAck). The UI can add it to `Date.now()` for host-relative "now".
+#[tauri::command]
+pub async fn get_clock_offset(state: State<'_, Arc<AppState>>) -> Result<Option<i64>, String> {
+    Ok(
+        match state
+            .clock_offset_ms
+            .load(std::sync::atomic::Ordering::Relaxed)
+        {
+            CLOCK_OFFSET_UNSET => None,
+            offset => Some(offset),
+        },
+    )
+}
+
 #[tauri::command]
 pub async fn get_server_info(state: State<'_, Arc<AppState>>) -> Result<Option<String>, String> {
     let addr = state.server_addr.read().await.map(|addr| addr.to_string());
@@ -2808,40 +4509,89 @@ pub async fn get_server_info(state: State<'_, Arc<AppState>>) -> Result<Option<S
 
 /// Encrypt and send a message to one peer over its Noise transport. The transport
 /// lock is held across encrypt + write so Noise nonces always reach the wire in order
-/// (out-of-order frames would fail to decrypt).
-async fn send_secure(
-    writer: &Arc<tokio::sync::Mutex<tokio::net::tcp::OwnedWriteHalf>>,
-    transport: &Arc<tokio::sync::Mutex<TransportState>>,
+/// (out-of-order frames would fail to decrypt). A large payload goes one fragment per
+/// lock, so other frames to the same peer aren't stuck behind it, and file chunks yield
+/// to everything else (see PeerWriter).
+pub(crate) async fn send_secure(
+    writer: &Arc<PeerWriter<FramedWriter<tokio::net::tcp::OwnedWriteHalf>>>,
+    transport: &Arc<tokio::sync::Mutex<Transport>>,
     message: &Message,
 ) -> Result<(), String> {
-    let payload = serde_json::to_string(message).map_err(|e| e.to_string())?;
-    let mut ts = transport.lock().await;
-    let ciphertext = secure::encrypt(&mut ts, payload.as_bytes())?;
-    let mut w = writer.lock().await;
-    w.write_all(&(ciphertext.len() as u32).to_be_bytes())
-        .await
-        .map_err(|e| e.to_string())?;
-    w.write_all(&ciphertext).await.map_err(|e| e.to_string())?;
+    let payload = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+    let codec = transport.lock().await.codec;
+    let pieces = secure::fragment(codec, payload);
+    let fragmented = pieces.len() > 1;
+    for piece in pieces {
+        {
+            let _lane = lane(writer, message).await;
+            let mut ts = transport.lock().await;
+            let ciphertext = secure::encrypt(&mut ts, &piece)?;
+            let mut w = writer.lock().await;
+            w.write_frame(&ciphertext)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        if fragmented {
+            tokio::task::yield_now().await;
+        }
+    }
     Ok(())
 }
 
+/// Send one registered client a frame; a no-op if it isn't connected.
+pub(crate) async fn send_to_client(state: &Arc<AppState>, user_id: u64, message: &Message) {
+    let conn = {
+        let streams = state.server_streams.lock().await;
+        streams
+            .get(&user_id)
+            .map(|c| (Arc::clone(&c.writer), Arc::clone(&c.transport)))
+    };
+    if let Some((writer, transport)) = conn {
+        let _ = send_secure(&writer, &transport, message).await;
+    }
+}
+
 /// Client-side equivalent: encrypt and send to the server over the single client
 /// transport. Locks transport then writer (consistent order) to keep nonces ordered.
-async fn send_secure_client(state: &Arc<AppState>, message: &Message) -> Result<(), String> {
-    let payload = serde_json::to_string(message).map_err(|e| e.to_string())?;
-    let mut ts_guard = state.client_transport.lock().await;
-    let ts = ts_guard
-        .as_mut()
-        .ok_or_else(|| "Not connected (no secure session)".to_string())?;
-    let ciphertext = secure::encrypt(ts, payload.as_bytes())?;
-    let mut w_guard = state.client_stream.lock().await;
-    let w = w_guard
-        .as_mut()
-        .ok_or_else(|| "Not connected to server".to_string())?;
-    w.write_all(&(ciphertext.len() as u32).to_be_bytes())
-        .await
-        .map_err(|e| e.to_string())?;
-    w.write_all(&ciphertext).await.map_err(|e| e.to_string())?;
+pub(crate) async fn send_secure_client(
+    state: &Arc<AppState>,
+    message: &Message,
+) -> Result<(), String> {
+    send_secure_optional(&state.client_stream, &state.client_transport, message).await
+}
+
+/// Encrypt and send over a client-side writer/transport pair that may be torn down (the primary
+/// connection's AppState fields, or an extra session's). Same lock order and lanes as the host
+/// path.
+async fn send_secure_optional(
+    stream: &PeerWriter<Option<FramedWriter<tokio::net::tcp::OwnedWriteHalf>>>,
+    transport: &tokio::sync::Mutex<Option<Transport>>,
+    message: &Message,
+) -> Result<(), String> {
+    let payload = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+    let codec = transport.lock().await.as_ref().and_then(|ts| ts.codec);
+    let pieces = secure::fragment(codec, payload);
+    let fragmented = pieces.len() > 1;
+    for piece in pieces {
+        {
+            let _lane = lane(stream, message).await;
+            let mut ts_guard = transport.lock().await;
+            let ts = ts_guard
+                .as_mut()
+                .ok_or_else(|| "Not connected (no secure session)".to_string())?;
+            let ciphertext = secure::encrypt(ts, &piece)?;
+            let mut w_guard = stream.lock().await;
+            let w = w_guard
+                .as_mut()
+                .ok_or_else(|| "Not connected to server".to_string())?;
+            w.write_frame(&ciphertext)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        if fragmented {
+            tokio::task::yield_now().await;
+        }
+    }
     Ok(())
 }
 
@@ -2855,20 +4605,18 @@ pub async fn client_disconnect(
     let username = { state.username.read().await.clone() };
     let room = { state.current_room.read().await.clone() };
     let room_id_opt = { *state.current_room_id.read().await };
+    *state.client_host.write().await = None;
 
     // Best-effort: send an (encrypted) Disconnect to the server, then drop the session.
     let disconnect_msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::Disconnect,
         username: username.clone(),
-        user_id: user_id_opt.unwrap_or(0),
-        message: "client disconnect".to_string(),
-        message_id: Uuid::new_v4().to_string(),
         room: room.clone(),
         room_id: room_id_opt.unwrap_or(0),
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
+        ..frame(
+            MessageType::Disconnect,
+            user_id_opt.unwrap_or(0),
+            "client disconnect".to_string(),
+        )
     };
     let _ = send_secure_client(state.inner(), &disconnect_msg).await;
     {
@@ -2911,6 +4659,103 @@ pub async fn client_disconnect(
     Ok(())
 }
 
+/// Client: give up on the primary connection without a goodbye (the network it ran over is
+/// gone), and report it lost so the UI reconnects straight away. A no-op when not connected.
+pub(crate) async fn abandon_client_connection(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    reason: &str,
+) {
+    if state.client_stream.lock().await.take().is_none() {
+        return;
+    }
+    state.client_transport.lock().await.take();
+    if let Some(handle) = state.client_listener.lock().await.take() {
+        handle.abort();
+    }
+    stop_client_heartbeat(state).await;
+    tracing::info!("🔌 Dropped the connection to the host: {}", reason);
+    let _ = app.emit("connection_lost", ());
+}
+
+/// Client: pause the primary connection's keepalive (the app is in the background).
+pub(crate) async fn stop_client_heartbeat(state: &Arc<AppState>) {
+    if let Some(handle) = state.client_heartbeat.lock().await.take() {
+        handle.abort();
+    }
+}
+
+/// Client: start the keepalive again, if still connected. The first beat goes out at once.
+#[cfg(mobile)]
+pub(crate) async fn restart_client_heartbeat(state: &Arc<AppState>) {
+    if state.client_stream.lock().await.is_none() {
+        return;
+    }
+    let mut guard = state.client_heartbeat.lock().await;
+    if let Some(old) = guard.take() {
+        old.abort();
+    }
+    *guard = Some(spawn_client_heartbeat(Arc::clone(&state.client_stream)));
+}
+
+/// Host: close every client connection (keeping the host itself up), after sending each one
+/// `notice`. Parked connections are let go too, and the rosters shrink to just the host.
+pub(crate) async fn drop_all_clients(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    notice: &Message,
+) {
+    let dropped: Vec<(u64, String, ClientLink)> = {
+        let mut guard = state.server_streams.lock().await;
+        guard
+            .drain()
+            .map(|(uid, c)| {
+                stop_forwarder(&c);
+                let link = (Arc::clone(&c.writer), Arc::clone(&c.transport));
+                (uid, c.username.clone(), link)
+            })
+            .collect()
+    };
+    for (_, _, (writer, transport)) in &dropped {
+        let _ = send_secure(writer, transport, notice).await;
+        let _ = writer.lock().await.shutdown().await;
+    }
+    let parked: Vec<ParkedConnection> = state.parked.lock().await.drain().map(|(_, p)| p).collect();
+    for p in &parked {
+        p.expiry.abort();
+    }
+    if let Some(pool) = state.pool.get() {
+        let gone = dropped
+            .iter()
+            .map(|(uid, name, _)| (*uid, name.as_str()))
+            .chain(
+                parked
+                    .iter()
+                    .map(|p| (p.conn.user_id, p.conn.username.as_str())),
+            );
+        for (uid, name) in gone {
+            presence::departed(app, state, pool, uid, name).await;
+        }
+    }
+    let host_id = *state.user_id.read().await;
+    let rooms: Vec<String> = {
+        let mut rooms = state.room_clients.lock().await;
+        let mut changed = Vec::new();
+        for (room, ids) in rooms.iter_mut() {
+            let before = ids.len();
+            ids.retain(|&id| Some(id) == host_id);
+            if ids.len() != before {
+                changed.push(room.clone());
+            }
+        }
+        changed
+    };
+    for room in rooms {
+        broadcast_user_list(app, state, &room).await;
+    }
+    tracing::info!("🚧 Disconnected {} client(s)", dropped.len());
+}
+
 #[tauri::command]
 pub async fn server_participant_disconnect(
     app: tauri::AppHandle,
@@ -2924,17 +4769,14 @@ pub async fn server_participant_disconnect(
 
     // Prepare a disconnect message from the host
     let disconnect_msg = Message {
-        version: PROTOCOL_VERSION,
-        message_type: MessageType::Disconnect,
         username: host_username.clone(),
-        user_id: host_user_id,
-        message: "server_participant_disconnect".to_string(),
-        message_id: Uuid::new_v4().to_string(),
         room: host_room.clone(),
         room_id: host_room_id,
-        created_at: now_secs(),
-        is_emoji: false,
-        email: None,
+        ..frame(
+            MessageType::Disconnect,
+            host_user_id,
+            "server_participant_disconnect".to_string(),
+        )
     };
 
     // Best-effort: send an encrypted disconnect notice to each client, then drop them.
@@ -2944,7 +4786,10 @@ pub async fn server_participant_disconnect(
         let mut guard = state.server_streams.lock().await;
         let snapshot = guard
             .values()
-            .map(|c| (Arc::clone(&c.writer), Arc::clone(&c.transport)))
+            .map(|c| {
+                stop_forwarder(c);
+                (Arc::clone(&c.writer), Arc::clone(&c.transport))
+            })
             .collect();
         // Dropping the ClientConnections closes their write halves.
         guard.clear();
@@ -2953,21 +4798,29 @@ pub async fn server_participant_disconnect(
     for (writer, transport) in &targets {
         let _ = send_secure(writer, transport, &disconnect_msg).await;
     }
+    maintenance::clear(&state);
+    port_mapping::stop(&state);
     // Stop the LAN discovery responder so udp/3626 frees for a future host session.
     if let Some(handle) = state.discovery_responder.lock().await.take() {
         handle.abort();
     }
+    server_events::stop(&state).await;
     // Stop advertising over mDNS.
     if let Ok(mut guard) = state.mdns.lock() {
         if let Some(daemon) = guard.take() {
             let _ = daemon.shutdown();
         }
     }
-    // Clear room->clients index
+    // Clear room->clients index and the room channels, and forget parked connections.
     {
         let mut rooms = state.room_clients.lock().await;
         rooms.clear();
     }
+    for (_, parked) in state.parked.lock().await.drain() {
+        parked.expiry.abort();
+    }
+    state.room_channels.lock().await.clear();
+    state.wake_tokens.lock().await.clear();
     // Also clear any client-mode writer/transport if present (host may have connected out).
     {
         let mut client_w = state.client_stream.lock().await;
@@ -2993,6 +4846,281 @@ pub async fn server_participant_disconnect(
     Ok(())
 }
 
+// ---- Additional server sessions (multiple workspaces) ----
+// The primary connection (host mode, or the first joined server) keeps using the commands
+// above. Each extra joined server is a ClientSession in `state.sessions`, driven by these
+// session-scoped variants; its frames are emitted as `session_message` tagged with its id.
+// Sessions join servers only and carry chat, room switches and rosters (see ClientSession).
+
+async fn get_session(state: &Arc<AppState>, session_id: &str) -> AppResult<Arc<ClientSession>> {
+    state
+        .sessions
+        .lock()
+        .await
+        .get(session_id)
+        .cloned()
+        .ok_or_else(|| AppError::Validation(format!("Unknown session {}", session_id)))
+}
+
+/// Open a session to `host`: connect, send the Connect frame and add it to `state.sessions`.
+/// Returns it with the read half its listener will drain.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn open_session(
+    state: &Arc<AppState>,
+    host: String,
+    username: String,
+    user_id: u64,
+    email: String,
+    room: String,
+    room_id: u64,
+    password: &str,
+    credential: Option<String>,
+) -> AppResult<(
+    Arc<ClientSession>,
+    FramedReader<tokio::net::tcp::OwnedReadHalf>,
+)> {
+    let (reader, writer, transport) = open_secure_client(&host, password).await?;
+
+    let session_id = Uuid::new_v4().to_string();
+    let session = Arc::new(ClientSession {
+        session_id: session_id.clone(),
+        host,
+        username: username.clone(),
+        user_id,
+        current_room: tokio::sync::RwLock::new(room.clone()),
+        current_room_id: tokio::sync::RwLock::new(room_id),
+        stream: Arc::new(PeerWriter::new(Some(writer))),
+        transport: Arc::new(tokio::sync::Mutex::new(Some(transport))),
+        listener: tokio::sync::Mutex::new(None),
+        heartbeat: tokio::sync::Mutex::new(None),
+        clock_offset_ms: Arc::new(std::sync::atomic::AtomicI64::new(CLOCK_OFFSET_UNSET)),
+        emitted_ids: Arc::new(std::sync::Mutex::new(EmittedIds::default())),
+        max_message_chars: Arc::new(std::sync::atomic::AtomicUsize::new(
+            state
+                .max_message_chars
+                .load(std::sync::atomic::Ordering::Relaxed),
+        )),
+    });
+
+    let text = SystemEvent::Joined {
+        user: username.clone(),
+    }
+    .to_wire();
+    let connect_message = Message {
+        username: username.clone(),
+        room,
+        room_id,
+        email: Some(email),
+        credential,
+        ..frame(MessageType::Connect, user_id, text)
+    };
+    send_secure_optional(&session.stream, &session.transport, &connect_message)
+        .await
+        .map_err(|e| {
+            AppError::Network(format!("Failed to send connect message to server: {}", e))
+        })?;
+
+    // Added before its listener starts, so a connection that ends at once is still forgotten.
+    state
+        .sessions
+        .lock()
+        .await
+        .insert(session_id, Arc::clone(&session));
+    Ok((session, reader))
+}
+
+/// Drop an extra session from `state.sessions` and close it: no more writes, no heartbeat.
+/// Its listener is left alone, since this runs on it when the connection ends.
+pub(crate) async fn forget_session(
+    state: &Arc<AppState>,
+    session_id: &str,
+) -> Option<Arc<ClientSession>> {
+    let session = state.sessions.lock().await.remove(session_id)?;
+    session.stream.lock().await.take();
+    session.transport.lock().await.take();
+    if let Some(handle) = session.heartbeat.lock().await.take() {
+        handle.abort();
+    }
+    tracing::info!("Session {} to {} closed", session.session_id, session.host);
+    Some(session)
+}
+
+/// Join another server as an extra workspace. Returns the new session id, which every other
+/// `session_*` command and every event from this server carries.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)] // Tauri command: params map 1:1 to JS invoke args.
+pub async fn session_connect(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    host: String,
+    username: String,
+    user_id: u64,
+    email: String,
+    room: String,
+    room_id: u64,
+    password: String,
+    credential: Option<String>,
+) -> AppResult<String> {
+    tracing::info!("🔵 Opening an extra session to {}", host);
+    let (session, reader) = open_session(
+        state.inner(),
+        host,
+        username,
+        user_id,
+        email,
+        room,
+        room_id,
+        &password,
+        credential,
+    )
+    .await?;
+    let session_id = session.session_id.clone();
+
+    let listener = start_client_listener(
+        app,
+        reader,
+        Arc::clone(&session.transport),
+        0,
+        Some(session_id.clone()),
+        ListenerShared {
+            clock_offset_ms: Arc::clone(&session.clock_offset_ms),
+            max_message_chars: Arc::clone(&session.max_message_chars),
+            emitted_ids: Arc::clone(&session.emitted_ids),
+        },
+    );
+    *session.listener.lock().await = Some(listener);
+    *session.heartbeat.lock().await = Some(spawn_client_heartbeat(Arc::clone(&session.stream)));
+    tracing::info!("✅ Session {} connected to {}", session_id, session.host);
+    Ok(session_id)
+}
+
+/// Send a chat message on an extra session. Like `send_as_client`, the sender's own UI gets
+/// the message immediately (as a tagged `session_message`) rather than waiting for an echo.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn session_send(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    session_id: String,
+    message: String,
+) -> AppResult<()> {
+    if message.trim().is_empty() {
+        return Err(AppError::Validation("Message cannot be empty".to_string()));
+    }
+    let (message, is_emoji) = emoji::normalize(&message);
+    let session = get_session(state.inner(), &session_id).await?;
+    limits::check_length(
+        &message,
+        session
+            .max_message_chars
+            .load(std::sync::atomic::Ordering::Relaxed),
+    )?;
+    let chat_message = Message {
+        username: session.username.clone(),
+        room: session.current_room.read().await.clone(),
+        room_id: *session.current_room_id.read().await,
+        created_at: server_now_secs(&session.clock_offset_ms),
+        is_emoji,
+        ..frame(MessageType::Chat, session.user_id, message)
+    };
+    send_secure_optional(&session.stream, &session.transport, &chat_message)
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to send message to server: {}", e)))?;
+    session
+        .emitted_ids
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(chat_message.message_id.clone());
+
+    if let Ok(payload) = serde_json::to_string(&chat_message) {
+        let _ = app.emit(
+            "session_message",
+            SessionEvent {
+                session_id,
+                payload,
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Switch rooms on an extra session; the host replies with history + roster as usual.
+#[tauri::command]
+pub async fn session_join_room(
+    state: State<'_, Arc<AppState>>,
+    session_id: String,
+    new_room: String,
+    new_room_id: u64,
+) -> AppResult<()> {
+    let session = get_session(state.inner(), &session_id).await?;
+    let old_room = session.current_room.read().await.clone();
+    *session.current_room.write().await = new_room.clone();
+    *session.current_room_id.write().await = new_room_id;
+
+    let text = SystemEvent::MovedRoom {
+        user: session.username.clone(),
+        from: old_room,
+        to: new_room.clone(),
+    }
+    .to_wire();
+    let room_join_msg = Message {
+        username: session.username.clone(),
+        room: new_room,
+        room_id: new_room_id,
+        ..frame(MessageType::RoomJoin, session.user_id, text)
+    };
+    send_secure_optional(&session.stream, &session.transport, &room_join_msg)
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to send room join: {}", e)))
+}
+
+/// Close one extra session: best-effort Disconnect, then stop its tasks and forget it.
+#[tauri::command]
+pub async fn session_disconnect(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    session_id: String,
+) -> AppResult<()> {
+    let Ok(session) = get_session(state.inner(), &session_id).await else {
+        return Ok(()); // already gone
+    };
+    let disconnect_msg = Message {
+        username: session.username.clone(),
+        room: session.current_room.read().await.clone(),
+        room_id: *session.current_room_id.read().await,
+        ..frame(
+            MessageType::Disconnect,
+            session.user_id,
+            "client disconnect".to_string(),
+        )
+    };
+    let _ = send_secure_optional(&session.stream, &session.transport, &disconnect_msg).await;
+    forget_session(state.inner(), &session_id).await;
+    if let Some(handle) = session.listener.lock().await.take() {
+        handle.abort();
+    }
+    let _ = app.emit("session_disconnected", session_id);
+    Ok(())
+}
+
+/// The extra sessions currently open, for the workspace switcher.
+#[tauri::command]
+pub async fn list_sessions(state: State<'_, Arc<AppState>>) -> Result<Vec<SessionInfo>, String> {
+    let sessions: Vec<Arc<ClientSession>> = state.sessions.lock().await.values().cloned().collect();
+    let mut out = Vec::with_capacity(sessions.len());
+    for s in sessions {
+        out.push(SessionInfo {
+            session_id: s.session_id.clone(),
+            host: s.host.clone(),
+            username: s.username.clone(),
+            user_id: s.user_id,
+            current_room: s.current_room.read().await.clone(),
+            current_room_id: *s.current_room_id.read().await,
+        });
+    }
+    out.sort_by(|a, b| a.host.cmp(&b.host));
+    Ok(out)
+}
+
 #[cfg(test)]
 mod discovery_tests {
     use super::*;
@@ -3132,4 +5260,45 @@ mod rate_limit_tests {
         }
         assert_eq!(allowed, RATE_LIMIT_PER_SEC as usize);
     }
+
+    #[tokio::test]
+    async fn file_chunks_wait_for_control_frames() {
+        let writer = Arc::new(PeerWriter::new(()));
+        let lane = writer.urgent();
+        let chunk = tokio::spawn({
+            let writer = Arc::clone(&writer);
+            async move { writer.bulk_turn().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!chunk.is_finished());
+        drop(lane);
+        tokio::time::timeout(Duration::from_secs(1), chunk)
+            .await
+            .expect("chunk goes once the control frame is out")
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod clock_tests {
+    use super::*;
+    use std::sync::atomic::AtomicI64;
+
+    #[test]
+    fn first_sample_is_taken_then_smoothed() {
+        let first = next_clock_offset(CLOCK_OFFSET_UNSET, 60_000);
+        assert_eq!(first, 60_000);
+        // A jittery sample only moves the estimate a quarter of the way.
+        assert_eq!(next_clock_offset(first, 60_400), 60_100);
+        assert_eq!(next_clock_offset(first, 59_600), 59_900);
+    }
+
+    #[test]
+    fn server_now_applies_the_offset() {
+        let unset = AtomicI64::new(CLOCK_OFFSET_UNSET);
+        assert!(server_now_secs(&unset).abs_diff(now_secs()) <= 1);
+        // Local clock an hour fast → host time is an hour behind it.
+        let fast = AtomicI64::new(-3_600_000);
+        assert!(server_now_secs(&fast).abs_diff(now_secs() - 3600) <= 1);
+    }
 }
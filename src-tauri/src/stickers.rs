@@ -0,0 +1,608 @@
+// Sticker packs.
+//
+// A host curates named packs of sticker images (`sticker_packs` / `stickers`). A Sticker message
+// carries only `{pack_id, sticker_id, name}`; each machine draws the image from its own copy of
+// the packs. Clients keep that copy in sync the same way as custom emoji (see custom_emoji.rs):
+// a StickerManifest of ids and hashes on connect and after every change, a StickerRequest for
+// what's missing or stale, and one StickerData frame per sticker. The client cache keeps the
+// host's ids so a message resolves to the same sticker everywhere.
+
+use crate::custom_emoji::{decode_image, image_hash, sniff_mime};
+use crate::error::{AppError, AppResult};
+use crate::sockets::{
+    frame, now_secs, publish_host_message, send_secure_client, send_to_client, server_now_secs,
+    AppState, Message, MessageType,
+};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::sync::Arc;
+use tauri::{Emitter, Manager, State};
+
+/// Largest sticker image (bytes): as big as still fits one Noise frame once base64-encoded.
+const MAX_STICKER_BYTES: usize = 46 * 1024;
+
+/// Most stickers a host keeps across all packs, and the most one StickerRequest may ask for.
+const MAX_STICKERS: usize = 1000;
+
+/// A Sticker message's `message`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StickerRef {
+    pub pack_id: i64,
+    pub sticker_id: i64,
+    // The sticker's name, shown if the image isn't available.
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PackEntry {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StickerEntry {
+    pub id: i64,
+    pub pack_id: i64,
+    pub name: String,
+    pub hash: String,
+}
+
+/// A StickerManifest payload: every pack and every sticker's hash.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Manifest {
+    pub packs: Vec<PackEntry>,
+    pub stickers: Vec<StickerEntry>,
+}
+
+/// A StickerData payload.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StickerImage {
+    pub id: i64,
+    pub pack_id: i64,
+    pub name: String,
+    pub mime: String,
+    pub hash: String,
+    // Base64 image bytes.
+    pub data: String,
+}
+
+/// A pack for the UI, its stickers' images inlined as data URLs.
+#[derive(Serialize, Debug)]
+pub struct StickerPack {
+    pub id: i64,
+    pub name: String,
+    pub stickers: Vec<Sticker>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Sticker {
+    pub id: i64,
+    pub name: String,
+    pub url: String,
+}
+
+fn validate_name(name: &str, what: &str) -> AppResult<()> {
+    let len = name.chars().count();
+    if !(1..=32).contains(&len) || name.chars().any(char::is_control) {
+        return Err(AppError::Validation(format!(
+            "{} names must be 1–32 characters",
+            what
+        )));
+    }
+    Ok(())
+}
+
+pub async fn create_sticker_pack_internal(
+    pool: &SqlitePool,
+    name: &str,
+    created_by: Option<i64>,
+) -> AppResult<i64> {
+    let name = name.trim();
+    validate_name(name, "Pack")?;
+    let res = sqlx::query("INSERT INTO sticker_packs (name, created_by) VALUES ($1, $2)")
+        .bind(name)
+        .bind(created_by)
+        .execute(pool)
+        .await;
+    match res {
+        Ok(r) => Ok(r.last_insert_rowid()),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(AppError::Conflict(
+            format!("A sticker pack named '{}' already exists", name),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn add_sticker_internal(
+    pool: &SqlitePool,
+    pack_id: i64,
+    name: &str,
+    image: &str,
+) -> AppResult<i64> {
+    let name = name.trim();
+    validate_name(name, "Sticker")?;
+    let bytes = decode_image(image)?;
+    let mime = sniff_mime(&bytes, MAX_STICKER_BYTES)?;
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM stickers")
+        .fetch_one(pool)
+        .await?;
+    if total as usize >= MAX_STICKERS {
+        return Err(AppError::Validation(format!(
+            "A server can have at most {} stickers",
+            MAX_STICKERS
+        )));
+    }
+    let res = sqlx::query(
+        "INSERT INTO stickers (pack_id, name, mime, data, hash)
+         SELECT id, $2, $3, $4, $5 FROM sticker_packs WHERE id = $1",
+    )
+    .bind(pack_id)
+    .bind(name)
+    .bind(mime)
+    .bind(&bytes)
+    .bind(image_hash(&bytes))
+    .execute(pool)
+    .await;
+    match res {
+        Ok(r) if r.rows_affected() == 0 => {
+            Err(AppError::Validation(format!("No sticker pack {}", pack_id)))
+        }
+        Ok(r) => Ok(r.last_insert_rowid()),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(AppError::Conflict(
+            format!("The pack already has a sticker named '{}'", name),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn manifest_internal(pool: &SqlitePool) -> AppResult<Manifest> {
+    let packs = sqlx::query("SELECT id, name FROM sticker_packs ORDER BY id")
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|r| PackEntry {
+            id: r.get("id"),
+            name: r.get("name"),
+        })
+        .collect();
+    let stickers = sqlx::query("SELECT id, pack_id, name, hash FROM stickers ORDER BY id")
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|r| StickerEntry {
+            id: r.get("id"),
+            pack_id: r.get("pack_id"),
+            name: r.get("name"),
+            hash: r.get("hash"),
+        })
+        .collect();
+    Ok(Manifest { packs, stickers })
+}
+
+/// Apply a host's manifest to the local cache: take its packs, drop stickers it no longer has,
+/// and return the sticker ids to fetch. The bool is whether anything local changed.
+pub async fn reconcile_manifest_internal(
+    pool: &SqlitePool,
+    manifest: &Manifest,
+) -> AppResult<(bool, Vec<i64>)> {
+    let ids_json = |ids: Vec<i64>| serde_json::to_string(&ids).unwrap_or_default();
+    let sticker_ids = ids_json(manifest.stickers.iter().map(|s| s.id).collect());
+    let pack_ids = ids_json(manifest.packs.iter().map(|p| p.id).collect());
+
+    let mut tx = pool.begin().await?;
+    let mut changed =
+        sqlx::query("DELETE FROM stickers WHERE id NOT IN (SELECT value FROM json_each($1))")
+            .bind(&sticker_ids)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+            > 0;
+    changed |=
+        sqlx::query("DELETE FROM sticker_packs WHERE id NOT IN (SELECT value FROM json_each($1))")
+            .bind(&pack_ids)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected()
+            > 0;
+    for pack in &manifest.packs {
+        changed |= sqlx::query(
+            "INSERT INTO sticker_packs (id, name) VALUES ($1, $2)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name WHERE name != excluded.name",
+        )
+        .bind(pack.id)
+        .bind(&pack.name)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+    }
+    tx.commit().await?;
+
+    let local = manifest_internal(pool).await?.stickers;
+    let wanted = manifest
+        .stickers
+        .iter()
+        .filter(|s| !local.contains(s))
+        .map(|s| s.id)
+        .collect();
+    Ok((changed, wanted))
+}
+
+/// Client: store one sticker from the host, under the host's id.
+async fn store_synced(pool: &SqlitePool, image: &StickerImage) -> AppResult<()> {
+    validate_name(&image.name, "Sticker")?;
+    let bytes = decode_image(&image.data)?;
+    let mime = sniff_mime(&bytes, MAX_STICKER_BYTES)?;
+    if image_hash(&bytes) != image.hash {
+        return Err(AppError::Validation(format!(
+            "Sticker '{}' doesn't match its hash",
+            image.name
+        )));
+    }
+    sqlx::query(
+        "INSERT INTO stickers (id, pack_id, name, mime, data, hash) VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT(id) DO UPDATE SET pack_id = excluded.pack_id, name = excluded.name,
+                                       mime = excluded.mime, data = excluded.data,
+                                       hash = excluded.hash",
+    )
+    .bind(image.id)
+    .bind(image.pack_id)
+    .bind(&image.name)
+    .bind(mime)
+    .bind(&bytes)
+    .bind(&image.hash)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The sticker a Sticker message refers to, with its name filled in from this machine's packs,
+/// as the JSON to send. None if there's no such sticker here.
+pub async fn resolve_internal(pool: &SqlitePool, message: &str) -> Option<String> {
+    let mut sticker: StickerRef = serde_json::from_str(message).ok()?;
+    sticker.name = sqlx::query_scalar("SELECT name FROM stickers WHERE id = $1 AND pack_id = $2")
+        .bind(sticker.sticker_id)
+        .bind(sticker.pack_id)
+        .fetch_optional(pool)
+        .await
+        .ok()??;
+    serde_json::to_string(&sticker).ok()
+}
+
+/// How a Sticker message reads where only text fits (notifications, email digests).
+pub(crate) fn preview(message: &str) -> String {
+    match serde_json::from_str::<StickerRef>(message) {
+        Ok(s) if !s.name.is_empty() => format!("[Sticker: {}]", s.name),
+        _ => "[Sticker]".to_string(),
+    }
+}
+
+/// Host → one client: the current manifest.
+pub(crate) async fn send_manifest(state: &Arc<AppState>, pool: &SqlitePool, user_id: u64) {
+    let manifest = match manifest_internal(pool).await {
+        Ok(m) => m,
+        Err(e) => {
+            tracing::warn!("Sticker manifest failed: {}", e);
+            return;
+        }
+    };
+    let json = serde_json::to_string(&manifest).unwrap_or_default();
+    send_to_client(
+        state,
+        user_id,
+        &frame(MessageType::StickerManifest, user_id, json),
+    )
+    .await;
+}
+
+async fn broadcast_manifest(state: &Arc<AppState>, pool: &SqlitePool) {
+    let uids: Vec<u64> = state.server_streams.lock().await.keys().copied().collect();
+    for uid in uids {
+        send_manifest(state, pool, uid).await;
+    }
+}
+
+/// Host: answer a client's StickerRequest (`ids_json` is a JSON array of sticker ids).
+pub(crate) async fn answer_request(
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    user_id: u64,
+    ids_json: &str,
+) {
+    let Ok(ids) = serde_json::from_str::<Vec<i64>>(ids_json) else {
+        return;
+    };
+    for id in ids.into_iter().take(MAX_STICKERS) {
+        let row = sqlx::query("SELECT pack_id, name, mime, data, hash FROM stickers WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await;
+        let Ok(Some(row)) = row else { continue };
+        let image = StickerImage {
+            id,
+            pack_id: row.get("pack_id"),
+            name: row.get("name"),
+            mime: row.get("mime"),
+            hash: row.get("hash"),
+            data: base64::engine::general_purpose::STANDARD.encode(row.get::<Vec<u8>, _>("data")),
+        };
+        let json = serde_json::to_string(&image).unwrap_or_default();
+        send_to_client(
+            state,
+            user_id,
+            &frame(MessageType::StickerData, user_id, json),
+        )
+        .await;
+    }
+}
+
+/// Client: handle a StickerManifest / StickerData frame from the primary connection's host.
+/// The UI is told (`stickers_updated`) when the local packs change.
+pub(crate) async fn handle_host_frame(app: &tauri::AppHandle, message: &Message) {
+    let (Some(pool), Some(state)) = (
+        app.try_state::<SqlitePool>(),
+        app.try_state::<Arc<AppState>>(),
+    ) else {
+        return;
+    };
+    let changed = match message.message_type {
+        MessageType::StickerManifest => {
+            let Ok(manifest) = serde_json::from_str::<Manifest>(&message.message) else {
+                return;
+            };
+            match reconcile_manifest_internal(&pool, &manifest).await {
+                Ok((changed, wanted)) => {
+                    if !wanted.is_empty() {
+                        let json = serde_json::to_string(&wanted).unwrap_or_default();
+                        let request = frame(MessageType::StickerRequest, message.user_id, json);
+                        if let Err(e) = send_secure_client(state.inner(), &request).await {
+                            tracing::warn!("Sticker request failed: {}", e);
+                        }
+                    }
+                    changed
+                }
+                Err(e) => {
+                    tracing::warn!("Sticker sync failed: {}", e);
+                    false
+                }
+            }
+        }
+        MessageType::StickerData => {
+            let Ok(image) = serde_json::from_str::<StickerImage>(&message.message) else {
+                return;
+            };
+            match store_synced(&pool, &image).await {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!("Rejected sticker {} from host: {}", image.id, e);
+                    false
+                }
+            }
+        }
+        _ => false,
+    };
+    if changed {
+        let _ = app.emit("stickers_updated", ());
+    }
+}
+
+/// Create an empty sticker pack on this host.
+#[tauri::command]
+pub async fn create_sticker_pack(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    name: String,
+    user_id: Option<i64>,
+) -> AppResult<i64> {
+    let id = create_sticker_pack_internal(&db, &name, user_id).await?;
+    broadcast_manifest(state.inner(), &db).await;
+    Ok(id)
+}
+
+/// Add a sticker (`image` is base64 or a data URL) to one of this host's packs.
+#[tauri::command]
+pub async fn add_sticker(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    pack_id: i64,
+    name: String,
+    image: String,
+) -> AppResult<i64> {
+    let id = add_sticker_internal(&db, pack_id, &name, &image).await?;
+    broadcast_manifest(state.inner(), &db).await;
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn delete_sticker(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    sticker_id: i64,
+) -> AppResult<()> {
+    let deleted = sqlx::query("DELETE FROM stickers WHERE id = $1")
+        .bind(sticker_id)
+        .execute(&*db)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(AppError::Validation(format!("No sticker {}", sticker_id)));
+    }
+    broadcast_manifest(state.inner(), &db).await;
+    Ok(())
+}
+
+/// Delete a pack and its stickers. Messages that used them show the sticker's name instead.
+#[tauri::command]
+pub async fn delete_sticker_pack(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    pack_id: i64,
+) -> AppResult<()> {
+    let deleted = sqlx::query("DELETE FROM sticker_packs WHERE id = $1")
+        .bind(pack_id)
+        .execute(&*db)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(AppError::Validation(format!("No sticker pack {}", pack_id)));
+    }
+    broadcast_manifest(state.inner(), &db).await;
+    Ok(())
+}
+
+/// The packs available here: the host's own, or on a client the synced copy.
+#[tauri::command]
+pub async fn list_sticker_packs(db: State<'_, SqlitePool>) -> AppResult<Vec<StickerPack>> {
+    let mut packs: Vec<StickerPack> =
+        sqlx::query("SELECT id, name FROM sticker_packs ORDER BY name")
+            .fetch_all(&*db)
+            .await?
+            .iter()
+            .map(|r| StickerPack {
+                id: r.get("id"),
+                name: r.get("name"),
+                stickers: Vec::new(),
+            })
+            .collect();
+    let rows = sqlx::query("SELECT id, pack_id, name, mime, data FROM stickers ORDER BY id")
+        .fetch_all(&*db)
+        .await?;
+    for r in &rows {
+        let pack_id: i64 = r.get("pack_id");
+        if let Some(pack) = packs.iter_mut().find(|p| p.id == pack_id) {
+            pack.stickers.push(Sticker {
+                id: r.get("id"),
+                name: r.get("name"),
+                url: format!(
+                    "data:{};base64,{}",
+                    r.get::<String, _>("mime"),
+                    base64::engine::general_purpose::STANDARD.encode(r.get::<Vec<u8>, _>("data"))
+                ),
+            });
+        }
+    }
+    Ok(packs)
+}
+
+/// Send a sticker to the current room, as the host participant or over the client connection.
+#[tauri::command(rename_all = "snake_case")]
+pub async fn send_sticker(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    pack_id: i64,
+    sticker_id: i64,
+    user_id: u64,
+) -> AppResult<()> {
+    let sticker = serde_json::to_string(&StickerRef {
+        pack_id,
+        sticker_id,
+        name: String::new(),
+    })
+    .map_err(|e| AppError::Internal(e.to_string()))?;
+    let Some(sticker) = resolve_internal(&db, &sticker).await else {
+        return Err(AppError::Validation(
+            "That sticker isn't available".to_string(),
+        ));
+    };
+    let is_server = *state.is_server.read().await;
+    let message = Message {
+        username: state.username.read().await.clone(),
+        room: state.current_room.read().await.clone(),
+        room_id: state.current_room_id.read().await.unwrap_or(1),
+        created_at: if is_server {
+            now_secs()
+        } else {
+            server_now_secs(&state.clock_offset_ms)
+        },
+        ..frame(MessageType::Sticker, user_id, sticker)
+    };
+    if is_server {
+        publish_host_message(&app, state.inner(), db.inner(), message).await;
+        Ok(())
+    } else {
+        send_secure_client(state.inner(), &message)
+            .await
+            .map_err(AppError::Network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        pool
+    }
+
+    fn gif(extra: u8) -> String {
+        let mut bytes = b"GIF89a".to_vec();
+        bytes.push(extra);
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    #[tokio::test]
+    async fn packs_sync_to_a_client_cache() {
+        let host = setup().await;
+        let client = setup().await;
+        let cats = create_sticker_pack_internal(&host, "Cats", None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            create_sticker_pack_internal(&host, "Cats", None).await,
+            Err(AppError::Conflict(_))
+        ));
+        let wave = add_sticker_internal(&host, cats, "wave", &gif(1))
+            .await
+            .unwrap();
+        assert!(add_sticker_internal(&host, 99, "nap", &gif(2))
+            .await
+            .is_err());
+
+        let manifest = manifest_internal(&host).await.unwrap();
+        let (changed, wanted) = reconcile_manifest_internal(&client, &manifest)
+            .await
+            .unwrap();
+        assert!(changed);
+        assert_eq!(wanted, [wave]);
+
+        let image = StickerImage {
+            id: wave,
+            pack_id: cats,
+            name: "wave".to_string(),
+            mime: "image/gif".to_string(),
+            hash: manifest.stickers[0].hash.clone(),
+            data: gif(1),
+        };
+        store_synced(&client, &image).await.unwrap();
+        let (_, wanted) = reconcile_manifest_internal(&client, &manifest)
+            .await
+            .unwrap();
+        assert!(wanted.is_empty());
+
+        // A Sticker message resolves by the shared ids, named from the local copy.
+        let msg = format!(r#"{{"pack_id":{},"sticker_id":{}}}"#, cats, wave);
+        let resolved = resolve_internal(&client, &msg).await.unwrap();
+        assert_eq!(preview(&resolved), "[Sticker: wave]");
+
+        // The host drops the pack; the client follows.
+        sqlx::query("DELETE FROM sticker_packs")
+            .execute(&host)
+            .await
+            .unwrap();
+        let manifest = manifest_internal(&host).await.unwrap();
+        reconcile_manifest_internal(&client, &manifest)
+            .await
+            .unwrap();
+        assert!(resolve_internal(&client, &msg).await.is_none());
+    }
+}
@@ -0,0 +1,181 @@
+// Where the host saves what goes through its rooms.
+//
+// The socket paths (handle_server_message, clean_client, the host's own posts) hand each
+// message to a MessageStore instead of calling the db_queries savers themselves, so the
+// networking can run without a database behind it. SqliteStore is the real one, over this
+// instance's pool; MemoryStore (tests only) keeps the messages in a Vec. The host saves through
+// AppState::message_store: the store injected there, else SqliteStore.
+
+use crate::db_queries::{
+    save_code_snippet_internal, save_location_internal, save_message_internal,
+};
+use crate::sockets::{Message, MessageType};
+use sqlx::SqlitePool;
+use std::future::Future;
+use std::pin::Pin;
+
+pub type StoreFuture<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + Send + 'a>>;
+
+pub trait MessageStore: Send + Sync {
+    /// Save `message` as a row of its kind (see `stored_kind`). Saving the same message_id
+    /// again is a no-op.
+    fn save<'a>(&'a self, message: &'a Message) -> StoreFuture<'a>;
+}
+
+/// The `messages.message_type` a frame of this kind is saved as.
+pub fn stored_kind(message_type: MessageType) -> &'static str {
+    match message_type {
+        MessageType::Connect => "Connect",
+        MessageType::Disconnect => "Disconnect",
+        MessageType::RoomJoin => "RoomJoin",
+        MessageType::RoomLeave => "RoomLeave",
+        MessageType::Sticker => "Sticker",
+        MessageType::Attachment => "Attachment",
+        MessageType::Contact => "Contact",
+        MessageType::Code => "Code",
+        MessageType::Location => "Location",
+        _ => "Chat",
+    }
+}
+
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+impl MessageStore for SqliteStore {
+    // Code snippets keep their language and filename, locations their coordinates.
+    fn save<'a>(&'a self, message: &'a Message) -> StoreFuture<'a> {
+        Box::pin(async move {
+            let (room_id, user_id) = (message.room_id as i64, message.user_id as i64);
+            match message.message_type {
+                MessageType::Code => {
+                    save_code_snippet_internal(
+                        &self.pool,
+                        room_id,
+                        user_id,
+                        &message.message,
+                        message.language.as_deref(),
+                        message.filename.as_deref(),
+                        &message.message_id,
+                    )
+                    .await
+                }
+                MessageType::Location => {
+                    save_location_internal(
+                        &self.pool,
+                        room_id,
+                        user_id,
+                        &message.message,
+                        &message.message_id,
+                    )
+                    .await
+                }
+                kind => {
+                    save_message_internal(
+                        &self.pool,
+                        room_id,
+                        user_id,
+                        message.message.clone(),
+                        stored_kind(kind).to_string(),
+                        message.is_emoji,
+                        message.message_id.clone(),
+                    )
+                    .await
+                }
+            }
+            .map(|_| ())
+        })
+    }
+}
+
+/// Messages kept in memory, in the order they were saved.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore {
+    saved: std::sync::Mutex<Vec<Message>>,
+}
+
+#[cfg(test)]
+impl MemoryStore {
+    pub fn saved(&self) -> Vec<Message> {
+        self.saved.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl MessageStore for MemoryStore {
+    fn save<'a>(&'a self, message: &'a Message) -> StoreFuture<'a> {
+        let mut saved = self.saved.lock().unwrap();
+        if !saved.iter().any(|m| m.message_id == message.message_id) {
+            saved.push(message.clone());
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn frame(message_type: MessageType, message_id: &str) -> Message {
+        Message {
+            username: "Alice".to_string(),
+            message_id: message_id.to_string(),
+            room: "Company Wide".to_string(),
+            room_id: 1,
+            created_at: 0,
+            language: Some("rust".to_string()),
+            ..crate::sockets::frame(message_type, 1, "fn main() {}".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_save_each_message_once_under_its_kind() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::query("INSERT INTO users (id, name, email) VALUES (1, 'Alice', 'a@x')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let store: Box<dyn MessageStore> = Box::new(SqliteStore::new(pool.clone()));
+        store.save(&frame(MessageType::Code, "a")).await.unwrap();
+        store.save(&frame(MessageType::Code, "a")).await.unwrap();
+        store
+            .save(&frame(MessageType::RoomJoin, "b"))
+            .await
+            .unwrap();
+        let rows: Vec<(String, String, Option<String>)> =
+            sqlx::query_as("SELECT message_id, message_type, language FROM messages ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (
+                    "a".to_string(),
+                    "Code".to_string(),
+                    Some("rust".to_string())
+                ),
+                ("b".to_string(), "RoomJoin".to_string(), None),
+            ]
+        );
+
+        let memory = MemoryStore::default();
+        memory.save(&frame(MessageType::Chat, "a")).await.unwrap();
+        memory.save(&frame(MessageType::Chat, "a")).await.unwrap();
+        assert_eq!(memory.saved().len(), 1);
+    }
+}
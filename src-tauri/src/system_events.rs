@@ -0,0 +1,66 @@
+// Structured system events (join / leave / room switch).
+//
+// Presence notices used to be English prose composed by the sender ("Bob left the chat"), which
+// every viewer then saw verbatim. They now travel as a small JSON object in the `message` field
+// of Connect / Disconnect / RoomJoin / RoomLeave frames — the event name plus its parameters —
+// and each viewer renders it in their own language (src/i18n.ts). Rows written before this
+// change still hold prose; the frontend shows any `message` that isn't an event as-is.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SystemEvent {
+    // The host started the server (and is its first participant).
+    ServerStarted {
+        user: String,
+    },
+    // A participant connected.
+    Joined {
+        user: String,
+    },
+    // A participant disconnected.
+    Left {
+        user: String,
+    },
+    // A participant switched rooms.
+    MovedRoom {
+        user: String,
+        from: String,
+        to: String,
+    },
+    // A participant left a room (back to the lobby).
+    LeftRoom {
+        user: String,
+        room: String,
+    },
+}
+
+impl SystemEvent {
+    /// The event as carried in a frame's `message` (and stored in `messages.message`).
+    pub fn to_wire(&self) -> String {
+        // Plain string fields only, so serialization can't fail.
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_round_trip_as_tagged_json() {
+        let event = SystemEvent::MovedRoom {
+            user: "Bob".to_string(),
+            from: "general".to_string(),
+            to: "random".to_string(),
+        };
+        assert_eq!(
+            event.to_wire(),
+            r#"{"event":"moved_room","user":"Bob","from":"general","to":"random"}"#
+        );
+        let parsed: SystemEvent = serde_json::from_str(&event.to_wire()).unwrap();
+        assert_eq!(parsed, event);
+        assert!(serde_json::from_str::<SystemEvent>("Bob left the chat").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+// OpenTelemetry export of tracing spans (optional, off by default).
+//
+// The message pipeline is instrumented with ordinary `tracing` spans: `handle_frame` (one inbound
+// frame on the host, including `connect.authenticate`), `persist` (the DB write), `broadcast`
+// (room fan-out) with `emit_local` (the local UI event), and `deliver` (one recipient's socket
+// write). Each carries the `message_id`, so a slow delivery can be matched to its broadcast and
+// write. Without an exporter these spans cost next to nothing.
+//
+// Export is configured the standard OpenTelemetry way, through environment variables read at
+// startup: setting `OTEL_EXPORTER_OTLP_ENDPOINT` (or `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`), e.g.
+// `http://localhost:4318`, sends spans over OTLP/HTTP to a collector (Jaeger, Tempo, …).
+// `OTEL_TRACES_SAMPLER` / `OTEL_TRACES_SAMPLER_ARG` control sampling.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::sync::OnceLock;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+const ENDPOINT_VARS: [&str; 2] = [
+    "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+];
+
+// Kept so pending spans can be flushed on exit.
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+// Why the exporter couldn't be built, kept until logging is up to report it.
+static EXPORT_ERROR: OnceLock<String> = OnceLock::new();
+
+fn endpoint_configured(var: impl Fn(&str) -> Option<String>) -> bool {
+    ENDPOINT_VARS
+        .iter()
+        .any(|name| var(name).is_some_and(|v| !v.trim().is_empty()))
+}
+
+/// The OTLP layer for the tracing subscriber, or None when no endpoint is configured (or the
+/// exporter can't be built — see `warn_if_disabled`).
+pub fn otlp_layer<S>() -> Option<impl Layer<S>>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    if !endpoint_configured(|name| std::env::var(name).ok()) {
+        return None;
+    }
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            let _ = EXPORT_ERROR.set(e.to_string());
+            return None;
+        }
+    };
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name("nutler").build())
+        .build();
+    let tracer = provider.tracer("nutler");
+    let _ = PROVIDER.set(provider);
+    Some(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Log why export is off despite a configured endpoint. Called once the subscriber is up, since
+/// `otlp_layer` runs while it's still being built.
+pub fn warn_if_disabled() {
+    if let Some(e) = EXPORT_ERROR.get() {
+        tracing::warn!("OpenTelemetry export disabled: {}", e);
+    }
+}
+
+/// Flush and stop the exporter. Called once on app exit; a no-op when export is off.
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            tracing::warn!("OpenTelemetry shutdown: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_needs_a_non_empty_endpoint() {
+        assert!(!endpoint_configured(|_| None));
+        assert!(!endpoint_configured(|_| Some("  ".to_string())));
+        assert!(endpoint_configured(|name| {
+            (name == "OTEL_EXPORTER_OTLP_ENDPOINT").then(|| "http://localhost:4318".to_string())
+        }));
+    }
+}
@@ -0,0 +1,503 @@
+// End-to-end test harness: an in-process host and fake clients.
+//
+// TestServer runs the host's real accept loop (sockets::spawn_accept_loop) on an ephemeral
+// loopback port, against an in-memory database, with tauri's mock runtime standing in for the
+// app: the host path is generic over tauri::Runtime, so the AppHandle it emits through is a
+// MockRuntime one here. TestClient speaks the wire protocol itself, over the same connector the
+// app uses (open_secure_client + send_secure), and decrypts whatever the host sends it. Tests
+// then assert on what each client received and on what the host persisted.
+//
+// The host spawns its tasks on tauri's own runtime, so tests run there too: a plain #[test]
+// that drives its body with tauri::async_runtime::block_on.
+
+use crate::codec::{FramedReader, FramedWriter};
+use crate::secure::{self, Reassembler, Transport};
+use crate::sockets::{
+    frame, open_secure_client, send_secure, spawn_accept_loop, AppState, Message, MessageType,
+    PeerWriter,
+};
+use crate::storage::{MemoryStore, MessageStore};
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::test::{mock_app, MockRuntime};
+use tauri::Manager;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+/// The room password every TestServer is hosted with.
+pub const PASSWORD: &str = "test-password";
+
+/// How long a client waits for a frame before the test fails.
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A fresh, migrated in-memory database, for any module's tests.
+pub async fn memory_pool() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    crate::db::run_migrations(&pool).await.unwrap();
+    pool
+}
+
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub pool: SqlitePool,
+    pub state: Arc<AppState>,
+    // Keeps the mock app (and so the AppHandle the host emits through) alive.
+    app: tauri::App<MockRuntime>,
+    accept: tauri::async_runtime::JoinHandle<()>,
+}
+
+impl TestServer {
+    /// A host on 127.0.0.1 with a fresh, migrated database.
+    pub async fn start() -> Self {
+        Self::launch(None).await
+    }
+
+    /// Like start, but room messages are saved to `store` instead of the database.
+    pub async fn with_store(store: Arc<dyn MessageStore>) -> Self {
+        Self::launch(Some(store)).await
+    }
+
+    async fn launch(store: Option<Arc<dyn MessageStore>>) -> Self {
+        let pool = memory_pool().await;
+        let state = Arc::new(AppState::default());
+        let _ = state.pool.set(pool.clone());
+        *state.is_server.write().await = true;
+        if let Some(store) = store {
+            let _ = state.message_store.set(store);
+        }
+
+        let app = mock_app();
+        app.manage(Arc::clone(&state));
+        app.manage(pool.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        *state.server_addr.write().await = Some(addr);
+        let accept = spawn_accept_loop(
+            app.handle().clone(),
+            Arc::clone(&state),
+            pool.clone(),
+            listener,
+            secure::derive_psk(PASSWORD),
+        );
+        Self {
+            addr,
+            pool,
+            state,
+            app,
+            accept,
+        }
+    }
+
+    /// The id of a room seeded by the migrations, e.g. "Company Wide".
+    pub async fn room_id(&self, name: &str) -> u64 {
+        let id: i64 = sqlx::query_scalar("SELECT id FROM chat_rooms WHERE name = $1")
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await
+            .unwrap();
+        id as u64
+    }
+
+    /// Host as `username` (user `user_id`), as a participant too.
+    pub async fn host_as(&self, user_id: u64, username: &str) {
+        *self.state.user_id.write().await = Some(user_id);
+        *self.state.username.write().await = username.to_string();
+    }
+
+    /// Pass `message` down the host's own participant path, as the hosting user.
+    pub async fn host_sends(&self, message: Message) {
+        crate::sockets::handle_participant_frame(
+            self.app.handle(),
+            &self.state,
+            &self.pool,
+            message,
+        )
+        .await;
+    }
+
+    /// Wait until the host has exactly `count` live client connections.
+    pub async fn wait_for_connections(&self, count: usize) {
+        let started = tokio::time::Instant::now();
+        while self.state.server_streams.lock().await.len() != count {
+            assert!(
+                started.elapsed() < RECV_TIMEOUT,
+                "the host never had {} connections",
+                count
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// Wait until the host has saved a message with `message_id` (saving happens after
+    /// delivery, in the background). Returns its text.
+    pub async fn persisted(&self, message_id: &str) -> String {
+        let started = tokio::time::Instant::now();
+        loop {
+            let text: Option<String> =
+                sqlx::query_scalar("SELECT message FROM messages WHERE message_id = $1")
+                    .bind(message_id)
+                    .fetch_optional(&self.pool)
+                    .await
+                    .unwrap();
+            if let Some(text) = text {
+                return text;
+            }
+            assert!(
+                started.elapsed() < RECV_TIMEOUT,
+                "message {} was never saved",
+                message_id
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
+
+pub struct TestClient {
+    pub username: String,
+    // The host-assigned canonical id, from its Identity frame.
+    pub user_id: u64,
+    pub room: String,
+    pub room_id: u64,
+    reader: FramedReader<OwnedReadHalf>,
+    writer: Arc<PeerWriter<FramedWriter<OwnedWriteHalf>>>,
+    transport: Arc<tokio::sync::Mutex<Transport>>,
+    reassembler: Reassembler,
+}
+
+impl TestClient {
+    /// Connect to `server` as `username` in `room`, and wait until the host has registered us.
+    pub async fn connect(server: &TestServer, username: &str, room: &str) -> Self {
+        let (reader, writer, transport) = open_secure_client(&server.addr.to_string(), PASSWORD)
+            .await
+            .unwrap();
+        let mut client = Self {
+            username: username.to_string(),
+            user_id: 0,
+            room: room.to_string(),
+            room_id: server.room_id(room).await,
+            reader,
+            writer: Arc::new(PeerWriter::new(writer)),
+            transport: Arc::new(tokio::sync::Mutex::new(transport)),
+            reassembler: Reassembler::default(),
+        };
+        let mut connect = client.frame(MessageType::Connect, String::new());
+        connect.email = Some(format!("{}@test.invalid", username.to_lowercase()));
+        client.send(&connect).await;
+        let identity = client
+            .recv_until(|m| m.message_type == MessageType::Identity)
+            .await;
+        client.user_id = identity.user_id;
+        client
+    }
+
+    /// A frame from us, in our room.
+    pub fn frame(&self, message_type: MessageType, message: String) -> Message {
+        Message {
+            username: self.username.clone(),
+            room: self.room.clone(),
+            room_id: self.room_id,
+            ..frame(message_type, self.user_id, message)
+        }
+    }
+
+    pub async fn send(&self, message: &Message) {
+        send_secure(&self.writer, &self.transport, message)
+            .await
+            .unwrap();
+    }
+
+    /// Post `text` to our room. Returns its message id.
+    pub async fn say(&self, text: &str) -> String {
+        let message = self.frame(MessageType::Chat, text.to_string());
+        self.send(&message).await;
+        message.message_id
+    }
+
+    /// The next frame from the host (keep-alives skipped).
+    pub async fn recv(&mut self) -> Message {
+        tokio::time::timeout(RECV_TIMEOUT, async {
+            loop {
+                let Some(ciphertext) = self.reader.read_frame().await.unwrap() else {
+                    continue;
+                };
+                let plaintext = {
+                    let mut ts = self.transport.lock().await;
+                    secure::decrypt(&mut ts, &ciphertext).unwrap()
+                };
+                if let Some(payload) = self.reassembler.push(plaintext).unwrap() {
+                    return serde_json::from_slice(&payload).unwrap();
+                }
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("{} heard nothing from the host", self.username))
+    }
+
+    /// The next frame matching `pred`, skipping the rest (rosters, manifests, ...).
+    pub async fn recv_until(&mut self, pred: impl Fn(&Message) -> bool) -> Message {
+        loop {
+            let message = self.recv().await;
+            if pred(&message) {
+                return message;
+            }
+        }
+    }
+
+    /// The next chat message, from anyone.
+    pub async fn recv_chat(&mut self) -> Message {
+        self.recv_until(|m| m.message_type == MessageType::Chat)
+            .await
+    }
+}
+
+#[test]
+fn chat_is_broadcast_to_the_room_and_persisted() {
+    tauri::async_runtime::block_on(async {
+        let server = TestServer::start().await;
+        let mut alice = TestClient::connect(&server, "Alice", "Company Wide").await;
+        let mut bob = TestClient::connect(&server, "Bob", "Company Wide").await;
+        let mut carol = TestClient::connect(&server, "Carol", "Company Wide").await;
+        assert_ne!(alice.user_id, bob.user_id);
+
+        let id = alice.say("hello everyone").await;
+        for peer in [&mut bob, &mut carol] {
+            let got = peer.recv_chat().await;
+            assert_eq!(
+                (got.message_id.as_str(), got.message.as_str()),
+                (id.as_str(), "hello everyone")
+            );
+            assert_eq!(got.username, "Alice");
+        }
+        assert_eq!(server.persisted(&id).await, "hello everyone");
+
+        // The host attributes the row to the connection's canonical id, whatever the frame says.
+        let mut forged = bob.frame(MessageType::Chat, "from bob".to_string());
+        forged.user_id = alice.user_id;
+        bob.send(&forged).await;
+        assert_eq!(alice.recv_chat().await.message, "from bob");
+        server.persisted(&forged.message_id).await;
+        let author: i64 = sqlx::query_scalar("SELECT user_id FROM messages WHERE message_id = $1")
+            .bind(&forged.message_id)
+            .fetch_one(&server.pool)
+            .await
+            .unwrap();
+        assert_eq!(author as u64, bob.user_id);
+    });
+}
+
+#[test]
+fn other_rooms_do_not_hear_it() {
+    tauri::async_runtime::block_on(async {
+        let server = TestServer::start().await;
+        sqlx::query("INSERT INTO chat_rooms (name, is_private) VALUES ('side', 0)")
+            .execute(&server.pool)
+            .await
+            .unwrap();
+        let alice = TestClient::connect(&server, "Alice", "Company Wide").await;
+        let mut bob = TestClient::connect(&server, "Bob", "Company Wide").await;
+        let mut dave = TestClient::connect(&server, "Dave", "side").await;
+
+        alice.say("company news").await;
+        assert_eq!(bob.recv_chat().await.message, "company news");
+        let elsewhere = TestClient::connect(&server, "Erin", "side").await;
+        let id = elsewhere.say("department news").await;
+        // Dave's first chat is Erin's: Alice's never reached his room.
+        let got = dave.recv_chat().await;
+        assert_eq!(got.message_id, id);
+    });
+}
+
+#[test]
+fn an_injected_store_gets_the_messages() {
+    tauri::async_runtime::block_on(async {
+        let store = Arc::new(MemoryStore::default());
+        let server = TestServer::with_store(store.clone()).await;
+        let alice = TestClient::connect(&server, "Alice", "Company Wide").await;
+        let mut bob = TestClient::connect(&server, "Bob", "Company Wide").await;
+
+        let id = alice.say("kept in memory").await;
+        assert_eq!(bob.recv_chat().await.message_id, id);
+        // The chat and both Connect notices, saved in the background.
+        let saved = |kind: MessageType| {
+            store
+                .saved()
+                .iter()
+                .filter(|m| m.message_type == kind)
+                .count()
+        };
+        let started = tokio::time::Instant::now();
+        while (saved(MessageType::Chat), saved(MessageType::Connect)) != (1, 2) {
+            assert!(
+                started.elapsed() < RECV_TIMEOUT,
+                "messages were never saved"
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+            .fetch_one(&server.pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 0);
+    });
+}
+
+#[test]
+fn the_sender_does_not_get_its_own_message_back() {
+    tauri::async_runtime::block_on(async {
+        let server = TestServer::start().await;
+        let mut alice = TestClient::connect(&server, "Alice", "Company Wide").await;
+        let mut bob = TestClient::connect(&server, "Bob", "Company Wide").await;
+
+        let mine = alice.say("first").await;
+        assert_eq!(bob.recv_chat().await.message_id, mine);
+        let theirs = bob.say("second").await;
+        // Alice's next chat is Bob's: her connection was skipped for her own.
+        assert_eq!(alice.recv_chat().await.message_id, theirs);
+    });
+}
+
+#[test]
+fn server_stats_show_each_connection_draining() {
+    tauri::async_runtime::block_on(async {
+        let server = TestServer::start().await;
+        let alice = TestClient::connect(&server, "Alice", "Company Wide").await;
+        let mut bob = TestClient::connect(&server, "Bob", "Company Wide").await;
+        let id = alice.say("hello").await;
+        assert_eq!(bob.recv_chat().await.message_id, id);
+
+        let stats = crate::metrics::server_stats(&server.state).await;
+        assert_eq!(stats.connected_clients, 2);
+        let mut names: Vec<&str> = stats
+            .connections
+            .iter()
+            .map(|c| c.username.as_str())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["Alice", "Bob"]);
+        for conn in &stats.connections {
+            assert_eq!((conn.queued, conn.lagging), (0, false));
+            assert_eq!(conn.room, "Company Wide");
+            assert!(conn.last_write_age_ms < 5000);
+        }
+    });
+}
+
+#[test]
+fn the_hosts_typing_and_presence_reach_clients_like_anyone_elses() {
+    tauri::async_runtime::block_on(async {
+        let server = TestServer::start().await;
+        let mut bob = TestClient::connect(&server, "Bob", "Company Wide").await;
+        server.host_as(900, "Hana").await;
+
+        // Whatever id the frame claims, it goes out as the hosting user's.
+        let mut typing = bob.frame(MessageType::Typing, String::new());
+        typing.username = "Hana".to_string();
+        typing.is_emoji = true;
+        server.host_sends(typing).await;
+        let heard = bob
+            .recv_until(|m| m.message_type == MessageType::Typing)
+            .await;
+        assert_eq!(
+            (heard.user_id, heard.username.as_str(), heard.is_emoji),
+            (900, "Hana", true)
+        );
+
+        let away = bob.frame(MessageType::Presence, crate::presence::AWAY.to_string());
+        server.host_sends(away).await;
+        let heard = bob
+            .recv_until(|m| m.message_type == MessageType::Presence)
+            .await;
+        assert_eq!(
+            (
+                heard.user_id,
+                heard.username.as_str(),
+                heard.message.as_str()
+            ),
+            (900, "Hana", crate::presence::AWAY)
+        );
+        assert!(server.state.away_users.lock().await.contains_key(&900));
+    });
+}
+
+#[test]
+fn an_extra_session_is_forgotten_once_its_connection_ends() {
+    tauri::async_runtime::block_on(async {
+        let server = TestServer::start().await;
+        let client = Arc::new(AppState::default());
+        let (session, _reader) = crate::sockets::open_session(
+            &client,
+            server.addr.to_string(),
+            "Sam".to_string(),
+            0,
+            "sam@test.invalid".to_string(),
+            "Company Wide".to_string(),
+            server.room_id("Company Wide").await,
+            PASSWORD,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(client
+            .sessions
+            .lock()
+            .await
+            .contains_key(&session.session_id));
+        server.wait_for_connections(1).await;
+
+        // What its listener does when the connection ends: the host sees it close too.
+        crate::sockets::forget_session(&client, &session.session_id).await;
+        assert!(client.sessions.lock().await.is_empty());
+        server.wait_for_connections(0).await;
+        assert!(crate::sockets::forget_session(&client, &session.session_id)
+            .await
+            .is_none());
+    });
+}
+
+#[test]
+fn a_muted_user_can_neither_edit_nor_offer_a_file() {
+    tauri::async_runtime::block_on(async {
+        let server = TestServer::start().await;
+        let mut alice = TestClient::connect(&server, "Alice", "Company Wide").await;
+        crate::moderation::set_muted_internal(
+            &server.pool,
+            alice.user_id as i64,
+            true,
+            None,
+            Some("spam"),
+        )
+        .await
+        .unwrap();
+
+        let edit = alice.frame(MessageType::Edit, "now with a link".to_string());
+        alice.send(&edit).await;
+        let muted = alice
+            .recv_until(|m| m.message_type == MessageType::Muted)
+            .await;
+        assert_eq!(muted.message_id, edit.message_id);
+
+        let offer = alice.frame(
+            MessageType::AttachmentOffer,
+            r#"{"filename":"notes.txt","size":5,"sha256":"00"}"#.to_string(),
+        );
+        alice.send(&offer).await;
+        let ack = alice
+            .recv_until(|m| m.message_type == MessageType::AttachmentAck)
+            .await;
+        assert_eq!(ack.message_id, offer.message_id);
+        let ack: crate::attachments::TransferAck = serde_json::from_str(&ack.message).unwrap();
+        assert!(matches!(ack.error, Some(crate::error::AppError::Auth(m)) if m.contains("muted")));
+    });
+}
@@ -0,0 +1,138 @@
+// Upload throttling for file transfers.
+//
+// On slow office Wi-Fi one big file share can fill the link and hold chat up behind it. With a
+// limit set, the chunked file senders (a client's uploads, and the host streaming downloads to
+// each client) pace their chunks so a connection's file traffic stays under it; every transfer
+// on one connection shares that allowance. Chat and other frames are never paced, so they slip
+// in between the chunks.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tauri::State;
+use tokio::time::Instant;
+
+const SETTINGS_KEY: &str = "transfer_throttle";
+
+/// The pacing key for a client's connection to its host (user ids start at 1).
+pub const TO_HOST: u64 = 0;
+
+/// Slower than this, a single chunk would take long enough to trip transfer timeouts.
+const MIN_KB_PER_SEC: u64 = 16;
+
+/// Pacing entries kept before idle ones are dropped.
+const MAX_TRACKED: usize = 256;
+
+// The current limit in KB/s; 0 is unlimited.
+static MAX_KB_PER_SEC: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ThrottleConfig {
+    // File traffic per connection, KB/s; 0 is unlimited.
+    #[serde(default)]
+    pub max_kb_per_sec: u64,
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<ThrottleConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt throttle settings: {}", e))),
+        None => Ok(ThrottleConfig::default()),
+    }
+}
+
+pub async fn load(pool: &SqlitePool) {
+    match load_config(pool).await {
+        Ok(config) => MAX_KB_PER_SEC.store(config.max_kb_per_sec, Ordering::Relaxed),
+        Err(e) => tracing::warn!("{}", e),
+    }
+}
+
+/// Book `bytes` on a connection whose allowance is free from `next_free`, at `rate` bytes a
+/// second. Returns how long to wait before sending them.
+fn reserve(next_free: &mut Instant, bytes: usize, rate: u64, now: Instant) -> Duration {
+    let start = (*next_free).max(now);
+    *next_free = start + Duration::from_secs_f64(bytes as f64 / rate as f64);
+    start - now
+}
+
+/// Wait until `bytes` more of file traffic fit under the limit on the connection to `peer`.
+pub async fn pace(state: &AppState, peer: u64, bytes: usize) {
+    let kb_per_sec = MAX_KB_PER_SEC.load(Ordering::Relaxed);
+    if kb_per_sec == 0 {
+        return;
+    }
+    let wait = {
+        let mut pacing = state
+            .transfer_pacing
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if pacing.len() > MAX_TRACKED {
+            pacing.retain(|_, next_free| *next_free > now);
+        }
+        let next_free = pacing.entry(peer).or_insert(now);
+        reserve(next_free, bytes, kb_per_sec * 1024, now)
+    };
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[tauri::command]
+pub async fn get_throttle_config(db: State<'_, SqlitePool>) -> AppResult<ThrottleConfig> {
+    load_config(&db).await
+}
+
+#[tauri::command]
+pub async fn set_throttle_config(
+    db: State<'_, SqlitePool>,
+    config: ThrottleConfig,
+) -> AppResult<()> {
+    if config.max_kb_per_sec != 0 && config.max_kb_per_sec < MIN_KB_PER_SEC {
+        return Err(AppError::Validation(format!(
+            "Allow at least {} KB/s for file transfers, or 0 for no limit",
+            MIN_KB_PER_SEC
+        )));
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    MAX_KB_PER_SEC.store(config.max_kb_per_sec, Ordering::Relaxed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_are_spaced_to_the_rate() {
+        let now = Instant::now();
+        let mut next_free = now;
+        // 64 KB/s: the first 32 KB chunk goes at once, the next half a second later.
+        assert_eq!(
+            reserve(&mut next_free, 32 * 1024, 64 * 1024, now),
+            Duration::ZERO
+        );
+        assert_eq!(
+            reserve(&mut next_free, 32 * 1024, 64 * 1024, now),
+            Duration::from_millis(500)
+        );
+        // A connection that sat idle has nothing banked: it's paced from now.
+        let later = now + Duration::from_secs(10);
+        assert_eq!(
+            reserve(&mut next_free, 32 * 1024, 64 * 1024, later),
+            Duration::ZERO
+        );
+        assert_eq!(next_free, later + Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,329 @@
+// Voice message transcription (optional, host-side, off by default).
+//
+// When an audio attachment is filed, the host passes it to a speech-to-text engine in the
+// background and stores the transcript on its `attachments` row. The transcript is indexed in
+// `attachments_fts`, so `search_all` finds voice messages by what was said. The engine is either a
+// local program run as `<command> [args…] <file>` that prints the transcript (whisper.cpp's
+// `whisper-cli -m <model> -nt -f` fits; it wants 16 kHz WAV), or an HTTP endpoint speaking the
+// OpenAI `audio/transcriptions` API (multipart upload, `{"text": …}` back).
+//
+// The settings live in `app_settings`; the API key lives in the OS keychain.
+
+use crate::attachments::AttachmentRef;
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use crate::sockets::AppState;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+use uuid::Uuid;
+
+const SETTINGS_KEY: &str = "transcription";
+const KEYRING_API_KEY_USER: &str = "transcription-api-key-v1";
+
+/// Long voice messages take a while on a CPU; an engine still busy after this has failed.
+const TRANSCRIBE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptionProvider {
+    // A local program: no audio leaves the machine.
+    #[default]
+    Command,
+    // An OpenAI-compatible `audio/transcriptions` endpoint.
+    Http,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TranscriptionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub provider: TranscriptionProvider,
+    // Path to the engine, e.g. whisper.cpp's `whisper-cli`.
+    #[serde(default)]
+    pub command: Option<String>,
+    // Passed before the file's path, e.g. ["-m", "ggml-base.bin", "-nt", "-f"].
+    #[serde(default)]
+    pub args: Vec<String>,
+    // e.g. "https://api.openai.com/v1/audio/transcriptions"
+    #[serde(default)]
+    pub endpoint: String,
+    #[serde(default = "default_model")]
+    pub model: String,
+}
+
+fn default_model() -> String {
+    "whisper-1".to_string()
+}
+
+impl Default for TranscriptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: TranscriptionProvider::Command,
+            command: None,
+            args: Vec::new(),
+            endpoint: String::new(),
+            model: default_model(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<TranscriptionConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt transcription settings: {}", e))),
+        None => Ok(TranscriptionConfig::default()),
+    }
+}
+
+fn keyring_entry() -> AppResult<keyring::Entry> {
+    keyring::Entry::new(crate::db::KEYRING_SERVICE, KEYRING_API_KEY_USER)
+        .map_err(|e| AppError::Internal(format!("Keychain unavailable: {}", e)))
+}
+
+/// whisper.cpp prints one line per segment (with timestamps unless `-nt`); join them into prose.
+fn clean_transcript(raw: &str) -> String {
+    raw.lines()
+        .map(|l| match l.trim().strip_prefix('[') {
+            // "[00:00:00.000 --> 00:00:02.000]   Hello there"
+            Some(rest) if rest.contains("-->") => rest.split_once(']').map_or("", |(_, t)| t),
+            _ => l,
+        })
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A multipart/form-data body with the model and the file.
+fn multipart_body(
+    boundary: &str,
+    model: &str,
+    filename: &str,
+    mime: &str,
+    audio: &[u8],
+) -> Vec<u8> {
+    let filename = filename.replace(['"', '\r', '\n'], "_");
+    let mut body = format!(
+        "--{b}\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\n{model}\r\n\
+         --{b}\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\njson\r\n\
+         --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\n\
+         Content-Type: {mime}\r\n\r\n",
+        b = boundary,
+    )
+    .into_bytes();
+    body.extend_from_slice(audio);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+async fn transcribe_http(
+    config: &TranscriptionConfig,
+    path: &Path,
+    filename: &str,
+    mime: &str,
+) -> AppResult<String> {
+    use openidconnect::reqwest;
+    let audio = tokio::fs::read(path)
+        .await
+        .map_err(|e| AppError::Internal(format!("Couldn't read {}: {}", filename, e)))?;
+    let boundary = format!("nutler-{}", Uuid::new_v4().simple());
+    let body = multipart_body(&boundary, &config.model, filename, mime, &audio);
+    let http = reqwest::ClientBuilder::new()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(TRANSCRIBE_TIMEOUT)
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to build HTTP client: {}", e)))?;
+    let mut request = http.post(&config.endpoint).header(
+        "Content-Type",
+        format!("multipart/form-data; boundary={}", boundary),
+    );
+    if let Some(key) = keyring_entry().ok().and_then(|e| e.get_password().ok()) {
+        request = request.header("Authorization", format!("Bearer {}", key));
+    }
+    let response = request
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| AppError::Network(format!("Couldn't reach the transcriber: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(AppError::Network(format!(
+            "The transcriber answered {}",
+            response.status()
+        )));
+    }
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| AppError::Network(format!("Couldn't read the transcript: {}", e)))?;
+    let parsed: TranscriptionResponse = serde_json::from_slice(&bytes).map_err(|e| {
+        AppError::Network(format!("The transcriber's answer wasn't understood: {}", e))
+    })?;
+    Ok(parsed.text.trim().to_string())
+}
+
+async fn transcribe_command(command: &str, args: &[String], path: &Path) -> AppResult<String> {
+    let run = tokio::process::Command::new(command)
+        .args(args)
+        .arg(path)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(TRANSCRIBE_TIMEOUT, run).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return Err(AppError::Internal(format!(
+                "The transcriber couldn't run: {}",
+                e
+            )))
+        }
+        Err(_) => return Err(AppError::Internal("The transcriber timed out".to_string())),
+    };
+    if !output.status.success() {
+        return Err(AppError::Internal(format!(
+            "The transcriber failed (exit status {})",
+            output
+                .status
+                .code()
+                .map_or("none".to_string(), |c| c.to_string())
+        )));
+    }
+    Ok(clean_transcript(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Store `transcript` on attachment `id`; the index follows by trigger.
+pub(crate) async fn store_transcript(
+    pool: &SqlitePool,
+    id: i64,
+    transcript: &str,
+) -> AppResult<()> {
+    sqlx::query("UPDATE attachments SET transcript = $1 WHERE id = $2")
+        .bind(transcript)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+async fn transcribe(pool: &SqlitePool, path: PathBuf, attachment: &AttachmentRef) -> AppResult<()> {
+    let config = load_config(pool).await?;
+    if !config.enabled {
+        return Ok(());
+    }
+    let transcript = match config.provider {
+        TranscriptionProvider::Command => {
+            let command = config.command.as_deref().unwrap_or_default();
+            transcribe_command(command, &config.args, &path).await?
+        }
+        TranscriptionProvider::Http => {
+            transcribe_http(&config, &path, &attachment.filename, &attachment.mime).await?
+        }
+    };
+    if transcript.is_empty() {
+        return Ok(());
+    }
+    store_transcript(pool, attachment.id, &transcript).await?;
+    tracing::info!("Transcribed {}", attachment.filename);
+    Ok(())
+}
+
+/// Host: transcribe a just-filed audio attachment in the background, when transcription is on.
+pub(crate) fn after_upload(state: &Arc<AppState>, pool: &SqlitePool, attachment: &AttachmentRef) {
+    if !attachment.mime.starts_with("audio/") {
+        return;
+    }
+    let Some(dir) = state.attachments_dir.get() else {
+        return;
+    };
+    let path = dir.join(attachment.id.to_string());
+    let pool = pool.clone();
+    let attachment = attachment.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = transcribe(&pool, path, &attachment).await {
+            tracing::warn!("Couldn't transcribe {}: {}", attachment.filename, e);
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_transcription_config(db: State<'_, SqlitePool>) -> AppResult<TranscriptionConfig> {
+    load_config(&db).await
+}
+
+/// Save the transcription settings. `api_key`, when given, goes to the OS keychain.
+#[tauri::command]
+pub async fn set_transcription_config(
+    db: State<'_, SqlitePool>,
+    config: TranscriptionConfig,
+    api_key: Option<String>,
+) -> AppResult<()> {
+    if config.enabled {
+        match config.provider {
+            TranscriptionProvider::Command => {
+                let command = config.command.as_deref().unwrap_or_default();
+                if !Path::new(command).is_file() {
+                    return Err(AppError::Validation(format!(
+                        "No transcriber program at {}",
+                        command
+                    )));
+                }
+            }
+            TranscriptionProvider::Http => {
+                let endpoint = config.endpoint.trim();
+                if !(endpoint.starts_with("https://") || endpoint.starts_with("http://")) {
+                    return Err(AppError::Validation(
+                        "The transcriber's address must start with http:// or https://".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+    if let Some(key) = api_key {
+        keyring_entry()?
+            .set_password(&key)
+            .map_err(|e| AppError::Internal(format!("Failed to save the API key: {}", e)))?;
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whisper_output_becomes_one_line() {
+        let raw = "\n[00:00:00.000 --> 00:00:02.500]   The build server is down.\n\
+                   [00:00:02.500 --> 00:00:04.000]   Can someone look?\n";
+        assert_eq!(
+            clean_transcript(raw),
+            "The build server is down. Can someone look?"
+        );
+        assert_eq!(clean_transcript("  plain text \n\n"), "plain text");
+    }
+
+    #[test]
+    fn multipart_carries_model_and_file() {
+        let body = multipart_body("B", "whisper-1", "memo \"1\".ogg", "audio/ogg", b"OggS");
+        let text = String::from_utf8(body).unwrap();
+        assert!(text.starts_with("--B\r\n"));
+        assert!(text.contains("name=\"model\"\r\n\r\nwhisper-1\r\n"));
+        assert!(text.contains("filename=\"memo _1_.ogg\"\r\nContent-Type: audio/ogg\r\n\r\nOggS"));
+        assert!(text.ends_with("\r\n--B--\r\n"));
+    }
+}
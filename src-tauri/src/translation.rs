@@ -0,0 +1,416 @@
+// Message translation (optional, local setting, off by default).
+//
+// `translate_message(message_id, target_lang)` translates a saved message so people in a
+// multilingual office can read each other. The provider is either an HTTP service speaking the
+// LibreTranslate API (self-hosted or a vendor's, with an optional API key), or an offline model
+// run as `<command> [args…] <target_lang>` with the text on stdin and the translation on stdout
+// (e.g. an Argos Translate wrapper). Results are cached in `translations` per message and target
+// language, so each message goes to the provider once; an edited message is translated again.
+//
+// The endpoint and command live in `app_settings`; the API key lives in the OS keychain.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::path::Path;
+use std::time::Duration;
+use tauri::State;
+use tokio::io::AsyncWriteExt;
+
+const SETTINGS_KEY: &str = "translation";
+const KEYRING_API_KEY_USER: &str = "translation-api-key-v1";
+
+/// A provider that hasn't answered by then counts as failed.
+const TRANSLATE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TranslationProvider {
+    // A LibreTranslate-compatible HTTP endpoint.
+    #[default]
+    Http,
+    // A local program: no text leaves the machine.
+    Command,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TranslationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub provider: TranslationProvider,
+    // e.g. "https://translate.corp.example/translate"
+    #[serde(default)]
+    pub endpoint: String,
+    // Path to the offline translator.
+    #[serde(default)]
+    pub command: Option<String>,
+    // Passed before the target language, e.g. ["--from", "auto"].
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Translation {
+    pub message_id: String,
+    pub target_lang: String,
+    pub text: String,
+    // As detected by the provider, when it says.
+    pub source_lang: Option<String>,
+    // True when it came from the cache rather than the provider.
+    pub cached: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct LibreResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+    #[serde(rename = "detectedLanguage", default)]
+    detected_language: Option<LibreDetected>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LibreDetected {
+    language: String,
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<TranslationConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt translation settings: {}", e))),
+        None => Ok(TranslationConfig::default()),
+    }
+}
+
+fn keyring_entry() -> AppResult<keyring::Entry> {
+    keyring::Entry::new(crate::db::KEYRING_SERVICE, KEYRING_API_KEY_USER)
+        .map_err(|e| AppError::Internal(format!("Keychain unavailable: {}", e)))
+}
+
+/// A language tag like "de", "pt-BR" or "zh-Hans", lowercased in its first part.
+fn normalize_lang(lang: &str) -> AppResult<String> {
+    let lang = lang.trim();
+    let mut parts = lang.split('-');
+    let primary = parts.next().unwrap_or_default();
+    let primary_ok =
+        (2..=3).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_alphabetic());
+    let rest_ok =
+        parts.all(|p| (2..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()));
+    if !primary_ok || !rest_ok {
+        return Err(AppError::Validation(format!(
+            "\"{}\" isn't a language code",
+            lang
+        )));
+    }
+    Ok(match lang.split_once('-') {
+        Some((primary, rest)) => format!("{}-{}", primary.to_ascii_lowercase(), rest),
+        None => lang.to_ascii_lowercase(),
+    })
+}
+
+async fn translate_http(
+    config: &TranslationConfig,
+    text: &str,
+    target_lang: &str,
+) -> AppResult<(String, Option<String>)> {
+    use openidconnect::reqwest;
+    let mut body = serde_json::json!({
+        "q": text,
+        "source": "auto",
+        "target": target_lang,
+        "format": "text",
+    });
+    if let Some(key) = keyring_entry().ok().and_then(|e| e.get_password().ok()) {
+        body["api_key"] = key.into();
+    }
+    let http = reqwest::ClientBuilder::new()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(TRANSLATE_TIMEOUT)
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to build HTTP client: {}", e)))?;
+    let response = http
+        .post(&config.endpoint)
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| AppError::Network(format!("Couldn't reach the translator: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(AppError::Network(format!(
+            "The translator answered {}",
+            response.status()
+        )));
+    }
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| AppError::Network(format!("Couldn't read the translation: {}", e)))?;
+    let parsed: LibreResponse = serde_json::from_slice(&bytes).map_err(|e| {
+        AppError::Network(format!("The translator's answer wasn't understood: {}", e))
+    })?;
+    Ok((
+        parsed.translated_text,
+        parsed.detected_language.map(|d| d.language),
+    ))
+}
+
+async fn translate_command(
+    command: &str,
+    args: &[String],
+    text: &str,
+    target_lang: &str,
+) -> AppResult<String> {
+    let mut child = tokio::process::Command::new(command)
+        .args(args)
+        .arg(target_lang)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| AppError::Internal(format!("The translator couldn't run: {}", e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .await
+            .map_err(|e| AppError::Internal(format!("Couldn't pass the text on: {}", e)))?;
+    }
+    let output = match tokio::time::timeout(TRANSLATE_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(AppError::Internal(format!("The translator failed: {}", e))),
+        Err(_) => return Err(AppError::Internal("The translator timed out".to_string())),
+    };
+    if !output.status.success() {
+        return Err(AppError::Internal(format!(
+            "The translator failed (exit status {})",
+            output
+                .status
+                .code()
+                .map_or("none".to_string(), |c| c.to_string())
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The cached translation, if one was made from this exact text.
+async fn cached(
+    pool: &SqlitePool,
+    message_id: &str,
+    target_lang: &str,
+    source_text: &str,
+) -> AppResult<Option<Translation>> {
+    let row = sqlx::query(
+        "SELECT text, source_lang FROM translations
+         WHERE message_id = $1 AND target_lang = $2 AND source_text = $3",
+    )
+    .bind(message_id)
+    .bind(target_lang)
+    .bind(source_text)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|row| Translation {
+        message_id: message_id.to_string(),
+        target_lang: target_lang.to_string(),
+        text: row.get("text"),
+        source_lang: row.get("source_lang"),
+        cached: true,
+    }))
+}
+
+async fn store(pool: &SqlitePool, t: &Translation, source_text: &str) -> AppResult<()> {
+    sqlx::query(
+        "INSERT INTO translations (message_id, target_lang, source_text, source_lang, text)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT(message_id, target_lang) DO UPDATE SET
+             source_text = excluded.source_text, source_lang = excluded.source_lang,
+             text = excluded.text, created_at = CURRENT_TIMESTAMP",
+    )
+    .bind(&t.message_id)
+    .bind(&t.target_lang)
+    .bind(source_text)
+    .bind(&t.source_lang)
+    .bind(&t.text)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn translate_message_internal(
+    pool: &SqlitePool,
+    message_id: &str,
+    target_lang: &str,
+) -> AppResult<Translation> {
+    let target_lang = normalize_lang(target_lang)?;
+    let source_text: String = sqlx::query_scalar(
+        "SELECT message FROM messages
+         WHERE message_id = $1 AND deleted_at IS NULL AND message_type IN ('Chat', 'Code')",
+    )
+    .bind(message_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::Validation("That message can't be translated".to_string()))?;
+    if let Some(hit) = cached(pool, message_id, &target_lang, &source_text).await? {
+        return Ok(hit);
+    }
+
+    let config = load_config(pool).await?;
+    if !config.enabled {
+        return Err(AppError::Validation(
+            "Translation isn't set up on this machine".to_string(),
+        ));
+    }
+    let (text, source_lang) = match config.provider {
+        TranslationProvider::Http => translate_http(&config, &source_text, &target_lang).await?,
+        TranslationProvider::Command => {
+            let command = config.command.as_deref().unwrap_or_default();
+            (
+                translate_command(command, &config.args, &source_text, &target_lang).await?,
+                None,
+            )
+        }
+    };
+    let translation = Translation {
+        message_id: message_id.to_string(),
+        target_lang,
+        text,
+        source_lang,
+        cached: false,
+    };
+    if let Err(e) = store(pool, &translation, &source_text).await {
+        tracing::warn!("Couldn't cache a translation: {}", e);
+    }
+    Ok(translation)
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn translate_message(
+    db: State<'_, SqlitePool>,
+    message_id: String,
+    target_lang: String,
+) -> AppResult<Translation> {
+    translate_message_internal(&db, &message_id, &target_lang).await
+}
+
+#[tauri::command]
+pub async fn get_translation_config(db: State<'_, SqlitePool>) -> AppResult<TranslationConfig> {
+    load_config(&db).await
+}
+
+/// Save the translation settings. `api_key`, when given, goes to the OS keychain.
+#[tauri::command]
+pub async fn set_translation_config(
+    db: State<'_, SqlitePool>,
+    config: TranslationConfig,
+    api_key: Option<String>,
+) -> AppResult<()> {
+    if config.enabled {
+        match config.provider {
+            TranslationProvider::Http => {
+                let endpoint = config.endpoint.trim();
+                if !(endpoint.starts_with("https://") || endpoint.starts_with("http://")) {
+                    return Err(AppError::Validation(
+                        "The translator's address must start with http:// or https://".to_string(),
+                    ));
+                }
+            }
+            TranslationProvider::Command => {
+                let command = config.command.as_deref().unwrap_or_default();
+                if !Path::new(command).is_file() {
+                    return Err(AppError::Validation(format!(
+                        "No translator program at {}",
+                        command
+                    )));
+                }
+            }
+        }
+    }
+    if let Some(key) = api_key {
+        keyring_entry()?
+            .set_password(&key)
+            .map_err(|e| AppError::Internal(format!("Failed to save the API key: {}", e)))?;
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[test]
+    fn language_codes_are_checked_and_normalized() {
+        assert_eq!(normalize_lang(" DE ").unwrap(), "de");
+        assert_eq!(normalize_lang("PT-BR").unwrap(), "pt-BR");
+        assert_eq!(normalize_lang("zh-Hans").unwrap(), "zh-Hans");
+        assert!(normalize_lang("").is_err());
+        assert!(normalize_lang("english").is_err());
+        assert!(normalize_lang("de; rm -rf").is_err());
+    }
+
+    #[test]
+    fn libretranslate_answers_parse() {
+        let r: LibreResponse = serde_json::from_str(
+            r#"{"translatedText":"Hallo","detectedLanguage":{"confidence":90,"language":"en"}}"#,
+        )
+        .unwrap();
+        assert_eq!(r.translated_text, "Hallo");
+        assert_eq!(r.detected_language.unwrap().language, "en");
+        let r: LibreResponse = serde_json::from_str(r#"{"translatedText":"Hola"}"#).unwrap();
+        assert!(r.detected_language.is_none());
+    }
+
+    #[tokio::test]
+    async fn translations_are_cached_until_the_message_changes() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        let t = Translation {
+            message_id: "m1".into(),
+            target_lang: "de".into(),
+            text: "Guten Morgen".into(),
+            source_lang: Some("en".into()),
+            cached: false,
+        };
+        store(&pool, &t, "Good morning").await.unwrap();
+
+        let hit = cached(&pool, "m1", "de", "Good morning")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(hit.text, "Guten Morgen");
+        assert!(hit.cached);
+        assert!(cached(&pool, "m1", "fr", "Good morning")
+            .await
+            .unwrap()
+            .is_none());
+        // Edited since: translated afresh.
+        assert!(cached(&pool, "m1", "de", "Good evening")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn unknown_messages_are_refused() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        assert!(matches!(
+            translate_message_internal(&pool, "missing", "de").await,
+            Err(AppError::Validation(_))
+        ));
+    }
+}
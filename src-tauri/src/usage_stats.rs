@@ -0,0 +1,299 @@
+// Anonymous usage statistics (opt-in, off by default).
+//
+// Once the user turns them on, the app counts how often the UI calls each command and how often
+// each of our modules logs a warning or an error. Only those names and counts are kept: no
+// message text, user or room names, addresses or ids. Counts collect in memory and are saved to
+// `usage_counters` every few minutes. `export_usage_stats` writes the report to a JSON file the
+// user can read, and `submit_usage_stats` posts the same report to the configured URL. Nothing is
+// sent anywhere unless the user submits it. Turning statistics off deletes what was collected.
+
+use crate::db_queries::{get_setting_internal, set_setting_internal};
+use crate::error::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::State;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+const SETTINGS_KEY: &str = "usage_stats";
+
+pub const COMMAND: &str = "command";
+pub const PROBLEM: &str = "problem";
+
+/// How often counts in memory are saved.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Log targets of our own modules start with this.
+const CRATE_TARGET: &str = "nutler_lib::";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// (kind, name) → count, not yet saved.
+static PENDING: Mutex<Option<HashMap<(&'static str, String), u64>>> = Mutex::new(None);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct UsageConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // Where `submit_usage_stats` posts the report (https only).
+    #[serde(default)]
+    pub submit_url: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct UsageReport {
+    pub app_version: String,
+    pub os: String,
+    // When the oldest counter started.
+    pub since: Option<String>,
+    // Command name → times called.
+    pub commands: BTreeMap<String, i64>,
+    // "<module> <level>" → warnings / errors logged.
+    pub problems: BTreeMap<String, i64>,
+}
+
+/// Count one use of `name`, when statistics are on.
+pub fn count(kind: &'static str, name: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    *pending
+        .get_or_insert_with(HashMap::new)
+        .entry((kind, name.to_string()))
+        .or_insert(0) += 1;
+}
+
+/// Wrap the app's command handler so each call is counted by name.
+pub fn counted<R: tauri::Runtime>(
+    handler: impl Fn(tauri::ipc::Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(tauri::ipc::Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        count(COMMAND, invoke.message.command());
+        handler(invoke)
+    }
+}
+
+/// A tracing layer counting the warnings and errors each of our modules logs (not their text).
+pub struct ProblemCounter;
+
+impl<S: tracing::Subscriber> Layer<S> for ProblemCounter {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        if *meta.level() > tracing::Level::WARN {
+            return;
+        }
+        if let Some(module) = meta.target().strip_prefix(CRATE_TARGET) {
+            count(
+                PROBLEM,
+                &format!("{} {}", module, meta.level().as_str().to_lowercase()),
+            );
+        }
+    }
+}
+
+async fn load_config(pool: &SqlitePool) -> AppResult<UsageConfig> {
+    match get_setting_internal(pool, SETTINGS_KEY)
+        .await
+        .map_err(AppError::Db)?
+    {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| AppError::Internal(format!("Corrupt usage statistics config: {}", e))),
+        None => Ok(UsageConfig::default()),
+    }
+}
+
+pub async fn load(pool: &SqlitePool) {
+    match load_config(pool).await {
+        Ok(config) => ENABLED.store(config.enabled, Ordering::Relaxed),
+        Err(e) => tracing::warn!("{}", e),
+    }
+}
+
+/// Save the counts collected in memory.
+pub(crate) async fn flush(pool: &SqlitePool) -> AppResult<()> {
+    let pending = PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .unwrap_or_default();
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let mut tx = pool.begin().await?;
+    for ((kind, name), n) in pending {
+        sqlx::query(
+            "INSERT INTO usage_counters (kind, name, count) VALUES ($1, $2, $3)
+             ON CONFLICT(kind, name) DO UPDATE SET count = count + excluded.count",
+        )
+        .bind(kind)
+        .bind(name)
+        .bind(n as i64)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+pub fn spawn_flusher(pool: SqlitePool) {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = flush(&pool).await {
+                tracing::debug!("Usage statistics not saved: {}", e);
+            }
+        }
+    });
+}
+
+pub async fn report_internal(pool: &SqlitePool) -> AppResult<UsageReport> {
+    flush(pool).await?;
+    let rows: Vec<(String, String, i64)> =
+        sqlx::query_as("SELECT kind, name, count FROM usage_counters")
+            .fetch_all(pool)
+            .await?;
+    let since: Option<String> = sqlx::query_scalar("SELECT MIN(first_seen) FROM usage_counters")
+        .fetch_one(pool)
+        .await?;
+    let mut report = UsageReport {
+        app_version: crate::client_version::APP_VERSION.to_string(),
+        os: std::env::consts::OS.to_string(),
+        since,
+        commands: BTreeMap::new(),
+        problems: BTreeMap::new(),
+    };
+    for (kind, name, n) in rows {
+        match kind.as_str() {
+            COMMAND => report.commands.insert(name, n),
+            _ => report.problems.insert(name, n),
+        };
+    }
+    Ok(report)
+}
+
+/// Forget everything collected so far.
+async fn clear(pool: &SqlitePool) -> AppResult<()> {
+    PENDING.lock().unwrap_or_else(|e| e.into_inner()).take();
+    sqlx::query("DELETE FROM usage_counters")
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_usage_stats_config(db: State<'_, SqlitePool>) -> AppResult<UsageConfig> {
+    load_config(&db).await
+}
+
+/// Turn statistics on or off (off deletes what was collected), and set where they're submitted.
+#[tauri::command]
+pub async fn set_usage_stats_config(
+    db: State<'_, SqlitePool>,
+    config: UsageConfig,
+) -> AppResult<()> {
+    if let Some(url) = &config.submit_url {
+        if !url.starts_with("https://") {
+            return Err(AppError::Validation(
+                "Statistics can only be submitted over https".to_string(),
+            ));
+        }
+    }
+    let json = serde_json::to_string(&config).map_err(|e| AppError::Internal(e.to_string()))?;
+    set_setting_internal(&db, SETTINGS_KEY, &json)
+        .await
+        .map_err(AppError::Db)?;
+    ENABLED.store(config.enabled, Ordering::Relaxed);
+    if !config.enabled {
+        clear(&db).await?;
+    }
+    Ok(())
+}
+
+/// The report as it would be exported or submitted.
+#[tauri::command]
+pub async fn get_usage_stats(db: State<'_, SqlitePool>) -> AppResult<UsageReport> {
+    report_internal(&db).await
+}
+
+/// Write the report to `path` as JSON.
+#[tauri::command]
+pub async fn export_usage_stats(db: State<'_, SqlitePool>, path: String) -> AppResult<()> {
+    let report = report_internal(&db).await?;
+    let json = serde_json::to_vec_pretty(&report).map_err(|e| AppError::Internal(e.to_string()))?;
+    tokio::fs::write(&path, json)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to save {}: {}", path, e)))
+}
+
+/// Post the report to the configured URL.
+#[tauri::command]
+pub async fn submit_usage_stats(db: State<'_, SqlitePool>) -> AppResult<()> {
+    use openidconnect::reqwest;
+    let config = load_config(&db).await?;
+    let Some(url) = config.submit_url.filter(|_| config.enabled) else {
+        return Err(AppError::Validation(
+            "Turn on usage statistics and set where to submit them first".to_string(),
+        ));
+    };
+    let report = report_internal(&db).await?;
+    let body = serde_json::to_string(&report).map_err(|e| AppError::Internal(e.to_string()))?;
+    let http = reqwest::ClientBuilder::new()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(Duration::from_secs(15))
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to build HTTP client: {}", e)))?;
+    let response = http
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| AppError::Network(format!("Couldn't submit statistics: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(AppError::Network(format!(
+            "The statistics server answered {}",
+            response.status()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn counts_are_kept_only_while_enabled() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+
+        count(COMMAND, "ignored");
+        ENABLED.store(true, Ordering::Relaxed);
+        count(COMMAND, "get_chat_rooms");
+        count(COMMAND, "get_chat_rooms");
+        count(PROBLEM, "sockets error");
+        let report = report_internal(&pool).await.unwrap();
+        assert_eq!(report.commands.get("get_chat_rooms"), Some(&2));
+        assert!(!report.commands.contains_key("ignored"));
+        assert_eq!(report.problems.get("sockets error"), Some(&1));
+        assert!(report.since.is_some());
+
+        count(COMMAND, "get_chat_rooms");
+        let report = report_internal(&pool).await.unwrap();
+        assert_eq!(report.commands.get("get_chat_rooms"), Some(&3));
+
+        ENABLED.store(false, Ordering::Relaxed);
+        clear(&pool).await.unwrap();
+        assert!(report_internal(&pool).await.unwrap().commands.is_empty());
+    }
+}
@@ -0,0 +1,245 @@
+// Per-user UI settings, kept on the host.
+//
+// A user's preferences (theme, notification level, muted rooms, …) are one JSON object the UI
+// owns, stored on the host under the user's account id so they follow the user to whichever
+// machine they sign in from. The host sends a client its settings in a SettingsSync frame after
+// Connect. A client saves by sending SettingsSync to the host, which stores it under the
+// connection's user and echoes it back. Each sync a client receives reaches its UI as a
+// `settings_synced` event.
+//
+// One setting is also read here: typing privacy. `sendTyping: false` stops this user's typing
+// indicator going out anywhere, and `noTypingRooms` (room ids) stops it in those rooms. The
+// typing commands check it before sending, and the host checks a client's saved settings before
+// relaying its Typing frames.
+
+use crate::error::{AppError, AppResult};
+use crate::sockets::{
+    frame, send_error_notice, send_secure_client, send_to_client, AppState, Message, MessageType,
+};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tauri::{Emitter, Manager, State};
+
+/// The largest settings object kept, serialized. Well inside one Noise message.
+const MAX_SETTINGS_BYTES: usize = 16 * 1024;
+
+/// The settings as stored: a JSON object no larger than MAX_SETTINGS_BYTES.
+fn serialize(settings: &Value) -> AppResult<String> {
+    if !settings.is_object() {
+        return Err(AppError::Validation(
+            "Settings must be a JSON object".to_string(),
+        ));
+    }
+    let json = settings.to_string();
+    if json.len() > MAX_SETTINGS_BYTES {
+        return Err(AppError::Validation(format!(
+            "Settings can't be larger than {} KB",
+            MAX_SETTINGS_BYTES / 1024
+        )));
+    }
+    Ok(json)
+}
+
+pub async fn get_user_settings_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+) -> AppResult<Option<Value>> {
+    let json: Option<String> =
+        sqlx::query_scalar("SELECT settings FROM user_settings WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+    json.map(|j| {
+        serde_json::from_str(&j)
+            .map_err(|e| AppError::Internal(format!("Corrupt user settings: {}", e)))
+    })
+    .transpose()
+}
+
+pub async fn set_user_settings_internal(
+    pool: &SqlitePool,
+    user_id: i64,
+    settings: &Value,
+) -> AppResult<()> {
+    let json = serialize(settings)?;
+    sqlx::query(
+        "INSERT INTO user_settings (user_id, settings) VALUES ($1, $2)
+         ON CONFLICT(user_id) DO UPDATE
+             SET settings = excluded.settings, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(user_id)
+    .bind(json)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Whether `settings` let others see this user typing in `room_id`.
+pub fn typing_allowed(settings: Option<&Value>, room_id: u64) -> bool {
+    let Some(settings) = settings else {
+        return true;
+    };
+    if settings.get("sendTyping") == Some(&Value::Bool(false)) {
+        return false;
+    }
+    !settings
+        .get("noTypingRooms")
+        .and_then(Value::as_array)
+        .is_some_and(|rooms| rooms.iter().any(|r| r.as_u64() == Some(room_id)))
+}
+
+/// Host: whether `user_id`'s saved settings let others see them typing in `room_id`. Settings
+/// that can't be read count as a no.
+pub(crate) async fn typing_allowed_for(pool: &SqlitePool, user_id: u64, room_id: u64) -> bool {
+    match get_user_settings_internal(pool, user_id as i64).await {
+        Ok(settings) => typing_allowed(settings.as_ref(), room_id),
+        Err(e) => {
+            tracing::warn!("Couldn't load settings for user {}: {}", user_id, e);
+            false
+        }
+    }
+}
+
+fn settings_frame(user_id: u64, settings: &Value) -> Message {
+    frame(MessageType::SettingsSync, user_id, settings.to_string())
+}
+
+/// Host: send a newly registered client its saved settings, if it has any.
+pub(crate) async fn send_saved(state: &Arc<AppState>, pool: &SqlitePool, user_id: u64) {
+    match get_user_settings_internal(pool, user_id as i64).await {
+        Ok(Some(settings)) => {
+            send_to_client(state, user_id, &settings_frame(user_id, &settings)).await
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Couldn't load settings for user {}: {}", user_id, e),
+    }
+}
+
+/// Host: a client's SettingsSync frame, saved under the connection's user and echoed back.
+pub(crate) async fn handle_client_frame(
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    auth_user_id: u64,
+    message: &Message,
+) {
+    let saved = match serde_json::from_str::<Value>(&message.message) {
+        Ok(settings) => set_user_settings_internal(pool, auth_user_id as i64, &settings)
+            .await
+            .map(|()| settings),
+        Err(e) => Err(AppError::Validation(format!("Unreadable settings: {}", e))),
+    };
+    match saved {
+        Ok(settings) => {
+            send_to_client(
+                state,
+                auth_user_id,
+                &settings_frame(auth_user_id, &settings),
+            )
+            .await
+        }
+        Err(e) => send_error_notice(state, auth_user_id, &e.to_string()).await,
+    }
+}
+
+/// Client: settings from the host. Kept for `get_user_settings` and passed to the UI.
+pub(crate) async fn handle_host_frame(app: &tauri::AppHandle, message: &Message) {
+    let Ok(settings) = serde_json::from_str::<Value>(&message.message) else {
+        return;
+    };
+    if let Some(state) = app.try_state::<Arc<AppState>>() {
+        *state.synced_settings.write().await = Some(settings.clone());
+    }
+    if let Err(e) = app.emit("settings_synced", &settings) {
+        tracing::warn!("Failed to emit settings: {}", e);
+    }
+}
+
+/// This user's settings: from the host's store on the host, or as last synced on a client.
+/// None if nothing has been saved yet.
+#[tauri::command]
+pub async fn get_user_settings(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+) -> AppResult<Option<Value>> {
+    if !*state.is_server.read().await {
+        return Ok(state.synced_settings.read().await.clone());
+    }
+    match *state.user_id.read().await {
+        Some(id) => get_user_settings_internal(&db, id as i64).await,
+        None => Ok(None),
+    }
+}
+
+/// Replace this user's settings (the whole object). A client's go to its host, which confirms
+/// them with a SettingsSync (`settings_synced`).
+#[tauri::command]
+pub async fn set_user_settings(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    settings: Value,
+) -> AppResult<()> {
+    serialize(&settings)?;
+    let Some(user_id) = *state.user_id.read().await else {
+        return Err(AppError::Auth("Sign in to save settings".to_string()));
+    };
+    if *state.is_server.read().await {
+        set_user_settings_internal(&db, user_id as i64, &settings).await
+    } else {
+        *state.synced_settings.write().await = Some(settings.clone());
+        send_secure_client(state.inner(), &settings_frame(user_id, &settings))
+            .await
+            .map_err(AppError::Network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn settings_round_trip_per_user() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::db::run_migrations(&pool).await.unwrap();
+        sqlx::raw_sql(
+            "INSERT INTO users (id, name, email, department_id)
+                 VALUES (1, 'Alice', 'a@x', 1), (2, 'Bob', 'b@x', 1);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(get_user_settings_internal(&pool, 1).await.unwrap(), None);
+        let first = json!({"theme": "light", "mutedRooms": [3]});
+        set_user_settings_internal(&pool, 1, &first).await.unwrap();
+        let second = json!({"theme": "dark", "notifications": "mentions"});
+        set_user_settings_internal(&pool, 1, &second).await.unwrap();
+        assert_eq!(
+            get_user_settings_internal(&pool, 1).await.unwrap(),
+            Some(second)
+        );
+        assert_eq!(get_user_settings_internal(&pool, 2).await.unwrap(), None);
+
+        assert!(set_user_settings_internal(&pool, 1, &json!(["theme"]))
+            .await
+            .is_err());
+        let huge = json!({"notes": "x".repeat(MAX_SETTINGS_BYTES)});
+        assert!(set_user_settings_internal(&pool, 1, &huge).await.is_err());
+    }
+
+    #[test]
+    fn typing_can_be_kept_private_everywhere_or_per_room() {
+        assert!(typing_allowed(None, 3));
+        assert!(typing_allowed(Some(&json!({"theme": "dark"})), 3));
+        assert!(!typing_allowed(Some(&json!({"sendTyping": false})), 3));
+        let some_rooms = json!({"sendTyping": true, "noTypingRooms": [3, 7]});
+        assert!(!typing_allowed(Some(&some_rooms), 3));
+        assert!(typing_allowed(Some(&some_rooms), 4));
+    }
+}
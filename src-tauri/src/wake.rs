@@ -0,0 +1,243 @@
+// Waking a backgrounded phone when something new arrives for it.
+//
+// In the background a mobile client sends no heartbeat (connectivity.rs) and the OS may close
+// its socket at any time. So when it goes to the background it asks its host for a wake token
+// (WakeRequest → WakeToken over the live connection), and then, every WAKE_POLL, asks one tiny
+// question on the host's discovery port: "how many unread messages do I have?" One datagram
+// each way, no Noise session. The token is sent in the clear, so the answer is only ever that
+// user's total unread count, and only to a LAN address (like discovery itself).
+//
+// When the count goes up the phone shows a notification and remembers it was woken; back in
+// the foreground it then redials with its resume token rather than trusting the old socket,
+// so the host re-sends unread counts and rooms and the room's missed frames as usual.
+
+use crate::db_queries::get_unread_counts_internal;
+use crate::sockets::{
+    self, frame, is_lan_source, AppState, Message, MessageType, DISCOVERY_MAGIC,
+    DISCOVERY_MAX_PACKET, DISCOVERY_PORT, DISCOVERY_VERSION,
+};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Manager;
+use tauri_plugin_notification::NotificationExt;
+
+/// How often a backgrounded client asks, and how long it waits for the answer.
+const WAKE_POLL: Duration = Duration::from_secs(60);
+const REPLY_WAIT: Duration = Duration::from_secs(3);
+
+/// A wake datagram, sharing the discovery port: kind "wake" (client → host, with its `token`)
+/// or "woken" (host → client, with the `unread` count).
+#[derive(Serialize, Deserialize)]
+struct WakePacket {
+    magic: String,
+    version: u16,
+    kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unread: Option<i64>,
+}
+
+fn packet(kind: &str, token: Option<String>, unread: Option<i64>) -> WakePacket {
+    WakePacket {
+        magic: DISCOVERY_MAGIC.to_string(),
+        version: DISCOVERY_VERSION,
+        kind: kind.to_string(),
+        token,
+        unread,
+    }
+}
+
+/// Parse a datagram of the given kind, or None if it isn't one.
+fn parse(datagram: &[u8], kind: &str) -> Option<WakePacket> {
+    let pkt: WakePacket = serde_json::from_slice(datagram).ok()?;
+    (pkt.magic == DISCOVERY_MAGIC && pkt.version == DISCOVERY_VERSION && pkt.kind == kind)
+        .then_some(pkt)
+}
+
+/// Host: hand `user_id` its wake token, the same one for as long as we host.
+pub(crate) async fn send_token(state: &Arc<AppState>, user_id: u64) {
+    let token = {
+        let mut tokens = state.wake_tokens.lock().await;
+        match tokens.iter().find(|(_, uid)| **uid == user_id) {
+            Some((token, _)) => token.clone(),
+            None => {
+                let token = crate::db::random_hex_key();
+                tokens.insert(token.clone(), user_id);
+                token
+            }
+        }
+    };
+    let msg = frame(MessageType::WakeToken, user_id, token);
+    sockets::send_to_client(state, user_id, &msg).await;
+}
+
+/// Host: the reply to a wake poll from `src`, or None if `datagram` isn't one we answer.
+pub(crate) async fn answer(state: &Arc<AppState>, datagram: &[u8], src: IpAddr) -> Option<Vec<u8>> {
+    let token = parse(datagram, "wake")?.token?;
+    if !is_lan_source(src) {
+        return None;
+    }
+    let user_id = *state.wake_tokens.lock().await.get(&token)?;
+    let counts = get_unread_counts_internal(state.pool.get()?, user_id as i64)
+        .await
+        .ok()?;
+    let unread = counts.iter().map(|c| c.count).sum();
+    serde_json::to_vec(&packet("woken", None, Some(unread))).ok()
+}
+
+/// Client (mobile, going to the background): ask the host for a wake token.
+#[cfg(mobile)]
+pub(crate) async fn request_token(state: &Arc<AppState>) {
+    let user_id = state.user_id.read().await.unwrap_or(0);
+    let msg = frame(MessageType::WakeRequest, user_id, String::new());
+    if let Err(e) = sockets::send_secure_client(state, &msg).await {
+        tracing::debug!("No wake token requested: {}", e);
+    }
+}
+
+/// Client: the host's WakeToken arrived; poll with it while we're in the background.
+pub(crate) async fn handle_host_frame(app: &tauri::AppHandle, msg: &Message) {
+    let Some(state) = app.try_state::<Arc<AppState>>() else {
+        return;
+    };
+    let state = Arc::clone(state.inner());
+    // Already back in the foreground.
+    if state
+        .suspended_at
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .is_none()
+    {
+        return;
+    }
+    let host = match state.client_stream.lock().await.as_ref() {
+        Some(writer) => match writer.peer_addr() {
+            Ok(addr) => addr.ip(),
+            Err(_) => return,
+        },
+        None => return,
+    };
+    let poller = spawn_poller(app.clone(), Arc::clone(&state), host, msg.message.clone());
+    if let Some(old) = state
+        .wake_poller
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .replace(poller)
+    {
+        old.abort();
+    }
+}
+
+/// Client (mobile, back in the foreground): stop polling. True if a poll found something new
+/// since we went to the background.
+#[cfg(mobile)]
+pub(crate) fn stop(state: &Arc<AppState>) -> bool {
+    if let Some(poller) = state
+        .wake_poller
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+    {
+        poller.abort();
+    }
+    state.woken.swap(false, std::sync::atomic::Ordering::SeqCst)
+}
+
+/// Ask the host once; None if it doesn't answer in time.
+async fn poll(host: IpAddr, token: &str) -> Option<i64> {
+    let bind = if host.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = tokio::net::UdpSocket::bind(bind).await.ok()?;
+    let out = serde_json::to_vec(&packet("wake", Some(token.to_string()), None)).ok()?;
+    socket.send_to(&out, (host, DISCOVERY_PORT)).await.ok()?;
+    let mut buf = vec![0u8; DISCOVERY_MAX_PACKET];
+    let (len, src) = tokio::time::timeout(REPLY_WAIT, socket.recv_from(&mut buf))
+        .await
+        .ok()?
+        .ok()?;
+    if src.ip() != host {
+        return None;
+    }
+    parse(&buf[..len], "woken")?.unread
+}
+
+fn spawn_poller(
+    app: tauri::AppHandle,
+    state: Arc<AppState>,
+    host: IpAddr,
+    token: String,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        // The first answer is the baseline: what was unread when we left isn't news.
+        let mut seen: Option<i64> = None;
+        let mut ticker = tokio::time::interval(WAKE_POLL);
+        loop {
+            ticker.tick().await;
+            let Some(unread) = poll(host, &token).await else {
+                continue;
+            };
+            if seen.is_some_and(|seen| unread > seen) {
+                state.woken.store(true, std::sync::atomic::Ordering::SeqCst);
+                notify(&app, &state, unread).await;
+            }
+            seen = Some(unread);
+        }
+    })
+}
+
+/// Show a notification unless this user turned notifications off.
+async fn notify(app: &tauri::AppHandle, state: &Arc<AppState>, unread: i64) {
+    let off = state
+        .synced_settings
+        .read()
+        .await
+        .as_ref()
+        .and_then(|s| s.get("notifications"))
+        .and_then(|v| v.as_str())
+        == Some("off");
+    if off {
+        return;
+    }
+    if let Err(e) = app
+        .notification()
+        .builder()
+        .title("Nutler")
+        .body(format!(
+            "{} unread message{}",
+            unread,
+            if unread == 1 { "" } else { "s" }
+        ))
+        .show()
+    {
+        tracing::warn!("Failed to show wake notification: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wake_datagrams_only_parse_as_their_own_kind() {
+        let poll = serde_json::to_vec(&packet("wake", Some("abc".to_string()), None)).unwrap();
+        assert_eq!(
+            parse(&poll, "wake").and_then(|p| p.token).as_deref(),
+            Some("abc")
+        );
+        assert!(parse(&poll, "woken").is_none());
+        assert!(parse(&poll, "probe").is_none());
+
+        let reply = serde_json::to_vec(&packet("woken", None, Some(4))).unwrap();
+        assert_eq!(parse(&reply, "woken").and_then(|p| p.unread), Some(4));
+        // A discovery probe on the same port is not a wake poll.
+        let probe = br#"{"magic":"nutler-disc","version":1,"kind":"probe","nonce":"n"}"#;
+        assert!(parse(probe, "wake").is_none());
+        assert!(parse(b"not json", "wake").is_none());
+    }
+}
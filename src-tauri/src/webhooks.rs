@@ -0,0 +1,613 @@
+// Incoming webhooks (optional, host-side, off by default).
+//
+// Each webhook is bound to a room and a bot user and is addressed by a random token:
+// `POST http://<host>:<port>/hooks/<token>`. The body is a Slack-compatible payload
+// (`{"text": ..., "channel": ...}` plus `attachments` / `blocks`), so alerting tools that already
+// speak Slack (Grafana, GitLab, Alertmanager relays, …) can post without a custom adapter. Rich
+// content is flattened to a plain-text message.
+//
+// Only a SHA-256 of each token is stored; the token itself is shown once, at creation.
+
+use crate::db_queries::room_join_allowed_internal;
+use crate::error::{AppError, AppResult};
+use crate::room_permissions;
+use crate::sockets::{frame, publish_host_message, AppState, Message, MessageType};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Semaphore;
+
+/// Default port when `start_webhook_listener` is called without one.
+const DEFAULT_WEBHOOK_PORT: u16 = 9465;
+
+/// Request head + body cap. Alert payloads are small; this stops a client streaming forever.
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Requests served at once; more connections are dropped until one finishes, so slow clients
+/// can't pile up.
+const MAX_CONCURRENT_REQUESTS: usize = 16;
+
+#[derive(Serialize)]
+pub struct IncomingWebhook {
+    pub id: i64,
+    pub name: String,
+    pub room_id: i64,
+    pub room_name: String,
+    pub created_at: String,
+}
+
+/// Returned once by `create_incoming_webhook`; the token can't be recovered later.
+#[derive(Serialize)]
+pub struct CreatedWebhook {
+    pub id: i64,
+    pub token: String,
+    // Path to POST to on the webhook listener, e.g. "/hooks/3f…".
+    pub path: String,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+struct SlackPayload {
+    text: Option<String>,
+    channel: Option<String>,
+    attachments: Vec<SlackAttachment>,
+    // Block Kit is open-ended; only the text-bearing parts are read.
+    blocks: Vec<Value>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+struct SlackAttachment {
+    fallback: Option<String>,
+    pretext: Option<String>,
+    title: Option<String>,
+    title_link: Option<String>,
+    text: Option<String>,
+    fields: Vec<SlackField>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+struct SlackField {
+    title: String,
+    value: String,
+}
+
+fn token_hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Rewrite Slack link markup to plain text: `<url|label>` → `label (url)`, `<url>` → `url`,
+/// `<!here>` → `@here`, `<@U123>` → `@U123`. Unclosed `<` is left as-is.
+pub(crate) fn unslack(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('>') else {
+            out.push_str(&rest[open..]);
+            return out;
+        };
+        let inner = &after[..close];
+        if let Some(special) = inner.strip_prefix('!') {
+            out.push('@');
+            out.push_str(special.split('|').next().unwrap_or(special));
+        } else if let Some(user) = inner.strip_prefix('@') {
+            out.push('@');
+            out.push_str(user.split('|').next_back().unwrap_or(user));
+        } else {
+            match inner.split_once('|') {
+                Some((url, label)) => out.push_str(&format!("{} ({})", label, url)),
+                None => out.push_str(inner),
+            }
+        }
+        rest = &after[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn push_line(lines: &mut Vec<String>, text: Option<&str>) {
+    if let Some(text) = text.map(str::trim).filter(|t| !t.is_empty()) {
+        lines.push(unslack(text));
+    }
+}
+
+/// Text-bearing parts of one Block Kit block: `text.text`, `fields[].text` and (for context
+/// blocks) `elements[].text`.
+fn block_lines(block: &Value, lines: &mut Vec<String>) {
+    push_line(lines, block.pointer("/text/text").and_then(Value::as_str));
+    for key in ["fields", "elements"] {
+        if let Some(items) = block.get(key).and_then(Value::as_array) {
+            for item in items {
+                push_line(lines, item.get("text").and_then(Value::as_str));
+            }
+        }
+    }
+}
+
+/// Flatten a Slack payload into one plain message. With blocks present, `text` is Slack's
+/// notification fallback and is not repeated. `None` when nothing displayable remains. Longer
+/// than `max_chars` is cut short with an ellipsis.
+fn payload_text(payload: &SlackPayload, max_chars: usize) -> Option<String> {
+    let mut lines = Vec::new();
+    if payload.blocks.is_empty() {
+        push_line(&mut lines, payload.text.as_deref());
+    } else {
+        for block in &payload.blocks {
+            block_lines(block, &mut lines);
+        }
+    }
+    for att in &payload.attachments {
+        let before = lines.len();
+        push_line(&mut lines, att.pretext.as_deref());
+        match (&att.title, &att.title_link) {
+            (Some(title), Some(link)) => push_line(
+                &mut lines,
+                Some(&format!("{} ({})", title.trim(), link.trim())),
+            ),
+            (title, _) => push_line(&mut lines, title.as_deref()),
+        }
+        push_line(&mut lines, att.text.as_deref());
+        for field in &att.fields {
+            push_line(
+                &mut lines,
+                Some(&format!("{}: {}", field.title.trim(), field.value.trim())),
+            );
+        }
+        if lines.len() == before {
+            push_line(&mut lines, att.fallback.as_deref());
+        }
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    let text = lines.join("\n");
+    if text.chars().count() <= max_chars {
+        Some(text)
+    } else {
+        let cut: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+        Some(format!("{}…", cut))
+    }
+}
+
+struct WebhookTarget {
+    user_id: i64,
+    username: String,
+    room_id: i64,
+}
+
+async fn lookup_token(pool: &SqlitePool, token: &str) -> Result<Option<WebhookTarget>, String> {
+    let row = sqlx::query(
+        "SELECT w.user_id, w.room_id, u.name FROM incoming_webhooks w
+         JOIN users u ON u.id = w.user_id WHERE w.token_hash = $1",
+    )
+    .bind(token_hash(token))
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to look up webhook: {}", e))?;
+    Ok(row.map(|r| WebhookTarget {
+        user_id: r.get("user_id"),
+        username: r.get("name"),
+        room_id: r.get("room_id"),
+    }))
+}
+
+/// The room to post to: the payload's `channel` when it names a non-DM room the webhook's bot
+/// may access and post in, else the webhook's own room. Returns (id, name).
+async fn resolve_room(
+    pool: &SqlitePool,
+    target: &WebhookTarget,
+    channel: Option<&str>,
+) -> Result<(i64, String), String> {
+    if let Some(channel) = channel.map(|c| c.trim().trim_start_matches('#')) {
+        let named: Option<(i64, String)> = sqlx::query_as(
+            "SELECT id, name FROM chat_rooms WHERE name = $1 COLLATE NOCASE AND is_dm = 0",
+        )
+        .bind(channel)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("Failed to look up channel: {}", e))?;
+        if let Some((id, name)) = named {
+            // An announcement-only room keeps posting to its moderators, bots included.
+            if room_join_allowed_internal(pool, target.user_id, id).await?
+                && room_permissions::check(
+                    pool,
+                    id,
+                    Some(target.user_id),
+                    room_permissions::Action::Post,
+                )
+                .await
+                .is_ok()
+            {
+                return Ok((id, name));
+            }
+        }
+    }
+    sqlx::query_as("SELECT id, name FROM chat_rooms WHERE id = $1")
+        .bind(target.room_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| format!("Webhook room is gone: {}", e))
+}
+
+/// Handle one webhook POST. Returns (HTTP status, body) — bodies mirror Slack's
+/// (`ok`, `invalid_payload`, `no_service`, …) so existing integrations log sensible errors.
+async fn handle_post(
+    app: &tauri::AppHandle,
+    state: &Arc<AppState>,
+    pool: &SqlitePool,
+    token: &str,
+    body: &[u8],
+) -> (&'static str, &'static str) {
+    let target = match lookup_token(pool, token).await {
+        Ok(Some(t)) => t,
+        Ok(None) => return ("404 Not Found", "no_service"),
+        Err(e) => {
+            tracing::warn!("Webhook: {}", e);
+            return ("500 Internal Server Error", "server_error");
+        }
+    };
+    let payload: SlackPayload = match serde_json::from_slice(body) {
+        Ok(p) => p,
+        Err(_) => return ("400 Bad Request", "invalid_payload"),
+    };
+    let max_chars = state.max_message_chars.load(Ordering::Relaxed);
+    let Some(text) = payload_text(&payload, max_chars) else {
+        return ("400 Bad Request", "no_text");
+    };
+    let (room_id, room) = match resolve_room(pool, &target, payload.channel.as_deref()).await {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::warn!("Webhook: {}", e);
+            return ("500 Internal Server Error", "server_error");
+        }
+    };
+    let message = Message {
+        username: target.username,
+        room_id: room_id as u64,
+        room,
+        ..frame(MessageType::Chat, target.user_id as u64, text)
+    };
+    publish_host_message(app, state, pool, message).await;
+    ("200 OK", "ok")
+}
+
+/// Read one HTTP/1.x request and answer it. Only `POST /hooks/<token>` is served.
+async fn serve_one(
+    app: tauri::AppHandle,
+    state: Arc<AppState>,
+    pool: SqlitePool,
+    mut stream: tokio::net::TcpStream,
+) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        match tokio::time::timeout(Duration::from_secs(10), stream.read(&mut chunk)).await {
+            Ok(Ok(n)) if n > 0 => buf.extend_from_slice(&chunk[..n]),
+            _ => return,
+        }
+        if buf.len() > MAX_REQUEST_BYTES {
+            return;
+        }
+    };
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|l| l.split_once(':'))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let (status, reply) = if content_length > MAX_REQUEST_BYTES {
+        ("413 Payload Too Large", "payload_too_large")
+    } else {
+        while buf.len() < head_end + content_length {
+            match tokio::time::timeout(Duration::from_secs(10), stream.read(&mut chunk)).await {
+                Ok(Ok(n)) if n > 0 => buf.extend_from_slice(&chunk[..n]),
+                _ => return,
+            }
+        }
+        let body = &buf[head_end..head_end + content_length];
+        let request_line = head.lines().next().unwrap_or_default();
+        let mut parts = request_line.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("POST"), Some(path)) => match path.strip_prefix("/hooks/") {
+                Some(token) if !token.is_empty() && !token.contains('/') => {
+                    handle_post(&app, &state, &pool, token, body).await
+                }
+                _ => ("404 Not Found", "not_found"),
+            },
+            _ => ("405 Method Not Allowed", "method_not_allowed"),
+        }
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reply.len(),
+        reply
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Create a webhook posting into `room_id` as a bot user called `name`. The returned token is
+/// the only copy.
+#[tauri::command]
+pub async fn create_incoming_webhook(
+    db: State<'_, SqlitePool>,
+    name: String,
+    room_id: i64,
+) -> AppResult<CreatedWebhook> {
+    let name = name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(AppError::Validation(
+            "Webhook name must be between 1 and 64 characters".to_string(),
+        ));
+    }
+    let is_dm: Option<bool> = sqlx::query_scalar("SELECT is_dm FROM chat_rooms WHERE id = $1")
+        .bind(room_id)
+        .fetch_optional(db.inner())
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    match is_dm {
+        None => return Err(AppError::Validation("Room not found".to_string())),
+        Some(true) => {
+            return Err(AppError::Validation(
+                "Webhooks can't post into direct messages".to_string(),
+            ))
+        }
+        Some(false) => {}
+    }
+
+    let token = crate::db::random_hex_key();
+    let mut tx = db.begin().await.map_err(|e| AppError::Db(e.to_string()))?;
+    // A dedicated bot user per webhook, so its messages are attributed and can be told apart.
+    // `.invalid` is reserved (RFC 2606), so the address can never collide with a real one.
+    let bot_email = format!("webhook-{}@webhooks.invalid", &token[..12]);
+    let user_id: i64 =
+        sqlx::query_scalar("INSERT INTO users (name, email) VALUES ($1, $2) RETURNING id")
+            .bind(&name)
+            .bind(&bot_email)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| AppError::Db(format!("Failed to create webhook user: {}", e)))?;
+    // Private rooms only accept members; the bot joins its own room.
+    sqlx::query(
+        "INSERT INTO user_rooms (user_id, room_id) VALUES ($1, $2)
+         ON CONFLICT(user_id, room_id) DO UPDATE SET is_active = 1",
+    )
+    .bind(user_id)
+    .bind(room_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| AppError::Db(e.to_string()))?;
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO incoming_webhooks (name, room_id, user_id, token_hash)
+         VALUES ($1, $2, $3, $4) RETURNING id",
+    )
+    .bind(&name)
+    .bind(room_id)
+    .bind(user_id)
+    .bind(token_hash(&token))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| AppError::Db(e.to_string()))?;
+    tx.commit().await.map_err(|e| AppError::Db(e.to_string()))?;
+
+    Ok(CreatedWebhook {
+        id,
+        path: format!("/hooks/{}", token),
+        token,
+    })
+}
+
+#[tauri::command]
+pub async fn list_incoming_webhooks(db: State<'_, SqlitePool>) -> AppResult<Vec<IncomingWebhook>> {
+    let rows = sqlx::query(
+        "SELECT w.id, w.name, w.room_id, cr.name AS room_name, w.created_at
+         FROM incoming_webhooks w JOIN chat_rooms cr ON cr.id = w.room_id
+         ORDER BY w.id",
+    )
+    .fetch_all(db.inner())
+    .await
+    .map_err(|e| AppError::Db(e.to_string()))?;
+    Ok(rows
+        .into_iter()
+        .map(|r| IncomingWebhook {
+            id: r.get("id"),
+            name: r.get("name"),
+            room_id: r.get("room_id"),
+            room_name: r.get("room_name"),
+            created_at: r.get::<Option<String>, _>("created_at").unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// Revoke a webhook. Its bot user and past messages are kept.
+#[tauri::command]
+pub async fn delete_incoming_webhook(db: State<'_, SqlitePool>, id: i64) -> AppResult<()> {
+    sqlx::query("DELETE FROM incoming_webhooks WHERE id = $1")
+        .bind(id)
+        .execute(db.inner())
+        .await
+        .map_err(|e| AppError::Db(e.to_string()))?;
+    Ok(())
+}
+
+/// Start the webhook listener on `port` (default 9465), replacing a running one. It binds
+/// 127.0.0.1 unless `lan` is set, in which case other machines (a Grafana server) can reach it.
+/// Returns the bound address.
+#[tauri::command]
+pub async fn start_webhook_listener(
+    app: tauri::AppHandle,
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, SqlitePool>,
+    port: Option<u16>,
+    lan: bool,
+) -> AppResult<String> {
+    if let Some(old) = state.webhook_server.lock().await.take() {
+        old.abort();
+    }
+    let ip = if lan { "0.0.0.0" } else { "127.0.0.1" };
+    let bind_addr = format!("{}:{}", ip, port.unwrap_or(DEFAULT_WEBHOOK_PORT));
+    let listener = tokio::net::TcpListener::bind(&bind_addr)
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to bind {}: {}", bind_addr, e)))?;
+    let local = listener
+        .local_addr()
+        .map_err(|e| AppError::Network(e.to_string()))?;
+
+    let state_clone = Arc::clone(state.inner());
+    let pool = db.inner().clone();
+    let limiter = Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS));
+    let handle = tauri::async_runtime::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    let Ok(permit) = Arc::clone(&limiter).try_acquire_owned() else {
+                        tracing::warn!("Webhook listener busy; dropping {}", addr);
+                        drop(stream);
+                        continue;
+                    };
+                    let request =
+                        serve_one(app.clone(), Arc::clone(&state_clone), pool.clone(), stream);
+                    tauri::async_runtime::spawn(async move {
+                        request.await;
+                        drop(permit);
+                    });
+                }
+                Err(e) => tracing::warn!("Webhook listener accept failed: {}", e),
+            }
+        }
+    });
+    *state.webhook_server.lock().await = Some(handle);
+    tracing::info!("🪝 Webhook listener on http://{}/hooks/<token>", local);
+    Ok(local.to_string())
+}
+
+#[tauri::command]
+pub async fn stop_webhook_listener(state: State<'_, Arc<AppState>>) -> AppResult<()> {
+    if let Some(handle) = state.webhook_server.lock().await.take() {
+        handle.abort();
+        tracing::info!("🪝 Webhook listener stopped");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::room_permissions::{RoomPermissions, Who};
+    use crate::testing::memory_pool;
+
+    fn parse(json: &str) -> SlackPayload {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn plain_text_payload_with_slack_links() {
+        let p = parse(
+            r##"{"text": "Deploy done <!here>: <https://ci.local/42|build 42> by <@U1|ada>",
+                "channel": "#ops"}"##,
+        );
+        assert_eq!(p.channel.as_deref(), Some("#ops"));
+        assert_eq!(
+            payload_text(&p, 4000).unwrap(),
+            "Deploy done @here: build 42 (https://ci.local/42) by @ada"
+        );
+        assert!(payload_text(&parse(r#"{"text": "  "}"#), 4000).is_none());
+    }
+
+    #[test]
+    fn grafana_style_attachments_flatten_to_lines() {
+        let p = parse(
+            r#"{"attachments": [
+                {"title": "[Alerting] CPU high", "title_link": "http://grafana/d/1",
+                 "text": "node-3 above 90%", "fields": [{"title": "cpu", "value": "97"}]},
+                {"fallback": "only a fallback"}
+            ]}"#,
+        );
+        assert_eq!(
+            payload_text(&p, 4000).unwrap(),
+            "[Alerting] CPU high (http://grafana/d/1)\nnode-3 above 90%\ncpu: 97\nonly a fallback"
+        );
+    }
+
+    #[test]
+    fn blocks_replace_the_fallback_text() {
+        let p = parse(
+            r#"{"text": "fallback",
+                "blocks": [
+                  {"type": "header", "text": {"type": "plain_text", "text": "Pipeline failed"}},
+                  {"type": "section", "fields": [{"type": "mrkdwn", "text": "*Job:* test"}]},
+                  {"type": "context", "elements": [{"type": "mrkdwn", "text": "main @ abc123"}]},
+                  {"type": "divider"}
+                ]}"#,
+        );
+        assert_eq!(
+            payload_text(&p, 4000).unwrap(),
+            "Pipeline failed\n*Job:* test\nmain @ abc123"
+        );
+    }
+
+    #[test]
+    fn long_payloads_are_cut_to_the_hosts_limit() {
+        let p = parse(r#"{"text": "abcdefghij"}"#);
+        assert_eq!(payload_text(&p, 5).unwrap(), "abcd…");
+        assert_eq!(payload_text(&p, 10).unwrap(), "abcdefghij");
+    }
+
+    #[tokio::test]
+    async fn a_channel_override_needs_post_permission() {
+        let pool = memory_pool().await;
+        let bot: i64 = sqlx::query_scalar(
+            "INSERT INTO users (name, email) VALUES ('Alerts', 'a@webhooks.invalid') RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let mut rooms = Vec::new();
+        for name in ["alerts", "ops", "news"] {
+            let id: i64 =
+                sqlx::query_scalar("INSERT INTO chat_rooms (name) VALUES ($1) RETURNING id")
+                    .bind(name)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            rooms.push(id);
+        }
+        let (own, ops, news) = (rooms[0], rooms[1], rooms[2]);
+        let announcements = RoomPermissions {
+            post: Who::Moderators,
+            ..Default::default()
+        };
+        room_permissions::set_internal(&pool, news, &announcements, None)
+            .await
+            .unwrap();
+        let target = WebhookTarget {
+            user_id: bot,
+            username: "Alerts".into(),
+            room_id: own,
+        };
+
+        let resolved = resolve_room(&pool, &target, Some("#ops")).await.unwrap();
+        assert_eq!(resolved.0, ops);
+        // An announcement-only room isn't the bot's to post in: its own room gets it instead.
+        let resolved = resolve_room(&pool, &target, Some("news")).await.unwrap();
+        assert_eq!(resolved, (own, "alerts".to_string()));
+    }
+}
@@ -3,6 +3,7 @@ import { useChatConnection } from "./hooks/useChatConnection";
 import { useTheme } from "./hooks/useTheme";
 import { LoginView } from "./components/LoginView";
 import { Workspace } from "./components/Workspace";
+import { QuickReply } from "./components/QuickReply";
 
 const Chat = () => {
   const c = useChatConnection();
@@ -20,49 +21,97 @@ const Chat = () => {
           serverIp={c.serverIp}
           setServerIp={c.setServerIp}
           onDiscover={c.discoverServers}
+          startupDiscovered={c.startupDiscovered}
+          bindAddress={c.bindAddress}
+          setBindAddress={c.setBindAddress}
+          onListInterfaces={c.listNetworkInterfaces}
           onLogin={c.login}
+          profiles={c.profiles}
+          onSwitchProfile={c.switchProfile}
+          onCreateProfile={c.createProfile}
+          onLoadSavedPassword={c.loadSavedPassword}
+          onRememberPassword={c.rememberPassword}
         />
       </div>
     );
   }
 
   return (
-    <Workspace
-      departments={c.departments}
-      chatRooms={c.chatRooms}
-      currentRoom={c.currentRoom}
-      currentUser={c.currentUser}
-      canonicalUserId={c.canonicalUserId}
-      unreadByRoom={c.unreadByRoom}
-      messages={c.messages}
-      loadingMessages={c.loadingMessages}
-      typingUsers={c.typingUsers}
-      onTyping={c.sendTyping}
-      membersByRoom={c.membersByRoom}
-      connectionStatus={c.connectionStatus}
-      error={c.error}
-      hasMore={c.hasMore}
-      onSelectRoom={c.joinRoom}
-      onCreateRoom={c.createRoom}
-      onSearch={c.searchMessages}
-      onJumpToRoom={c.jumpToRoom}
-      onSendMessage={c.sendMessage}
-      onEditMessage={c.editMessage}
-      onDeleteMessage={c.deleteMessage}
-      reactions={c.reactionsByMessage}
-      onToggleReaction={c.toggleReaction}
-      onLoadOlder={c.loadOlderMessages}
-      directory={c.directory}
-      onAddMember={c.addMember}
-      onCreateDm={c.createDm}
-      onLeaveRoom={c.leaveRoom}
-      onLogout={c.logout}
-      onDismissError={c.dismissError}
-      theme={theme}
-      onToggleTheme={toggleTheme}
-      preferences={c.preferences}
-      onSetPreferences={c.setPreferences}
-    />
+    <>
+      <Workspace
+        departments={c.departments}
+        chatRooms={c.chatRooms}
+        currentRoom={c.currentRoom}
+        currentUser={c.currentUser}
+        canonicalUserId={c.canonicalUserId}
+        unreadByRoom={c.unreadByRoom}
+        messages={c.messages}
+        loadingMessages={c.loadingMessages}
+        typingUsers={c.typingUsers}
+        onTyping={c.sendTyping}
+        membersByRoom={c.membersByRoom}
+        connectionStatus={c.connectionStatus}
+        error={c.error}
+        hasMore={c.hasMore}
+        onSelectRoom={c.joinRoom}
+        onCreateRoom={c.createRoom}
+        onSearch={c.searchMessages}
+        onSearchDirectory={c.searchDirectory}
+        onJumpToRoom={c.jumpToRoom}
+        onSendMessage={c.sendMessage}
+        onListEmoji={c.listEmoji}
+        customEmoji={c.customEmoji}
+        stickerPacks={c.stickerPacks}
+        onSendSticker={c.sendSticker}
+        onShareContact={c.shareContact}
+        onSendCodeSnippet={c.sendCodeSnippet}
+        onSendLocation={c.sendLocation}
+        onSendAttachment={c.sendAttachment}
+        onPasteImage={c.pasteImage}
+        onDownloadAttachment={c.downloadAttachment}
+        thumbnails={c.thumbnails}
+        onLoadThumbnail={c.loadThumbnail}
+        awayUsers={c.awayUsers}
+        reminders={c.reminders}
+        dueReminder={c.dueReminder}
+        onRemindMe={c.remindMe}
+        onCancelReminder={c.cancelReminder}
+        onDismissReminder={c.dismissReminder}
+        isHost={c.isHost}
+        flags={c.flags}
+        onFlagMessage={c.flagMessage}
+        onTranslate={c.translateMessage}
+        onResolveFlag={c.resolveFlag}
+        onGetReach={c.getAnnouncementReach}
+        joinRequests={c.joinRequests}
+        onDecideJoinRequest={c.decideJoinRequest}
+        onCreateInviteLink={c.createInviteLink}
+        onRedeemInvite={c.redeemInvite}
+        onEditMessage={c.editMessage}
+        onDeleteMessage={c.deleteMessage}
+        reactions={c.reactionsByMessage}
+        onToggleReaction={c.toggleReaction}
+        onLoadOlder={c.loadOlderMessages}
+        directory={c.directory}
+        onAddMember={c.addMember}
+        onCreateDm={c.createDm}
+        onLeaveRoom={c.leaveRoom}
+        onExportRoomPdf={(room) => void c.exportRoomPdf(room)}
+        onLogout={c.logout}
+        onDismissError={c.dismissError}
+        theme={theme}
+        onToggleTheme={toggleTheme}
+        preferences={c.preferences}
+        onSetPreferences={c.setPreferences}
+      />
+      {c.quickReplyRoom && (
+        <QuickReply
+          room={c.quickReplyRoom}
+          onSend={c.sendMessage}
+          onClose={c.closeQuickReply}
+        />
+      )}
+    </>
   );
 };
 
@@ -3,6 +3,7 @@ import {
   Send,
   Smile,
   SmilePlus,
+  Sticker as StickerIcon,
   Hash,
   Lock,
   MessageSquare,
@@ -14,19 +15,58 @@ import {
   Trash2,
   Check,
   X,
+  Code2,
+  BellPlus,
+  Flag,
+  Radio,
+  Paperclip,
+  Download,
+  FileText,
+  FileDown,
+  Keyboard,
+  Languages,
+  KeyboardOff,
+  Contact,
+  Phone,
+  Mail,
+  MapPin,
 } from "lucide-react";
-import { ChatRoom, DirectoryUser, Message, Reaction, User } from "../types";
+import {
+  AnnouncementReach,
+  AttachmentRef,
+  ChatRoom,
+  CustomEmoji,
+  DirectoryUser,
+  EmojiEntry,
+  Message,
+  Reaction,
+  SharedLocation,
+  StickerPack,
+  User,
+} from "../types";
 import { InviteModal } from "./InviteModal";
+import { EmojiPicker } from "./EmojiPicker";
+import { StickerPicker } from "./StickerPicker";
+import { ContactPicker } from "./ContactPicker";
+import { SnippetComposer } from "./SnippetComposer";
+import { LocationComposer } from "./LocationComposer";
+import { ReachModal } from "./ReachModal";
 import {
   initials,
   avatarColor,
   formatTime,
   formatDateSeparator,
+  formatBytes,
   sameDay,
   shouldGroup,
   isSystem,
+  mentionsRoom,
   parseMentions,
+  highlightCode,
+  CodeTokenKind,
+  reminderPresets,
 } from "../utils";
+import { Language, renderSystemMessage } from "../i18n";
 
 interface ChatPaneProps {
   room: ChatRoom;
@@ -37,6 +77,13 @@ interface ChatPaneProps {
   canonicalUserId: number | null;
   // Preference: does plain Enter send? (Cmd/Ctrl+Enter always sends.)
   sendOnEnter: boolean;
+  // Preference: language system notices are rendered in.
+  language: Language;
+  // Preference: is our typing indicator kept out of this room?
+  typingHidden: boolean;
+  onToggleTypingHidden: () => void;
+  // Save the room's transcript as a PDF.
+  onExportPdf: (room: ChatRoom) => void;
   messages: Message[];
   loading: boolean;
   hasMore: boolean;
@@ -44,7 +91,37 @@ interface ChatPaneProps {
   memberCount: number;
   typingUsers: string[];
   onTyping: (typing: boolean) => void;
-  onSendMessage: (text: string, isEmoji?: boolean) => void;
+  onSendMessage: (text: string) => void;
+  onListEmoji: (query: string) => Promise<EmojiEntry[]>;
+  // The host's custom emoji, offered as :name: reactions.
+  customEmoji: CustomEmoji[];
+  stickerPacks: StickerPack[];
+  onSendSticker: (packId: number, stickerId: number) => void;
+  onShareContact: (contactUserId: number) => void;
+  onSendCodeSnippet: (
+    code: string,
+    language: string,
+    filename: string,
+  ) => Promise<boolean>;
+  onSendLocation: (
+    label: string,
+    latitude: number | null,
+    longitude: number | null,
+  ) => Promise<boolean>;
+  onSendAttachment: () => void;
+  // Share the image on the OS clipboard (a pasted screenshot).
+  onPasteImage: () => void;
+  onDownloadAttachment: (attachment: AttachmentRef) => void;
+  // Image attachments' thumbnails by id (null: none); onLoadThumbnail asks for a missing one.
+  thumbnails: Record<number, string | null>;
+  onLoadThumbnail: (attachmentId: number) => void;
+  onRemindMe: (msg: Message, at: Date) => void;
+  onFlagMessage: (targetId: string, reason: string) => void;
+  onTranslate: (messageId: string) => Promise<string | null>;
+  // Host mode only: the delivery report for an @room announcement.
+  onGetReach?: (messageId: string) => Promise<AnnouncementReach | null>;
+  // Host mode only: a shareable invite link for a room.
+  onCreateInviteLink?: (roomId: number) => Promise<string | null>;
   onEditMessage: (targetId: string, newText: string) => Promise<void>;
   onDeleteMessage: (targetId: string) => Promise<void>;
   reactions: Record<string, Reaction[]>;
@@ -70,12 +147,33 @@ const EMOJIS = [
   "🙌",
 ];
 
+// A reaction pill's emoji: a custom emoji's image for `:name:`, else the text as-is.
+const ReactionEmoji: React.FC<{
+  emoji: string;
+  customEmoji: CustomEmoji[];
+}> = ({ emoji, customEmoji }) => {
+  const custom = customEmoji.find((c) => `:${c.name}:` === emoji);
+  if (!custom) return <span>{emoji}</span>;
+  return (
+    <img
+      src={custom.url}
+      alt={emoji}
+      title={emoji}
+      className="w-4 h-4 object-contain"
+    />
+  );
+};
+
 export const ChatPane: React.FC<ChatPaneProps> = ({
   room,
   currentUser,
   currentUserId,
   canonicalUserId,
   sendOnEnter,
+  language,
+  typingHidden,
+  onToggleTypingHidden,
+  onExportPdf,
   messages,
   loading,
   hasMore,
@@ -84,6 +182,23 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
   typingUsers,
   onTyping,
   onSendMessage,
+  onListEmoji,
+  customEmoji,
+  stickerPacks,
+  onSendSticker,
+  onShareContact,
+  onSendCodeSnippet,
+  onSendLocation,
+  onSendAttachment,
+  onPasteImage,
+  onDownloadAttachment,
+  thumbnails,
+  onLoadThumbnail,
+  onRemindMe,
+  onFlagMessage,
+  onTranslate,
+  onGetReach,
+  onCreateInviteLink,
   onEditMessage,
   onDeleteMessage,
   reactions,
@@ -99,11 +214,17 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
   const prefix = isDm ? "" : "#";
   const [inputText, setInputText] = useState("");
   const [showEmoji, setShowEmoji] = useState(false);
+  const [showStickers, setShowStickers] = useState(false);
+  const [showContacts, setShowContacts] = useState(false);
   const [showInvite, setShowInvite] = useState(false);
+  const [showSnippet, setShowSnippet] = useState(false);
+  const [showLocation, setShowLocation] = useState(false);
+  const [reach, setReach] = useState<AnnouncementReach | null>(null);
   const [loadingOlder, setLoadingOlder] = useState(false);
   const [editingId, setEditingId] = useState<string | null>(null);
   const [editText, setEditText] = useState("");
   const [reactingId, setReactingId] = useState<string | null>(null);
+  const [remindingId, setRemindingId] = useState<string | null>(null);
 
   const startEdit = (msg: Message) => {
     if (!msg.message_id) return;
@@ -123,6 +244,24 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
     if (!msg.message_id) return;
     if (window.confirm("Delete this message?")) onDeleteMessage(msg.message_id);
   };
+  const showReach = async (msg: Message) => {
+    if (!msg.message_id || !onGetReach) return;
+    const r = await onGetReach(msg.message_id);
+    if (r) setReach(r);
+  };
+  const promptFlag = (msg: Message) => {
+    if (!msg.message_id) return;
+    const reason = window.prompt("Why are you reporting this message?");
+    if (reason?.trim()) onFlagMessage(msg.message_id, reason.trim());
+  };
+  // message_id → its translation, shown under the original.
+  const [translations, setTranslations] = useState<Record<string, string>>({});
+  const translate = async (msg: Message) => {
+    if (!msg.message_id) return;
+    const text = await onTranslate(msg.message_id);
+    if (text != null)
+      setTranslations((prev) => ({ ...prev, [msg.message_id!]: text }));
+  };
   const endRef = useRef<HTMLDivElement>(null);
   const scrollRef = useRef<HTMLDivElement>(null);
   const [atBottom, setAtBottom] = useState(true);
@@ -155,6 +294,7 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
     setEditingId(null);
     setEditText("");
     setReactingId(null);
+    setRemindingId(null);
     restoreRef.current = null;
     loadingOlderRef.current = false;
   }, [room.id]);
@@ -295,6 +435,31 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
               <span className="hidden sm:inline">Add</span>
             </button>
           )}
+          <button
+            onClick={onToggleTypingHidden}
+            title={
+              typingHidden
+                ? "Show when I'm typing here"
+                : "Hide when I'm typing here"
+            }
+            aria-label="Hide when I'm typing here"
+            aria-pressed={typingHidden}
+            className="flex items-center gap-1.5 px-2.5 py-1.5 rounded-md text-sm text-[var(--text-dim)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            {typingHidden ? (
+              <KeyboardOff className="w-4 h-4" />
+            ) : (
+              <Keyboard className="w-4 h-4" />
+            )}
+          </button>
+          <button
+            onClick={() => onExportPdf(room)}
+            title="Export transcript as PDF"
+            aria-label="Export transcript as PDF"
+            className="flex items-center gap-1.5 px-2.5 py-1.5 rounded-md text-sm text-[var(--text-dim)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <FileDown className="w-4 h-4" />
+          </button>
           <button
             onClick={onLeave}
             title="Leave channel"
@@ -314,10 +479,31 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
           selfId={currentUserId}
           selfName={currentUser.name}
           onAdd={(userId) => onAddMember(room.id, userId)}
+          onCreateLink={
+            onCreateInviteLink ? () => onCreateInviteLink(room.id) : undefined
+          }
           onClose={() => setShowInvite(false)}
         />
       )}
 
+      {showSnippet && (
+        <SnippetComposer
+          roomLabel={`${prefix}${title}`}
+          onSend={onSendCodeSnippet}
+          onClose={() => setShowSnippet(false)}
+        />
+      )}
+
+      {showLocation && (
+        <LocationComposer
+          roomLabel={`${prefix}${title}`}
+          onSend={onSendLocation}
+          onClose={() => setShowLocation(false)}
+        />
+      )}
+
+      {reach && <ReachModal reach={reach} onClose={() => setReach(null)} />}
+
       {/* Messages */}
       <div className="relative flex-1 min-h-0">
         <div
@@ -371,7 +557,7 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
                       {showDate && <DateSeparator iso={msg.created_at} />}
                       <div className="flex justify-center my-1.5">
                         <span className="text-[12px] text-[var(--text-faint)] bg-[var(--surface)] px-3 py-1 rounded-full">
-                          {msg.message}
+                          {renderSystemMessage(msg, language)}
                         </span>
                       </div>
                     </React.Fragment>
@@ -387,6 +573,11 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
                   (canonicalUserId != null && msg.user_id === canonicalUserId);
                 const isDeleted = !!msg.deleted_at;
                 const canModify = isMe && !isDeleted && !!msg.message_id;
+                const isSticker = msg.message_type === "Sticker";
+                const isCode = msg.message_type === "Code";
+                const isAttachment = msg.message_type === "Attachment";
+                const isContact = msg.message_type === "Contact";
+                const isLocation = msg.message_type === "Location";
                 const isEditing =
                   editingId != null && editingId === msg.message_id;
                 const msgReactions = msg.message_id
@@ -482,6 +673,24 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
                           <div className="text-sm italic text-[var(--text-faint)]">
                             This message was deleted
                           </div>
+                        ) : isSticker ? (
+                          <StickerMessage
+                            message={msg.message}
+                            packs={stickerPacks}
+                          />
+                        ) : isCode ? (
+                          <CodeSnippet message={msg} />
+                        ) : isContact ? (
+                          <ContactCardMessage message={msg.message} />
+                        ) : isLocation ? (
+                          <LocationMessage message={msg.message} />
+                        ) : isAttachment ? (
+                          <AttachmentCard
+                            message={msg.message}
+                            thumbnails={thumbnails}
+                            onLoadThumbnail={onLoadThumbnail}
+                            onDownload={onDownloadAttachment}
+                          />
                         ) : (
                           <div
                             className={`break-words leading-relaxed max-w-[90%] ${
@@ -501,6 +710,12 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
                                 (edited)
                               </span>
                             )}
+                            {msg.message_id &&
+                              translations[msg.message_id] != null && (
+                                <div className="mt-0.5 text-sm italic text-[var(--text-faint)]">
+                                  {translations[msg.message_id]}
+                                </div>
+                              )}
                           </div>
                         )}
 
@@ -526,7 +741,10 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
                                   r.me ? "Click to remove" : "Click to add"
                                 }
                               >
-                                <span>{r.emoji}</span>
+                                <ReactionEmoji
+                                  emoji={r.emoji}
+                                  customEmoji={customEmoji}
+                                />
                                 <span>{r.count}</span>
                               </button>
                             ))}
@@ -554,16 +772,68 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
                           >
                             <SmilePlus className="w-3.5 h-3.5" />
                           </button>
-                          {canModify && (
-                            <>
+                          <button
+                            onClick={() =>
+                              setRemindingId(
+                                remindingId === msg.message_id
+                                  ? null
+                                  : msg.message_id!,
+                              )
+                            }
+                            title="Remind me"
+                            aria-label="Remind me about this message"
+                            className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)]"
+                          >
+                            <BellPlus className="w-3.5 h-3.5" />
+                          </button>
+                          {onGetReach &&
+                            msg.message_type === "Chat" &&
+                            mentionsRoom(msg.message) && (
                               <button
-                                onClick={() => startEdit(msg)}
-                                title="Edit"
-                                aria-label="Edit message"
+                                onClick={() => void showReach(msg)}
+                                title="Who got this"
+                                aria-label="Show who this announcement reached"
                                 className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)]"
                               >
-                                <Pencil className="w-3.5 h-3.5" />
+                                <Radio className="w-3.5 h-3.5" />
                               </button>
+                            )}
+                          {!isMe && msg.message_type === "Chat" && (
+                            <button
+                              onClick={() => void translate(msg)}
+                              title="Translate"
+                              aria-label="Translate message"
+                              className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)]"
+                            >
+                              <Languages className="w-3.5 h-3.5" />
+                            </button>
+                          )}
+                          {!isMe && (
+                            <button
+                              onClick={() => promptFlag(msg)}
+                              title="Report"
+                              aria-label="Report message to moderators"
+                              className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--danger)] hover:bg-[var(--surface-2)]"
+                            >
+                              <Flag className="w-3.5 h-3.5" />
+                            </button>
+                          )}
+                          {canModify && (
+                            <>
+                              {!isSticker &&
+                                !isCode &&
+                                !isAttachment &&
+                                !isContact &&
+                                !isLocation && (
+                                <button
+                                  onClick={() => startEdit(msg)}
+                                  title="Edit"
+                                  aria-label="Edit message"
+                                  className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)]"
+                                >
+                                  <Pencil className="w-3.5 h-3.5" />
+                                </button>
+                              )}
                               <button
                                 onClick={() => confirmDelete(msg)}
                                 title="Delete"
@@ -596,6 +866,51 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
                               {e}
                             </button>
                           ))}
+                          {customEmoji.map((c) => (
+                            <button
+                              key={c.name}
+                              onClick={() => {
+                                onToggleReaction(
+                                  msg.message_id!,
+                                  `:${c.name}:`,
+                                );
+                                setReactingId(null);
+                              }}
+                              title={`:${c.name}:`}
+                              className="hover:bg-[var(--surface-3)] p-1 rounded-lg transition-colors"
+                              aria-label={`React ${c.name}`}
+                            >
+                              <img
+                                src={c.url}
+                                alt={`:${c.name}:`}
+                                className="w-6 h-6 object-contain"
+                              />
+                            </button>
+                          ))}
+                        </div>
+                      )}
+
+                      {remindingId === msg.message_id && msg.message_id && (
+                        <div
+                          role="menu"
+                          aria-label="Remind me"
+                          className={`absolute top-8 z-50 w-52 bg-[var(--surface-2)] border border-[var(--border)] rounded-xl shadow-2xl p-1 animate-scale-in ${
+                            isMe ? "left-2" : "right-2"
+                          }`}
+                        >
+                          {reminderPresets(new Date()).map((p) => (
+                            <button
+                              key={p.label}
+                              role="menuitem"
+                              onClick={() => {
+                                onRemindMe(msg, p.at);
+                                setRemindingId(null);
+                              }}
+                              className="w-full text-left px-3 py-1.5 rounded-lg text-sm text-[var(--text-dim)] hover:bg-[var(--surface-3)] hover:text-[var(--text)] transition-colors"
+                            >
+                              {p.label}
+                            </button>
+                          ))}
                         </div>
                       )}
                     </div>
@@ -636,23 +951,111 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
           )}
         </div>
         <div className="relative flex items-end gap-2">
+          {showStickers && (
+            <StickerPicker
+              packs={stickerPacks}
+              onPick={(packId, stickerId) => {
+                onSendSticker(packId, stickerId);
+                setShowStickers(false);
+              }}
+            />
+          )}
+          {showContacts && (
+            <ContactPicker
+              users={directory}
+              onPick={(userId) => {
+                onShareContact(userId);
+                setShowContacts(false);
+              }}
+            />
+          )}
           {showEmoji && (
-            <div className="absolute bottom-14 left-0 bg-[var(--surface-2)] border border-[var(--border)] p-2 rounded-xl shadow-2xl grid grid-cols-6 gap-1 z-50 animate-scale-in">
-              {EMOJIS.map((emoji) => (
-                <button
-                  key={emoji}
-                  onClick={() => setInputText((p) => p + emoji)}
-                  className="text-xl hover:bg-[var(--surface-3)] p-1.5 rounded-lg transition-colors"
-                  aria-label={`Insert ${emoji}`}
-                >
-                  {emoji}
-                </button>
-              ))}
-            </div>
+            <EmojiPicker
+              onSearch={onListEmoji}
+              onPick={(emoji) => setInputText((p) => p + emoji)}
+            />
           )}
 
           <button
-            onClick={() => setShowEmoji((s) => !s)}
+            onClick={() => {
+              setShowStickers((s) => !s);
+              setShowEmoji(false);
+              setShowContacts(false);
+            }}
+            aria-label="Sticker picker"
+            className={`p-2.5 rounded-lg transition-colors ${
+              showStickers
+                ? "bg-[var(--surface-3)] text-[var(--text)]"
+                : "text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)]"
+            }`}
+          >
+            <StickerIcon className="w-5 h-5" />
+          </button>
+
+          <button
+            onClick={() => {
+              setShowSnippet(true);
+              setShowEmoji(false);
+              setShowStickers(false);
+            }}
+            aria-label="Share a code snippet"
+            title="Code snippet"
+            className="p-2.5 rounded-lg text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <Code2 className="w-5 h-5" />
+          </button>
+
+          <button
+            onClick={() => {
+              setShowEmoji(false);
+              setShowStickers(false);
+              setShowContacts(false);
+              onSendAttachment();
+            }}
+            aria-label="Share a file"
+            title="Attach a file"
+            className="p-2.5 rounded-lg text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <Paperclip className="w-5 h-5" />
+          </button>
+
+          <button
+            onClick={() => {
+              setShowContacts((s) => !s);
+              setShowEmoji(false);
+              setShowStickers(false);
+            }}
+            aria-label="Share a contact"
+            title="Share a contact"
+            className={`p-2.5 rounded-lg transition-colors ${
+              showContacts
+                ? "bg-[var(--surface-3)] text-[var(--text)]"
+                : "text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)]"
+            }`}
+          >
+            <Contact className="w-5 h-5" />
+          </button>
+
+          <button
+            onClick={() => {
+              setShowLocation(true);
+              setShowEmoji(false);
+              setShowStickers(false);
+              setShowContacts(false);
+            }}
+            aria-label="Share a location"
+            title="Share a location"
+            className="p-2.5 rounded-lg text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <MapPin className="w-5 h-5" />
+          </button>
+
+          <button
+            onClick={() => {
+              setShowEmoji((s) => !s);
+              setShowStickers(false);
+              setShowContacts(false);
+            }}
             aria-label="Emoji picker"
             className={`p-2.5 rounded-lg transition-colors ${
               showEmoji
@@ -674,6 +1077,14 @@ export const ChatPane: React.FC<ChatPaneProps> = ({
               value={inputText}
               onChange={handleInputChange}
               onKeyDown={handleKeyDown}
+              onPaste={(e) => {
+                // An image on the clipboard is shared as a file, not pasted as text.
+                const items = Array.from(e.clipboardData.items);
+                if (items.some((i) => i.type.startsWith("image/"))) {
+                  e.preventDefault();
+                  onPasteImage();
+                }
+              }}
               placeholder={`Message ${prefix}${title}`}
               autoComplete="off"
               className="w-full bg-transparent border-none text-[var(--text)] placeholder-[var(--text-faint)] px-4 py-3 focus:outline-none"
@@ -704,6 +1115,230 @@ const DateSeparator: React.FC<{ iso: string }> = ({ iso }) => (
   </div>
 );
 
+// An Attachment message ({id, filename, size, mime}): the file's name and size, and a button
+// to save a copy from the host. Images also show their thumbnail once it's loaded.
+const AttachmentCard: React.FC<{
+  message: string;
+  thumbnails: Record<number, string | null>;
+  onLoadThumbnail: (attachmentId: number) => void;
+  onDownload: (attachment: AttachmentRef) => void;
+}> = ({ message, thumbnails, onLoadThumbnail, onDownload }) => {
+  let ref: AttachmentRef | null = null;
+  try {
+    ref = JSON.parse(message);
+  } catch {
+    /* shown as a generic attachment below */
+  }
+  const id = typeof ref?.id === "number" ? ref.id : null;
+  const isImage = !!ref?.mime?.startsWith("image/");
+  const thumbnail = id != null ? thumbnails[id] : undefined;
+  useEffect(() => {
+    if (id != null && isImage && thumbnail === undefined) onLoadThumbnail(id);
+  }, [id, isImage, thumbnail, onLoadThumbnail]);
+
+  if (!ref || id == null) {
+    return (
+      <div className="text-sm italic text-[var(--text-faint)]">
+        [Attachment]
+      </div>
+    );
+  }
+  const attachment = ref;
+  return (
+    <div className="flex flex-col gap-1.5 max-w-[90%]">
+      {thumbnail && (
+        <img
+          src={thumbnail}
+          alt={attachment.filename}
+          className="max-w-xs max-h-60 rounded-lg border border-[var(--border)] object-contain"
+        />
+      )}
+      <div className="flex items-center gap-3 bg-[var(--surface-2)] border border-[var(--border)] rounded-xl px-3 py-2">
+        <FileText className="w-5 h-5 shrink-0 text-[var(--text-faint)]" />
+        <div className="min-w-0">
+          <div className="text-sm text-[var(--text)] truncate">
+            {attachment.filename}
+          </div>
+          <div className="text-[11px] text-[var(--text-faint)]">
+            {formatBytes(attachment.size)}
+          </div>
+        </div>
+        <button
+          onClick={() => onDownload(attachment)}
+          aria-label={`Download ${attachment.filename}`}
+          title="Download"
+          className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-3)]"
+        >
+          <Download className="w-4 h-4" />
+        </button>
+      </div>
+    </div>
+  );
+};
+
+// A Contact message ({user_id, name, email, title, phone, department}): a colleague's card, as
+// the host's directory had it when it was shared.
+const ContactCardMessage: React.FC<{ message: string }> = ({ message }) => {
+  let card: {
+    name?: string;
+    email?: string;
+    title?: string;
+    phone?: string;
+    department?: string;
+  } = {};
+  try {
+    card = JSON.parse(message);
+  } catch {
+    /* shown as a generic contact below */
+  }
+  if (!card.name) {
+    return (
+      <div className="text-sm italic text-[var(--text-faint)]">[Contact]</div>
+    );
+  }
+  const subtitle = [card.title, card.department].filter(Boolean).join(" · ");
+  return (
+    <div className="flex items-start gap-3 max-w-[90%] bg-[var(--surface-2)] border border-[var(--border)] rounded-xl px-3 py-2">
+      <div
+        className="flex items-center justify-center w-9 h-9 rounded-full text-xs font-semibold text-white shrink-0"
+        style={{ background: avatarColor(card.name) }}
+      >
+        {initials(card.name)}
+      </div>
+      <div className="min-w-0">
+        <div className="text-sm font-medium text-[var(--text)] truncate">
+          {card.name}
+        </div>
+        {subtitle && (
+          <div className="text-[11px] text-[var(--text-faint)] truncate">
+            {subtitle}
+          </div>
+        )}
+        {card.email && (
+          <div className="flex items-center gap-1.5 mt-1 text-xs text-[var(--text-dim)]">
+            <Mail className="w-3.5 h-3.5 shrink-0" />
+            <span className="truncate select-all">{card.email}</span>
+          </div>
+        )}
+        {card.phone && (
+          <div className="flex items-center gap-1.5 mt-0.5 text-xs text-[var(--text-dim)]">
+            <Phone className="w-3.5 h-3.5 shrink-0" />
+            <span className="truncate select-all">{card.phone}</span>
+          </div>
+        )}
+      </div>
+    </div>
+  );
+};
+
+// A Location message ({label, latitude, longitude}): the place's name and, for a position, its
+// coordinates (selectable, to paste into a map).
+const LocationMessage: React.FC<{ message: string }> = ({ message }) => {
+  let loc: SharedLocation = {};
+  try {
+    loc = JSON.parse(message);
+  } catch {
+    /* shown as a generic location below */
+  }
+  const hasCoords =
+    typeof loc.latitude === "number" && typeof loc.longitude === "number";
+  if (!loc.label && !hasCoords) {
+    return (
+      <div className="text-sm italic text-[var(--text-faint)]">[Location]</div>
+    );
+  }
+  const coords = hasCoords
+    ? `${loc.latitude!.toFixed(5)}, ${loc.longitude!.toFixed(5)}`
+    : null;
+  return (
+    <div className="flex items-center gap-3 max-w-[90%] bg-[var(--surface-2)] border border-[var(--border)] rounded-xl px-3 py-2">
+      <MapPin className="w-5 h-5 shrink-0 text-[var(--accent)]" />
+      <div className="min-w-0">
+        {loc.label && (
+          <div className="text-sm text-[var(--text)] truncate">
+            {loc.label}
+          </div>
+        )}
+        {coords && (
+          <div className="text-[11px] text-[var(--text-faint)] select-all">
+            {coords}
+          </div>
+        )}
+      </div>
+    </div>
+  );
+};
+
+// A Sticker message ({pack_id, sticker_id, name}): the image from our copy of the packs, or
+// its name if that sticker has since been removed.
+const StickerMessage: React.FC<{ message: string; packs: StickerPack[] }> = ({
+  message,
+  packs,
+}) => {
+  let ref: { pack_id?: number; sticker_id?: number; name?: string } = {};
+  try {
+    ref = JSON.parse(message);
+  } catch {
+    /* shown as a generic sticker below */
+  }
+  const sticker = packs
+    .find((p) => p.id === ref.pack_id)
+    ?.stickers.find((s) => s.id === ref.sticker_id);
+  if (!sticker) {
+    return (
+      <div className="text-sm italic text-[var(--text-faint)]">
+        [Sticker{ref.name ? `: ${ref.name}` : ""}]
+      </div>
+    );
+  }
+  return (
+    <img
+      src={sticker.url}
+      alt={sticker.name}
+      title={sticker.name}
+      className="w-32 h-32 object-contain"
+    />
+  );
+};
+
+const CODE_TOKEN_CLASS: Record<CodeTokenKind, string> = {
+  plain: "",
+  string: "text-[var(--online)]",
+  comment: "text-[var(--text-faint)] italic",
+  number: "text-[var(--accent-strong)]",
+  keyword: "text-[var(--accent)] font-semibold",
+};
+
+// A Code message: the snippet verbatim in a scrollable block (whitespace-pre keeps the
+// indentation), under a header naming the file and/or language.
+const CodeSnippet: React.FC<{ message: Message }> = ({ message }) => (
+  <figure className="w-full max-w-[90%] mt-0.5 rounded-lg border border-[var(--border)] bg-[var(--surface-2)] overflow-hidden text-left">
+    {(message.filename || message.language) && (
+      <figcaption className="flex items-center gap-2 px-3 py-1 border-b border-[var(--border)] text-[11px] text-[var(--text-faint)]">
+        {message.filename && (
+          <span className="font-mono text-[var(--text-dim)] truncate">
+            {message.filename}
+          </span>
+        )}
+        {message.language && <span>{message.language}</span>}
+      </figcaption>
+    )}
+    <pre className="px-3 py-2 text-xs leading-relaxed font-mono text-[var(--text)] whitespace-pre overflow-x-auto max-h-96 scrollbar-thin scrollbar-track-transparent">
+      <code>
+        {highlightCode(message.message, message.language).map((t, i) =>
+          t.kind === "plain" ? (
+            t.text
+          ) : (
+            <span key={i} className={CODE_TOKEN_CLASS[t.kind]}>
+              {t.text}
+            </span>
+          ),
+        )}
+      </code>
+    </pre>
+  </figure>
+);
+
 // Render message text with @mentions highlighted (extra emphasis if it's you).
 const MessageText: React.FC<{ text: string; meName: string }> = ({
   text,
@@ -0,0 +1,57 @@
+import React, { useState } from "react";
+import { DirectoryUser } from "../types";
+import { initials, avatarColor } from "../utils";
+
+interface ContactPickerProps {
+  users: DirectoryUser[];
+  onPick: (userId: number) => void;
+}
+
+// Composer contact picker: pick someone from the directory to share their card in the room.
+export const ContactPicker: React.FC<ContactPickerProps> = ({
+  users,
+  onPick,
+}) => {
+  const [query, setQuery] = useState("");
+  const q = query.trim().toLowerCase();
+  const matches = users.filter((u) => u.name.toLowerCase().includes(q));
+
+  return (
+    <div className="absolute bottom-14 left-0 w-72 bg-[var(--surface-2)] border border-[var(--border)] p-2 rounded-xl shadow-2xl z-50 animate-scale-in">
+      <input
+        value={query}
+        onChange={(e) => setQuery(e.target.value)}
+        placeholder="Share someone's contact…"
+        aria-label="Search people"
+        autoFocus
+        className="w-full mb-2 bg-[var(--surface)] border border-[var(--border)] rounded-lg px-3 py-1.5 text-sm text-[var(--text)] placeholder-[var(--text-faint)] focus:outline-none focus:border-[var(--accent)]"
+      />
+      <ul className="max-h-56 overflow-y-auto scrollbar-thin scrollbar-track-transparent">
+        {matches.map((u) => (
+          <li key={u.id}>
+            <button
+              onClick={() => onPick(u.id)}
+              aria-label={`Share ${u.name}'s contact`}
+              className="w-full flex items-center gap-2.5 px-2 py-1.5 rounded-lg text-left hover:bg-[var(--surface-3)] transition-colors"
+            >
+              <span
+                className="flex items-center justify-center w-6 h-6 rounded-full text-[10px] font-semibold text-white shrink-0"
+                style={{ background: avatarColor(u.name) }}
+              >
+                {initials(u.name)}
+              </span>
+              <span className="text-sm text-[var(--text)] truncate">
+                {u.name}
+              </span>
+            </button>
+          </li>
+        ))}
+        {matches.length === 0 && (
+          <li className="px-2 py-4 text-xs text-[var(--text-faint)] text-center">
+            No one matches.
+          </li>
+        )}
+      </ul>
+    </div>
+  );
+};
@@ -11,6 +11,7 @@ interface CreateChannelModalProps {
     description: string,
     departmentId: number | null,
     isPrivate: boolean,
+    announcementOnly: boolean,
   ) => Promise<void>;
   onClose: () => void;
 }
@@ -27,6 +28,7 @@ export const CreateChannelModal: React.FC<CreateChannelModalProps> = ({
     defaultDepartmentId,
   );
   const [isPrivate, setIsPrivate] = useState(false);
+  const [announcementOnly, setAnnouncementOnly] = useState(false);
   const [submitting, setSubmitting] = useState(false);
   const [error, setError] = useState<string | null>(null);
   const trapRef = useFocusTrap<HTMLDivElement>(onClose);
@@ -37,7 +39,13 @@ export const CreateChannelModal: React.FC<CreateChannelModalProps> = ({
     setSubmitting(true);
     setError(null);
     try {
-      await onCreate(name.trim(), description.trim(), departmentId, isPrivate);
+      await onCreate(
+        name.trim(),
+        description.trim(),
+        departmentId,
+        isPrivate,
+        announcementOnly,
+      );
       onClose();
     } catch (err) {
       setError(
@@ -147,6 +155,21 @@ export const CreateChannelModal: React.FC<CreateChannelModalProps> = ({
             </span>
           </label>
 
+          <label className="flex items-center gap-2.5 cursor-pointer select-none">
+            <input
+              type="checkbox"
+              checked={announcementOnly}
+              onChange={(e) => setAnnouncementOnly(e.target.checked)}
+              className="w-4 h-4 accent-[var(--accent)]"
+            />
+            <span className="text-sm text-[var(--text-dim)]">
+              Announcements only{" "}
+              <span className="text-[var(--text-faint)]">
+                (only you and the host can post)
+              </span>
+            </span>
+          </label>
+
           {error && (
             <div
               role="alert"
@@ -0,0 +1,62 @@
+import React, { useState, useEffect, useRef } from "react";
+import { Search } from "lucide-react";
+import { EmojiEntry } from "../types";
+
+interface EmojiPickerProps {
+  onSearch: (query: string) => Promise<EmojiEntry[]>;
+  onPick: (emoji: string) => void;
+}
+
+// Composer emoji picker: the most used emoji, or those whose :shortcode: matches the search.
+// The list comes from the backend so it's the same table `:name:` expands with on send.
+export const EmojiPicker: React.FC<EmojiPickerProps> = ({
+  onSearch,
+  onPick,
+}) => {
+  const [query, setQuery] = useState("");
+  const [entries, setEntries] = useState<EmojiEntry[]>([]);
+
+  // A request token drops out-of-order responses while typing.
+  const reqId = useRef(0);
+  useEffect(() => {
+    const id = ++reqId.current;
+    onSearch(query).then((r) => {
+      if (id === reqId.current) setEntries(r);
+    });
+  }, [query, onSearch]);
+
+  return (
+    <div className="absolute bottom-14 left-0 w-72 bg-[var(--surface-2)] border border-[var(--border)] p-2 rounded-xl shadow-2xl z-50 animate-scale-in">
+      <div className="flex items-center gap-2 px-2 mb-2 h-8 rounded-lg bg-[var(--surface)] border border-[var(--border)]">
+        <Search className="w-3.5 h-3.5 text-[var(--text-faint)] shrink-0" />
+        <input
+          value={query}
+          onChange={(e) => setQuery(e.target.value)}
+          placeholder="Search :shortcode:"
+          aria-label="Search emoji"
+          autoFocus
+          className="flex-1 min-w-0 bg-transparent text-sm text-[var(--text)] placeholder-[var(--text-faint)] focus:outline-none"
+        />
+      </div>
+      {entries.length === 0 ? (
+        <p className="px-2 py-4 text-xs text-[var(--text-faint)] text-center">
+          No emoji found.
+        </p>
+      ) : (
+        <div className="grid grid-cols-8 gap-0.5 max-h-48 overflow-y-auto scrollbar-thin scrollbar-track-transparent">
+          {entries.map((e) => (
+            <button
+              key={e.shortcode}
+              onClick={() => onPick(e.emoji)}
+              title={`:${e.shortcode}:`}
+              className="text-xl hover:bg-[var(--surface-3)] p-1 rounded-lg transition-colors"
+              aria-label={`Insert ${e.shortcode}`}
+            >
+              {e.emoji}
+            </button>
+          ))}
+        </div>
+      )}
+    </div>
+  );
+};
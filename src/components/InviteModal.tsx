@@ -1,5 +1,5 @@
 import React, { useState } from "react";
-import { X, UserPlus, Check, Search } from "lucide-react";
+import { X, UserPlus, Check, Search, Link2, Copy } from "lucide-react";
 import { DirectoryUser } from "../types";
 import { initials, avatarColor } from "../utils";
 import { useFocusTrap } from "../hooks/useFocusTrap";
@@ -10,6 +10,8 @@ interface InviteModalProps {
   selfId: number;
   selfName: string;
   onAdd: (userId: number) => Promise<void> | void;
+  // Host mode: issue a shareable invite link (null if it failed).
+  onCreateLink?: () => Promise<string | null>;
   onClose: () => void;
 }
 
@@ -21,11 +23,14 @@ export const InviteModal: React.FC<InviteModalProps> = ({
   selfId,
   selfName,
   onAdd,
+  onCreateLink,
   onClose,
 }) => {
   const trapRef = useFocusTrap<HTMLDivElement>(onClose);
   const [query, setQuery] = useState("");
   const [added, setAdded] = useState<Set<number>>(new Set());
+  const [link, setLink] = useState<string | null>(null);
+  const [copied, setCopied] = useState(false);
 
   // Exclude self. In client mode currentUserId is the client's LOCAL id while the directory
   // carries the host's CANONICAL ids, so also match by name (the reliable cross-mode key).
@@ -38,6 +43,18 @@ export const InviteModal: React.FC<InviteModalProps> = ({
     await onAdd(u.id);
   };
 
+  const createLink = async () => {
+    if (!onCreateLink) return;
+    setLink(await onCreateLink());
+    setCopied(false);
+  };
+
+  const copyLink = async () => {
+    if (!link) return;
+    await navigator.clipboard.writeText(link);
+    setCopied(true);
+  };
+
   return (
     <div
       className="fixed inset-0 z-50 flex items-start justify-center bg-black/50 p-4 pt-[10vh]"
@@ -116,6 +133,44 @@ export const InviteModal: React.FC<InviteModalProps> = ({
             ))
           )}
         </div>
+
+        {onCreateLink && (
+          <div className="flex items-center gap-2 px-4 py-3 border-t border-[var(--border)]">
+            {link ? (
+              <>
+                <input
+                  readOnly
+                  value={link}
+                  onFocus={(e) => e.target.select()}
+                  aria-label="Invite link"
+                  className="flex-1 min-w-0 bg-[var(--surface-2)] rounded-md px-2 py-1 text-xs text-[var(--text-dim)] focus:outline-none"
+                />
+                <button
+                  onClick={copyLink}
+                  className="flex items-center gap-1.5 px-2.5 py-1 rounded-md text-xs font-medium bg-[var(--surface-3)] text-[var(--text)] hover:bg-[var(--accent)] hover:text-white transition-colors"
+                >
+                  {copied ? (
+                    <>
+                      <Check className="w-3.5 h-3.5" /> Copied
+                    </>
+                  ) : (
+                    <>
+                      <Copy className="w-3.5 h-3.5" /> Copy
+                    </>
+                  )}
+                </button>
+              </>
+            ) : (
+              <button
+                onClick={createLink}
+                className="flex items-center gap-1.5 text-xs text-[var(--text-dim)] hover:text-[var(--text)] transition-colors"
+              >
+                <Link2 className="w-3.5 h-3.5" /> Create an invite link (valid
+                for 24 hours)
+              </button>
+            )}
+          </div>
+        )}
       </div>
     </div>
   );
@@ -0,0 +1,87 @@
+import React from "react";
+import { X, Lock, Check, Ban } from "lucide-react";
+import { JoinRequest } from "../types";
+import { useFocusTrap } from "../hooks/useFocusTrap";
+
+interface JoinRequestsModalProps {
+  requests: JoinRequest[];
+  onDecide: (id: number, approve: boolean) => void;
+  onClose: () => void;
+}
+
+// Pending requests to join the private channels we moderate, oldest first.
+export const JoinRequestsModal: React.FC<JoinRequestsModalProps> = ({
+  requests,
+  onDecide,
+  onClose,
+}) => {
+  const trapRef = useFocusTrap<HTMLDivElement>(onClose);
+
+  return (
+    <div
+      className="fixed inset-0 z-50 flex items-start justify-center bg-black/50 p-4 pt-[10vh]"
+      onMouseDown={onClose}
+      role="dialog"
+      aria-modal="true"
+      aria-label="Join requests"
+    >
+      <div
+        ref={trapRef}
+        className="w-full max-w-md bg-[var(--surface)] border border-[var(--border)] rounded-2xl shadow-2xl animate-scale-in overflow-hidden"
+        onMouseDown={(e) => e.stopPropagation()}
+      >
+        <div className="flex items-center justify-between px-5 h-14 border-b border-[var(--border)]">
+          <h2 className="font-semibold text-[var(--text)]">Join requests</h2>
+          <button
+            onClick={onClose}
+            aria-label="Close"
+            className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <X className="w-4 h-4" />
+          </button>
+        </div>
+
+        <div className="max-h-[50vh] overflow-y-auto scrollbar-thin scrollbar-track-transparent">
+          {requests.length === 0 ? (
+            <p className="px-4 py-6 text-sm text-[var(--text-faint)] text-center">
+              No one is waiting to join.
+            </p>
+          ) : (
+            requests.map((r) => (
+              <div
+                key={r.id}
+                className="flex items-center gap-2 px-4 py-2.5 border-b border-[var(--border-soft)]"
+              >
+                <div className="min-w-0 flex-1">
+                  <div className="text-sm text-[var(--text)] truncate">
+                    {r.username}
+                  </div>
+                  <div className="flex items-center gap-1 text-[11px] text-[var(--text-faint)]">
+                    <Lock className="w-3 h-3" />
+                    <span className="truncate">{r.room}</span>
+                  </div>
+                </div>
+                <button
+                  onClick={() => onDecide(r.id, true)}
+                  title="Let them in"
+                  aria-label={`Approve ${r.username}`}
+                  className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--online)] hover:bg-[var(--surface-2)] transition-colors"
+                >
+                  <Check className="w-4 h-4" />
+                </button>
+                <button
+                  onClick={() => onDecide(r.id, false)}
+                  title="Decline"
+                  aria-label={`Deny ${r.username}`}
+                  className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--danger)] hover:bg-[var(--surface-2)] transition-colors"
+                >
+                  <Ban className="w-4 h-4" />
+                </button>
+              </div>
+            ))
+          )}
+        </div>
+      </div>
+    </div>
+  );
+};
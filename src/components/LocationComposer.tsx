@@ -0,0 +1,140 @@
+import React, { useState } from "react";
+import { Crosshair, X } from "lucide-react";
+import { useFocusTrap } from "../hooks/useFocusTrap";
+
+interface LocationComposerProps {
+  roomLabel: string;
+  onSend: (
+    label: string,
+    latitude: number | null,
+    longitude: number | null,
+  ) => Promise<boolean>;
+  onClose: () => void;
+}
+
+// Share a place: a name ("Meeting room 3B"), this device's position where it has one, or both.
+export const LocationComposer: React.FC<LocationComposerProps> = ({
+  roomLabel,
+  onSend,
+  onClose,
+}) => {
+  const trapRef = useFocusTrap<HTMLDivElement>(onClose);
+  const [label, setLabel] = useState("");
+  const [coords, setCoords] = useState<[number, number] | null>(null);
+  const [locating, setLocating] = useState(false);
+  const [locateError, setLocateError] = useState<string | null>(null);
+  const [sending, setSending] = useState(false);
+  const canLocate = typeof navigator !== "undefined" && !!navigator.geolocation;
+
+  const locate = () => {
+    setLocating(true);
+    setLocateError(null);
+    navigator.geolocation.getCurrentPosition(
+      (pos) => {
+        setCoords([pos.coords.latitude, pos.coords.longitude]);
+        setLocating(false);
+      },
+      (err) => {
+        setLocateError(err.message || "Couldn't get your position");
+        setLocating(false);
+      },
+      { timeout: 15000 },
+    );
+  };
+
+  const ready = !!label.trim() || coords != null;
+  const send = async () => {
+    if (!ready || sending) return;
+    setSending(true);
+    const sent = await onSend(
+      label.trim(),
+      coords ? coords[0] : null,
+      coords ? coords[1] : null,
+    );
+    setSending(false);
+    if (sent) onClose();
+  };
+
+  return (
+    <div
+      className="fixed inset-0 z-50 flex items-start justify-center bg-black/50 p-4 pt-[10vh]"
+      onMouseDown={onClose}
+      role="dialog"
+      aria-modal="true"
+      aria-label={`Share a location in ${roomLabel}`}
+    >
+      <div
+        ref={trapRef}
+        className="w-full max-w-md bg-[var(--surface)] border border-[var(--border)] rounded-2xl shadow-2xl animate-scale-in overflow-hidden"
+        onMouseDown={(e) => e.stopPropagation()}
+      >
+        <div className="flex items-center justify-between px-5 h-14 border-b border-[var(--border)]">
+          <h2 className="font-semibold text-[var(--text)] truncate">
+            Location in {roomLabel}
+          </h2>
+          <button
+            onClick={onClose}
+            aria-label="Close"
+            className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <X className="w-4 h-4" />
+          </button>
+        </div>
+
+        <div className="p-4 space-y-3">
+          <input
+            value={label}
+            onChange={(e) => setLabel(e.target.value)}
+            onKeyDown={(e) => {
+              if (e.key === "Enter") {
+                e.preventDefault();
+                void send();
+              }
+            }}
+            placeholder="Place, e.g. Meeting room 3B"
+            aria-label="Place"
+            maxLength={200}
+            autoFocus
+            className="w-full bg-[var(--surface-2)] border border-[var(--border)] rounded-lg px-3 py-1.5 text-sm text-[var(--text)] placeholder-[var(--text-faint)] focus:outline-none focus:border-[var(--accent)]"
+          />
+          {canLocate && (
+            <div className="flex items-center gap-2 text-xs text-[var(--text-faint)]">
+              <button
+                onClick={locate}
+                disabled={locating}
+                className="inline-flex items-center gap-1.5 px-2.5 py-1.5 rounded-lg text-[var(--text-dim)] bg-[var(--surface-2)] hover:bg-[var(--surface-3)] disabled:opacity-40 transition-colors"
+              >
+                <Crosshair className="w-3.5 h-3.5" />
+                {locating ? "Locating…" : "Use my position"}
+              </button>
+              {coords && (
+                <span>
+                  {coords[0].toFixed(5)}, {coords[1].toFixed(5)}
+                </span>
+              )}
+              {locateError && (
+                <span className="text-[var(--danger)]">{locateError}</span>
+              )}
+            </div>
+          )}
+        </div>
+
+        <div className="flex justify-end gap-2 px-4 pb-4">
+          <button
+            onClick={onClose}
+            className="px-3 py-1.5 rounded-lg text-sm text-[var(--text-dim)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            Cancel
+          </button>
+          <button
+            onClick={() => void send()}
+            disabled={!ready || sending}
+            className="px-3 py-1.5 rounded-lg text-sm bg-[var(--accent)] text-white hover:bg-[var(--accent-strong)] disabled:opacity-40 disabled:cursor-not-allowed transition-colors"
+          >
+            Share location
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+};
@@ -3,7 +3,7 @@ import { type ComponentProps } from "react";
 import { render, screen } from "@testing-library/react";
 import userEvent from "@testing-library/user-event";
 import { LoginView } from "./LoginView";
-import { Department, ServerInfo } from "../types";
+import { Department, NetworkInterface, ServerInfo } from "../types";
 
 const departments: Department[] = [
   { id: 1, name: "Engineering" },
@@ -126,6 +126,38 @@ describe("LoginView", () => {
     expect(screen.queryByText(/no hosts found/i)).not.toBeInTheDocument();
   });
 
+  it("lets a host pick one interface address to listen on", async () => {
+    const user = userEvent.setup();
+    const interfaces: NetworkInterface[] = [
+      {
+        name: "eth0",
+        ipv4: ["192.168.1.20"],
+        ipv6: [],
+        is_up: true,
+        is_loopback: false,
+      },
+      {
+        name: "wlan0",
+        ipv4: ["10.0.0.9"],
+        ipv6: [],
+        is_up: false,
+        is_loopback: false,
+      },
+    ];
+    const setBindAddress = vi.fn();
+    renderLogin({
+      mode: "server",
+      setBindAddress,
+      onListInterfaces: vi.fn().mockResolvedValue(interfaces),
+    });
+
+    const picker = await screen.findByRole("combobox", { name: /listen on/i });
+    // Interfaces that are down aren't offered.
+    expect(screen.queryByText(/wlan0/)).not.toBeInTheDocument();
+    await user.selectOptions(picker, "192.168.1.20");
+    expect(setBindAddress).toHaveBeenCalledWith("192.168.1.20");
+  });
+
   it("clears a restored department that no longer exists", () => {
     localStorage.setItem(
       "nutler.profile",
@@ -7,10 +7,18 @@ import {
   Mail,
   AlertCircle,
   Wifi,
+  Network,
+  UserCircle,
 } from "lucide-react";
-import { Department, ConnectionMode, ServerInfo } from "../types";
+import {
+  Department,
+  ConnectionMode,
+  ServerInfo,
+  NetworkInterface,
+  Profiles,
+} from "../types";
 import { loadProfile } from "../session";
-import { errCode } from "../utils";
+import { errCode, errText } from "../utils";
 
 interface LoginViewProps {
   departments: Department[];
@@ -19,12 +27,25 @@ interface LoginViewProps {
   serverIp: string;
   setServerIp: (ip: string) => void;
   onDiscover?: () => Promise<ServerInfo[]>;
+  // Hosts discovery found at launch, when the last server didn't answer.
+  startupDiscovered?: ServerInfo[];
+  // Host mode: which address to listen on ("" = every interface).
+  bindAddress?: string;
+  setBindAddress?: (address: string) => void;
+  onListInterfaces?: () => Promise<NetworkInterface[]>;
   onLogin: (
     username: string,
     email: string,
     departmentId: number,
     password: string,
   ) => Promise<void>;
+  // Local profiles: who is using this machine. Switching reloads the app.
+  profiles?: Profiles | null;
+  onSwitchProfile?: (id: string) => Promise<void>;
+  onCreateProfile?: (name: string) => Promise<void>;
+  // The active profile's remembered room password (OS keychain).
+  onLoadSavedPassword?: () => Promise<string | null>;
+  onRememberPassword?: (password: string | null) => Promise<void>;
 }
 
 const inputClass =
@@ -37,7 +58,16 @@ export const LoginView: React.FC<LoginViewProps> = ({
   serverIp,
   setServerIp,
   onDiscover,
+  startupDiscovered,
+  bindAddress = "",
+  setBindAddress,
+  onListInterfaces,
   onLogin,
+  profiles,
+  onSwitchProfile,
+  onCreateProfile,
+  onLoadSavedPassword,
+  onRememberPassword,
 }) => {
   // Pre-fill from the remembered profile (never the password).
   const [username, setUsername] = useState(() => loadProfile().username ?? "");
@@ -46,12 +76,17 @@ export const LoginView: React.FC<LoginViewProps> = ({
     () => loadProfile().departmentId ?? null,
   );
   const [password, setPassword] = useState("");
+  const [remember, setRemember] = useState(false);
+  // "New profile" name entry; null while not adding one.
+  const [newProfile, setNewProfile] = useState<string | null>(null);
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
   // LAN discovery (client mode): results + state for the "Find hosts" affordance.
   const [discovered, setDiscovered] = useState<ServerInfo[]>([]);
   const [discovering, setDiscovering] = useState(false);
   const [discoverNote, setDiscoverNote] = useState<string | null>(null);
+  // Host mode: the addresses of this machine's interfaces that are up.
+  const [interfaces, setInterfaces] = useState<NetworkInterface[]>([]);
 
   const handleDiscover = async () => {
     if (!onDiscover || discovering) return;
@@ -69,9 +104,49 @@ export const LoginView: React.FC<LoginViewProps> = ({
       setDiscovering(false);
     }
   };
+  useEffect(() => {
+    if (startupDiscovered?.length) setDiscovered(startupDiscovered);
+  }, [startupDiscovered]);
+
   // If the form is pre-filled, drop the user straight on the password field.
   const [returning] = useState(() => !!loadProfile().username);
 
+  // Pre-fill a password this profile remembered.
+  useEffect(() => {
+    if (!onLoadSavedPassword) return;
+    let active = true;
+    onLoadSavedPassword().then((saved) => {
+      if (active && saved) {
+        setPassword(saved);
+        setRemember(true);
+      }
+    });
+    return () => {
+      active = false;
+    };
+  }, [onLoadSavedPassword]);
+
+  const handleProfile = async (id: string) => {
+    if (id === "__new__") {
+      setNewProfile("");
+      return;
+    }
+    try {
+      await onSwitchProfile?.(id);
+    } catch (err) {
+      setError(errText(err));
+    }
+  };
+
+  const handleCreateProfile = async () => {
+    if (!newProfile?.trim()) return;
+    try {
+      await onCreateProfile?.(newProfile.trim());
+    } catch (err) {
+      setError(errText(err));
+    }
+  };
+
   // Drop stale discovery results/notes when switching connection mode (the component stays
   // mounted across the client/server toggle, so this state would otherwise persist).
   useEffect(() => {
@@ -79,6 +154,29 @@ export const LoginView: React.FC<LoginViewProps> = ({
     setDiscoverNote(null);
   }, [mode]);
 
+  // Offer the host's interfaces to bind; a remembered address that's gone falls back to all.
+  useEffect(() => {
+    if (mode !== "server" || !onListInterfaces) return;
+    let active = true;
+    onListInterfaces()
+      .then((list) => {
+        if (!active) return;
+        const up = list.filter((i) => i.is_up);
+        setInterfaces(up);
+        const known = up.some((i) =>
+          [...i.ipv4, ...i.ipv6].includes(bindAddress),
+        );
+        if (bindAddress && !known) setBindAddress?.("");
+      })
+      .catch(() => {
+        if (active) setInterfaces([]);
+      });
+    return () => {
+      active = false;
+    };
+    // Only when entering host mode: re-listing on every pick would be needless.
+  }, [mode, onListInterfaces]);
+
   // A restored department id may no longer exist (department list changed, or a
   // corrupt profile). Once the live list loads, drop a stale id so the form can't
   // submit a blank-but-truthy selection into a wrong/None room.
@@ -99,6 +197,7 @@ export const LoginView: React.FC<LoginViewProps> = ({
     setError(null);
     try {
       await onLogin(username, email, departmentId, password);
+      await onRememberPassword?.(remember ? password : null);
     } catch (err) {
       // The backend returns a typed error code (auth = wrong password, network = unreachable).
       const code = errCode(err);
@@ -163,6 +262,65 @@ export const LoginView: React.FC<LoginViewProps> = ({
         </button>
       </div>
 
+      {profiles && onSwitchProfile && (
+        <div className="mb-4">
+          {newProfile === null ? (
+            <Field icon={<UserCircle className="w-4 h-4" />} label="Profile">
+              <select
+                value={profiles.active}
+                onChange={(e) => void handleProfile(e.target.value)}
+                aria-label="Profile"
+                className={`${inputClass} appearance-none cursor-pointer`}
+              >
+                {profiles.profiles.map((p) => (
+                  <option key={p.id} value={p.id}>
+                    {p.name}
+                  </option>
+                ))}
+                {onCreateProfile && (
+                  <option value="__new__">New profile…</option>
+                )}
+              </select>
+            </Field>
+          ) : (
+            <div className="flex gap-2">
+              <input
+                type="text"
+                value={newProfile}
+                onChange={(e) => setNewProfile(e.target.value)}
+                onKeyDown={(e) => {
+                  if (e.key === "Enter") {
+                    e.preventDefault();
+                    void handleCreateProfile();
+                  } else if (e.key === "Escape") {
+                    setNewProfile(null);
+                  }
+                }}
+                placeholder="Profile name"
+                aria-label="New profile name"
+                autoFocus
+                className="flex-1 bg-[var(--surface-2)] border border-[var(--border)] rounded-xl py-2.5 px-4 text-[var(--text)] placeholder-[var(--text-faint)] focus:outline-none focus:border-[var(--accent)] transition-colors"
+              />
+              <button
+                type="button"
+                onClick={() => void handleCreateProfile()}
+                disabled={!newProfile.trim()}
+                className="px-4 rounded-xl bg-[var(--accent)] hover:bg-[var(--accent-strong)] text-white text-sm font-semibold disabled:opacity-50"
+              >
+                Create
+              </button>
+              <button
+                type="button"
+                onClick={() => setNewProfile(null)}
+                className="px-3 rounded-xl text-sm text-[var(--text-dim)] hover:text-[var(--text)]"
+              >
+                Cancel
+              </button>
+            </div>
+          )}
+        </div>
+      )}
+
       <form className="space-y-3.5" onSubmit={handleSubmit}>
         {mode === "client" && (
           <div className="space-y-2">
@@ -228,6 +386,26 @@ export const LoginView: React.FC<LoginViewProps> = ({
           </div>
         )}
 
+        {mode === "server" && setBindAddress && interfaces.length > 0 && (
+          <Field icon={<Network className="w-4 h-4" />} label="Listen on">
+            <select
+              value={bindAddress}
+              onChange={(e) => setBindAddress(e.target.value)}
+              aria-label="Listen on"
+              className={`${inputClass} appearance-none cursor-pointer`}
+            >
+              <option value="">All network interfaces</option>
+              {interfaces.flatMap((i) =>
+                [...i.ipv4, ...i.ipv6].map((ip) => (
+                  <option key={`${i.name}-${ip}`} value={ip}>
+                    {i.name} — {ip}
+                  </option>
+                )),
+              )}
+            </select>
+          </Field>
+        )}
+
         <Field icon={<Lock className="w-4 h-4" />} label="Room password">
           <input
             type="password"
@@ -243,6 +421,17 @@ export const LoginView: React.FC<LoginViewProps> = ({
             className={inputClass}
           />
         </Field>
+        {onRememberPassword && (
+          <label className="flex items-center gap-2 text-xs text-[var(--text-dim)] cursor-pointer select-none">
+            <input
+              type="checkbox"
+              checked={remember}
+              onChange={(e) => setRemember(e.target.checked)}
+              className="accent-[var(--accent)]"
+            />
+            Remember the password for this profile
+          </label>
+        )}
 
         <Field icon={<User className="w-4 h-4" />} label="Username">
           <input
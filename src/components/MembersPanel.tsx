@@ -4,6 +4,8 @@ import { initials, avatarColor } from "../utils";
 export interface Member {
   name: string;
   online: boolean;
+  // Idle (auto-away); only meaningful while online.
+  away?: boolean;
   isYou: boolean;
 }
 
@@ -28,7 +30,14 @@ export const MembersPanel: React.FC<MembersPanelProps> = ({ members }) => {
         </div>
         <span
           className="absolute -bottom-0.5 -right-0.5 w-2.5 h-2.5 rounded-full border-2 border-[var(--surface)]"
-          style={{ background: m.online ? "var(--online)" : "#57606a" }}
+          style={{
+            background: !m.online
+              ? "#57606a"
+              : m.away
+                ? "#d29922"
+                : "var(--online)",
+          }}
+          title={!m.online ? "Offline" : m.away ? "Away" : "Online"}
         />
       </div>
       <span
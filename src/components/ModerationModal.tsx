@@ -0,0 +1,115 @@
+import React from "react";
+import { X, Hash, Check, Trash2 } from "lucide-react";
+import { FlaggedMessage } from "../types";
+import { formatSearchTime } from "../utils";
+import { useFocusTrap } from "../hooks/useFocusTrap";
+
+interface ModerationModalProps {
+  flags: FlaggedMessage[];
+  onJump: (roomId: number) => void;
+  onResolve: (flagId: number, deleteMessage: boolean) => void;
+  onClose: () => void;
+}
+
+// The host's queue of reported messages, oldest first. Each report can be dismissed, or the
+// message deleted for everyone.
+export const ModerationModal: React.FC<ModerationModalProps> = ({
+  flags,
+  onJump,
+  onResolve,
+  onClose,
+}) => {
+  const trapRef = useFocusTrap<HTMLDivElement>(onClose);
+
+  const remove = (f: FlaggedMessage) => {
+    if (window.confirm("Delete this message for everyone?")) {
+      onResolve(f.id, true);
+    }
+  };
+
+  return (
+    <div
+      className="fixed inset-0 z-50 flex items-start justify-center bg-black/50 p-4 pt-[10vh]"
+      onMouseDown={onClose}
+      role="dialog"
+      aria-modal="true"
+      aria-label="Reported messages"
+    >
+      <div
+        ref={trapRef}
+        className="w-full max-w-lg bg-[var(--surface)] border border-[var(--border)] rounded-2xl shadow-2xl animate-scale-in overflow-hidden"
+        onMouseDown={(e) => e.stopPropagation()}
+      >
+        <div className="flex items-center justify-between px-5 h-14 border-b border-[var(--border)]">
+          <h2 className="font-semibold text-[var(--text)]">
+            Reported messages
+          </h2>
+          <button
+            onClick={onClose}
+            aria-label="Close"
+            className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <X className="w-4 h-4" />
+          </button>
+        </div>
+
+        <div className="max-h-[50vh] overflow-y-auto scrollbar-thin scrollbar-track-transparent">
+          {flags.length === 0 ? (
+            <p className="px-4 py-6 text-sm text-[var(--text-faint)] text-center">
+              Nothing to review.
+            </p>
+          ) : (
+            flags.map((f) => (
+              <div
+                key={f.id}
+                className="flex items-center gap-2 px-4 py-2.5 border-b border-[var(--border-soft)] hover:bg-[var(--surface-2)] transition-colors"
+              >
+                <button
+                  onClick={() => {
+                    onJump(f.room_id);
+                    onClose();
+                  }}
+                  className="min-w-0 flex-1 text-left"
+                >
+                  <div className="flex items-center gap-1.5 text-[11px] text-[var(--text-faint)]">
+                    <Hash className="w-3 h-3" />
+                    <span className="truncate">{f.room}</span>
+                    <span>·</span>
+                    <span className="shrink-0">
+                      {formatSearchTime(f.created_at)}
+                    </span>
+                  </div>
+                  <div className="text-sm text-[var(--text-dim)] truncate">
+                    <span className="font-medium text-[var(--text)]">
+                      {f.author ?? "Unknown"}:
+                    </span>{" "}
+                    {f.message}
+                  </div>
+                  <div className="text-xs text-[var(--text-faint)] truncate">
+                    {f.reporter ?? "Someone"}: “{f.reason}”
+                  </div>
+                </button>
+                <button
+                  onClick={() => onResolve(f.id, false)}
+                  title="Dismiss report"
+                  aria-label="Dismiss report"
+                  className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-3)] transition-colors"
+                >
+                  <Check className="w-4 h-4" />
+                </button>
+                <button
+                  onClick={() => remove(f)}
+                  title="Delete message"
+                  aria-label="Delete message"
+                  className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--danger)] hover:bg-[var(--surface-3)] transition-colors"
+                >
+                  <Trash2 className="w-4 h-4" />
+                </button>
+              </div>
+            ))
+          )}
+        </div>
+      </div>
+    </div>
+  );
+};
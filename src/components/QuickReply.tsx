@@ -0,0 +1,76 @@
+import React, { useState } from "react";
+import { MessageCircle, X } from "lucide-react";
+import { ChatRoom } from "../types";
+import { useFocusTrap } from "../hooks/useFocusTrap";
+
+interface QuickReplyProps {
+  room: ChatRoom;
+  onSend: (text: string) => Promise<void>;
+  onClose: () => void;
+}
+
+// The quick-reply popover (global shortcut): one line to the most recent DM, then out of the way.
+export const QuickReply: React.FC<QuickReplyProps> = ({
+  room,
+  onSend,
+  onClose,
+}) => {
+  const trapRef = useFocusTrap<HTMLDivElement>(onClose);
+  const [text, setText] = useState("");
+  const [sending, setSending] = useState(false);
+  const label = room.display_name || room.name;
+
+  const send = async () => {
+    if (!text.trim() || sending) return;
+    setSending(true);
+    await onSend(text.trim());
+    setSending(false);
+    onClose();
+  };
+
+  return (
+    <div
+      className="fixed inset-0 z-50 flex items-start justify-center bg-black/50 p-4 pt-[20vh]"
+      onMouseDown={onClose}
+      role="dialog"
+      aria-modal="true"
+      aria-label={`Quick reply to ${label}`}
+    >
+      <div
+        ref={trapRef}
+        className="w-full max-w-md bg-[var(--surface)] border border-[var(--border)] rounded-2xl shadow-2xl animate-scale-in overflow-hidden"
+        onMouseDown={(e) => e.stopPropagation()}
+      >
+        <div className="flex items-center justify-between px-4 h-12 border-b border-[var(--border)]">
+          <h2 className="flex items-center gap-2 text-sm font-semibold text-[var(--text)] truncate">
+            <MessageCircle className="w-4 h-4 text-[var(--text-faint)]" />
+            Reply to {label}
+          </h2>
+          <button
+            onClick={onClose}
+            aria-label="Close"
+            className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <X className="w-4 h-4" />
+          </button>
+        </div>
+        <div className="p-4">
+          <input
+            value={text}
+            onChange={(e) => setText(e.target.value)}
+            onKeyDown={(e) => {
+              if (e.key === "Enter") {
+                e.preventDefault();
+                void send();
+              }
+            }}
+            placeholder={`Message ${label}`}
+            aria-label={`Message ${label}`}
+            autoFocus
+            className="w-full bg-[var(--surface-2)] border border-[var(--border)] rounded-lg px-3 py-2 text-sm text-[var(--text)] placeholder-[var(--text-faint)] focus:outline-none focus:border-[var(--accent)]"
+          />
+        </div>
+      </div>
+    </div>
+  );
+};
@@ -0,0 +1,143 @@
+import React, { useState, useEffect, useRef } from "react";
+import { Search, Hash, Loader2 } from "lucide-react";
+import { DirectoryHit } from "../types";
+import { initials, avatarColor } from "../utils";
+import { useFocusTrap } from "../hooks/useFocusTrap";
+
+interface QuickSwitcherProps {
+  onSearch: (query: string) => Promise<DirectoryHit[]>;
+  onPick: (hit: DirectoryHit) => void;
+  onClose: () => void;
+}
+
+// Ctrl/Cmd+K: jump to a room or a person's DM by (fuzzy) name. Arrow keys move, Enter picks.
+export const QuickSwitcher: React.FC<QuickSwitcherProps> = ({
+  onSearch,
+  onPick,
+  onClose,
+}) => {
+  const [query, setQuery] = useState("");
+  const [results, setResults] = useState<DirectoryHit[]>([]);
+  const [active, setActive] = useState(0);
+  const [loading, setLoading] = useState(false);
+  const timer = useRef<ReturnType<typeof setTimeout> | undefined>(undefined);
+  const trapRef = useFocusTrap<HTMLDivElement>(onClose);
+
+  // Debounced (shorter than message search — this is a switcher); a request token guards
+  // against out-of-order responses.
+  const reqId = useRef(0);
+  useEffect(() => {
+    const q = query.trim();
+    if (timer.current) clearTimeout(timer.current);
+    if (!q) {
+      setResults([]);
+      setLoading(false);
+      return;
+    }
+    setLoading(true);
+    const id = ++reqId.current;
+    timer.current = setTimeout(async () => {
+      const r = await onSearch(q);
+      if (id !== reqId.current) return;
+      setResults(r);
+      setActive(0);
+      setLoading(false);
+    }, 120);
+    return () => {
+      if (timer.current) clearTimeout(timer.current);
+    };
+  }, [query, onSearch]);
+
+  const pick = (hit: DirectoryHit) => {
+    onPick(hit);
+    onClose();
+  };
+
+  const onKeyDown = (e: React.KeyboardEvent) => {
+    if (e.key === "ArrowDown" && results.length) {
+      e.preventDefault();
+      setActive((i) => (i + 1) % results.length);
+    } else if (e.key === "ArrowUp" && results.length) {
+      e.preventDefault();
+      setActive((i) => (i - 1 + results.length) % results.length);
+    } else if (e.key === "Enter" && results[active]) {
+      e.preventDefault();
+      pick(results[active]);
+    }
+  };
+
+  return (
+    <div
+      className="fixed inset-0 z-50 flex items-start justify-center bg-black/50 p-4 pt-[10vh]"
+      onMouseDown={onClose}
+      role="dialog"
+      aria-modal="true"
+      aria-label="Jump to"
+    >
+      <div
+        ref={trapRef}
+        className="w-full max-w-lg bg-[var(--surface)] border border-[var(--border)] rounded-2xl shadow-2xl animate-scale-in overflow-hidden"
+        onMouseDown={(e) => e.stopPropagation()}
+      >
+        <div className="flex items-center gap-2 px-4 h-14 border-b border-[var(--border)]">
+          <Search className="w-4 h-4 text-[var(--text-faint)] shrink-0" />
+          <input
+            value={query}
+            onChange={(e) => setQuery(e.target.value)}
+            onKeyDown={onKeyDown}
+            placeholder="Jump to a channel or person…"
+            aria-label="Jump to"
+            className="flex-1 bg-transparent text-[var(--text)] placeholder-[var(--text-faint)] focus:outline-none"
+          />
+          {loading && (
+            <Loader2 className="w-4 h-4 animate-spin text-[var(--text-faint)]" />
+          )}
+        </div>
+
+        <div
+          className="max-h-[50vh] overflow-y-auto scrollbar-thin scrollbar-track-transparent"
+          role="listbox"
+          aria-label="Matches"
+        >
+          {query.trim() && !loading && results.length === 0 ? (
+            <p className="px-4 py-6 text-sm text-[var(--text-faint)] text-center">
+              No matches.
+            </p>
+          ) : (
+            results.map((hit, i) => (
+              <button
+                key={`${hit.kind}-${hit.id}`}
+                role="option"
+                aria-selected={i === active}
+                onMouseEnter={() => setActive(i)}
+                onClick={() => pick(hit)}
+                className={`w-full text-left px-4 py-2 flex items-center gap-3 transition-colors ${
+                  i === active ? "bg-[var(--surface-2)]" : ""
+                }`}
+              >
+                {hit.kind === "room" ? (
+                  <Hash className="w-4 h-4 text-[var(--text-faint)] shrink-0" />
+                ) : (
+                  <div
+                    className="flex items-center justify-center w-6 h-6 rounded-full text-[10px] font-semibold text-white shrink-0"
+                    style={{ background: avatarColor(hit.name) }}
+                  >
+                    {initials(hit.name)}
+                  </div>
+                )}
+                <span className="text-sm text-[var(--text)] truncate">
+                  {hit.name}
+                </span>
+                {hit.detail && (
+                  <span className="text-[11px] text-[var(--text-faint)] truncate">
+                    {hit.detail}
+                  </span>
+                )}
+              </button>
+            ))
+          )}
+        </div>
+      </div>
+    </div>
+  );
+};
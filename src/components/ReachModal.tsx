@@ -0,0 +1,91 @@
+import React from "react";
+import { X, CheckCheck, CircleSlash } from "lucide-react";
+import { AnnouncementReach } from "../types";
+import { formatSearchTime } from "../utils";
+import { useFocusTrap } from "../hooks/useFocusTrap";
+
+interface ReachModalProps {
+  reach: AnnouncementReach;
+  onClose: () => void;
+}
+
+// Host only: which connected users an @room announcement reached, and who missed it (they were
+// elsewhere in the workspace, or never acknowledged it).
+export const ReachModal: React.FC<ReachModalProps> = ({ reach, onClose }) => {
+  const trapRef = useFocusTrap<HTMLDivElement>(onClose);
+  const total = reach.delivered.length + reach.undelivered.length;
+
+  return (
+    <div
+      className="fixed inset-0 z-50 flex items-start justify-center bg-black/50 p-4 pt-[10vh]"
+      onMouseDown={onClose}
+      role="dialog"
+      aria-modal="true"
+      aria-label="Announcement reach"
+    >
+      <div
+        ref={trapRef}
+        className="w-full max-w-md bg-[var(--surface)] border border-[var(--border)] rounded-2xl shadow-2xl animate-scale-in overflow-hidden"
+        onMouseDown={(e) => e.stopPropagation()}
+      >
+        <div className="flex items-center justify-between px-5 h-14 border-b border-[var(--border)]">
+          <h2 className="font-semibold text-[var(--text)]">
+            Reached {reach.delivered.length} of {total}
+          </h2>
+          <button
+            onClick={onClose}
+            aria-label="Close"
+            className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <X className="w-4 h-4" />
+          </button>
+        </div>
+
+        <div className="max-h-[50vh] overflow-y-auto scrollbar-thin scrollbar-track-transparent py-2">
+          {total === 0 && (
+            <p className="px-4 py-4 text-sm text-[var(--text-faint)] text-center">
+              Nobody else was connected when this was sent.
+            </p>
+          )}
+          {reach.undelivered.length > 0 && (
+            <>
+              <div className="px-4 pt-2 pb-1 text-[11px] uppercase tracking-wide text-[var(--text-faint)]">
+                Missed
+              </div>
+              {reach.undelivered.map((r) => (
+                <div
+                  key={r.user_id}
+                  className="flex items-center gap-2 px-4 py-1.5 text-sm text-[var(--text-dim)]"
+                >
+                  <CircleSlash className="w-3.5 h-3.5 text-[var(--danger)]" />
+                  <span className="truncate">{r.username}</span>
+                </div>
+              ))}
+            </>
+          )}
+          {reach.delivered.length > 0 && (
+            <>
+              <div className="px-4 pt-2 pb-1 text-[11px] uppercase tracking-wide text-[var(--text-faint)]">
+                Delivered
+              </div>
+              {reach.delivered.map((r) => (
+                <div
+                  key={r.user_id}
+                  className="flex items-center gap-2 px-4 py-1.5 text-sm text-[var(--text-dim)]"
+                >
+                  <CheckCheck className="w-3.5 h-3.5 text-[var(--online)]" />
+                  <span className="truncate flex-1">{r.username}</span>
+                  {r.delivered_at && (
+                    <span className="text-[11px] text-[var(--text-faint)]">
+                      {formatSearchTime(r.delivered_at)}
+                    </span>
+                  )}
+                </div>
+              ))}
+            </>
+          )}
+        </div>
+      </div>
+    </div>
+  );
+};
@@ -0,0 +1,92 @@
+import React from "react";
+import { X, Hash, BellOff } from "lucide-react";
+import { Reminder } from "../types";
+import { formatSearchTime } from "../utils";
+import { useFocusTrap } from "../hooks/useFocusTrap";
+
+interface RemindersModalProps {
+  reminders: Reminder[];
+  onJump: (roomId: number) => void;
+  onCancel: (id: number) => void;
+  onClose: () => void;
+}
+
+// Pending message reminders, soonest first. Picking one opens its room; each can be cancelled.
+export const RemindersModal: React.FC<RemindersModalProps> = ({
+  reminders,
+  onJump,
+  onCancel,
+  onClose,
+}) => {
+  const trapRef = useFocusTrap<HTMLDivElement>(onClose);
+
+  return (
+    <div
+      className="fixed inset-0 z-50 flex items-start justify-center bg-black/50 p-4 pt-[10vh]"
+      onMouseDown={onClose}
+      role="dialog"
+      aria-modal="true"
+      aria-label="Reminders"
+    >
+      <div
+        ref={trapRef}
+        className="w-full max-w-lg bg-[var(--surface)] border border-[var(--border)] rounded-2xl shadow-2xl animate-scale-in overflow-hidden"
+        onMouseDown={(e) => e.stopPropagation()}
+      >
+        <div className="flex items-center justify-between px-5 h-14 border-b border-[var(--border)]">
+          <h2 className="font-semibold text-[var(--text)]">Reminders</h2>
+          <button
+            onClick={onClose}
+            aria-label="Close"
+            className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <X className="w-4 h-4" />
+          </button>
+        </div>
+
+        <div className="max-h-[50vh] overflow-y-auto scrollbar-thin scrollbar-track-transparent">
+          {reminders.length === 0 ? (
+            <p className="px-4 py-6 text-sm text-[var(--text-faint)] text-center">
+              No reminders. Use “Remind me” on a message to add one.
+            </p>
+          ) : (
+            reminders.map((r) => (
+              <div
+                key={r.id}
+                className="flex items-center gap-2 px-4 py-2.5 border-b border-[var(--border-soft)] hover:bg-[var(--surface-2)] transition-colors"
+              >
+                <button
+                  onClick={() => {
+                    onJump(r.room_id);
+                    onClose();
+                  }}
+                  className="min-w-0 flex-1 text-left"
+                >
+                  <div className="flex items-center gap-1.5 text-[11px] text-[var(--text-faint)]">
+                    <Hash className="w-3 h-3" />
+                    <span className="truncate">{r.room}</span>
+                    <span>·</span>
+                    <span className="shrink-0">
+                      {formatSearchTime(r.remind_at)}
+                    </span>
+                  </div>
+                  <div className="text-sm text-[var(--text-dim)] truncate">
+                    {r.preview}
+                  </div>
+                </button>
+                <button
+                  onClick={() => onCancel(r.id)}
+                  title="Cancel reminder"
+                  aria-label="Cancel reminder"
+                  className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--danger)] hover:bg-[var(--surface-3)] transition-colors"
+                >
+                  <BellOff className="w-4 h-4" />
+                </button>
+              </div>
+            ))
+          )}
+        </div>
+      </div>
+    </div>
+  );
+};
@@ -121,7 +121,9 @@ export const SearchModal: React.FC<SearchModalProps> = ({
                     </span>
                   </div>
                   <div className="text-sm text-[var(--text-dim)] truncate">
-                    {r.message}
+                    {r.image_text != null
+                      ? `🖼 ${r.image_text}`
+                      : r.message}
                   </div>
                 </div>
               </button>
@@ -4,7 +4,13 @@ import userEvent from "@testing-library/user-event";
 import { SettingsModal } from "./SettingsModal";
 import { Preferences } from "../preferences";
 
-const prefs: Preferences = { notifications: "all", sendOnEnter: true };
+const prefs: Preferences = {
+  notifications: "all",
+  sendOnEnter: true,
+  language: "en",
+  sendTyping: true,
+  noTypingRooms: [],
+};
 
 const renderSettings = (overrides: Partial<Preferences> = {}) => {
   const onToggleTheme = vi.fn();
@@ -54,6 +60,24 @@ describe("SettingsModal", () => {
     expect(onSetPreferences).toHaveBeenCalledWith({ sendOnEnter: false });
   });
 
+  it("turns off the typing indicator", async () => {
+    const user = userEvent.setup();
+    const { onSetPreferences } = renderSettings();
+    const sw = screen.getByRole("switch", { name: /show when i'm typing/i });
+    expect(sw).toBeChecked();
+    await user.click(sw);
+    expect(onSetPreferences).toHaveBeenCalledWith({ sendTyping: false });
+  });
+
+  it("switches the system-message language", async () => {
+    const user = userEvent.setup();
+    const { onSetPreferences } = renderSettings();
+    const select = screen.getByRole("combobox", { name: /system messages/i });
+    expect(select).toHaveValue("en");
+    await user.selectOptions(select, "de");
+    expect(onSetPreferences).toHaveBeenCalledWith({ language: "de" });
+  });
+
   it("closes from the header button", async () => {
     const user = userEvent.setup();
     const { onClose } = renderSettings();
@@ -1,7 +1,8 @@
 import React from "react";
-import { X, Sun, Moon, Bell } from "lucide-react";
+import { X, Sun, Moon, Bell, Languages } from "lucide-react";
 import { Theme } from "../hooks/useTheme";
 import { Preferences, NotificationMode } from "../preferences";
+import { LANGUAGES, isLanguage } from "../i18n";
 import { useFocusTrap } from "../hooks/useFocusTrap";
 
 interface SettingsModalProps {
@@ -141,6 +142,38 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({
             </div>
           </section>
 
+          {/* Language */}
+          <section>
+            <h3 className="flex items-center gap-1.5 text-[11px] font-semibold uppercase tracking-wider text-[var(--text-faint)] mb-2">
+              <Languages className="w-3.5 h-3.5" /> Language
+            </h3>
+            <div className="flex items-center justify-between gap-3">
+              <label htmlFor="language" className="min-w-0">
+                <span className="block text-sm text-[var(--text)]">
+                  System messages
+                </span>
+                <span className="block text-[11px] text-[var(--text-faint)]">
+                  Joins, leaves and room switches
+                </span>
+              </label>
+              <select
+                id="language"
+                value={preferences.language}
+                onChange={(e) =>
+                  isLanguage(e.target.value) &&
+                  onSetPreferences({ language: e.target.value })
+                }
+                className="bg-[var(--surface-2)] border border-[var(--border)] rounded-lg px-2 py-1.5 text-sm text-[var(--text)]"
+              >
+                {LANGUAGES.map((l) => (
+                  <option key={l.value} value={l.value}>
+                    {l.label}
+                  </option>
+                ))}
+              </select>
+            </div>
+          </section>
+
           {/* Composer */}
           <section>
             <h3 className="text-[11px] font-semibold uppercase tracking-wider text-[var(--text-faint)] mb-2">
@@ -178,6 +211,38 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({
                 />
               </button>
             </div>
+            <div className="flex items-center justify-between gap-3 mt-3">
+              <label htmlFor="send-typing" className="min-w-0">
+                <span className="block text-sm text-[var(--text)]">
+                  Show when I'm typing
+                </span>
+                <span className="block text-[11px] text-[var(--text-faint)]">
+                  {preferences.sendTyping
+                    ? "Others see a typing indicator while you write."
+                    : "Nobody sees when you're typing."}
+                </span>
+              </label>
+              <button
+                id="send-typing"
+                type="button"
+                role="switch"
+                aria-checked={preferences.sendTyping}
+                onClick={() =>
+                  onSetPreferences({ sendTyping: !preferences.sendTyping })
+                }
+                className={`relative w-10 h-6 rounded-full shrink-0 transition-colors ${
+                  preferences.sendTyping
+                    ? "bg-[var(--accent)]"
+                    : "bg-[var(--surface-3)]"
+                }`}
+              >
+                <span
+                  className={`absolute top-0.5 left-0.5 w-5 h-5 rounded-full bg-white shadow transition-transform ${
+                    preferences.sendTyping ? "translate-x-4" : ""
+                  }`}
+                />
+              </button>
+            </div>
           </section>
         </div>
       </div>
@@ -1,4 +1,4 @@
-import React, { useState } from "react";
+import React, { useState, useEffect } from "react";
 import {
   Hash,
   LogOut,
@@ -9,11 +9,19 @@ import {
   Search,
   MessageSquare,
   Settings,
+  Bell,
+  ShieldAlert,
+  UserCheck,
+  Link2,
 } from "lucide-react";
 import {
   ChatRoom,
   Department,
+  DirectoryHit,
   DirectoryUser,
+  FlaggedMessage,
+  JoinRequest,
+  Reminder,
   SearchResult,
   User,
 } from "../types";
@@ -23,8 +31,12 @@ import { Preferences } from "../preferences";
 import { initials, avatarColor } from "../utils";
 import { CreateChannelModal } from "./CreateChannelModal";
 import { SearchModal } from "./SearchModal";
+import { QuickSwitcher } from "./QuickSwitcher";
 import { NewDmModal } from "./NewDmModal";
 import { SettingsModal } from "./SettingsModal";
+import { RemindersModal } from "./RemindersModal";
+import { ModerationModal } from "./ModerationModal";
+import { JoinRequestsModal } from "./JoinRequestsModal";
 
 interface SidebarProps {
   departments: Department[];
@@ -40,10 +52,24 @@ interface SidebarProps {
     description: string,
     departmentId: number | null,
     isPrivate: boolean,
+    announcementOnly: boolean,
   ) => Promise<void>;
   onCreateDm: (targetIds: number[]) => Promise<void> | void;
   onSearch: (query: string) => Promise<SearchResult[]>;
+  onSearchDirectory: (query: string) => Promise<DirectoryHit[]>;
   onJumpToRoom: (roomId: number) => void;
+  // We've gone Away (idle); shown in place of "Online" while connected.
+  isAway: boolean;
+  reminders: Reminder[];
+  onCancelReminder: (id: number) => void;
+  // Host mode only: the moderation queue.
+  isHost: boolean;
+  flags: FlaggedMessage[];
+  onResolveFlag: (flagId: number, deleteMessage: boolean) => void;
+  // Requests to join private channels we moderate.
+  joinRequests: JoinRequest[];
+  onDecideJoinRequest: (id: number, approve: boolean) => void;
+  onRedeemInvite: (link: string) => void;
   onLogout: () => void;
   theme: Theme;
   onToggleTheme: () => void;
@@ -57,6 +83,8 @@ const statusMeta: Record<ConnectionStatus, { color: string; label: string }> = {
   disconnected: { color: "var(--danger)", label: "Disconnected" },
 };
 
+const awayMeta = { color: "#d29922", label: "Away" };
+
 export const Sidebar: React.FC<SidebarProps> = ({
   departments,
   chatRooms,
@@ -69,7 +97,17 @@ export const Sidebar: React.FC<SidebarProps> = ({
   onCreateRoom,
   onCreateDm,
   onSearch,
+  onSearchDirectory,
   onJumpToRoom,
+  isAway,
+  reminders,
+  onCancelReminder,
+  isHost,
+  flags,
+  onResolveFlag,
+  joinRequests,
+  onDecideJoinRequest,
+  onRedeemInvite,
   onLogout,
   theme,
   onToggleTheme,
@@ -80,6 +118,34 @@ export const Sidebar: React.FC<SidebarProps> = ({
   const [showSearch, setShowSearch] = useState(false);
   const [showNewDm, setShowNewDm] = useState(false);
   const [showSettings, setShowSettings] = useState(false);
+  const [showSwitcher, setShowSwitcher] = useState(false);
+  const [showReminders, setShowReminders] = useState(false);
+  const [showModeration, setShowModeration] = useState(false);
+  const [showJoinRequests, setShowJoinRequests] = useState(false);
+
+  // Ctrl/Cmd+K opens the quick switcher from anywhere in the workspace.
+  useEffect(() => {
+    const onKey = (e: KeyboardEvent) => {
+      if ((e.ctrlKey || e.metaKey) && e.key.toLowerCase() === "k") {
+        e.preventDefault();
+        setShowSwitcher(true);
+      }
+    };
+    window.addEventListener("keydown", onKey);
+    return () => window.removeEventListener("keydown", onKey);
+  }, []);
+
+  // Rooms and people are matched by name: in client mode the local DB's ids aren't the
+  // host's, but the room list and directory (both pushed by the host) are.
+  const pickHit = (hit: DirectoryHit) => {
+    if (hit.kind === "room") {
+      const room = chatRooms.find((r) => r.name === hit.name && !r.is_dm);
+      if (room) onSelectRoom(room);
+    } else {
+      const person = directory.find((u) => u.name === hit.name);
+      if (person && person.name !== currentUser.name) onCreateDm([person.id]);
+    }
+  };
 
   // DMs live in their own section; channels are grouped by department.
   const channels = chatRooms.filter((r) => !r.is_dm);
@@ -97,7 +163,10 @@ export const Sidebar: React.FC<SidebarProps> = ({
   const orphans = channels.filter((r) => !matched.has(r.id));
   if (orphans.length) groups.push({ name: "Other", rooms: orphans });
 
-  const status = statusMeta[connectionStatus];
+  const status =
+    connectionStatus === "connected" && isAway
+      ? awayMeta
+      : statusMeta[connectionStatus];
 
   // One sidebar row. `Icon` differs for channels (#) vs DMs (message bubble); `label` lets
   // DMs show their derived display name rather than the synthetic stored name.
@@ -176,6 +245,52 @@ export const Sidebar: React.FC<SidebarProps> = ({
         >
           <Search className="w-4 h-4" />
         </button>
+        <button
+          onClick={() => setShowReminders(true)}
+          title="Reminders"
+          aria-label={`Reminders (${reminders.length})`}
+          className="relative p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+        >
+          <Bell className="w-4 h-4" />
+          {reminders.length > 0 && (
+            <span className="absolute top-1 right-1 w-1.5 h-1.5 rounded-full bg-[var(--accent)]" />
+          )}
+        </button>
+        {isHost && (
+          <button
+            onClick={() => setShowModeration(true)}
+            title="Reported messages"
+            aria-label={`Reported messages (${flags.length})`}
+            className="relative p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <ShieldAlert className="w-4 h-4" />
+            {flags.length > 0 && (
+              <span className="absolute top-1 right-1 w-1.5 h-1.5 rounded-full bg-[var(--danger)]" />
+            )}
+          </button>
+        )}
+        {joinRequests.length > 0 && (
+          <button
+            onClick={() => setShowJoinRequests(true)}
+            title="Join requests"
+            aria-label={`Join requests (${joinRequests.length})`}
+            className="relative p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <UserCheck className="w-4 h-4" />
+            <span className="absolute top-1 right-1 w-1.5 h-1.5 rounded-full bg-[var(--accent)]" />
+          </button>
+        )}
+        <button
+          onClick={() => {
+            const link = window.prompt("Paste an invite link");
+            if (link?.trim()) onRedeemInvite(link.trim());
+          }}
+          title="Join with an invite link"
+          aria-label="Join with an invite link"
+          className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+        >
+          <Link2 className="w-4 h-4" />
+        </button>
         <button
           onClick={() => setShowCreate(true)}
           title="Create a channel"
@@ -317,6 +432,40 @@ export const Sidebar: React.FC<SidebarProps> = ({
         />
       )}
 
+      {showReminders && (
+        <RemindersModal
+          reminders={reminders}
+          onJump={onJumpToRoom}
+          onCancel={onCancelReminder}
+          onClose={() => setShowReminders(false)}
+        />
+      )}
+
+      {showModeration && (
+        <ModerationModal
+          flags={flags}
+          onJump={onJumpToRoom}
+          onResolve={onResolveFlag}
+          onClose={() => setShowModeration(false)}
+        />
+      )}
+
+      {showJoinRequests && (
+        <JoinRequestsModal
+          requests={joinRequests}
+          onDecide={onDecideJoinRequest}
+          onClose={() => setShowJoinRequests(false)}
+        />
+      )}
+
+      {showSwitcher && (
+        <QuickSwitcher
+          onSearch={onSearchDirectory}
+          onPick={pickHit}
+          onClose={() => setShowSwitcher(false)}
+        />
+      )}
+
       {showNewDm && (
         <NewDmModal
           users={directory}
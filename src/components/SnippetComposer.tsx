@@ -0,0 +1,115 @@
+import React, { useState } from "react";
+import { X } from "lucide-react";
+import { useFocusTrap } from "../hooks/useFocusTrap";
+
+interface SnippetComposerProps {
+  roomLabel: string;
+  onSend: (
+    code: string,
+    language: string,
+    filename: string,
+  ) => Promise<boolean>;
+  onClose: () => void;
+}
+
+// Paste a config or stack trace as a code snippet. The textarea's contents go out verbatim —
+// no trimming — so indentation and blank lines survive.
+export const SnippetComposer: React.FC<SnippetComposerProps> = ({
+  roomLabel,
+  onSend,
+  onClose,
+}) => {
+  const trapRef = useFocusTrap<HTMLDivElement>(onClose);
+  const [code, setCode] = useState("");
+  const [language, setLanguage] = useState("");
+  const [filename, setFilename] = useState("");
+  const [sending, setSending] = useState(false);
+
+  const send = async () => {
+    if (!code.trim() || sending) return;
+    setSending(true);
+    const sent = await onSend(code, language, filename);
+    setSending(false);
+    if (sent) onClose();
+  };
+
+  return (
+    <div
+      className="fixed inset-0 z-50 flex items-start justify-center bg-black/50 p-4 pt-[10vh]"
+      onMouseDown={onClose}
+      role="dialog"
+      aria-modal="true"
+      aria-label={`Share a code snippet in ${roomLabel}`}
+    >
+      <div
+        ref={trapRef}
+        className="w-full max-w-2xl bg-[var(--surface)] border border-[var(--border)] rounded-2xl shadow-2xl animate-scale-in overflow-hidden"
+        onMouseDown={(e) => e.stopPropagation()}
+      >
+        <div className="flex items-center justify-between px-5 h-14 border-b border-[var(--border)]">
+          <h2 className="font-semibold text-[var(--text)] truncate">
+            Code snippet in {roomLabel}
+          </h2>
+          <button
+            onClick={onClose}
+            aria-label="Close"
+            className="p-1.5 rounded-md text-[var(--text-faint)] hover:text-[var(--text)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            <X className="w-4 h-4" />
+          </button>
+        </div>
+
+        <div className="p-4 space-y-3">
+          <div className="flex gap-2">
+            <input
+              value={filename}
+              onChange={(e) => setFilename(e.target.value)}
+              placeholder="File name (optional)"
+              aria-label="File name"
+              className="flex-1 bg-[var(--surface-2)] border border-[var(--border)] rounded-lg px-3 py-1.5 text-sm text-[var(--text)] placeholder-[var(--text-faint)] focus:outline-none focus:border-[var(--accent)]"
+            />
+            <input
+              value={language}
+              onChange={(e) => setLanguage(e.target.value)}
+              placeholder="Language (optional)"
+              aria-label="Language"
+              className="w-44 bg-[var(--surface-2)] border border-[var(--border)] rounded-lg px-3 py-1.5 text-sm text-[var(--text)] placeholder-[var(--text-faint)] focus:outline-none focus:border-[var(--accent)]"
+            />
+          </div>
+          <textarea
+            value={code}
+            onChange={(e) => setCode(e.target.value)}
+            onKeyDown={(e) => {
+              if (e.key === "Enter" && (e.metaKey || e.ctrlKey)) {
+                e.preventDefault();
+                void send();
+              }
+            }}
+            placeholder="Paste code here…"
+            aria-label="Code"
+            spellCheck={false}
+            autoFocus
+            rows={14}
+            className="w-full bg-[var(--surface-2)] border border-[var(--border)] rounded-lg px-3 py-2 font-mono text-xs text-[var(--text)] placeholder-[var(--text-faint)] whitespace-pre overflow-auto focus:outline-none focus:border-[var(--accent)]"
+          />
+        </div>
+
+        <div className="flex justify-end gap-2 px-4 pb-4">
+          <button
+            onClick={onClose}
+            className="px-3 py-1.5 rounded-lg text-sm text-[var(--text-dim)] hover:bg-[var(--surface-2)] transition-colors"
+          >
+            Cancel
+          </button>
+          <button
+            onClick={() => void send()}
+            disabled={!code.trim() || sending}
+            className="px-3 py-1.5 rounded-lg text-sm bg-[var(--accent)] text-white hover:bg-[var(--accent-strong)] disabled:opacity-40 disabled:cursor-not-allowed transition-colors"
+          >
+            Send snippet
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+};
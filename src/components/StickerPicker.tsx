@@ -0,0 +1,63 @@
+import React, { useState } from "react";
+import { StickerPack } from "../types";
+
+interface StickerPickerProps {
+  packs: StickerPack[];
+  onPick: (packId: number, stickerId: number) => void;
+}
+
+// Composer sticker picker: one tab per pack (the host's packs, synced to clients).
+export const StickerPicker: React.FC<StickerPickerProps> = ({
+  packs,
+  onPick,
+}) => {
+  const [packId, setPackId] = useState<number | null>(packs[0]?.id ?? null);
+  const pack = packs.find((p) => p.id === packId) ?? packs[0];
+
+  return (
+    <div className="absolute bottom-14 left-0 w-80 bg-[var(--surface-2)] border border-[var(--border)] p-2 rounded-xl shadow-2xl z-50 animate-scale-in">
+      {!pack ? (
+        <p className="px-2 py-4 text-xs text-[var(--text-faint)] text-center">
+          This server has no sticker packs yet.
+        </p>
+      ) : (
+        <>
+          <div className="flex gap-1 mb-2 overflow-x-auto" role="tablist">
+            {packs.map((p) => (
+              <button
+                key={p.id}
+                role="tab"
+                aria-selected={p.id === pack.id}
+                onClick={() => setPackId(p.id)}
+                className={`px-2 py-1 rounded-lg text-xs whitespace-nowrap transition-colors ${
+                  p.id === pack.id
+                    ? "bg-[var(--surface-3)] text-[var(--text)]"
+                    : "text-[var(--text-faint)] hover:text-[var(--text)]"
+                }`}
+              >
+                {p.name}
+              </button>
+            ))}
+          </div>
+          <div className="grid grid-cols-4 gap-1 max-h-56 overflow-y-auto scrollbar-thin scrollbar-track-transparent">
+            {pack.stickers.map((s) => (
+              <button
+                key={s.id}
+                onClick={() => onPick(pack.id, s.id)}
+                title={s.name}
+                aria-label={`Send sticker ${s.name}`}
+                className="p-1 rounded-lg hover:bg-[var(--surface-3)] transition-colors"
+              >
+                <img
+                  src={s.url}
+                  alt={s.name}
+                  className="w-16 h-16 object-contain"
+                />
+              </button>
+            ))}
+          </div>
+        </>
+      )}
+    </div>
+  );
+};
@@ -1,12 +1,21 @@
 import React, { useEffect, useMemo } from "react";
-import { Hash, RefreshCw, WifiOff, X } from "lucide-react";
+import { Bell, Hash, RefreshCw, WifiOff, X } from "lucide-react";
 import {
+  AnnouncementReach,
+  AttachmentRef,
   ChatRoom,
+  CustomEmoji,
   Department,
+  DirectoryHit,
   DirectoryUser,
+  EmojiEntry,
+  FlaggedMessage,
+  JoinRequest,
   Message,
   Reaction,
+  Reminder,
   SearchResult,
+  StickerPack,
   User,
 } from "../types";
 import { ConnectionStatus } from "../hooks/useChatConnection";
@@ -37,10 +46,48 @@ interface WorkspaceProps {
     description: string,
     departmentId: number | null,
     isPrivate: boolean,
+    announcementOnly: boolean,
   ) => Promise<void>;
   onSearch: (query: string) => Promise<SearchResult[]>;
+  onSearchDirectory: (query: string) => Promise<DirectoryHit[]>;
   onJumpToRoom: (roomId: number) => void;
-  onSendMessage: (text: string, isEmoji?: boolean) => void;
+  onSendMessage: (text: string) => void;
+  onListEmoji: (query: string) => Promise<EmojiEntry[]>;
+  customEmoji: CustomEmoji[];
+  stickerPacks: StickerPack[];
+  onSendSticker: (packId: number, stickerId: number) => void;
+  onShareContact: (contactUserId: number) => void;
+  onSendCodeSnippet: (
+    code: string,
+    language: string,
+    filename: string,
+  ) => Promise<boolean>;
+  onSendLocation: (
+    label: string,
+    latitude: number | null,
+    longitude: number | null,
+  ) => Promise<boolean>;
+  onSendAttachment: () => void;
+  onPasteImage: () => void;
+  onDownloadAttachment: (attachment: AttachmentRef) => void;
+  thumbnails: Record<number, string | null>;
+  onLoadThumbnail: (attachmentId: number) => void;
+  awayUsers: Set<string>;
+  reminders: Reminder[];
+  dueReminder: Reminder | null;
+  onRemindMe: (msg: Message, at: Date) => void;
+  onCancelReminder: (id: number) => void;
+  onDismissReminder: () => void;
+  isHost: boolean;
+  flags: FlaggedMessage[];
+  onFlagMessage: (targetId: string, reason: string) => void;
+  onTranslate: (messageId: string) => Promise<string | null>;
+  onResolveFlag: (flagId: number, deleteMessage: boolean) => void;
+  onGetReach: (messageId: string) => Promise<AnnouncementReach | null>;
+  joinRequests: JoinRequest[];
+  onDecideJoinRequest: (id: number, approve: boolean) => void;
+  onCreateInviteLink: (roomId: number) => Promise<string | null>;
+  onRedeemInvite: (link: string) => void;
   onEditMessage: (targetId: string, newText: string) => Promise<void>;
   onDeleteMessage: (targetId: string) => Promise<void>;
   reactions: Record<string, Reaction[]>;
@@ -50,6 +97,7 @@ interface WorkspaceProps {
   onAddMember: (roomId: number, userId: number) => void;
   onCreateDm: (targetIds: number[]) => Promise<void> | void;
   onLeaveRoom: () => void;
+  onExportRoomPdf: (room: ChatRoom) => void;
   onLogout: () => void;
   onDismissError: () => void;
   theme: Theme;
@@ -76,8 +124,37 @@ export const Workspace: React.FC<WorkspaceProps> = ({
   onSelectRoom,
   onCreateRoom,
   onSearch,
+  onSearchDirectory,
   onJumpToRoom,
   onSendMessage,
+  onListEmoji,
+  customEmoji,
+  stickerPacks,
+  onSendSticker,
+  onShareContact,
+  onSendCodeSnippet,
+  onSendLocation,
+  onSendAttachment,
+  onPasteImage,
+  onDownloadAttachment,
+  thumbnails,
+  onLoadThumbnail,
+  awayUsers,
+  reminders,
+  dueReminder,
+  onRemindMe,
+  onCancelReminder,
+  onDismissReminder,
+  isHost,
+  flags,
+  onFlagMessage,
+  onTranslate,
+  onResolveFlag,
+  onGetReach,
+  joinRequests,
+  onDecideJoinRequest,
+  onCreateInviteLink,
+  onRedeemInvite,
   onEditMessage,
   onDeleteMessage,
   reactions,
@@ -87,6 +164,7 @@ export const Workspace: React.FC<WorkspaceProps> = ({
   onAddMember,
   onCreateDm,
   onLeaveRoom,
+  onExportRoomPdf,
   onLogout,
   onDismissError,
   theme,
@@ -104,12 +182,13 @@ export const Workspace: React.FC<WorkspaceProps> = ({
       .map((name) => ({
         name,
         online: true,
+        away: awayUsers.has(name),
         isYou: name === currentUser.name,
       }))
       .sort((a, b) =>
         a.isYou ? -1 : b.isYou ? 1 : a.name.localeCompare(b.name),
       );
-  }, [currentRoom, membersByRoom, currentUser.name]);
+  }, [currentRoom, membersByRoom, currentUser.name, awayUsers]);
 
   const onlineCount = members.length;
 
@@ -127,7 +206,17 @@ export const Workspace: React.FC<WorkspaceProps> = ({
         onCreateRoom={onCreateRoom}
         onCreateDm={onCreateDm}
         onSearch={onSearch}
+        onSearchDirectory={onSearchDirectory}
         onJumpToRoom={onJumpToRoom}
+        isAway={awayUsers.has(currentUser.name)}
+        reminders={reminders}
+        onCancelReminder={onCancelReminder}
+        isHost={isHost}
+        flags={flags}
+        onResolveFlag={onResolveFlag}
+        joinRequests={joinRequests}
+        onDecideJoinRequest={onDecideJoinRequest}
+        onRedeemInvite={onRedeemInvite}
         onLogout={onLogout}
         theme={theme}
         onToggleTheme={onToggleTheme}
@@ -153,7 +242,37 @@ export const Workspace: React.FC<WorkspaceProps> = ({
             currentUserId={currentUser.id}
             canonicalUserId={canonicalUserId}
             sendOnEnter={preferences.sendOnEnter}
+            language={preferences.language}
+            typingHidden={preferences.noTypingRooms.includes(currentRoom.id)}
+            onToggleTypingHidden={() =>
+              onSetPreferences({
+                noTypingRooms: preferences.noTypingRooms.includes(
+                  currentRoom.id,
+                )
+                  ? preferences.noTypingRooms.filter(
+                      (id) => id !== currentRoom.id,
+                    )
+                  : [...preferences.noTypingRooms, currentRoom.id],
+              })
+            }
             onSendMessage={onSendMessage}
+            onListEmoji={onListEmoji}
+            customEmoji={customEmoji}
+            stickerPacks={stickerPacks}
+            onSendSticker={onSendSticker}
+            onShareContact={onShareContact}
+            onSendCodeSnippet={onSendCodeSnippet}
+            onSendLocation={onSendLocation}
+            onSendAttachment={onSendAttachment}
+            onPasteImage={onPasteImage}
+            onDownloadAttachment={onDownloadAttachment}
+            thumbnails={thumbnails}
+            onLoadThumbnail={onLoadThumbnail}
+            onRemindMe={onRemindMe}
+            onFlagMessage={onFlagMessage}
+            onTranslate={onTranslate}
+            onGetReach={isHost ? onGetReach : undefined}
+            onCreateInviteLink={isHost ? onCreateInviteLink : undefined}
             onEditMessage={onEditMessage}
             onDeleteMessage={onDeleteMessage}
             reactions={reactions}
@@ -162,6 +281,7 @@ export const Workspace: React.FC<WorkspaceProps> = ({
             directory={directory}
             onAddMember={onAddMember}
             onLeave={onLeaveRoom}
+            onExportPdf={onExportRoomPdf}
           />
         ) : (
           <EmptyState />
@@ -170,6 +290,16 @@ export const Workspace: React.FC<WorkspaceProps> = ({
 
       <MembersPanel members={members} />
 
+      {dueReminder && (
+        <ReminderToast
+          reminder={dueReminder}
+          onJump={() => {
+            onJumpToRoom(dueReminder.room_id);
+            onDismissReminder();
+          }}
+          onClose={onDismissReminder}
+        />
+      )}
       {error && <ErrorToast message={error} onClose={onDismissError} />}
     </div>
   );
@@ -212,6 +342,42 @@ const EmptyState: React.FC = () => (
   </div>
 );
 
+// A reminder that just came due. Stays until dismissed (the OS notification may have been
+// missed); sits above the error toast so both can show.
+const ReminderToast: React.FC<{
+  reminder: Reminder;
+  onJump: () => void;
+  onClose: () => void;
+}> = ({ reminder, onJump, onClose }) => (
+  <div
+    role="status"
+    className="fixed bottom-24 right-5 z-50 max-w-sm flex items-start gap-3 bg-[var(--surface-2)] border border-[var(--accent)]/40 rounded-xl shadow-2xl px-4 py-3 animate-slide-up"
+  >
+    <Bell className="w-4 h-4 mt-0.5 text-[var(--accent-strong)] shrink-0" />
+    <div className="min-w-0 flex-1">
+      <div className="text-[11px] text-[var(--text-faint)]">
+        Reminder · #{reminder.room}
+      </div>
+      <div className="text-sm text-[var(--text)] break-words">
+        {reminder.preview}
+      </div>
+      <button
+        onClick={onJump}
+        className="mt-1 text-xs font-medium text-[var(--accent-strong)] hover:underline"
+      >
+        Go to message
+      </button>
+    </div>
+    <button
+      onClick={onClose}
+      aria-label="Dismiss"
+      className="text-[var(--text-faint)] hover:text-[var(--text)] transition-colors"
+    >
+      <X className="w-4 h-4" />
+    </button>
+  </div>
+);
+
 const ErrorToast: React.FC<{ message: string; onClose: () => void }> = ({
   message,
   onClose,
@@ -1,27 +1,108 @@
-import { useState, useEffect, useCallback, useRef } from "react";
+import { useState, useEffect, useCallback, useMemo, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
+import { open, save } from "@tauri-apps/plugin-dialog";
 import {
+  ActivityReport,
+  AnnouncementReach,
+  AttachmentRef,
   ChatRoom,
+  CompressionConfig,
   ConnectionMode,
+  CsvImportReport,
+  CustomEmoji,
+  DateRange,
+  DbLocation,
+  DigestPeriod,
+  DigestSchedule,
   Department,
+  DirectoryHit,
   DirectoryUser,
+  DiskGuardConfig,
+  DiskSpace,
+  EmojiEntry,
+  FileDropProgress,
+  FlaggedMessage,
+  HotkeyConfig,
+  JoinRequest,
+  MaintenanceBanner,
+  MergeReport,
   Message,
+  NetworkInterface,
+  QuarantinedFile,
   Reaction,
   ReactionAggregate,
+  RemoteAccess,
+  Reminder,
+  Collection,
+  Bookmark,
+  DepartmentNotice,
+  DepartmentAnnouncementResult,
+  SetupChoices,
+  SetupRecord,
+  SetupStatus,
+  RoomMember,
+  RoomPermissions,
+  MessageContext,
+  MessageEventsConfig,
+  DiscoveryConfig,
+  KnownServer,
+  LastServer,
+  MutedUser,
+  Profile,
+  Profiles,
+  ReadReport,
+  RoomDigest,
+  RoomHits,
+  SearchFilters,
   SearchResult,
+  ServerEvent,
+  ServerStats,
+  RoomFileFilter,
+  RoomFilesPage,
   ServerInfo,
+  ServerlessDelivery,
+  ServerlessPeer,
+  StickerPack,
+  ThrottleConfig,
+  UpgradeHint,
+  UsageConfig,
+  UsageReport,
   User,
   ViewState,
 } from "../types";
-import { mentionsUser, errText } from "../utils";
+import { mentionsUser, errText, errCode, isSystem } from "../utils";
 import { notify, ensureNotificationPermission } from "../notifications";
-import { loadProfile, saveProfile } from "../session";
+import {
+  loadProfile,
+  saveProfile,
+  getActiveProfile,
+  setActiveProfile,
+  profileKey,
+} from "../session";
+import { Preferences, preferencesFromSettings } from "../preferences";
 import { usePreferences } from "./usePreferences";
 import { useMessageStore } from "./useMessageStore";
 
 export type ConnectionStatus = "connected" | "reconnecting" | "disconnected";
 
+// Host console entries kept in memory.
+const MAX_SERVER_EVENTS = 200;
+
+// The typed events the backend emits frames on (message_events.rs).
+const MESSAGE_EVENTS = [
+  "chat_message",
+  "system_message",
+  "presence",
+  "reaction",
+  "message_edited",
+  "message_deleted",
+  "typing",
+  "history",
+  "join_request",
+  "control_message",
+];
+
 // Normalize a message from either source into one shape with an ISO-8601 UTC timestamp,
 // so the UI never has to branch on origin:
 //   - live socket: `created_at` is epoch-seconds (number)
@@ -54,6 +135,8 @@ const normalizeMessage = (m: any, fallbackRoomId?: number): Message => {
     message: m?.message,
     message_type: m?.message_type,
     is_emoji: m?.is_emoji ?? false,
+    language: m?.language ?? null,
+    filename: m?.filename ?? null,
     created_at: createdAt,
     edited_at: m?.edited_at ?? null,
     deleted_at: m?.deleted_at ?? null,
@@ -69,14 +152,54 @@ export const useChatConnection = () => {
   const [serverIp, setServerIp] = useState(
     () => loadProfile().serverIp ?? "127.0.0.1:3625",
   );
-  // Persisted app preferences (notification level, send-on-Enter) — own hook.
-  const { preferences, setPreferences, preferencesRef } = usePreferences();
+  // Host mode: the one address to listen on, or "" for all interfaces.
+  const [bindAddress, setBindAddress] = useState(
+    () => loadProfile().bindAddress ?? "",
+  );
+  // Persisted app preferences (notification level, send-on-Enter) — own hook. Also synced
+  // through the host (see setPreferences below).
+  const {
+    preferences,
+    setPreferences: setLocalPreferences,
+    preferencesRef,
+  } = usePreferences();
+  // The whole settings object last synced with the host; preferences are part of it.
+  const syncedSettingsRef = useRef<Record<string, unknown>>({});
 
   const [currentUser, setCurrentUser] = useState<User | null>(null);
   const [currentRoom, setCurrentRoom] = useState<ChatRoom | null>(null);
 
   const [departments, setDepartments] = useState<Department[]>([]);
   const [chatRooms, setChatRooms] = useState<ChatRoom[]>([]);
+  // Live head counts from the host's RoomOccupancy frames, by room id. They replace the
+  // member counts in the room list, which are only as fresh as the last room list.
+  const [occupancyByRoom, setOccupancyByRoom] = useState<Record<number, number>>(
+    {},
+  );
+  // The host's maintenance notice while one is up. The ref lets reconnects back off meanwhile.
+  const [maintenance, setMaintenance] = useState<MaintenanceBanner | null>(
+    null,
+  );
+  const maintenanceRef = useRef<MaintenanceBanner | null>(null);
+  // The last disk_space event; `low` means uploads are being refused.
+  const [diskSpace, setDiskSpace] = useState<DiskSpace | null>(null);
+  // The host's router port mapping, as last reported.
+  const [remoteAccess, setRemoteAccessStatus] = useState<RemoteAccess | null>(
+    null,
+  );
+  // Serverless mode: the peers heard, and the messages seen (sent and received),
+  // kept in memory only since there's no host.
+  const [serverlessPeers, setServerlessPeers] = useState<ServerlessPeer[]>([]);
+  const [serverlessMessages, setServerlessMessages] = useState<Message[]>([]);
+  const roomsWithOccupancy = useMemo(
+    () =>
+      chatRooms.map((r) =>
+        r.id in occupancyByRoom
+          ? { ...r, user_count: occupancyByRoom[r.id] }
+          : r,
+      ),
+    [chatRooms, occupancyByRoom],
+  );
   // Per-room message data (messages, roster, reactions, unread, typing, loading/pagination) —
   // own hook. The connection logic below still owns ingest + actions and drives these setters.
   const {
@@ -103,6 +226,31 @@ export const useChatConnection = () => {
   const [error, setError] = useState<string | null>(null);
   // User directory (host pushes it) for the invite + DM pickers.
   const [directory, setDirectory] = useState<DirectoryUser[]>([]);
+  // Custom emoji: the host's own set, or (client mode) the copy synced from the host.
+  const [customEmoji, setCustomEmoji] = useState<CustomEmoji[]>([]);
+  // Sticker packs, the same way.
+  const [stickerPacks, setStickerPacks] = useState<StickerPack[]>([]);
+  // Image attachments' thumbnails (data URLs) by attachment id; null = that file has none.
+  const [thumbnails, setThumbnails] = useState<Record<number, string | null>>(
+    {},
+  );
+  // Pending message reminders (local to this machine), and the one that just came due.
+  const [reminders, setReminders] = useState<Reminder[]>([]);
+  const [dueReminder, setDueReminder] = useState<Reminder | null>(null);
+  // Room welcomes the host sent us on first joining, by room name, until dismissed.
+  const [welcomeByRoom, setWelcomeByRoom] = useState<Record<string, string>>(
+    {},
+  );
+  // The latest announcement to our department, posted while we were in another room.
+  const [departmentNotice, setDepartmentNotice] =
+    useState<DepartmentNotice | null>(null);
+  // Host mode: open moderation flags, refreshed as new reports come in.
+  const [flags, setFlags] = useState<FlaggedMessage[]>([]);
+  const [serverEvents, setServerEvents] = useState<ServerEvent[]>([]);
+  // Requests to join private channels we moderate (host: all of them).
+  const [joinRequests, setJoinRequests] = useState<JoinRequest[]>([]);
+  // Names of users currently Away (host-relayed Presence frames; includes us).
+  const [awayUsers, setAwayUsers] = useState<Set<string>>(new Set());
   // The host-assigned canonical id for THIS user (client mode). Our local id differs from it,
   // so we use this to recognise our own messages by id rather than by (collision-prone) name.
   const [canonicalUserId, setCanonicalUserId] = useState<number | null>(null);
@@ -110,6 +258,7 @@ export const useChatConnection = () => {
 
   // Refs so the once-registered listeners read the latest values without re-subscribing.
   const passwordRef = useRef("");
+  const resumeTokenRef = useRef<string | null>(null);
   // Resolver for an in-flight client-mode "load older" request, settled when the matching
   // HistoryPage arrives (so the caller can await the prepend and anchor the scroll).
   const pendingOlderRef = useRef<{ room: string; resolve: () => void } | null>(
@@ -130,6 +279,10 @@ export const useChatConnection = () => {
   // Always-fresh handle to joinRoom so the (stable) ingest callback can open a DM the host
   // just created (DmReady) without capturing a stale joinRoom closure.
   const joinRoomRef = useRef<((room: ChatRoom) => Promise<void>) | null>(null);
+  // When each room last had a post (ms), to find the most recent DM for a quick reply.
+  const lastPostAtRef = useRef<Record<number, number>>({});
+  // The DM the quick-reply popover (global shortcut) is open for.
+  const [quickReplyRoom, setQuickReplyRoom] = useState<ChatRoom | null>(null);
 
   const PAGE_SIZE = 50;
 
@@ -156,6 +309,26 @@ export const useChatConnection = () => {
         return;
       }
 
+      // Someone went Away or came back Online. No room.
+      if (nm.message_type === "Presence") {
+        setAwayUsers((prev) => {
+          const next = new Set(prev);
+          if (nm.message === "Away") next.add(nm.username);
+          else next.delete(nm.username);
+          return next;
+        });
+        // The host says "Offline" once a connection is gone for good.
+        const isOnline = nm.message !== "Offline";
+        setDirectory((prev) =>
+          prev.map((u) =>
+            u.id === nm.user_id && u.is_online !== isOnline
+              ? { ...u, is_online: isOnline }
+              : u,
+          ),
+        );
+        return;
+      }
+
       // Host-pushed user directory (for invite/DM pickers). No room.
       if (nm.message_type === "UserDirectory") {
         try {
@@ -166,16 +339,114 @@ export const useChatConnection = () => {
         return;
       }
 
+      // Someone asked to join a private channel we moderate (`message_id` = request id).
+      if (nm.message_type === "JoinRequest") {
+        const req: JoinRequest = {
+          id: Number(nm.message_id),
+          room_id: nm.room_id,
+          room: nm.room,
+          user_id: nm.user_id,
+          username: nm.username,
+        };
+        setJoinRequests((prev) =>
+          prev.some((r) => r.id === req.id) ? prev : [...prev, req],
+        );
+        return;
+      }
+
+      // A join request was decided (or, for our own, filed). Moderators drop it from the queue;
+      // the requester hears how it went.
+      if (nm.message_type === "JoinDecision") {
+        const id = Number(nm.message_id);
+        setJoinRequests((prev) => prev.filter((r) => r.id !== id));
+        if (nm.user_id === canonicalUserIdRef.current) {
+          const outcome: Record<string, string> = {
+            pending: `Asked to join #${nm.room}. A moderator will review your request.`,
+            approved: `You've been let into #${nm.room}.`,
+            denied: `Your request to join #${nm.room} was declined.`,
+          };
+          if (outcome[nm.message]) setError(outcome[nm.message]);
+        }
+        return;
+      }
+
       // Host-side failure of one of our requests (e.g. duplicate channel name) → surface it.
+      // A refused post (e.g. in an announcement room) names it, so take back our copy.
       if (nm.message_type === "ErrorNotice") {
         if (nm.message) setError(nm.message);
         return;
       }
 
+      // We're muted, or the room is read-only for us: the host dropped our post,
+      // so take back the copy we showed.
+      if (
+        nm.message_type === "Muted" ||
+        nm.message_type === "ReadOnlyRoom"
+      ) {
+        setMessagesByRoom((prev) => {
+          const list = prev[nm.room];
+          if (!list) return prev;
+          return {
+            ...prev,
+            [nm.room]: list.filter((m) => m.message_id !== nm.message_id),
+          };
+        });
+        setError(
+          nm.message ||
+            (nm.message_type === "Muted"
+              ? "You've been muted by the host"
+              : "Only moderators can post here"),
+        );
+        return;
+      }
+
+      // The host refused our name (someone else goes by it). It has already closed the
+      // connection, so go back to sign-in rather than retrying under the same name.
+      if (nm.message_type === "NameInUse") {
+        setError(nm.message || "That name is already taken");
+        invoke("client_disconnect").catch(() => {});
+        setCurrentUser(null);
+        setCurrentRoom(null);
+        setView("login");
+        localStorage.removeItem(profileKey("nutler.userId"));
+        return;
+      }
+
+      // Our app is too old for this host. It has closed the connection; retrying won't help
+      // until the user upgrades, so go back to sign-in with a pointer to the download.
+      if (nm.message_type === "UpgradeRequired") {
+        let text = "This server needs a newer version of Nutler";
+        try {
+          const hint = JSON.parse(nm.message) as UpgradeHint;
+          text = `This server needs Nutler ${hint.min_version} or newer`;
+          if (hint.download_url) {
+            text += ` — download it from ${hint.download_url}`;
+          }
+        } catch (err) {
+          console.error("Bad upgrade payload:", err);
+        }
+        setError(text);
+        invoke("client_disconnect").catch(() => {});
+        setCurrentUser(null);
+        setCurrentRoom(null);
+        setView("login");
+        localStorage.removeItem(profileKey("nutler.userId"));
+        return;
+      }
+
+      // The host's token for resuming this connection after a brief drop (see reconnect).
+      if (nm.message_type === "ResumeToken") {
+        resumeTokenRef.current = nm.message || null;
+        return;
+      }
+
       // Host tells us our canonical id (client mode) so we can recognise our own messages.
       if (nm.message_type === "Identity") {
         canonicalUserIdRef.current = nm.user_id;
         setCanonicalUserId(nm.user_id);
+        // The host let us in, so whatever maintenance turned us away is over.
+        maintenanceRef.current = null;
+        setMaintenance(null);
         return;
       }
 
@@ -352,6 +623,58 @@ export const useChatConnection = () => {
         return;
       }
 
+      // A room's welcome, for us alone on our first join.
+      if (nm.message_type === "SystemResponse") {
+        if (nm.message) {
+          setWelcomeByRoom((prev) => ({ ...prev, [nm.room]: nm.message }));
+        }
+        return;
+      }
+
+      // An announcement went to our department's rooms while we're elsewhere.
+      if (nm.message_type === "DepartmentNotice") {
+        try {
+          const notice = JSON.parse(nm.message) as Omit<
+            DepartmentNotice,
+            "from" | "room" | "room_id"
+          >;
+          setDepartmentNotice({
+            ...notice,
+            from: nm.username,
+            room: nm.room,
+            room_id: nm.room_id,
+          });
+        } catch (err) {
+          console.error("Bad department notice:", err);
+        }
+        return;
+      }
+
+      // Planned maintenance on the host: show (or clear) its banner.
+      if (nm.message_type === "Maintenance") {
+        try {
+          const banner = JSON.parse(nm.message) as MaintenanceBanner & {
+            active: boolean;
+          };
+          const next = banner.active
+            ? { text: banner.text, disconnect_at: banner.disconnect_at }
+            : null;
+          maintenanceRef.current = next;
+          setMaintenance(next);
+        } catch (err) {
+          console.error("Bad maintenance payload:", err);
+        }
+        return;
+      }
+
+      if (nm.message_type === "RoomOccupancy") {
+        const count = Number(nm.message);
+        if (Number.isInteger(count) && count >= 0) {
+          setOccupancyByRoom((prev) => ({ ...prev, [nm.room_id]: count }));
+        }
+        return;
+      }
+
       if (nm.message_type === "UserList") {
         try {
           const names = JSON.parse(nm.message) as string[];
@@ -409,6 +732,13 @@ export const useChatConnection = () => {
         setMessagesByRoom((prev) => {
           const list = prev[nm.room];
           if (!list) return prev;
+          // A Delete flagged is_emoji was unsent inside the host's window: drop it entirely.
+          if (deleted && nm.is_emoji) {
+            return {
+              ...prev,
+              [nm.room]: list.filter((m) => m.message_id !== nm.message_id),
+            };
+          }
           return {
             ...prev,
             [nm.room]: list.map((m) =>
@@ -446,16 +776,37 @@ export const useChatConnection = () => {
         return { ...prev, [nm.room]: [...list, nm] };
       });
 
+      if (!isSystem(nm)) {
+        const at = new Date(nm.created_at).getTime() || Date.now();
+        if (at > (lastPostAtRef.current[nm.room_id] ?? 0)) {
+          lastPostAtRef.current[nm.room_id] = at;
+        }
+      }
+
       // Desktop notification for chat messages from others, gated by the user's preference:
       //   off      → never; mentions → only when @-mentioned;
       //   all      → when the window isn't focused, or we've been @-mentioned.
       const me = currentUserRef.current;
       if (
         me &&
-        (nm.message_type === "Chat" || !nm.message_type) &&
+        (nm.message_type === "Chat" ||
+          nm.message_type === "Sticker" ||
+          nm.message_type === "Code" ||
+          nm.message_type === "Contact" ||
+          nm.message_type === "Location" ||
+          !nm.message_type) &&
         nm.username !== me.name
       ) {
-        const mentioned = mentionsUser(nm.message, me.name);
+        const sticker = nm.message_type === "Sticker";
+        const code = nm.message_type === "Code";
+        const contact = nm.message_type === "Contact";
+        const location = nm.message_type === "Location";
+        const mentioned =
+          !sticker &&
+          !code &&
+          !contact &&
+          !location &&
+          mentionsUser(nm.message, me.name);
         const level = preferencesRef.current.notifications;
         const shouldNotify =
           level === "off"
@@ -464,7 +815,18 @@ export const useChatConnection = () => {
               ? mentioned
               : !document.hasFocus() || mentioned;
         if (shouldNotify) {
-          notify(`#${nm.room}`, `${nm.username}: ${nm.message}`);
+          notify(
+            `#${nm.room}`,
+            sticker
+              ? `${nm.username} sent a sticker`
+              : code
+                ? `${nm.username} shared a snippet${nm.filename ? `: ${nm.filename}` : ""}`
+                : contact
+                  ? `${nm.username} shared a contact`
+                  : location
+                    ? `${nm.username} shared a location`
+                    : `${nm.username}: ${nm.message}`,
+          );
         }
       }
       // Note: host unread badges are refreshed by the backend, which emits authoritative
@@ -637,19 +999,20 @@ export const useChatConnection = () => {
   // mount→unmount→remount) before `listen` resolves, the resolved handle unsubscribes itself
   // instead of leaking a second listener — which would double every event (and so double
   // reaction counts, since reactions increment per event rather than dedupe by id).
+  // Frames arrive already parsed, on one event per kind (see message_events.rs); the legacy
+  // `message` string event is left to anything else that still listens for it.
   useEffect(() => {
     let unlisten: (() => void) | undefined;
     let active = true;
     (async () => {
-      const fn = await listen<string>("message", (e) => {
-        if (!e.payload) return; // lifecycle events use their own channels
-        try {
-          const m = JSON.parse(e.payload);
-          if (m) ingestMessage(m);
-        } catch (err) {
-          console.error("Error parsing message:", err);
-        }
-      });
+      const fns = await Promise.all(
+        MESSAGE_EVENTS.map((name) =>
+          listen<Message>(name, (e) => {
+            if (e.payload) ingestMessage(e.payload);
+          }),
+        ),
+      );
+      const fn = () => fns.forEach((f) => f());
       if (!active) fn();
       else unlisten = fn;
     })();
@@ -677,6 +1040,347 @@ export const useChatConnection = () => {
     };
   }, []);
 
+  // Local profiles. The backend's active profile is the source of truth: if this window's
+  // storage namespace disagrees, adopt it and reload so every hook re-reads its state.
+  const [profiles, setProfiles] = useState<Profiles | null>(null);
+  const loadProfiles = useCallback(async () => {
+    try {
+      const list = (await invoke("list_profiles")) as Profiles;
+      if (list.active !== getActiveProfile()) {
+        setActiveProfile(list.active);
+        window.location.reload();
+        return;
+      }
+      setProfiles(list);
+    } catch (err) {
+      console.error("Failed to load profiles:", err);
+    }
+  }, []);
+  useEffect(() => {
+    void loadProfiles();
+  }, [loadProfiles]);
+  const switchProfile = async (id: string) => {
+    await invoke("switch_profile", { id });
+    setActiveProfile(id);
+    // Start over under the new profile's stored login and preferences.
+    window.location.reload();
+  };
+  const createProfile = async (name: string) => {
+    const profile = (await invoke("create_profile", { name })) as Profile;
+    await switchProfile(profile.id);
+  };
+  const deleteProfile = async (id: string) => {
+    await invoke("delete_profile", { id });
+    await loadProfiles();
+  };
+  // First-run setup for the active profile: whether it's needed, and saving the wizard's
+  // answers (all or nothing).
+  const getSetupStatus = async () =>
+    (await invoke("get_setup_status")) as SetupStatus;
+  const completeSetup = async (
+    choices: SetupChoices,
+  ): Promise<SetupRecord | null> => {
+    try {
+      return (await invoke("complete_setup", { choices })) as SetupRecord;
+    } catch (err) {
+      setError(`Setup didn't finish: ${errText(err)}`);
+      return null;
+    }
+  };
+  // The active profile's remembered room password (OS keychain), and saving or forgetting it.
+  const loadSavedPassword = useCallback(async () => {
+    try {
+      return (await invoke("get_profile_credential")) as string | null;
+    } catch (err) {
+      console.error("Failed to read the saved password:", err);
+      return null;
+    }
+  }, []);
+  const rememberPassword = async (password: string | null) => {
+    try {
+      await invoke("set_profile_credential", { credential: password });
+    } catch (err) {
+      console.error("Failed to save the password:", err);
+    }
+  };
+
+  // The user directory as CSV. Null when the file dialog is cancelled; exports return the row
+  // count.
+  const csvFilters = [{ name: "CSV", extensions: ["csv"] }];
+  const importUsersCsv = async () => {
+    const path = await open({
+      multiple: false,
+      directory: false,
+      filters: csvFilters,
+    });
+    if (typeof path !== "string") return null;
+    return (await invoke("import_users_csv", { path })) as CsvImportReport;
+  };
+  const exportCsv = async (command: string, defaultPath: string) => {
+    const path = await save({ defaultPath, filters: csvFilters });
+    if (!path) return null;
+    return (await invoke(command, { path })) as number;
+  };
+  const exportUsersCsv = () => exportCsv("export_users_csv", "users.csv");
+  const exportDepartmentsCsv = () =>
+    exportCsv("export_departments_csv", "departments.csv");
+
+  // The message log, for moving history between machines: export writes it (null
+  // when the dialog is cancelled), merge folds another machine's export in.
+  const exportMessageLog = async () => {
+    const path = await save({
+      defaultPath: "nutler-messages.json",
+      filters: [{ name: "JSON", extensions: ["json"] }],
+    });
+    if (!path) return null;
+    return (await invoke("export_message_log", { path })) as number;
+  };
+  const mergeMessageLog = async () => {
+    const path = await open({
+      multiple: false,
+      directory: false,
+      filters: [{ name: "JSON", extensions: ["json"] }],
+    });
+    if (typeof path !== "string") return null;
+    return (await invoke("merge_message_log", { path })) as MergeReport;
+  };
+
+  // A room's transcript as a PDF: the message count, or null when the dialog is
+  // cancelled or the export fails (the error shows).
+  const exportRoomPdf = async (room: ChatRoom, dateRange?: DateRange) => {
+    const path = await save({
+      defaultPath: `${room.name}.pdf`,
+      filters: [{ name: "PDF", extensions: ["pdf"] }],
+    });
+    if (!path) return null;
+    try {
+      return (await invoke("export_room_pdf", {
+        room_id: room.id,
+        date_range: dateRange ?? null,
+        path,
+      })) as number;
+    } catch (err) {
+      setError(`Transcript not exported: ${errText(err)}`);
+      return null;
+    }
+  };
+
+  // Opt-in usage statistics. The report can be previewed, saved as JSON (false when the
+  // dialog is cancelled) or submitted to the configured URL.
+  const getUsageStatsConfig = async () =>
+    (await invoke("get_usage_stats_config")) as UsageConfig;
+  const setUsageStatsConfig = (config: UsageConfig) =>
+    invoke("set_usage_stats_config", { config });
+  const getUsageStats = async () =>
+    (await invoke("get_usage_stats")) as UsageReport;
+  const exportUsageStats = async () => {
+    const path = await save({
+      defaultPath: "nutler-usage.json",
+      filters: [{ name: "JSON", extensions: ["json"] }],
+    });
+    if (!path) return false;
+    await invoke("export_usage_stats", { path });
+    return true;
+  };
+  const submitUsageStats = () => invoke("submit_usage_stats");
+
+  // Room digests: look at one, post it into the room (host only), or save it as
+  // HTML (null when the dialog is cancelled); plus the weekly schedule.
+  const generateDigest = async (
+    roomId: number,
+    period: DigestPeriod = "week",
+    delivery?: { kind: "post" } | { kind: "html"; path: string },
+  ) =>
+    (await invoke("generate_digest", {
+      room_id: roomId,
+      period,
+      delivery: delivery ?? null,
+    })) as RoomDigest;
+  const exportDigestHtml = async (
+    room: ChatRoom,
+    period: DigestPeriod = "week",
+  ) => {
+    const path = await save({
+      defaultPath: `${room.name}-digest.html`,
+      filters: [{ name: "HTML", extensions: ["html"] }],
+    });
+    if (!path) return null;
+    return generateDigest(room.id, period, { kind: "html", path });
+  };
+  const getDigestSchedule = async () =>
+    (await invoke("get_digest_schedule")) as DigestSchedule;
+  const setDigestSchedule = (schedule: DigestSchedule) =>
+    invoke("set_digest_schedule", { schedule });
+
+  // Per-department and per-room engagement for the admin dashboard.
+  const getActivityReport = async (period: DigestPeriod = "week") =>
+    (await invoke("get_activity_report", { period })) as ActivityReport;
+
+  // Read reports for compliance rooms: who has and hasn't read each announcement
+  // (or one), and the same as CSV (null when the dialog is cancelled).
+  const setComplianceRoom = (roomId: number, enabled: boolean) =>
+    invoke("set_compliance_room", { room_id: roomId, enabled });
+  const getReadReport = async (roomId: number, messageId?: string) =>
+    (await invoke("get_read_report", {
+      room_id: roomId,
+      message_id: messageId ?? null,
+    })) as ReadReport;
+  const exportReadReportCsv = async (room: ChatRoom, messageId?: string) => {
+    const path = await save({
+      defaultPath: `${room.name}-read-report.csv`,
+      filters: [{ name: "CSV", extensions: ["csv"] }],
+    });
+    if (!path) return null;
+    return (await invoke("export_read_report_csv", {
+      room_id: room.id,
+      message_id: messageId ?? null,
+      path,
+    })) as number;
+  };
+
+  // Bandwidth for file transfers, so a big share doesn't starve chat.
+  const getThrottleConfig = async () =>
+    (await invoke("get_throttle_config")) as ThrottleConfig;
+  const setThrottleConfig = (config: ThrottleConfig) =>
+    invoke("set_throttle_config", { config });
+
+  // Compression of large payloads; applies to connections made afterwards.
+  const getHotkeys = async () =>
+    (await invoke("get_hotkeys")) as HotkeyConfig;
+  const setHotkeys = (config: HotkeyConfig) =>
+    invoke("set_hotkeys", { config });
+  const getCompressionConfig = async () =>
+    (await invoke("get_compression_config")) as CompressionConfig;
+  const setCompressionConfig = (config: CompressionConfig) =>
+    invoke("set_compression_config", { config });
+  const getMessageEventsConfig = async () =>
+    (await invoke("get_message_events_config")) as MessageEventsConfig;
+  const setMessageEventsConfig = (config: MessageEventsConfig) =>
+    invoke("set_message_events_config", { config });
+
+  // Disk-space guard: how much is free, and how much must stay free.
+  const getDiskSpace = async () =>
+    (await invoke("get_disk_space")) as DiskSpace | null;
+  const getDiskGuardConfig = async () =>
+    (await invoke("get_disk_guard_config")) as DiskGuardConfig;
+  const setDiskGuardConfig = (config: DiskGuardConfig) =>
+    invoke("set_disk_guard_config", { config });
+
+  // Where the database lives; a new folder takes effect (and the database moves) on restart.
+  const getDatabaseLocation = async () =>
+    (await invoke("get_database_location")) as DbLocation;
+  const setDatabasePath = async (path: string | null) =>
+    (await invoke("set_database_path", { path })) as DbLocation;
+
+  // Settings sync: the host's copy wins on login, and later syncs (a save from another
+  // machine, or the host confirming ours) are applied as they arrive.
+  const applySyncedSettings = useCallback(
+    (settings: unknown) => {
+      if (!settings || typeof settings !== "object" || Array.isArray(settings))
+        return;
+      syncedSettingsRef.current = settings as Record<string, unknown>;
+      const patch = preferencesFromSettings(settings);
+      if (Object.keys(patch).length > 0) setLocalPreferences(patch);
+    },
+    [setLocalPreferences],
+  );
+  useEffect(() => {
+    if (!currentUser) return;
+    (async () => {
+      try {
+        applySyncedSettings(await invoke("get_user_settings"));
+      } catch (err) {
+        console.error("Failed to load synced settings:", err);
+      }
+    })();
+  }, [currentUser?.id, applySyncedSettings]);
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen("settings_synced", (e) =>
+        applySyncedSettings(e.payload),
+      );
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, [applySyncedSettings]);
+  // Change preferences locally and save them to the host for the user's other machines.
+  const setPreferences = useCallback(
+    (patch: Partial<Preferences>) => {
+      setLocalPreferences(patch);
+      const settings = {
+        ...syncedSettingsRef.current,
+        ...preferencesRef.current,
+        ...patch,
+      };
+      syncedSettingsRef.current = settings;
+      if (!currentUserRef.current) return;
+      invoke("set_user_settings", { settings }).catch((err) =>
+        console.error("Failed to sync settings:", err),
+      );
+    },
+    [setLocalPreferences, preferencesRef],
+  );
+
+  // Custom emoji: load on login, and reload whenever the backend syncs a change.
+  const loadCustomEmoji = useCallback(async () => {
+    try {
+      setCustomEmoji((await invoke("list_custom_emoji")) as CustomEmoji[]);
+    } catch (err) {
+      console.error("Failed to load custom emoji:", err);
+    }
+  }, []);
+  useEffect(() => {
+    if (currentUser) void loadCustomEmoji();
+  }, [currentUser?.id, loadCustomEmoji]);
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen("custom_emoji_updated", () => {
+        void loadCustomEmoji();
+      });
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, [loadCustomEmoji]);
+
+  // Sticker packs: likewise loaded on login and reloaded when the backend syncs a change.
+  const loadStickerPacks = useCallback(async () => {
+    try {
+      setStickerPacks((await invoke("list_sticker_packs")) as StickerPack[]);
+    } catch (err) {
+      console.error("Failed to load sticker packs:", err);
+    }
+  }, []);
+  useEffect(() => {
+    if (currentUser) void loadStickerPacks();
+  }, [currentUser?.id, loadStickerPacks]);
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen("stickers_updated", () => {
+        void loadStickerPacks();
+      });
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, [loadStickerPacks]);
+
   // Reconnection — registered once per (mode, user, serverIp); reads room from a ref.
   useEffect(() => {
     if (mode !== "client" || !currentUser) return;
@@ -687,6 +1391,9 @@ export const useChatConnection = () => {
     let retryCount = 0;
     let retryDelay = 1000;
     const maxRetries = 6;
+    // Set from a drop until we're back; `dialing` while a connect is in flight.
+    let retrying = false;
+    let dialing = false;
 
     const attempt = () => {
       if (retryCount >= maxRetries) {
@@ -694,7 +1401,13 @@ export const useChatConnection = () => {
         return;
       }
       timer = setTimeout(() => {
+        timer = undefined;
+        dialing = true;
         const room = currentRoomRef.current;
+        // Only the first attempt after a drop resumes: the host issues a fresh token when it
+        // accepts us, and a stale or unknown one shouldn't keep failing every retry.
+        const resumeToken = resumeTokenRef.current;
+        resumeTokenRef.current = null;
         invoke("client_connect_to_server", {
           host: serverIp,
           username: currentUser.name,
@@ -703,13 +1416,17 @@ export const useChatConnection = () => {
           room: room?.name || currentUser.department_name,
           roomId: room?.id || currentUser.department_id,
           password: passwordRef.current,
+          resumeToken,
         })
           .then(() => {
+            dialing = false;
+            retrying = false;
             retryCount = 0;
             retryDelay = 1000;
             setConnectionStatus("connected");
           })
           .catch(() => {
+            dialing = false;
             retryCount++;
             retryDelay = Math.min(retryDelay * 2, 15000);
             attempt();
@@ -719,15 +1436,35 @@ export const useChatConnection = () => {
 
     (async () => {
       const fn = await listen("connection_lost", () => {
+        setConnectionStatus("reconnecting");
+        retrying = true;
+        if (timer) clearTimeout(timer);
+        retryCount = 0;
+        // A host down for maintenance turns us away until it's back; don't hammer it.
+        retryDelay = maintenanceRef.current ? 15000 : 1000;
+        attempt();
+      });
+      // A new network (Wi-Fi back, a phone switching to cellular) is worth trying at once,
+      // even if we'd backed off or given up.
+      const netFn = await listen("network_changed", () => {
+        if (!retrying || dialing) return;
+        if (timer) clearTimeout(timer);
         setConnectionStatus("reconnecting");
         retryCount = 0;
-        retryDelay = 1000;
+        retryDelay = 250;
         attempt();
       });
-      // If the effect was torn down before listen resolved, unsubscribe the late handle so a
+      // If the effect was torn down before listen resolved, unsubscribe the late handles so a
       // second listener can't leak and spawn a duplicate reconnect loop.
-      if (!active) fn();
-      else unlisten = fn;
+      if (!active) {
+        fn();
+        netFn();
+      } else {
+        unlisten = () => {
+          fn();
+          netFn();
+        };
+      }
     })();
 
     return () => {
@@ -738,13 +1475,17 @@ export const useChatConnection = () => {
   }, [mode, currentUser, serverIp]);
 
   // Actions
+  // `target` (the startup reconnect) connects to that server and reopens that room instead of
+  // the address in the form and the department room.
   const login = async (
     username: string,
     email: string,
     departmentId: number,
     password: string,
+    target?: { host: string; room?: { name: string; id: number } },
   ) => {
     setError(null);
+    const host = target?.host ?? serverIp;
     try {
       const user = (await invoke("upsert_user", {
         name: username,
@@ -754,9 +1495,16 @@ export const useChatConnection = () => {
 
       passwordRef.current = password;
       setCurrentUser(user);
-      localStorage.setItem("nutler.userId", String(user.id));
+      localStorage.setItem(profileKey("nutler.userId"), String(user.id));
       // Remember the non-secret fields to pre-fill next launch.
-      saveProfile({ username, email, departmentId, mode, serverIp });
+      saveProfile({
+        username,
+        email,
+        departmentId,
+        mode,
+        serverIp: host,
+        bindAddress,
+      });
       ensureNotificationPermission(); // ask once, up front
 
       // Best-effort DB presence flag (last-seen / online).
@@ -773,17 +1521,19 @@ export const useChatConnection = () => {
           room: user.department_name,
           roomId: user.department_id,
           password,
+          bindAddress: bindAddress || null,
         });
       } else {
         await invoke("client_connect_to_server", {
-          host: serverIp,
+          host,
           username,
           userId: user.id,
           email,
-          room: user.department_name,
-          roomId: user.department_id,
+          room: target?.room?.name ?? user.department_name,
+          roomId: target?.room?.id ?? user.department_id,
           password,
         });
+        resumeRoomRef.current = target?.room?.id ?? null;
       }
 
       await loadChatRooms(user.id);
@@ -802,7 +1552,9 @@ export const useChatConnection = () => {
           /* best-effort */
         }
         try {
-          setDirectory((await invoke("list_users")) as DirectoryUser[]);
+          setDirectory(
+            (await invoke("list_users", { userId: user.id })) as DirectoryUser[],
+          );
         } catch {
           /* best-effort */
         }
@@ -819,6 +1571,63 @@ export const useChatConnection = () => {
     }
   };
 
+  // The server we last used, checked at launch: reconnect to it if it answers, or else list
+  // what discovery finds for the login screen.
+  const [startupDiscovered, setStartupDiscovered] = useState<ServerInfo[]>([]);
+  const startupCheckedRef = useRef(false);
+  useEffect(() => {
+    if (startupCheckedRef.current) return;
+    startupCheckedRef.current = true;
+    const saved = loadProfile();
+    if (
+      saved.mode !== "client" ||
+      !saved.username ||
+      !saved.email ||
+      saved.departmentId == null
+    ) {
+      return;
+    }
+    const { username, email, departmentId } = saved;
+    (async () => {
+      try {
+        const last = (await invoke("check_last_server")) as LastServer;
+        const password = last.healthy ? await loadSavedPassword() : null;
+        if (last.server && password) {
+          const room =
+            last.server.last_room && last.server.last_room_id != null
+              ? { name: last.server.last_room, id: last.server.last_room_id }
+              : undefined;
+          setServerIp(last.server.address);
+          passwordRef.current = password;
+          await login(username, email, departmentId, password, {
+            host: last.server.address,
+            room,
+          });
+          return;
+        }
+      } catch (err) {
+        console.warn("Reconnecting to the last server failed:", err);
+      }
+      try {
+        setStartupDiscovered(await discoverServers());
+      } catch {
+        /* the login screen can still search by hand */
+      }
+    })();
+    // Once, at launch.
+  }, []);
+
+  // After a reconnect, reopen the room we were in once the room list has it.
+  const resumeRoomRef = useRef<number | null>(null);
+  useEffect(() => {
+    const id = resumeRoomRef.current;
+    if (id == null || !currentUser) return;
+    const room = chatRooms.find((r) => r.id === id);
+    if (!room) return;
+    resumeRoomRef.current = null;
+    void joinRoomRef.current?.(room);
+  }, [chatRooms, currentUser]);
+
   const joinRoom = async (room: ChatRoom) => {
     if (!currentUser) return;
     if (currentRoom?.id === room.id) return; // already open
@@ -885,6 +1694,34 @@ export const useChatConnection = () => {
   // Keep the always-fresh handle in sync (used by ingest's DmReady path).
   joinRoomRef.current = joinRoom;
 
+  // The quick-reply shortcut: open the DM with the latest post (else the first one) and
+  // pop up a one-line reply to it.
+  const chatRoomsRef = useRef<ChatRoom[]>([]);
+  chatRoomsRef.current = chatRooms;
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen("quick_reply", async () => {
+        const lastPost = lastPostAtRef.current;
+        const dms = chatRoomsRef.current
+          .filter((r) => r.is_dm)
+          .sort((a, b) => (lastPost[b.id] ?? 0) - (lastPost[a.id] ?? 0));
+        const dm = dms[0];
+        if (!dm) return;
+        await joinRoomRef.current?.(dm);
+        setQuickReplyRoom(dm);
+      });
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, []);
+  const closeQuickReply = () => setQuickReplyRoom(null);
+
   // Open (or create) a direct message with the given users, then switch to it. The host
   // resolves it locally; a client asks the host and opens it on the DmReady reply.
   const createDm = async (targetIds: number[]) => {
@@ -913,6 +1750,7 @@ export const useChatConnection = () => {
     description: string,
     departmentId: number | null,
     isPrivate: boolean,
+    announcementOnly: boolean,
   ) => {
     if (!currentUser) return;
     try {
@@ -922,6 +1760,7 @@ export const useChatConnection = () => {
           description: description || null,
           departmentId: departmentId ?? null,
           isPrivate,
+          announcementOnly,
           actorId: currentUser.id,
         })) as ChatRoom;
         await loadChatRooms(currentUser.id);
@@ -932,6 +1771,7 @@ export const useChatConnection = () => {
           description: description || null,
           departmentId: departmentId ?? null,
           isPrivate,
+          announcementOnly,
         });
       }
     } catch (err) {
@@ -972,36 +1812,252 @@ export const useChatConnection = () => {
     [],
   );
 
-  // Find Nutler hosts on the LAN (UDP broadcast). User-triggered from the login screen.
-  // Failures propagate so the login UI can distinguish "no hosts" from a real error.
-  const discoverServers = useCallback(async (): Promise<ServerInfo[]> => {
-    return (await invoke("discover_servers")) as ServerInfo[];
-  }, []);
-
-  // Open a room by id (e.g. from a search result).
-  const jumpToRoom = (roomId: number) => {
-    const room = chatRooms.find((r) => r.id === roomId);
-    if (room) joinRoom(room);
-  };
+  // Quick-switcher (Ctrl/Cmd+K) fuzzy lookup of people and rooms.
+  const userId = currentUser?.id;
+  const searchDirectory = useCallback(
+    async (query: string): Promise<DirectoryHit[]> => {
+      if (userId === undefined) return [];
+      try {
+        return (await invoke("search_directory", {
+          userId,
+          query,
+          limit: 20,
+        })) as DirectoryHit[];
+      } catch (err) {
+        console.error("Directory search failed:", err);
+        return [];
+      }
+    },
+    [userId],
+  );
 
-  // Leave the active room (back to "no channel selected"); stays in the workspace.
-  const leaveRoom = async () => {
-    if (!currentUser || !currentRoom) return;
-    const room = currentRoom;
-    try {
-      if (mode === "server") {
-        await invoke("server_leave_room", {
-          userId: currentUser.id,
-          room: room.name,
-          roomId: room.id,
-        });
-      } else {
-        await invoke("client_leave_room", {
-          userId: currentUser.id,
-          room: room.name,
-          roomId: room.id,
-        });
+  // The member sidebar: everyone in a room, with role, join date and presence.
+  const getRoomMembers = useCallback(
+    async (roomId: number): Promise<RoomMember[]> => {
+      if (userId === undefined) return [];
+      try {
+        return (await invoke("get_room_members", {
+          roomId,
+          userId,
+        })) as RoomMember[];
+      } catch (err) {
+        console.error("Failed to load members:", err);
+        return [];
+      }
+    },
+    [userId],
+  );
+
+  // Host: planned maintenance. Clients see `message` as a banner, and new connections are
+  // turned away; with `disconnectAfterSecs`, everyone is disconnected when it runs out.
+  const enterMaintenanceMode = async (
+    message: string,
+    disconnectAfterSecs?: number,
+  ) => {
+    try {
+      await invoke("enter_maintenance_mode", {
+        message,
+        disconnectAfterSecs: disconnectAfterSecs ?? null,
+      });
+    } catch (err) {
+      setError(`Couldn't start maintenance: ${errText(err)}`);
+    }
+  };
+
+  const exitMaintenanceMode = async () => {
+    try {
+      await invoke("exit_maintenance_mode");
+    } catch (err) {
+      setError(`Couldn't end maintenance: ${errText(err)}`);
+    }
+  };
+
+  // Host: post `text` to every room of a department; members elsewhere get a notice.
+  const sendDepartmentAnnouncement = async (
+    departmentId: number,
+    text: string,
+  ): Promise<DepartmentAnnouncementResult | null> => {
+    try {
+      return (await invoke("send_department_announcement", {
+        department_id: departmentId,
+        text,
+      })) as DepartmentAnnouncementResult;
+    } catch (err) {
+      setError(`Announcement not sent: ${errText(err)}`);
+      return null;
+    }
+  };
+
+  const dismissDepartmentNotice = () => setDepartmentNotice(null);
+
+  const dismissWelcome = (room: string) =>
+    setWelcomeByRoom((prev) => {
+      const next = { ...prev };
+      delete next[room];
+      return next;
+    });
+
+  // Full-text search across every room the user can see, grouped by room.
+  const searchAll = useCallback(
+    async (query: string, filters?: SearchFilters): Promise<RoomHits[]> => {
+      if (userId === undefined) return [];
+      try {
+        return (await invoke("search_all", {
+          userId,
+          query,
+          filters: filters ?? null,
+        })) as RoomHits[];
+      } catch (err) {
+        console.error("Search failed:", err);
+        return [];
+      }
+    },
+    [userId],
+  );
+
+  // The messages around one message, for opening a room scrolled to it.
+  const getMessageContext = useCallback(
+    async (
+      messageId: string,
+      before?: number,
+      after?: number,
+    ): Promise<MessageContext | null> => {
+      if (userId === undefined) return null;
+      try {
+        const ctx = (await invoke("get_message_context", {
+          userId,
+          messageId,
+          before: before ?? null,
+          after: after ?? null,
+        })) as MessageContext;
+        return {
+          ...ctx,
+          messages: ctx.messages.map((m) => normalizeMessage(m, ctx.room_id)),
+        };
+      } catch (err) {
+        console.error("Couldn't load message context:", err);
+        return null;
       }
+    },
+    [userId],
+  );
+
+  // Emoji picker lookup by shortcode (the backend's table, so it matches what :name: expands to).
+  const listEmoji = useCallback(
+    async (query: string): Promise<EmojiEntry[]> => {
+      try {
+        return (await invoke("list_emoji", { query })) as EmojiEntry[];
+      } catch (err) {
+        console.error("Emoji lookup failed:", err);
+        return [];
+      }
+    },
+    [],
+  );
+
+  // Find Nutler hosts on the LAN (UDP broadcast, plus the configured ranges). User-triggered
+  // from the login screen.
+  // Failures propagate so the login UI can distinguish "no hosts" from a real error.
+  const discoverServers = useCallback(async (): Promise<ServerInfo[]> => {
+    return (await invoke("discover_servers")) as ServerInfo[];
+  }, []);
+
+  const getDiscoveryConfig = async () =>
+    (await invoke("get_discovery_config")) as DiscoveryConfig;
+  const setDiscoveryConfig = (config: DiscoveryConfig) =>
+    invoke("set_discovery_config", { config });
+
+  // Servers joined before (and favorites), for one-click reconnects.
+  const listKnownServers = async () =>
+    (await invoke("list_known_servers")) as KnownServer[];
+  const favoriteServer = (address: string, favorite: boolean, name?: string) =>
+    invoke("favorite_server", { address, name: name ?? null, favorite });
+
+  // This machine's network interfaces, for picking the host's bind address.
+  const listNetworkInterfaces = useCallback(
+    async (): Promise<NetworkInterface[]> =>
+      (await invoke("list_network_interfaces")) as NetworkInterface[],
+    [],
+  );
+  // Serverless mode, for when nobody hosts. Everyone in the group uses the same
+  // passphrase; messages go straight to the peers in the room.
+  const serverlessStart = (
+    username: string,
+    passphrase: string,
+    rooms: string[],
+    port?: number,
+  ) => invoke("serverless_start", { username, passphrase, rooms, port });
+  const serverlessStop = async () => {
+    await invoke("serverless_stop");
+    setServerlessMessages([]);
+  };
+  const serverlessSetRooms = (rooms: string[]) =>
+    invoke("serverless_set_rooms", { rooms });
+  const serverlessSend = async (room: string, message: string) => {
+    const delivery = (await invoke("serverless_send", {
+      room,
+      message,
+    })) as ServerlessDelivery;
+    setServerlessMessages((prev) => [...prev, delivery.message]);
+    if (delivery.unreachable.length > 0) {
+      setError(`Not delivered to ${delivery.unreachable.join(", ")}`);
+    }
+    return delivery;
+  };
+  useEffect(() => {
+    const unlisteners: (() => void)[] = [];
+    let active = true;
+    (async () => {
+      const fns = [
+        await listen<ServerlessPeer[]>("serverless_peers", (e) =>
+          setServerlessPeers(e.payload),
+        ),
+        await listen<string>("serverless_message", (e) => {
+          const msg = JSON.parse(e.payload) as Message;
+          setServerlessMessages((prev) => [...prev, msg]);
+        }),
+      ];
+      if (!active) fns.forEach((fn) => fn());
+      else unlisteners.push(...fns);
+    })();
+    return () => {
+      active = false;
+      unlisteners.forEach((fn) => fn());
+    };
+  }, []);
+  // Remote access: the host maps its port on the router; the outcome arrives as an event.
+  const getRemoteAccess = async () =>
+    (await invoke("get_remote_access")) as RemoteAccess;
+  const setRemoteAccess = (enabled: boolean) =>
+    invoke("set_remote_access", { enabled });
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen<RemoteAccess>("remote_access", (e) =>
+        setRemoteAccessStatus(e.payload),
+      );
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, []);
+
+  // Open a room by id (e.g. from a search result).
+  const jumpToRoom = (roomId: number) => {
+    const room = chatRooms.find((r) => r.id === roomId);
+    if (room) joinRoom(room);
+  };
+
+  // Leave the active room (back to "no channel selected"); stays in the workspace.
+  const leaveRoom = async () => {
+    if (!currentUser || !currentRoom) return;
+    const room = currentRoom;
+    try {
+      // Also tells the room (via the host when we're a client).
       await invoke("leave_room", { userId: currentUser.id, roomId: room.id });
       setCurrentRoom(null);
     } catch (err) {
@@ -1009,30 +2065,642 @@ export const useChatConnection = () => {
     }
   };
 
-  const sendMessage = async (text: string, isEmoji = false) => {
+  const sendMessage = async (text: string) => {
     if (!currentUser || !currentRoom) return;
     try {
       const command =
         mode === "server" ? "send_as_server_participant" : "send_as_client";
       // The backend echoes the sent message back to our UI, so it lands via the
-      // listener — no separate optimistic insert needed.
+      // listener — no separate optimistic insert needed. It also expands :shortcodes: and
+      // decides is_emoji.
       await invoke(command, {
         message: text,
         user_id: currentUser.id,
-        is_emoji: isEmoji,
       });
     } catch (err) {
       console.error("Send message failed:", err);
-      setError(`Message not sent: ${err}`);
+      // Over the host's length limit: say so, rather than a generic failure.
+      setError(
+        errCode(err) === "message_too_large"
+          ? `Message not sent: ${errText(err)}. Shorten it or split it up.`
+          : `Message not sent: ${errText(err)}`,
+      );
+    }
+  };
+
+  // Auto-away: report interaction to the backend, which owns the idle timer and the
+  // Away/Online broadcast. Throttled; the first event after going Away always gets through
+  // since the last report is then at least the idle period old.
+  useEffect(() => {
+    if (!currentUser) return;
+    let last = 0;
+    const onActivity = () => {
+      const now = Date.now();
+      if (now - last < 30_000) return;
+      last = now;
+      invoke("report_activity").catch(() => {});
+    };
+    const events = ["mousemove", "mousedown", "keydown", "wheel", "focus"];
+    for (const e of events) window.addEventListener(e, onActivity);
+    onActivity();
+    return () => {
+      for (const e of events) window.removeEventListener(e, onActivity);
+    };
+  }, [currentUser?.id]);
+
+  // Reminders live in this machine's DB, so the same commands serve host and client mode.
+  const loadReminders = useCallback(async () => {
+    try {
+      setReminders((await invoke("list_reminders")) as Reminder[]);
+    } catch (err) {
+      console.error("Failed to load reminders:", err);
+    }
+  }, []);
+  useEffect(() => {
+    if (currentUser) void loadReminders();
+  }, [currentUser?.id, loadReminders]);
+  // The backend fires each due reminder once (and posts the OS notification itself); we show
+  // it in-app with a jump back to the message's room.
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen<Reminder>("reminder", (event) => {
+        setDueReminder(event.payload);
+        void loadReminders();
+      });
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, [loadReminders]);
+
+  // The excerpt a reminder or bookmark keeps of a message (a client has no local copy).
+  const savedPreview = (msg: Message) => {
+    const kind =
+      msg.message_type === "Code"
+        ? "a snippet"
+        : msg.message_type === "Sticker"
+          ? "a sticker"
+          : msg.message_type === "Contact"
+            ? "a contact"
+            : msg.message_type === "Location"
+              ? "a location"
+              : null;
+    return kind
+      ? `${msg.username} shared ${kind}`
+      : `${msg.username}: ${msg.message}`;
+  };
+
+  const remindMe = async (msg: Message, at: Date) => {
+    if (!msg.message_id) return;
+    try {
+      await invoke("remind_me", {
+        message_id: msg.message_id,
+        at: Math.floor(at.getTime() / 1000),
+        room_id: msg.room_id,
+        room: msg.room,
+        preview: savedPreview(msg),
+      });
+      await loadReminders();
+    } catch (err) {
+      setError(`Reminder not set: ${errText(err)}`);
+    }
+  };
+
+  const cancelReminder = async (id: number) => {
+    try {
+      await invoke("cancel_reminder", { id });
+    } catch (err) {
+      setError(`Couldn't cancel the reminder: ${errText(err)}`);
+    }
+    await loadReminders();
+  };
+
+  const dismissReminder = () => setDueReminder(null);
+
+  // Bookmark collections, also kept in this machine's DB.
+  const [collections, setCollections] = useState<Collection[]>([]);
+  const loadCollections = useCallback(async () => {
+    try {
+      setCollections((await invoke("list_collections")) as Collection[]);
+    } catch (err) {
+      console.error("Failed to load collections:", err);
+    }
+  }, []);
+  useEffect(() => {
+    if (currentUser) void loadCollections();
+  }, [currentUser?.id, loadCollections]);
+
+  const createCollection = async (name: string): Promise<Collection | null> => {
+    try {
+      const created = (await invoke("create_collection", {
+        name,
+      })) as Collection;
+      await loadCollections();
+      return created;
+    } catch (err) {
+      setError(`Collection not created: ${errText(err)}`);
+      return null;
+    }
+  };
+
+  const deleteCollection = async (collectionId: number) => {
+    try {
+      await invoke("delete_collection", { collection_id: collectionId });
+    } catch (err) {
+      setError(`Couldn't delete the collection: ${errText(err)}`);
+    }
+    await loadCollections();
+  };
+
+  const addToCollection = async (collectionId: number, msg: Message) => {
+    if (!msg.message_id) return;
+    try {
+      await invoke("add_to_collection", {
+        collection_id: collectionId,
+        message_id: msg.message_id,
+        room_id: msg.room_id,
+        room: msg.room,
+        author: msg.username,
+        preview: savedPreview(msg),
+      });
+      await loadCollections();
+    } catch (err) {
+      setError(`Bookmark not saved: ${errText(err)}`);
+    }
+  };
+
+  const removeFromCollection = async (
+    collectionId: number,
+    messageId: string,
+  ) => {
+    try {
+      await invoke("remove_from_collection", {
+        collection_id: collectionId,
+        message_id: messageId,
+      });
+    } catch (err) {
+      setError(`Couldn't remove the bookmark: ${errText(err)}`);
+    }
+    await loadCollections();
+  };
+
+  const getCollection = async (collectionId: number): Promise<Bookmark[]> => {
+    try {
+      return (await invoke("get_collection", {
+        collection_id: collectionId,
+      })) as Bookmark[];
+    } catch (err) {
+      setError(`Couldn't open the collection: ${errText(err)}`);
+      return [];
+    }
+  };
+
+  // A client's report goes to the host over the socket; failures (e.g. a duplicate) come back
+  // as an ErrorNotice.
+  const flagMessage = async (targetId: string, reason: string) => {
+    try {
+      await invoke("flag_message", { message_id: targetId, reason });
+    } catch (err) {
+      setError(`Couldn't report the message: ${errText(err)}`);
+    }
+  };
+
+  // The moderation queue lives in the host's DB, so only host mode loads it.
+  const loadFlags = useCallback(async () => {
+    if (modeRef.current !== "server") return;
+    try {
+      setFlags((await invoke("get_flagged_messages")) as FlaggedMessage[]);
+    } catch (err) {
+      console.error("Failed to load flagged messages:", err);
+    }
+  }, []);
+  useEffect(() => {
+    if (currentUser) void loadFlags();
+  }, [currentUser?.id, loadFlags]);
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen<FlaggedMessage>("message_flagged", () => {
+        void loadFlags();
+      });
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, [loadFlags]);
+
+  // Host console: recent connection events from the log, then live ones as
+  // they happen (newest first, the latest MAX_SERVER_EVENTS kept).
+  useEffect(() => {
+    if (mode !== "server" || !currentUser) return;
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      try {
+        const recent = (await invoke("get_server_events", {
+          limit: MAX_SERVER_EVENTS,
+        })) as ServerEvent[];
+        if (active) setServerEvents(recent);
+      } catch (err) {
+        console.error("Failed to load server events:", err);
+      }
+      const fn = await listen<ServerEvent>("server_event", (e) =>
+        setServerEvents((prev) =>
+          [e.payload, ...prev].slice(0, MAX_SERVER_EVENTS),
+        ),
+      );
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, [mode, currentUser?.id]);
+
+  // Host only: who an @room announcement reached (delivery acks are tracked host-side).
+  const getAnnouncementReach = async (
+    messageId: string,
+  ): Promise<AnnouncementReach | null> => {
+    try {
+      return (await invoke("get_announcement_reach", {
+        message_id: messageId,
+      })) as AnnouncementReach;
+    } catch (err) {
+      setError(`Couldn't load the delivery report: ${errText(err)}`);
+      return null;
+    }
+  };
+
+  // Host only: queue depth and last-write age per client, most backed-up first.
+  const getServerStats = async (): Promise<ServerStats | null> => {
+    try {
+      return (await invoke("get_server_stats")) as ServerStats;
+    } catch (err) {
+      setError(`Couldn't load server stats: ${errText(err)}`);
+      return null;
+    }
+  };
+
+  // Translated into the system-message language, on this machine's translator (the result is
+  // cached in the local DB).
+  const translateMessage = async (messageId: string): Promise<string | null> => {
+    try {
+      const t = (await invoke("translate_message", {
+        message_id: messageId,
+        target_lang: preferencesRef.current.language,
+      })) as { text: string };
+      return t.text;
+    } catch (err) {
+      setError(`Couldn't translate the message: ${errText(err)}`);
+      return null;
+    }
+  };
+
+  // Host mode loads the whole queue; a client moderator is sent its requests on connect.
+  useEffect(() => {
+    if (!currentUser || modeRef.current !== "server") return;
+    (async () => {
+      try {
+        setJoinRequests((await invoke("list_join_requests")) as JoinRequest[]);
+      } catch (err) {
+        console.error("Failed to load join requests:", err);
+      }
+    })();
+  }, [currentUser?.id]);
+
+  // The decision comes back as a JoinDecision, which clears the request.
+  const decideJoinRequest = async (id: number, approve: boolean) => {
+    try {
+      await invoke(approve ? "approve_join_request" : "deny_join_request", {
+        request_id: id,
+      });
+    } catch (err) {
+      setError(`Couldn't decide the join request: ${errText(err)}`);
+    }
+  };
+
+  // Host only. Links last a day; anyone holding one can use it until then.
+  const createInviteLink = async (roomId: number): Promise<string | null> => {
+    try {
+      const invite = (await invoke("create_invite_link", {
+        room_id: roomId,
+        expires_in: 24 * 60 * 60,
+      })) as { link: string };
+      return invite.link;
+    } catch (err) {
+      setError(`Couldn't create an invite link: ${errText(err)}`);
+      return null;
+    }
+  };
+
+  // A client's invite goes to the host, which replies with the room to switch to (DmReady).
+  const redeemInvite = async (link: string) => {
+    try {
+      const room = (await invoke("redeem_invite", {
+        token: link,
+      })) as ChatRoom | null;
+      if (room) {
+        await loadChatRooms();
+        await joinRoom(room);
+      }
+    } catch (err) {
+      setError(`Couldn't use the invite: ${errText(err)}`);
+    }
+  };
+
+  const resolveFlag = async (flagId: number, deleteMessage: boolean) => {
+    try {
+      await invoke("resolve_flag", {
+        flag_id: flagId,
+        delete_message: deleteMessage,
+      });
+    } catch (err) {
+      setError(`Couldn't resolve the report: ${errText(err)}`);
+    }
+    await loadFlags();
+  };
+
+  // Who may post, invite and pin in a room (host mode only).
+  const getRoomPermissions = async (roomId: number) =>
+    (await invoke("get_room_permissions", {
+      room_id: roomId,
+    })) as RoomPermissions;
+  const setRoomPermissions = (roomId: number, permissions: RoomPermissions) =>
+    invoke("set_room_permissions", { room_id: roomId, permissions });
+
+  // The message each user gets privately on first joining a room (host mode only);
+  // `{user}` and `{room}` are filled in, and an empty template removes it.
+  const getRoomWelcome = async (roomId: number) =>
+    (await invoke("get_room_welcome", { room_id: roomId })) as string | null;
+  const setRoomWelcome = (roomId: number, template: string) =>
+    invoke("set_room_welcome", { room_id: roomId, template });
+
+  // Host-enforced mutes (host mode only).
+  const getMutedUsers = async () =>
+    (await invoke("get_muted_users")) as MutedUser[];
+  const muteUser = (userId: number, reason?: string) =>
+    invoke("mute_user", { user_id: userId, reason: reason ?? null });
+  const unmuteUser = (userId: number) =>
+    invoke("unmute_user", { user_id: userId });
+  // Admins see every department's private rooms and people (host mode only).
+  const setUserAdmin = (userId: number, admin: boolean) =>
+    invoke("set_user_admin", { user_id: userId, admin });
+  // Give an existing user a password; they can't register over their own email (host mode only).
+  const setUserPassword = (userId: number, password: string) =>
+    invoke("set_user_password", { user_id: userId, password });
+
+  // Like sendMessage, the sticker comes back to our UI through the listener.
+  const sendSticker = async (packId: number, stickerId: number) => {
+    if (!currentUser || !currentRoom) return;
+    try {
+      await invoke("send_sticker", {
+        pack_id: packId,
+        sticker_id: stickerId,
+        user_id: currentUser.id,
+      });
+    } catch (err) {
+      setError(`Sticker not sent: ${errText(err)}`);
+    }
+  };
+
+  const shareContact = async (contactUserId: number) => {
+    if (!currentUser || !currentRoom) return;
+    try {
+      await invoke("share_contact", {
+        contact_user_id: contactUserId,
+        user_id: currentUser.id,
+      });
+    } catch (err) {
+      setError(`Contact not shared: ${errText(err)}`);
+    }
+  };
+
+  // Code is sent verbatim (the backend skips emoji normalization for snippets). Resolves
+  // true when sent, so the composer knows to close.
+  const sendCodeSnippet = async (
+    code: string,
+    language: string,
+    filename: string,
+  ): Promise<boolean> => {
+    if (!currentUser || !currentRoom) return false;
+    try {
+      await invoke("send_code_snippet", {
+        code,
+        language: language.trim() || null,
+        filename: filename.trim() || null,
+        user_id: currentUser.id,
+      });
+      return true;
+    } catch (err) {
+      setError(`Snippet not sent: ${errText(err)}`);
+      return false;
+    }
+  };
+
+  const sendLocation = async (
+    label: string,
+    latitude: number | null,
+    longitude: number | null,
+  ): Promise<boolean> => {
+    if (!currentUser || !currentRoom) return false;
+    try {
+      await invoke("send_location", {
+        label: label || null,
+        latitude,
+        longitude,
+        user_id: currentUser.id,
+      });
+      return true;
+    } catch (err) {
+      setError(`Location not shared: ${errText(err)}`);
+      return false;
     }
   };
 
+  // Pick a file and share it in the current room. Like a sticker it comes back through the
+  // listener, once the host has the whole file.
+  const sendAttachment = async () => {
+    if (!currentUser || !currentRoom) return;
+    const path = await open({ multiple: false, directory: false });
+    if (typeof path !== "string") return;
+    try {
+      await invoke("send_attachment", { room_id: currentRoom.id, path });
+    } catch (err) {
+      setError(`File not shared: ${errText(err)}`);
+    }
+  };
+
+  // Share the image on the OS clipboard (Ctrl+V of a screenshot). The backend reads it, since
+  // the webview has no file path for a pasted blob.
+  const pasteImage = async () => {
+    if (!currentUser || !currentRoom) return;
+    try {
+      await invoke("paste_image_from_clipboard", { room_id: currentRoom.id });
+    } catch (err) {
+      setError(`Image not shared: ${errText(err)}`);
+    }
+  };
+
+  // From the media cache when it's there. Otherwise a client's host sends it, and
+  // thumbnail_ready brings us back here to pick it up.
+  const loadThumbnail = useCallback(async (attachmentId: number) => {
+    try {
+      const url = (await invoke("get_attachment_thumbnail", {
+        attachment_id: attachmentId,
+      })) as string | null;
+      if (url) setThumbnails((prev) => ({ ...prev, [attachmentId]: url }));
+    } catch {
+      setThumbnails((prev) => ({ ...prev, [attachmentId]: null }));
+    }
+  }, []);
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen<{ id: number }>("thumbnail_ready", (e) => {
+        void loadThumbnail(e.payload.id);
+      });
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, [loadThumbnail]);
+
+  const downloadAttachment = async (attachment: AttachmentRef) => {
+    const dest = await save({ defaultPath: attachment.filename });
+    if (!dest) return;
+    try {
+      await invoke("download_attachment", {
+        attachment_id: attachment.id,
+        dest,
+      });
+    } catch (err) {
+      setError(`Couldn't download ${attachment.filename}: ${errText(err)}`);
+    }
+  };
+
+  // A room's Files tab: one page (from 0) of what's been shared there, newest first.
+  const getRoomAttachments = async (
+    roomId: number,
+    typeFilter: RoomFileFilter = "all",
+    page = 0,
+  ): Promise<RoomFilesPage | null> => {
+    try {
+      return (await invoke("get_room_attachments", {
+        room_id: roomId,
+        type_filter: typeFilter,
+        page,
+      })) as RoomFilesPage;
+    } catch (err) {
+      setError(`Couldn't load the room's files: ${errText(err)}`);
+      return null;
+    }
+  };
+  // A client's download finishes after the command returns, so failures arrive as events.
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen<{ id: number; error: string }>(
+        "attachment_failed",
+        (e) => setError(`Download failed: ${e.payload.error}`),
+      );
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, []);
+  // A received file our scan policy refused (see file_scan.rs).
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen<QuarantinedFile>(
+        "attachment_quarantined",
+        (e) =>
+          setError(`${e.payload.filename} was quarantined: ${e.payload.reason}`),
+      );
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, []);
+
+  // Files dropped onto the window are shared by the backend; say which didn't make it.
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen<FileDropProgress>("file_drop", (e) => {
+        const { filename, status, error } = e.payload;
+        if (status === "refused" || status === "failed") {
+          setError(`${filename} not shared: ${error ?? "unknown error"}`);
+        }
+      });
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, []);
+
+  // Space ran low, or an upload was refused for it. diskSpace follows every change.
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let active = true;
+    (async () => {
+      const fn = await listen<DiskSpace>("disk_space", (e) => {
+        const space = e.payload;
+        setDiskSpace(space);
+        if (space.low) {
+          const freeMb = Math.floor(space.free_bytes / (1024 * 1024));
+          const hint = space.hint ? ` ${space.hint}` : "";
+          setError(`Disk space is low (${freeMb} MB free).${hint}`);
+        }
+      });
+      if (!active) fn();
+      else unlisten = fn;
+    })();
+    return () => {
+      active = false;
+      if (unlisten) unlisten();
+    };
+  }, []);
+
   // Stable (reads refs) so ChatPane's throttle/debounce timers never call a stale
   // copy. Best-effort: a failed typing ping must never surface or block the composer.
   const sendTyping = useCallback(async (typing: boolean) => {
     const room = currentRoomRef.current;
     const user = currentUserRef.current;
     if (!room || !user) return;
+    // Kept private (the backend checks again); a stop is always fine to send.
+    const prefs = preferencesRef.current;
+    if (
+      typing &&
+      (!prefs.sendTyping || prefs.noTypingRooms.includes(room.id))
+    )
+      return;
     try {
       const cmd =
         modeRef.current === "server" ? "server_typing" : "client_typing";
@@ -1128,13 +2796,18 @@ export const useChatConnection = () => {
 
     setCurrentUser(null);
     setCurrentRoom(null);
+    setAwayUsers(new Set());
+    setOccupancyByRoom({});
+    maintenanceRef.current = null;
+    setMaintenance(null);
     resetMessageStore();
     setDirectory([]);
     setCanonicalUserId(null);
     canonicalUserIdRef.current = null;
+    resumeTokenRef.current = null;
     setConnectionStatus("connected");
     setView("login");
-    localStorage.removeItem("nutler.userId");
+    localStorage.removeItem(profileKey("nutler.userId"));
   };
 
   const dismissError = () => setError(null);
@@ -1144,7 +2817,7 @@ export const useChatConnection = () => {
     mode,
     serverIp,
     departments,
-    chatRooms,
+    chatRooms: roomsWithOccupancy,
     messages,
     messagesByRoom,
     membersByRoom,
@@ -1171,18 +2844,136 @@ export const useChatConnection = () => {
     currentRoom,
     setMode,
     setServerIp,
+    bindAddress,
+    setBindAddress,
     login,
     joinRoom,
     createRoom,
     leaveRoom,
     sendMessage,
+    sendSticker,
+    shareContact,
+    sendCodeSnippet,
+    sendLocation,
+    sendAttachment,
+    pasteImage,
+    downloadAttachment,
+    getRoomAttachments,
+    thumbnails,
+    loadThumbnail,
     editMessage,
     deleteMessage,
     toggleReaction,
     reactionsByMessage,
     loadOlderMessages,
     searchMessages,
+    searchDirectory,
+    searchAll,
+    getRoomMembers,
+    maintenance,
+    enterMaintenanceMode,
+    exitMaintenanceMode,
+    sendDepartmentAnnouncement,
+    departmentNotice,
+    dismissDepartmentNotice,
+    profiles,
+    switchProfile,
+    createProfile,
+    deleteProfile,
+    getSetupStatus,
+    completeSetup,
+    loadSavedPassword,
+    rememberPassword,
+    getDatabaseLocation,
+    setDatabasePath,
+    importUsersCsv,
+    exportUsersCsv,
+    exportDepartmentsCsv,
+    exportMessageLog,
+    mergeMessageLog,
+    exportRoomPdf,
+    getUsageStatsConfig,
+    setUsageStatsConfig,
+    getUsageStats,
+    exportUsageStats,
+    submitUsageStats,
+    generateDigest,
+    exportDigestHtml,
+    getDigestSchedule,
+    setDigestSchedule,
+    getActivityReport,
+    setComplianceRoom,
+    getReadReport,
+    exportReadReportCsv,
+    getThrottleConfig,
+    setThrottleConfig,
+    getCompressionConfig,
+    setCompressionConfig,
+    getMessageEventsConfig,
+    setMessageEventsConfig,
+    getHotkeys,
+    setHotkeys,
+    quickReplyRoom,
+    closeQuickReply,
+    getDiskSpace,
+    getDiskGuardConfig,
+    setDiskGuardConfig,
+    diskSpace,
+    getMessageContext,
+    listEmoji,
+    customEmoji,
+    stickerPacks,
+    awayUsers,
+    reminders,
+    dueReminder,
+    remindMe,
+    collections,
+    createCollection,
+    deleteCollection,
+    addToCollection,
+    removeFromCollection,
+    getCollection,
+    cancelReminder,
+    dismissReminder,
+    isHost: mode === "server",
+    flags,
+    serverEvents,
+    flagMessage,
+    translateMessage,
+    resolveFlag,
+    getRoomPermissions,
+    setRoomPermissions,
+    getRoomWelcome,
+    setRoomWelcome,
+    welcomeByRoom,
+    dismissWelcome,
+    getMutedUsers,
+    muteUser,
+    unmuteUser,
+    setUserAdmin,
+    setUserPassword,
+    getAnnouncementReach,
+    getServerStats,
+    joinRequests,
+    decideJoinRequest,
+    createInviteLink,
+    redeemInvite,
     discoverServers,
+    startupDiscovered,
+    getDiscoveryConfig,
+    setDiscoveryConfig,
+    listKnownServers,
+    favoriteServer,
+    listNetworkInterfaces,
+    getRemoteAccess,
+    setRemoteAccess,
+    remoteAccess,
+    serverlessStart,
+    serverlessStop,
+    serverlessSetRooms,
+    serverlessSend,
+    serverlessPeers,
+    serverlessMessages,
     jumpToRoom,
     logout,
     dismissError,
@@ -10,6 +10,9 @@ describe("usePreferences", () => {
     expect(result.current.preferences).toEqual({
       notifications: "all",
       sendOnEnter: true,
+      language: "en",
+      sendTyping: true,
+      noTypingRooms: [],
     });
 
     act(() => result.current.setPreferences({ notifications: "off" }));
@@ -0,0 +1,49 @@
+import { describe, it, expect } from "vitest";
+import { renderSystemMessage, isLanguage } from "./i18n";
+import { Message } from "./types";
+
+const sys = (message: string): Message => ({
+  message_id: "m",
+  room_id: 1,
+  room: "general",
+  user_id: 1,
+  username: "Bob",
+  message,
+  message_type: "RoomJoin",
+  created_at: "2026-01-01T00:00:00Z",
+});
+
+describe("renderSystemMessage", () => {
+  const moved = sys(
+    JSON.stringify({
+      event: "moved_room",
+      user: "Bob",
+      from: "general",
+      to: "hr",
+    }),
+  );
+
+  it("renders a structured event in the viewer's language", () => {
+    expect(renderSystemMessage(moved, "en")).toBe(
+      "📍 Bob moved from general to hr",
+    );
+    expect(renderSystemMessage(moved, "de")).toBe(
+      "📍 Bob ist von general nach hr gewechselt",
+    );
+  });
+
+  it("shows legacy prose and malformed events unchanged", () => {
+    expect(renderSystemMessage(sys("Bob left the chat"), "fr")).toBe(
+      "Bob left the chat",
+    );
+    const missing = JSON.stringify({ event: "moved_room", user: "Bob" });
+    expect(renderSystemMessage(sys(missing), "en")).toBe(missing);
+    const unknown = JSON.stringify({ event: "exploded", user: "Bob" });
+    expect(renderSystemMessage(sys(unknown), "en")).toBe(unknown);
+  });
+
+  it("only accepts languages with a catalog", () => {
+    expect(isLanguage("es")).toBe(true);
+    expect(isLanguage("xx")).toBe(false);
+  });
+});
@@ -0,0 +1,122 @@
+// Localized rendering of system events (join / leave / room switch). The host sends these as
+// structured JSON in `message` (src-tauri/src/system_events.rs): an `event` name plus its
+// parameters. Each viewer renders them here in their own language. Legacy rows that still hold
+// English prose, or anything that isn't a known event, fall through unchanged.
+
+import { Message } from "./types";
+
+export type Language = "en" | "es" | "fr" | "de" | "pt";
+
+export const LANGUAGES: { value: Language; label: string }[] = [
+  { value: "en", label: "English" },
+  { value: "es", label: "Español" },
+  { value: "fr", label: "Français" },
+  { value: "de", label: "Deutsch" },
+  { value: "pt", label: "Português" },
+];
+
+export function isLanguage(v: unknown): v is Language {
+  return LANGUAGES.some((l) => l.value === v);
+}
+
+// The OS/browser language if we have a catalog for it, else English.
+export function detectLanguage(): Language {
+  try {
+    const base = (navigator.language || "en").toLowerCase().split("-")[0];
+    return isLanguage(base) ? base : "en";
+  } catch {
+    return "en";
+  }
+}
+
+type SystemEvent =
+  | { event: "server_started"; user: string }
+  | { event: "joined"; user: string }
+  | { event: "left"; user: string }
+  | { event: "moved_room"; user: string; from: string; to: string }
+  | { event: "left_room"; user: string; room: string };
+
+type Catalog = {
+  [E in SystemEvent as E["event"]]: (e: E) => string;
+};
+
+const CATALOGS: Record<Language, Catalog> = {
+  en: {
+    server_started: (e) => `🟢 ${e.user} started the server and joined the chat`,
+    joined: (e) => `🔵 ${e.user} joined the chat`,
+    left: (e) => `${e.user} left the chat`,
+    moved_room: (e) => `📍 ${e.user} moved from ${e.from} to ${e.to}`,
+    left_room: (e) => `👋 ${e.user} left ${e.room}`,
+  },
+  es: {
+    server_started: (e) => `🟢 ${e.user} inició el servidor y se unió al chat`,
+    joined: (e) => `🔵 ${e.user} se unió al chat`,
+    left: (e) => `${e.user} salió del chat`,
+    moved_room: (e) => `📍 ${e.user} pasó de ${e.from} a ${e.to}`,
+    left_room: (e) => `👋 ${e.user} salió de ${e.room}`,
+  },
+  fr: {
+    server_started: (e) =>
+      `🟢 ${e.user} a démarré le serveur et a rejoint la discussion`,
+    joined: (e) => `🔵 ${e.user} a rejoint la discussion`,
+    left: (e) => `${e.user} a quitté la discussion`,
+    moved_room: (e) => `📍 ${e.user} est passé de ${e.from} à ${e.to}`,
+    left_room: (e) => `👋 ${e.user} a quitté ${e.room}`,
+  },
+  de: {
+    server_started: (e) =>
+      `🟢 ${e.user} hat den Server gestartet und ist dem Chat beigetreten`,
+    joined: (e) => `🔵 ${e.user} ist dem Chat beigetreten`,
+    left: (e) => `${e.user} hat den Chat verlassen`,
+    moved_room: (e) => `📍 ${e.user} ist von ${e.from} nach ${e.to} gewechselt`,
+    left_room: (e) => `👋 ${e.user} hat ${e.room} verlassen`,
+  },
+  pt: {
+    server_started: (e) => `🟢 ${e.user} iniciou o servidor e entrou no chat`,
+    joined: (e) => `🔵 ${e.user} entrou no chat`,
+    left: (e) => `${e.user} saiu do chat`,
+    moved_room: (e) => `📍 ${e.user} mudou de ${e.from} para ${e.to}`,
+    left_room: (e) => `👋 ${e.user} saiu de ${e.room}`,
+  },
+};
+
+function isStr(v: unknown): v is string {
+  return typeof v === "string";
+}
+
+// Validate a parsed payload field-by-field (a peer's frame is untrusted input).
+function parseSystemEvent(text: string): SystemEvent | null {
+  if (!text.startsWith("{")) return null;
+  let v: Record<string, unknown>;
+  try {
+    v = JSON.parse(text) as Record<string, unknown>;
+  } catch {
+    return null;
+  }
+  if (!v || typeof v !== "object" || !isStr(v.user)) return null;
+  switch (v.event) {
+    case "server_started":
+    case "joined":
+    case "left":
+      return { event: v.event, user: v.user };
+    case "moved_room":
+      return isStr(v.from) && isStr(v.to)
+        ? { event: v.event, user: v.user, from: v.from, to: v.to }
+        : null;
+    case "left_room":
+      return isStr(v.room)
+        ? { event: v.event, user: v.user, room: v.room }
+        : null;
+    default:
+      return null;
+  }
+}
+
+// The text to show for a system message in `lang`.
+export function renderSystemMessage(msg: Message, lang: Language): string {
+  const e = parseSystemEvent(msg.message);
+  if (!e) return msg.message;
+  const catalog = CATALOGS[lang] ?? CATALOGS.en;
+  // The catalog is keyed by event, so each entry takes exactly its own event shape.
+  return (catalog[e.event] as (e: SystemEvent) => string)(e);
+}
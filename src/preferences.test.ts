@@ -1,6 +1,7 @@
 import { describe, it, expect, beforeEach } from "vitest";
 import {
   loadPreferences,
+  preferencesFromSettings,
   savePreferences,
   DEFAULT_PREFERENCES,
   Preferences,
@@ -12,7 +13,13 @@ beforeEach(() => {
 
 describe("preferences", () => {
   it("round-trips valid preferences", () => {
-    const p: Preferences = { notifications: "mentions", sendOnEnter: false };
+    const p: Preferences = {
+      notifications: "mentions",
+      sendOnEnter: false,
+      language: "fr",
+      sendTyping: false,
+      noTypingRooms: [2, 5],
+    };
     savePreferences(p);
     expect(loadPreferences()).toEqual(p);
   });
@@ -24,7 +31,13 @@ describe("preferences", () => {
   it("falls back to defaults for wrong-typed or unknown values", () => {
     localStorage.setItem(
       "nutler.preferences",
-      JSON.stringify({ notifications: "loud", sendOnEnter: "yes" }),
+      JSON.stringify({
+        notifications: "loud",
+        sendOnEnter: "yes",
+        language: "xx",
+        sendTyping: "no",
+        noTypingRooms: ["general"],
+      }),
     );
     expect(loadPreferences()).toEqual(DEFAULT_PREFERENCES);
   });
@@ -38,6 +51,9 @@ describe("preferences", () => {
     savePreferences({
       notifications: "off",
       sendOnEnter: true,
+      language: "en",
+      sendTyping: true,
+      noTypingRooms: [],
       // @ts-expect-error — a stray field must not be written to storage
       password: "s3cret",
     });
@@ -47,6 +63,25 @@ describe("preferences", () => {
     expect(loadPreferences()).toEqual({
       notifications: "off",
       sendOnEnter: true,
+      language: "en",
+      sendTyping: true,
+      noTypingRooms: [],
     });
   });
+
+  it("takes only valid preference fields from synced settings", () => {
+    expect(
+      preferencesFromSettings({
+        notifications: "off",
+        sendOnEnter: "yes",
+        language: "xx",
+        theme: "light",
+      }),
+    ).toEqual({ notifications: "off" });
+    expect(
+      preferencesFromSettings({ sendTyping: false, noTypingRooms: [4] }),
+    ).toEqual({ sendTyping: false, noTypingRooms: [4] });
+    expect(preferencesFromSettings(null)).toEqual({});
+    expect(preferencesFromSettings(["all"])).toEqual({});
+  });
 });
@@ -1,8 +1,11 @@
-// Persisted, non-secret app preferences (localStorage). Mirrors src/session.ts: a single
-// JSON key, hand-rolled per-field validation, and try/catch around all storage access so
+// Persisted, non-secret app preferences (localStorage, per profile). Mirrors src/session.ts: a
+// single JSON key, hand-rolled per-field validation, and try/catch around all storage access so
 // private-mode / unavailable storage is non-fatal. Unlike session's Partial return, this
 // always resolves to a full, valid Preferences with defaults filled in.
 
+import { Language, isLanguage, detectLanguage } from "./i18n";
+import { profileKey } from "./session";
+
 export type NotificationMode = "all" | "mentions" | "off";
 
 export interface Preferences {
@@ -14,6 +17,13 @@ export interface Preferences {
   // true: Enter sends (Shift+Enter does not). false: Enter does nothing (avoids accidental
   // sends). Cmd/Ctrl+Enter always sends in either mode.
   sendOnEnter: boolean;
+  // Language system notices (joins, leaves, room switches) are rendered in. Defaults to the
+  // OS language when we have it, else English.
+  language: Language;
+  // false: never tell others we're typing. Rooms listed in noTypingRooms (ids) are kept
+  // private even when it's true. Both are checked again before a typing ping goes out.
+  sendTyping: boolean;
+  noTypingRooms: number[];
 }
 
 const KEY = "nutler.preferences";
@@ -21,15 +31,22 @@ const KEY = "nutler.preferences";
 export const DEFAULT_PREFERENCES: Preferences = {
   notifications: "all",
   sendOnEnter: true,
+  language: detectLanguage(),
+  sendTyping: true,
+  noTypingRooms: [],
 };
 
 function isNotificationMode(v: unknown): v is NotificationMode {
   return v === "all" || v === "mentions" || v === "off";
 }
 
+function isRoomIds(v: unknown): v is number[] {
+  return Array.isArray(v) && v.every((id) => Number.isInteger(id));
+}
+
 export function loadPreferences(): Preferences {
   try {
-    const raw = localStorage.getItem(KEY);
+    const raw = localStorage.getItem(profileKey(KEY));
     if (!raw) return { ...DEFAULT_PREFERENCES };
     const p = JSON.parse(raw) as Record<string, unknown>;
     return {
@@ -40,20 +57,47 @@ export function loadPreferences(): Preferences {
         typeof p.sendOnEnter === "boolean"
           ? p.sendOnEnter
           : DEFAULT_PREFERENCES.sendOnEnter,
+      language: isLanguage(p.language)
+        ? p.language
+        : DEFAULT_PREFERENCES.language,
+      sendTyping:
+        typeof p.sendTyping === "boolean"
+          ? p.sendTyping
+          : DEFAULT_PREFERENCES.sendTyping,
+      noTypingRooms: isRoomIds(p.noTypingRooms)
+        ? p.noTypingRooms
+        : DEFAULT_PREFERENCES.noTypingRooms,
     };
   } catch {
     return { ...DEFAULT_PREFERENCES };
   }
 }
 
+// The preference fields of a settings object synced from the host (user_settings.rs), leaving
+// out anything missing or invalid so a bad value never replaces a good local one.
+export function preferencesFromSettings(raw: unknown): Partial<Preferences> {
+  if (!raw || typeof raw !== "object" || Array.isArray(raw)) return {};
+  const p = raw as Record<string, unknown>;
+  const out: Partial<Preferences> = {};
+  if (isNotificationMode(p.notifications)) out.notifications = p.notifications;
+  if (typeof p.sendOnEnter === "boolean") out.sendOnEnter = p.sendOnEnter;
+  if (isLanguage(p.language)) out.language = p.language;
+  if (typeof p.sendTyping === "boolean") out.sendTyping = p.sendTyping;
+  if (isRoomIds(p.noTypingRooms)) out.noTypingRooms = p.noTypingRooms;
+  return out;
+}
+
 export function savePreferences(p: Preferences): void {
   try {
     // Construct an explicit allowlisted object so a stray/secret field can never leak.
     const safe: Preferences = {
       notifications: p.notifications,
       sendOnEnter: p.sendOnEnter,
+      language: p.language,
+      sendTyping: p.sendTyping,
+      noTypingRooms: p.noTypingRooms,
     };
-    localStorage.setItem(KEY, JSON.stringify(safe));
+    localStorage.setItem(profileKey(KEY), JSON.stringify(safe));
   } catch {
     /* storage unavailable (private mode); non-fatal */
   }
@@ -1,5 +1,10 @@
 import { describe, it, expect, beforeEach } from "vitest";
-import { loadProfile, saveProfile } from "./session";
+import {
+  loadProfile,
+  saveProfile,
+  setActiveProfile,
+  profileKey,
+} from "./session";
 
 beforeEach(() => {
   localStorage.clear();
@@ -64,4 +69,21 @@ describe("session profile", () => {
     localStorage.setItem("nutler.profile", "{not json");
     expect(loadProfile()).toEqual({});
   });
+
+  it("keeps each local profile's fields apart", () => {
+    const fields = {
+      email: "x@x.com",
+      departmentId: 1,
+      mode: "client" as const,
+      serverIp: "10.0.0.2:3625",
+    };
+    saveProfile({ ...fields, username: "Ada" });
+    setActiveProfile("desk");
+    expect(profileKey("nutler.profile")).toBe("nutler.profile.desk");
+    expect(loadProfile()).toEqual({});
+    saveProfile({ ...fields, username: "Grace" });
+    expect(loadProfile().username).toBe("Grace");
+    setActiveProfile("default");
+    expect(loadProfile().username).toBe("Ada");
+  });
 });
@@ -1,20 +1,50 @@
 import { ConnectionMode } from "./types";
 
 // Remembers the last *non-secret* login fields so the form pre-fills on relaunch.
-// The room password is the encryption PSK and is deliberately NEVER persisted.
+// The room password is the encryption PSK and is never persisted here (a profile can
+// remember it in the OS keychain instead; see profiles.rs).
 const KEY = "nutler.profile";
 
+// The local profile in use (profiles.rs). Everything stored per person lives under keys
+// suffixed with the profile id; the default profile keeps the original, unsuffixed keys.
+const ACTIVE_PROFILE_KEY = "nutler.activeProfile";
+export const DEFAULT_PROFILE = "default";
+
+export const getActiveProfile = (): string => {
+  try {
+    return localStorage.getItem(ACTIVE_PROFILE_KEY) || DEFAULT_PROFILE;
+  } catch {
+    return DEFAULT_PROFILE;
+  }
+};
+
+export const setActiveProfile = (id: string): void => {
+  try {
+    localStorage.setItem(ACTIVE_PROFILE_KEY, id);
+  } catch {
+    /* storage may be unavailable (private mode); non-fatal */
+  }
+};
+
+// `key` namespaced to the active profile.
+export const profileKey = (key: string): string => {
+  const id = getActiveProfile();
+  return id === DEFAULT_PROFILE ? key : `${key}.${id}`;
+};
+
 export interface SessionProfile {
   username: string;
   email: string;
   departmentId: number | null;
   mode: ConnectionMode;
   serverIp: string;
+  // Host mode: the address to listen on ("" or absent = every interface).
+  bindAddress?: string;
 }
 
 export const loadProfile = (): Partial<SessionProfile> => {
   try {
-    const raw = localStorage.getItem(KEY);
+    const raw = localStorage.getItem(profileKey(KEY));
     if (!raw) return {};
     const p = JSON.parse(raw) as Record<string, unknown>;
     // Validate each field by type — a corrupt or hand-edited profile must never
@@ -29,6 +59,8 @@ export const loadProfile = (): Partial<SessionProfile> => {
           ? (p.mode as ConnectionMode)
           : undefined,
       serverIp: typeof p.serverIp === "string" ? p.serverIp : undefined,
+      bindAddress:
+        typeof p.bindAddress === "string" ? p.bindAddress : undefined,
     };
   } catch {
     return {};
@@ -44,9 +76,10 @@ export const saveProfile = (p: SessionProfile): void => {
     departmentId: p.departmentId,
     mode: p.mode,
     serverIp: p.serverIp,
+    bindAddress: p.bindAddress,
   };
   try {
-    localStorage.setItem(KEY, JSON.stringify(safe));
+    localStorage.setItem(profileKey(KEY), JSON.stringify(safe));
   } catch {
     /* storage may be unavailable (private mode); non-fatal */
   }
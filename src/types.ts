@@ -27,6 +27,40 @@ export interface ServerInfo {
   user_count: number;
 }
 
+export interface NetworkInterface {
+  name: string;
+  ipv4: string[];
+  ipv6: string[];
+  is_up: boolean;
+  is_loopback: boolean;
+}
+
+// A peer heard in serverless mode (no host): where it takes messages, and its rooms.
+export interface ServerlessPeer {
+  id: string;
+  name: string;
+  ip: string | null;
+  port: number;
+  rooms: string[];
+  heartbeat: number;
+}
+
+// A serverless message as sent, with who it reached.
+export interface ServerlessDelivery {
+  message: Message;
+  delivered: string[];
+  unreachable: string[];
+}
+
+// Remote access through the router: where teammates outside the LAN connect while
+// the port is mapped, or why it couldn't be.
+export interface RemoteAccess {
+  enabled: boolean;
+  external_addr: string | null;
+  protocol: "upnp" | "nat_pmp" | null;
+  error: string | null;
+}
+
 export interface ChatRoom {
   id: number;
   name: string;
@@ -38,6 +72,8 @@ export interface ChatRoom {
   // For DMs, the label derived from the other members (the stored `name` is a synthetic key).
   display_name?: string;
   user_count?: number;
+  // Organizing tags (lowercase, sorted); see set_room_tags.
+  tags?: string[];
 }
 
 export interface Message {
@@ -51,11 +87,61 @@ export interface Message {
   message: string;
   message_type?: string;
   is_emoji?: boolean;
+  language?: string | null; // Code snippets: highlighter hint
+  filename?: string | null; // Code snippets: optional file name; attachments: the file's
   created_at: string; // normalized ISO-8601 UTC string
   edited_at?: string | null;
   deleted_at?: string | null;
 }
 
+// An Attachment message's `message`: a file the host keeps for the room.
+export interface AttachmentRef {
+  id: number;
+  filename: string;
+  size: number;
+  mime: string;
+}
+
+// One file in a room's Files tab (newest first), from the host's store.
+export interface RoomFile extends AttachmentRef {
+  message_id: string;
+  uploaded_by: number | null;
+  uploader: string | null;
+  created_at: string;
+}
+
+export type RoomFileFilter = "all" | "images" | "media" | "documents";
+
+export interface RoomFilesPage {
+  files: RoomFile[];
+  has_more: boolean;
+}
+
+// A Location message's `message`: a named place, a position, or both.
+export interface SharedLocation {
+  label?: string;
+  latitude?: number;
+  longitude?: number;
+}
+
+// The `attachment_quarantined` event: a received file our scan policy refused.
+export interface QuarantinedFile {
+  attachment_id: number | null;
+  filename: string;
+  reason: string;
+  path: string | null; // where it was put aside
+}
+
+// The `file_drop` event: how one file dropped onto the window is getting on.
+export interface FileDropProgress {
+  drop_id: string;
+  filename: string;
+  index: number; // from 1
+  count: number;
+  status: "queued" | "sending" | "sent" | "refused" | "failed";
+  error?: string;
+}
+
 export interface Reaction {
   emoji: string;
   count: number;
@@ -69,6 +155,159 @@ export interface ReactionAggregate {
   me: boolean;
 }
 
+// A quick-switcher match (search_directory): a user or a room, best first.
+export interface DirectoryHit {
+  kind: "user" | "room";
+  id: number;
+  name: string;
+  detail?: string | null; // email for users, description for rooms
+  score: number;
+}
+
+export interface EmojiEntry {
+  shortcode: string;
+  emoji: string;
+}
+
+// A host's custom emoji, used as :name:. `url` is a data URL of the image.
+export interface CustomEmoji {
+  name: string;
+  hash: string;
+  url: string;
+}
+
+export interface Sticker {
+  id: number;
+  name: string;
+  url: string;
+}
+
+// A host's sticker pack (or, in client mode, the synced copy of it).
+export interface StickerPack {
+  id: number;
+  name: string;
+  stickers: Sticker[];
+}
+
+// A pending message reminder (kept locally; see src-tauri/src/reminders.rs).
+export interface Reminder {
+  id: number;
+  message_id: string;
+  room_id: number;
+  room: string;
+  preview: string;
+  remind_at: string; // UTC "YYYY-MM-DD HH:MM:SS"
+}
+
+// A named set of bookmarked messages, kept on this machine.
+export interface Collection {
+  id: number;
+  name: string;
+  count: number;
+  created_at: string;
+}
+
+export interface Bookmark {
+  collection_id: number;
+  message_id: string;
+  room_id: number;
+  room: string;
+  author: string;
+  preview: string;
+  added_at: string; // UTC "YYYY-MM-DD HH:MM:SS"
+}
+
+// A message reported to the host's moderators (host mode only).
+export interface FlaggedMessage {
+  id: number;
+  message_id: string;
+  room_id: number;
+  room: string;
+  author: string | null;
+  message: string; // empty once deleted
+  reason: string;
+  reporter: string | null;
+  created_at: string;
+  status: "open" | "dismissed" | "removed";
+  resolved_by: string | null;
+  resolved_at: string | null;
+}
+
+// Who may do each thing in a room: any member, or only its moderators (its
+// creator and the host). Posting kept to moderators makes an announcement room.
+export type RoomRole = "members" | "moderators";
+
+export interface RoomPermissions {
+  post: RoomRole;
+  invite: RoomRole;
+  pin: RoomRole;
+}
+
+// A user the host has muted (host mode only); their posts are dropped.
+export interface MutedUser {
+  user_id: number;
+  name: string;
+  muted_at: string;
+  reason: string | null;
+}
+
+// One entry in the host console: a connection event, a mute, a lagging client, or
+// a throughput snapshot (`kind` "throughput", summarised in `detail`).
+export interface ServerEvent {
+  kind: string;
+  user_id: number | null;
+  user_name: string | null;
+  peer: string | null;
+  detail: string | null;
+  created_at: number;
+}
+
+// How one client connection is keeping up with delivery (host only).
+export interface ConnectionStats {
+  user_id: number;
+  username: string;
+  room: string;
+  queued: number;
+  last_write_age_ms: number;
+  lagging: boolean;
+}
+
+export interface ServerStats {
+  connected_clients: number;
+  outbox_depth: number;
+  messages_broadcast: number;
+  connections: ConnectionStats[];
+}
+
+// A pending request to join a private channel, for its moderators to approve or deny.
+export interface MessageEdit {
+  user_id?: number;
+  author?: string;
+  message: string;
+  edited_at: string;
+}
+
+export interface JoinRequest {
+  id: number;
+  room_id: number;
+  room: string;
+  user_id: number;
+  username: string;
+}
+
+// Who an announcement (@room message) reached, from the host's delivery acks.
+export interface ReachEntry {
+  user_id: number;
+  username: string;
+  delivered_at: string | null;
+}
+
+export interface AnnouncementReach {
+  message_id: string;
+  delivered: ReachEntry[];
+  undelivered: ReachEntry[];
+}
+
 export interface SearchResult {
   message_id?: string;
   room_id: number;
@@ -76,6 +315,341 @@ export interface SearchResult {
   username: string;
   message: string;
   created_at: string;
+  // An image found by the text in it.
+  image_text?: string;
+}
+
+export interface SearchFilters {
+  room_id?: number;
+  author_id?: number;
+  after?: string;
+  before?: string;
+  limit?: number;
+}
+
+export interface SearchContext {
+  message_id?: string;
+  username: string;
+  message: string;
+  created_at: string;
+}
+
+export interface SearchHit extends SearchContext {
+  // A voice message found by what was said.
+  transcript?: string;
+  // An image found by the text in it.
+  image_text?: string;
+  previous?: SearchContext;
+  next?: SearchContext;
+}
+
+export interface RoomHits {
+  room_id: number;
+  room_name: string;
+  hits: SearchHit[];
+}
+
+export interface MessageContext {
+  room_id: number;
+  messages: Message[];
+  has_older: boolean;
+  has_newer: boolean;
+}
+
+export type MemberRole = "owner" | "guest" | "member";
+export type MemberPresence = "online" | "away" | "offline";
+
+export interface RoomMember {
+  user_id: number;
+  name: string;
+  email: string;
+  department_name: string | null;
+  role: MemberRole;
+  joined_at: string | null;
+  presence: MemberPresence;
+  last_seen: string | null;
+}
+
+// The host's planned-maintenance notice (a Maintenance frame).
+export interface MaintenanceBanner {
+  text: string;
+  // When clients get disconnected, Unix seconds (null = they stay connected).
+  disconnect_at: number | null;
+}
+
+// An announcement to our department, shown because we were in another room (a
+// DepartmentNotice frame). `room` is where it was posted first.
+export interface DepartmentNotice {
+  department_id: number;
+  department: string;
+  text: string;
+  from: string;
+  room: string;
+  room_id: number;
+}
+
+// What send_department_announcement did.
+export interface DepartmentAnnouncementResult {
+  rooms: number;
+  // Members told directly, being connected in some other room.
+  notified: number;
+}
+
+// Why the host turned us away: our app version is below its minimum.
+export interface UpgradeHint {
+  min_version: string;
+  // Ours, as the host saw it (null for builds that don't say).
+  version: string | null;
+  download_url: string | null;
+}
+
+export interface Profile {
+  id: string;
+  name: string;
+}
+
+export interface Profiles {
+  profiles: Profile[];
+  active: string;
+}
+
+// First-run setup: whether this machine hosts a server or joins one.
+export type SetupMode = "host" | "join";
+
+export interface SetupRecord {
+  user_id: number;
+  mode: SetupMode;
+  // "host:port", for join.
+  server: string | null;
+}
+
+export interface SetupStatus {
+  profile: string;
+  needs_setup: boolean;
+  setup: SetupRecord | null;
+  user: User | null;
+  departments: Department[];
+}
+
+// The wizard's answers. Pick a department by id, or name a new one.
+export interface SetupChoices {
+  name: string;
+  email: string;
+  department_id?: number | null;
+  new_department?: string | null;
+  mode: SetupMode;
+  server?: string | null;
+}
+
+export interface RejectedRow {
+  line: number;
+  email: string;
+  reason: string;
+}
+
+export interface CsvImportReport {
+  users_created: number;
+  users_updated: number;
+  departments_created: number;
+  rejected: RejectedRow[];
+}
+
+export interface DbLocation {
+  db_path: string;
+  key_dir: string;
+  portable: boolean;
+  // A different folder chosen for the next launch, if any.
+  pending_dir: string | null;
+}
+
+// What merging another machine's message log changed here.
+export interface MergeReport {
+  added: number;
+  updated: number;
+  unchanged: number;
+  users_created: number;
+  rooms_created: number;
+}
+
+// Opt-in usage statistics: whether they're collected, and where a report is submitted.
+export interface UsageConfig {
+  enabled: boolean;
+  submit_url: string | null;
+}
+
+// Counts only: command name → calls, "<module> <level>" → problems logged.
+export interface UsageReport {
+  app_version: string;
+  os: string;
+  since: string | null;
+  commands: Record<string, number>;
+  problems: Record<string, number>;
+}
+
+// Free space on the fullest of the database's and attachments' disks. When `low`,
+// uploads are refused until space is freed; `hint` says how.
+export interface DiskSpace {
+  path: string;
+  free_bytes: number;
+  min_free_bytes: number;
+  low: boolean;
+  hint: string | null;
+}
+
+// File traffic allowed per connection, KB/s; 0 is unlimited.
+export interface ThrottleConfig {
+  max_kb_per_sec: number;
+}
+
+// Offer and accept compression of large payloads on new connections.
+export interface CompressionConfig {
+  enabled: boolean;
+}
+
+// Also emit every frame on the old `message` event, as a JSON string.
+export interface MessageEventsConfig {
+  legacy_message_events: boolean;
+}
+
+// A server this machine joined or favorited. `address` is "host:port" as connected to.
+export interface KnownServer {
+  address: string;
+  name: string | null;
+  last_room: string | null;
+  last_room_id: number | null;
+  favorite: boolean;
+  last_connected_at: string | null;
+}
+
+// The launch-time health check: the server last connected to, and whether it answered.
+export interface LastServer {
+  server: KnownServer | null;
+  healthy: boolean;
+  info: ServerInfo | null;
+}
+
+// Extra ranges (IPv4 CIDR, private only) and UDP ports discovery probes, and whether to probe
+// this machine's own subnets too.
+export interface DiscoveryConfig {
+  subnets: string[];
+  ports: number[];
+  scan_own_subnet: boolean;
+}
+
+// System-wide shortcuts (accelerator syntax, e.g. "CommandOrControl+Shift+Space"); an empty
+// one is off.
+export interface HotkeyConfig {
+  toggle_window: string;
+  quick_reply: string;
+}
+
+// Inclusive "YYYY-MM-DD" dates (UTC); either end may be left open.
+export interface DateRange {
+  from?: string;
+  to?: string;
+}
+
+export type DigestPeriod = "day" | "week" | "month";
+
+// A room's activity over a period (UTC timestamps; `to` is exclusive). A
+// "conversation" is a run of messages with no half-hour pause.
+export interface RoomDigest {
+  room_id: number;
+  room: string;
+  period: DigestPeriod;
+  from: string;
+  to: string;
+  message_count: number;
+  active_members: number;
+  top_posters: { name: string; count: number }[];
+  daily_counts: { day: string; count: number }[];
+  top_conversations: {
+    started_at: string;
+    ended_at: string;
+    message_count: number;
+    participants: string[];
+    opening: string;
+    message_id: string | null;
+  }[];
+  most_reacted: {
+    message_id: string;
+    author: string;
+    created_at: string;
+    snippet: string;
+    reactions: number;
+    emoji: string[];
+  }[];
+  files: {
+    id: number;
+    filename: string;
+    size: number;
+    uploader: string;
+    created_at: string;
+  }[];
+}
+
+// Engagement over a period, for the admin dashboard. Hours and days are UTC; a
+// rank of 1 is the busiest (ties share one), and peak_hour is null when idle.
+export interface ActivityReport {
+  period: DigestPeriod;
+  from: string;
+  to: string;
+  messages: number;
+  active_users: number;
+  departments: {
+    department_id: number;
+    department: string;
+    members: number;
+    active_users: number;
+    messages: number;
+    rank: number;
+    peak_hour: number | null;
+  }[];
+  rooms: {
+    room_id: number;
+    room: string;
+    active_users: number;
+    messages: number;
+    rank: number;
+    peak_hour: number | null;
+  }[];
+  // Messages per hour of the day, 0–23.
+  hours: number[];
+  days: { day: string; messages: number; active_users: number }[];
+}
+
+// Who has and hasn't read each announcement in a compliance room (newest first).
+// A member has read one once they've read the room at or past it.
+export interface MemberRead {
+  user_id: number;
+  name: string;
+  last_read_at: string | null;
+  delivered_at: string | null;
+}
+
+export interface ReadReport {
+  room_id: number;
+  room: string;
+  announcements: {
+    message_id: string;
+    author: string;
+    created_at: string;
+    snippet: string;
+    read: MemberRead[];
+    unread: MemberRead[];
+  }[];
+}
+
+// Weekly digests the host posts every Monday from `hour_utc`.
+export interface DigestSchedule {
+  enabled: boolean;
+  rooms: number[];
+  hour_utc: number;
+}
+
+// Keep at least this much free; 0 turns the guard off.
+export interface DiskGuardConfig {
+  min_free_mb: number;
 }
 
 export type ViewState = "login" | "workspace";
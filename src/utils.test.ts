@@ -2,11 +2,15 @@ import { describe, it, expect } from "vitest";
 import {
   initials,
   avatarColor,
+  formatBytes,
   sameDay,
   shouldGroup,
   isSystem,
   mentionsUser,
+  mentionsRoom,
   parseMentions,
+  highlightCode,
+  reminderPresets,
 } from "./utils";
 import { Message } from "./types";
 
@@ -83,6 +87,12 @@ describe("isSystem", () => {
   it("treats Chat as a normal message", () => {
     expect(isSystem(msg({ message_type: "Chat" }))).toBe(false);
   });
+  it("treats stickers and code snippets as normal messages", () => {
+    expect(isSystem(msg({ message_type: "Sticker" }))).toBe(false);
+    expect(isSystem(msg({ message_type: "Code" }))).toBe(false);
+    expect(isSystem(msg({ message_type: "Contact" }))).toBe(false);
+    expect(isSystem(msg({ message_type: "Location" }))).toBe(false);
+  });
   it("treats non-Chat types as system notices", () => {
     expect(isSystem(msg({ message_type: "RoomJoin" }))).toBe(true);
   });
@@ -100,6 +110,18 @@ describe("mentionsUser", () => {
   });
 });
 
+describe("mentionsRoom", () => {
+  it("matches @room as a whole token", () => {
+    expect(mentionsRoom("@Room standup in 5")).toBe(true);
+    expect(mentionsRoom("deploy at 5, @room.")).toBe(true);
+  });
+  it("ignores emails and longer names", () => {
+    expect(mentionsRoom("ops@room.example")).toBe(false);
+    expect(mentionsRoom("@roomba")).toBe(false);
+    expect(mentionsRoom("@room.ops")).toBe(false);
+  });
+});
+
 describe("parseMentions", () => {
   it("splits text into plain and mention runs", () => {
     expect(parseMentions("hi @bob and @ada")).toEqual([
@@ -129,3 +151,45 @@ describe("error helpers", () => {
     expect(errCode("boom")).toBeUndefined();
   });
 });
+
+describe("formatBytes", () => {
+  it("picks a unit for the size", () => {
+    expect(formatBytes(812)).toBe("812 B");
+    expect(formatBytes(14 * 1024 + 100)).toBe("14 KB");
+    expect(formatBytes(3.2 * 1024 * 1024)).toBe("3.2 MB");
+    expect(formatBytes(2 * 1024 * 1024 * 1024)).toBe("2.0 GB");
+  });
+});
+
+describe("highlightCode", () => {
+  it("tags strings, comments, numbers and keywords, keeping whitespace", () => {
+    expect(highlightCode('let x = "a b";  // note\n  42', "rust")).toEqual([
+      { text: "let", kind: "keyword" },
+      { text: " x = ", kind: "plain" },
+      { text: '"a b"', kind: "string" },
+      { text: ";  ", kind: "plain" },
+      { text: "// note", kind: "comment" },
+      { text: "\n  ", kind: "plain" },
+      { text: "42", kind: "number" },
+    ]);
+  });
+  it("leaves # alone in C-family languages", () => {
+    expect(highlightCode("#include", "c")).toEqual([
+      { text: "#include", kind: "plain" },
+    ]);
+    expect(highlightCode("# comment", "yaml")).toEqual([
+      { text: "# comment", kind: "comment" },
+    ]);
+  });
+});
+
+describe("reminderPresets", () => {
+  it("offers relative times, tomorrow morning and next Monday", () => {
+    // Saturday 31 Oct 2026, 22:30 local time.
+    const now = new Date(2026, 9, 31, 22, 30);
+    const at = reminderPresets(now).map((p) => p.at);
+    expect(at[0].getTime() - now.getTime()).toBe(20 * 60 * 1000);
+    expect(at[3]).toEqual(new Date(2026, 10, 1, 9, 0));
+    expect(at[4]).toEqual(new Date(2026, 10, 2, 9, 0));
+  });
+});
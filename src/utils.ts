@@ -62,6 +62,34 @@ export function formatSearchTime(s: string): string {
   });
 }
 
+// A file size for display: "812 B", "14 KB", "3.2 MB".
+export function formatBytes(bytes: number): string {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${Math.round(bytes / 1024)} KB`;
+  if (bytes < 1024 * 1024 * 1024)
+    return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+  return `${(bytes / (1024 * 1024 * 1024)).toFixed(1)} GB`;
+}
+
+// The "Remind me" menu's choices, relative to `now` (local time).
+export function reminderPresets(now: Date): { label: string; at: Date }[] {
+  const inMinutes = (m: number) => new Date(now.getTime() + m * 60 * 1000);
+  const tomorrow = new Date(now);
+  tomorrow.setDate(tomorrow.getDate() + 1);
+  tomorrow.setHours(9, 0, 0, 0);
+  // Next Monday 9:00 (a week from today if today is Monday).
+  const nextWeek = new Date(now);
+  nextWeek.setDate(nextWeek.getDate() + ((8 - now.getDay()) % 7 || 7));
+  nextWeek.setHours(9, 0, 0, 0);
+  return [
+    { label: "In 20 minutes", at: inMinutes(20) },
+    { label: "In 1 hour", at: inMinutes(60) },
+    { label: "In 3 hours", at: inMinutes(180) },
+    { label: "Tomorrow at 9:00", at: tomorrow },
+    { label: "Next Monday at 9:00", at: nextWeek },
+  ];
+}
+
 export function sameDay(a: string, b: string): boolean {
   const da = new Date(a);
   const db = new Date(b);
@@ -84,10 +112,13 @@ export function shouldGroup(prev: Message | undefined, msg: Message): boolean {
   return dt >= 0 && dt < 5 * 60 * 1000;
 }
 
+// Kinds a user posts (text, stickers, code snippets, contact cards, locations); everything else
+// is a system event.
+const POSTED_TYPES = new Set(["Chat", "Sticker", "Code", "Contact", "Location"]);
+
 // System (non-chat) events render as centered notices rather than bubbles.
 export function isSystem(msg: Message): boolean {
-  const t = msg.message_type || "Chat";
-  return t !== "Chat";
+  return !POSTED_TYPES.has(msg.message_type || "Chat");
 }
 
 // Whether `text` @-mentions the given user (case-insensitive).
@@ -96,6 +127,12 @@ export function mentionsUser(text: string, name: string): boolean {
   return text.toLowerCase().includes("@" + name.toLowerCase());
 }
 
+// Whether text is an announcement to the whole room: "@room" as a token of its own (the same
+// rule as the host's, which tracks who each one reached).
+export function mentionsRoom(text: string): boolean {
+  return /(^|[^\w.-])@room(?![\w]|[.-]\w)/i.test(text);
+}
+
 // Split text into runs, flagging @mention tokens for styled rendering.
 export function parseMentions(
   text: string,
@@ -115,6 +152,68 @@ export function parseMentions(
   return parts;
 }
 
+export type CodeTokenKind = "plain" | "string" | "comment" | "number" | "keyword";
+
+const CODE_KEYWORDS = new Set(
+  (
+    "as async await break case catch class const continue def default do elif else enum " +
+    "export extends false fn for from func function if impl import in interface let loop " +
+    "match mod mut new nil none null pub return select self static struct switch then this " +
+    "throw true try type use var where while with yield"
+  ).split(" "),
+);
+
+// A deliberately small, language-agnostic highlighter for code snippets: strings, comments
+// (//, #, /* */, "-- "), numbers and common keywords. `language` only switches `#` comments off
+// for C-family languages, where `#` starts a preprocessor line.
+export function highlightCode(
+  code: string,
+  language?: string | null,
+): { text: string; kind: CodeTokenKind }[] {
+  const hashComments = !/^(c|c\+\+|cpp|c#|csharp|objective-c)$/.test(
+    language ?? "",
+  );
+  const re = new RegExp(
+    [
+      /(\/\*[\s\S]*?\*\/|\/\/[^\n]*|--(?=[ \t])[^\n]*)/.source,
+      hashComments ? /(#[^\n]*)/.source : /($^)/.source,
+      /("(?:\\.|[^"\\\n])*"|'(?:\\.|[^'\\\n])*'|`(?:\\.|[^`\\])*`)/.source,
+      /(\b\d[\d_]*(?:\.\d+)?\b)/.source,
+      /([A-Za-z_]\w*)/.source,
+    ].join("|"),
+    "g",
+  );
+  const tokens: { text: string; kind: CodeTokenKind }[] = [];
+  const push = (text: string, kind: CodeTokenKind) => {
+    const prev = tokens[tokens.length - 1];
+    if (prev && prev.kind === kind) prev.text += text;
+    else tokens.push({ text, kind });
+  };
+  let last = 0;
+  let m: RegExpExecArray | null;
+  while ((m = re.exec(code)) !== null) {
+    if (m[0] === "") {
+      re.lastIndex++;
+      continue;
+    }
+    if (m.index > last) push(code.slice(last, m.index), "plain");
+    const kind: CodeTokenKind =
+      m[1] || m[2]
+        ? "comment"
+        : m[3]
+          ? "string"
+          : m[4]
+            ? "number"
+            : CODE_KEYWORDS.has(m[5])
+              ? "keyword"
+              : "plain";
+    push(m[0], kind);
+    last = m.index + m[0].length;
+  }
+  if (last < code.length) push(code.slice(last), "plain");
+  return tokens;
+}
+
 // Backend command errors: our typed AppError serializes to { code, message } (see
 // src-tauri/src/error.rs); older commands still return a plain string. These normalize both.
 export function errText(e: unknown): string {